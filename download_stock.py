@@ -1,15 +1,273 @@
-import sys
-import yfinance as yf
+import argparse
+import hashlib
+import io
+import json
 import os
+import sys
+import time
+import urllib.error
+import urllib.parse
+import urllib.request
+
+import pandas as pd
+
+INTERVALS = ["1m", "5m", "15m", "1h", "1d"]
+# Exit status when every source failed, so the TUI can tell "the data
+# provider is down or changed" apart from a bad ticker or a crash.
+EXIT_SOURCE_UNAVAILABLE = 3
+# The TUI passes its [net] settings so both sides behave the same.
+USER_AGENT = os.environ.get("STM_USER_AGENT", "Mozilla/5.0 (compatible; stm)")
+TIMEOUT = int(os.environ.get("STM_HTTP_TIMEOUT", "20"))
+# Quote currencies of crypto pairs, as in the TUI's Ticker::crypto_pair.
+CRYPTO_QUOTES = {"USD", "USDT", "USDC", "EUR", "GBP", "JPY", "CAD", "BTC", "ETH"}
+
+parser = argparse.ArgumentParser(description="Download stock data into pre_stock/")
+parser.add_argument("ticker")
+parser.add_argument("--interval", default="1d", choices=INTERVALS, help="Bar size")
+parser.add_argument("--period", default="1y", help="Range to fetch, e.g. 5d, 1mo, 1y, max")
+parser.add_argument("--start", help="First date to fetch (YYYY-MM-DD); merges into the existing file")
+parser.add_argument("--end", help="Exclusive end date (YYYY-MM-DD), used with --start")
+parser.add_argument("--dir", default="pre_stock", help="Directory to store the CSV in (a [[data.roots]] dir)")
+args = parser.parse_args()
+
+ticker = args.ticker.upper()
+os.makedirs(args.dir, exist_ok=True)
+filename = os.path.join(args.dir, f"{ticker}.csv")
+meta_filename = os.path.join(args.dir, f"{ticker}.meta.json")
+# Raw responses kept with their ETag/Last-Modified, revalidated on the next run.
+CACHE_DIR = os.path.join(args.dir, ".http_cache")
+
+
+def crypto_pair(symbol):
+    """(base, quote) of a crypto pair like BTC-USD, or None. Share classes
+    such as BRK-B aren't pairs."""
+    base, sep, quote = symbol.partition("-")
+    if sep and len(base) >= 2 and base.isalnum() and quote in CRYPTO_QUOTES:
+        return base, quote
+    return None
+
+
+class SourceError(Exception):
+    pass
+
+
+def http_get(url):
+    """GET with a conditional-request cache: a 304 reuses the stored body
+    instead of downloading identical data again."""
+    key = hashlib.sha1(url.encode()).hexdigest()
+    body_path = os.path.join(CACHE_DIR, key)
+    validators_path = body_path + ".json"
+    headers = {"User-Agent": USER_AGENT}
+    validators = {}
+    if os.path.exists(body_path) and os.path.exists(validators_path):
+        with open(validators_path) as f:
+            validators = json.load(f)
+        if validators.get("etag"):
+            headers["If-None-Match"] = validators["etag"]
+        if validators.get("last_modified"):
+            headers["If-Modified-Since"] = validators["last_modified"]
+    req = urllib.request.Request(url, headers=headers)
+    try:
+        with urllib.request.urlopen(req, timeout=TIMEOUT) as resp:
+            body = resp.read()
+            etag, last_modified = resp.headers.get("ETag"), resp.headers.get("Last-Modified")
+    except urllib.error.HTTPError as e:
+        if e.code == 304 and validators:
+            with open(body_path, "rb") as f:
+                return f.read()
+        raise
+    if etag or last_modified:
+        os.makedirs(CACHE_DIR, exist_ok=True)
+        with open(body_path, "wb") as f:
+            f.write(body)
+        with open(validators_path, "w") as f:
+            json.dump({"etag": etag, "last_modified": last_modified}, f)
+    return body
+
+
+def time_window(period, start, end):
+    """(start, end) as UTC timestamps for sources that take explicit dates."""
+    if start:
+        end_ts = pd.Timestamp(end, tz="UTC") if end else pd.Timestamp.now(tz="UTC")
+        return pd.Timestamp(start, tz="UTC"), end_ts
+    now = pd.Timestamp.now(tz="UTC")
+    if period == "max":
+        return pd.Timestamp("1970-01-02", tz="UTC"), now
+    if period == "ytd":
+        return pd.Timestamp(year=now.year, month=1, day=1, tz="UTC"), now
+    for suffix, unit in (("mo", "months"), ("d", "days"), ("y", "years")):
+        if period.endswith(suffix) and period[: -len(suffix)].isdigit():
+            return now - pd.DateOffset(**{unit: int(period[: -len(suffix)])}), now
+    raise SourceError(f"unsupported period {period}")
+
+
+def flatten(data):
+    """yfinance writes a two-level (Price/Ticker) header; keep the Price level."""
+    if isinstance(data.columns, pd.MultiIndex):
+        data = data.copy()
+        data.columns = data.columns.get_level_values(0)
+    return data
+
+
+# ============================
+# Sources, tried in order
+# ============================
+def from_yfinance(interval, period, start, end):
+    import yfinance as yf
+
+    if start:
+        data = yf.download(ticker, start=start, end=end, interval=interval, progress=False)
+    else:
+        data = yf.download(ticker, period=period, interval=interval, progress=False)
+    return flatten(data)
+
+
+def from_chart_api(interval, period, start, end):
+    """Yahoo's JSON chart endpoint, which yfinance itself wraps."""
+    params = {"interval": "60m" if interval == "1h" else interval, "includePrePost": "false"}
+    if start:
+        lo, hi = time_window(period, start, end)
+        params.update(period1=int(lo.timestamp()), period2=int(hi.timestamp()))
+    else:
+        params["range"] = period
+    errors = []
+    for host in ("query1", "query2"):
+        url = f"https://{host}.finance.yahoo.com/v8/finance/chart/{urllib.parse.quote(ticker)}?" + urllib.parse.urlencode(params)
+        try:
+            body = json.loads(http_get(url))
+        except Exception as e:
+            errors.append(f"{host}: {e}")
+            continue
+        chart = body.get("chart") or {}
+        if chart.get("error"):
+            raise SourceError(chart["error"].get("description", "chart error"))
+        result = (chart.get("result") or [None])[0]
+        if not result or not result.get("timestamp"):
+            raise SourceError("no bars in response")
+        offset = result.get("meta", {}).get("gmtoffset", 0)
+        quote = result["indicators"]["quote"][0]
+        adj = (result["indicators"].get("adjclose") or [{}])[0].get("adjclose")
+        # Exchange-local timestamps, as yfinance writes them.
+        index = pd.to_datetime([t + offset for t in result["timestamp"]], unit="s")
+        data = pd.DataFrame(
+            {
+                "Open": quote.get("open"),
+                "High": quote.get("high"),
+                "Low": quote.get("low"),
+                "Close": quote.get("close"),
+                "Adj Close": adj if adj else quote.get("close"),
+                "Volume": quote.get("volume"),
+            },
+            index=index,
+        )
+        data.index.name = "Date" if interval == "1d" else "Datetime"
+        if interval == "1d":
+            data.index = data.index.normalize()
+        return data
+    raise SourceError("; ".join(errors))
+
+
+def from_query1_csv(interval, period, start, end):
+    """The older CSV download endpoint. Daily bars only."""
+    if interval != "1d":
+        raise SourceError("daily bars only")
+    lo, hi = time_window(period, start, end)
+    params = {"period1": int(lo.timestamp()), "period2": int(hi.timestamp()), "interval": "1d", "events": "history"}
+    url = f"https://query1.finance.yahoo.com/v7/finance/download/{urllib.parse.quote(ticker)}?" + urllib.parse.urlencode(params)
+    data = pd.read_csv(io.BytesIO(http_get(url)), index_col="Date", parse_dates=True)
+    return data
+
+
+def from_stooq(interval, period, start, end):
+    """Stooq's daily CSV export, a non-Yahoo fallback for US listings and
+    crypto pairs (BTC-USD is btcusd there)."""
+    if interval != "1d":
+        raise SourceError("daily bars only")
+    if not ticker.replace("-", "").replace(".", "").isalnum():
+        raise SourceError(f"no Stooq symbol for {ticker}")
+    pair = crypto_pair(ticker)
+    symbol = "".join(pair).lower() if pair else ticker.lower().replace("-", ".") + ".us"
+    raw = http_get(f"https://stooq.com/q/d/l/?s={urllib.parse.quote(symbol)}&i=d")
+    if not raw.strip() or raw.startswith(b"No data"):
+        raise SourceError(f"no data for {symbol}")
+    data = pd.read_csv(io.BytesIO(raw), index_col="Date", parse_dates=True)
+    lo, hi = time_window(period, start, end)
+    lo, hi = lo.tz_localize(None), hi.tz_localize(None)
+    return data[(data.index >= lo) & (data.index < hi)]
+
+
+SOURCES = [
+    ("yfinance", from_yfinance),
+    ("chart-api", from_chart_api),
+    ("query1-csv", from_query1_csv),
+    ("stooq", from_stooq),
+]
+
+
+def fetch(interval, period=None, start=None, end=None):
+    """First source that returns rows wins; if none do, exits with
+    EXIT_SOURCE_UNAVAILABLE and what each one said."""
+    failures = []
+    for name, source in SOURCES:
+        try:
+            data = source(interval, period, start, end)
+        except Exception as e:
+            failures.append(f"{name}: {e}")
+            continue
+        if data is None or data.empty:
+            failures.append(f"{name}: no rows")
+            continue
+        return name, data
+    print("Data source unavailable: " + "; ".join(failures), file=sys.stderr)
+    sys.exit(EXIT_SOURCE_UNAVAILABLE)
+
+
+def read_existing(path):
+    with open(path) as f:
+        f.readline()
+        second = f.readline()
+    # Older files have yfinance's two-level (Price/Ticker) header.
+    header = [0, 1] if second.startswith("Ticker") else 0
+    return flatten(pd.read_csv(path, header=header, index_col=0, parse_dates=True))
+
+
+def last_bar(data):
+    return data.index[-1].strftime("%Y-%m-%d %H:%M:%S") if len(data) else None
+
+
+def write_meta(interval, period, source, data):
+    # Sidecar read by the TUI to label the chart axis for this bar size.
+    # Crypto trades around the clock, so its bars have no session gaps.
+    # The TUI updates from last_bar on instead of downloading it all again.
+    meta = {"interval": interval, "range": period, "source": source, "fetched_at": int(time.time())}
+    meta["sessions"] = "24/7" if crypto_pair(ticker) else "exchange"
+    meta["last_bar"] = last_bar(data)
+    with open(meta_filename, "w") as f:
+        json.dump(meta, f)
+
 
-if len(sys.argv) < 2:
-    print("Usage: python download_stock.py <TICKER>")
-    sys.exit(1)
+def update_last_bar(data):
+    if not os.path.exists(meta_filename):
+        return
+    with open(meta_filename) as f:
+        meta = json.load(f)
+    meta["last_bar"] = last_bar(data)
+    with open(meta_filename, "w") as f:
+        json.dump(meta, f)
 
-ticker = sys.argv[1].upper()
-data = yf.download(ticker, period="1y")  # Download 1 year of data
-os.makedirs("pre_stock", exist_ok=True)
-filename = os.path.join("pre_stock", f"{ticker}.csv")
-data.to_csv(filename)
-print(f"Downloaded data for {ticker} to {filename}")
 
+if args.start:
+    # Targeted re-download of a missing range, merged into the stored history.
+    source, data = fetch(args.interval, start=args.start, end=args.end)
+    if os.path.exists(filename) and os.path.getsize(filename) > 0:
+        existing = read_existing(filename)
+        data = pd.concat([existing, data])
+        data = data[~data.index.duplicated(keep="last")].sort_index()
+    data.to_csv(filename)
+    update_last_bar(data)
+    print(f"Downloaded {args.start}..{args.end} for {ticker} into {filename} via {source}")
+else:
+    source, data = fetch(args.interval, period=args.period)
+    data.to_csv(filename)
+    write_meta(args.interval, args.period, source, data)
+    print(f"Downloaded {args.period} of {args.interval} data for {ticker} to {filename} via {source}")
@@ -1,3 +1,6 @@
+import argparse
+import csv
+import json
 import os
 import torch
 import torch.nn as nn
@@ -43,13 +46,17 @@ def create_synthetic_data(seq_length, num_samples):
     y = y.reshape(-1, 1)
     return torch.tensor(X, dtype=torch.float32), torch.tensor(y, dtype=torch.float32)
 
-def train_model():
+HIDDEN_SIZE = 50
+NUM_LAYERS = 2
+SEQ_LENGTH = 10
+
+def train_model(model_dir):
     # Hyperparameters
     input_size = 1
-    hidden_size = 50
-    num_layers = 2
+    hidden_size = HIDDEN_SIZE
+    num_layers = NUM_LAYERS
     output_size = 1
-    seq_length = 10
+    seq_length = SEQ_LENGTH
     num_samples = 1000
     num_epochs = 100
     learning_rate = 0.01
@@ -74,12 +81,71 @@ def train_model():
         if (epoch + 1) % 10 == 0:
             print(f"Epoch [{epoch+1}/{num_epochs}], Loss: {loss.item():.4f}")
 
-    # Save the model to the "model/" folder in the project root.
-    os.makedirs("../model", exist_ok=True)
-    model_path = "../model/lstm_model.pth"
+    # Held-out samples the model didn't train on.
+    model.eval()
+    X_val, y_val = create_synthetic_data(seq_length, 200)
+    with torch.no_grad():
+        val_loss = criterion(model(X_val), y_val).item()
+    print(f"Validation loss: {val_loss:.4f}")
+
+    # Save the weights, the ONNX export and the metrics to model_dir (a
+    # version directory when run by stm's pipeline).
+    os.makedirs(model_dir, exist_ok=True)
+    model_path = os.path.join(model_dir, "lstm_model.pth")
     torch.save(model.state_dict(), model_path)
     print(f"Model saved to {model_path}")
+    with open(os.path.join(model_dir, "metrics.json"), "w") as f:
+        json.dump({"train_loss": loss.item(), "val_loss": val_loss}, f)
 
-if __name__ == "__main__":
-    train_model()
+    # Also export to ONNX for the TUI's in-process inference (built with
+    # --features native-ml). Training still succeeds if the export can't run.
+    onnx_path = os.path.join(model_dir, "lstm_model.onnx")
+    try:
+        torch.onnx.export(model, X[:1], onnx_path, input_names=["input"], output_names=["output"])
+        print(f"ONNX model exported to {onnx_path}")
+    except Exception as e:
+        print(f"ONNX export skipped: {e}")
+    return model
+
+def load_model(model_path):
+    model = LSTMModel(1, HIDDEN_SIZE, NUM_LAYERS, 1)
+    model.load_state_dict(torch.load(model_path))
+    model.eval()
+    print(f"Model loaded from {model_path}")
+    return model
 
+def read_closes(csv_path):
+    """Closes of a price CSV, flat or with yfinance's extra header rows."""
+    with open(csv_path, newline="") as f:
+        rows = list(csv.reader(f))
+    column = rows[0].index("Close")
+    closes = []
+    for row in rows[1:]:
+        try:
+            closes.append(float(row[column]))
+        except (IndexError, ValueError):
+            continue
+    return closes
+
+def predict_next(model, closes):
+    """The close after the last SEQ_LENGTH, scaled into the -1..1 range of
+    the training data and back, as the TUI's native inference does."""
+    window = closes[-SEQ_LENGTH:]
+    mid = (max(window) + min(window)) / 2
+    half_span = max((max(window) - min(window)) / 2, 1e-12)
+    x = torch.tensor([[(c - mid) / half_span] for c in window], dtype=torch.float32).unsqueeze(0)
+    with torch.no_grad():
+        return mid + model(x).item() * half_span
+
+if __name__ == "__main__":
+    parser = argparse.ArgumentParser()
+    parser.add_argument("--model-dir", default="../model", help="Trained into if it has no lstm_model.pth yet, else loaded from")
+    parser.add_argument("--csv", help="Price CSV whose next close to predict")
+    args = parser.parse_args()
+    weights = os.path.join(args.model_dir, "lstm_model.pth")
+    model = load_model(weights) if os.path.exists(weights) else train_model(args.model_dir)
+    if args.csv:
+        closes = read_closes(args.csv)
+        if len(closes) < SEQ_LENGTH:
+            raise SystemExit(f"Need at least {SEQ_LENGTH} closes in {args.csv}, have {len(closes)}")
+        print(f"Predicted next close: {predict_next(model, closes):.4f}")
@@ -0,0 +1,253 @@
+//! Account summaries (`account_summary.csv`) and the trade math that moves
+//! their balances as trades are recorded; `ledger` folds the same balances
+//! from the trade history when it's read.
+
+use std::error::Error;
+
+use chrono::NaiveDateTime;
+use csv::{ReaderBuilder, WriterBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::ids::AccountId;
+use crate::trades::TradeRecord;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountSummary {
+    pub name: AccountId,
+    pub initial_amount: f64,
+    pub current_amount: f64,
+    pub change: f64,
+    pub percentage_change: f64,
+    /// ISO 4217 code the balances are held in.
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    /// Closed accounts stay in the file (and in the trade history) but are
+    /// hidden from the summary table unless archived accounts are shown.
+    /// Older files without this column read as open.
+    #[serde(default)]
+    pub archived: bool,
+    /// Balance the account opened with, before anything in the trade
+    /// history; the other balance columns are folded from it by `ledger`.
+    /// Older files leave it empty until then.
+    #[serde(default)]
+    pub opening_amount: Option<f64>,
+}
+
+impl AccountSummary {
+    /// A new open account holding `balance` in `currency`.
+    pub fn new(name: AccountId, balance: f64, currency: &str) -> Self {
+        Self {
+            name,
+            initial_amount: balance,
+            current_amount: balance,
+            change: 0.0,
+            percentage_change: 0.0,
+            currency: currency.to_string(),
+            archived: false,
+            opening_amount: Some(balance),
+        }
+    }
+}
+
+fn default_currency() -> String {
+    "USD".to_string()
+}
+
+pub fn read_accounts_from_csv(path: &str) -> Result<Vec<AccountSummary>, Box<dyn Error>> {
+    let mut rdr = ReaderBuilder::new().from_path(path)?;
+    let mut records = Vec::new();
+    for result in rdr.deserialize() {
+        let rec: AccountSummary = result?;
+        records.push(rec);
+    }
+    Ok(records)
+}
+
+pub fn write_accounts_to_csv(path: &str, accounts: &[AccountSummary]) -> Result<(), Box<dyn Error>> {
+    let mut wtr = WriterBuilder::new().from_path(path)?;
+    for account in accounts {
+        wtr.serialize(account)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Applies a gain (positive `amount`) or loss to the account named `name`:
+/// the current balance moves by `amount` and the change columns follow.
+/// Returns the trade to add to the history, stamped `at`. Names match
+/// case-insensitively; closed accounts are refused.
+pub fn process_trade(
+    accounts: &mut [AccountSummary],
+    name: &AccountId,
+    amount: f64,
+    at: NaiveDateTime,
+) -> Result<TradeRecord, String> {
+    let account = accounts
+        .iter_mut()
+        .find(|a| a.name.as_str().to_lowercase() == name.as_str().to_lowercase())
+        .ok_or_else(|| format!("No account named {}", name))?;
+    if account.archived {
+        return Err(format!("{} is closed", account.name));
+    }
+    account.current_amount += amount;
+    account.change = account.current_amount - account.initial_amount;
+    account.percentage_change =
+        if account.initial_amount != 0.0 { account.change / account.initial_amount * 100.0 } else { 0.0 };
+    Ok(TradeRecord { timestamp: Some(at), ..TradeRecord::new(account.name.clone(), amount, account.current_amount) })
+}
+
+/// Moves `amount` from the account named `from` to the one named `to`.
+/// Both starting balances move with the current ones, so the transfer
+/// isn't counted as either account's gain or loss. Returns the two legs,
+/// outgoing first, for `trades::append_transfer`.
+pub fn process_transfer(
+    accounts: &mut [AccountSummary],
+    from: &AccountId,
+    to: &AccountId,
+    amount: f64,
+    at: NaiveDateTime,
+) -> Result<[TradeRecord; 2], String> {
+    if amount <= 0.0 {
+        return Err("Amount must be positive".to_string());
+    }
+    let find = |accounts: &[AccountSummary], name: &AccountId| {
+        let i = accounts
+            .iter()
+            .position(|a| a.name.as_str().to_lowercase() == name.as_str().to_lowercase())
+            .ok_or_else(|| format!("No account named {}", name))?;
+        if accounts[i].archived {
+            return Err(format!("{} is closed", accounts[i].name));
+        }
+        Ok(i)
+    };
+    let (source, target) = (find(accounts, from)?, find(accounts, to)?);
+    if source == target {
+        return Err("Can't transfer to the same account".to_string());
+    }
+    if accounts[source].currency.trim().to_uppercase() != accounts[target].currency.trim().to_uppercase() {
+        return Err(format!("{} and {} hold different currencies", accounts[source].name, accounts[target].name));
+    }
+    Ok([(source, -amount), (target, amount)].map(|(i, amount)| {
+        let account = &mut accounts[i];
+        account.initial_amount += amount;
+        account.current_amount += amount;
+        account.percentage_change =
+            if account.initial_amount != 0.0 { account.change / account.initial_amount * 100.0 } else { 0.0 };
+        TradeRecord { timestamp: Some(at), ..TradeRecord::new(account.name.clone(), amount, account.current_amount) }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn account(name: &str, initial: f64) -> AccountSummary {
+        AccountSummary::new(AccountId::parse(name).unwrap(), initial, "USD")
+    }
+
+    fn at() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 6, 3).unwrap().and_hms_opt(9, 30, 0).unwrap()
+    }
+
+    fn id(name: &str) -> AccountId {
+        AccountId::parse(name).unwrap()
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("stm-{}-{}", std::process::id(), name)).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn process_trade_moves_balance_and_change() {
+        let mut accounts = vec![account("Alice", 10.0), account("Bob", 20.0)];
+        let trade = process_trade(&mut accounts, &id("Alice"), 5.0, at()).unwrap();
+        assert_eq!(accounts[0].current_amount, 15.0);
+        assert_eq!(accounts[0].change, 5.0);
+        assert_eq!(accounts[0].percentage_change, 50.0);
+        assert_eq!(trade.name, id("Alice"));
+        assert_eq!(trade.transaction, 5.0);
+        assert_eq!(trade.new_balance, 15.0);
+        assert_eq!(trade.timestamp, Some(at()));
+        assert_eq!(accounts[1], account("Bob", 20.0));
+    }
+
+    #[test]
+    fn process_trade_accumulates_gains_and_losses() {
+        let mut accounts = vec![account("Alice", 10.0), account("Bob", 20.0)];
+        process_trade(&mut accounts, &id("Alice"), 5.0, at()).unwrap();
+        process_trade(&mut accounts, &id("Bob"), -3.0, at()).unwrap();
+        let last = process_trade(&mut accounts, &id("Alice"), 2.0, at()).unwrap();
+        assert_eq!(last.new_balance, 17.0);
+        assert_eq!(accounts[0].percentage_change, 70.0);
+        assert_eq!(accounts[1].current_amount, 17.0);
+        assert_eq!(accounts[1].change, -3.0);
+        assert_eq!(accounts[1].percentage_change, -15.0);
+    }
+
+    #[test]
+    fn process_trade_matches_names_case_insensitively() {
+        let mut accounts = vec![account("Alice", 10.0)];
+        let trade = process_trade(&mut accounts, &id("alice"), 1.0, at()).unwrap();
+        assert_eq!(trade.name, id("Alice"));
+    }
+
+    #[test]
+    fn process_trade_refuses_unknown_and_closed_accounts() {
+        let mut accounts = vec![account("Alice", 10.0)];
+        assert_eq!(process_trade(&mut accounts, &id("Carol"), 1.0, at()), Err("No account named Carol".to_string()));
+        accounts[0].archived = true;
+        assert_eq!(process_trade(&mut accounts, &id("Alice"), 1.0, at()), Err("Alice is closed".to_string()));
+        assert_eq!(accounts[0].current_amount, 10.0);
+    }
+
+    #[test]
+    fn process_transfer_moves_cash_without_changing_gains() {
+        let mut accounts = vec![account("Alice", 10.0), account("Bob", 20.0)];
+        process_trade(&mut accounts, &id("Alice"), 5.0, at()).unwrap();
+        let [out, into] = process_transfer(&mut accounts, &id("alice"), &id("Bob"), 5.0, at()).unwrap();
+        assert_eq!((out.name, out.transaction, out.new_balance), (id("Alice"), -5.0, 10.0));
+        assert_eq!((into.name, into.transaction, into.new_balance), (id("Bob"), 5.0, 25.0));
+        assert_eq!((accounts[0].initial_amount, accounts[0].change), (5.0, 5.0));
+        assert_eq!(accounts[0].percentage_change, 100.0);
+        assert_eq!((accounts[1].initial_amount, accounts[1].change), (25.0, 0.0));
+        assert_eq!(
+            process_transfer(&mut accounts, &id("Bob"), &id("bob"), 1.0, at()),
+            Err("Can't transfer to the same account".to_string())
+        );
+    }
+
+    #[test]
+    fn process_trade_on_empty_starting_balance_has_no_percentage() {
+        let mut accounts = vec![account("Alice", 0.0)];
+        process_trade(&mut accounts, &id("Alice"), 5.0, at()).unwrap();
+        assert_eq!(accounts[0].percentage_change, 0.0);
+    }
+
+    #[test]
+    fn accounts_round_trip_through_csv() {
+        let path = temp_path("accounts.csv");
+        let mut closed = account("Bob", 20.0);
+        closed.archived = true;
+        closed.currency = "EUR".to_string();
+        let accounts = vec![account("Alice", 10.0), closed];
+        write_accounts_to_csv(&path, &accounts).unwrap();
+        assert_eq!(read_accounts_from_csv(&path).unwrap(), accounts);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn older_account_files_read_with_defaults() {
+        let path = temp_path("old-accounts.csv");
+        fs::write(&path, "name,initial_amount,current_amount,change,percentage_change\nAlice,10.0,17.0,7.0,70.0\n").unwrap();
+        let accounts = read_accounts_from_csv(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].currency, "USD");
+        assert!(!accounts[0].archived);
+        assert_eq!(accounts[0].current_amount, 17.0);
+    }
+}
@@ -0,0 +1,252 @@
+//! Price alert rules and their historical backtest.
+//!
+//! A rule is `METRIC OP VALUE`, e.g. `close > 150`, `change% <= -3` or
+//! `volume >= 5m`. Metrics are `close`, `change%` (percent move from the
+//! previous bar) and `volume`; operators are `>`, `>=`, `<` and `<=`.
+//!
+//! Rules fire when the condition becomes true, not on every bar it stays
+//! true, so a backtest lists the bars a live alert would have notified on.
+
+use std::fmt;
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::data::{Bar, Interval, PriceSeries};
+use crate::date_range::DateRange;
+use crate::ids::Ticker;
+use crate::number_input::NumberInput;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    Close,
+    PctChange,
+    Volume,
+}
+
+impl Metric {
+    fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "close" | "price" => Some(Metric::Close),
+            "change%" | "pct" | "%" => Some(Metric::PctChange),
+            "volume" | "vol" => Some(Metric::Volume),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Close => "close",
+            Metric::PctChange => "change%",
+            Metric::Volume => "volume",
+        }
+    }
+
+    /// Rules on prices and volumes can't go below zero; percent moves can.
+    fn threshold_input(self) -> NumberInput {
+        let input = NumberInput::new("Threshold");
+        match self {
+            Metric::Close | Metric::Volume => input.min(0.0),
+            Metric::PctChange => input,
+        }
+    }
+
+    /// The metric on `bars[i]`, if the bar has it.
+    fn value(self, bars: &[Bar], i: usize) -> Option<f64> {
+        match self {
+            Metric::Close => Some(bars[i].close),
+            Metric::PctChange => {
+                let prev = bars.get(i.checked_sub(1)?)?.close;
+                (prev != 0.0).then(|| (bars[i].close - prev) / prev * 100.0)
+            }
+            Metric::Volume => bars[i].volume,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Above,
+    AtLeast,
+    Below,
+    AtMost,
+}
+
+impl Op {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            ">" => Some(Op::Above),
+            ">=" => Some(Op::AtLeast),
+            "<" => Some(Op::Below),
+            "<=" => Some(Op::AtMost),
+            _ => None,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Op::Above => ">",
+            Op::AtLeast => ">=",
+            Op::Below => "<",
+            Op::AtMost => "<=",
+        }
+    }
+
+    fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Op::Above => value > threshold,
+            Op::AtLeast => value >= threshold,
+            Op::Below => value < threshold,
+            Op::AtMost => value <= threshold,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertRule {
+    pub metric: Metric,
+    pub op: Op,
+    pub threshold: f64,
+}
+
+impl AlertRule {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = input.split_whitespace().collect();
+        let [metric, op, value] = parts[..] else {
+            return Err("Alert rule is METRIC OP VALUE, e.g. 'close > 150' or 'change% <= -3'".to_string());
+        };
+        let metric = Metric::parse(metric).ok_or_else(|| format!("Unknown metric {} (close/change%/volume)", metric))?;
+        let op = Op::parse(op).ok_or_else(|| format!("Unknown operator {} (> >= < <=)", op))?;
+        let threshold = metric.threshold_input().parse(value)?;
+        Ok(Self { metric, op, threshold })
+    }
+
+    /// Bars on which the alert would have fired.
+    pub fn backtest(&self, bars: &[Bar]) -> Vec<Trigger> {
+        let mut triggers = Vec::new();
+        // A condition already true on the first bar counts as firing there.
+        let mut was_true = false;
+        for i in 0..bars.len() {
+            let Some(value) = self.metric.value(bars, i) else {
+                continue;
+            };
+            let now_true = self.op.holds(value, self.threshold);
+            if now_true && !was_true {
+                triggers.push(Trigger { index: i, at: bars[i].at, value, close: bars[i].close });
+            }
+            was_true = now_true;
+        }
+        triggers
+    }
+}
+
+impl fmt::Display for AlertRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.metric.name(), self.op.symbol(), self.threshold)
+    }
+}
+
+/// One bar an alert fired on.
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    /// Index into the backtested bars.
+    pub index: usize,
+    pub at: NaiveDateTime,
+    /// The metric's value on that bar.
+    pub value: f64,
+    pub close: f64,
+}
+
+/// Full-screen preview of a rule backtested on one ticker's history.
+pub struct AlertPreview {
+    pub ticker: Ticker,
+    pub rule: AlertRule,
+    pub interval: Interval,
+    // The whole history; the backtest only covers `range` of it.
+    history: Vec<Bar>,
+    pub range: DateRange,
+    pub bars: Vec<Bar>,
+    pub triggers: Vec<Trigger>,
+    // First trigger shown in the list.
+    pub scroll: usize,
+}
+
+impl AlertPreview {
+    pub fn new(ticker: Ticker, rule: AlertRule, series: PriceSeries) -> Self {
+        let mut preview = Self {
+            ticker,
+            rule,
+            interval: series.interval,
+            history: series.bars,
+            range: DateRange::default(),
+            bars: Vec::new(),
+            triggers: Vec::new(),
+            scroll: 0,
+        };
+        preview.set_range(DateRange::default());
+        preview
+    }
+
+    /// Date of the latest bar, which date presets count back from.
+    pub fn latest(&self) -> Option<NaiveDate> {
+        self.history.last().map(|b| b.at.date())
+    }
+
+    /// Re-runs the backtest on the part of the history inside `range`.
+    pub fn set_range(&mut self, range: DateRange) {
+        self.range = range;
+        self.bars = range.slice(&self.history, |b| b.at).to_vec();
+        self.triggers = self.rule.backtest(&self.bars);
+        self.scroll = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bars(closes: &[f64]) -> Vec<Bar> {
+        let day = chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, &close)| Bar { at: day + chrono::Duration::days(i as i64), close, volume: Some(close * 1e6) })
+            .collect()
+    }
+
+    fn fired(rule: &str, bars: &[Bar]) -> Vec<usize> {
+        AlertRule::parse(rule).unwrap().backtest(bars).iter().map(|t| t.index).collect()
+    }
+
+    #[test]
+    fn parse_reads_metrics_operators_and_thresholds() {
+        let rule = AlertRule::parse("Price >= 1.5k").unwrap();
+        assert_eq!(rule, AlertRule { metric: Metric::Close, op: Op::AtLeast, threshold: 1_500.0 });
+        assert_eq!(rule.to_string(), "close >= 1500");
+        assert_eq!(AlertRule::parse("change% <= -3").unwrap().to_string(), "change% <= -3");
+        assert_eq!(AlertRule::parse("vol > 5m").unwrap().threshold, 5e6);
+        assert!(AlertRule::parse("close > ").unwrap_err().starts_with("Alert rule is METRIC OP VALUE"));
+        assert!(AlertRule::parse("open > 1").unwrap_err().starts_with("Unknown metric open"));
+        assert!(AlertRule::parse("close = 1").unwrap_err().starts_with("Unknown operator ="));
+        // Only percent moves go negative.
+        assert!(AlertRule::parse("close < -1").is_err());
+    }
+
+    #[test]
+    fn backtest_fires_when_the_condition_turns_true() {
+        let bars = bars(&[12.0, 11.0, 10.0, 9.0, 10.0, 11.0, 12.0, 9.0]);
+        // True from the first bar: fires there, then re-arms only once the
+        // condition has gone false.
+        assert_eq!(fired("close > 10", &bars), [0, 5]);
+        assert_eq!(fired("close >= 10", &bars), [0, 4]);
+        assert_eq!(fired("close < 10", &bars), [3, 7]);
+        assert_eq!(fired("close <= 10", &bars), [2, 7]);
+        assert_eq!(fired("volume >= 12m", &bars), [0, 6]);
+
+        // The first bar has no move to measure; 12 -> 9 is -25%.
+        let drops = AlertRule::parse("change% <= -9").unwrap().backtest(&bars);
+        assert_eq!(drops.iter().map(|t| t.index).collect::<Vec<_>>(), [2, 7]);
+        assert_eq!((drops[1].value, drops[1].close), (-25.0, 9.0));
+        assert_eq!(fired("change% > -100", &bars), [1]);
+        assert!(fired("close > 100", &bars).is_empty());
+    }
+}
@@ -0,0 +1,157 @@
+//! Trading performance from the trade history, for the analytics view.
+//!
+//! Every recorded gain or loss counts as one closed trade; transfers
+//! between accounts are left out, and dividends count toward the months'
+//! P&L and returns but aren't trades. Expectancy is the average result per
+//! trade. A month's return is its P&L over the total account value before
+//! its first trade, as the equity curve has it, and a year's the same over
+//! the year. Undated trades count toward the totals but toward no month.
+//! Results are net of the fees broker fills paid; the gross P&L adds them
+//! back.
+
+use std::collections::BTreeMap;
+
+use chrono::Datelike;
+
+use crate::accounts::AccountSummary;
+use crate::trades::{self, TradeRecord};
+
+/// Results within a month or a year.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Period {
+    pub trades: usize,
+    pub pnl: f64,
+    /// Total account value before the period's first trade.
+    pub start: f64,
+}
+
+impl Period {
+    /// P&L in percent of the value it started from; `None` from nothing.
+    pub fn return_pct(&self) -> Option<f64> {
+        (self.start > 0.0).then(|| self.pnl / self.start * 100.0)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Analytics {
+    pub trades: usize,
+    pub wins: usize,
+    pub losses: usize,
+    pub gross_win: f64,
+    /// Positive, the sum of the losses' sizes.
+    pub gross_loss: f64,
+    pub dividends: f64,
+    /// Commissions paid, positive.
+    pub fees: f64,
+    /// By `(year, month)`.
+    pub months: BTreeMap<(i32, u32), Period>,
+    pub years: BTreeMap<i32, Period>,
+}
+
+impl Analytics {
+    /// `trades` should be in time order, as Live Trades keeps them.
+    pub fn new(accounts: &[AccountSummary], trades: &[TradeRecord]) -> Self {
+        let mut total = trades::equity_curve(accounts, trades).first().map_or(0.0, |p| p.total);
+        let mut analytics = Self::default();
+        for trade in trades {
+            let before = total;
+            total += trade.transaction;
+            if trade.transfer.is_some() {
+                continue;
+            }
+            analytics.fees += trade.fee.unwrap_or_default();
+            if trade.dividend {
+                analytics.dividends += trade.transaction;
+            } else {
+                analytics.trades += 1;
+                if trade.transaction > 0.0 {
+                    analytics.wins += 1;
+                    analytics.gross_win += trade.transaction;
+                } else if trade.transaction < 0.0 {
+                    analytics.losses += 1;
+                    analytics.gross_loss -= trade.transaction;
+                }
+            }
+            let Some(at) = trade.timestamp else {
+                continue;
+            };
+            let start = Period { start: before, ..Period::default() };
+            for period in
+                [analytics.months.entry((at.year(), at.month())).or_insert(start), analytics.years.entry(at.year()).or_insert(start)]
+            {
+                period.trades += usize::from(!trade.dividend);
+                period.pnl += trade.transaction;
+            }
+        }
+        analytics
+    }
+
+    /// Share of trades that gained, in percent.
+    pub fn win_rate(&self) -> Option<f64> {
+        (self.trades > 0).then(|| self.wins as f64 / self.trades as f64 * 100.0)
+    }
+
+    pub fn avg_win(&self) -> Option<f64> {
+        (self.wins > 0).then(|| self.gross_win / self.wins as f64)
+    }
+
+    /// Negative, like the losses.
+    pub fn avg_loss(&self) -> Option<f64> {
+        (self.losses > 0).then(|| -self.gross_loss / self.losses as f64)
+    }
+
+    /// Gross wins over gross losses; `None` without losses.
+    pub fn profit_factor(&self) -> Option<f64> {
+        (self.gross_loss > 0.0).then(|| self.gross_win / self.gross_loss)
+    }
+
+    /// Everything the trades and dividends made, after fees.
+    pub fn net_pnl(&self) -> f64 {
+        self.gross_win - self.gross_loss + self.dividends
+    }
+
+    pub fn gross_pnl(&self) -> f64 {
+        self.net_pnl() + self.fees
+    }
+
+    pub fn expectancy(&self) -> Option<f64> {
+        (self.trades > 0).then(|| (self.gross_win - self.gross_loss) / self.trades as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::ids::AccountId;
+
+    #[test]
+    fn trades_roll_up_into_ratios_and_months() {
+        let trade = |amount: f64, month: u32, transfer: Option<u64>| TradeRecord {
+            timestamp: NaiveDate::from_ymd_opt(2024, month, 3).unwrap().and_hms_opt(9, 30, 0),
+            transfer,
+            ..TradeRecord::new(AccountId::parse("Alice").unwrap(), amount, 0.0)
+        };
+        let alice = AccountSummary::new(AccountId::parse("Alice").unwrap(), 100.0, "USD");
+        let history = [
+            trade(10.0, 1, None),
+            trade(-5.0, 1, None),
+            trade(50.0, 2, Some(1)),
+            TradeRecord { fee: Some(1.5), ..trade(31.0, 2, None) },
+            trade(-4.0, 3, None),
+        ];
+        let stats = Analytics::new(&[alice], &history);
+        assert_eq!((stats.trades, stats.wins, stats.losses), (4, 2, 2));
+        assert_eq!(stats.win_rate(), Some(50.0));
+        assert_eq!((stats.avg_win(), stats.avg_loss()), (Some(20.5), Some(-4.5)));
+        assert_eq!(stats.profit_factor(), Some(41.0 / 9.0));
+        assert_eq!(stats.expectancy(), Some(8.0));
+        assert_eq!((stats.net_pnl(), stats.gross_pnl()), (32.0, 33.5));
+        // February starts after January's +5 and the transfer in.
+        assert_eq!(stats.months[&(2024, 1)].return_pct(), Some(5.0));
+        assert_eq!(stats.months[&(2024, 2)], Period { trades: 1, pnl: 31.0, start: 155.0 });
+        assert_eq!(stats.years[&2024], Period { trades: 4, pnl: 32.0, start: 100.0 });
+        assert_eq!(Analytics::new(&[], &[]).profit_factor(), None);
+    }
+}
@@ -0,0 +1,3496 @@
+//! Application state and the key-handling reducer.
+//!
+//! `App::handle_event` is pure with respect to the outside world: it only
+//! mutates `App` and returns `Effect`s describing any I/O to perform. The
+//! effect runner in `effects.rs` executes them and feeds results back in as
+//! further `AppEvent`s.
+
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use chrono::SubsecRound;
+use crossterm::event::KeyCode;
+use serde::{Deserialize, Serialize};
+use tui::style::Color;
+
+use crate::accounts::{self, AccountSummary};
+use crate::alerts::{AlertPreview, AlertRule};
+use crate::analytics::Analytics;
+use crate::axis::Scale;
+use crate::changelog::{self, Entry};
+use crate::dividends::{self, DividendsView};
+use crate::drawings::{Crosshair, Drawing, Drawings, Point};
+use crate::config::{
+    self, AutoTradeConfig, CalendarConfig, ChartConfig, FeesConfig, GameConfig, LayoutConfig, MovesConfig, NewsConfig, OverviewConfig, Panel,
+    PipelineConfig, RetentionConfig, SizingConfig, StrategyConfig, TaxConfig,
+};
+use crate::ids::{AccountId, Ticker};
+use crate::data::{self, Bar, Benchmark, Interval, PriceSeries, StockInfo};
+use crate::date_range::{DateRange, PickerStep, RangePicker};
+use crate::errors::{AppError, ErrorLog};
+use crate::hints::Context;
+use crate::chart_image::{self, ChartImage, Marker};
+use crate::export;
+use crate::update::{self, Release};
+use crate::fx::{self, Rates};
+use crate::game::{self, Game, GameView, Instruction};
+use crate::health::HealthReport;
+use crate::jobs::{Job, JobQueue};
+use crate::keymap::{self, Action, Key, Keymap};
+use crate::ledger::Ledger;
+use crate::menu::{self, MenuState, MenuStep};
+use crate::market::live::LiveFeed;
+use crate::market::symbols::SymbolBook;
+use crate::markets;
+use crate::ml::history::{self, Prediction};
+use crate::ml::registry::{ModelsView, Registry};
+use crate::events::{Event, EventCache};
+use crate::news::{Headline, NewsCache};
+use crate::number_input::NumberInput;
+use crate::overview::OverviewView;
+use crate::recovery::{AccountDraft, Drafts, TradeDraft};
+use crate::session::Session;
+use crate::sizing::{self, Suggestion};
+use crate::splits;
+use crate::strategy::{self, Latest, Signal};
+use crate::tax::TaxLots;
+use crate::refresh::{self, Loaded, Loader, Request, Source, Status};
+use crate::prices;
+use crate::retention;
+use crate::returns::ReturnsView;
+use crate::risk::RiskReport;
+use crate::stats::UsageStats;
+use crate::theme::Theme;
+use crate::time_travel::{Snapshot, TimeTravelView, TravelStep};
+use crate::trades::{self, TradeCursor, TradeRecord};
+use crate::capabilities::{self, Capabilities, Capability};
+use crate::broker::{self, Broker, BrokerView, Fill, Notice, OrderRequest, Side, Submitted};
+use crate::market::book::OrderBook;
+use crate::market::minutes::MinuteBar;
+use crate::universe::{self, SeriesKey};
+#[cfg(feature = "streaming")]
+use crate::market::stream::StreamFeed;
+
+// ============================
+// ML List Modes
+// ============================
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MLMode {
+    List,
+    Search,
+    Filter,
+    // Typing an alert rule to backtest on the selected stock.
+    Alert,
+    // Typing a date to move the trade history to.
+    JumpToDate,
+}
+
+/// Column the ML list is sorted by (keys `1`..`5`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    #[default]
+    Ticker,
+    Price,
+    Change,
+    PctChange,
+    // Against the benchmark; stocks without enough history sort lowest.
+    RelStrength,
+}
+
+/// Fuzzy subsequence match of `query` against `candidate`, case-insensitive.
+/// Higher scores are better; prefix and consecutive hits score extra.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let candidate: Vec<char> = candidate.to_uppercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut last_hit: Option<usize> = None;
+    for q in query.to_uppercase().chars() {
+        let offset = candidate[pos..].iter().position(|&c| c == q)?;
+        let hit = pos + offset;
+        score += 1;
+        if hit == 0 {
+            score += 3;
+        }
+        if last_hit.is_some_and(|l| l + 1 == hit) {
+            score += 2;
+        }
+        last_hit = Some(hit);
+        pos = hit + 1;
+    }
+    // Prefer shorter tickers when the query covers them equally well.
+    Some(score * 10 - candidate.len() as i32)
+}
+
+// ============================
+// Panel Focus
+// ============================
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Focus {
+    Chart,
+    LiveTrades,
+    Accounts,
+    Jobs,
+    #[default]
+    MLList,
+    News,
+    Calendar,
+}
+
+impl Focus {
+    /// Next panel in Tab order (top-left to bottom, wrapping).
+    fn next(self) -> Self {
+        match self {
+            Focus::Chart => Focus::LiveTrades,
+            Focus::LiveTrades => Focus::Accounts,
+            Focus::Accounts => Focus::Jobs,
+            Focus::Jobs => Focus::MLList,
+            Focus::MLList => Focus::News,
+            Focus::News => Focus::Calendar,
+            Focus::Calendar => Focus::Chart,
+        }
+    }
+
+    fn prev(self) -> Self {
+        match self {
+            Focus::Chart => Focus::Calendar,
+            Focus::LiveTrades => Focus::Chart,
+            Focus::Accounts => Focus::LiveTrades,
+            Focus::Jobs => Focus::Accounts,
+            Focus::MLList => Focus::Jobs,
+            Focus::News => Focus::MLList,
+            Focus::Calendar => Focus::News,
+        }
+    }
+
+    /// Where the panel's data comes from, for the refresh key. The Jobs
+    /// panel has no source; it's updated as jobs report back.
+    fn source(self) -> Option<Source> {
+        match self {
+            Focus::Chart => Some(Source::Chart),
+            Focus::LiveTrades => Some(Source::Trades),
+            Focus::Accounts => Some(Source::Accounts),
+            Focus::Jobs => None,
+            Focus::MLList => Some(Source::Stocks),
+            Focus::News => Some(Source::News),
+            Focus::Calendar => Some(Source::Calendar),
+        }
+    }
+
+    pub fn panel(self) -> Panel {
+        match self {
+            Focus::Chart => Panel::Chart,
+            Focus::LiveTrades => Panel::LiveTrades,
+            Focus::Accounts => Panel::Accounts,
+            Focus::Jobs => Panel::Jobs,
+            Focus::MLList => Panel::MlList,
+            Focus::News => Panel::News,
+            Focus::Calendar => Panel::Calendar,
+        }
+    }
+}
+
+/// Percent of height moved between the chart and table rows per step.
+const RESIZE_STEP: u16 = 5;
+/// Smallest height, in percent, a row can be resized down to.
+const MIN_ROW: u16 = 10;
+// ============================
+// Quote Flash Highlighting
+// ============================
+/// How long a row stays highlighted after its price changes.
+const FLASH_DURATION: Duration = Duration::from_millis(1200);
+/// Move (in percent) at which the flash reaches full intensity.
+const FLASH_FULL_PCT: f64 = 2.0;
+/// Up/Down steps of the crosshair across the visible closes' range.
+const CROSSHAIR_STEPS: f64 = 40.0;
+/// Bars back the Signal column looks for the latest buy or sell.
+const SIGNAL_LOOKBACK: usize = 20;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Flash {
+    up: bool,
+    // 0.0..=1.0, proportional to the size of the move.
+    strength: f64,
+    started: Instant,
+}
+
+impl Flash {
+    /// Background colour for this flash at `now`, or `None` once it has faded.
+    pub fn color(&self, now: Instant) -> Option<Color> {
+        let elapsed = now.duration_since(self.started);
+        if elapsed >= FLASH_DURATION {
+            return None;
+        }
+        let fade = 1.0 - elapsed.as_secs_f64() / FLASH_DURATION.as_secs_f64();
+        let level = (40.0 + 160.0 * self.strength * fade) as u8;
+        Some(if self.up { Color::Rgb(0, level, 0) } else { Color::Rgb(level, 0, 0) })
+    }
+}
+
+// ============================
+// Account Form
+// ============================
+/// Labels of the account form fields, in Tab order.
+pub const ACCOUNT_FIELDS: [&str; 3] = ["Name", "Starting balance", "Currency"];
+/// Index of the numeric field in `ACCOUNT_FIELDS`.
+pub const BALANCE_FIELD: usize = 1;
+
+/// Create/edit form for an account, shown over the dashboard while open.
+#[derive(Debug, Clone, Default)]
+pub struct AccountForm {
+    // Index into `accounts` being edited; `None` creates a new account.
+    pub editing: Option<usize>,
+    pub fields: [String; 3],
+    // Index into `fields` receiving keystrokes.
+    pub active: usize,
+    pub error: Option<String>,
+}
+
+impl AccountForm {
+    fn edit(index: usize, account: &AccountSummary) -> Self {
+        Self {
+            editing: Some(index),
+            fields: [account.name.to_string(), format!("{:.2}", account.initial_amount), account.currency.clone()],
+            ..Self::default()
+        }
+    }
+}
+
+// ============================
+// Trade Form
+// ============================
+/// Labels of the trade form fields, in Tab order.
+pub const TRADE_FIELDS: [&str; 4] = ["Account", "Amount", "Ticker", "Note"];
+/// The same fields' labels when the form records a transfer.
+pub const TRANSFER_FIELDS: [&str; 4] = ["From", "Amount", "To", "Note"];
+/// Index of the numeric field in `TRADE_FIELDS`.
+pub const AMOUNT_FIELD: usize = 1;
+
+/// Form recording a gain or loss on an account, shown over the dashboard
+/// while open. Ticker and note are optional. With `transfer` set it moves
+/// cash between two accounts instead, the ticker field naming the account
+/// receiving it; with `dividend`, it records a dividend paid on the ticker,
+/// which is then required.
+#[derive(Debug, Clone, Default)]
+pub struct TradeForm {
+    pub fields: [String; 4],
+    // Index into `fields` receiving keystrokes.
+    pub active: usize,
+    pub transfer: bool,
+    pub dividend: bool,
+    pub error: Option<String>,
+}
+
+/// What an open date range picker applies its range to.
+#[derive(Debug, Clone)]
+pub enum RangeTarget {
+    Chart,
+    /// The open alert preview.
+    Backtest,
+    Download { ticker: Ticker, interval: Interval },
+}
+
+/// An order placed on a strategy signal, until it closes.
+#[derive(Debug, Clone)]
+struct AutoOrder {
+    ticker: Ticker,
+    side: Side,
+    qty: f64,
+    /// Put in the note of each fill booked for it.
+    reason: String,
+}
+
+// ============================
+// App State
+// ============================
+pub struct App {
+    pub stocks: Vec<StockInfo>,
+    pub selected: usize,
+    pub ml_mode: MLMode,
+    pub search_input: String,
+    pub show_instructions: bool,
+    pub ml_output: String,
+    // Logged model predictions, shown as per-ticker accuracy.
+    pub ml_history: Vec<Prediction>,
+    pub accounts: Vec<AccountSummary>,
+    pub trades: Vec<TradeRecord>,
+    pub focus: Focus,
+    pub trades_scroll: usize,
+    // Rows Live Trades showed in the last frame, the PageUp/PageDown step.
+    pub trades_page: usize,
+    // Where the last read of the history stopped; timed reloads only read
+    // the rows added since.
+    trades_cursor: Option<TradeCursor>,
+    // The history's sums per account, once it has been read; account
+    // balances are folded from it.
+    ledger: Option<Ledger>,
+    pub jump_input: String,
+    // Trade picked with the mouse in Live Trades, as an index into `trades`.
+    pub trades_selected: Option<usize>,
+    // Cursor within `visible_accounts()`.
+    pub accounts_selected: usize,
+    pub show_archived: bool,
+    // Summary table in order of % change rather than file order.
+    pub rank_accounts: bool,
+    pub account_form: Option<AccountForm>,
+    pub trade_form: Option<TradeForm>,
+    pub jobs: JobQueue,
+    pub errors: ErrorLog,
+    pub show_errors: bool,
+    pub usage: UsageStats,
+    pub show_usage: bool,
+    pub keymap: Keymap,
+    pub theme: Theme,
+    // Row proportions and hidden panels, saved to `[layout]` when changed.
+    pub layout: LayoutConfig,
+    pub moves: MovesConfig,
+    // Cursor within the Jobs panel, newest job first.
+    pub jobs_selected: usize,
+    // Number of bars hidden off the right edge of the chart.
+    pub chart_offset: usize,
+    // Dates the chart is zoomed to, kept when switching tickers.
+    pub chart_range: DateRange,
+    pub range_picker: Option<(RangeTarget, RangePicker)>,
+    // Series of the selected ticker shown in the Stock Chart panel.
+    pub chart: PriceSeries,
+    // Levels and trendlines drawn on each ticker's chart, and the cursor
+    // placing them while it's shown.
+    pub drawings: Drawings,
+    pub crosshair: Option<Crosshair>,
+    // Tickers marked for comparison, in marking order. While any are
+    // marked the chart overlays them instead of showing `chart`.
+    pub marked: Vec<Ticker>,
+    // Series of `marked`, reloaded with the chart.
+    pub compare: Vec<PriceSeries>,
+    // `chart_benchmark` from the config and its series, drawn over the
+    // selected ticker's unless that is the benchmark itself.
+    pub chart_benchmark: Option<Ticker>,
+    pub overlay: Option<PriceSeries>,
+    // Volume histogram under the price line.
+    pub show_volume: bool,
+    // Price axis of the Stock Chart and Compare chart.
+    pub chart_scale: Scale,
+    // Bid/ask depth of the selected ticker beside the chart.
+    pub show_order_book: bool,
+    pub benchmark: Benchmark,
+    // Volatility, beta, drawdown and VaR of `chart` and of the accounts.
+    pub risk: RiskReport,
+    pub loader: Loader,
+    // Sources whose last reload didn't succeed or was asked for with the
+    // refresh key; absent ones are ready.
+    loads: HashMap<Source, Status>,
+    // Sources with a reload running, which aren't requested again until it
+    // reports back.
+    in_flight: HashSet<Source>,
+    last_auto_refresh: Option<Instant>,
+    // Whether the prediction history has been asked for or delivered; it's
+    // read once something shows it, see `refresh`.
+    history_read: bool,
+    // Selection and marks of the restored session, applied to the first
+    // stock list, which loads after startup.
+    restored: Option<(Option<Ticker>, Vec<Ticker>)>,
+    // Rates for the account totals; fetched every `fx::FETCH_INTERVAL` if
+    // `fx_fetch` is set.
+    pub fx: Rates,
+    fx_fetch: bool,
+    last_fx_fetch: Option<Instant>,
+    pub export_format: export::Format,
+    /// What the chart is saved as.
+    pub chart_format: chart_image::Format,
+    // Applied by a maintenance job every `retention::MAINTENANCE_INTERVAL`
+    // while any policy is set.
+    pub retention: RetentionConfig,
+    pub pipeline: PipelineConfig,
+    // Position suggested after the last model run, P pre-fills it as a
+    // broker order.
+    pub sizing: SizingConfig,
+    pub suggestion: Option<Suggestion>,
+    // Latest `[strategy]` signal of each listed ticker, for the Signal
+    // column, and the last bar each was computed on, so only signals on
+    // bars that arrive later become orders.
+    pub strategy: StrategyConfig,
+    pub signals: HashMap<Ticker, Latest>,
+    signal_bars: HashMap<Ticker, chrono::NaiveDateTime>,
+    // Whether signals place orders (S), and the orders placed that way by
+    // the id `submit_order` gave them, until they close; their fills are
+    // booked with the reason in the note.
+    pub autotrade: AutoTradeConfig,
+    pub armed: bool,
+    auto_orders: HashMap<String, AutoOrder>,
+    last_maintenance: Option<Instant>,
+    // Whether to look for a newer release once at startup, whether that
+    // check has started and finished, and the newer release it found; its
+    // notes are shown in a popup while `show_release_notes`.
+    update_check: bool,
+    update_requested: bool,
+    update_checked: bool,
+    pub release: Option<Release>,
+    pub show_release_notes: bool,
+    // The changelog entries in the popup, and whether they're the ones new
+    // since the last session's version rather than the whole changelog.
+    pub changelog: Option<(Vec<&'static Entry>, bool)>,
+    // `[news]` settings, the headlines fetched so far, and the ticker and
+    // time of the last fetch started, so one that fails isn't retried every
+    // frame.
+    pub news_config: NewsConfig,
+    pub news: NewsCache,
+    news_request: Option<(Ticker, Instant)>,
+    pub news_selected: usize,
+    // Company names from `[data] provider`, shown beside tickers.
+    pub symbols: SymbolBook,
+    pub calendar_config: CalendarConfig,
+    pub calendar: EventCache,
+    pub chart_config: ChartConfig,
+    // The tickers last asked for and when, so the list is only refetched
+    // when it changes or the cache is due.
+    calendar_request: Option<(Vec<Ticker>, Instant)>,
+    /// Tickers whose events couldn't be fetched last time.
+    pub calendar_failed: Vec<Ticker>,
+    pub calendar_selected: usize,
+    // Chart reload last requested, to spot a new selection or marked list.
+    chart_request: Option<Request>,
+    pub live: Option<LiveFeed>,
+    // Why live quotes are enabled but can't be fetched, if they can't.
+    pub live_unavailable: Option<String>,
+    #[cfg(feature = "streaming")]
+    pub stream: Option<StreamFeed>,
+    // The broker orders are routed to and the view placing them.
+    pub broker: Option<Box<dyn Broker>>,
+    pub broker_view: Option<BrokerView>,
+    pub models_view: Option<ModelsView>,
+    // Python, the network and the optional features, and why any is
+    // missing.
+    pub capabilities: Capabilities,
+    // Set when the last download found every data source down; the reason
+    // is also in the Errors view.
+    pub source_unavailable: Option<String>,
+    // Rows whose price changed recently, keyed by ticker.
+    pub flashes: HashMap<Ticker, Flash>,
+    pub sort_key: SortKey,
+    pub sort_desc: bool,
+    pub filter_input: String,
+    pub alert_input: String,
+    pub alert_preview: Option<AlertPreview>,
+    pub returns_view: Option<ReturnsView>,
+    pub analytics: Option<Analytics>,
+    pub dividends: Option<DividendsView>,
+    // The market overview, full screen while open, and its `[overview]`
+    // settings.
+    pub overview: Option<OverviewView>,
+    pub overview_config: OverviewConfig,
+    pub time_travel: Option<TimeTravelView>,
+    /// The menu bar while it's open.
+    pub menu: Option<MenuState>,
+    /// The startup checks, shown until dismissed.
+    pub health: Option<HealthReport>,
+    // The trading game, full screen while open, and its `[game]` settings.
+    pub game_view: Option<GameView>,
+    pub game_config: GameConfig,
+    /// Open tax lots of the trade history, for the broker view's break-even
+    /// prices, with the `[tax]` and `[fees]` settings.
+    pub lots: TaxLots,
+    pub tax_config: TaxConfig,
+    pub fees_config: FeesConfig,
+    // Lines of the last retention dry run, shown full screen until closed.
+    pub retention_report: Option<Vec<String>>,
+    // Cursor within the filtered matches while in filter mode.
+    pub filter_selected: usize,
+    pub should_quit: bool,
+    // Set by Ctrl+Z; the event loop hands the terminal back to the shell.
+    pub suspend_requested: bool,
+}
+
+impl App {
+    pub fn new(config: &config::Config) -> Self {
+        let (keymap, keymap_problems) = Keymap::from_config(&config.keys);
+        let (theme, theme_problems) = Theme::from_config(&config.theme, &config.themes);
+        let capabilities = capabilities::for_config(config);
+        let live_unavailable = capabilities.check(&[Capability::Network]).err().filter(|_| config.live.enabled);
+        let mut app = Self {
+            stocks: Vec::new(),
+            selected: 0,
+            ml_mode: MLMode::List,
+            search_input: String::new(),
+            show_instructions: false,
+            ml_output: String::new(),
+            ml_history: Vec::new(),
+            accounts: Vec::new(),
+            trades: Vec::new(),
+            focus: Focus::default(),
+            trades_scroll: 0,
+            trades_page: 1,
+            trades_cursor: None,
+            ledger: None,
+            jump_input: String::new(),
+            trades_selected: None,
+            accounts_selected: 0,
+            show_archived: false,
+            rank_accounts: false,
+            account_form: None,
+            trade_form: None,
+            jobs: JobQueue::start(),
+            errors: ErrorLog::default(),
+            show_errors: false,
+            usage: UsageStats::default(),
+            show_usage: false,
+            keymap,
+            theme,
+            layout: config.layout.clone(),
+            moves: config.moves.clone(),
+            jobs_selected: 0,
+            chart_offset: 0,
+            chart_range: DateRange::default(),
+            range_picker: None,
+            chart: PriceSeries::default(),
+            drawings: Drawings::default(),
+            crosshair: None,
+            marked: Vec::new(),
+            compare: Vec::new(),
+            chart_benchmark: config.chart_benchmark.clone(),
+            overlay: None,
+            show_volume: true,
+            chart_scale: Scale::default(),
+            show_order_book: false,
+            // Closes are loaded by the first timed refresh.
+            benchmark: Benchmark {
+                ticker: config.benchmark.clone(),
+                dates: Vec::new(),
+                closes: Vec::new(),
+                return_3m: None,
+                error: None,
+            },
+            risk: RiskReport::default(),
+            loader: Loader::start(),
+            loads: HashMap::new(),
+            in_flight: HashSet::new(),
+            last_auto_refresh: None,
+            history_read: false,
+            restored: None,
+            fx: Rates::new(&config.fx),
+            fx_fetch: config.fx.fetch,
+            last_fx_fetch: None,
+            export_format: config.export.format,
+            chart_format: config.export.chart_format,
+            retention: config.retention.clone(),
+            pipeline: config.pipeline.clone(),
+            sizing: config.sizing.clone(),
+            suggestion: None,
+            strategy: config.strategy.clone(),
+            signals: HashMap::new(),
+            signal_bars: HashMap::new(),
+            autotrade: config.autotrade.clone(),
+            armed: false,
+            auto_orders: HashMap::new(),
+            update_check: config.updates.check,
+            update_requested: false,
+            update_checked: false,
+            release: None,
+            show_release_notes: false,
+            changelog: None,
+            last_maintenance: None,
+            chart_request: None,
+            live: if live_unavailable.is_some() { None } else { LiveFeed::start(&config.live) },
+            live_unavailable,
+            #[cfg(feature = "streaming")]
+            stream: StreamFeed::start(&config.stream),
+            broker: broker::start(config).ok(),
+            broker_view: None,
+            models_view: None,
+            capabilities,
+            source_unavailable: None,
+            flashes: HashMap::new(),
+            sort_key: SortKey::default(),
+            sort_desc: false,
+            filter_input: String::new(),
+            alert_input: String::new(),
+            alert_preview: None,
+            returns_view: None,
+            time_travel: None,
+            analytics: None,
+            dividends: None,
+            overview: None,
+            overview_config: config.overview.clone(),
+            menu: None,
+            health: None,
+            game_view: None,
+            game_config: config.game.clone(),
+            lots: TaxLots::default(),
+            tax_config: config.tax.clone(),
+            fees_config: config.fees,
+            news_config: config.news.clone(),
+            news: NewsCache::default(),
+            news_request: None,
+            news_selected: 0,
+            calendar_config: config.calendar.clone(),
+            calendar: EventCache::default(),
+            chart_config: config.chart.clone(),
+            symbols: SymbolBook::default(),
+            calendar_request: None,
+            calendar_failed: Vec::new(),
+            calendar_selected: 0,
+            retention_report: None,
+            filter_selected: 0,
+            should_quit: false,
+            suspend_requested: false,
+        };
+        for message in keymap_problems.into_iter().chain(theme_problems) {
+            app.errors.push(AppError::Load { path: config::CONFIG_PATH.to_string(), message });
+        }
+        if app.layout.is_hidden(app.focus.panel()) {
+            app.cycle_focus(Focus::next);
+        }
+        app
+    }
+
+    pub fn selected_ticker(&self) -> Option<&Ticker> {
+        self.stocks.get(self.selected).map(|s| &s.ticker)
+    }
+
+    /// Load state of `source`, shown on the panels that use it.
+    pub fn load_status(&self, source: Source) -> &Status {
+        static READY: Status = Status::Ready;
+        self.loads.get(&source).unwrap_or(&READY)
+    }
+
+    /// Shows `source` as loading until its first reload is in, for data
+    /// left to the loader at startup.
+    pub fn load_started(&mut self, source: Source) {
+        self.loads.insert(source, Status::Loading);
+    }
+
+    /// Records a failed load of `source`, e.g. at startup.
+    pub fn load_failed(&mut self, source: Source, err: AppError) {
+        self.loads.insert(source, Status::Failed(err.to_string()));
+        self.errors.push(err);
+    }
+
+    /// Reloads due this frame: the timed sources every `AUTO_REFRESH`, the
+    /// chart as soon as the selection or the marked list changes, the
+    /// exchange rates every `fx::FETCH_INTERVAL`, and headlines when the
+    /// selected ticker's are missing or older than `[news] refresh_mins`.
+    /// The earnings calendar is refreshed when the ML list's tickers change
+    /// and every `[calendar] refresh_hours`. Nothing is fetched from the
+    /// network when the health check found none.
+    fn auto_refresh(&mut self) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        #[cfg(feature = "streaming")]
+        if let Some(stream) = &mut self.stream {
+            let closed = stream.take_closed();
+            if !closed.is_empty() {
+                effects.push(Effect::AppendBars(closed));
+            }
+        }
+        let online = self.capabilities.missing(Capability::Network).is_none();
+        if online && self.calendar_config.enabled && !self.stocks.is_empty() {
+            let mut tickers = self.stock_tickers();
+            tickers.sort();
+            let every = Duration::from_secs(self.calendar_config.refresh_hours.max(1) * 3600);
+            let due = self.calendar_request.as_ref().is_none_or(|(t, at)| *t != tickers || at.elapsed() >= every);
+            if due && let Some(effect) = self.request(self.request_for(Source::Calendar), false) {
+                self.calendar_request = Some((tickers, Instant::now()));
+                effects.push(effect);
+            }
+        }
+        if online
+            && self.news_config.enabled
+            && let Some(ticker) = self.selected_ticker().cloned()
+        {
+            let every = Duration::from_secs(self.news_config.refresh_mins.max(1) * 60);
+            let tried = self.news_request.as_ref().is_some_and(|(t, at)| *t == ticker && at.elapsed() < every);
+            if self.news.is_stale(&ticker, every)
+                && !tried
+                && let Some(effect) = self.request(self.request_for(Source::News), false)
+            {
+                self.news_request = Some((ticker, Instant::now()));
+                effects.push(effect);
+            }
+        }
+        if online && self.fx_fetch && self.last_fx_fetch.is_none_or(|at| at.elapsed() >= fx::FETCH_INTERVAL) {
+            self.last_fx_fetch = Some(Instant::now());
+            effects.extend(self.request(self.request_for(Source::Fx), false));
+        }
+        if self.retention.is_set() && self.last_maintenance.is_none_or(|at| at.elapsed() >= retention::MAINTENANCE_INTERVAL) {
+            self.last_maintenance = Some(Instant::now());
+            effects.push(Effect::Maintenance { retention: self.retention.clone(), dry_run: self.retention.dry_run });
+        }
+        if !self.history_read && !self.layout.is_hidden(Panel::Performance) {
+            effects.extend(self.read_history());
+        }
+        if online && self.update_check && !self.update_requested {
+            self.update_requested = true;
+            effects.push(Effect::CheckUpdate);
+        }
+        if self.last_auto_refresh.is_none_or(|at| at.elapsed() >= refresh::AUTO_REFRESH) {
+            self.last_auto_refresh = Some(Instant::now());
+            for source in Source::TIMED {
+                effects.extend(self.request(self.request_for(source), false));
+            }
+        } else if self.chart_request.as_ref() != Some(&self.request_for(Source::Chart)) {
+            effects.extend(self.request(self.request_for(Source::Chart), false));
+        }
+        effects
+    }
+
+    fn request_for(&self, source: Source) -> Request {
+        match source {
+            Source::Stocks => Request::Stocks,
+            Source::Trades => Request::Trades(self.trades_cursor.clone()),
+            Source::Accounts => Request::Accounts,
+            Source::Chart => Request::Chart {
+                ticker: self.selected_ticker().cloned(),
+                series: self.stocks.get(self.selected).map(|s| s.series.clone()),
+                marked: self.marked.clone(),
+                overlay: self.chart_benchmark.clone().filter(|b| Some(b) != self.selected_ticker()),
+            },
+            Source::Benchmark => Request::Benchmark(self.benchmark.ticker.clone()),
+            Source::Fx => Request::Fx(self.fx.base().to_string()),
+            Source::News => Request::News { ticker: self.selected_ticker().cloned(), config: self.news_config.clone() },
+            Source::Calendar => Request::Calendar { tickers: self.stock_tickers(), config: self.calendar_config.clone() },
+            Source::History => Request::History,
+            Source::Models => Request::Models,
+            Source::Game => Request::Game,
+        }
+    }
+
+    /// Starts the one read of the prediction history, shown as loading.
+    fn read_history(&mut self) -> Option<Effect> {
+        self.history_read = true;
+        self.request(Request::History, true)
+    }
+
+    /// Whether the prediction history is in.
+    fn history_ready(&self) -> bool {
+        self.history_read && !self.in_flight.contains(&Source::History)
+    }
+
+    /// Starts `request` unless its source is already reloading. A `manual`
+    /// request (the refresh key) shows "loading…" until it's back; timed
+    /// ones keep showing the current data.
+    fn request(&mut self, request: Request, manual: bool) -> Option<Effect> {
+        let source = request.source();
+        if manual {
+            self.loads.insert(source, Status::Loading);
+        }
+        if !self.in_flight.insert(source) {
+            return None;
+        }
+        if source == Source::Chart {
+            self.chart_request = Some(request.clone());
+        }
+        Some(Effect::Refresh(request))
+    }
+
+    /// Applies a finished reload of `source`. A failed one leaves the
+    /// panel's previous data in place. New trades return the save of the
+    /// balances they fold to.
+    fn loaded(&mut self, source: Source, result: Result<Loaded, AppError>) -> Option<Effect> {
+        self.in_flight.remove(&source);
+        let mut save = None;
+        let failure = match result {
+            Err(err) => {
+                if matches!(source, Source::Models | Source::Game) {
+                    self.ml_output = format!("Failed to open: {}", err);
+                }
+                Some(err)
+            }
+            Ok(Loaded::Stocks(stocks)) => {
+                for stock in &stocks {
+                    if let Some(err) = &stock.error {
+                        self.errors.push(AppError::load(&universe::path_of(&stock.ticker).to_string_lossy(), err));
+                    }
+                }
+                self.refresh_stocks(stocks);
+                None
+            }
+            Ok(Loaded::Trades(read)) => {
+                if read.appended
+                    && let Some(ledger) = &mut self.ledger
+                {
+                    ledger.fold(&read.trades);
+                } else {
+                    self.ledger = Some(Ledger::new(&read.trades));
+                }
+                if let Some(ledger) = &self.ledger {
+                    ledger.apply(&mut self.accounts);
+                }
+                if read.appended && !read.trades.is_empty() {
+                    save = Some(Effect::SaveAccounts(self.accounts.clone()));
+                }
+                if read.appended {
+                    self.trades.extend(read.trades);
+                } else {
+                    self.trades = read.trades;
+                    self.trades_selected = self.trades_selected.filter(|&i| i < self.trades.len());
+                    self.scroll_trades_to(self.trades_scroll);
+                }
+                // Rows are shown oldest first, whatever order the file has.
+                if trades::sort_by_time(&mut self.trades) {
+                    self.trades_selected = None;
+                }
+                self.lots = TaxLots::new(&self.trades, &self.tax_config);
+                self.trades_cursor = Some(read.cursor);
+                None
+            }
+            Ok(Loaded::Accounts(accounts)) => {
+                self.accounts = accounts;
+                if let Some(ledger) = &self.ledger {
+                    ledger.apply(&mut self.accounts);
+                }
+                self.clamp_account_cursor();
+                None
+            }
+            Ok(Loaded::Chart { series, compare, overlay }) => {
+                let failure = match (&series.ticker, &series.error) {
+                    (Some(ticker), Some(err)) => Some(AppError::load(&universe::path_of(ticker).to_string_lossy(), err)),
+                    _ => None,
+                };
+                self.set_chart(series);
+                self.compare = compare;
+                self.overlay = overlay;
+                failure
+            }
+            Ok(Loaded::Fx(rates)) => {
+                self.fx.set_fetched(rates);
+                None
+            }
+            Ok(Loaded::News { ticker, headlines }) => {
+                if let Some(ticker) = ticker {
+                    self.news.insert(ticker, headlines);
+                }
+                None
+            }
+            Ok(Loaded::Calendar { cache, failed }) => {
+                splits::remember(&cache);
+                self.calendar = cache;
+                self.calendar_failed = failed;
+                None
+            }
+            Ok(Loaded::History(predictions)) => {
+                self.ml_history = predictions;
+                self.refresh_signals();
+                None
+            }
+            Ok(Loaded::Models(registry)) => {
+                self.open_models(registry);
+                None
+            }
+            Ok(Loaded::Game(game)) => {
+                self.open_game(game);
+                None
+            }
+            Ok(Loaded::Benchmark(benchmark)) => {
+                let failure = benchmark.error.as_ref().map(|err| AppError::load(&universe::path_of(&benchmark.ticker).to_string_lossy(), err));
+                self.benchmark = benchmark;
+                if self.sort_key == SortKey::RelStrength {
+                    self.sort_stocks(self.selected_row());
+                }
+                failure
+            }
+        };
+        match failure {
+            Some(err) => self.load_failed(source, err),
+            None => {
+                self.loads.remove(&source);
+            }
+        }
+        if matches!(source, Source::Chart | Source::Benchmark | Source::Accounts | Source::Trades) {
+            self.refresh_risk();
+        }
+        save
+    }
+
+    /// Recomputes the Risk panel from the loaded series and trades.
+    pub fn refresh_risk(&mut self) {
+        let equity = trades::equity_curve(&self.accounts, &self.trades);
+        self.risk = RiskReport::new(&self.chart, &self.benchmark, &equity);
+    }
+
+    /// The chart series' bars inside `chart_range`.
+    pub fn chart_bars(&self) -> &[Bar] {
+        self.chart_range.slice(&self.chart.bars, |b| b.at)
+    }
+
+    /// The chart bars left of the pan offset, at least two when there are.
+    pub fn visible_bars(&self) -> &[Bar] {
+        let bars = self.chart_bars();
+        &bars[..bars.len() - self.chart_offset.min(bars.len().saturating_sub(2))]
+    }
+
+    /// The chart benchmark over `bars`, if it has history there.
+    pub fn chart_overlay(&self, bars: &[Bar]) -> Option<Overlay> {
+        let series = self.overlay.as_ref().filter(|s| s.ticker != self.chart.ticker)?;
+        let closes = series.closes_at(bars.iter().map(|b| b.at));
+        let start = closes.iter().position(|c| c.is_some_and(|c| c != 0.0))?;
+        let (own_start, bench_start) = (bars[start].close, closes[start]?);
+        let points: Vec<(f64, f64)> = closes
+            .iter()
+            .enumerate()
+            .skip(start)
+            .filter_map(|(i, &c)| Some((i as f64, own_start * c? / bench_start)))
+            .collect();
+        let (own_last, bench_last) = (bars.last()?.close, closes.last().copied()??);
+        if points.len() < 2 || own_start == 0.0 {
+            return None;
+        }
+        Some(Overlay {
+            ticker: series.ticker.clone()?,
+            points,
+            own: (own_last / own_start - 1.0) * 100.0,
+            benchmark: (bench_last / bench_start - 1.0) * 100.0,
+        })
+    }
+
+    /// Replaces the chart series, resetting the pan when the ticker changes.
+    pub fn set_chart(&mut self, series: PriceSeries) {
+        if series.ticker != self.chart.ticker {
+            self.chart_offset = 0;
+            self.crosshair = None;
+            if let Some(ticker) = &series.ticker {
+                self.usage.view_ticker(ticker);
+            }
+        }
+        self.chart = series;
+    }
+
+    /// Drains quote and tick updates from the background feeds, logging
+    /// their failures.
+    pub fn poll_feeds(&mut self) {
+        if let Some(live) = &mut self.live {
+            for message in live.poll() {
+                self.errors.push(AppError::Feed { source: "live quotes", message });
+            }
+        }
+        #[cfg(feature = "streaming")]
+        if let Some(stream) = &mut self.stream {
+            for message in stream.poll() {
+                self.errors.push(AppError::Feed { source: "stream", message });
+            }
+            // A 1m chart grows with the ticks, the forming bar last.
+            if self.chart.interval == Interval::OneMinute
+                && let Some(ticker) = &self.chart.ticker
+            {
+                let closed = stream.closed().iter().filter(|(t, _)| t == ticker).map(|(_, bar)| bar);
+                for bar in closed.chain(stream.minutes.forming(ticker)) {
+                    crate::market::minutes::merge(&mut self.chart.bars, bar);
+                }
+            }
+        }
+    }
+
+    /// Drains the broker feed. Fills come back as events for the reducer
+    /// to book; messages go to the broker view and failures to the log.
+    pub fn poll_broker(&mut self) -> Vec<AppEvent> {
+        let Some(broker) = &mut self.broker else {
+            return Vec::new();
+        };
+        let source = broker.name();
+        let mut events = Vec::new();
+        for notice in broker.poll() {
+            match notice {
+                Notice::Fill(fill) => events.push(AppEvent::Fill(fill)),
+                Notice::Message(message) => self.broker_message(message),
+                Notice::Error(message) => {
+                    self.broker_message(message.clone());
+                    self.errors.push(AppError::Feed { source, message });
+                }
+                Notice::Rejected { id, message } => {
+                    self.auto_orders.remove(&id);
+                    self.broker_message(message.clone());
+                    self.errors.push(AppError::Feed { source, message });
+                }
+            }
+        }
+        // Auto orders cancelled or rejected with nothing filled have no
+        // fill to wait for.
+        if let Some(broker) = &self.broker {
+            let orders = &broker.state().orders;
+            self.auto_orders.retain(|id, _| !orders.iter().any(|o| o.known_as(id) && !o.is_open() && o.filled_qty == 0.0));
+        }
+        events
+    }
+
+    /// Shows `message` in the broker view, or the ML output box when it's
+    /// closed.
+    fn broker_message(&mut self, message: String) {
+        match &mut self.broker_view {
+            Some(view) => view.message = message,
+            None => self.ml_output = message,
+        }
+    }
+
+    /// Cached headlines of the selected ticker, newest first.
+    pub fn headlines(&self) -> &[Headline] {
+        self.selected_ticker().and_then(|t| self.news.get(t)).unwrap_or_default()
+    }
+
+    /// Events of the ML list's tickers within `[calendar] days_ahead`,
+    /// soonest first.
+    pub fn upcoming_events(&self) -> Vec<&Event> {
+        let today = chrono::Local::now().date_naive();
+        self.calendar.upcoming(&self.stock_tickers(), today, self.calendar_config.days_ahead)
+    }
+
+    /// The streamed book of the selected ticker, or why there is none to show.
+    pub fn order_book(&self) -> Result<&OrderBook, String> {
+        let Some(ticker) = self.selected_ticker() else {
+            return Err("No ticker selected".to_string());
+        };
+        if let Err(reason) = self.capabilities.check(&[Capability::Streaming]) {
+            return Err(format!("Depth {}", reason));
+        }
+        #[cfg(feature = "streaming")]
+        if let Some(stream) = &self.stream {
+            if !stream.provider().streams_quotes() {
+                return Err(format!("{} streams trades only; set provider = \"polygon\" in [stream] for quotes", stream.provider().name()));
+            }
+            return stream.books.get(ticker).ok_or_else(|| format!("Waiting for {} quotes", ticker));
+        }
+        Err(format!("Enable [stream] in stm.toml to stream {} quotes", ticker))
+    }
+
+    /// Status of the WebSocket stream for the header, if one is configured.
+    pub fn stream_status(&self) -> Option<String> {
+        if let Err(reason) = self.capabilities.check(&[Capability::Streaming]) {
+            return Some(format!("Stream {}", reason));
+        }
+        #[cfg(feature = "streaming")]
+        if let Some(stream) = &self.stream {
+            return Some(stream.status());
+        }
+        None
+    }
+
+    /// True while a text box is capturing keystrokes.
+    pub fn is_typing(&self) -> bool {
+        !matches!(self.ml_mode, MLMode::List) || self.account_form.is_some() || self.trade_form.is_some()
+    }
+
+    /// Indices into `stocks` shown in the ML list: everything, or the fuzzy
+    /// matches for the filter, best match first.
+    pub fn visible_stocks(&self) -> Vec<usize> {
+        if !matches!(self.ml_mode, MLMode::Filter) || self.filter_input.is_empty() {
+            return (0..self.stocks.len()).collect();
+        }
+        // `crypto:` narrows to a data root's tickers, `crypto:btc` within
+        // it; `nyse:` to a subdirectory's.
+        let from = |s: &StockInfo, source: &str| s.source.split('/').any(|part| part.eq_ignore_ascii_case(source));
+        let (source, query) = match self.filter_input.split_once(':') {
+            Some((source, query)) if self.stocks.iter().any(|s| from(s, source)) => (Some(source), query),
+            _ => (None, self.filter_input.as_str()),
+        };
+        let mut matches: Vec<(usize, i32)> = self
+            .stocks
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| source.is_none_or(|source| from(s, source)))
+            .filter_map(|(i, s)| fuzzy_score(query, s.ticker.as_str()).map(|score| (i, score)))
+            .collect();
+        matches.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+        matches.into_iter().map(|(i, _)| i).collect()
+    }
+
+    /// Job under the Jobs panel cursor.
+    pub fn selected_job(&self) -> Option<&Job> {
+        self.jobs.jobs.iter().rev().nth(self.jobs_selected)
+    }
+
+    /// Snapshot of unsubmitted input for autosave.
+    pub fn drafts(&self) -> Drafts {
+        Drafts {
+            account_form: self.account_form.as_ref().map(|form| AccountDraft {
+                editing: form.editing.and_then(|i| self.accounts.get(i)).map(|a| a.name.clone()),
+                fields: form.fields.clone(),
+                active: form.active,
+            }),
+            trade_form: self.trade_form.as_ref().map(|form| TradeDraft {
+                fields: form.fields.clone(),
+                active: form.active,
+                transfer: form.transfer,
+                dividend: form.dividend,
+            }),
+            search: matches!(self.ml_mode, MLMode::Search).then(|| self.search_input.clone()),
+            filter: matches!(self.ml_mode, MLMode::Filter).then(|| self.filter_input.clone()),
+        }
+    }
+
+    /// What to restore on the next start.
+    pub fn session(&self) -> Session {
+        // Quitting before the stock list is in keeps the restored selection.
+        let (selected, marked) = match &self.restored {
+            Some((selected, marked)) => (selected.clone(), marked.clone()),
+            None => (self.selected_ticker().cloned(), self.marked.clone()),
+        };
+        Session {
+            selected,
+            focus: self.focus,
+            sort_key: self.sort_key,
+            sort_desc: self.sort_desc,
+            chart_range: self.chart_range,
+            marked,
+            show_volume: self.show_volume,
+            chart_scale: self.chart_scale,
+            show_order_book: self.show_order_book,
+            show_archived: self.show_archived,
+            rank_accounts: self.rank_accounts,
+            last_seen_version: Some(update::CURRENT.to_string()),
+        }
+    }
+
+    /// Puts the dashboard back where the last session left it. Tickers no
+    /// longer stored are dropped, and what this version added since the one
+    /// that saved it is shown.
+    pub fn restore_session(&mut self, session: Session) {
+        self.focus = session.focus;
+        self.sort_key = session.sort_key;
+        self.sort_desc = session.sort_desc;
+        self.chart_range = session.chart_range;
+        self.show_volume = session.show_volume;
+        self.chart_scale = session.chart_scale;
+        self.show_order_book = session.show_order_book;
+        self.show_archived = session.show_archived;
+        self.rank_accounts = session.rank_accounts;
+        let new = changelog::since(session.last_seen_version.as_deref());
+        if !new.is_empty() {
+            self.changelog = Some((new, true));
+        }
+        if self.stocks.is_empty() {
+            self.restored = Some((session.selected, session.marked));
+        } else {
+            self.restore_selection(session.selected, session.marked);
+        }
+    }
+
+    fn restore_selection(&mut self, selected: Option<Ticker>, marked: Vec<Ticker>) {
+        self.marked = marked.into_iter().filter(|t| self.stocks.iter().any(|s| &s.ticker == t)).collect();
+        self.sort_stocks(selected.map(|t| (t, SeriesKey::default())));
+    }
+
+    /// Reopens drafts saved by a session that didn't exit cleanly. An edit
+    /// whose account no longer exists becomes a new-account form.
+    pub fn restore_drafts(&mut self, drafts: Drafts) {
+        if let Some(draft) = drafts.account_form {
+            self.account_form = Some(AccountForm {
+                editing: draft.editing.and_then(|name| self.accounts.iter().position(|a| a.name == name)),
+                fields: draft.fields,
+                active: draft.active.min(ACCOUNT_FIELDS.len() - 1),
+                error: None,
+            });
+            self.focus = Focus::Accounts;
+        }
+        if let Some(draft) = drafts.trade_form {
+            self.trade_form = Some(TradeForm {
+                fields: draft.fields,
+                active: draft.active.min(TRADE_FIELDS.len() - 1),
+                transfer: draft.transfer,
+                dividend: draft.dividend,
+                error: None,
+            });
+        }
+        if let Some(search) = drafts.search {
+            self.ml_mode = MLMode::Search;
+            self.search_input = search;
+        } else if let Some(filter) = drafts.filter {
+            self.ml_mode = MLMode::Filter;
+            self.filter_input = filter;
+        }
+        self.ml_output = "Restored unsaved input from the last session".to_string();
+    }
+
+    /// Indices into `accounts` shown in the summary table, in table order.
+    pub fn visible_accounts(&self) -> Vec<usize> {
+        let mut visible: Vec<usize> = (0..self.accounts.len())
+            .filter(|&i| self.show_archived || !self.accounts[i].archived)
+            .collect();
+        if self.rank_accounts {
+            let pct = |i: usize| self.accounts[i].percentage_change;
+            visible.sort_by(|&a, &b| pct(b).total_cmp(&pct(a)));
+        }
+        visible
+    }
+
+    /// Closes the selected account, or reopens it if it's already archived.
+    fn toggle_archived(&mut self) -> Option<Effect> {
+        let &i = self.visible_accounts().get(self.accounts_selected)?;
+        let account = &mut self.accounts[i];
+        account.archived = !account.archived;
+        self.ml_output = if account.archived {
+            format!("Closed account {}", account.name)
+        } else {
+            format!("Reopened account {}", account.name)
+        };
+        self.clamp_account_cursor();
+        Some(Effect::SaveAccounts(self.accounts.clone()))
+    }
+
+    /// Validates the open account form and applies it, returning the error to
+    /// show in the form if it doesn't pass.
+    fn submit_account_form(&mut self) -> Result<Effect, String> {
+        let Some(form) = &self.account_form else {
+            return Err("No account form open".to_string());
+        };
+        let name = AccountId::parse(&form.fields[0]).map_err(|e| format!("Invalid name: {}", e))?;
+        let duplicate = self.accounts.iter().enumerate().any(|(i, a)| {
+            Some(i) != form.editing && a.name.as_str().to_lowercase() == name.as_str().to_lowercase()
+        });
+        if duplicate {
+            return Err(format!("An account named {} already exists", name));
+        }
+        let balance = self.balance_input().parse(&form.fields[BALANCE_FIELD])?;
+        let currency = match form.fields[2].trim() {
+            "" => "USD".to_string(),
+            c if c.len() == 3 && c.chars().all(|c| c.is_ascii_alphabetic()) => c.to_ascii_uppercase(),
+            c => return Err(format!("Currency must be a 3-letter code, not {}", c)),
+        };
+
+        match form.editing {
+            Some(i) => {
+                // Trades are keyed by name, in any case, so renaming would
+                // orphan them; the ledger has the whole history, not just
+                // the rows loaded into the Live Trades panel.
+                let old = &self.accounts[i].name;
+                if old.as_str().to_lowercase() != name.as_str().to_lowercase() {
+                    match &self.ledger {
+                        None => return Err("The trade history is still loading; rename once it's in".to_string()),
+                        Some(ledger) if ledger.has_trades(old) => {
+                            return Err(format!("{} has trade history and can't be renamed", old));
+                        }
+                        Some(_) => {}
+                    }
+                }
+                let account = &mut self.accounts[i];
+                account.name = name;
+                account.initial_amount = balance;
+                account.change = account.current_amount - balance;
+                account.percentage_change = if balance != 0.0 { account.change / balance * 100.0 } else { 0.0 };
+                account.currency = currency;
+                // Taken again from the new starting balance less transfers.
+                account.opening_amount = None;
+                self.ml_output = format!("Updated account {}", account.name);
+                if let Some(ledger) = &self.ledger {
+                    ledger.apply(&mut self.accounts[i..=i]);
+                }
+            }
+            None => {
+                self.ml_output = format!("Created account {}", name);
+                self.usage.bump(|u| &mut u.accounts_created);
+                self.accounts.push(AccountSummary::new(name, balance, &currency));
+            }
+        }
+        self.account_form = None;
+        Ok(Effect::SaveAccounts(self.accounts.clone()))
+    }
+
+    /// The account form's starting balance field. When editing, `N%` is a
+    /// percentage of the account's current starting balance.
+    pub fn balance_input(&self) -> NumberInput {
+        let input = NumberInput::new("Starting balance").min(0.0).step(100.0);
+        match self.account_form.as_ref().and_then(|f| f.editing) {
+            Some(i) => input.percent_of(self.accounts[i].initial_amount),
+            None => input,
+        }
+    }
+
+    /// The trade form's amount field: negative for a loss, and `N%` is a
+    /// percentage of the named account's current balance.
+    pub fn amount_input(&self) -> NumberInput {
+        let input = NumberInput::new("Amount");
+        let account = self.trade_form.as_ref().and_then(|form| {
+            let name = form.fields[0].trim().to_lowercase();
+            self.accounts.iter().find(|a| a.name.as_str().to_lowercase() == name)
+        });
+        match account {
+            Some(account) => input.percent_of(account.current_amount),
+            None => input,
+        }
+    }
+
+    /// Opens the trade form on the account under the Accounts cursor, and
+    /// the selected stock when the ML list or chart has focus.
+    fn open_trade_form(&mut self) {
+        let mut form = TradeForm::default();
+        if let Some(&i) = self.visible_accounts().get(self.accounts_selected)
+            && !self.accounts[i].archived
+        {
+            form.fields[0] = self.accounts[i].name.to_string();
+            form.active = AMOUNT_FIELD;
+        }
+        if matches!(self.focus, Focus::MLList | Focus::Chart)
+            && let Some(ticker) = self.selected_ticker()
+        {
+            form.fields[2] = ticker.to_string();
+        }
+        self.trade_form = Some(form);
+    }
+
+    /// Opens the trade form as a transfer out of the account under the
+    /// Accounts cursor.
+    fn open_transfer_form(&mut self) {
+        let mut form = TradeForm { transfer: true, ..TradeForm::default() };
+        if let Some(&i) = self.visible_accounts().get(self.accounts_selected)
+            && !self.accounts[i].archived
+        {
+            form.fields[0] = self.accounts[i].name.to_string();
+            form.active = AMOUNT_FIELD;
+        }
+        self.trade_form = Some(form);
+    }
+
+    /// Opens the trade form as a dividend on `ticker` paid to the account
+    /// under the Accounts cursor, its amount pre-filled from the provider's
+    /// latest dividend where the account's fills held shares.
+    fn open_dividend_form(&mut self, ticker: Option<Ticker>) {
+        let mut form = TradeForm { dividend: true, ..TradeForm::default() };
+        let account = self.visible_accounts().get(self.accounts_selected).map(|&i| &self.accounts[i]).filter(|a| !a.archived);
+        if let Some(account) = account {
+            form.fields[0] = account.name.to_string();
+            form.active = AMOUNT_FIELD;
+        }
+        if let Some(ticker) = &ticker {
+            form.fields[2] = ticker.to_string();
+            let today = chrono::Local::now().date_naive();
+            if let Some(amount) =
+                account.and_then(|a| dividends::suggested(&self.trades, &self.calendar, &a.name, ticker, today))
+            {
+                form.fields[AMOUNT_FIELD] = format!("{:.2}", amount);
+            }
+        }
+        self.trade_form = Some(form);
+    }
+
+    /// Validates the open trade form and applies the trade to its account,
+    /// returning the error to show in the form if it doesn't pass.
+    fn submit_trade_form(&mut self) -> Result<Effect, String> {
+        let Some(form) = &self.trade_form else {
+            return Err("No trade form open".to_string());
+        };
+        let name = AccountId::parse(&form.fields[0]).map_err(|e| format!("Invalid account: {}", e))?;
+        let amount = self.amount_input().parse(&form.fields[AMOUNT_FIELD])?;
+        if form.transfer {
+            return self.submit_transfer(&name, amount);
+        }
+        if amount == 0.0 {
+            return Err("Amount must not be zero".to_string());
+        }
+        let ticker = match form.fields[2].trim() {
+            "" => None,
+            t => Some(Ticker::parse(t).map_err(|e| format!("Invalid ticker: {}", e))?),
+        };
+        if form.dividend && (ticker.is_none() || amount < 0.0) {
+            return Err("A dividend needs its ticker and a positive amount".to_string());
+        }
+        let note = Some(form.fields[3].trim().to_string()).filter(|n| !n.is_empty());
+        let dividend = form.dividend;
+        let now = chrono::Local::now().naive_local().trunc_subsecs(0);
+        let mut trade = accounts::process_trade(&mut self.accounts, &name, amount, now)?;
+        trade.ticker = ticker;
+        trade.note = note;
+        trade.dividend = dividend;
+        self.ml_output = match dividend {
+            true => format!("Recorded a {:.2} dividend on {}, balance {:.2}", amount, trade.name, trade.new_balance),
+            false => format!("Recorded {:+.2} on {}, balance {:.2}", amount, trade.name, trade.new_balance),
+        };
+        self.usage.bump(|u| &mut u.trades_entered);
+        self.trade_form = None;
+        Ok(Effect::RecordTrade(trade))
+    }
+
+    /// The transfer half of `submit_trade_form`, out of `from`.
+    fn submit_transfer(&mut self, from: &AccountId, amount: f64) -> Result<Effect, String> {
+        let Some(form) = &self.trade_form else {
+            return Err("No trade form open".to_string());
+        };
+        let to = AccountId::parse(&form.fields[2]).map_err(|e| format!("Invalid account to transfer to: {}", e))?;
+        let note = Some(form.fields[3].trim().to_string()).filter(|n| !n.is_empty());
+        let now = chrono::Local::now().naive_local().trunc_subsecs(0);
+        let legs = accounts::process_transfer(&mut self.accounts, from, &to, amount, now)?
+            .map(|leg| TradeRecord { note: note.clone(), ..leg });
+        self.ml_output = format!("Moved {:.2} from {} to {}", amount, legs[0].name, legs[1].name);
+        self.trade_form = None;
+        Ok(Effect::RecordTransfer(legs))
+    }
+
+    /// The portfolio report as of now, rendered in the configured format.
+    fn export_report(&self) -> Effect {
+        let at = chrono::Local::now().naive_local().trunc_subsecs(0);
+        let snapshot = export::Snapshot {
+            at,
+            accounts: &self.accounts,
+            rates: &self.fx,
+            trades: &self.trades,
+            stocks: &self.stocks,
+            moves: &self.moves,
+            predictions: &self.ml_history,
+        };
+        let text = export::render(at, &export::sections(&snapshot), self.export_format);
+        Effect::WriteReport { at, format: self.export_format, text }
+    }
+
+    /// The chart as the panel shows it, with the trades recorded against
+    /// its ticker marked, as an image in the `[export]` chart format.
+    fn export_chart(&self) -> Result<Effect, String> {
+        let ticker = self.chart.ticker.clone().ok_or("Select a stock to export its chart")?;
+        let bars = self.visible_bars();
+        let overlay = self.chart_overlay(bars);
+        let markers: Vec<Marker> = self
+            .trades
+            .iter()
+            .filter(|t| t.ticker.as_ref() == Some(&ticker) && t.transfer.is_none())
+            .filter_map(|t| Some(Marker { at: t.timestamp?, amount: t.transaction }))
+            .collect();
+        let title = if self.chart_range.is_all() {
+            format!("{} ({})", ticker, self.chart.interval.as_str())
+        } else {
+            format!("{} ({}, {})", ticker, self.chart.interval.as_str(), self.chart_range)
+        };
+        let image = ChartImage {
+            title,
+            interval: self.chart.interval,
+            bars: bars.to_vec(),
+            overlay,
+            markers,
+            show_volume: self.show_volume,
+        };
+        if !image.is_drawable() {
+            return Err(format!("Not enough bars of {} to draw", ticker));
+        }
+        let at = chrono::Local::now().naive_local().trunc_subsecs(0);
+        Ok(Effect::WriteChart { ticker, at, format: self.chart_format, image })
+    }
+
+    /// Scrolls Live Trades to start at row `scroll`, keeping the last page
+    /// full.
+    fn scroll_trades_to(&mut self, scroll: usize) {
+        self.trades_scroll = scroll.min(self.trades.len().saturating_sub(self.trades_page));
+    }
+
+    /// Scrolls Live Trades to the first trade on or after the typed date
+    /// and selects it.
+    fn jump_to_date(&mut self) {
+        let date = match chrono::NaiveDate::parse_from_str(self.jump_input.trim(), "%Y-%m-%d") {
+            Ok(date) => date,
+            Err(_) => {
+                self.ml_output = format!("Date must be YYYY-MM-DD, not {}", self.jump_input.trim());
+                return;
+            }
+        };
+        match trades::first_on_or_after(&self.trades, date) {
+            Some(i) => {
+                self.scroll_trades_to(i);
+                self.trades_selected = Some(i);
+                self.ml_mode = MLMode::List;
+                self.jump_input.clear();
+            }
+            None => self.ml_output = format!("No trades on or after {}", date),
+        }
+    }
+
+    fn clamp_account_cursor(&mut self) {
+        let visible = self.visible_accounts().len();
+        self.accounts_selected = self.accounts_selected.min(visible.saturating_sub(1));
+    }
+
+    /// Leaves filter mode, moving the main selection to the highlighted match.
+    fn accept_filter(&mut self) {
+        if let Some(&i) = self.visible_stocks().get(self.filter_selected) {
+            self.selected = i;
+        }
+        self.clear_filter();
+    }
+
+    fn clear_filter(&mut self) {
+        self.ml_mode = MLMode::List;
+        self.filter_input.clear();
+        self.filter_selected = 0;
+    }
+
+    /// Replaces the stock list, marking rows whose price moved since the
+    /// previous load so they flash on the next frames.
+    pub fn refresh_stocks(&mut self, mut stocks: Vec<StockInfo>) {
+        if let Some(live) = &self.live {
+            for stock in &mut stocks {
+                if let Some(quote) = live.quotes.get(&stock.ticker) {
+                    stock.price = quote.price;
+                    stock.change = quote.change;
+                    stock.pct_change = quote.pct_change;
+                }
+            }
+            live.set_tickers(stocks.iter().map(|s| s.ticker.clone()).collect());
+        }
+        // Ticks are newer than any poll, so they win. The change is measured
+        // against the previous close implied by the loaded quote.
+        #[cfg(feature = "streaming")]
+        if let Some(stream) = &self.stream {
+            for stock in &mut stocks {
+                if let Some(tick) = stream.ticks.get(&stock.ticker) {
+                    let prev_close = stock.price - stock.change;
+                    stock.price = tick.price;
+                    stock.change = tick.price - prev_close;
+                    stock.pct_change = if prev_close != 0.0 { stock.change / prev_close * 100.0 } else { 0.0 };
+                }
+            }
+            stream.set_tickers(stocks.iter().map(|s| s.ticker.clone()).collect());
+        }
+        let now = Instant::now();
+        for new in &stocks {
+            let Some(old) = self.stocks.iter().find(|s| s.ticker == new.ticker) else {
+                continue;
+            };
+            if old.price != new.price && old.price != 0.0 {
+                let move_pct = (new.price - old.price) / old.price * 100.0;
+                self.flashes.insert(new.ticker.clone(), Flash {
+                    up: move_pct > 0.0,
+                    strength: (move_pct.abs() / FLASH_FULL_PCT).clamp(0.0, 1.0),
+                    started: now,
+                });
+            }
+        }
+        self.flashes.retain(|_, f| f.color(now).is_some());
+        let selected = self.selected_row();
+        self.stocks = stocks;
+        match self.restored.take() {
+            Some((selected, marked)) => self.restore_selection(selected, marked),
+            None => self.sort_stocks(selected),
+        }
+        self.refresh_signals();
+    }
+
+    /// Recomputes the Signal column. While auto-trading is armed, a signal
+    /// at a ticker's last bar also becomes an order once that bar arrives
+    /// after the first load.
+    fn refresh_signals(&mut self) {
+        let strategy = strategy::build(&self.strategy, &self.ml_history);
+        let mut signals = HashMap::new();
+        let mut arrived = Vec::new();
+        for stock in &self.stocks {
+            let Ok(bars) = prices::cached_bars(&universe::path_of(&stock.ticker)) else {
+                continue;
+            };
+            let Some(last) = bars.last().map(|b| b.at) else {
+                continue;
+            };
+            let seen = self.signal_bars.insert(stock.ticker.clone(), last);
+            let Some(latest) = strategy::latest(strategy.as_ref(), &stock.ticker, &bars, SIGNAL_LOOKBACK) else {
+                continue;
+            };
+            if latest.bars_ago == 0 && seen.is_some_and(|seen| seen < last) {
+                arrived.push((stock.ticker.clone(), latest.signal, stock.price));
+            }
+            signals.insert(stock.ticker.clone(), latest);
+        }
+        let name = strategy.name();
+        drop(strategy);
+        self.signals = signals;
+        if self.armed {
+            for (ticker, signal, price) in arrived {
+                self.auto_trade(&name, ticker, signal, price);
+            }
+        }
+    }
+
+    /// Arms or disarms auto-trading. Arming needs `[autotrade] enabled` and
+    /// a broker that books its fills, so each one is in the trade history.
+    fn toggle_auto_trade(&mut self) {
+        if self.armed {
+            self.armed = false;
+            self.broker_message("Auto-trading disarmed".to_string());
+            return;
+        }
+        let message = match &self.broker {
+            _ if !self.autotrade.enabled => "Auto-trading is off (stm.toml [autotrade] enabled)".to_string(),
+            None => "Auto-trading needs a broker".to_string(),
+            Some(broker) if broker.booking_account().trim().is_empty() => {
+                format!("Auto-trading needs an account to book {} fills to (stm.toml [paper] account)", broker.name())
+            }
+            Some(broker) => {
+                self.armed = true;
+                format!("Auto-trading armed: {} signals place {} orders of {} shares", self.strategy_name(), broker.name(), self.autotrade.qty)
+            }
+        };
+        self.broker_message(message);
+    }
+
+    /// Name of the `[strategy]` in use, e.g. `SMA 10/30`.
+    pub fn strategy_name(&self) -> String {
+        strategy::build(&self.strategy, &self.ml_history).name()
+    }
+
+    /// Orders `[autotrade] qty` shares on `signal` (see `auto_qty`),
+    /// counting the orders still to fill as done; skipped when that leaves
+    /// less than a lot.
+    fn auto_trade(&mut self, strategy: &str, ticker: Ticker, signal: Signal, price: f64) {
+        let (Some(broker), Some(side)) = (&self.broker, signal.side()) else {
+            return;
+        };
+        let source = broker.name();
+        let state = broker.state();
+        let held: f64 = broker.positions().iter().filter(|p| p.ticker == ticker).map(|p| p.qty).sum();
+        // Open orders as last reported, and those sent since that the
+        // broker hasn't listed yet.
+        let open = state.open_orders().map(|o| (o.ticker.clone(), o.side, o.qty - o.filled_qty));
+        let unlisted = self
+            .auto_orders
+            .iter()
+            .filter(|(id, _)| !state.orders.iter().any(|o| o.known_as(id)))
+            .map(|(_, o)| (o.ticker.clone(), o.side, o.qty));
+        let (mut buying, mut selling) = (0.0, 0.0);
+        for (_, side, qty) in open.chain(unlisted).filter(|(t, _, _)| *t == ticker) {
+            match side {
+                Side::Buy => buying += qty,
+                Side::Sell => selling += qty,
+            }
+        }
+        let qty = auto_qty(&self.autotrade, side, held, buying, selling, price);
+        let qty = markets::rules_for(&ticker).whole_lots(qty);
+        let reason = format!("Auto {} {} signal", strategy, signal.as_str());
+        if qty < 1.0 {
+            let why = if side == Side::Buy { "position limit reached" } else { "no shares held" };
+            self.broker_message(format!("{} on {} skipped: {}", reason, ticker, why));
+            return;
+        }
+        let order = OrderRequest { side, qty, ticker: ticker.clone(), limit: None, trigger: None };
+        match self.place(order) {
+            Ok(placed) => {
+                self.broker_message(format!("{} on {}: {}", reason, ticker, placed.message));
+                self.auto_orders.insert(placed.id, AutoOrder { ticker, side, qty, reason });
+            }
+            Err(err) => {
+                let message = format!("{} on {} not placed: {}", reason, ticker, err);
+                self.broker_message(message.clone());
+                self.errors.push(AppError::Feed { source, message });
+            }
+        }
+    }
+
+    /// Sorts by `key`, flipping direction when the key is already active.
+    fn toggle_sort(&mut self, key: SortKey) {
+        if self.sort_key == key {
+            self.sort_desc = !self.sort_desc;
+        } else {
+            self.sort_key = key;
+            self.sort_desc = false;
+        }
+        self.sort_stocks(self.selected_row());
+    }
+
+    /// The selected row's ticker and file, to find it by once the list has
+    /// changed.
+    fn selected_row(&self) -> Option<(Ticker, SeriesKey)> {
+        self.stocks.get(self.selected).map(|s| (s.ticker.clone(), s.series.clone()))
+    }
+
+    /// Applies the current sort order, keeping `selected` under the cursor:
+    /// the row read from the same file, or else the ticker's first.
+    fn sort_stocks(&mut self, selected: Option<(Ticker, SeriesKey)>) {
+        let key = self.sort_key;
+        let bench = self.benchmark.return_3m;
+        let strength = |s: &StockInfo| s.relative_strength(bench).unwrap_or(f64::NEG_INFINITY);
+        self.stocks.sort_by(|a, b| {
+            let ord = match key {
+                SortKey::Ticker => a.ticker.cmp(&b.ticker),
+                SortKey::Price => a.price.total_cmp(&b.price),
+                SortKey::Change => a.change.total_cmp(&b.change),
+                SortKey::PctChange => a.pct_change.total_cmp(&b.pct_change),
+                SortKey::RelStrength => strength(a).total_cmp(&strength(b)),
+            };
+            if self.sort_desc { ord.reverse() } else { ord }
+        });
+        if let Some((ticker, series)) = selected
+            && let Some(i) = self
+                .stocks
+                .iter()
+                .position(|s| s.ticker == ticker && s.series == series)
+                .or_else(|| self.stocks.iter().position(|s| s.ticker == ticker))
+        {
+            self.selected = i;
+        }
+        self.selected = self.selected.min(self.stocks.len().saturating_sub(1));
+    }
+
+    /// Moves the focused panel's cursor/scroll position up (`-1`) or down (`+1`).
+    fn scroll_focused(&mut self, delta: isize) {
+        self.scroll(self.focus, delta);
+    }
+
+    fn scroll(&mut self, panel: Focus, delta: isize) {
+        match panel {
+            Focus::MLList => {
+                if !self.stocks.is_empty() {
+                    let len = self.stocks.len() as isize;
+                    self.selected = (self.selected as isize + delta).rem_euclid(len) as usize;
+                }
+            }
+            Focus::Accounts => {
+                let visible = self.visible_accounts().len();
+                if visible > 0 {
+                    let len = visible as isize;
+                    self.accounts_selected =
+                        (self.accounts_selected as isize + delta).rem_euclid(len) as usize;
+                }
+            }
+            Focus::Jobs => {
+                let max = self.jobs.jobs.len().saturating_sub(1);
+                self.jobs_selected = self.jobs_selected.saturating_add_signed(delta).min(max);
+            }
+            Focus::LiveTrades => self.scroll_trades_to(self.trades_scroll.saturating_add_signed(delta)),
+            Focus::News => {
+                let max = self.headlines().len().saturating_sub(1);
+                self.news_selected = self.news_selected.saturating_add_signed(delta).min(max);
+            }
+            Focus::Calendar => {
+                let max = self.upcoming_events().len().saturating_sub(1);
+                self.calendar_selected = self.calendar_selected.saturating_add_signed(delta).min(max);
+            }
+            Focus::Chart => {
+                // Up pans towards the latest bar, Down pans back in time, a
+                // twentieth of the series per step. At least two bars stay
+                // visible.
+                let len = self.chart_bars().len();
+                let step = (len / 20).max(1) as isize;
+                let max = len.saturating_sub(2);
+                self.chart_offset = self.chart_offset.saturating_add_signed(-delta * step).min(max);
+            }
+        }
+    }
+}
+
+/// Shares an `[autotrade]` order on `side` may be for, with `held` in the
+/// position and `buying` and `selling` on its orders still to fill. A buy
+/// is cut to what keeps the position within `max_shares` and, at `price`,
+/// `max_value`; a sell to the shares not already being sold.
+fn auto_qty(limits: &AutoTradeConfig, side: Side, held: f64, buying: f64, selling: f64, price: f64) -> f64 {
+    let mut qty = limits.qty as f64;
+    match side {
+        Side::Buy => {
+            let bound = held + buying;
+            qty = qty.min(limits.max_shares as f64 - bound);
+            if limits.max_value > 0.0 && price > 0.0 {
+                qty = qty.min(((limits.max_value - bound * price) / price).floor());
+            }
+        }
+        Side::Sell => qty = qty.min((held - selling).floor()),
+    }
+    qty
+}
+
+/// Parses search box input of the form `TICKER [INTERVAL] [RANGE]`, e.g.
+/// `aapl`, `aapl 5m` or `aapl 1h 1mo`. Intraday intervals default to a 5 day
+/// range (yfinance caps how far back minute bars go), daily to 1 year.
+/// `?QUERY` looks symbols up instead.
+fn parse_download_request(input: &str) -> Result<Option<Effect>, String> {
+    if let Some(query) = input.trim().strip_prefix('?') {
+        return match query.trim() {
+            "" => Err("Type ?QUERY to look up symbols".to_string()),
+            query => Ok(Some(Effect::SearchSymbols(query.to_string()))),
+        };
+    }
+    let mut parts = input.split_whitespace();
+    let Some(ticker) = parts.next() else {
+        return Ok(None);
+    };
+    let interval = match parts.next() {
+        Some(s) => Interval::parse(s).ok_or_else(|| format!("Unknown interval {} (1m/5m/15m/1h/1d)", s))?,
+        None => Interval::OneDay,
+    };
+    let range = match parts.next() {
+        Some(s) if data::RANGES.contains(&s) => s.to_string(),
+        Some(s) => return Err(format!("Unknown range {} ({})", s, data::RANGES.join("/"))),
+        None => interval.default_range().to_string(),
+    };
+    let ticker = Ticker::parse(ticker).map_err(|e| format!("Invalid ticker: {}", e))?;
+    Ok(Some(Effect::RunDownload { ticker, interval, range }))
+}
+
+// ============================
+// Events and Effects
+// ============================
+#[derive(Debug)]
+pub enum AppEvent {
+    Key(Key),
+    /// Status line produced by an effect, shown in the ML output box.
+    Output(String),
+    /// Fresh stock list, e.g. after a download finished.
+    StocksLoaded(Vec<StockInfo>),
+    /// Prediction log after a model run or a download resolved actuals.
+    HistoryLoaded(Vec<Prediction>),
+    /// A failure to record in the Errors view.
+    Error(AppError),
+    /// Left click in a panel, on the `item`th entry of its list if it has
+    /// one under the pointer (see `ui::mouse_event`).
+    Click { panel: Focus, item: Option<usize> },
+    /// Scroll wheel over a panel; positive is down.
+    Scroll { panel: Focus, delta: isize },
+    /// Outcome of the last download: why every data source failed, or
+    /// `None` once one worked again.
+    DataSource(Option<String>),
+    /// Once per frame, after input: starts any reloads that are due.
+    Tick,
+    /// What a retention dry run would drop, one line per file.
+    RetentionReport(Vec<String>),
+    /// The update check finished, with the newer release if there is one.
+    UpdateChecked(Option<Release>),
+    /// A background reload finished (see `refresh`).
+    Loaded { source: Source, result: Result<Loaded, AppError> },
+    /// Shares of a broker order filled, to book as a trade.
+    Fill(Fill),
+    /// The model registry after a model run trained a version.
+    ModelsLoaded(Registry),
+    /// A model run's logged prediction, after its `HistoryLoaded`, with
+    /// the closes it was made from.
+    Predicted { prediction: Prediction, closes: Vec<f64> },
+    /// Company names after a search or download learned new ones.
+    SymbolsLoaded(SymbolBook),
+}
+
+/// The chart benchmark over the shown bars, and each side's return across
+/// them in percent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overlay {
+    pub ticker: Ticker,
+    // Benchmark closes at each bar's index, rebased to the selected ticker's
+    // close where the benchmark's history starts.
+    pub points: Vec<(f64, f64)>,
+    pub own: f64,
+    pub benchmark: f64,
+}
+
+/// Side effects requested by the reducer, executed by `effects::run`.
+#[derive(Debug, PartialEq)]
+pub enum Effect {
+    RunDownload { ticker: Ticker, interval: Interval, range: String },
+    FillGaps { ticker: Ticker, interval: Interval, gaps: Vec<(chrono::NaiveDate, chrono::NaiveDate)> },
+    /// Download of `start..=end` (up to the latest bar if `end` is `None`),
+    /// merged into the ticker's existing CSV.
+    DownloadRange { ticker: Ticker, interval: Interval, start: chrono::NaiveDate, end: Option<chrono::NaiveDate> },
+    /// Each ticker's default range at its interval, through the download
+    /// queue, see `downloads`.
+    DownloadAll(Vec<(Ticker, Interval)>),
+    /// Symbols matching a query, from `[data] provider`.
+    SearchSymbols(String),
+    /// The pipeline's stages before the model, see `ml::pipeline`.
+    Preprocess { ticker: Ticker, pipeline: PipelineConfig },
+    /// With `train`, a new model version is trained rather than the active
+    /// one used.
+    RunMl { ticker: Ticker, pipeline: PipelineConfig, train: bool },
+    CancelJob(u64),
+    SaveAccounts(Vec<AccountSummary>),
+    /// Append a trade to the trade history, which the account balances are
+    /// folded from; account_summary.csv is saved by the reload that reads
+    /// it back.
+    RecordTrade(TradeRecord),
+    /// Append both legs of a transfer.
+    RecordTransfer([TradeRecord; 2]),
+    SaveLayout(LayoutConfig),
+    /// Apply the retention policy as a background job, or with `dry_run`
+    /// only report what it would delete.
+    Maintenance { retention: RetentionConfig, dry_run: bool },
+    /// Dry run of the retention policy whose findings open full screen.
+    RetentionReport(RetentionConfig),
+    /// Look for a newer release in the background.
+    CheckUpdate,
+    /// Save a chart image of `ticker` under `export::REPORTS_DIR`.
+    WriteChart { ticker: Ticker, at: chrono::NaiveDateTime, format: chart_image::Format, image: ChartImage },
+    /// Save a rendered portfolio report under `export::REPORTS_DIR`.
+    WriteReport { at: chrono::NaiveDateTime, format: export::Format, text: String },
+    /// Save the trading game to `game::GAME_PATH`.
+    SaveGame(Game),
+    /// Save the model registry to `registry::REGISTRY_PATH`.
+    SaveModels(Registry),
+    /// Save the chart drawings to `drawings::DRAWINGS_PATH`.
+    SaveDrawings(Drawings),
+    /// Add a rule to the ticker's `[daemon.alerts]` in stm.toml.
+    AddAlert { ticker: Ticker, rule: String },
+    /// Append streamed minute bars to the CSVs of tickers stored at 1m.
+    AppendBars(Vec<(Ticker, MinuteBar)>),
+    /// Reload a panel's data in the background.
+    Refresh(Request),
+}
+
+impl App {
+    pub fn handle_event(&mut self, event: AppEvent) -> Vec<Effect> {
+        match event {
+            AppEvent::Key(key) => {
+                let effects = self.handle_key(key);
+                self.runnable(effects)
+            }
+            AppEvent::Output(line) => {
+                self.ml_output = line;
+                Vec::new()
+            }
+            AppEvent::RetentionReport(lines) => {
+                self.retention_report = Some(lines);
+                Vec::new()
+            }
+            AppEvent::UpdateChecked(release) => {
+                self.update_checked = true;
+                self.release = release;
+                Vec::new()
+            }
+            AppEvent::StocksLoaded(stocks) => {
+                self.refresh_stocks(stocks);
+                Vec::new()
+            }
+            AppEvent::Tick => self.auto_refresh(),
+            AppEvent::Loaded { source, result } => self.loaded(source, result).into_iter().collect(),
+            AppEvent::Fill(fill) => self.book_fill(fill),
+            AppEvent::ModelsLoaded(registry) => {
+                if let Some(view) = &mut self.models_view {
+                    if let Some(active) = registry.active(&view.ticker) {
+                        view.message = format!("Trained v{}; {} predicts with it", active.version, view.ticker);
+                    }
+                    view.registry = registry;
+                    view.selected = 0;
+                }
+                Vec::new()
+            }
+            AppEvent::HistoryLoaded(predictions) => {
+                // The whole log, so there's nothing left to read.
+                self.history_read = true;
+                self.ml_history = predictions;
+                self.refresh_signals();
+                Vec::new()
+            }
+            AppEvent::SymbolsLoaded(book) => {
+                self.symbols = book;
+                Vec::new()
+            }
+            AppEvent::Predicted { prediction, closes } => {
+                self.suggest(&prediction, &closes);
+                Vec::new()
+            }
+            AppEvent::Error(err) => {
+                self.errors.push(err);
+                Vec::new()
+            }
+            AppEvent::Click { panel, item } => {
+                self.click(panel, item);
+                Vec::new()
+            }
+            AppEvent::Scroll { panel, delta } => {
+                if !self.mouse_blocked() {
+                    match (panel, &self.ml_mode) {
+                        (Focus::MLList, MLMode::Search | MLMode::Alert | MLMode::JumpToDate) => {}
+                        (Focus::MLList, MLMode::Filter) => {
+                            let max = self.visible_stocks().len().saturating_sub(1);
+                            self.filter_selected = self.filter_selected.saturating_add_signed(delta).min(max);
+                        }
+                        _ => self.scroll(panel, delta),
+                    }
+                }
+                Vec::new()
+            }
+            AppEvent::DataSource(unavailable) => {
+                self.source_unavailable = unavailable;
+                Vec::new()
+            }
+        }
+    }
+
+    /// Full-screen views and the popups own the screen; clicks and
+    /// wheel events behind them are dropped.
+    /// What the hints bar lists keys for.
+    pub fn hint_context(&self) -> Context {
+        if self.account_form.is_some() || self.trade_form.is_some() || self.range_picker.is_some() {
+            Context::Form
+        } else if !matches!(self.ml_mode, MLMode::List) {
+            Context::Typing(self.ml_mode)
+        } else if self.crosshair.is_some() && self.focus == Focus::Chart {
+            Context::Crosshair
+        } else {
+            Context::Panel(self.focus)
+        }
+    }
+
+    fn mouse_blocked(&self) -> bool {
+        self.health.is_some()
+            || self.show_instructions
+            || self.show_errors
+            || self.show_usage
+            || self.alert_preview.is_some()
+            || self.returns_view.is_some()
+            || self.analytics.is_some()
+            || self.dividends.is_some()
+            || self.overview.is_some()
+            || self.time_travel.is_some()
+            || self.game_view.is_some()
+            || self.broker_view.is_some()
+            || self.models_view.is_some()
+            || self.retention_report.is_some()
+            || self.show_release_notes
+            || self.changelog.is_some()
+            || self.account_form.is_some()
+            || self.trade_form.is_some()
+            || self.range_picker.is_some()
+            || self.menu.is_some()
+    }
+
+    /// Focuses the clicked panel and selects the clicked entry. Picking a
+    /// trade also moves the Accounts cursor to the trade's account.
+    fn click(&mut self, panel: Focus, item: Option<usize>) {
+        if self.mouse_blocked() {
+            return;
+        }
+        self.focus = panel;
+        let Some(item) = item else {
+            return;
+        };
+        match panel {
+            Focus::MLList => match self.ml_mode {
+                MLMode::Filter => self.filter_selected = item,
+                _ => self.selected = item,
+            },
+            Focus::LiveTrades => {
+                self.trades_selected = Some(item);
+                let name = &self.trades[item].name;
+                if let Some(pos) = self.visible_accounts().iter().position(|&i| &self.accounts[i].name == name) {
+                    self.accounts_selected = pos;
+                }
+            }
+            Focus::Accounts => self.accounts_selected = item,
+            Focus::Jobs => self.jobs_selected = item,
+            Focus::News => self.news_selected = item,
+            Focus::Calendar => self.calendar_selected = item,
+            Focus::Chart => {}
+        }
+    }
+
+    fn handle_form_key(&mut self, code: KeyCode) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        let balance_input = self.balance_input();
+        let Some(form) = &mut self.account_form else {
+            return effects;
+        };
+        if form.active == BALANCE_FIELD && balance_input.handle_key(&mut form.fields[BALANCE_FIELD], code) {
+            return effects;
+        }
+        match code {
+            KeyCode::Esc => self.account_form = None,
+            KeyCode::Tab | KeyCode::Down => form.active = (form.active + 1) % ACCOUNT_FIELDS.len(),
+            KeyCode::BackTab | KeyCode::Up => {
+                form.active = (form.active + ACCOUNT_FIELDS.len() - 1) % ACCOUNT_FIELDS.len();
+            }
+            KeyCode::Char(c) => form.fields[form.active].push(c),
+            KeyCode::Backspace => {
+                form.fields[form.active].pop();
+            }
+            KeyCode::Enter => match self.submit_account_form() {
+                Ok(effect) => effects.push(effect),
+                Err(msg) => {
+                    if let Some(form) = &mut self.account_form {
+                        form.error = Some(msg);
+                    }
+                }
+            },
+            _ => {}
+        }
+        effects
+    }
+
+    fn handle_trade_form_key(&mut self, code: KeyCode) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        let amount_input = self.amount_input();
+        let Some(form) = &mut self.trade_form else {
+            return effects;
+        };
+        if form.active == AMOUNT_FIELD && amount_input.handle_key(&mut form.fields[AMOUNT_FIELD], code) {
+            return effects;
+        }
+        match code {
+            KeyCode::Esc => self.trade_form = None,
+            KeyCode::Tab | KeyCode::Down => form.active = (form.active + 1) % TRADE_FIELDS.len(),
+            KeyCode::BackTab | KeyCode::Up => {
+                form.active = (form.active + TRADE_FIELDS.len() - 1) % TRADE_FIELDS.len();
+            }
+            KeyCode::Char(c) => form.fields[form.active].push(c),
+            KeyCode::Backspace => {
+                form.fields[form.active].pop();
+            }
+            KeyCode::Enter => match self.submit_trade_form() {
+                Ok(effect) => {
+                    // The new row is picked up by reading what was appended
+                    // to the history, once the trade is written.
+                    effects.push(effect);
+                    effects.extend(self.request(self.request_for(Source::Trades), false));
+                }
+                Err(msg) => {
+                    if let Some(form) = &mut self.trade_form {
+                        form.error = Some(msg);
+                    }
+                }
+            },
+            _ => {}
+        }
+        effects
+    }
+
+    /// Every key but Esc and the game's own shortcut goes to the order line,
+    /// so that names and tickers can be typed.
+    fn handle_game_key(&mut self, key: Key) -> Vec<Effect> {
+        let marks = self.marks();
+        let spread = self.game_config.house_spread_pct;
+        let close = self.keymap.key(Action::TradingGame);
+        let Some(view) = &mut self.game_view else {
+            return Vec::new();
+        };
+        match key.code {
+            KeyCode::Esc => self.game_view = None,
+            _ if key == close && view.input.is_empty() => self.game_view = None,
+            KeyCode::Char(c) if !key.ctrl => view.input.push(c),
+            KeyCode::Backspace => {
+                view.input.pop();
+            }
+            KeyCode::Enter => {
+                let now = chrono::Local::now().naive_local();
+                let outcome = Instruction::parse(&view.input).and_then(|instruction| match instruction {
+                    Instruction::Place(request) => {
+                        let placed = view.game.place(&request, &marks, spread, now)?;
+                        view.ticker = Some(request.ticker.clone());
+                        Ok(game::describe(&request, &placed))
+                    }
+                    Instruction::Cancel { player, id } => {
+                        let order = view.game.cancel(&player, id)?;
+                        Ok(format!("Cancelled #{}: {} {} {} at {:.2}", id, player, order.qty, order.ticker, order.limit))
+                    }
+                });
+                match outcome {
+                    Ok(message) => {
+                        view.message = message;
+                        view.input.clear();
+                        return vec![Effect::SaveGame(view.game.clone())];
+                    }
+                    Err(message) => view.message = message,
+                }
+            }
+            _ => {}
+        }
+        Vec::new()
+    }
+
+    /// Opens the saved game once it's read, or starts one, and seats any
+    /// account not yet playing.
+    fn open_game(&mut self, saved: Option<Game>) {
+        let mut game = saved.unwrap_or_else(|| Game::new(self.game_config.starting_cash));
+        game.join(self.accounts.iter().filter(|a| !a.archived).map(|a| &a.name));
+        if game.players.is_empty() {
+            self.ml_output = "Add an account to play the trading game".to_string();
+            return;
+        }
+        let message = format!("{} players; type NAME buy|sell QTY TICKER [PRICE], or NAME cancel ID", game.players.len());
+        self.game_view = Some(GameView { game, input: String::new(), message, ticker: self.selected_ticker().cloned() });
+    }
+
+    fn open_broker(&mut self) {
+        let message =
+            "Type buy|sell QTY [TICKER] [PRICE | stop PRICE | tp PRICE], or cancel ID; the ticker defaults to the selected one"
+                .to_string();
+        self.broker_view = Some(BrokerView { input: String::new(), message, selected: 0 });
+    }
+
+    /// Sizes a position for `prediction`, made from `closes`, against the
+    /// broker's account.
+    fn suggest(&mut self, prediction: &Prediction, closes: &[f64]) {
+        let accuracy = history::accuracy(&self.ml_history).into_iter().find(|a| a.ticker == prediction.ticker);
+        let balances = self.broker.as_deref().and_then(|b| b.account());
+        let held = self.broker.as_deref().map_or(0.0, |b| {
+            b.positions().iter().filter(|p| p.ticker == prediction.ticker).map(|p| p.qty).sum()
+        });
+        self.suggestion = Some(sizing::suggest(prediction, closes, accuracy.as_ref(), balances.as_ref(), held, &self.sizing));
+    }
+
+    /// Opens the broker view with the suggested order typed in.
+    fn prefill_suggestion(&mut self) {
+        let Some(suggestion) = &self.suggestion else {
+            self.ml_output = "No suggestion yet; run the model on a stock first".to_string();
+            return;
+        };
+        let Some(line) = suggestion.order_line() else {
+            self.ml_output = format!("Suggested for {}: {}", suggestion.ticker, suggestion);
+            return;
+        };
+        let message = format!("Suggested: {}; Enter sends it, edit it first if you like", suggestion);
+        self.open_broker();
+        if let Some(view) = &mut self.broker_view {
+            view.input = line;
+            view.message = message;
+        }
+    }
+
+    /// Shows the crosshair at the latest visible close, or hides it.
+    fn toggle_crosshair(&mut self) {
+        if self.crosshair.take().is_some() {
+            return;
+        }
+        let bars = self.visible_bars();
+        match (&self.chart.ticker, bars.last()) {
+            (Some(_), Some(last)) => {
+                self.crosshair = Some(Crosshair { bar: bars.len() - 1, price: last.close, anchor: None });
+                self.ml_output = "Arrows move the crosshair; Enter places a level there, t starts a trendline".to_string();
+            }
+            _ => self.ml_output = "Select a stock with price history to draw on its chart".to_string(),
+        }
+    }
+
+    /// Moves the crosshair, or places, removes or alerts on the chart's
+    /// drawings with it. `None` for keys it leaves to the dashboard.
+    fn handle_crosshair_key(&mut self, key: Key) -> Option<Vec<Effect>> {
+        let ticker = self.chart.ticker.clone()?;
+        let mut cross = self.crosshair.clone()?;
+        let bars = self.visible_bars();
+        let last = bars.len().checked_sub(1)?;
+        cross.bar = cross.bar.min(last);
+        let point = Point { at: bars[cross.bar].at, price: cross.price };
+        let (lo, hi) = bars.iter().fold((f64::MAX, f64::MIN), |(lo, hi), b| (lo.min(b.close), hi.max(b.close)));
+        let rules = markets::rules_for(&ticker);
+        let step = ((hi - lo) / CROSSHAIR_STEPS).max(rules.tick);
+        let mut effects = Vec::new();
+        match key.code {
+            KeyCode::Left => cross.bar = cross.bar.saturating_sub(1),
+            KeyCode::Right => cross.bar = (cross.bar + 1).min(last),
+            KeyCode::Up => cross.price += step,
+            KeyCode::Down => cross.price = (cross.price - step).max(0.0),
+            KeyCode::Enter => {
+                let level = Drawing::Level { price: (cross.price / rules.tick).round() * rules.tick };
+                self.drawings.add(&ticker, level);
+                self.ml_output = format!("Drew a {} on {}", level.describe(&ticker), ticker);
+                effects.push(Effect::SaveDrawings(self.drawings.clone()));
+            }
+            KeyCode::Char('t') => match cross.anchor.take() {
+                None => {
+                    cross.anchor = Some(point);
+                    self.ml_output = "Move to the trendline's other end and press t again; Esc cancels it".to_string();
+                }
+                Some(anchor) if anchor.at == point.at => {
+                    cross.anchor = Some(anchor);
+                    self.ml_output = "A trendline's ends go on different bars".to_string();
+                }
+                Some(anchor) => {
+                    let trend = Drawing::trend(anchor, point);
+                    self.drawings.add(&ticker, trend);
+                    self.ml_output = format!("Drew a {} on {}", trend.describe(&ticker), ticker);
+                    effects.push(Effect::SaveDrawings(self.drawings.clone()));
+                }
+            },
+            KeyCode::Delete | KeyCode::Backspace => match self.drawings.nearest(&ticker, point, |_| true) {
+                Some(i) => {
+                    let removed = self.drawings.remove(&ticker, i);
+                    self.ml_output = format!("Removed the {} from {}", removed.describe(&ticker), ticker);
+                    effects.push(Effect::SaveDrawings(self.drawings.clone()));
+                }
+                None => self.ml_output = format!("No line on {} at the crosshair", ticker),
+            },
+            KeyCode::Char('a') => {
+                let levels = |d: &Drawing| matches!(d, Drawing::Level { .. });
+                let close = self.chart.bars.last().map_or(cross.price, |b| b.close);
+                match self.drawings.nearest(&ticker, point, levels).and_then(|i| self.drawings.get(&ticker)[i].alert(&ticker, close)) {
+                    Some(rule) => effects.push(Effect::AddAlert { ticker, rule }),
+                    None => self.ml_output = "Place a level first (Enter); trendlines can't become alerts".to_string(),
+                }
+            }
+            KeyCode::Esc if cross.anchor.is_some() => {
+                cross.anchor = None;
+                self.ml_output = "Trendline cancelled".to_string();
+            }
+            KeyCode::Esc => {
+                self.crosshair = None;
+                return Some(effects);
+            }
+            _ => return None,
+        }
+        self.crosshair = Some(cross);
+        Some(effects)
+    }
+
+    /// Opens the selected ticker's versions once the registry is read.
+    fn open_models(&mut self, registry: Registry) {
+        let Some(ticker) = self.selected_ticker().cloned() else {
+            self.ml_output = "Select a stock to see its model versions".to_string();
+            return;
+        };
+        let message = match registry.active(&ticker) {
+            Some(active) => format!("Predicting with v{}; Enter uses the selected version, t trains a new one", active.version),
+            None => "No versions yet; t trains one, as does the next model run".to_string(),
+        };
+        self.models_view = Some(ModelsView { ticker, registry, selected: 0, message });
+    }
+
+    fn handle_models_key(&mut self, key: Key) -> Vec<Effect> {
+        let close = self.keymap.key(Action::Models);
+        let Some(view) = &mut self.models_view else {
+            return Vec::new();
+        };
+        let rows = view.registry.versions(&view.ticker).len();
+        match key.code {
+            KeyCode::Esc => self.models_view = None,
+            _ if key == close => self.models_view = None,
+            _ if key == self.keymap.key(Action::Quit) => self.should_quit = true,
+            KeyCode::Down => view.selected = (view.selected + 1).min(rows.saturating_sub(1)),
+            KeyCode::Up => view.selected = view.selected.saturating_sub(1),
+            KeyCode::Enter => {
+                let Some(version) = view.selected_version().map(|v| v.version) else {
+                    return Vec::new();
+                };
+                let ticker = view.ticker.clone();
+                if let Err(message) = view.registry.set_active(&ticker, version) {
+                    view.message = message;
+                    return Vec::new();
+                }
+                view.message = format!("{} predicts with v{} from now on", ticker, version);
+                return vec![Effect::SaveModels(view.registry.clone())];
+            }
+            KeyCode::Char('t') => {
+                view.message = format!("Training a new version of {}…", view.ticker);
+                return vec![Effect::RunMl { ticker: view.ticker.clone(), pipeline: self.pipeline.clone(), train: true }];
+            }
+            _ => {}
+        }
+        Vec::new()
+    }
+
+    /// Every key but Esc, the view's own shortcut, Up/Down and Delete goes
+    /// to the order line, as in the game. Up/Down pick an open order and
+    /// Delete cancels it.
+    fn handle_broker_key(&mut self, key: Key) -> Vec<Effect> {
+        let close = self.keymap.key(Action::Broker);
+        let selected = self.selected_ticker().cloned();
+        let open: Vec<String> =
+            self.broker.as_deref().map(|b| b.state().open_orders().map(|o| o.id.clone()).collect()).unwrap_or_default();
+        let Some(view) = &mut self.broker_view else {
+            return Vec::new();
+        };
+        view.selected = view.selected.min(open.len().saturating_sub(1));
+        match key.code {
+            KeyCode::Esc => self.broker_view = None,
+            _ if key == close && view.input.is_empty() => self.broker_view = None,
+            KeyCode::Down => view.selected = (view.selected + 1).min(open.len().saturating_sub(1)),
+            KeyCode::Up => view.selected = view.selected.saturating_sub(1),
+            KeyCode::Delete => {
+                let outcome = match open.get(view.selected) {
+                    Some(id) => self.route(broker::Instruction::Cancel(id.clone())),
+                    None => Err("No open orders to cancel".to_string()),
+                };
+                if let Some(view) = &mut self.broker_view {
+                    view.message = outcome.unwrap_or_else(|e| e);
+                }
+            }
+            KeyCode::Char(c) if !key.ctrl => view.input.push(c),
+            KeyCode::Backspace => {
+                view.input.pop();
+            }
+            KeyCode::Enter if !view.input.trim().is_empty() => {
+                let line = std::mem::take(&mut view.input);
+                let outcome = broker::Instruction::parse(&line, selected.as_ref()).and_then(|i| self.route(i));
+                if let Some(view) = &mut self.broker_view {
+                    match outcome {
+                        Ok(message) => view.message = message,
+                        Err(message) => {
+                            view.message = message;
+                            view.input = line;
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        Vec::new()
+    }
+
+    /// Hands a typed order or cancel to the broker.
+    fn route(&mut self, instruction: broker::Instruction) -> Result<String, String> {
+        let Some(broker) = &mut self.broker else {
+            return Err("The broker needs a build with --features alpaca".to_string());
+        };
+        match instruction {
+            broker::Instruction::Place(order) => self.place(order).map(|placed| placed.message),
+            broker::Instruction::Cancel(prefix) => broker.cancel(&prefix),
+        }
+    }
+
+    /// Sends `order` to the broker once its market's rules allow it.
+    fn place(&mut self, order: OrderRequest) -> Result<Submitted, String> {
+        let Some(broker) = &mut self.broker else {
+            return Err("The broker needs a build with --features alpaca".to_string());
+        };
+        markets::rules_for(&order.ticker).check(&order)?;
+        broker.submit_order(order)
+    }
+
+    /// Books a broker fill to the broker's account as a trade. Without an
+    /// account it's only reported.
+    fn book_fill(&mut self, fill: Fill) -> Vec<Effect> {
+        let filled = format!("Filled {} {} {} at {:.2}", fill.side.as_str(), fill.qty, fill.ticker, fill.price);
+        let Some(broker) = &self.broker else {
+            return Vec::new();
+        };
+        let (source, account) = (broker.name(), broker.booking_account().trim().to_string());
+        // The auto order it's for, kept until the order has closed. A
+        // broker that lists the order by an id of its own still has the
+        // one it was sent with.
+        let order = broker.state().orders.iter().find(|o| o.id == fill.order_id);
+        let auto_id = order
+            .and_then(|o| o.client_id.clone())
+            .filter(|id| self.auto_orders.contains_key(id))
+            .unwrap_or_else(|| fill.order_id.clone());
+        let reason = self.auto_orders.get(&auto_id).map(|o| o.reason.clone());
+        if !order.is_some_and(|o| o.is_open()) {
+            self.auto_orders.remove(&auto_id);
+        }
+        if account.is_empty() {
+            self.broker_message(filled);
+            return Vec::new();
+        }
+        let now = chrono::Local::now().naive_local().trunc_subsecs(0);
+        let fee = self.fees_config.charge(fill.qty * fill.price);
+        let booked = AccountId::parse(&account)
+            .map_err(|e| format!("Invalid {} account: {}", source, e))
+            .and_then(|name| accounts::process_trade(&mut self.accounts, &name, fill.amount() - fee, now));
+        match booked {
+            Ok(mut trade) => {
+                trade.ticker = Some(fill.ticker.clone());
+                trade.fee = Some(fee).filter(|&fee| fee > 0.0);
+                trade.note = Some(fill.note(source));
+                trade.qty = Some(match fill.side {
+                    Side::Buy => fill.qty,
+                    Side::Sell => -fill.qty,
+                });
+                if let Some(reason) = reason {
+                    trade.note = Some(format!("{}: {}", reason, fill.note(source)));
+                }
+                match trade.fee {
+                    Some(fee) => self.broker_message(format!("{}, booked to {} less a {:.2} fee", filled, trade.name, fee)),
+                    None => self.broker_message(format!("{}, booked to {}", filled, trade.name)),
+                }
+                let mut effects = vec![Effect::RecordTrade(trade)];
+                effects.extend(self.request(self.request_for(Source::Trades), false));
+                effects
+            }
+            Err(e) => {
+                let message = format!("{} but not booked: {}", filled, e);
+                self.broker_message(message.clone());
+                self.errors.push(AppError::Feed { source, message });
+                Vec::new()
+            }
+        }
+    }
+
+    /// Tickers of the ML list, in its order; once each when one is held in
+    /// two places.
+    pub fn stock_tickers(&self) -> Vec<Ticker> {
+        let mut seen = HashSet::new();
+        self.stocks.iter().filter(|s| seen.insert(&s.ticker)).map(|s| s.ticker.clone()).collect()
+    }
+
+    /// Latest price of each loaded symbol, for the game's house quotes and
+    /// leaderboard.
+    pub fn marks(&self) -> HashMap<Ticker, f64> {
+        self.stocks.iter().filter(|s| s.error.is_none() && s.price > 0.0).map(|s| (s.ticker.clone(), s.price)).collect()
+    }
+
+    fn handle_key(&mut self, key: Key) -> Vec<Effect> {
+        if key == keymap::SUSPEND {
+            self.suspend_requested = true;
+            return Vec::new();
+        }
+        let code = key.code;
+        if self.health.is_some() {
+            match code {
+                KeyCode::Enter | KeyCode::Esc => self.health = None,
+                _ if key == self.keymap.key(Action::Quit) => self.should_quit = true,
+                _ => {}
+            }
+            return Vec::new();
+        }
+        if self.account_form.is_some() {
+            return self.handle_form_key(code);
+        }
+        if self.trade_form.is_some() {
+            return self.handle_trade_form_key(code);
+        }
+        if self.range_picker.is_some() {
+            return self.handle_picker_key(code);
+        }
+        if self.game_view.is_some() {
+            return self.handle_game_key(key);
+        }
+        if self.broker_view.is_some() {
+            return self.handle_broker_key(key);
+        }
+        if self.models_view.is_some() {
+            return self.handle_models_key(key);
+        }
+        if self.time_travel.is_some() {
+            let tickers = self.stock_tickers();
+            let Some(view) = &mut self.time_travel else {
+                return Vec::new();
+            };
+            if key == self.keymap.key(Action::TimeTravel) && view.input.is_empty() {
+                self.time_travel = None;
+                return Vec::new();
+            }
+            match view.handle_key(code) {
+                TravelStep::Stay => {}
+                TravelStep::Close => self.time_travel = None,
+                TravelStep::Go(date) => view.snapshot = Snapshot::at(date, &self.accounts, &self.trades, &tickers),
+            }
+            return Vec::new();
+        }
+        let quit = self.keymap.key(Action::Quit);
+        if self.show_usage {
+            if code == KeyCode::Esc || key == self.keymap.key(Action::ShowUsage) {
+                self.show_usage = false;
+            } else if key == quit {
+                self.should_quit = true;
+            }
+            return Vec::new();
+        }
+        if self.returns_view.is_some() {
+            if code == KeyCode::Esc || key == self.keymap.key(Action::ShowReturns) {
+                self.returns_view = None;
+            } else if key == quit {
+                self.should_quit = true;
+            }
+            return Vec::new();
+        }
+        if self.analytics.is_some() {
+            if code == KeyCode::Esc || key == self.keymap.key(Action::Analytics) {
+                self.analytics = None;
+            } else if key == quit {
+                self.should_quit = true;
+            }
+            return Vec::new();
+        }
+        if let Some(view) = &mut self.dividends {
+            match code {
+                KeyCode::Up => view.selected = view.selected.saturating_sub(1),
+                KeyCode::Down => view.selected = (view.selected + 1).min(view.incomes.len().saturating_sub(1)),
+                KeyCode::Enter => {
+                    let ticker = view.selected().map(|i| i.ticker.clone());
+                    self.dividends = None;
+                    self.open_dividend_form(ticker);
+                }
+                KeyCode::Esc => self.dividends = None,
+                _ if key == self.keymap.key(Action::Dividends) => self.dividends = None,
+                _ if key == quit => self.should_quit = true,
+                _ => {}
+            }
+            return Vec::new();
+        }
+        if let Some(view) = &mut self.overview {
+            match code {
+                KeyCode::Left => view.step(-1, 0),
+                KeyCode::Right => view.step(1, 0),
+                KeyCode::Up => view.step(0, -1),
+                KeyCode::Down => view.step(0, 1),
+                KeyCode::Enter => {
+                    let ticker = view.selected().map(|t| t.ticker.clone());
+                    self.overview = None;
+                    if let Some(i) = ticker.and_then(|t| self.stocks.iter().position(|s| s.ticker == t)) {
+                        self.selected = i;
+                        self.focus = Focus::Chart;
+                    }
+                }
+                KeyCode::Esc => self.overview = None,
+                _ if key == self.keymap.key(Action::MarketOverview) => self.overview = None,
+                _ if key == quit => self.should_quit = true,
+                _ => {}
+            }
+            return Vec::new();
+        }
+        if self.show_release_notes {
+            if code == KeyCode::Esc || key == self.keymap.key(Action::ReleaseNotes) {
+                self.show_release_notes = false;
+            } else if key == quit {
+                self.should_quit = true;
+            }
+            return Vec::new();
+        }
+        if self.changelog.is_some() {
+            if matches!(code, KeyCode::Esc | KeyCode::Enter) || key == self.keymap.key(Action::Changelog) {
+                self.changelog = None;
+            } else if key == quit {
+                self.should_quit = true;
+            }
+            return Vec::new();
+        }
+        if self.retention_report.is_some() {
+            if code == KeyCode::Esc || key == self.keymap.key(Action::RetentionReport) {
+                self.retention_report = None;
+            } else if key == quit {
+                self.should_quit = true;
+            }
+            return Vec::new();
+        }
+        if let Some(preview) = &mut self.alert_preview {
+            match code {
+                KeyCode::Esc => self.alert_preview = None,
+                KeyCode::Down => preview.scroll = (preview.scroll + 1).min(preview.triggers.len().saturating_sub(1)),
+                KeyCode::Up => preview.scroll = preview.scroll.saturating_sub(1),
+                _ if key == self.keymap.key(Action::AlertPreview) => self.alert_preview = None,
+                _ if key == self.keymap.key(Action::DateRange) => {
+                    if let Some(latest) = preview.latest() {
+                        let picker = RangePicker::new(format!("Backtest {}", preview.ticker), latest, preview.range);
+                        self.range_picker = Some((RangeTarget::Backtest, picker));
+                    }
+                }
+                _ if key == quit => self.should_quit = true,
+                _ => {}
+            }
+            return Vec::new();
+        }
+        if self.show_errors {
+            if code == KeyCode::Esc || key == self.keymap.key(Action::ShowErrors) {
+                self.show_errors = false;
+            } else if key == Key::plain(KeyCode::Char('c')) {
+                self.errors.clear();
+            } else if key == quit {
+                self.should_quit = true;
+            }
+            return Vec::new();
+        }
+        let mut effects = Vec::new();
+        if let Some(menu) = &mut self.menu {
+            let step = if key == self.keymap.key(Action::Menu) { MenuStep::Close } else { menu.handle_key(code) };
+            match step {
+                MenuStep::Stay => {}
+                MenuStep::Close => self.menu = None,
+                MenuStep::Run(action) => {
+                    self.menu = None;
+                    if !self.run_action(action, &mut effects) {
+                        self.ml_output = format!("{} doesn't apply here: {}", menu::label(action), action.description());
+                    }
+                    self.count_usage(&effects);
+                }
+            }
+            return effects;
+        }
+        // While a text box is open only focus changes, the menu and Ctrl
+        // chords act as shortcuts; every other key goes to the box.
+        let typing = self.is_typing();
+        if self.crosshair.is_some()
+            && self.focus == Focus::Chart
+            && !typing
+            && !key.ctrl
+            && let Some(effects) = self.handle_crosshair_key(key)
+        {
+            return effects;
+        }
+        let actions: Vec<Action> = self
+            .keymap
+            .actions_for(key)
+            .filter(|a| {
+                !typing
+                    || key.ctrl
+                    || matches!(a, Action::FocusNext | Action::FocusPrev)
+                    || (*a == Action::Menu && !matches!(key.code, KeyCode::Char(_)))
+            })
+            .collect();
+        for action in actions {
+            if self.run_action(action, &mut effects) {
+                self.count_usage(&effects);
+                return effects;
+            }
+        }
+        if key.ctrl {
+            return effects;
+        }
+        match code {
+            KeyCode::Esc => {
+                self.clear_filter();
+                self.search_input.clear();
+                self.alert_input.clear();
+                self.jump_input.clear();
+                self.show_instructions = false;
+            }
+            KeyCode::Enter => match self.ml_mode {
+                MLMode::Filter => self.accept_filter(),
+                MLMode::Alert => self.open_alert_preview(),
+                MLMode::JumpToDate => self.jump_to_date(),
+                MLMode::Search => {
+                    // In search mode, download stock data.
+                    match parse_download_request(&self.search_input) {
+                        Ok(Some(effect)) => {
+                            effects.push(effect);
+                            self.ml_mode = MLMode::List;
+                            self.search_input.clear();
+                        }
+                        Ok(None) => {}
+                        Err(msg) => self.ml_output = msg,
+                    }
+                }
+                MLMode::List => {
+                    // In list mode, run the pipeline on the selected stock.
+                    if let Some(stock) = self.stocks.get(self.selected) {
+                        effects.push(Effect::RunMl { ticker: stock.ticker.clone(), pipeline: self.pipeline.clone(), train: false });
+                    }
+                }
+            },
+            KeyCode::Down => match self.ml_mode {
+                MLMode::List => self.scroll_focused(1),
+                MLMode::Filter => {
+                    let matches = self.visible_stocks().len();
+                    if self.filter_selected + 1 < matches {
+                        self.filter_selected += 1;
+                    }
+                }
+                MLMode::Search | MLMode::Alert | MLMode::JumpToDate => {}
+            },
+            KeyCode::Up => match self.ml_mode {
+                MLMode::List => self.scroll_focused(-1),
+                MLMode::Filter => self.filter_selected = self.filter_selected.saturating_sub(1),
+                MLMode::Search | MLMode::Alert | MLMode::JumpToDate => {}
+            },
+            KeyCode::Char(c) => match self.ml_mode {
+                MLMode::Search => self.search_input.push(c),
+                MLMode::Alert => self.alert_input.push(c),
+                MLMode::JumpToDate => self.jump_input.push(c),
+                MLMode::Filter => {
+                    self.filter_input.push(c);
+                    self.filter_selected = 0;
+                }
+                MLMode::List => {}
+            },
+            KeyCode::Backspace => match self.ml_mode {
+                MLMode::Search => {
+                    self.search_input.pop();
+                }
+                MLMode::Alert => {
+                    self.alert_input.pop();
+                }
+                MLMode::JumpToDate => {
+                    self.jump_input.pop();
+                }
+                MLMode::Filter => {
+                    self.filter_input.pop();
+                    self.filter_selected = 0;
+                }
+                MLMode::List => {}
+            },
+            _ => {}
+        }
+        self.count_usage(&effects);
+        effects
+    }
+
+    /// Performs `action` if it applies in the current context (some only
+    /// work with a particular panel focused). Returns false if it doesn't.
+    fn run_action(&mut self, action: Action, effects: &mut Vec<Effect>) -> bool {
+        if let Some(reason) = self.unavailable(action) {
+            self.ml_output = format!("{} {}", menu::label(action), reason);
+            return true;
+        }
+        match action {
+            Action::Quit => self.should_quit = true,
+            Action::Help => self.show_instructions = !self.show_instructions,
+            Action::FocusNext => self.cycle_focus(Focus::next),
+            Action::FocusPrev => self.cycle_focus(Focus::prev),
+            Action::Search => {
+                self.ml_mode = MLMode::Search;
+                self.search_input.clear();
+            }
+            Action::Filter => {
+                self.ml_mode = MLMode::Filter;
+                self.filter_input.clear();
+                self.filter_selected = 0;
+            }
+            Action::FillGaps => {
+                if let Some(stock) = self.stocks.get(self.selected) {
+                    effects.push(Effect::FillGaps {
+                        ticker: stock.ticker.clone(),
+                        interval: data::read_interval(&stock.ticker),
+                        gaps: stock.gaps.clone(),
+                    });
+                }
+            }
+            Action::DownloadAll => {
+                let tickers: Vec<(Ticker, Interval)> =
+                    self.stocks.iter().map(|s| (s.ticker.clone(), data::read_interval(&s.ticker))).collect();
+                if !tickers.is_empty() {
+                    self.ml_output = format!("Queued downloads of {} tickers", tickers.len());
+                    effects.push(Effect::DownloadAll(tickers));
+                }
+            }
+            Action::SortTicker => self.toggle_sort(SortKey::Ticker),
+            Action::SortPrice => self.toggle_sort(SortKey::Price),
+            Action::SortChange => self.toggle_sort(SortKey::Change),
+            Action::SortPctChange => self.toggle_sort(SortKey::PctChange),
+            Action::SortRelStrength => self.toggle_sort(SortKey::RelStrength),
+            Action::ToggleVolume => self.show_volume = !self.show_volume,
+            Action::ChartScale => {
+                self.chart_scale = self.chart_scale.next();
+                self.ml_output = format!("Chart axis: {}", self.chart_scale.name());
+            }
+            Action::ToggleOrderBook if self.focus == Focus::Chart => self.show_order_book = !self.show_order_book,
+            Action::Crosshair if self.focus == Focus::Chart => self.toggle_crosshair(),
+            Action::ShowErrors => {
+                self.show_errors = true;
+                self.errors.mark_seen();
+            }
+            Action::ShowUsage => self.show_usage = true,
+            Action::NewAccount if self.focus == Focus::Accounts => {
+                self.account_form = Some(AccountForm::default());
+            }
+            Action::EditAccount if self.focus == Focus::Accounts => {
+                if let Some(&i) = self.visible_accounts().get(self.accounts_selected) {
+                    self.account_form = Some(AccountForm::edit(i, &self.accounts[i]));
+                }
+            }
+            Action::CloseAccount if self.focus == Focus::Accounts => {
+                effects.extend(self.toggle_archived());
+            }
+            Action::ToggleArchived if self.focus == Focus::Accounts => {
+                self.show_archived = !self.show_archived;
+                self.clamp_account_cursor();
+            }
+            Action::RankAccounts if self.focus == Focus::Accounts => {
+                // The cursor stays on the same account.
+                let selected = self.visible_accounts().get(self.accounts_selected).copied();
+                self.rank_accounts = !self.rank_accounts;
+                if let Some(pos) = selected.and_then(|i| self.visible_accounts().iter().position(|&v| v == i)) {
+                    self.accounts_selected = pos;
+                }
+            }
+            Action::RecordTrade => self.open_trade_form(),
+            Action::Transfer => self.open_transfer_form(),
+            Action::PageUp if self.focus == Focus::LiveTrades => {
+                self.scroll_trades_to(self.trades_scroll.saturating_sub(self.trades_page));
+            }
+            Action::PageDown if self.focus == Focus::LiveTrades => {
+                self.scroll_trades_to(self.trades_scroll + self.trades_page);
+            }
+            Action::JumpToDate if self.focus == Focus::LiveTrades => {
+                self.ml_mode = MLMode::JumpToDate;
+                self.jump_input.clear();
+            }
+            Action::CancelJob if self.focus == Focus::Jobs => {
+                if let Some(job) = self.selected_job() {
+                    effects.push(Effect::CancelJob(job.id));
+                }
+            }
+            Action::ShowReturns => match self.selected_ticker().map(ReturnsView::load) {
+                Some(Ok(view)) => self.returns_view = Some(view),
+                Some(Err(msg)) => self.ml_output = msg,
+                None => self.ml_output = "Select a stock to see its returns".to_string(),
+            },
+            Action::ExportReport if !self.history_ready() => {
+                if !self.history_read {
+                    effects.extend(self.read_history());
+                }
+                self.ml_output = "Reading the prediction history for the report; export again in a moment".to_string();
+            }
+            Action::ExportReport => effects.push(self.export_report()),
+            Action::TradingGame => effects.extend(self.request(Request::Game, false)),
+            Action::Broker => self.open_broker(),
+            Action::PaperOrder => self.prefill_suggestion(),
+            Action::AutoTrade => self.toggle_auto_trade(),
+            Action::Models if self.selected_ticker().is_none() => {
+                self.ml_output = "Select a stock to see its model versions".to_string();
+            }
+            Action::Models => effects.extend(self.request(Request::Models, false)),
+            Action::TimeTravel => {
+                let today = chrono::Local::now().date_naive();
+                let traded = self.trades_selected.and_then(|i| self.trades.get(i)).and_then(|t| t.timestamp);
+                let date = traded.map_or(today.checked_sub_months(chrono::Months::new(1)).unwrap_or(today), |at| at.date());
+                let tickers = self.stock_tickers();
+                let snapshot = Snapshot::at(date, &self.accounts, &self.trades, &tickers);
+                self.time_travel = Some(TimeTravelView::new(snapshot, today));
+            }
+            Action::ExportChart => match self.export_chart() {
+                Ok(effect) => effects.push(effect),
+                Err(msg) => self.ml_output = msg,
+            },
+            Action::ReleaseNotes if self.release.is_some() => self.show_release_notes = true,
+            Action::ReleaseNotes if self.update_checked => {
+                self.ml_output = format!("stm {} is the latest release", update::CURRENT);
+            }
+            Action::ReleaseNotes if self.update_requested => {
+                self.ml_output = "No newer release found yet; see the update check in Jobs".to_string();
+            }
+            Action::ReleaseNotes => {
+                self.ml_output = format!("No newer release known ([updates] check in {})", config::CONFIG_PATH);
+            }
+            Action::Changelog => self.changelog = Some((changelog::CHANGELOG.iter().collect(), false)),
+            Action::RetentionReport if self.retention.is_set() => {
+                effects.push(Effect::RetentionReport(self.retention.clone()));
+            }
+            Action::RetentionReport => {
+                self.ml_output = format!("No retention policy set ([retention] in {})", config::CONFIG_PATH);
+            }
+            Action::AlertPreview => {
+                self.ml_mode = MLMode::Alert;
+                self.alert_input.clear();
+            }
+            Action::ToggleCompare if self.focus == Focus::MLList => {
+                if let Some(ticker) = self.selected_ticker().cloned() {
+                    match self.marked.iter().position(|t| *t == ticker) {
+                        Some(i) => {
+                            self.marked.remove(i);
+                        }
+                        None => self.marked.push(ticker),
+                    }
+                }
+            }
+            Action::Menu => self.menu = Some(MenuState::default()),
+            Action::Analytics => self.analytics = Some(Analytics::new(&self.accounts, &self.trades)),
+            Action::MarketOverview => {
+                let view = OverviewView::new(&self.stocks, &self.overview_config);
+                match view.tiles.is_empty() {
+                    true => self.ml_output = "No stocks in the ML list to show".to_string(),
+                    false => self.overview = Some(view),
+                }
+            }
+            Action::Dividends => {
+                let today = chrono::Local::now().date_naive();
+                self.dividends = Some(DividendsView::new(dividends::summary(&self.trades, &self.calendar, today)));
+            }
+            Action::Refresh => match self.focus.source() {
+                Some(source) => {
+                    if source == Source::Trades {
+                        // Re-read the whole file, in case it was edited.
+                        self.trades_cursor = None;
+                    }
+                    if source == Source::Accounts && self.fx_fetch {
+                        effects.extend(self.request(self.request_for(Source::Fx), false));
+                    }
+                    effects.extend(self.request(self.request_for(source), true));
+                }
+                None => self.ml_output = "Nothing to refresh in this panel".to_string(),
+            },
+            Action::DateRange if self.focus == Focus::Chart => match self.chart.bars.last() {
+                Some(last) => {
+                    let picker = RangePicker::new("Chart zoom", last.at.date(), self.chart_range);
+                    self.range_picker = Some((RangeTarget::Chart, picker));
+                }
+                None => self.ml_output = "No price history to zoom".to_string(),
+            },
+            Action::DateRange if self.focus == Focus::MLList => {
+                if let Some(ticker) = self.selected_ticker().cloned() {
+                    let today = chrono::Local::now().date_naive();
+                    let picker = RangePicker::new(format!("Download {}", ticker), today, DateRange::default());
+                    let interval = data::read_interval(&ticker);
+                    self.range_picker = Some((RangeTarget::Download { ticker, interval }, picker));
+                }
+            }
+            Action::GrowChart => effects.extend(self.resize_rows(RESIZE_STEP as i16)),
+            Action::ShrinkChart => effects.extend(self.resize_rows(-(RESIZE_STEP as i16))),
+            Action::CollapsePanel => {
+                let panel = self.focus.panel();
+                if !self.layout.is_hidden(panel) {
+                    self.layout.hidden.push(panel);
+                    self.cycle_focus(Focus::next);
+                    effects.push(Effect::SaveLayout(self.layout.clone()));
+                }
+            }
+            Action::RestorePanels if !self.layout.hidden.is_empty() => {
+                self.layout.hidden.clear();
+                effects.push(Effect::SaveLayout(self.layout.clone()));
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Applies the date range picker's selection to what it was opened for.
+    fn handle_picker_key(&mut self, code: KeyCode) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        let Some((target, picker)) = &mut self.range_picker else {
+            return effects;
+        };
+        let selection = match picker.handle_key(code) {
+            PickerStep::Open => return effects,
+            PickerStep::Cancelled => {
+                self.range_picker = None;
+                return effects;
+            }
+            PickerStep::Picked(selection) => selection,
+        };
+        match target {
+            RangeTarget::Chart => {
+                self.chart_range = selection.range;
+                self.chart_offset = 0;
+            }
+            RangeTarget::Backtest => {
+                if let Some(preview) = &mut self.alert_preview {
+                    preview.set_range(selection.range);
+                }
+            }
+            RangeTarget::Download { ticker, interval } => {
+                let (ticker, interval) = (ticker.clone(), *interval);
+                match (selection.preset, selection.range.start) {
+                    (Some(preset), _) => effects.push(Effect::RunDownload { ticker, interval, range: preset.period().to_string() }),
+                    (None, Some(start)) => effects.push(Effect::DownloadRange { ticker, interval, start, end: selection.range.end }),
+                    (None, None) => {
+                        picker.error = Some("A download needs a From date or a preset".to_string());
+                        return effects;
+                    }
+                }
+            }
+        }
+        self.range_picker = None;
+        self.count_usage(&effects);
+        effects
+    }
+
+    /// Backtests the typed rule on the selected stock's history and opens
+    /// the preview, or explains why it can't.
+    fn open_alert_preview(&mut self) {
+        let rule = match AlertRule::parse(&self.alert_input) {
+            Ok(rule) => rule,
+            Err(msg) => {
+                self.ml_output = msg;
+                return;
+            }
+        };
+        let Some(ticker) = self.selected_ticker().cloned() else {
+            self.ml_output = "Select a stock to backtest the alert on".to_string();
+            return;
+        };
+        let series = data::load_series(&ticker);
+        if series.bars.is_empty() {
+            self.ml_output = format!("No price history for {}", ticker);
+            return;
+        }
+        self.alert_preview = Some(AlertPreview::new(ticker, rule, series));
+        self.ml_mode = MLMode::List;
+        self.alert_input.clear();
+    }
+
+    /// Moves focus with `step` to the nearest panel that isn't hidden. Stays
+    /// put if every other panel is.
+    fn cycle_focus(&mut self, step: fn(Focus) -> Focus) {
+        let mut next = step(self.focus);
+        while next != self.focus && self.layout.is_hidden(next.panel()) {
+            next = step(next);
+        }
+        self.focus = next;
+    }
+
+    /// Moves `delta` percent of height from the table row to the chart row
+    /// (or back, if negative), keeping both at least `MIN_ROW`.
+    fn resize_rows(&mut self, delta: i16) -> Option<Effect> {
+        let [chart, tables, _] = &mut self.layout.rows;
+        let (from, to) = if delta > 0 { (&mut *tables, &mut *chart) } else { (&mut *chart, &mut *tables) };
+        let step = (delta.unsigned_abs()).min(from.saturating_sub(MIN_ROW));
+        if step == 0 {
+            return None;
+        }
+        *from -= step;
+        *to += step;
+        Some(Effect::SaveLayout(self.layout.clone()))
+    }
+
+    /// Why `action` can't run, when something it needs is missing.
+    pub fn unavailable(&self, action: Action) -> Option<String> {
+        let needs: Vec<Capability> = match action {
+            Action::FillGaps => self.stocks.get(self.selected).map_or(Vec::new(), |s| capabilities::download(&s.ticker).to_vec()),
+            Action::DownloadAll => self.stocks.iter().flat_map(|s| capabilities::download(&s.ticker)).copied().collect(),
+            Action::Broker | Action::PaperOrder | Action::AutoTrade => vec![Capability::Alpaca],
+            _ => return None,
+        };
+        self.capabilities.check(&needs).err()
+    }
+
+    /// Why `effect` can't run, for the output line, when something it
+    /// needs is missing.
+    fn refused(&self, effect: &Effect) -> Option<String> {
+        let (what, needs): (&str, Vec<Capability>) = match effect {
+            Effect::RunDownload { ticker, .. } | Effect::DownloadRange { ticker, .. } | Effect::FillGaps { ticker, .. } => {
+                ("Downloads", capabilities::download(ticker).to_vec())
+            }
+            Effect::DownloadAll(tickers) => ("Downloads", tickers.iter().flat_map(|(t, _)| capabilities::download(t)).copied().collect()),
+            Effect::RunMl { train: true, .. } => ("Training", capabilities::prediction(true).to_vec()),
+            Effect::RunMl { train: false, .. } => ("Predictions", capabilities::prediction(false).to_vec()),
+            _ => return None,
+        };
+        self.capabilities.check(&needs).err().map(|reason| format!("{} {}", what, reason))
+    }
+
+    /// `effects` less those `refused`, whose reason is shown instead.
+    fn runnable(&mut self, effects: Vec<Effect>) -> Vec<Effect> {
+        let mut runnable = Vec::with_capacity(effects.len());
+        for effect in effects {
+            let Some(reason) = self.refused(&effect) else {
+                runnable.push(effect);
+                continue;
+            };
+            if let (Effect::RunMl { train: true, .. }, Some(view)) = (&effect, &mut self.models_view) {
+                view.message = reason.clone();
+            }
+            self.ml_output = reason;
+        }
+        runnable
+    }
+
+    fn count_usage(&mut self, effects: &[Effect]) {
+        let effects: Vec<&Effect> = effects.iter().filter(|e| self.refused(e).is_none()).collect();
+        for effect in effects {
+            match effect {
+                Effect::RunDownload { .. } | Effect::DownloadRange { .. } | Effect::DownloadAll(_) => {
+                    self.usage.bump(|u| &mut u.downloads)
+                }
+                Effect::FillGaps { .. } => self.usage.bump(|u| &mut u.gap_fills),
+                Effect::RunMl { .. } => self.usage.bump(|u| &mut u.ml_runs),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::broker::{BrokerState, Order, Position};
+
+    /// A broker that takes every order and lists what the test puts in
+    /// `reported` on the next poll, as a remote one would.
+    struct FakeBroker {
+        reported: Rc<RefCell<BrokerState>>,
+        state: BrokerState,
+        sent: u64,
+    }
+
+    impl Broker for FakeBroker {
+        fn name(&self) -> &'static str {
+            "Fake"
+        }
+
+        fn booking_account(&self) -> &str {
+            "Alice"
+        }
+
+        fn submit_order(&mut self, order: OrderRequest) -> Result<Submitted, String> {
+            self.sent += 1;
+            Ok(Submitted { id: format!("c{}", self.sent), message: format!("Sent {} {}", order.side.as_str(), order.qty) })
+        }
+
+        fn cancel(&mut self, prefix: &str) -> Result<String, String> {
+            Err(format!("No open order starting {}", prefix))
+        }
+
+        fn state(&self) -> &BrokerState {
+            &self.state
+        }
+
+        fn poll(&mut self) -> Vec<Notice> {
+            self.state = self.reported.borrow().clone();
+            Vec::new()
+        }
+    }
+
+    fn ticker(symbol: &str) -> Ticker {
+        Ticker::parse(symbol).unwrap()
+    }
+
+    fn app() -> App {
+        let mut app = App::new(&config::Config::default());
+        app.broker = None;
+        app.accounts = vec![AccountSummary::new(AccountId::parse("Alice").unwrap(), 10_000.0, "USD")];
+        app
+    }
+
+    fn order(id: &str, client_id: &str, qty: f64, filled_qty: f64, status: &str) -> Order {
+        Order {
+            id: id.to_string(),
+            ticker: ticker("AAPL"),
+            side: Side::Buy,
+            qty,
+            limit: None,
+            trigger: None,
+            filled_qty,
+            filled_avg_price: (filled_qty > 0.0).then_some(100.0),
+            status: status.to_string(),
+            submitted_at: None,
+            client_id: Some(client_id.to_string()),
+        }
+    }
+
+    fn fill(order_id: &str, qty: f64) -> Fill {
+        Fill { order_id: order_id.to_string(), ticker: ticker("AAPL"), side: Side::Buy, qty, price: 100.0 }
+    }
+
+    fn booked_note(effects: &[Effect]) -> Option<&str> {
+        effects.iter().find_map(|e| match e {
+            Effect::RecordTrade(trade) => trade.note.as_deref(),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn auto_orders_stay_within_the_position_limits() {
+        let limits = AutoTradeConfig { enabled: true, qty: 5, max_shares: 10, max_value: 1_000.0 };
+        assert_eq!(auto_qty(&limits, Side::Buy, 0.0, 0.0, 0.0, 50.0), 5.0);
+        assert_eq!(auto_qty(&limits, Side::Buy, 8.0, 0.0, 0.0, 50.0), 2.0);
+        // 1,000 buys three shares at 300, and one more once two are held.
+        assert_eq!(auto_qty(&limits, Side::Buy, 0.0, 0.0, 0.0, 300.0), 3.0);
+        assert_eq!(auto_qty(&limits, Side::Buy, 2.0, 0.0, 0.0, 300.0), 1.0);
+        // Shares still to fill count as held.
+        assert_eq!(auto_qty(&limits, Side::Buy, 3.0, 6.0, 0.0, 50.0), 1.0);
+        assert!(auto_qty(&limits, Side::Buy, 4.0, 6.0, 0.0, 50.0) < 1.0);
+        let unlimited = AutoTradeConfig { max_value: 0.0, ..limits.clone() };
+        assert_eq!(auto_qty(&unlimited, Side::Buy, 0.0, 0.0, 0.0, 1e6), 5.0);
+
+        assert_eq!(auto_qty(&limits, Side::Sell, 3.0, 0.0, 0.0, 50.0), 3.0);
+        assert_eq!(auto_qty(&limits, Side::Sell, 7.0, 0.0, 6.0, 50.0), 1.0);
+        assert!(auto_qty(&limits, Side::Sell, 0.5, 0.0, 0.0, 50.0) < 1.0);
+    }
+
+    #[test]
+    fn auto_orders_count_until_they_close_and_label_only_their_fills() {
+        let mut app = app();
+        let reported = Rc::new(RefCell::new(BrokerState::default()));
+        app.broker = Some(Box::new(FakeBroker { reported: reported.clone(), state: BrokerState::default(), sent: 0 }));
+        app.autotrade = AutoTradeConfig { enabled: true, qty: 5, max_shares: 8, max_value: 0.0 };
+        let aapl = ticker("AAPL");
+
+        // Sent but not yet listed by the broker, the first order still
+        // counts towards the limit.
+        app.auto_trade("SMA 10/30", aapl.clone(), Signal::Buy, 100.0);
+        app.auto_trade("SMA 10/30", aapl.clone(), Signal::Buy, 100.0);
+        assert_eq!((app.auto_orders["c1"].qty, app.auto_orders["c2"].qty), (5.0, 3.0));
+        app.auto_trade("SMA 10/30", aapl.clone(), Signal::Buy, 100.0);
+        assert!(app.ml_output.ends_with("skipped: position limit reached"), "{}", app.ml_output);
+        assert_eq!(app.auto_orders.len(), 2);
+
+        // The broker lists them by ids of its own: the first filled, the
+        // second resting.
+        *reported.borrow_mut() = BrokerState {
+            positions: vec![Position {
+                ticker: aapl.clone(),
+                qty: 5.0,
+                avg_entry_price: 100.0,
+                current_price: 100.0,
+                market_value: 500.0,
+                unrealized_pl: 0.0,
+            }],
+            orders: vec![order("b2", "c2", 3.0, 0.0, "new"), order("b1", "c1", 5.0, 5.0, "filled")],
+            ..BrokerState::default()
+        };
+        app.poll_broker();
+        let effects = app.handle_event(AppEvent::Fill(fill("b1", 5.0)));
+        assert_eq!(booked_note(&effects), Some("Auto SMA 10/30 Buy signal: Fake buy 5 @ 100.00 (b1)"));
+        assert!(!app.auto_orders.contains_key("c1"));
+        app.auto_trade("SMA 10/30", aapl.clone(), Signal::Buy, 100.0);
+        assert!(app.ml_output.ends_with("skipped: position limit reached"));
+
+        // A fill of an order typed in the broker view isn't labelled, and
+        // a cancelled auto order is dropped without one.
+        let effects = app.handle_event(AppEvent::Fill(fill("m1", 1.0)));
+        assert_eq!(booked_note(&effects), Some("Fake buy 1 @ 100.00 (m1)"));
+        reported.borrow_mut().orders[0].status = "canceled".to_string();
+        app.poll_broker();
+        assert!(app.auto_orders.is_empty());
+
+        // A sell is cut to the shares held.
+        app.auto_trade("SMA 10/30", aapl, Signal::Sell, 100.0);
+        assert_eq!((app.auto_orders["c3"].side, app.auto_orders["c3"].qty), (Side::Sell, 5.0));
+    }
+
+    fn press(app: &mut App, code: KeyCode) -> Vec<Effect> {
+        app.handle_event(AppEvent::Key(Key::plain(code)))
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            assert_eq!(press(app, KeyCode::Char(c)), Vec::new(), "typing {:?}", c);
+        }
+    }
+
+    fn stock(symbol: &str) -> StockInfo {
+        StockInfo {
+            ticker: ticker(symbol),
+            price: 100.0,
+            change: 0.0,
+            pct_change: 0.0,
+            gaps: Vec::new(),
+            return_3m: None,
+            error: None,
+            source: "data".to_string(),
+            series: SeriesKey::default(),
+        }
+    }
+
+    #[test]
+    fn focus_cycles_past_collapsed_panels_and_while_typing() {
+        let mut app = app();
+        assert_eq!(app.focus, Focus::MLList);
+        press(&mut app, KeyCode::Tab);
+        press(&mut app, KeyCode::Tab);
+        assert_eq!(app.focus, Focus::Calendar);
+        press(&mut app, KeyCode::Tab);
+        assert_eq!(app.focus, Focus::Chart);
+        press(&mut app, KeyCode::BackTab);
+        assert_eq!(app.focus, Focus::Calendar);
+
+        // Collapsing the focused panel moves on, and Tab skips it until
+        // the panels are restored.
+        let effects = press(&mut app, KeyCode::Char('z'));
+        assert!(matches!(&effects[..], [Effect::SaveLayout(layout)] if layout.hidden == [Panel::Calendar]));
+        assert_eq!(app.focus, Focus::Chart);
+        press(&mut app, KeyCode::BackTab);
+        assert_eq!(app.focus, Focus::News);
+        press(&mut app, KeyCode::Tab);
+        assert_eq!(app.focus, Focus::Chart);
+        assert!(matches!(&press(&mut app, KeyCode::Char('Z'))[..], [Effect::SaveLayout(layout)] if layout.hidden.is_empty()));
+        press(&mut app, KeyCode::BackTab);
+        assert_eq!(app.focus, Focus::Calendar);
+
+        // A text box takes shortcut keys, but Tab still moves focus.
+        press(&mut app, KeyCode::Char('s'));
+        type_text(&mut app, "qz");
+        press(&mut app, KeyCode::Tab);
+        assert_eq!((app.focus, app.ml_mode, app.search_input.as_str()), (Focus::Chart, MLMode::Search, "qz"));
+        assert!(!app.should_quit && app.layout.hidden.is_empty());
+    }
+
+    #[test]
+    fn account_and_trade_forms_save_what_is_typed() {
+        let mut app = app();
+        // Forms for accounts open from the Accounts panel only.
+        press(&mut app, KeyCode::Char('n'));
+        assert!(app.account_form.is_none());
+        press(&mut app, KeyCode::BackTab);
+        press(&mut app, KeyCode::BackTab);
+        assert_eq!(app.focus, Focus::Accounts);
+
+        press(&mut app, KeyCode::Char('n'));
+        type_text(&mut app, "alice");
+        assert_eq!(press(&mut app, KeyCode::Enter), Vec::new());
+        assert_eq!(app.account_form.as_ref().unwrap().error.as_deref(), Some("An account named alice already exists"));
+        for _ in 0..5 {
+            press(&mut app, KeyCode::Backspace);
+        }
+        type_text(&mut app, "Bob");
+        press(&mut app, KeyCode::Tab);
+        // The balance field reads k/m/b suffixes.
+        type_text(&mut app, "2.5k");
+        press(&mut app, KeyCode::Tab);
+        type_text(&mut app, "eur");
+        let effects = press(&mut app, KeyCode::Enter);
+        assert!(app.account_form.is_none());
+        let [Effect::SaveAccounts(accounts)] = &effects[..] else { panic!("{:?}", effects) };
+        assert_eq!(accounts[1], AccountSummary::new(AccountId::parse("Bob").unwrap(), 2_500.0, "EUR"));
+        assert_eq!(app.accounts, *accounts);
+
+        // The trade form starts on the amount of the account under the
+        // cursor; q is typed rather than quitting.
+        press(&mut app, KeyCode::Char('t'));
+        assert_eq!(app.trade_form.as_ref().map(|f| (f.fields[0].as_str(), f.active)), Some(("Alice", AMOUNT_FIELD)));
+        type_text(&mut app, "-150");
+        press(&mut app, KeyCode::Tab);
+        type_text(&mut app, "aapl");
+        press(&mut app, KeyCode::Tab);
+        type_text(&mut app, "quick stop");
+        let effects = press(&mut app, KeyCode::Enter);
+        assert!(app.trade_form.is_none() && !app.should_quit);
+        let [Effect::RecordTrade(trade), Effect::Refresh(Request::Trades { .. })] = &effects[..] else { panic!("{:?}", effects) };
+        assert_eq!((trade.name.as_str(), trade.transaction, trade.new_balance), ("Alice", -150.0, 9_850.0));
+        assert_eq!((trade.ticker.clone(), trade.note.as_deref()), (Some(ticker("AAPL")), Some("quick stop")));
+        assert_eq!(app.accounts[0].current_amount, 9_850.0);
+
+        // An amount of zero is refused in place; Esc drops the form.
+        press(&mut app, KeyCode::Char('t'));
+        type_text(&mut app, "0");
+        assert_eq!(press(&mut app, KeyCode::Enter), Vec::new());
+        assert_eq!(app.trade_form.as_ref().unwrap().error.as_deref(), Some("Amount must not be zero"));
+        press(&mut app, KeyCode::Esc);
+        assert!(app.trade_form.is_none());
+    }
+
+    #[test]
+    fn search_downloads_and_filter_moves_the_selection() {
+        let mut app = app();
+        app.stocks = vec![stock("AAPL"), stock("AMZN"), stock("MSFT")];
+
+        press(&mut app, KeyCode::Char('/'));
+        type_text(&mut app, "ms");
+        assert_eq!(app.visible_stocks(), [2]);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!((app.ml_mode, app.selected, app.filter_input.as_str()), (MLMode::List, 2, ""));
+        // Esc leaves the filter without moving the selection.
+        press(&mut app, KeyCode::Char('/'));
+        type_text(&mut app, "a");
+        press(&mut app, KeyCode::Down);
+        assert_eq!(app.filter_selected, 1);
+        press(&mut app, KeyCode::Esc);
+        assert_eq!((app.ml_mode, app.selected), (MLMode::List, 2));
+
+        press(&mut app, KeyCode::Char('s'));
+        type_text(&mut app, "msft 2h");
+        assert_eq!(press(&mut app, KeyCode::Enter), Vec::new());
+        assert_eq!(app.ml_mode, MLMode::Search);
+        assert!(app.ml_output.starts_with("Unknown interval 2h"), "{}", app.ml_output);
+        press(&mut app, KeyCode::Backspace);
+        press(&mut app, KeyCode::Backspace);
+        type_text(&mut app, "1h");
+        assert_eq!(
+            press(&mut app, KeyCode::Enter),
+            [Effect::RunDownload { ticker: ticker("MSFT"), interval: Interval::OneHour, range: "5d".to_string() }]
+        );
+        assert_eq!((app.ml_mode, app.search_input.as_str()), (MLMode::List, ""));
+        press(&mut app, KeyCode::Char('s'));
+        type_text(&mut app, "?apple");
+        assert_eq!(press(&mut app, KeyCode::Enter), [Effect::SearchSymbols("apple".to_string())]);
+
+        // Enter on the list runs the model on the selected stock.
+        assert_eq!(
+            press(&mut app, KeyCode::Enter),
+            [Effect::RunMl { ticker: ticker("MSFT"), pipeline: app.pipeline.clone(), train: false }]
+        );
+    }
+
+    #[test]
+    fn models_view_opens_once_the_registry_is_read() {
+        let mut app = app();
+        assert_eq!(press(&mut app, KeyCode::Char('M')), Vec::new());
+        assert_eq!(app.ml_output, "Select a stock to see its model versions");
+
+        app.stocks = vec![stock("AAPL")];
+        assert_eq!(press(&mut app, KeyCode::Char('M')), [Effect::Refresh(Request::Models)]);
+        assert_eq!(press(&mut app, KeyCode::Char('M')), Vec::new());
+        assert!(app.models_view.is_none());
+        let failed = Err(AppError::load(crate::ml::registry::REGISTRY_PATH, "expected value"));
+        app.handle_event(AppEvent::Loaded { source: Source::Models, result: failed });
+        assert!(app.models_view.is_none());
+        assert_eq!(app.ml_output, "Failed to open: could not read models/registry.json: expected value");
+
+        press(&mut app, KeyCode::Char('M'));
+        app.handle_event(AppEvent::Loaded { source: Source::Models, result: Ok(Loaded::Models(Registry::default())) });
+        let view = app.models_view.as_ref().unwrap();
+        assert_eq!((&view.ticker, view.message.as_str()), (&ticker("AAPL"), "No versions yet; t trains one, as does the next model run"));
+    }
+
+    #[test]
+    fn game_opens_once_read_and_seats_the_accounts() {
+        let mut app = app();
+        assert_eq!(press(&mut app, KeyCode::Char('G')), [Effect::Refresh(Request::Game)]);
+        assert!(app.game_view.is_none());
+        app.handle_event(AppEvent::Loaded { source: Source::Game, result: Ok(Loaded::Game(None)) });
+        let game = &app.game_view.as_ref().unwrap().game;
+        assert_eq!(game.players.iter().map(|p| (p.name.as_str(), p.cash)).collect::<Vec<_>>(), [("Alice", app.game_config.starting_cash)]);
+    }
+
+    #[test]
+    fn accounts_with_trades_under_any_case_keep_their_name() {
+        let mut app = app();
+        press(&mut app, KeyCode::BackTab);
+        press(&mut app, KeyCode::BackTab);
+        let rename = |app: &mut App, to: &str| {
+            press(app, KeyCode::Char('e'));
+            for _ in 0..app.account_form.as_ref().unwrap().fields[0].len() {
+                press(app, KeyCode::Backspace);
+            }
+            type_text(app, to);
+            press(app, KeyCode::Enter)
+        };
+        assert_eq!(rename(&mut app, "Carol"), Vec::new());
+        assert_eq!(app.account_form.take().unwrap().error.as_deref(), Some("The trade history is still loading; rename once it's in"));
+
+        // The history has the account's trades under another case.
+        app.ledger = Some(Ledger::new(&[TradeRecord::new(AccountId::parse("ALICE").unwrap(), 5.0, 10_005.0)]));
+        assert_eq!(rename(&mut app, "Carol"), Vec::new());
+        assert_eq!(app.account_form.take().unwrap().error.as_deref(), Some("Alice has trade history and can't be renamed"));
+        // Changing only the case keeps the trades with it.
+        assert!(matches!(&rename(&mut app, "alice")[..], [Effect::SaveAccounts(accounts)] if accounts[0].name.as_str() == "alice"));
+    }
+}
@@ -0,0 +1,153 @@
+//! Where the charts' price axes are ticked.
+//!
+//! Ticks fall on round numbers, 1, 2 or 5 times a power of ten apart, so a
+//! chart from 182.3 to 197.8 is ticked at 185, 190 and 195 rather than at
+//! fifths of its span. Labels show as many decimals as the spacing needs.
+//!
+//! A log axis spanning a decade or more is ticked at 1, 2 and 5 times each
+//! power of ten instead, which fall evenly up it.
+
+use serde::{Deserialize, Serialize};
+
+/// Most decimals a label is shown to.
+const MAX_DECIMALS: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ticks {
+    /// Ascending, all within the range ticked.
+    pub values: Vec<f64>,
+    pub decimals: usize,
+}
+
+impl Ticks {
+    pub fn label(&self, value: f64) -> String {
+        format!("{:.*}", self.decimals, value)
+    }
+
+    /// Columns the widest label takes.
+    pub fn width(&self) -> usize {
+        self.values.iter().map(|&v| self.label(v).chars().count()).max().unwrap_or(0)
+    }
+}
+
+/// About `count` round ticks from `lo` to `hi`; none for an empty range or
+/// a `count` of 0.
+pub fn ticks(lo: f64, hi: f64, count: usize) -> Ticks {
+    let span = hi - lo;
+    if count == 0 || !span.is_finite() || span <= 0.0 {
+        return Ticks { values: Vec::new(), decimals: 0 };
+    }
+    let rough = span / count as f64;
+    let power = 10f64.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0].iter().map(|m| m * power).find(|&s| s >= rough).unwrap_or(10.0 * power);
+    let first = (lo / step).ceil() as i64;
+    let last = (hi / step).floor() as i64;
+    // Multiples of the step rather than sums of it, which drift.
+    let values = (first..=last).map(|k| k as f64 * step).collect();
+    let decimals = (-step.log10().floor()).clamp(0.0, MAX_DECIMALS as f64) as usize;
+    Ticks { values, decimals }
+}
+
+/// About `count` ticks from `lo` to `hi` for a log axis, both above 0: as
+/// `ticks` within a decade, otherwise 1, 2 and 5 times powers of ten, or
+/// only the powers when those are too many.
+pub fn log_ticks(lo: f64, hi: f64, count: usize) -> Ticks {
+    if count == 0 || !(lo > 0.0 && hi.is_finite()) || hi <= lo {
+        return Ticks { values: Vec::new(), decimals: 0 };
+    }
+    if hi / lo < 10.0 {
+        return ticks(lo, hi, count);
+    }
+    let decades = lo.log10().floor() as i32..=hi.log10().ceil() as i32;
+    let within = |multiples: &[f64]| -> Vec<f64> {
+        decades
+            .clone()
+            .flat_map(|k| multiples.iter().map(move |m| m * 10f64.powi(k)))
+            .filter(|&v| v >= lo && v <= hi)
+            .collect()
+    };
+    let mut values = within(&[1.0, 2.0, 5.0]);
+    if values.len() > count * 2 {
+        values = within(&[1.0]);
+    }
+    let smallest = values.first().copied().unwrap_or(lo);
+    let decimals = (-smallest.log10().floor()).clamp(0.0, MAX_DECIMALS as f64) as usize;
+    Ticks { values, decimals }
+}
+
+/// How a chart's values map to its height.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scale {
+    #[default]
+    Linear,
+    /// Equal moves in percent take equal heights.
+    Log,
+    /// Percent change from the first bar shown.
+    Percent,
+}
+
+impl Scale {
+    pub fn next(self) -> Self {
+        match self {
+            Scale::Linear => Scale::Log,
+            Scale::Log => Scale::Percent,
+            Scale::Percent => Scale::Linear,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Scale::Linear => "linear",
+            Scale::Log => "log",
+            Scale::Percent => "% from first bar",
+        }
+    }
+
+    /// Where `value` goes up the axis, `first` being the first bar's. A log
+    /// axis can't show values of 0 or below, nor a percent one changes from
+    /// 0.
+    pub fn apply(self, value: f64, first: f64) -> Option<f64> {
+        match self {
+            Scale::Linear => Some(value),
+            Scale::Log => (value > 0.0).then(|| value.ln()),
+            Scale::Percent => (first != 0.0).then(|| (value / first - 1.0) * 100.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_fall_on_round_numbers() {
+        let t = ticks(182.3, 197.8, 5);
+        assert_eq!(t.values, vec![185.0, 190.0, 195.0]);
+        assert_eq!((t.label(185.0), t.width()), ("185".to_string(), 3));
+
+        let small = ticks(0.1234, 0.1281, 4);
+        assert_eq!(small.decimals, 3);
+        assert_eq!(small.values.iter().map(|&v| small.label(v)).collect::<Vec<_>>(), ["0.124", "0.126", "0.128"]);
+
+        let percent = ticks(-12.0, 30.0, 8);
+        assert_eq!(percent.values, vec![-10.0, 0.0, 10.0, 20.0, 30.0]);
+        assert!(ticks(5.0, 5.0, 4).values.is_empty());
+        assert!(ticks(1.0, 2.0, 0).values.is_empty());
+    }
+
+    #[test]
+    fn log_axes_tick_each_decade() {
+        assert_eq!(log_ticks(182.3, 197.8, 5), ticks(182.3, 197.8, 5));
+        let long = log_ticks(3.0, 480.0, 5);
+        assert_eq!(long.values, vec![5.0, 10.0, 20.0, 50.0, 100.0, 200.0]);
+        assert_eq!(long.decimals, 0);
+        assert_eq!(log_ticks(0.02, 9000.0, 3).values, vec![0.1, 1.0, 10.0, 100.0, 1000.0]);
+        assert_eq!(log_ticks(0.02, 9000.0, 3).decimals, 1);
+        assert!(log_ticks(0.0, 10.0, 5).values.is_empty());
+
+        assert_eq!(Scale::Percent.apply(150.0, 100.0), Some(50.0));
+        assert_eq!(Scale::Log.apply(-1.0, 100.0), None);
+        assert_eq!(Scale::Percent.next().next(), Scale::Log);
+    }
+}
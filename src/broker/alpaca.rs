@@ -0,0 +1,408 @@
+//! Alpaca paper trading (`alpaca` feature).
+//!
+//! A background thread places and cancels the orders the broker view sends
+//! it, and polls the account, positions and this session's orders every
+//! `[alpaca] poll_secs`, all through the shared `net` client. Crypto pairs
+//! are sent as Alpaca's `BTC/USD` symbols, good until cancelled since they
+//! trade around the clock; stock orders are good for the day. A stop-loss
+//! goes out as a stop order and a take-profit as a limit at its price, so
+//! the latter comes back as a plain limit order. Each order is sent with
+//! a `client_order_id` of the app's own, so it can be followed before
+//! Alpaca has listed it. The API keys come from the environment variables
+//! Alpaca's own tools read, unless `[alpaca]` sets them.
+
+use std::error::Error;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use serde_json::{json, Value};
+
+use crate::broker::{self, Balances, Broker, BrokerState, FillTracker, Notice, Order, OrderRequest, Position, Side, Submitted, Trigger};
+use crate::chaos;
+use crate::config::AlpacaConfig;
+use crate::ids::Ticker;
+use crate::net;
+
+/// Host every order goes to; other `base_url`s are refused.
+const PAPER_HOST: &str = "paper-api.alpaca.markets";
+/// Orders listed per poll, newest first.
+const ORDER_LIMIT: usize = 100;
+pub const KEY_ID_ENV: &str = "APCA_API_KEY_ID";
+pub const SECRET_KEY_ENV: &str = "APCA_API_SECRET_KEY";
+
+enum Command {
+    /// The order and its client order id.
+    Place(OrderRequest, String),
+    Cancel(String),
+}
+
+enum Update {
+    Snapshot { balances: Balances, positions: Vec<Position>, orders: Vec<Order> },
+    Notice(Notice),
+}
+
+/// Handle to the Alpaca thread plus what it last reported.
+pub struct AlpacaFeed {
+    // `None` when the config was refused; nothing is sent anywhere then.
+    commands: Option<Sender<Command>>,
+    rx: Option<Receiver<Update>>,
+    tracker: FillTracker,
+    account: String,
+    state: BrokerState,
+    // Start of this session's client order ids, and the number of the next.
+    session: String,
+    next_order: u64,
+}
+
+impl AlpacaFeed {
+    /// Starts the thread. A config that can't be used gives a feed that
+    /// only reports why.
+    pub fn start(config: &AlpacaConfig) -> Self {
+        let config = &with_env_keys(config.clone(), |var| std::env::var(var).ok());
+        let mut feed = Self {
+            commands: None,
+            rx: None,
+            tracker: FillTracker::default(),
+            account: config.account.clone(),
+            state: BrokerState::default(),
+            session: format!("stm-{}", Utc::now().format("%Y%m%d%H%M%S")),
+            next_order: 1,
+        };
+        if let Err(e) = check(config) {
+            feed.state.last_error = Some(e);
+            return feed;
+        }
+        let (commands, requests) = mpsc::channel();
+        let (tx, rx) = mpsc::channel();
+        let worker = config.clone();
+        thread::spawn(move || run(worker, requests, tx));
+        feed.commands = Some(commands);
+        feed.rx = Some(rx);
+        feed
+    }
+
+    fn send(&self, command: Command) -> Result<(), String> {
+        let Some(commands) = &self.commands else {
+            return Err(self.state.last_error.clone().unwrap_or_else(|| "Alpaca isn't set up".to_string()));
+        };
+        commands.send(command).map_err(|_| "The Alpaca thread has stopped".to_string())
+    }
+
+}
+
+impl Broker for AlpacaFeed {
+    fn name(&self) -> &'static str {
+        "Alpaca paper"
+    }
+
+    fn booking_account(&self) -> &str {
+        &self.account
+    }
+
+    fn submit_order(&mut self, order: OrderRequest) -> Result<Submitted, String> {
+        let message = format!("Sending {} {} {}…", order.side.as_str(), order.qty, order.ticker);
+        let id = format!("{}-{}", self.session, self.next_order);
+        self.send(Command::Place(order, id.clone()))?;
+        self.next_order += 1;
+        Ok(Submitted { id, message })
+    }
+
+    fn cancel(&mut self, prefix: &str) -> Result<String, String> {
+        let id = broker::open_order_id(&self.state.orders, prefix)?;
+        self.send(Command::Cancel(id.clone())).map(|()| format!("Cancelling {}…", id))
+    }
+
+    fn state(&self) -> &BrokerState {
+        &self.state
+    }
+
+    fn poll(&mut self) -> Vec<Notice> {
+        let Some(rx) = &self.rx else {
+            return Vec::new();
+        };
+        let mut notices = Vec::new();
+        for update in rx.try_iter() {
+            match update {
+                Update::Snapshot { balances, positions, orders } => {
+                    notices.extend(self.tracker.update(&orders).into_iter().map(Notice::Fill));
+                    self.state.balances = Some(balances);
+                    self.state.positions = positions;
+                    self.state.orders = orders;
+                    self.state.last_update = Some(Local::now());
+                    self.state.last_error = None;
+                }
+                Update::Notice(Notice::Error(message)) => {
+                    self.state.last_error = Some(message.clone());
+                    notices.push(Notice::Error(message));
+                }
+                Update::Notice(notice) => notices.push(notice),
+            }
+        }
+        notices
+    }
+}
+
+/// `config` with the keys it leaves blank read from `env`.
+fn with_env_keys(mut config: AlpacaConfig, env: impl Fn(&str) -> Option<String>) -> AlpacaConfig {
+    for (key, var) in [(&mut config.key_id, KEY_ID_ENV), (&mut config.secret_key, SECRET_KEY_ENV)] {
+        if key.trim().is_empty() {
+            *key = env(var).map(|v| v.trim().to_string()).unwrap_or_default();
+        }
+    }
+    config
+}
+
+/// Why `config` can't be used, if it can't.
+fn check(config: &AlpacaConfig) -> Result<(), String> {
+    if config.key_id.is_empty() || config.secret_key.is_empty() {
+        return Err(format!("Set {} and {}, or key_id and secret_key in [alpaca]", KEY_ID_ENV, SECRET_KEY_ENV));
+    }
+    let host = config.base_url.split_once("://").map_or(config.base_url.as_str(), |(_, rest)| rest);
+    if host.trim_end_matches('/') != PAPER_HOST {
+        return Err(format!("[alpaca] base_url must be https://{}; only paper trading is supported", PAPER_HOST));
+    }
+    Ok(())
+}
+
+fn run(config: AlpacaConfig, commands: Receiver<Command>, tx: Sender<Update>) {
+    let interval = Duration::from_secs(config.poll_secs.max(1));
+    // Only orders from this session are listed, so fills booked by an
+    // earlier one aren't booked again.
+    let since = Utc::now();
+    loop {
+        let update = match snapshot(&config, since) {
+            Ok((balances, positions, orders)) => Update::Snapshot { balances, positions, orders },
+            Err(e) => Update::Notice(Notice::Error(e)),
+        };
+        if tx.send(update).is_err() {
+            return; // UI has gone away
+        }
+        let notice = match commands.recv_timeout(interval) {
+            Ok(Command::Place(order, id)) => match place(&config, &order, &id) {
+                Ok(placed) => Notice::Message(describe(&placed)),
+                Err(e) => Notice::Rejected {
+                    id,
+                    message: format!("{} {} {} failed: {}", order.side.as_str(), order.qty, order.ticker, e),
+                },
+            },
+            Ok(Command::Cancel(id)) => match cancel(&config, &id) {
+                Ok(()) => Notice::Message(format!("Asked to cancel {}", short_id(&id))),
+                Err(e) => Notice::Error(format!("Cancelling {} failed: {}", short_id(&id), e)),
+            },
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        if tx.send(Update::Notice(notice)).is_err() {
+            return;
+        }
+    }
+}
+
+fn describe(order: &Order) -> String {
+    let price = match order.trigger {
+        Some(trigger) => format!("on {}", trigger),
+        None => order.limit.map_or("at market".to_string(), |p| format!("at {:.2}", p)),
+    };
+    format!("Placed {} {} {} {}: {} ({})", order.side.as_str(), order.qty, order.ticker, price, order.status, short_id(&order.id))
+}
+
+fn short_id(id: &str) -> &str {
+    id.get(..8).unwrap_or(id)
+}
+
+fn snapshot(config: &AlpacaConfig, since: DateTime<Utc>) -> Result<(Balances, Vec<Position>, Vec<Order>), String> {
+    let account = call(config, "GET", "/v2/account", None)?;
+    let balances = Balances {
+        cash: number(&account["cash"]).unwrap_or_default(),
+        equity: number(&account["equity"]).unwrap_or_default(),
+        buying_power: number(&account["buying_power"]).unwrap_or_default(),
+    };
+    let positions = call(config, "GET", "/v2/positions", None)?;
+    let after = since.to_rfc3339_opts(SecondsFormat::Secs, true);
+    let path = format!("/v2/orders?status=all&direction=desc&limit={}&after={}", ORDER_LIMIT, after);
+    let orders = call(config, "GET", &path, None)?;
+    Ok((
+        balances,
+        positions.as_array().map(|p| p.iter().filter_map(parse_position).collect()).unwrap_or_default(),
+        orders.as_array().map(|o| o.iter().filter_map(parse_order).collect()).unwrap_or_default(),
+    ))
+}
+
+fn place(config: &AlpacaConfig, order: &OrderRequest, client_id: &str) -> Result<Order, String> {
+    let crypto = order.ticker.is_crypto();
+    let mut body = json!({
+        "symbol": symbol(&order.ticker),
+        "qty": order.qty.to_string(),
+        "side": order.side.as_str(),
+        "type": "market",
+        "time_in_force": if crypto { "gtc" } else { "day" },
+        "client_order_id": client_id,
+    });
+    match (order.trigger, order.limit) {
+        (Some(Trigger::StopLoss(stop)), _) => {
+            body["type"] = json!("stop");
+            body["stop_price"] = json!(stop.to_string());
+        }
+        (Some(Trigger::TakeProfit(limit)), _) | (None, Some(limit)) => {
+            body["type"] = json!("limit");
+            body["limit_price"] = json!(limit.to_string());
+        }
+        (None, None) => {}
+    }
+    let placed = call(config, "POST", "/v2/orders", Some(&body))?;
+    parse_order(&placed).ok_or_else(|| "unexpected response".to_string())
+}
+
+fn cancel(config: &AlpacaConfig, id: &str) -> Result<(), String> {
+    call(config, "DELETE", &format!("/v2/orders/{}", id), None).map(|_| ())
+}
+
+/// Sends one request, returning its JSON (`null` for an empty body) or
+/// the message Alpaca gave for refusing it.
+fn call(config: &AlpacaConfig, method: &str, path: &str, body: Option<&Value>) -> Result<Value, String> {
+    chaos::inject("alpaca").map_err(|e| e.to_string())?;
+    let url = format!("{}{}", config.base_url.trim_end_matches('/'), path);
+    let headers = [("APCA-API-KEY-ID", config.key_id.as_str()), ("APCA-API-SECRET-KEY", config.secret_key.as_str())];
+    let body = body.map(Value::to_string);
+    let text = net::client()
+        .send(method, &url, &headers, body.as_deref())
+        .and_then(|response| Ok(response.into_string()?))
+        .map_err(refusal)?;
+    if text.trim().is_empty() {
+        return Ok(Value::Null);
+    }
+    serde_json::from_str(&text).map_err(|e| e.to_string())
+}
+
+/// The `message` of an error response, else the error itself.
+fn refusal(err: Box<dyn Error>) -> String {
+    match err.downcast::<ureq::Error>() {
+        Ok(err) => match *err {
+            ureq::Error::Status(code, response) => {
+                let body = response.into_string().unwrap_or_default();
+                let message = serde_json::from_str::<Value>(&body).ok().and_then(|v| v["message"].as_str().map(str::to_string));
+                format!("HTTP {}: {}", code, message.unwrap_or(body))
+            }
+            err => err.to_string(),
+        },
+        Err(err) => err.to_string(),
+    }
+}
+
+/// Alpaca's symbol for `ticker`: `BTC-USD` is `BTC/USD`.
+fn symbol(ticker: &Ticker) -> String {
+    match ticker.crypto_pair() {
+        Some((base, quote)) => format!("{}/{}", base, quote),
+        None => ticker.to_string(),
+    }
+}
+
+/// The ticker for an Alpaca symbol. Crypto comes as `BTC/USD` on orders
+/// but `BTCUSD` on positions.
+fn ticker_for(symbol: &str, asset_class: &str) -> Option<Ticker> {
+    if let Some((base, quote)) = symbol.split_once('/') {
+        return Ticker::parse(&format!("{}-{}", base, quote)).ok();
+    }
+    if asset_class == "crypto"
+        && let Some(quote) = ["USDT", "USDC", "USD"].into_iter().find(|q| symbol.len() > q.len() && symbol.ends_with(q))
+    {
+        return Ticker::parse(&format!("{}-{}", &symbol[..symbol.len() - quote.len()], quote)).ok();
+    }
+    Ticker::parse(symbol).ok()
+}
+
+/// Alpaca sends amounts as strings.
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::String(s) => s.parse().ok(),
+        other => other.as_f64(),
+    }
+}
+
+fn parse_order(v: &Value) -> Option<Order> {
+    Some(Order {
+        id: v["id"].as_str()?.to_string(),
+        ticker: ticker_for(v["symbol"].as_str()?, v["asset_class"].as_str().unwrap_or_default())?,
+        side: match v["side"].as_str()? {
+            "buy" => Side::Buy,
+            _ => Side::Sell,
+        },
+        qty: number(&v["qty"]).unwrap_or_default(),
+        limit: number(&v["limit_price"]),
+        trigger: number(&v["stop_price"]).map(Trigger::StopLoss),
+        filled_qty: number(&v["filled_qty"]).unwrap_or_default(),
+        filled_avg_price: number(&v["filled_avg_price"]),
+        status: v["status"].as_str()?.to_string(),
+        submitted_at: v["submitted_at"]
+            .as_str()
+            .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+            .map(|d| d.with_timezone(&Local)),
+        client_id: v["client_order_id"].as_str().map(str::to_string),
+    })
+}
+
+fn parse_position(v: &Value) -> Option<Position> {
+    Some(Position {
+        ticker: ticker_for(v["symbol"].as_str()?, v["asset_class"].as_str().unwrap_or_default())?,
+        qty: number(&v["qty"])?,
+        avg_entry_price: number(&v["avg_entry_price"]).unwrap_or_default(),
+        current_price: number(&v["current_price"]).unwrap_or_default(),
+        market_value: number(&v["market_value"]).unwrap_or_default(),
+        unrealized_pl: number(&v["unrealized_pl"]).unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders_and_positions_parse_with_crypto_symbols() {
+        let order: Value = serde_json::from_str(
+            r#"{"id":"61e69015-8549","symbol":"BTC/USD","asset_class":"crypto","side":"buy","qty":"0.5",
+                "filled_qty":"0.5","filled_avg_price":"64000.1","limit_price":null,"status":"filled",
+                "client_order_id":"stm-20240603143000-1",
+                "submitted_at":"2024-06-03T14:30:00.123456Z"}"#,
+        )
+        .unwrap();
+        let order = parse_order(&order).unwrap();
+        assert_eq!(order.ticker.as_str(), "BTC-USD");
+        assert_eq!((order.qty, order.filled_avg_price, order.limit), (0.5, Some(64000.1), None));
+        assert!(!order.is_open());
+        assert!(order.known_as("stm-20240603143000-1") && order.known_as("61e69015-8549"));
+        assert_eq!(symbol(&order.ticker), "BTC/USD");
+        let position: Value =
+            serde_json::from_str(r#"{"symbol":"BTCUSD","asset_class":"crypto","qty":"0.5","avg_entry_price":"64000"}"#).unwrap();
+        assert_eq!(parse_position(&position).unwrap().ticker.as_str(), "BTC-USD");
+        assert_eq!(ticker_for("BRK.B", "us_equity").unwrap().as_str(), "BRK.B");
+    }
+
+    #[test]
+    fn only_the_paper_endpoint_is_accepted() {
+        let config = AlpacaConfig { key_id: "k".to_string(), secret_key: "s".to_string(), ..AlpacaConfig::default() };
+        assert!(check(&config).is_ok());
+        let live = AlpacaConfig { base_url: "https://api.alpaca.markets".to_string(), ..config };
+        assert!(check(&live).unwrap_err().contains("paper"));
+        assert!(check(&AlpacaConfig::default()).is_err());
+    }
+
+    #[test]
+    fn keys_come_from_the_environment_unless_configured() {
+        let env = |var: &str| match var {
+            KEY_ID_ENV => Some("PKENV".to_string()),
+            SECRET_KEY_ENV => Some(" envsecret\n".to_string()),
+            _ => None,
+        };
+        let config = with_env_keys(AlpacaConfig::default(), env);
+        assert_eq!((config.key_id.as_str(), config.secret_key.as_str()), ("PKENV", "envsecret"));
+        assert!(check(&config).is_ok());
+
+        let set = AlpacaConfig { key_id: "PKTOML".to_string(), ..AlpacaConfig::default() };
+        let config = with_env_keys(set.clone(), env);
+        assert_eq!((config.key_id.as_str(), config.secret_key.as_str()), ("PKTOML", "envsecret"));
+        let config = with_env_keys(set, |_| None);
+        assert!(check(&config).unwrap_err().starts_with("Set APCA_API_KEY_ID and APCA_API_SECRET_KEY"));
+    }
+}
@@ -0,0 +1,540 @@
+//! Orders routed from the broker view to a brokerage account.
+//!
+//! The view takes lines typed as `buy|sell QTY [TICKER] [PRICE]`, where the
+//! ticker defaults to the selected stock and a price makes a limit order
+//! (`stop PRICE` a stop-loss, `tp PRICE` a take-profit), or `cancel ID`
+//! with the first characters of an open order's id. They go
+//! to whichever `Broker` `start` picks: an Alpaca paper account when
+//! `[alpaca]` is enabled (with the `alpaca` feature), otherwise the local
+//! paper engine. The view and the app only see the trait, so another
+//! brokerage is an adapter implementing it plus a case in `start`. Each
+//! fill is booked to the broker's configured account as a trade: a buy's
+//! cost as a debit and a sale's proceeds as a credit, so a ticker's net in
+//! the history is its realised result once the position is closed.
+
+#[cfg(feature = "alpaca")]
+pub mod alpaca;
+pub mod paper;
+
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Config, FeesConfig};
+use crate::ids::Ticker;
+use crate::markets;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Side::Buy => "buy",
+            Side::Sell => "sell",
+        }
+    }
+}
+
+/// Price that turns a resting order into a market one.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    /// Reached once the price falls to it for a sell, rises to it for a
+    /// buy.
+    StopLoss(f64),
+    /// Reached once the price rises to it for a sell, falls to it for a
+    /// buy.
+    TakeProfit(f64),
+}
+
+impl Trigger {
+    pub fn price(self) -> f64 {
+        match self {
+            Trigger::StopLoss(p) | Trigger::TakeProfit(p) => p,
+        }
+    }
+
+    /// Whether a `side` order triggered at this price fills at `price`.
+    pub fn reached(self, side: Side, price: f64) -> bool {
+        match (self, side) {
+            (Trigger::StopLoss(stop), Side::Sell) | (Trigger::TakeProfit(stop), Side::Buy) => price <= stop,
+            (Trigger::StopLoss(stop), Side::Buy) | (Trigger::TakeProfit(stop), Side::Sell) => price >= stop,
+        }
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trigger::StopLoss(p) => write!(f, "stop {:.2}", p),
+            Trigger::TakeProfit(p) => write!(f, "tp {:.2}", p),
+        }
+    }
+}
+
+/// An order as typed; `limit` and `trigger` are `None` for a market
+/// order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderRequest {
+    pub side: Side,
+    pub qty: f64,
+    pub ticker: Ticker,
+    pub limit: Option<f64>,
+    pub trigger: Option<Trigger>,
+}
+
+/// A line typed into the broker view.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    Place(OrderRequest),
+    /// The start of an open order's id.
+    Cancel(String),
+}
+
+impl Instruction {
+    /// Parses `buy|sell QTY [TICKER] [PRICE | stop PRICE | tp PRICE]` or
+    /// `cancel ID`. Without a ticker the order is for `selected`.
+    /// Quantities may be fractional.
+    pub fn parse(text: &str, selected: Option<&Ticker>) -> Result<Self, String> {
+        let mut words: Vec<&str> = text.split_whitespace().collect();
+        let usage = "Expected buy|sell QTY [TICKER] [PRICE | stop PRICE | tp PRICE], or cancel ID";
+        let trigger = match words.iter().rposition(|w| matches!(w.to_lowercase().as_str(), "stop" | "tp")) {
+            Some(at) if at + 2 == words.len() && at >= 2 => {
+                let price = price(words[at + 1])?;
+                let kind = words[at].to_lowercase();
+                words.truncate(at);
+                Some(if kind == "stop" { Trigger::StopLoss(price) } else { Trigger::TakeProfit(price) })
+            }
+            Some(_) => return Err(usage.to_string()),
+            None => None,
+        };
+        let (&verb, rest) = words.split_first().ok_or(usage)?;
+        let side = match verb.to_lowercase().as_str() {
+            "buy" => Side::Buy,
+            "sell" => Side::Sell,
+            "cancel" => {
+                return match rest {
+                    [id] => Ok(Instruction::Cancel(id.trim_start_matches('#').to_string())),
+                    _ => Err("Expected cancel ID".to_string()),
+                };
+            }
+            _ => return Err(usage.to_string()),
+        };
+        let (qty, ticker, limit) = match rest {
+            [qty] => (qty, None, None),
+            [qty, ticker] => (qty, Some(ticker), None),
+            [qty, ticker, price] if trigger.is_none() => (qty, Some(ticker), Some(price.trim_start_matches('@'))),
+            _ => return Err(usage.to_string()),
+        };
+        let qty = qty.parse::<f64>().ok().filter(|q| q.is_finite() && *q > 0.0).ok_or("Quantity must be above 0")?;
+        let ticker = match ticker {
+            Some(t) => Ticker::parse(t).map_err(|e| format!("Invalid ticker: {}", e))?,
+            None => selected.cloned().ok_or("Name a ticker; none is selected")?,
+        };
+        let limit = limit.map(price).transpose()?;
+        Ok(Instruction::Place(OrderRequest { side, qty, ticker, limit, trigger }))
+    }
+}
+
+fn price(text: &str) -> Result<f64, String> {
+    text.parse::<f64>().ok().filter(|p| p.is_finite() && *p > 0.0).ok_or_else(|| "Price must be above 0".to_string())
+}
+
+/// An order as the broker last reported it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Order {
+    pub id: String,
+    pub ticker: Ticker,
+    pub side: Side,
+    pub qty: f64,
+    pub limit: Option<f64>,
+    #[serde(default)]
+    pub trigger: Option<Trigger>,
+    pub filled_qty: f64,
+    /// Average price of what has filled so far.
+    pub filled_avg_price: Option<f64>,
+    /// The broker's own word for it, e.g. `new`, `partially_filled`.
+    pub status: String,
+    pub submitted_at: Option<DateTime<Local>>,
+    /// The id it was sent with, for a broker that only assigns `id` once
+    /// it has the order.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+}
+
+impl Order {
+    /// Its price as the orders tables show it, to its market's tick: the
+    /// fill's once there is one.
+    pub fn price_label(&self) -> String {
+        let rules = markets::rules_for(&self.ticker);
+        match (self.filled_avg_price, self.trigger, self.limit) {
+            (Some(avg), _, _) => rules.price(avg),
+            (None, Some(Trigger::StopLoss(stop)), _) => format!("stop {}", rules.price(stop)),
+            (None, Some(Trigger::TakeProfit(target)), _) => format!("tp {}", rules.price(target)),
+            (None, None, Some(limit)) => format!("lmt {}", rules.price(limit)),
+            (None, None, None) => "mkt".to_string(),
+        }
+    }
+
+    /// Whether it's the order `submit_order` gave `id` for.
+    pub fn known_as(&self, id: &str) -> bool {
+        self.id == id || self.client_id.as_deref() == Some(id)
+    }
+
+    /// Whether it can still fill or be cancelled.
+    pub fn is_open(&self) -> bool {
+        !matches!(self.status.as_str(), "filled" | "canceled" | "expired" | "rejected" | "replaced" | "done_for_day")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub ticker: Ticker,
+    /// Negative when short.
+    pub qty: f64,
+    pub avg_entry_price: f64,
+    pub current_price: f64,
+    pub market_value: f64,
+    pub unrealized_pl: f64,
+}
+
+impl Position {
+    /// The position as of `price`, newer than the broker's last report.
+    pub fn repriced(&self, price: f64) -> Position {
+        Position {
+            current_price: price,
+            market_value: self.qty * price,
+            unrealized_pl: self.qty * (price - self.avg_entry_price),
+            ..self.clone()
+        }
+    }
+
+    /// How far the price has moved from the average entry, in percent,
+    /// positive when in profit; a short profits from a fall.
+    pub fn pct_from_entry(&self) -> Option<f64> {
+        (self.avg_entry_price > 0.0)
+            .then(|| (self.current_price / self.avg_entry_price - 1.0) * 100.0 * self.qty.signum())
+    }
+
+    /// What it cost, fees included: `holding`'s cost per share, from the
+    /// tax lots of the fills booked, or without them the average entry and
+    /// one fee to open it.
+    pub fn cost(&self, holding: Option<(f64, f64)>, fees: &FeesConfig) -> f64 {
+        let entry = self.qty.abs() * self.avg_entry_price;
+        match holding {
+            Some((qty, cost)) if qty > 0.0 => cost / qty * self.qty.abs(),
+            _ => entry + fees.charge(entry),
+        }
+    }
+
+    /// The price selling it all at would bring `cost` back after the fee
+    /// on the sale. `None` for a short.
+    pub fn break_even(&self, cost: f64, fees: &FeesConfig) -> Option<f64> {
+        let kept = self.qty * (1.0 - fees.pct / 100.0);
+        (kept > 0.0).then(|| (cost + fees.per_fill) / kept)
+    }
+
+    /// Unrealized P&L after `cost` and the fee selling it now would pay;
+    /// `unrealized_pl` is before fees. `None` for a short.
+    pub fn net_pl(&self, cost: f64, fees: &FeesConfig) -> Option<f64> {
+        let value = self.qty * self.current_price;
+        (self.qty > 0.0).then(|| value - fees.charge(value) - cost)
+    }
+
+    /// The open stop-loss or take-profit closing it that the price is
+    /// nearest, and the move to reach it in percent.
+    pub fn nearest_exit(&self, orders: &[Order]) -> Option<(Trigger, f64)> {
+        let closing = if self.qty > 0.0 { Side::Sell } else { Side::Buy };
+        let price = self.current_price;
+        orders
+            .iter()
+            .filter(|o| o.is_open() && o.ticker == self.ticker && o.side == closing)
+            .filter_map(|o| o.trigger)
+            .filter(|_| price > 0.0)
+            .map(|trigger| (trigger, (trigger.price() / price - 1.0) * 100.0))
+            .min_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+    }
+}
+
+/// Balances of the brokerage account.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Balances {
+    pub cash: f64,
+    pub equity: f64,
+    pub buying_power: f64,
+}
+
+/// What the broker view shows, as of the last poll.
+#[derive(Debug, Clone, Default)]
+pub struct BrokerState {
+    pub balances: Option<Balances>,
+    pub positions: Vec<Position>,
+    /// This session's orders, newest first.
+    pub orders: Vec<Order>,
+    pub last_update: Option<DateTime<Local>>,
+    pub last_error: Option<String>,
+}
+
+/// Shares that filled since the last poll, at their average price.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fill {
+    pub order_id: String,
+    pub ticker: Ticker,
+    pub side: Side,
+    pub qty: f64,
+    pub price: f64,
+}
+
+impl Fill {
+    /// Cash the fill moved: negative for a buy.
+    pub fn amount(&self) -> f64 {
+        let value = self.qty * self.price;
+        match self.side {
+            Side::Buy => -value,
+            Side::Sell => value,
+        }
+    }
+
+    /// Note of the trade booked for it, naming the broker it came from.
+    pub fn note(&self, broker: &str) -> String {
+        let id: String = self.order_id.chars().take(8).collect();
+        format!("{} {} {} @ {:.2} ({})", broker, self.side.as_str(), self.qty, self.price, id)
+    }
+}
+
+/// How much of each order has been seen filled, to turn the cumulative
+/// figures the broker reports into the fills since the last poll.
+#[derive(Debug, Default)]
+pub struct FillTracker {
+    // Order id to filled quantity and its total cost.
+    filled: HashMap<String, (f64, f64)>,
+}
+
+impl FillTracker {
+    pub fn update(&mut self, orders: &[Order]) -> Vec<Fill> {
+        let mut fills = Vec::new();
+        for order in orders {
+            let Some(avg) = order.filled_avg_price else {
+                continue;
+            };
+            let cost = order.filled_qty * avg;
+            let (seen_qty, seen_cost) = self.filled.get(&order.id).copied().unwrap_or_default();
+            let qty = order.filled_qty - seen_qty;
+            if qty <= f64::EPSILON {
+                continue;
+            }
+            self.filled.insert(order.id.clone(), (order.filled_qty, cost));
+            fills.push(Fill {
+                order_id: order.id.clone(),
+                ticker: order.ticker.clone(),
+                side: order.side,
+                qty,
+                price: (cost - seen_cost) / qty,
+            });
+        }
+        fills
+    }
+}
+
+/// What the broker feed reports back to the app.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notice {
+    Fill(Fill),
+    /// An order was placed or cancelled.
+    Message(String),
+    Error(String),
+    /// The order `submit_order` gave `id` for was refused before the
+    /// broker listed it.
+    Rejected { id: String, message: String },
+}
+
+/// An order handed to a broker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Submitted {
+    /// What it's `known_as` in `BrokerState::orders` once reported.
+    pub id: String,
+    /// What to show while it's worked.
+    pub message: String,
+}
+
+/// A brokerage the broker view's orders are routed to. Adapters report
+/// through `poll`, called every frame, so one that talks to a remote API
+/// keeps the calls on a thread of its own.
+pub trait Broker {
+    /// e.g. `Alpaca paper`, for the view's title and booked trades' notes.
+    fn name(&self) -> &'static str;
+
+    /// Account in account_summary.csv that fills are booked to; empty
+    /// books nothing.
+    fn booking_account(&self) -> &str;
+
+    /// Sends `order`, returning the id to follow it by.
+    fn submit_order(&mut self, order: OrderRequest) -> Result<Submitted, String>;
+
+    /// Cancels the open order whose id starts with `prefix`.
+    fn cancel(&mut self, prefix: &str) -> Result<String, String>;
+
+    /// Everything the view shows, as of the last report.
+    fn state(&self) -> &BrokerState;
+
+    /// Applies what arrived since the last call, returning the fills to
+    /// book and the messages to show.
+    fn poll(&mut self) -> Vec<Notice>;
+
+    fn positions(&self) -> &[Position] {
+        &self.state().positions
+    }
+
+    /// Balances, once the broker has reported them.
+    fn account(&self) -> Option<Balances> {
+        self.state().balances
+    }
+}
+
+/// The broker the config picks: Alpaca when `[alpaca]` is enabled, the
+/// paper engine otherwise. Fails when Alpaca is enabled in a build without
+/// the `alpaca` feature.
+pub fn start(config: &Config) -> Result<Box<dyn Broker>, String> {
+    if config.alpaca.enabled {
+        #[cfg(feature = "alpaca")]
+        return Ok(Box::new(alpaca::AlpacaFeed::start(&config.alpaca)));
+        #[cfg(not(feature = "alpaca"))]
+        return Err("[alpaca] needs a build with --features alpaca".to_string());
+    }
+    Ok(Box::new(paper::PaperBroker::open(paper::PAPER_PATH, &config.paper, config.fees)))
+}
+
+/// The id of the one open order in `orders` whose id starts with `prefix`.
+pub fn open_order_id(orders: &[Order], prefix: &str) -> Result<String, String> {
+    let prefix = prefix.to_lowercase();
+    let matches: Vec<&Order> = orders.iter().filter(|o| o.is_open() && o.id.starts_with(&prefix)).collect();
+    match matches[..] {
+        [order] => Ok(order.id.clone()),
+        [] => Err(format!("No open order starting {}", prefix)),
+        _ => Err(format!("{} open orders start {}; type more of the id", matches.len(), prefix)),
+    }
+}
+
+/// The broker view: positions and orders over the line being typed.
+#[derive(Debug, Default)]
+pub struct BrokerView {
+    pub input: String,
+    /// Outcome of the last line, or what to type.
+    pub message: String,
+    /// Row of the open orders panel that Delete cancels.
+    pub selected: usize,
+}
+
+impl BrokerState {
+    /// The orders still resting, newest first.
+    pub fn open_orders(&self) -> impl Iterator<Item = &Order> {
+        self.orders.iter().filter(|o| o.is_open())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instructions_default_to_the_selected_ticker() {
+        let aapl = Ticker::parse("AAPL").unwrap();
+        let order = |side, qty, ticker: &str, limit| {
+            Instruction::Place(OrderRequest { side, qty, ticker: Ticker::parse(ticker).unwrap(), limit, trigger: None })
+        };
+        assert_eq!(Instruction::parse("buy 10", Some(&aapl)), Ok(order(Side::Buy, 10.0, "AAPL", None)));
+        assert_eq!(Instruction::parse("SELL 0.5 btc-usd @64000", None), Ok(order(Side::Sell, 0.5, "BTC-USD", Some(64000.0))));
+        assert_eq!(Instruction::parse("cancel #3f2a", None), Ok(Instruction::Cancel("3f2a".to_string())));
+        assert!(Instruction::parse("buy 10", None).is_err());
+        assert!(Instruction::parse("buy -1 AAPL", None).is_err());
+        assert!(Instruction::parse("hold 1 AAPL", None).is_err());
+        let Ok(Instruction::Place(stop)) = Instruction::parse("sell 10 stop 95", Some(&aapl)) else { panic!("expected an order") };
+        assert_eq!((stop.ticker, stop.trigger), (aapl, Some(Trigger::StopLoss(95.0))));
+        assert!(matches!(Instruction::parse("sell 1 MSFT TP 120", None), Ok(Instruction::Place(o)) if o.trigger == Some(Trigger::TakeProfit(120.0))));
+        assert!(Instruction::parse("sell 1 MSFT 110 stop 95", None).is_err());
+        assert!(Instruction::parse("sell 1 stop", None).is_err());
+        assert!(Trigger::StopLoss(95.0).reached(Side::Sell, 94.0) && !Trigger::StopLoss(95.0).reached(Side::Buy, 94.0));
+    }
+
+    #[test]
+    fn positions_measure_from_entry_and_to_the_nearest_exit() {
+        let aapl = Ticker::parse("AAPL").unwrap();
+        let position = Position {
+            ticker: aapl.clone(),
+            qty: 10.0,
+            avg_entry_price: 100.0,
+            current_price: 100.0,
+            market_value: 1_000.0,
+            unrealized_pl: 0.0,
+        };
+        let order = |side, trigger, status: &str| Order {
+            id: "1".to_string(),
+            ticker: aapl.clone(),
+            side,
+            qty: 10.0,
+            limit: None,
+            trigger: Some(trigger),
+            filled_qty: 0.0,
+            filled_avg_price: None,
+            status: status.to_string(),
+            submitted_at: None,
+            client_id: None,
+        };
+        let orders = [
+            order(Side::Sell, Trigger::StopLoss(90.0), "new"),
+            order(Side::Sell, Trigger::TakeProfit(125.0), "new"),
+            order(Side::Sell, Trigger::TakeProfit(121.0), "canceled"),
+            order(Side::Buy, Trigger::StopLoss(119.0), "new"),
+        ];
+        let up = position.repriced(120.0);
+        assert_eq!((up.market_value, up.unrealized_pl), (1_200.0, 200.0));
+        let fees = FeesConfig { per_fill: 1.0, pct: 0.0 };
+        let cost = up.cost(None, &fees);
+        assert_eq!((cost, up.cost(Some((20.0, 2_040.0)), &fees)), (1_001.0, 1_020.0));
+        assert_eq!((up.break_even(cost, &fees), up.net_pl(cost, &fees)), (Some(100.2), Some(198.0)));
+        assert!((up.pct_from_entry().unwrap() - 20.0).abs() < 1e-9);
+        let (trigger, pct) = up.nearest_exit(&orders).unwrap();
+        assert_eq!(trigger, Trigger::TakeProfit(125.0));
+        assert!((pct - 25.0 / 6.0).abs() < 1e-9);
+        assert_eq!(position.repriced(95.0).nearest_exit(&orders).map(|(t, _)| t), Some(Trigger::StopLoss(90.0)));
+
+        let short = Position { qty: -10.0, ..position.clone() }.repriced(90.0);
+        assert!((short.pct_from_entry().unwrap() - 10.0).abs() < 1e-9);
+        assert_eq!(short.nearest_exit(&orders).map(|(t, _)| t), Some(Trigger::StopLoss(119.0)));
+    }
+
+    #[test]
+    fn partial_fills_are_booked_once_at_their_own_price() {
+        let mut order = Order {
+            id: "abc".to_string(),
+            ticker: Ticker::parse("AAPL").unwrap(),
+            side: Side::Buy,
+            qty: 10.0,
+            limit: None,
+            trigger: None,
+            filled_qty: 4.0,
+            filled_avg_price: Some(100.0),
+            status: "partially_filled".to_string(),
+            submitted_at: None,
+            client_id: None,
+        };
+        let mut tracker = FillTracker::default();
+        let fills = tracker.update(std::slice::from_ref(&order));
+        assert_eq!((fills[0].qty, fills[0].price, fills[0].amount()), (4.0, 100.0, -400.0));
+        assert!(tracker.update(std::slice::from_ref(&order)).is_empty());
+        // Six more at 105 bring the average to 103.
+        order.filled_qty = 10.0;
+        order.filled_avg_price = Some(103.0);
+        let fills = tracker.update(&[order]);
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].qty, 6.0);
+        assert!((fills[0].price - 105.0).abs() < 1e-9);
+    }
+}
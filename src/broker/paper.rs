@@ -0,0 +1,398 @@
+//! The paper engine: a local account the broker view trades on when no
+//! brokerage is set up.
+//!
+//! Market orders fill at once at the ticker's latest stored close; limit
+//! orders rest until a close reaches their price, and stop-loss and
+//! take-profit orders until a close crosses their trigger, then fill at
+//! that close. Resting orders are checked every second, so new bars from a
+//! download, the daemon or the stream are picked up. Buys need the
+//! cash and sales the shares, there being no margin or shorting, and each
+//! fill pays the `[fees]` commission out of the cash. A split
+//! of a held ticker multiplies its shares and divides their average cost
+//! by its ratio once the split is known (see `splits`). The
+//! account, resting orders included, is kept in `PAPER_PATH`, so an order
+//! still open when the app closes can fill, and be booked, in a later
+//! session.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::broker::{self, Balances, Broker, BrokerState, Fill, Notice, Order, OrderRequest, Position, Side, Submitted};
+#[cfg(test)]
+use crate::broker::Trigger;
+use crate::config::{FeesConfig, PaperConfig};
+use crate::date_range::DateRange;
+use crate::ids::Ticker;
+use crate::prices;
+use crate::splits::{self, Split};
+
+pub const PAPER_PATH: &str = "paper_account.json";
+/// How often resting orders are checked against the stored closes.
+const CHECK_EVERY: Duration = Duration::from_secs(1);
+/// Finished orders kept, the newest; open ones are always kept.
+const MAX_ORDERS: usize = 200;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Holding {
+    qty: f64,
+    avg_price: f64,
+    /// The latest split restated for, or when first bought; older
+    /// accounts' holdings are taken as restated up to when they're opened.
+    #[serde(default)]
+    as_of: Option<NaiveDate>,
+}
+
+/// What's saved to `PAPER_PATH`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Book {
+    cash: f64,
+    holdings: BTreeMap<Ticker, Holding>,
+    /// Newest first.
+    orders: Vec<Order>,
+    next_id: u64,
+}
+
+impl Book {
+    fn new(cash: f64) -> Self {
+        Self { cash, holdings: BTreeMap::new(), orders: Vec::new(), next_id: 1 }
+    }
+}
+
+pub struct PaperBroker {
+    path: PathBuf,
+    account: String,
+    book: Book,
+    /// Why the saved account couldn't be read; nothing is traded or saved
+    /// until it's fixed, so it isn't overwritten.
+    broken: Option<String>,
+    state: BrokerState,
+    fees: FeesConfig,
+    /// Latest price of a ticker.
+    quote: fn(&Ticker) -> Option<f64>,
+    splits: fn(&Ticker) -> Vec<Split>,
+    /// Notices for the next poll.
+    pending: Vec<Notice>,
+    last_check: Option<Instant>,
+}
+
+impl PaperBroker {
+    /// The account saved at `path`, or a new one with `[paper]
+    /// starting_cash`, charging `fees` on each fill.
+    pub fn open(path: &str, config: &PaperConfig, fees: FeesConfig) -> Self {
+        let (book, broken) = match fs::read_to_string(path) {
+            Ok(text) => match serde_json::from_str(&text) {
+                Ok(book) => (book, None),
+                Err(e) => (Book::new(0.0), Some(format!("Can't read {}: {}; fix or delete it", path, e))),
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (Book::new(config.starting_cash), None),
+            Err(e) => (Book::new(0.0), Some(format!("Can't read {}: {}", path, e))),
+        };
+        let mut paper = Self {
+            path: PathBuf::from(path),
+            account: config.account.clone(),
+            book,
+            broken,
+            state: BrokerState::default(),
+            fees,
+            quote: latest_close,
+            splits: splits::known,
+            pending: Vec::new(),
+            last_check: None,
+        };
+        paper.refresh_state();
+        paper
+    }
+
+    /// Fills the order at `index` if `price` reaches it, or rejects it if
+    /// the account can't cover it.
+    fn try_fill(&mut self, index: usize, price: f64) -> Option<Notice> {
+        let order = &self.book.orders[index];
+        let reached = match (order.side, order.trigger, order.limit) {
+            (side, Some(trigger), _) => trigger.reached(side, price),
+            (_, None, None) => true,
+            (Side::Buy, None, Some(limit)) => price <= limit,
+            (Side::Sell, None, Some(limit)) => price >= limit,
+        };
+        if !reached {
+            return None;
+        }
+        let (ticker, side, qty) = (order.ticker.clone(), order.side, order.qty);
+        let held = self.book.holdings.get(&ticker).map_or(0.0, |h| h.qty);
+        let fee = self.fees.charge(qty * price);
+        let refusal = match side {
+            Side::Buy if qty * price + fee > self.book.cash + 1e-9 => {
+                Some(format!("costs {:.2} with {:.2} cash", qty * price + fee, self.book.cash))
+            }
+            Side::Sell if qty > held + 1e-9 => Some(format!("only {} held", held)),
+            _ => None,
+        };
+        let order = &mut self.book.orders[index];
+        if let Some(why) = refusal {
+            order.status = "rejected".to_string();
+            return Some(Notice::Error(format!("Rejected {} {} {} (#{}): {}", side.as_str(), qty, ticker, order.id, why)));
+        }
+        order.status = "filled".to_string();
+        order.filled_qty = qty;
+        order.filled_avg_price = Some(price);
+        let order_id = order.id.clone();
+        let today = Some(Local::now().date_naive());
+        let holding = self.book.holdings.entry(ticker.clone()).or_insert(Holding { qty: 0.0, avg_price: price, as_of: today });
+        match side {
+            Side::Buy => {
+                holding.avg_price = (holding.avg_price * holding.qty + price * qty) / (holding.qty + qty);
+                holding.qty += qty;
+                self.book.cash -= qty * price + fee;
+            }
+            Side::Sell => {
+                holding.qty -= qty;
+                self.book.cash += qty * price - fee;
+            }
+        }
+        if holding.qty <= 1e-9 {
+            self.book.holdings.remove(&ticker);
+        }
+        Some(Notice::Fill(Fill { order_id, ticker, side, qty, price }))
+    }
+
+    /// Restates holdings for the splits since they were last, before any
+    /// more shares are bought at the new price.
+    fn apply_splits(&mut self) -> bool {
+        let today = Local::now().date_naive();
+        let mut changed = false;
+        for (ticker, holding) in &mut self.book.holdings {
+            let Some(since) = holding.as_of else {
+                holding.as_of = Some(today);
+                changed = true;
+                continue;
+            };
+            for split in splits::between(&(self.splits)(ticker), since, today) {
+                holding.qty *= split.ratio;
+                holding.avg_price /= split.ratio;
+                holding.as_of = Some(split.date);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Checks every open order against its latest close.
+    fn check_orders(&mut self) -> bool {
+        let mut changed = false;
+        for i in (0..self.book.orders.len()).rev() {
+            if !self.book.orders[i].is_open() {
+                continue;
+            }
+            let Some(price) = (self.quote)(&self.book.orders[i].ticker) else {
+                continue;
+            };
+            if let Some(notice) = self.try_fill(i, price) {
+                self.pending.push(notice);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    fn refresh_state(&mut self) {
+        let positions: Vec<Position> = self
+            .book
+            .holdings
+            .iter()
+            .map(|(ticker, h)| {
+                let price = (self.quote)(ticker).unwrap_or(h.avg_price);
+                Position {
+                    ticker: ticker.clone(),
+                    qty: h.qty,
+                    avg_entry_price: h.avg_price,
+                    current_price: price,
+                    market_value: h.qty * price,
+                    unrealized_pl: h.qty * (price - h.avg_price),
+                }
+            })
+            .collect();
+        let value: f64 = positions.iter().map(|p| p.market_value).sum();
+        self.state.balances = Some(Balances { cash: self.book.cash, equity: self.book.cash + value, buying_power: self.book.cash });
+        self.state.positions = positions;
+        self.state.orders = self.book.orders.clone();
+        self.state.last_update = Some(Local::now());
+        self.state.last_error = self.broken.clone();
+    }
+
+    /// Writes the account, then rename, so a crash mid-write keeps the old
+    /// one.
+    fn save(&mut self) {
+        let mut finished = 0;
+        self.book.orders.retain(|o| {
+            finished += usize::from(!o.is_open());
+            o.is_open() || finished <= MAX_ORDERS
+        });
+        let tmp = self.path.with_extension("json.tmp");
+        let saved = serde_json::to_string_pretty(&self.book)
+            .map_err(io::Error::other)
+            .and_then(|text| fs::write(&tmp, text))
+            .and_then(|()| fs::rename(&tmp, &self.path));
+        if let Err(e) = saved {
+            self.pending.push(Notice::Error(format!("Failed to save {}: {}", self.path.display(), e)));
+        }
+    }
+
+    fn writable(&self) -> Result<(), String> {
+        match &self.broken {
+            Some(why) => Err(why.clone()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Broker for PaperBroker {
+    fn name(&self) -> &'static str {
+        "Paper"
+    }
+
+    fn booking_account(&self) -> &str {
+        &self.account
+    }
+
+    fn submit_order(&mut self, order: OrderRequest) -> Result<Submitted, String> {
+        self.writable()?;
+        self.apply_splits();
+        let price = (self.quote)(&order.ticker).ok_or_else(|| format!("No stored price for {}; download it first", order.ticker))?;
+        let id = self.book.next_id.to_string();
+        self.book.next_id += 1;
+        let placed = format!("Placed {} {} {} {} (#{})", order.side.as_str(), order.qty, order.ticker, describe(&order), id);
+        self.book.orders.insert(
+            0,
+            Order {
+                id: id.clone(),
+                ticker: order.ticker,
+                side: order.side,
+                qty: order.qty,
+                limit: order.limit,
+                trigger: order.trigger,
+                filled_qty: 0.0,
+                filled_avg_price: None,
+                status: "new".to_string(),
+                submitted_at: Some(Local::now()),
+                client_id: None,
+            },
+        );
+        if let Some(notice) = self.try_fill(0, price) {
+            self.pending.push(notice);
+        }
+        self.save();
+        self.refresh_state();
+        Ok(Submitted { id, message: placed })
+    }
+
+    fn cancel(&mut self, prefix: &str) -> Result<String, String> {
+        self.writable()?;
+        let id = broker::open_order_id(&self.book.orders, prefix)?;
+        if let Some(order) = self.book.orders.iter_mut().find(|o| o.id == id) {
+            order.status = "canceled".to_string();
+        }
+        self.save();
+        self.refresh_state();
+        Ok(format!("Cancelled #{}", id))
+    }
+
+    fn state(&self) -> &BrokerState {
+        &self.state
+    }
+
+    fn poll(&mut self) -> Vec<Notice> {
+        if self.broken.is_none() && self.last_check.is_none_or(|at| at.elapsed() >= CHECK_EVERY) {
+            self.last_check = Some(Instant::now());
+            if self.apply_splits() | self.check_orders() {
+                self.save();
+            }
+            self.refresh_state();
+        }
+        std::mem::take(&mut self.pending)
+    }
+}
+
+fn describe(order: &OrderRequest) -> String {
+    match (order.trigger, order.limit) {
+        (Some(trigger), _) => format!("at market on {}", trigger),
+        (None, Some(limit)) => format!("limit {:.2}", limit),
+        (None, None) => "at market".to_string(),
+    }
+}
+
+fn latest_close(ticker: &Ticker) -> Option<f64> {
+    prices::bars(ticker, DateRange::default()).ok()?.last().map(|b| b.close)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(side: Side, qty: f64, limit: Option<f64>) -> OrderRequest {
+        OrderRequest { side, qty, ticker: Ticker::parse("AAPL").unwrap(), limit, trigger: None }
+    }
+
+    #[test]
+    fn limit_orders_rest_until_the_close_reaches_them() {
+        let path = std::env::temp_dir().join(format!("stm-{}-paper.json", std::process::id()));
+        let config = PaperConfig { starting_cash: 1_000.0, account: String::new() };
+        let mut paper = PaperBroker::open(&path.to_string_lossy(), &config, FeesConfig::default());
+        paper.quote = |_| Some(100.0);
+        paper.submit_order(order(Side::Buy, 5.0, None)).unwrap();
+        paper.submit_order(order(Side::Sell, 2.0, Some(110.0))).unwrap();
+        assert!(matches!(&paper.poll()[..], [Notice::Fill(f)] if f.qty == 5.0 && f.amount() == -500.0));
+        assert_eq!(paper.account().map(|b| b.cash), Some(500.0));
+
+        paper.quote = |_| Some(110.0);
+        paper.last_check = None;
+        assert!(matches!(&paper.poll()[..], [Notice::Fill(f)] if f.side == Side::Sell && f.price == 110.0));
+        assert_eq!(paper.positions()[0].qty, 3.0);
+        // No shorting, and the saved account comes back as it was.
+        paper.submit_order(order(Side::Sell, 4.0, None)).unwrap();
+        assert!(matches!(&paper.poll()[..], [Notice::Error(_)]));
+        let reopened = PaperBroker::open(&path.to_string_lossy(), &config, FeesConfig::default());
+        assert_eq!(reopened.book, paper.book);
+
+        // A 2:1 split since the holding was bought doubles it at half the
+        // cost, once.
+        let aapl = Ticker::parse("AAPL").unwrap();
+        paper.book.holdings.get_mut(&aapl).unwrap().as_of = NaiveDate::from_ymd_opt(2020, 1, 1);
+        paper.splits = |_| vec![Split { date: NaiveDate::from_ymd_opt(2020, 6, 1).unwrap(), ratio: 2.0 }];
+        for _ in 0..2 {
+            paper.last_check = None;
+            paper.poll();
+        }
+        assert_eq!((paper.positions()[0].qty, paper.positions()[0].avg_entry_price), (6.0, 50.0));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn stops_and_take_profits_fill_once_a_close_crosses_them() {
+        let path = std::env::temp_dir().join(format!("stm-{}-paper-stops.json", std::process::id()));
+        let config = PaperConfig { starting_cash: 1_000.0, account: String::new() };
+        let mut paper = PaperBroker::open(&path.to_string_lossy(), &config, FeesConfig { per_fill: 1.0, pct: 0.0 });
+        paper.quote = |_| Some(100.0);
+        paper.submit_order(order(Side::Buy, 4.0, None)).unwrap();
+        let bracket = |trigger| OrderRequest { trigger: Some(trigger), ..order(Side::Sell, 2.0, None) };
+        paper.submit_order(bracket(Trigger::StopLoss(95.0))).unwrap();
+        paper.submit_order(bracket(Trigger::TakeProfit(110.0))).unwrap();
+        assert_eq!(paper.poll().len(), 1);
+        assert_eq!(paper.state().orders.iter().filter(|o| o.is_open()).count(), 2);
+
+        paper.quote = |_| Some(94.0);
+        paper.last_check = None;
+        assert!(matches!(&paper.poll()[..], [Notice::Fill(f)] if f.price == 94.0 && f.qty == 2.0));
+        paper.quote = |_| Some(111.0);
+        paper.last_check = None;
+        assert!(matches!(&paper.poll()[..], [Notice::Fill(f)] if f.price == 111.0));
+        assert!(paper.positions().is_empty());
+        // Three fills at a 1.00 commission each.
+        assert_eq!(paper.account().map(|b| b.cash), Some(1_007.0));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,207 @@
+//! Shareable bundles of alert rules and ML list screens.
+//!
+//! A bundle is a small TOML file: a `[bundle]` table naming it, the alert
+//! rules per ticker that `stm daemon` checks (`[daemon.alerts]`), and the
+//! `[moves]` thresholds the ML list highlights by. `stm bundle export`
+//! writes the current ones; `stm bundle import` validates a bundle and
+//! previews what it would change, and only writes it into `stm.toml` with
+//! `--apply`. Imported rules are added to those already set; imported
+//! thresholds replace the ones they name.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::alerts::AlertRule;
+use crate::config::{self, Config, MovesConfig};
+use crate::ids::Ticker;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BundleInfo {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub author: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Bundle {
+    pub bundle: BundleInfo,
+    #[serde(default)]
+    pub alerts: BTreeMap<Ticker, Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moves: Option<MovesConfig>,
+}
+
+/// Why a bundle was rejected: every problem found, not just the first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invalid(pub Vec<String>);
+
+impl fmt::Display for Invalid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid bundle: {}", self.0.join("; "))
+    }
+}
+
+impl Error for Invalid {}
+
+impl Bundle {
+    /// The alert rules and `[moves]` screen set in `config`.
+    pub fn from_config(config: &Config, info: BundleInfo) -> Self {
+        let moves = (config.moves != MovesConfig::default()).then(|| config.moves.clone());
+        Self { bundle: info, alerts: config.daemon.alerts.clone(), moves }
+    }
+
+    pub fn to_toml(&self) -> Result<String, Box<dyn Error>> {
+        Ok(format!("# stm bundle: import with `stm bundle import FILE`\n{}", toml::to_string(self)?))
+    }
+
+    /// Parses and validates a bundle file's contents.
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let bundle: Bundle = toml::from_str(text)?;
+        let mut problems = Vec::new();
+        if bundle.bundle.name.trim().is_empty() {
+            problems.push("[bundle] has no name".to_string());
+        }
+        for (ticker, rules) in &bundle.alerts {
+            for rule in rules {
+                if let Err(e) = AlertRule::parse(rule) {
+                    problems.push(format!("{} alert {:?}: {}", ticker, rule, e));
+                }
+            }
+        }
+        if let Some(moves) = &bundle.moves {
+            let thresholds = moves.threshold.iter().map(|&t| ("threshold".to_string(), t));
+            for (what, t) in thresholds.chain(moves.tickers.iter().map(|(k, &t)| (k.to_string(), t))) {
+                if !(t.is_finite() && t > 0.0) {
+                    problems.push(format!("[moves] {} must be a positive percent", what));
+                }
+            }
+        }
+        if problems.is_empty() { Ok(bundle) } else { Err(Box::new(Invalid(problems))) }
+    }
+
+    /// One line per change importing the bundle would make to `config`.
+    pub fn preview(&self, config: &Config) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (ticker, rules) in &self.alerts {
+            let existing = config.daemon.alerts.get(ticker);
+            for rule in rules {
+                let have = existing.is_some_and(|r| r.contains(rule));
+                lines.push(format!("{} alert {} {}", if have { "=" } else { "+" }, ticker, rule));
+            }
+        }
+        if let Some(moves) = &self.moves {
+            if let Some(t) = moves.threshold {
+                lines.push(change("moves threshold", config.moves.threshold, t));
+            }
+            for (ticker, &t) in &moves.tickers {
+                lines.push(change(&format!("moves {}", ticker), config.moves.tickers.get(ticker).copied(), t));
+            }
+        }
+        if lines.is_empty() {
+            lines.push("(nothing to import)".to_string());
+        }
+        lines
+    }
+
+    /// The `[daemon.alerts]` and `[moves]` settings with the bundle merged in.
+    pub fn merged(&self, config: &Config) -> (BTreeMap<Ticker, Vec<String>>, MovesConfig) {
+        let mut alerts = config.daemon.alerts.clone();
+        for (ticker, rules) in &self.alerts {
+            let entry = alerts.entry(ticker.clone()).or_default();
+            for rule in rules {
+                if !entry.contains(rule) {
+                    entry.push(rule.clone());
+                }
+            }
+        }
+        let mut moves = config.moves.clone();
+        if let Some(imported) = &self.moves {
+            moves.threshold = imported.threshold.or(moves.threshold);
+            moves.tickers.extend(imported.tickers.iter().map(|(k, &v)| (k.clone(), v)));
+        }
+        (alerts, moves)
+    }
+
+    /// Writes the merged settings into the config at `path`, keeping the
+    /// rest of the file as it is.
+    pub fn apply(&self, path: &str, config: &Config) -> Result<(), Box<dyn Error>> {
+        let (alerts, moves) = self.merged(config);
+        if !self.alerts.is_empty() {
+            config::save_section(path, "daemon.alerts", &toml::to_string(&alerts)?)?;
+        }
+        if self.moves.is_some() {
+            if let Some(t) = moves.threshold {
+                config::save_section(path, "moves", &format!("threshold = {:?}", t))?;
+            }
+            if !moves.tickers.is_empty() {
+                config::save_section(path, "moves.tickers", &toml::to_string(&moves.tickers)?)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn change(what: &str, old: Option<f64>, new: f64) -> String {
+    match old {
+        Some(old) if old == new => format!("= {} {}", what, new),
+        Some(old) => format!("~ {} {} -> {}", what, old, new),
+        None => format!("+ {} {}", what, new),
+    }
+}
+
+/// Reads and validates the bundle at `path`.
+pub fn load(path: &str) -> Result<Bundle, Box<dyn Error>> {
+    Bundle::parse(&fs::read_to_string(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticker(s: &str) -> Ticker {
+        Ticker::parse(s).unwrap()
+    }
+
+    #[test]
+    fn parse_lists_every_problem() {
+        let text = "[bundle]\nname = \"\"\n[alerts]\nAAPL = [\"close > 1\", \"close >> 1\"]\n[moves]\nthreshold = -2.0\n";
+        let err = Bundle::parse(text).unwrap_err();
+        let invalid = err.downcast_ref::<Invalid>().unwrap();
+        assert_eq!(invalid.0.len(), 3);
+        assert!(invalid.0[1].contains("close >> 1"));
+    }
+
+    #[test]
+    fn import_adds_rules_and_replaces_thresholds() {
+        let mut config = Config::default();
+        config.daemon.alerts.insert(ticker("AAPL"), vec!["close > 150".to_string()]);
+        config.moves.threshold = Some(2.0);
+        let bundle = Bundle::parse(
+            "[bundle]\nname = \"dips\"\n[alerts]\nAAPL = [\"close > 150\", \"change% <= -3\"]\n[moves]\nthreshold = 3.0\n[moves.tickers]\nTSLA = 6.0\n",
+        )
+        .unwrap();
+        assert_eq!(
+            bundle.preview(&config),
+            vec!["= alert AAPL close > 150", "+ alert AAPL change% <= -3", "~ moves threshold 2 -> 3", "+ moves TSLA 6"]
+        );
+        let (alerts, moves) = bundle.merged(&config);
+        assert_eq!(alerts[&ticker("AAPL")], vec!["close > 150".to_string(), "change% <= -3".to_string()]);
+        assert_eq!(moves.threshold, Some(3.0));
+        assert_eq!(moves.tickers[&ticker("TSLA")], 6.0);
+    }
+
+    #[test]
+    fn exported_bundles_parse_back() {
+        let mut config = Config::default();
+        config.daemon.alerts.insert(ticker("MSFT"), vec!["volume >= 5m".to_string()]);
+        let info = BundleInfo { name: "mine".to_string(), ..BundleInfo::default() };
+        let bundle = Bundle::from_config(&config, info);
+        assert_eq!(Bundle::parse(&bundle.to_toml().unwrap()).unwrap(), bundle);
+    }
+}
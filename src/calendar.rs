@@ -0,0 +1,200 @@
+//! NYSE trading calendar, used to spot sessions missing from stored history.
+//! Crypto pairs trade every day, so for them every day is a session.
+
+use std::collections::HashSet;
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+use crate::ids::Ticker;
+
+/// Which days a ticker trades on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sessions {
+    /// Exchange trading days: weekdays that aren't NYSE holidays.
+    Exchange,
+    /// Every day, around the clock.
+    Continuous,
+}
+
+impl Sessions {
+    pub fn for_ticker(ticker: &Ticker) -> Self {
+        if ticker.is_crypto() { Sessions::Continuous } else { Sessions::Exchange }
+    }
+
+    pub fn is_open_on(self, date: NaiveDate) -> bool {
+        match self {
+            Sessions::Exchange => is_trading_day(date),
+            Sessions::Continuous => true,
+        }
+    }
+}
+
+/// Returns true if the exchange is open on `date`.
+pub fn is_trading_day(date: NaiveDate) -> bool {
+    !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !holidays(date.year()).contains(&date)
+}
+
+/// Full-day market holidays for `year`, with weekend observance applied.
+fn holidays(year: i32) -> Vec<NaiveDate> {
+    let mut days = Vec::new();
+
+    // New Year's Day: Sunday moves to Monday; Saturday is not observed.
+    let new_year = ymd(year, 1, 1);
+    match new_year.weekday() {
+        Weekday::Sun => days.push(new_year + Duration::days(1)),
+        Weekday::Sat => {}
+        _ => days.push(new_year),
+    }
+
+    days.push(nth_weekday(year, 1, Weekday::Mon, 3)); // Martin Luther King Jr. Day
+    days.push(nth_weekday(year, 2, Weekday::Mon, 3)); // Washington's Birthday
+    days.push(easter_sunday(year) - Duration::days(2)); // Good Friday
+    days.push(last_weekday(year, 5, Weekday::Mon)); // Memorial Day
+    if year >= 2022 {
+        days.push(observed(ymd(year, 6, 19))); // Juneteenth
+    }
+    days.push(observed(ymd(year, 7, 4))); // Independence Day
+    days.push(nth_weekday(year, 9, Weekday::Mon, 1)); // Labor Day
+    days.push(nth_weekday(year, 11, Weekday::Thu, 4)); // Thanksgiving
+    days.push(observed(ymd(year, 12, 25))); // Christmas
+    days
+}
+
+/// Collapses sessions missing between the first and last stored date into
+/// inclusive `(start, end)` ranges. Non-trading days never split a range.
+pub fn missing_ranges(dates: &[NaiveDate], sessions: Sessions) -> Vec<(NaiveDate, NaiveDate)> {
+    let (Some(&first), Some(&last)) = (dates.iter().min(), dates.iter().max()) else {
+        return Vec::new();
+    };
+    let present: HashSet<NaiveDate> = dates.iter().copied().collect();
+
+    let mut ranges = Vec::new();
+    let mut open: Option<(NaiveDate, NaiveDate)> = None;
+    let mut day = first;
+    while day <= last {
+        if sessions.is_open_on(day) {
+            if present.contains(&day) {
+                if let Some(range) = open.take() {
+                    ranges.push(range);
+                }
+            } else {
+                open = Some(match open {
+                    Some((start, _)) => (start, day),
+                    None => (day, day),
+                });
+            }
+        }
+        day += Duration::days(1);
+    }
+    ranges.extend(open);
+    ranges
+}
+
+/// Total number of trading sessions covered by `ranges`.
+pub fn session_count(ranges: &[(NaiveDate, NaiveDate)], sessions: Sessions) -> usize {
+    ranges
+        .iter()
+        .map(|&(start, end)| {
+            start
+                .iter_days()
+                .take_while(|d| *d <= end)
+                .filter(|d| sessions.is_open_on(*d))
+                .count()
+        })
+        .sum()
+}
+
+fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).expect("valid calendar date")
+}
+
+/// Saturday holidays are observed on Friday, Sunday holidays on Monday.
+fn observed(date: NaiveDate) -> NaiveDate {
+    match date.weekday() {
+        Weekday::Sat => date - Duration::days(1),
+        Weekday::Sun => date + Duration::days(1),
+        _ => date,
+    }
+}
+
+fn nth_weekday(year: i32, month: u32, weekday: Weekday, n: u8) -> NaiveDate {
+    NaiveDate::from_weekday_of_month_opt(year, month, weekday, n).expect("valid weekday of month")
+}
+
+fn last_weekday(year: i32, month: u32, weekday: Weekday) -> NaiveDate {
+    let mut day = ymd(year, month + 1, 1) - Duration::days(1);
+    while day.weekday() != weekday {
+        day -= Duration::days(1);
+    }
+    day
+}
+
+/// Anonymous Gregorian computus.
+fn easter_sunday(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    ymd(year, month as u32, day as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holidays_follow_easter_and_weekend_observance() {
+        assert_eq!(
+            holidays(2021),
+            [
+                ymd(2021, 1, 1),
+                ymd(2021, 1, 18),
+                ymd(2021, 2, 15),
+                ymd(2021, 4, 2), // Good Friday
+                ymd(2021, 5, 31),
+                ymd(2021, 7, 5), // July 4th fell on a Sunday
+                ymd(2021, 9, 6),
+                ymd(2021, 11, 25),
+                ymd(2021, 12, 24), // Christmas fell on a Saturday
+            ]
+        );
+        // New Year's Day on a Saturday isn't moved back into the old year;
+        // Juneteenth on a Sunday is observed on the Monday.
+        assert!(is_trading_day(ymd(2021, 12, 31)));
+        assert!(!is_trading_day(ymd(2022, 6, 20)));
+        assert!(!is_trading_day(ymd(2024, 3, 29)));
+        assert!(is_trading_day(ymd(2024, 4, 1)));
+        assert_eq!(easter_sunday(2027), ymd(2027, 3, 28));
+    }
+
+    #[test]
+    fn gaps_run_across_weekends_and_holidays() {
+        // Thursday, then the next Tuesday: Friday and Monday are missing.
+        let dates = [ymd(2024, 3, 7), ymd(2024, 3, 12)];
+        let gaps = missing_ranges(&dates, Sessions::Exchange);
+        assert_eq!(gaps, [(ymd(2024, 3, 8), ymd(2024, 3, 11))]);
+        assert_eq!(session_count(&gaps, Sessions::Exchange), 2);
+        // Crypto misses the weekend too.
+        let gaps = missing_ranges(&dates, Sessions::Continuous);
+        assert_eq!(gaps, [(ymd(2024, 3, 8), ymd(2024, 3, 11))]);
+        assert_eq!(session_count(&gaps, Sessions::Continuous), 4);
+
+        // Good Friday and the weekend after it aren't gaps.
+        assert_eq!(missing_ranges(&[ymd(2024, 3, 28), ymd(2024, 4, 1)], Sessions::Exchange), []);
+        assert_eq!(
+            missing_ranges(&[ymd(2024, 3, 27), ymd(2024, 4, 2), ymd(2024, 4, 4)], Sessions::Exchange),
+            [(ymd(2024, 3, 28), ymd(2024, 4, 1)), (ymd(2024, 4, 3), ymd(2024, 4, 3))]
+        );
+        assert_eq!(missing_ranges(&[], Sessions::Exchange), []);
+    }
+}
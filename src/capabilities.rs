@@ -0,0 +1,141 @@
+//! What this machine and build can do, so what can't work says why.
+//!
+//! Python runs download_stock.py and ml/model.py; the network carries
+//! downloads, live quotes, headlines, the calendar and exchange rates; the
+//! `streaming` and `alpaca` features are optional parts of the build. The
+//! startup health check tells whether Python and the network are there
+//! (without it, `[health] enabled = false`, they're taken to be); the
+//! features are missing when `stm.toml` enables one the build lacks. An
+//! action needing something missing is disabled: its key and menu entry
+//! say why instead of starting a job that would fail, the menu greys it
+//! and the hints bar leaves it out, and the panels it feeds read
+//! "unavailable: REASON".
+
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use crate::config::Config;
+use crate::health::{HealthReport, Outcome};
+use crate::ids::Ticker;
+use crate::market::provider;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Capability {
+    Python,
+    Network,
+    Streaming,
+    Alpaca,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Capabilities {
+    /// Why each missing one is.
+    missing: BTreeMap<Capability, String>,
+}
+
+impl Capabilities {
+    /// The features `config` enables that the build lacks, and what the
+    /// health `report`, if one ran, found missing.
+    pub fn detect(config: &Config, report: Option<&HealthReport>) -> Self {
+        let mut capabilities = Self::default();
+        if config.stream.enabled && !cfg!(feature = "streaming") {
+            capabilities.set_missing(Capability::Streaming, "built without --features streaming");
+        }
+        if config.alpaca.enabled && !cfg!(feature = "alpaca") {
+            capabilities.set_missing(Capability::Alpaca, "built without --features alpaca");
+        }
+        for check in report.map_or(&[][..], |r| &r.checks) {
+            let capability = match check.name {
+                "Python" => Capability::Python,
+                "Network" => Capability::Network,
+                _ => continue,
+            };
+            if check.outcome >= Outcome::Warn {
+                capabilities.set_missing(capability, check.detail.clone());
+            }
+        }
+        capabilities
+    }
+
+    pub fn set_missing(&mut self, capability: Capability, reason: impl Into<String>) {
+        self.missing.insert(capability, reason.into());
+    }
+
+    /// Why `capability` is missing, if it is.
+    pub fn missing(&self, capability: Capability) -> Option<&str> {
+        self.missing.get(&capability).map(String::as_str)
+    }
+
+    /// "unavailable: REASON" for the first of `needs` that's missing.
+    pub fn check(&self, needs: &[Capability]) -> Result<(), String> {
+        match needs.iter().find_map(|c| self.missing(*c)) {
+            Some(reason) => Err(format!("unavailable: {}", reason)),
+            None => Ok(()),
+        }
+    }
+}
+
+static CAPABILITIES: OnceLock<Capabilities> = OnceLock::new();
+
+/// Sets what the session found. Only the first call has any effect.
+pub fn init(capabilities: Capabilities) {
+    let _ = CAPABILITIES.set(capabilities);
+}
+
+/// What `init` set, or without it what `config` alone tells.
+pub fn for_config(config: &Config) -> Capabilities {
+    CAPABILITIES.get().cloned().unwrap_or_else(|| Capabilities::detect(config, None))
+}
+
+/// What downloading `ticker` takes: the network, and Python unless its
+/// provider fetches bars itself.
+pub fn download(ticker: &Ticker) -> &'static [Capability] {
+    if provider::for_ticker(ticker).fetches() { &[Capability::Network] } else { &[Capability::Network, Capability::Python] }
+}
+
+/// What a prediction takes, or with `train` a training run: Python, unless
+/// the build predicts on the ONNX export and there is one.
+pub fn prediction(train: bool) -> &'static [Capability] {
+    if !train && native_model() { &[] } else { &[Capability::Python] }
+}
+
+#[cfg(feature = "native-ml")]
+fn native_model() -> bool {
+    std::path::Path::new(crate::ml::native::NATIVE_MODEL_PATH).exists()
+}
+
+#[cfg(not(feature = "native-ml"))]
+fn native_model() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::Check;
+
+    #[test]
+    fn missing_checks_disable_what_needs_them() {
+        let check = |name: &'static str, outcome: Outcome, detail: &str| Check {
+            name,
+            outcome,
+            detail: detail.to_string(),
+            hint: String::new(),
+        };
+        let report = HealthReport {
+            checks: vec![
+                check("Python", Outcome::Fail, "python3 not found: No such file or directory"),
+                check("Network", Outcome::Pass, "query1.finance.yahoo.com reachable"),
+                check("Python packages", Outcome::Warn, "Missing torch"),
+            ],
+        };
+        let capabilities = Capabilities::detect(&Config::default(), Some(&report));
+        assert_eq!(capabilities.missing(Capability::Network), None);
+        assert_eq!(
+            capabilities.check(&[Capability::Network, Capability::Python]),
+            Err("unavailable: python3 not found: No such file or directory".to_string())
+        );
+        assert_eq!(capabilities.check(&[Capability::Network]), Ok(()));
+        assert_eq!(Capabilities::detect(&Config::default(), None), Capabilities::default());
+    }
+}
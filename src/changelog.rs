@@ -0,0 +1,78 @@
+//! What each stm version added, built into the binary.
+//!
+//! The quit that saves the session stores the version that was running.
+//! When the next start finds a different one stored, the dashboard opens
+//! with the entries since in a "what's new" popup, once; the whole
+//! changelog is a key away after that. A note naming an action shows that
+//! action's key as bound now, so `[keys]` overrides are reflected. Fresh
+//! installs, with no session yet, start without the popup.
+
+use crate::keymap::Action;
+use crate::update;
+
+pub struct Note {
+    pub text: &'static str,
+    /// The action it added, whose key is shown alongside.
+    pub action: Option<Action>,
+}
+
+pub struct Entry {
+    pub version: &'static str,
+    pub notes: &'static [Note],
+}
+
+const fn note(text: &'static str, action: Action) -> Note {
+    Note { text, action: Some(action) }
+}
+
+const fn plain(text: &'static str) -> Note {
+    Note { text, action: None }
+}
+
+/// Newest first.
+pub const CHANGELOG: &[Entry] = &[Entry {
+    version: "0.1.0",
+    notes: &[
+        note("Market overview: the ML list as a heatmap, sized by market cap ([overview])", Action::MarketOverview),
+        note("Log and percent-from-first-bar axes for the Stock and Compare charts", Action::ChartScale),
+        note("Price levels and trendlines drawn on the chart, kept per ticker; a level can become an alert", Action::Crosshair),
+        note("Downloads of the whole ML list, rate limited and retried ([downloads])", Action::DownloadAll),
+        note("Dividends: income per holding and the next year's projected", Action::Dividends),
+        note("What's new after an upgrade, and this changelog", Action::Changelog),
+        note("Performance view: win rate, profit factor, expectancy and monthly returns", Action::Analytics),
+        note("Menu bar listing every action ([layout] menu_bar keeps it shown)", Action::Menu),
+        plain("stm verify compares account_summary.csv with the trade history; --repair rewrites it"),
+        plain("Account balances are folded from the trade history"),
+        plain("Stop-loss and take-profit orders, with an open orders panel in the broker view"),
+        plain("Streamed ticks fold into minute bars on 1m charts"),
+        note("Broker view with paper and Alpaca accounts", Action::Broker),
+        note("Signals place orders while autotrading is armed", Action::AutoTrade),
+        note("Time travel: the dashboard as of an earlier date", Action::TimeTravel),
+        note("Trading game on past prices", Action::TradingGame),
+        note("Transfers between accounts", Action::Transfer),
+    ],
+}];
+
+/// Entries newer than `last_seen` up to this build, newest first; all of
+/// them when no version was stored.
+pub fn since(last_seen: Option<&str>) -> Vec<&'static Entry> {
+    CHANGELOG
+        .iter()
+        .filter(|entry| !update::is_newer(entry.version, update::CURRENT))
+        .filter(|entry| last_seen.is_none_or(|seen| update::is_newer(entry.version, seen)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_since_the_stored_version() {
+        let versions = |seen| since(seen).iter().map(|e| e.version).collect::<Vec<_>>();
+        assert_eq!(versions(Some(update::CURRENT)), Vec::<&str>::new());
+        assert_eq!(versions(Some("0.0.1")), vec!["0.1.0"]);
+        assert_eq!(versions(None), vec!["0.1.0"]);
+        assert!(CHANGELOG.iter().any(|e| e.version == update::CURRENT));
+    }
+}
@@ -0,0 +1,102 @@
+//! Developer failure injection.
+//!
+//! Setting `STM_CHAOS` makes network fetches and Python subprocess calls
+//! sleep before running and fail at random, so stale quotes, reconnects and
+//! error messages can be exercised without a flaky network. The value is a
+//! comma-separated list of `delay_ms=N` and `fail_pct=N`; any other
+//! non-empty value (e.g. `STM_CHAOS=1`) uses the defaults.
+//!
+//! ```text
+//! STM_CHAOS=delay_ms=2000,fail_pct=50 cargo run
+//! ```
+
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const CHAOS_ENV: &str = "STM_CHAOS";
+const DEFAULT_DELAY_MS: u64 = 500;
+const DEFAULT_FAIL_PCT: u64 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chaos {
+    pub delay: Duration,
+    // 0..=100
+    pub fail_pct: u64,
+}
+
+impl Chaos {
+    fn parse(spec: &str) -> Self {
+        let mut chaos = Chaos { delay: Duration::from_millis(DEFAULT_DELAY_MS), fail_pct: DEFAULT_FAIL_PCT };
+        for part in spec.split(',') {
+            match part.trim().split_once('=') {
+                Some(("delay_ms", ms)) => {
+                    if let Ok(ms) = ms.trim().parse() {
+                        chaos.delay = Duration::from_millis(ms);
+                    }
+                }
+                Some(("fail_pct", pct)) => {
+                    if let Ok(pct) = pct.trim().parse::<u64>() {
+                        chaos.fail_pct = pct.min(100);
+                    }
+                }
+                _ => {}
+            }
+        }
+        chaos
+    }
+}
+
+/// The injection settings from `STM_CHAOS`, read once.
+pub fn settings() -> Option<Chaos> {
+    static SETTINGS: OnceLock<Option<Chaos>> = OnceLock::new();
+    *SETTINGS.get_or_init(|| {
+        env::var(CHAOS_ENV)
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .map(|v| Chaos::parse(&v))
+    })
+}
+
+#[derive(Debug)]
+pub struct InjectedFailure(pub &'static str);
+
+impl fmt::Display for InjectedFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "injected failure in {} ({} is set)", self.0, CHAOS_ENV)
+    }
+}
+
+impl Error for InjectedFailure {}
+
+/// Call before a fallible external operation named `what`. A no-op unless
+/// chaos mode is on; otherwise sleeps for the configured delay and fails
+/// `fail_pct` percent of the time.
+pub fn inject(what: &'static str) -> Result<(), InjectedFailure> {
+    let Some(chaos) = settings() else {
+        return Ok(());
+    };
+    thread::sleep(chaos.delay);
+    if next_random() % 100 < chaos.fail_pct {
+        return Err(InjectedFailure(what));
+    }
+    Ok(())
+}
+
+/// xorshift64, seeded from the clock. Good enough for coin flips.
+fn next_random() -> u64 {
+    static STATE: AtomicU64 = AtomicU64::new(0);
+    let mut x = STATE.load(Ordering::Relaxed);
+    if x == 0 {
+        x = SystemTime::now().duration_since(UNIX_EPOCH).map_or(1, |d| d.as_nanos() as u64) | 1;
+    }
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    STATE.store(x, Ordering::Relaxed);
+    x
+}
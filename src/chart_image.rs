@@ -0,0 +1,239 @@
+//! The chart as a PNG or SVG image, for journals and reports.
+//!
+//! The image shows what the Stock Chart panel does for the selected ticker:
+//! the closes in the chosen date range up to the pan offset, the benchmark
+//! overlay and the volume pane, plus a marker on the bar of each trade
+//! recorded against the ticker. It is drawn with plotters, onto its bitmap
+//! backend for PNG and its SVG backend for SVG. Colours are fixed for a
+//! light page rather than taken from the terminal theme. Images are written
+//! under `export::REPORTS_DIR` beside the portfolio reports.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDateTime;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use serde::Deserialize;
+
+use crate::app::Overlay;
+use crate::data::{Bar, Interval};
+use crate::export::REPORTS_DIR;
+use crate::ids::Ticker;
+
+const SIZE: (u32, u32) = (960, 540);
+/// Share of the plot height the volume pane takes when shown.
+const VOLUME_SHARE: f64 = 0.25;
+
+const LINE: RGBColor = RGBColor(0x1f, 0x77, 0xb4);
+const BENCHMARK: RGBColor = RGBColor(0xff, 0x7f, 0x0e);
+const GAIN: RGBColor = RGBColor(0x2c, 0xa0, 0x2c);
+const LOSS: RGBColor = RGBColor(0xd6, 0x27, 0x28);
+const GRID: RGBColor = RGBColor(0xee, 0xee, 0xee);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Png,
+    Svg,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Svg => "svg",
+        }
+    }
+}
+
+/// A trade recorded against the charted ticker.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Marker {
+    pub at: NaiveDateTime,
+    pub amount: f64,
+}
+
+/// What the image is drawn from, copied out of the app so the effect can
+/// draw it off the reducer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChartImage {
+    pub title: String,
+    pub interval: Interval,
+    pub bars: Vec<Bar>,
+    pub overlay: Option<Overlay>,
+    pub markers: Vec<Marker>,
+    pub show_volume: bool,
+}
+
+impl ChartImage {
+    /// Whether there is a line to draw: the panel draws none with fewer than
+    /// two bars.
+    pub fn is_drawable(&self) -> bool {
+        self.bars.len() >= 2
+    }
+
+    fn draw<DB: DrawingBackend>(&self, root: DrawingArea<DB, Shift>) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+        let bars = &self.bars;
+        root.fill(&WHITE)?;
+        let root = root.titled(&self.title, ("sans-serif", 20))?;
+        let overlay_points = self.overlay.as_ref().map_or(&[][..], |o| &o.points[..]);
+        let (lo, hi) = bars
+            .iter()
+            .map(|b| b.close)
+            .chain(overlay_points.iter().map(|&(_, y)| y))
+            .fold((f64::MAX, f64::MIN), |(lo, hi), y| (lo.min(y), hi.max(y)));
+        let pad = ((hi - lo) * 0.1).max(0.01);
+        let x_range = -0.5..bars.len() as f64 - 0.5;
+
+        let has_volume = self.show_volume && bars.iter().any(|b| b.volume.is_some());
+        let (price_area, volume_area) = if has_volume {
+            let height = root.dim_in_pixel().1 as f64;
+            let (upper, lower) = root.split_vertically((height * (1.0 - VOLUME_SHARE)) as u32);
+            (upper, Some(lower))
+        } else {
+            (root, None)
+        };
+        // Dates go under the lowest pane, labelled where the panel labels
+        // them.
+        let label_format = self.interval.axis_format(bars[bars.len() - 1].at - bars[0].at);
+        let date_label = |x: &f64| {
+            let i = x.round().clamp(0.0, (bars.len() - 1) as f64) as usize;
+            bars[i].at.format(label_format).to_string()
+        };
+
+        let mut price = ChartBuilder::on(&price_area)
+            .margin(10)
+            .y_label_area_size(60)
+            .x_label_area_size(if has_volume { 0 } else { 30 })
+            .build_cartesian_2d(x_range.clone(), lo - pad..hi + pad)?;
+        price
+            .configure_mesh()
+            .disable_x_mesh()
+            .light_line_style(TRANSPARENT)
+            .bold_line_style(GRID)
+            .x_labels(5)
+            .y_labels(5)
+            .x_label_formatter(&date_label)
+            .y_label_formatter(&|v| format!("{:.2}", v))
+            .draw()?;
+
+        if let Some(overlay) = &self.overlay {
+            price
+                .draw_series(DashedLineSeries::new(overlay.points.iter().copied(), 5, 3, BENCHMARK.stroke_width(2)))?
+                .label(format!("{} {:+.1}%", overlay.ticker, overlay.benchmark))
+                .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BENCHMARK.stroke_width(2)));
+        }
+        let line = price.draw_series(LineSeries::new(
+            bars.iter().enumerate().map(|(i, b)| (i as f64, b.close)),
+            LINE.stroke_width(2),
+        ))?;
+        if let Some(overlay) = &self.overlay {
+            line.label(format!("{:+.1}%", overlay.own))
+                .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], LINE.stroke_width(2)));
+        }
+
+        // Trades sit on the last bar at or before them: an upward triangle
+        // under the line for a gain, a downward one over it for a loss.
+        price.draw_series(self.markers.iter().filter_map(|marker| {
+            let i = bars.iter().rposition(|b| b.at <= marker.at)?;
+            let (tip, base, colour) = if marker.amount >= 0.0 { (6, 16, GAIN) } else { (-6, -16, LOSS) };
+            let triangle = Polygon::new(vec![(0, tip), (-5, base), (5, base)], colour.filled());
+            Some(EmptyElement::at((i as f64, bars[i].close)) + triangle)
+        }))?;
+
+        if self.overlay.is_some() {
+            price
+                .configure_series_labels()
+                .position(SeriesLabelPosition::UpperLeft)
+                .background_style(WHITE.mix(0.8))
+                .border_style(GRID)
+                .draw()?;
+        }
+
+        if let Some(area) = volume_area {
+            let vol_max = bars.iter().filter_map(|b| b.volume).fold(0.0, f64::max).max(1.0);
+            let mut volume = ChartBuilder::on(&area)
+                .margin(10)
+                .margin_top(0)
+                .y_label_area_size(60)
+                .x_label_area_size(30)
+                .build_cartesian_2d(x_range, 0.0..vol_max)?;
+            volume
+                .configure_mesh()
+                .disable_mesh()
+                .x_labels(5)
+                .y_labels(2)
+                .x_label_formatter(&date_label)
+                .y_label_formatter(&|v| format!("{:.0}", v))
+                .draw()?;
+            volume.draw_series(bars.iter().enumerate().filter_map(|(i, bar)| {
+                let up = i == 0 || bar.close >= bars[i - 1].close;
+                let x = i as f64;
+                Some(Rectangle::new([(x - 0.4, 0.0), (x + 0.4, bar.volume?)], if up { GAIN } else { LOSS }.mix(0.6).filled()))
+            }))?;
+        }
+        price_area.present()
+    }
+
+    /// Draws the image to `path` in `format`.
+    pub fn save(&self, path: &Path, format: Format) -> Result<(), Box<dyn Error>> {
+        match format {
+            Format::Png => self.draw(BitMapBackend::new(path, SIZE).into_drawing_area())?,
+            Format::Svg => self.draw(SVGBackend::new(path, SIZE).into_drawing_area())?,
+        }
+        Ok(())
+    }
+}
+
+/// Draws `image` to `reports/chart-TICKER-YYYYMMDD-HHMMSS.png` (or `.svg`),
+/// creating the directory if needed, and returns the path.
+pub fn write(ticker: &Ticker, at: NaiveDateTime, format: Format, image: &ChartImage) -> Result<PathBuf, Box<dyn Error>> {
+    fs::create_dir_all(REPORTS_DIR)?;
+    let name = format!("chart-{}-{}.{}", ticker, at.format("%Y%m%d-%H%M%S"), format.extension());
+    let path = PathBuf::from(REPORTS_DIR).join(name);
+    image.save(&path, format)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn images_draw_the_line_volume_and_trade_markers() {
+        let day = |d| chrono::NaiveDate::from_ymd_opt(2024, 6, d).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let bars = vec![
+            Bar { at: day(3), close: 10.0, volume: Some(100.0) },
+            Bar { at: day(4), close: 12.0, volume: Some(50.0) },
+            Bar { at: day(5), close: 11.0, volume: None },
+        ];
+        let image = ChartImage {
+            title: "A & B".to_string(),
+            interval: Interval::OneDay,
+            bars,
+            overlay: None,
+            // The trade before the first bar has nowhere to go.
+            markers: vec![Marker { at: day(4), amount: 5.0 }, Marker { at: day(1), amount: -1.0 }],
+            show_volume: true,
+        };
+        let mut svg = String::new();
+        image.draw(SVGBackend::with_string(&mut svg, SIZE).into_drawing_area()).unwrap();
+        assert!(svg.contains("A &amp; B"));
+        assert_eq!(svg.matches(r##"stroke="#1F77B4""##).count(), 1);
+        assert_eq!(svg.matches(r#"opacity="0.6""#).count(), 2);
+        assert_eq!(svg.matches("<polygon").count(), 1);
+        assert!(svg.contains("2024-06-05"));
+
+        let dir = std::env::temp_dir().join(format!("stm-{}-chart-image", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("chart.png");
+        image.save(&path, Format::Png).unwrap();
+        let png = fs::read(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        assert!(!ChartImage { bars: image.bars[..1].to_vec(), ..image }.is_drawable());
+    }
+}
@@ -0,0 +1,591 @@
+//! Headless commands, for scripts and cron jobs.
+//!
+//! Run without arguments the binary starts the dashboard; with a command it
+//! does one thing, prints the result and exits, non-zero on failure.
+//! Downloads and model runs go through the same job queue and effects the
+//! dashboard uses. Arguments are parsed with clap, and the commands that
+//! print a result take `--json` to print one JSON object instead of text.
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use clap::builder::PossibleValuesParser;
+use clap::{Parser, Subcommand};
+use serde_json::json;
+
+use crate::accounts::{self, AccountSummary};
+use crate::app::Effect;
+use crate::bundle::{self, Bundle, BundleInfo};
+use crate::config::{self, Config, StrategyKind};
+use crate::daemon;
+use crate::events::{self, EventCache};
+use crate::data::{self, Interval};
+use crate::date_range::DateRange;
+use crate::effects;
+use crate::fx::{self, Rates};
+use crate::health::{HealthReport, Outcome};
+use crate::ids::Ticker;
+use crate::ledger::{self, Divergence};
+use crate::market::{provider, symbols};
+use crate::ml::history::{self, HISTORY_PATH};
+use crate::prices;
+use crate::splits;
+use crate::strategy;
+use crate::tax::{self, TaxLots};
+use crate::trades;
+
+/// Without a command, starts the dashboard.
+#[derive(Debug, Parser)]
+#[command(name = "stm")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Clone, PartialEq, Subcommand)]
+pub enum Command {
+    /// Latest price and change from [data] provider
+    Quote {
+        #[arg(value_parser = ticker)]
+        ticker: Ticker,
+        /// Print one JSON object
+        #[arg(long)]
+        json: bool,
+    },
+    /// Look up symbols with [data] provider
+    Search {
+        #[arg(required = true)]
+        query: Vec<String>,
+        /// Print one JSON object
+        #[arg(long)]
+        json: bool,
+    },
+    /// Download bars
+    Download {
+        #[arg(value_parser = ticker)]
+        ticker: Ticker,
+        /// 1m/5m/15m/1h/1d
+        #[arg(long, default_value = "1d", value_parser = interval)]
+        interval: Interval,
+        /// How far back; by default the interval's usual range (1y for 1d)
+        #[arg(long, value_parser = PossibleValuesParser::new(data::RANGES))]
+        range: Option<String>,
+        /// Print one JSON object
+        #[arg(long)]
+        json: bool,
+    },
+    /// Account balances and their total
+    Portfolio {
+        /// Print one JSON object
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check account_summary.csv against the trade history
+    Verify {
+        /// Rewrite account_summary.csv from the history
+        #[arg(long)]
+        repair: bool,
+        /// Print one JSON object
+        #[arg(long)]
+        json: bool,
+    },
+    /// Gains realized on broker fills, short and long term by [tax] lots
+    Gains {
+        /// Also write one row per lot closed to FILE
+        #[arg(long, value_name = "FILE")]
+        csv: Option<String>,
+        /// Print one JSON object
+        #[arg(long)]
+        json: bool,
+    },
+    /// Run the model on the stored history
+    Predict {
+        #[arg(value_parser = ticker)]
+        ticker: Ticker,
+        /// Print one JSON object
+        #[arg(long)]
+        json: bool,
+    },
+    /// Trade the stored history on [strategy] signals
+    Backtest {
+        #[arg(value_parser = ticker)]
+        ticker: Ticker,
+        /// Print one JSON object
+        #[arg(long)]
+        json: bool,
+    },
+    /// Keep data fresh and check alerts ([daemon] in stm.toml)
+    Daemon {
+        /// Run one cycle and exit
+        #[arg(long)]
+        once: bool,
+    },
+    /// Share alert rules and the [moves] screen as a bundle file
+    #[command(subcommand)]
+    Bundle(BundleCommand),
+    /// Run the startup checks; fails if any check fails
+    Check {
+        /// Print one JSON object
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Subcommand)]
+pub enum BundleCommand {
+    /// Write the alert rules and [moves] screen as a bundle
+    Export {
+        file: String,
+        /// Bundle name; the file name by default
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Check and preview a bundle
+    Import {
+        file: String,
+        /// Add it to stm.toml
+        #[arg(long)]
+        apply: bool,
+    },
+}
+
+fn ticker(word: &str) -> Result<Ticker, String> {
+    Ticker::parse(word).map_err(|e| e.to_string())
+}
+
+fn interval(word: &str) -> Result<Interval, String> {
+    Interval::parse(word).ok_or_else(|| "expected 1m/5m/15m/1h/1d".to_string())
+}
+
+/// The command in `args` (without the program name), or `None` to start
+/// the dashboard. `-h`, `--help` and `help` come back as an error that
+/// prints the help when exited with.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Command>, clap::Error> {
+    let program = std::iter::once("stm".to_string());
+    Ok(Cli::try_parse_from(program.chain(args))?.command)
+}
+
+/// Runs `command`, printing its result to stdout.
+pub fn run(command: Command, config: &Config) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Quote { ticker, json } => quote(&ticker, json)?,
+        Command::Search { query, json } => search(&query.join(" "), json)?,
+        Command::Download { ticker, interval, range, json } => {
+            let range = range.unwrap_or_else(|| interval.default_range().to_string());
+            let lines = effects::run_blocking(Effect::RunDownload { ticker: ticker.clone(), interval, range: range.clone() })?;
+            if json {
+                println!("{}", json!({ "ticker": ticker, "interval": interval.as_str(), "range": range, "messages": lines }));
+            } else {
+                lines.iter().for_each(|line| println!("{}", line));
+            }
+        }
+        Command::Portfolio { json } => portfolio(config, json)?,
+        Command::Verify { repair, json } => verify(repair, json)?,
+        Command::Gains { csv, json } => gains(config, csv.as_deref(), json)?,
+        Command::Daemon { once } => daemon::run(&config.daemon, &config.pipeline, once)?,
+        Command::Bundle(BundleCommand::Export { file: path, name }) => {
+            let name = name.unwrap_or_else(|| {
+                let stem = std::path::Path::new(&path).file_stem().map(|s| s.to_string_lossy().into_owned());
+                stem.unwrap_or_else(|| "bundle".to_string())
+            });
+            let bundle = Bundle::from_config(config, BundleInfo { name, ..BundleInfo::default() });
+            fs::write(&path, bundle.to_toml()?)?;
+            let rules: usize = bundle.alerts.values().map(Vec::len).sum();
+            println!("Wrote {} with {} alert rules{}", path, rules, if bundle.moves.is_some() { " and the [moves] screen" } else { "" });
+        }
+        Command::Bundle(BundleCommand::Import { file: path, apply }) => {
+            let bundle = bundle::load(&path)?;
+            let info = &bundle.bundle;
+            println!("{}{}", info.name, if info.author.is_empty() { String::new() } else { format!(" by {}", info.author) });
+            if !info.description.is_empty() {
+                println!("{}", info.description);
+            }
+            bundle.preview(config).iter().for_each(|line| println!("  {}", line));
+            if apply {
+                bundle.apply(config::CONFIG_PATH, config)?;
+                println!("Added to {}", config::CONFIG_PATH);
+            } else {
+                println!("Nothing changed yet; run again with --apply to add it to {}", config::CONFIG_PATH);
+            }
+        }
+        Command::Check { json } => check(config, json)?,
+        Command::Backtest { ticker, json } => backtest(&ticker, config, json)?,
+        Command::Predict { ticker, json } => {
+            let lines = effects::run_blocking(Effect::RunMl { ticker: ticker.clone(), pipeline: config.pipeline.clone(), train: false })?;
+            if json {
+                let predictions = history::load_resolved(HISTORY_PATH)?;
+                let latest = predictions.iter().filter(|p| p.ticker == ticker).max_by_key(|p| p.predicted_at);
+                println!("{}", json!({ "ticker": ticker, "prediction": latest, "messages": lines }));
+            } else {
+                lines.iter().for_each(|line| println!("{}", line));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The startup checks, against the config the caller loaded (a config that
+/// didn't parse shows as the defaults here, so it's loaded again to tell).
+fn check(config: &Config, json: bool) -> Result<(), Box<dyn Error>> {
+    let config_error = config::load(config::CONFIG_PATH).err().map(|e| e.to_string());
+    let report = HealthReport::run(config_error.as_deref(), Duration::from_secs(config.net.connect_timeout_secs));
+    if json {
+        let checks: Vec<_> = report
+            .checks
+            .iter()
+            .map(|c| json!({ "name": c.name, "outcome": format!("{:?}", c.outcome).to_lowercase(), "detail": c.detail, "hint": c.hint }))
+            .collect();
+        println!("{}", json!({ "checks": checks }));
+    } else {
+        for c in &report.checks {
+            let mark = match c.outcome {
+                Outcome::Pass => "ok",
+                Outcome::Skip => "--",
+                Outcome::Warn => "warn",
+                Outcome::Fail => "FAIL",
+            };
+            println!("{:<4} {:<20} {}", mark, c.name, c.detail);
+            if !c.hint.is_empty() {
+                println!("{:<25} -> {}", "", c.hint);
+            }
+        }
+    }
+    match report.count(Outcome::Fail) {
+        0 => Ok(()),
+        n => Err(format!("{} check(s) failed", n).into()),
+    }
+}
+
+/// Compares account_summary.csv with the balances the trade history folds
+/// to. With `repair` the folded balances are written; trades of unknown
+/// accounts can't be repaired that way and still fail.
+fn verify(repair: bool, json: bool) -> Result<(), Box<dyn Error>> {
+    let (stored, trades) = ledger::read("account_summary.csv", "trading_history.csv")?;
+    let (folded, found) = ledger::verify(&stored, &trades);
+    let repaired = repair && found.iter().any(|d| matches!(d, Divergence::Balance { .. }));
+    if repaired {
+        accounts::write_accounts_to_csv("account_summary.csv", &folded)?;
+    }
+    let left = found.iter().filter(|d| !repaired || matches!(d, Divergence::Orphaned { .. })).count();
+    if json {
+        let found: Vec<String> = found.iter().map(|d| d.to_string()).collect();
+        println!(
+            "{}",
+            json!({ "accounts": stored.len(), "trades": trades.len(), "divergences": found, "repaired": repaired })
+        );
+    } else {
+        found.iter().for_each(|d| println!("{}", d));
+        let balances = found.iter().any(|d| matches!(d, Divergence::Balance { .. }));
+        if found.is_empty() {
+            println!("{} accounts match {} trades", stored.len(), trades.len());
+        } else if repaired {
+            println!("Rewrote account_summary.csv from the history");
+        } else if balances {
+            println!("Run stm verify --repair to rewrite account_summary.csv from the history");
+        }
+        if found.iter().any(|d| matches!(d, Divergence::Orphaned { .. })) {
+            println!("Add the missing accounts, or fix the names in trading_history.csv");
+        }
+    }
+    match left {
+        0 => Ok(()),
+        n => Err(format!("{} difference(s) from the trade history", n).into()),
+    }
+}
+
+/// Gains realized on the trade history's broker fills, matched to lots by
+/// `[tax]`; with `csv`, also written there.
+fn gains(config: &Config, csv: Option<&str>, json: bool) -> Result<(), Box<dyn Error>> {
+    let path = "trading_history.csv";
+    let mut history = if Path::new(path).exists() { trades::read_trades_from_csv(path)? } else { Vec::new() };
+    trades::sort_by_time(&mut history);
+    // Splits the calendar has reported restate the shares bought before.
+    if let Ok(calendar) = EventCache::load(events::CALENDAR_PATH) {
+        splits::remember(&calendar);
+    }
+    let lots = TaxLots::new(&history, &config.tax);
+    let (short, long) = lots.totals();
+    if let Some(csv) = csv {
+        tax::write_realized_csv(csv, &lots.realized)?;
+    }
+    let date = |at: Option<NaiveDateTime>| at.map_or("undated".to_string(), |at| at.format("%Y-%m-%d").to_string());
+    if json {
+        let realized: Vec<_> = lots
+            .realized
+            .iter()
+            .map(|r| {
+                json!({
+                    "account": r.account,
+                    "ticker": r.ticker,
+                    "qty": r.qty,
+                    "acquired": r.acquired,
+                    "sold": r.sold,
+                    "cost": r.cost,
+                    "proceeds": r.proceeds,
+                    "gain": r.gain(),
+                    "term": r.term(),
+                })
+            })
+            .collect();
+        let open: Vec<_> = lots
+            .open
+            .iter()
+            .map(|l| json!({ "account": l.account, "ticker": l.ticker, "qty": l.qty, "price": l.price, "acquired": l.acquired }))
+            .collect();
+        let unmatched: Vec<_> = lots
+            .unmatched
+            .iter()
+            .map(|u| json!({ "account": u.account, "ticker": u.ticker, "qty": u.qty, "sold": u.sold }))
+            .collect();
+        println!(
+            "{}",
+            json!({ "realized": realized, "short_term": short, "long_term": long, "open": open, "unmatched": unmatched })
+        );
+        return Ok(());
+    }
+    for r in &lots.realized {
+        println!(
+            "{} {} {:<6} {:>8} bought {} {:>12.2} -> {:>12.2} {:>+10.2} {}",
+            date(r.sold),
+            r.account,
+            r.ticker,
+            r.qty,
+            date(r.acquired),
+            r.cost,
+            r.proceeds,
+            r.gain(),
+            r.term()
+        );
+    }
+    for u in &lots.unmatched {
+        println!("{} {} {:<6} {:>8} sold without a lot bought before", date(u.sold), u.account, u.ticker, u.qty);
+    }
+    println!(
+        "Short-term {:+.2}, long-term {:+.2}, total {:+.2} over {} lots closed ({}); {} lots open",
+        short,
+        long,
+        short + long,
+        lots.realized.len(),
+        format!("{:?}", config.tax.method).to_lowercase(),
+        lots.open.len()
+    );
+    if let Some(csv) = csv {
+        println!("Wrote {}", csv);
+    }
+    Ok(())
+}
+
+/// Runs `[strategy]` over all of `ticker`'s stored bars from
+/// `backtest_cash`.
+fn backtest(ticker: &Ticker, config: &Config, json: bool) -> Result<(), Box<dyn Error>> {
+    let bars = prices::bars(ticker, DateRange::default())?;
+    let predictions = match config.strategy.kind {
+        StrategyKind::Ml => history::load_resolved(HISTORY_PATH)?,
+        _ => Vec::new(),
+    };
+    let strategy = strategy::build(&config.strategy, &predictions);
+    let run = strategy::backtest(strategy.as_ref(), ticker, &bars, config.strategy.backtest_cash);
+    if json {
+        let trades: Vec<_> =
+            run.trades.iter().map(|t| json!({ "at": t.at, "side": t.side, "qty": t.qty, "price": t.price })).collect();
+        println!(
+            "{}",
+            json!({
+                "ticker": ticker,
+                "strategy": run.strategy,
+                "bars": bars.len(),
+                "trades": trades,
+                "start_cash": run.start_cash,
+                "equity": run.equity,
+                "return_pct": run.return_pct,
+                "buy_hold_pct": run.buy_hold_pct,
+            })
+        );
+        return Ok(());
+    }
+    for t in &run.trades {
+        println!("{} {:<4} {:>8} at {:.2}", t.at.format("%Y-%m-%d %H:%M"), t.side.as_str(), t.qty, t.price);
+    }
+    println!(
+        "{} on {} over {} bars: {} trades, {:.2} -> {:.2} ({:+.2}%, buy and hold {:+.2}%)",
+        run.strategy,
+        ticker,
+        bars.len(),
+        run.trades.len(),
+        run.start_cash,
+        run.equity,
+        run.return_pct,
+        run.buy_hold_pct
+    );
+    Ok(())
+}
+
+fn quote(ticker: &Ticker, json: bool) -> Result<(), Box<dyn Error>> {
+    let data = provider::for_ticker(ticker);
+    let quote = data.quote(ticker)?;
+    if json {
+        // The three-month return is of the stored history, whatever the provider.
+        let stocks = data::load_stocks();
+        let return_3m = stocks.iter().find(|s| &s.ticker == ticker).and_then(|s| s.return_3m);
+        println!(
+            "{}",
+            json!({
+                "ticker": ticker,
+                "price": quote.price,
+                "change": quote.change,
+                "pct_change": quote.pct_change,
+                "return_3m": return_3m,
+                "provider": data.name(),
+            })
+        );
+    } else {
+        println!("{} {:.2} {:+.2} ({:+.2}%)", ticker, quote.price, quote.change, quote.pct_change);
+    }
+    Ok(())
+}
+
+fn search(query: &str, json: bool) -> Result<(), Box<dyn Error>> {
+    let matches = provider::current().search(query)?;
+    if let Err(e) = symbols::record(&matches) {
+        eprintln!("Couldn't save company names: {}", e);
+    }
+    if json {
+        let matches: Vec<_> =
+            matches.iter().map(|s| json!({ "ticker": s.ticker, "name": s.name, "exchange": s.exchange })).collect();
+        println!("{}", json!({ "query": query, "provider": provider::current().name(), "matches": matches }));
+    } else if matches.is_empty() {
+        println!("No symbols match {}", query);
+    } else {
+        for s in &matches {
+            println!("{:<10} {:<40} {}", s.ticker.as_str(), s.name, s.exchange);
+        }
+    }
+    Ok(())
+}
+
+/// Open accounts and their total in the `[fx]` base currency, fetching
+/// reference rates first if the dashboard would.
+fn portfolio(config: &Config, json: bool) -> Result<(), Box<dyn Error>> {
+    let accounts: Vec<AccountSummary> =
+        ledger::load("account_summary.csv", "trading_history.csv")?.into_iter().filter(|a| !a.archived).collect();
+    let mut rates = Rates::new(&config.fx);
+    if config.fx.fetch {
+        match fx::fetch(rates.base()) {
+            Ok(fetched) => rates.set_fetched(fetched),
+            Err(e) => eprintln!("Exchange rates unavailable, using configured ones: {}", e),
+        }
+    }
+    let totals = fx::totals(&accounts, &rates);
+    if json {
+        let total = json!({
+            "currency": rates.base(),
+            "initial_amount": totals.initial,
+            "current_amount": totals.current,
+            "change": totals.change,
+            "percentage_change": totals.percentage_change,
+            "excluding": totals.missing,
+        });
+        println!("{}", json!({ "accounts": accounts, "total": total }));
+        return Ok(());
+    }
+    let width = accounts.iter().map(|a| a.name.as_str().len()).max().unwrap_or(0).max(5 + rates.base().len() + 1);
+    for a in &accounts {
+        println!(
+            "{:<width$} {:>12.2} {:>12.2} {:>+10.2} {:>+8.2}% {}",
+            a.name.as_str(),
+            a.initial_amount,
+            a.current_amount,
+            a.change,
+            a.percentage_change,
+            a.currency
+        );
+    }
+    println!(
+        "{:<width$} {:>12.2} {:>12.2} {:>+10.2} {:>+8.2}% {}",
+        format!("Total {}", rates.base()),
+        totals.initial,
+        totals.current,
+        totals.change,
+        totals.percentage_change,
+        rates.base()
+    );
+    if !totals.missing.is_empty() {
+        println!("(excluding {}: no exchange rate)", totals.missing.join(", "));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn parse_reads_commands_and_options() {
+        let ticker = |s| Ticker::parse(s).unwrap();
+        assert!(parse(args("")).unwrap().is_none());
+        assert_eq!(
+            parse(args("download aapl --interval 5m --json")).unwrap(),
+            Some(Command::Download { ticker: ticker("AAPL"), interval: Interval::FiveMinutes, range: None, json: true })
+        );
+        assert_eq!(
+            parse(args("download MSFT --range 2y")).unwrap(),
+            Some(Command::Download { ticker: ticker("MSFT"), interval: Interval::OneDay, range: Some("2y".to_string()), json: false })
+        );
+        assert_eq!(parse(args("check --json")).unwrap(), Some(Command::Check { json: true }));
+        assert_eq!(parse(args("backtest spy")).unwrap(), Some(Command::Backtest { ticker: ticker("SPY"), json: false }));
+        assert_eq!(
+            parse(args("search apple inc")).unwrap(),
+            Some(Command::Search { query: args("apple inc"), json: false })
+        );
+        assert_eq!(parse(args("verify --repair")).unwrap(), Some(Command::Verify { repair: true, json: false }));
+        assert_eq!(
+            parse(args("gains --csv gains.csv")).unwrap(),
+            Some(Command::Gains { csv: Some("gains.csv".to_string()), json: false })
+        );
+        assert_eq!(parse(args("daemon --once")).unwrap(), Some(Command::Daemon { once: true }));
+        assert_eq!(
+            parse(args("bundle export rules.toml --name Momentum")).unwrap(),
+            Some(Command::Bundle(BundleCommand::Export { file: "rules.toml".to_string(), name: Some("Momentum".to_string()) }))
+        );
+        assert_eq!(
+            parse(args("bundle import rules.toml --apply")).unwrap(),
+            Some(Command::Bundle(BundleCommand::Import { file: "rules.toml".to_string(), apply: true }))
+        );
+    }
+
+    #[test]
+    fn parse_rejects_bad_input_and_shows_help() {
+        use clap::error::ErrorKind;
+
+        let kind = |line| parse(args(line)).unwrap_err().kind();
+        // Help for the command asked about, wherever -h goes.
+        for line in ["--help", "help", "quote AAPL -h", "quote -h", "help bundle", "bundle import --help"] {
+            assert_eq!(kind(line), ErrorKind::DisplayHelp, "{}", line);
+        }
+        assert!(parse(args("quote AAPL -h")).unwrap_err().to_string().contains("Latest price"));
+
+        assert_eq!(kind("quote"), ErrorKind::MissingRequiredArgument);
+        assert_eq!(kind("search --json"), ErrorKind::MissingRequiredArgument);
+        assert_eq!(kind("quote AAPL MSFT"), ErrorKind::UnknownArgument);
+        assert_eq!(kind("quote AA$PL"), ErrorKind::ValueValidation);
+        assert_eq!(kind("download AAPL --range 3d"), ErrorKind::InvalidValue);
+        assert_eq!(kind("download AAPL --interval 2m"), ErrorKind::ValueValidation);
+        assert_eq!(kind("frobnicate"), ErrorKind::InvalidSubcommand);
+        assert_eq!(kind("portfolio --verbose"), ErrorKind::UnknownArgument);
+        assert_eq!(kind("bundle"), ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand);
+        // Only commands that print a result take --json.
+        for line in ["daemon --json", "bundle export rules.toml --json", "bundle import rules.toml --json", "--json"] {
+            assert_eq!(kind(line), ErrorKind::UnknownArgument, "{}", line);
+        }
+        assert_eq!(kind("help --json"), ErrorKind::InvalidSubcommand);
+        // Options belong to their command.
+        assert_eq!(kind("quote AAPL --once"), ErrorKind::UnknownArgument);
+    }
+}
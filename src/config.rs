@@ -0,0 +1,947 @@
+//! User configuration loaded from `stm.toml` in the working directory.
+//!
+//! Every field has a default, so a missing file or missing keys are fine.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::chart_image;
+use crate::export::Format;
+use crate::ids::Ticker;
+use crate::theme::ThemeConfig;
+
+pub const CONFIG_PATH: &str = "stm.toml";
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Ticker shown in the header mini-chart for market context.
+    pub benchmark: Ticker,
+    /// Ticker overlaid on the Stock Chart, rebased to the selected ticker's
+    /// first shown close; unset draws the chart alone.
+    pub chart_benchmark: Option<Ticker>,
+    /// `dark`, `light` or the name of a `[themes.NAME]` table.
+    pub theme: String,
+    pub themes: BTreeMap<String, ThemeConfig>,
+    pub data: DataConfig,
+    /// `[[markets]]` entries: tick and lot sizes, see `markets`.
+    pub markets: Vec<MarketConfig>,
+    pub live: LiveConfig,
+    pub stream: StreamConfig,
+    pub alpaca: AlpacaConfig,
+    pub paper: PaperConfig,
+    pub sizing: SizingConfig,
+    pub strategy: StrategyConfig,
+    pub autotrade: AutoTradeConfig,
+    pub tax: TaxConfig,
+    pub fees: FeesConfig,
+    /// `[keys]` section: action name to key, see `keymap`.
+    pub keys: BTreeMap<String, String>,
+    pub layout: LayoutConfig,
+    pub chart: ChartConfig,
+    pub overview: OverviewConfig,
+    pub net: NetConfig,
+    pub downloads: DownloadsConfig,
+    pub fx: FxConfig,
+    pub moves: MovesConfig,
+    pub retention: RetentionConfig,
+    pub export: ExportConfig,
+    pub updates: UpdatesConfig,
+    pub daemon: DaemonConfig,
+    pub game: GameConfig,
+    pub news: NewsConfig,
+    pub calendar: CalendarConfig,
+    pub health: HealthConfig,
+    pub pipeline: PipelineConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            benchmark: Ticker::parse("SPY").expect("valid default ticker"),
+            chart_benchmark: None,
+            theme: "dark".to_string(),
+            themes: BTreeMap::new(),
+            data: DataConfig::default(),
+            markets: Vec::new(),
+            live: LiveConfig::default(),
+            stream: StreamConfig::default(),
+            alpaca: AlpacaConfig::default(),
+            paper: PaperConfig::default(),
+            sizing: SizingConfig::default(),
+            strategy: StrategyConfig::default(),
+            autotrade: AutoTradeConfig::default(),
+            tax: TaxConfig::default(),
+            fees: FeesConfig::default(),
+            keys: BTreeMap::new(),
+            layout: LayoutConfig::default(),
+            chart: ChartConfig::default(),
+            overview: OverviewConfig::default(),
+            net: NetConfig::default(),
+            downloads: DownloadsConfig::default(),
+            fx: FxConfig::default(),
+            moves: MovesConfig::default(),
+            retention: RetentionConfig::default(),
+            export: ExportConfig::default(),
+            updates: UpdatesConfig::default(),
+            daemon: DaemonConfig::default(),
+            game: GameConfig::default(),
+            news: NewsConfig::default(),
+            calendar: CalendarConfig::default(),
+            health: HealthConfig::default(),
+            pipeline: PipelineConfig::default(),
+        }
+    }
+}
+
+/// `[data]` section: where bars, quotes and symbol searches come from, see
+/// `market::provider`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DataConfig {
+    pub provider: DataProvider,
+    /// Price directories merged into one universe, see `universe`;
+    /// `pre_stock/` alone if empty.
+    pub roots: Vec<RootConfig>,
+    /// Levels of subdirectories below each root searched for CSVs; 0 reads
+    /// the root alone.
+    pub max_depth: usize,
+}
+
+impl Default for DataConfig {
+    fn default() -> Self {
+        Self { provider: DataProvider::default(), roots: Vec::new(), max_depth: 3 }
+    }
+}
+
+/// One `[[data.roots]]` entry.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RootConfig {
+    /// Tag shown beside its tickers.
+    pub name: String,
+    pub dir: String,
+    /// Where its tickers are downloaded from; `[data] provider` if unset.
+    #[serde(default)]
+    pub provider: Option<DataProvider>,
+    /// Minutes between `stm daemon` downloads of its tickers, `[daemon]
+    /// every_mins` if unset; 0 leaves them to manual downloads.
+    #[serde(default)]
+    pub refresh_mins: Option<u64>,
+}
+
+/// One `[[markets]]` entry: the tickers it covers and how they trade.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct MarketConfig {
+    pub name: String,
+    /// Ticker endings it covers, e.g. `.T` or `-USD`.
+    pub suffixes: Vec<String>,
+    /// `[[data.roots]]` names whose tickers it covers.
+    pub roots: Vec<String>,
+    /// Smallest price step; prices are shown to its decimals.
+    pub tick: f64,
+    /// Shares traded in multiples of this; 0 allows fractions.
+    pub lot: f64,
+    /// Zone the exchange keeps time in, e.g. `Asia/Tokyo`, for the minute
+    /// bars streamed into its tickers' CSVs.
+    pub timezone: Option<Tz>,
+}
+
+impl Default for MarketConfig {
+    fn default() -> Self {
+        Self { name: String::new(), suffixes: Vec::new(), roots: Vec::new(), tick: 0.01, lot: 0.0, timezone: None }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataProvider {
+    /// The stored CSVs, downloaded by download_stock.py.
+    #[default]
+    Csv,
+    Yahoo,
+    Stooq,
+}
+
+impl DataProvider {
+    pub fn name(self) -> &'static str {
+        match self {
+            DataProvider::Csv => "csv",
+            DataProvider::Yahoo => "yahoo",
+            DataProvider::Stooq => "stooq",
+        }
+    }
+}
+
+/// `[live]` section: real-time quote polling.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LiveConfig {
+    pub enabled: bool,
+    pub provider: QuoteProvider,
+    /// Required by Finnhub and Alpha Vantage; ignored for Yahoo.
+    pub api_key: String,
+    pub interval_secs: u64,
+    /// Quotes older than this are flagged as stale in the status bar.
+    pub stale_after_secs: u64,
+}
+
+impl Default for LiveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: QuoteProvider::Yahoo,
+            api_key: String::new(),
+            interval_secs: 15,
+            stale_after_secs: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuoteProvider {
+    Yahoo,
+    Finnhub,
+    AlphaVantage,
+}
+
+impl QuoteProvider {
+    pub fn name(self) -> &'static str {
+        match self {
+            QuoteProvider::Yahoo => "yahoo",
+            QuoteProvider::Finnhub => "finnhub",
+            QuoteProvider::AlphaVantage => "alphavantage",
+        }
+    }
+}
+
+/// `[net]` section: the shared HTTP client, see `net`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NetConfig {
+    /// Whole-request timeout.
+    pub timeout_secs: u64,
+    pub connect_timeout_secs: u64,
+    /// Extra attempts after a network error, a 429 or a 5xx.
+    pub retries: u32,
+    /// First retry delay, doubled on each further attempt, when the server
+    /// doesn't send `Retry-After`.
+    pub backoff_ms: u64,
+    pub user_agent: String,
+    /// Idle connections kept open per host.
+    pub pool_per_host: usize,
+}
+
+impl Default for NetConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 10,
+            connect_timeout_secs: 5,
+            retries: 2,
+            backoff_ms: 500,
+            user_agent: concat!("Mozilla/5.0 (compatible; stm/", env!("CARGO_PKG_VERSION"), ")").to_string(),
+            pool_per_host: 2,
+        }
+    }
+}
+
+/// `[downloads]` section: the queue that downloads many tickers at once,
+/// see `downloads`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DownloadsConfig {
+    /// Tickers downloaded at the same time.
+    pub concurrency: usize,
+    /// Downloads a minute started against each provider.
+    pub per_minute: u32,
+    /// `per_minute` for particular providers, by name (`yahoo`, `stooq`,
+    /// or `csv` for download_stock.py).
+    pub limits: BTreeMap<String, u32>,
+    /// Extra attempts at a ticker whose download was throttled or hit a
+    /// server error.
+    pub retries: u32,
+    /// First retry delay, doubled on each further attempt.
+    pub backoff_ms: u64,
+}
+
+impl Default for DownloadsConfig {
+    fn default() -> Self {
+        Self { concurrency: 2, per_minute: 30, limits: BTreeMap::new(), retries: 3, backoff_ms: 2000 }
+    }
+}
+
+impl DownloadsConfig {
+    pub fn per_minute(&self, provider: &str) -> u32 {
+        self.limits.get(provider).copied().unwrap_or(self.per_minute)
+    }
+}
+
+/// `[fx]` section: converting account balances for the totals, see `fx`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FxConfig {
+    /// Currency the totals are shown in.
+    pub base: String,
+    /// Fetch reference rates from frankfurter.app.
+    pub fetch: bool,
+    /// Value of one unit of each currency in `base`, e.g. `EUR = 1.08`.
+    pub rates: BTreeMap<String, f64>,
+}
+
+impl Default for FxConfig {
+    fn default() -> Self {
+        Self { base: "USD".to_string(), fetch: false, rates: BTreeMap::new() }
+    }
+}
+
+/// `[moves]` section: ML list rows whose % change reaches a threshold
+/// either way are drawn bold and inverted.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MovesConfig {
+    /// Percent move for every ticker; unset leaves rows plain.
+    pub threshold: Option<f64>,
+    /// Per-ticker overrides, e.g. `TSLA = 6.0`.
+    pub tickers: BTreeMap<Ticker, f64>,
+}
+
+impl MovesConfig {
+    pub fn is_significant(&self, ticker: &Ticker, pct_change: f64) -> bool {
+        self.tickers
+            .get(ticker)
+            .copied()
+            .or(self.threshold)
+            .is_some_and(|threshold| pct_change.abs() >= threshold)
+    }
+}
+
+/// `[retention]` section: days of data to keep, see `retention`. Unset
+/// keys keep everything.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Bars of intraday series; daily series are always kept whole.
+    pub intraday_days: Option<u32>,
+    /// Logged model predictions in `ml_history.csv`.
+    pub predictions_days: Option<u32>,
+    /// Log what the daily maintenance run would delete without deleting it.
+    pub dry_run: bool,
+}
+
+impl RetentionConfig {
+    pub fn is_set(&self) -> bool {
+        self.intraday_days.is_some() || self.predictions_days.is_some()
+    }
+}
+
+/// `[export]` section: portfolio reports, see `export`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ExportConfig {
+    /// `markdown` or `html`.
+    pub format: Format,
+    /// Chart images, see `chart_image`: `png` or `svg`.
+    pub chart_format: chart_image::Format,
+}
+
+/// `[updates]` section: looking for newer releases, see `update`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct UpdatesConfig {
+    /// Ask GitHub for the latest release at startup.
+    pub check: bool,
+}
+
+/// `[daemon]` section: what `stm daemon` keeps fresh, see `daemon`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DaemonConfig {
+    /// Tickers to download each cycle; every stored series if empty.
+    pub tickers: Vec<Ticker>,
+    /// Minutes between cycles.
+    pub every_mins: u64,
+    /// Run the `[pipeline]` stages before the model after each download.
+    pub preprocess: bool,
+    /// Alert rules per ticker, e.g. `AAPL = ["close > 150"]`.
+    pub alerts: BTreeMap<Ticker, Vec<String>>,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self { tickers: Vec::new(), every_mins: 60, preprocess: true, alerts: BTreeMap::new() }
+    }
+}
+
+/// `[news]` section: headlines for the selected ticker, see `news`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct NewsConfig {
+    pub enabled: bool,
+    pub provider: NewsProvider,
+    /// Needed for finnhub.
+    pub api_key: String,
+    /// How long a ticker's headlines are kept before they're fetched again.
+    pub refresh_mins: u64,
+}
+
+impl Default for NewsConfig {
+    fn default() -> Self {
+        Self { enabled: false, provider: NewsProvider::Rss, api_key: String::new(), refresh_mins: 15 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NewsProvider {
+    /// Yahoo Finance's per-ticker RSS feed; no key needed.
+    Rss,
+    Finnhub,
+}
+
+/// `[calendar]` section: upcoming earnings and ex-dividend dates, see
+/// `events`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CalendarConfig {
+    pub enabled: bool,
+    pub provider: CalendarProvider,
+    /// Needed for finnhub.
+    pub api_key: String,
+    /// How far ahead events are listed.
+    pub days_ahead: i64,
+    /// Events this many days away or fewer are highlighted.
+    pub highlight_days: i64,
+    /// How long a ticker's fetched events are kept before they're fetched
+    /// again.
+    pub refresh_hours: u64,
+}
+
+impl Default for CalendarConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: CalendarProvider::Yahoo,
+            api_key: String::new(),
+            days_ahead: 60,
+            highlight_days: 7,
+            refresh_hours: 12,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CalendarProvider {
+    /// Yahoo Finance's quote summary; no key needed.
+    Yahoo,
+    Finnhub,
+}
+
+/// `[health]` section: the startup checks, see `health`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HealthConfig {
+    pub enabled: bool,
+    /// Show the report even when every check passed.
+    pub always_show: bool,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self { enabled: true, always_show: false }
+    }
+}
+
+/// `[pipeline]` section: the stages of a model run, see `ml::pipeline`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct PipelineConfig {
+    /// Where each stage's last run is logged, under a directory per ticker.
+    pub log_dir: String,
+    /// Run in order; the last is the model, whose stdout is the prediction.
+    pub stages: Vec<StageConfig>,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        let stage = |name: &str, command: &[&str], inputs: &[&str], outputs: &[&str], optional| StageConfig {
+            name: name.to_string(),
+            command: command.iter().map(|s| s.to_string()).collect(),
+            inputs: inputs.iter().map(|s| s.to_string()).collect(),
+            outputs: outputs.iter().map(|s| s.to_string()).collect(),
+            optional,
+        };
+        Self {
+            log_dir: "pipeline_logs".to_string(),
+            stages: vec![
+                stage(
+                    "download",
+                    &["python3", "download_stock.py", "{ticker}", "--interval", "{interval}", "--dir", "{data_dir}"],
+                    &[],
+                    &["{data_dir}/{ticker}.csv"],
+                    false,
+                ),
+                stage("preprocess", &["python3", "ml/preprocess.py", "{data_dir}/{ticker}.csv"], &["{data_dir}/{ticker}.csv"], &[], true),
+                stage(
+                    "model",
+                    &["python3", "ml/model.py", "--model-dir", "{model_dir}", "--csv", "{data_dir}/{ticker}.csv"],
+                    &[],
+                    &[],
+                    false,
+                ),
+            ],
+        }
+    }
+}
+
+/// One `[[pipeline.stages]]` entry. `{ticker}`, `{interval}`,
+/// `{data_dir}` and `{model_dir}` in the command and paths are filled in
+/// for the run.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct StageConfig {
+    pub name: String,
+    /// Program and arguments; not run through a shell.
+    pub command: Vec<String>,
+    /// Files it reads: all must exist, and a newer one reruns the stage.
+    #[serde(default)]
+    pub inputs: Vec<String>,
+    /// Files it writes; the stage is skipped while they're up to date.
+    /// Without any it always runs.
+    #[serde(default)]
+    pub outputs: Vec<String>,
+    /// A failure is reported and the later stages run anyway.
+    #[serde(default)]
+    pub optional: bool,
+}
+
+/// `[game]` section: the trading game, see `game`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    /// Play money each account starts with.
+    pub starting_cash: f64,
+    /// Width of the house quotes around the latest close, in percent; no
+    /// house market maker if 0.
+    pub house_spread_pct: f64,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self { starting_cash: 10_000.0, house_spread_pct: 1.0 }
+    }
+}
+
+/// `[layout]` section: dashboard proportions and hidden panels. Written
+/// back by the app when they're changed from the keyboard.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LayoutConfig {
+    /// Heights of the chart, table and ML list rows, in percent.
+    pub rows: [u16; 3],
+    pub hidden: Vec<Panel>,
+    /// Keep the menu bar along the top, not only while it's open.
+    pub menu_bar: bool,
+    /// Keys of the focused panel along the bottom, see `hints`.
+    pub hints_bar: bool,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self { rows: [50, 30, 20], hidden: Vec::new(), menu_bar: false, hints_bar: true }
+    }
+}
+
+impl LayoutConfig {
+    pub fn is_hidden(&self, panel: Panel) -> bool {
+        self.hidden.contains(&panel)
+    }
+}
+
+/// `[chart]` section: how the charts are drawn.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ChartConfig {
+    pub marker: ChartMarker,
+    /// Gridlines across the Stock Chart and Compare chart at each tick.
+    pub grid: bool,
+    /// About how many price ticks to label up the side; 0 for none.
+    pub ticks: usize,
+}
+
+impl Default for ChartConfig {
+    fn default() -> Self {
+        Self { marker: ChartMarker::Braille, grid: true, ticks: 5 }
+    }
+}
+
+/// What lines are drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChartMarker {
+    /// 2x4 dots a cell.
+    Braille,
+    /// One dot a cell, for fonts without braille.
+    Dot,
+    /// One full block a cell.
+    Block,
+}
+
+/// `[overview]` section: the market overview's cells, see `overview`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct OverviewConfig {
+    pub size: TileSize,
+    /// Market capitalisation per ticker, for `size = "market_cap"`.
+    pub market_caps: BTreeMap<Ticker, f64>,
+    /// Percent move at which a cell's colour is at its strongest.
+    pub full_pct: f64,
+}
+
+impl Default for OverviewConfig {
+    fn default() -> Self {
+        Self { size: TileSize::Equal, market_caps: BTreeMap::new(), full_pct: 3.0 }
+    }
+}
+
+/// What a market overview cell's area is in proportion to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TileSize {
+    Equal,
+    MarketCap,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Panel {
+    Chart,
+    LiveTrades,
+    Performance,
+    Risk,
+    Accounts,
+    Allocation,
+    Equity,
+    Jobs,
+    MlList,
+    News,
+    Calendar,
+    Search,
+}
+
+/// Reads the config at `path`, falling back to defaults if it doesn't exist.
+pub fn load(path: &str) -> Result<Config, Box<dyn Error>> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(toml::from_str(&text)?),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Rewrites the values in the `[layout]` section of the config at `path`,
+/// adding the section if there isn't one. Everything else in the file,
+/// comments included, is kept as is.
+pub fn save_layout(path: &str, layout: &LayoutConfig) -> Result<(), Box<dyn Error>> {
+    save_section(path, "layout", &toml::to_string(layout)?)
+}
+
+/// Adds `rule` to `ticker`'s `[daemon.alerts]` in the config at `path`,
+/// unless it's already there, the way `save_layout` writes.
+pub fn add_alert(path: &str, ticker: &Ticker, rule: &str) -> Result<(), Box<dyn Error>> {
+    let mut rules = load(path)?.daemon.alerts.remove(ticker).unwrap_or_default();
+    if rules.iter().any(|r| r == rule) {
+        return Ok(());
+    }
+    rules.push(rule.to_string());
+    save_section(path, "daemon.alerts", &toml::to_string(&BTreeMap::from([(ticker, rules)]))?)
+}
+
+/// Sets the `key = value` lines of `values` in the `[section]` table of the
+/// config at `path` the way `save_layout` does. Keys already in the section
+/// but not in `values` are left alone.
+pub fn save_section(path: &str, section: &str, values: &str) -> Result<(), Box<dyn Error>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let header = format!("[{}]", section);
+    // `key = value` lines still to be written.
+    let mut pending: Vec<String> = values.lines().filter(|l| !l.trim().is_empty()).map(str::to_string).collect();
+    let key_of = |line: &str| line.split('=').next().unwrap_or("").trim().to_string();
+    let mut out: Vec<String> = Vec::new();
+    let mut in_section = false;
+    let mut found = false;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            if in_section {
+                insert_before_blank_tail(&mut out, pending.drain(..));
+            }
+            in_section = trimmed == header;
+            found |= in_section;
+        } else if in_section && !trimmed.starts_with('#') && trimmed.contains('=') {
+            let key = key_of(trimmed);
+            if let Some(i) = pending.iter().position(|p| key_of(p) == key) {
+                out.push(pending.remove(i));
+                continue;
+            }
+        }
+        out.push(line.to_string());
+    }
+    if in_section {
+        insert_before_blank_tail(&mut out, pending.drain(..));
+    }
+    if !found {
+        if out.last().is_some_and(|l| !l.trim().is_empty()) {
+            out.push(String::new());
+        }
+        out.push(header);
+        out.append(&mut pending);
+    }
+    // Write then rename, so a crash mid-write leaves the old config.
+    let tmp = format!("{}.tmp", path);
+    fs::write(&tmp, out.join("\n") + "\n")?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Appends `lines` to a section, ahead of the blank lines separating it
+/// from the next one.
+fn insert_before_blank_tail(out: &mut Vec<String>, lines: impl Iterator<Item = String>) {
+    let at = out.iter().rposition(|l| !l.trim().is_empty()).map_or(0, |i| i + 1);
+    out.splice(at..at, lines);
+}
+
+/// `[stream]` section: WebSocket ticks, used when built with `streaming`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+#[cfg_attr(not(feature = "streaming"), allow(dead_code))]
+pub struct StreamConfig {
+    pub enabled: bool,
+    pub provider: StreamProvider,
+    pub api_key: String,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: StreamProvider::Finnhub,
+            api_key: String::new(),
+        }
+    }
+}
+
+/// `[paper]` section: the paper engine, the broker used unless `[alpaca]`
+/// is enabled, see `broker::paper`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PaperConfig {
+    /// Cash the paper account opens with.
+    pub starting_cash: f64,
+    /// Account in account_summary.csv that fills are booked to; empty
+    /// leaves the history alone.
+    pub account: String,
+}
+
+impl Default for PaperConfig {
+    fn default() -> Self {
+        Self { starting_cash: 100_000.0, account: String::new() }
+    }
+}
+
+/// `[sizing]` section: how a prediction becomes a suggested position, see
+/// `sizing`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SizingConfig {
+    /// Share of equity lost if a buy made at full confidence hits its stop.
+    pub risk_per_trade: f64,
+    /// Bars the average true range is taken over.
+    pub atr_period: usize,
+    /// Stop distance below the entry, in ATRs.
+    pub stop_atr: f64,
+    /// Largest share of equity one suggested buy may take.
+    pub max_position: f64,
+    /// Below this confidence, 0.0..=1.0, the suggestion is to hold.
+    pub min_confidence: f64,
+}
+
+impl Default for SizingConfig {
+    fn default() -> Self {
+        Self { risk_per_trade: 0.01, atr_period: 14, stop_atr: 2.0, max_position: 0.25, min_confidence: 0.1 }
+    }
+}
+
+/// `[strategy]` section: the signals of the ML list's Signal column, `stm
+/// backtest` and auto-trading, see `strategy`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct StrategyConfig {
+    pub kind: StrategyKind,
+    /// Bars of the fast and slow averages of `sma`.
+    pub fast: usize,
+    pub slow: usize,
+    /// Bars the RSI of `rsi` is taken over.
+    pub rsi_period: usize,
+    /// RSI below which `rsi` buys, and above which it sells.
+    pub oversold: f64,
+    pub overbought: f64,
+    /// Predicted move, in percent of the last close, `ml` acts on.
+    pub ml_threshold_pct: f64,
+    /// Cash `stm backtest` starts with.
+    pub backtest_cash: f64,
+}
+
+impl Default for StrategyConfig {
+    fn default() -> Self {
+        Self {
+            kind: StrategyKind::default(),
+            fast: 10,
+            slow: 30,
+            rsi_period: 14,
+            oversold: 30.0,
+            overbought: 70.0,
+            ml_threshold_pct: 1.0,
+            backtest_cash: 10_000.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StrategyKind {
+    /// Fast average crossing the slow one.
+    #[default]
+    Sma,
+    /// RSI mean reversion.
+    Rsi,
+    /// The logged model predictions.
+    Ml,
+}
+
+/// `[autotrade]` section: broker orders placed on `[strategy]` signals
+/// while armed. It always starts disarmed.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AutoTradeConfig {
+    /// Whether it can be armed at all.
+    pub enabled: bool,
+    /// Shares bought or sold on each signal.
+    pub qty: u64,
+    /// Most shares of one ticker a buy may bring the position to, with
+    /// its open orders filled.
+    pub max_shares: u64,
+    /// Most a ticker's position may be worth after a buy, at the last
+    /// price; 0 for no limit.
+    pub max_value: f64,
+}
+
+impl Default for AutoTradeConfig {
+    fn default() -> Self {
+        Self { enabled: false, qty: 1, max_shares: 10, max_value: 5_000.0 }
+    }
+}
+
+/// `[tax]` section: how `stm gains` matches sells to the lots bought, see
+/// `tax`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct TaxConfig {
+    pub method: LotMethod,
+    /// A lot held longer than this many days is a long-term gain or loss.
+    pub long_term_days: i64,
+}
+
+impl Default for TaxConfig {
+    fn default() -> Self {
+        Self { method: LotMethod::default(), long_term_days: 365 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LotMethod {
+    /// Sells take the oldest lots first.
+    #[default]
+    Fifo,
+    /// Sells take the newest lots first.
+    Lifo,
+}
+
+/// `[fees]` section: the commission each broker fill pays, a flat amount
+/// plus a percent of what it traded. The paper engine takes it from its
+/// cash; it's booked with every fill either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct FeesConfig {
+    pub per_fill: f64,
+    pub pct: f64,
+}
+
+impl FeesConfig {
+    /// The fee on a fill worth `value`.
+    pub fn charge(&self, value: f64) -> f64 {
+        self.per_fill + value.abs() * self.pct / 100.0
+    }
+}
+
+/// `[alpaca]` section: orders routed to an Alpaca paper account, used when
+/// built with `alpaca`, see `broker`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+#[cfg_attr(not(feature = "alpaca"), allow(dead_code))]
+pub struct AlpacaConfig {
+    pub enabled: bool,
+    /// API keys; blank reads `APCA_API_KEY_ID` and `APCA_API_SECRET_KEY`
+    /// from the environment instead.
+    pub key_id: String,
+    pub secret_key: String,
+    /// Only the paper trading endpoint is accepted.
+    pub base_url: String,
+    /// Account in account_summary.csv that fills are booked to; empty
+    /// leaves the history alone.
+    pub account: String,
+    /// Seconds between polls of the account, positions and orders.
+    pub poll_secs: u64,
+}
+
+impl Default for AlpacaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            key_id: String::new(),
+            secret_key: String::new(),
+            base_url: "https://paper-api.alpaca.markets".to_string(),
+            account: String::new(),
+            poll_secs: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamProvider {
+    Finnhub,
+    Polygon,
+}
+
+#[cfg_attr(not(feature = "streaming"), allow(dead_code))]
+impl StreamProvider {
+    pub fn name(self) -> &'static str {
+        match self {
+            StreamProvider::Finnhub => "finnhub",
+            StreamProvider::Polygon => "polygon",
+        }
+    }
+    /// Whether the socket carries bid/ask quotes as well as trades.
+    pub fn streams_quotes(self) -> bool {
+        matches!(self, StreamProvider::Polygon)
+    }
+}
@@ -0,0 +1,197 @@
+//! `stm daemon`: keeps the stored data fresh with the dashboard closed.
+//!
+//! Every `[daemon] every_mins` it downloads each configured ticker (every
+//! stored series if none are listed) over its stored interval, runs the
+//! `[pipeline]` stages before the model, and checks the `[daemon.alerts]` rules against the new
+//! bars. The dashboard opens on whatever the last cycle wrote. An alert
+//! that fires is printed and appended to `ALERTS_LOG`, which also keeps it
+//! from firing again after a restart. Failures are printed and the next
+//! ticker or cycle goes ahead.
+//!
+//! With `[[data.roots]]`, each root's tickers are downloaded every
+//! `refresh_mins` instead, if it sets one, and not at all if that's 0; the
+//! daemon wakes as often as the most frequent root needs.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::NaiveDateTime;
+
+use crate::alerts::{AlertRule, Trigger};
+use crate::app::Effect;
+use crate::config::{DaemonConfig, PipelineConfig};
+use crate::data::{self, Bar};
+use crate::effects;
+use crate::errors::AppError;
+use crate::ids::Ticker;
+use crate::universe::{self, Root};
+
+pub const ALERTS_LOG: &str = "alerts_log.csv";
+
+/// An alert as the log identifies it: ticker, rule and bar time.
+type Fired = (String, String, String);
+
+/// Runs cycles until killed, or just one with `once`. Fails up front if an
+/// alert rule doesn't parse.
+pub fn run(config: &DaemonConfig, pipeline: &PipelineConfig, once: bool) -> Result<(), Box<dyn Error>> {
+    let mut rules = Vec::new();
+    for (ticker, specs) in &config.alerts {
+        for spec in specs {
+            let rule = AlertRule::parse(spec).map_err(|e| format!("[daemon.alerts] {}: {}", ticker, e))?;
+            rules.push((ticker.clone(), rule));
+        }
+    }
+    // Latest bar each ticker's rules were checked up to.
+    let mut checked: HashMap<Ticker, NaiveDateTime> = HashMap::new();
+    // When each root's tickers were last downloaded.
+    let mut refreshed: HashMap<String, Instant> = HashMap::new();
+    let wake_mins = universe::roots()
+        .iter()
+        .map(|root| refresh_mins(config, root))
+        .filter(|&mins| mins > 0)
+        .min()
+        .unwrap_or(config.every_mins);
+    loop {
+        cycle(config, pipeline, &rules, &mut checked, &mut refreshed);
+        if once {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_secs(wake_mins.max(1) * 60));
+    }
+}
+
+/// Minutes between downloads of `root`'s tickers; 0 for never.
+fn refresh_mins(config: &DaemonConfig, root: &Root) -> u64 {
+    root.refresh_mins.unwrap_or(config.every_mins.max(1))
+}
+
+fn cycle(
+    config: &DaemonConfig,
+    pipeline: &PipelineConfig,
+    rules: &[(Ticker, AlertRule)],
+    checked: &mut HashMap<Ticker, NaiveDateTime>,
+    refreshed: &mut HashMap<String, Instant>,
+) {
+    let now = Instant::now();
+    let due: Vec<&Root> = universe::roots()
+        .iter()
+        .filter(|root| match refresh_mins(config, root) {
+            0 => false,
+            mins => refreshed.get(&root.name).is_none_or(|at| now - *at >= Duration::from_secs(mins * 60)),
+        })
+        .collect();
+    for root in &due {
+        refreshed.insert(root.name.clone(), now);
+    }
+    let tickers = if config.tickers.is_empty() {
+        let mut stored: Vec<Ticker> = data::load_stocks().into_iter().map(|s| s.ticker).collect();
+        stored.sort();
+        stored
+    } else {
+        config.tickers.clone()
+    };
+    for ticker in tickers.iter().filter(|t| due.contains(&universe::root_of(t))) {
+        let interval = data::read_interval(ticker);
+        let range = interval.default_range().to_string();
+        report(effects::run_blocking(Effect::RunDownload { ticker: ticker.clone(), interval, range }));
+        if config.preprocess {
+            report(effects::run_blocking(Effect::Preprocess { ticker: ticker.clone(), pipeline: pipeline.clone() }));
+        }
+    }
+    if let Err(e) = check_alerts(rules, checked, Path::new(ALERTS_LOG)) {
+        log(&format!("Alert check failed: {}", e));
+    }
+}
+
+fn report(result: Result<Vec<String>, AppError>) {
+    match result {
+        Ok(lines) => lines.iter().for_each(|line| log(line)),
+        Err(e) => log(&e.to_string()),
+    }
+}
+
+fn log(line: &str) {
+    println!("{} {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), line);
+}
+
+/// Fires each rule's new triggers, appending them to the log at `path`.
+fn check_alerts(
+    rules: &[(Ticker, AlertRule)],
+    checked: &mut HashMap<Ticker, NaiveDateTime>,
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let logged = logged(path)?;
+    let new_file = !path.exists();
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut wtr = csv::Writer::from_writer(file);
+    if new_file {
+        wtr.write_record(["ticker", "rule", "at", "value", "close"])?;
+    }
+    let mut latest: HashMap<Ticker, NaiveDateTime> = HashMap::new();
+    for (ticker, rule) in rules {
+        let bars = data::load_series(ticker).bars;
+        let Some(last) = bars.last() else { continue };
+        latest.insert(ticker.clone(), last.at);
+        for trigger in new_triggers(rule, &bars, checked.get(ticker).copied()) {
+            let key: Fired = (ticker.to_string(), rule.to_string(), trigger.at.to_string());
+            if logged.contains(&key) {
+                continue;
+            }
+            log(&format!("ALERT {} {}: {:.2} at {} (close {:.2})", ticker, rule, trigger.value, trigger.at, trigger.close));
+            wtr.write_record([key.0, key.1, key.2, trigger.value.to_string(), trigger.close.to_string()])?;
+        }
+    }
+    wtr.flush()?;
+    checked.extend(latest);
+    Ok(())
+}
+
+/// Triggers on bars after `since`, or only on the latest bar the first
+/// time a ticker is checked, so starting up doesn't replay its history.
+fn new_triggers(rule: &AlertRule, bars: &[Bar], since: Option<NaiveDateTime>) -> Vec<Trigger> {
+    let last = bars.len().saturating_sub(1);
+    rule.backtest(bars)
+        .into_iter()
+        .filter(|t| match since {
+            Some(since) => t.at > since,
+            None => t.index == last,
+        })
+        .collect()
+}
+
+/// `(ticker, rule, at)` of every alert in the log.
+fn logged(path: &Path) -> Result<HashSet<Fired>, Box<dyn Error>> {
+    if !path.exists() {
+        return Ok(HashSet::new());
+    }
+    let mut rdr = csv::Reader::from_path(path)?;
+    let mut logged = HashSet::new();
+    for record in rdr.records() {
+        let record = record?;
+        if let (Some(ticker), Some(rule), Some(at)) = (record.get(0), record.get(1), record.get(2)) {
+            logged.insert((ticker.to_string(), rule.to_string(), at.to_string()));
+        }
+    }
+    Ok(logged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_triggers_skip_history_on_the_first_check() {
+        let day = |d| chrono::NaiveDate::from_ymd_opt(2024, 6, d).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let bar = |d, close| Bar { at: day(d), close, volume: None };
+        let rule = AlertRule::parse("close > 10").unwrap();
+        let bars = [bar(3, 11.0), bar(4, 9.0), bar(5, 12.0), bar(6, 9.0), bar(7, 13.0)];
+        let at = |ts: Vec<Trigger>| ts.iter().map(|t| t.at).collect::<Vec<_>>();
+        assert_eq!(at(new_triggers(&rule, &bars, None)), vec![day(7)]);
+        assert_eq!(at(new_triggers(&rule, &bars[..4], None)), Vec::new());
+        assert_eq!(at(new_triggers(&rule, &bars, Some(day(4)))), vec![day(5), day(7)]);
+    }
+}
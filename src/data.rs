@@ -0,0 +1,607 @@
+//! Stock prices: the CSVs download_stock.py writes to `pre_stock/`, or to
+//! the `universe` roots, read for the ML list, the chart and the benchmark. Reads go through the
+//! `prices` cache; `read_bars` is the CSV parser behind it.
+//! Each CSV's `.meta.json` sidecar says what was downloaded into it, so a
+//! later download can start from its newest bar (`resume_from`).
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, Months, NaiveDate, NaiveDateTime};
+use csv::ReaderBuilder;
+use serde::Deserialize;
+
+use crate::calendar::{self, Sessions};
+use crate::date_range::DateRange;
+use crate::ids::Ticker;
+use crate::prices::{self, Column};
+use crate::returns;
+use crate::splits::{self, Split};
+use crate::universe::{self, SeriesKey};
+
+// ============================
+// Stock Data for ML List
+// ============================
+#[derive(Debug)]
+pub struct StockInfo {
+    pub ticker: Ticker,
+    pub price: f64,
+    pub change: f64,
+    pub pct_change: f64,
+    // Sessions missing from the stored history, as inclusive date ranges.
+    pub gaps: Vec<(NaiveDate, NaiveDate)>,
+    // Percent change over the last three months, if the history is as long.
+    pub return_3m: Option<f64>,
+    // Why the file couldn't be read, shown in place of its history status.
+    pub error: Option<String>,
+    // Name of the `universe` root holding the file.
+    pub source: String,
+    // The file it was read from, which tells apart a ticker held twice.
+    pub series: SeriesKey,
+}
+
+/// One row of a price CSV, matched by column name; other columns (Open,
+/// High, Low) are ignored until something needs them. Cells that are
+/// empty, `null` or otherwise unparsable become `None`.
+#[derive(Debug, Deserialize)]
+struct RawBar {
+    #[serde(rename = "Date", alias = "Datetime")]
+    date: String,
+    #[serde(rename = "Close", default, deserialize_with = "csv::invalid_option")]
+    close: Option<f64>,
+    #[serde(rename = "Volume", default, deserialize_with = "csv::invalid_option")]
+    volume: Option<f64>,
+    #[serde(rename = "Adj Close", default, deserialize_with = "csv::invalid_option")]
+    adj_close: Option<f64>,
+}
+
+/// A price bar with a usable timestamp and close.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bar {
+    pub at: NaiveDateTime,
+    pub close: f64,
+    // `None` when the file has no Volume column or the cell is blank.
+    pub volume: Option<f64>,
+}
+
+/// Reads a Yahoo Finance CSV by header name, oldest bar first, restated
+/// for the splits it's found to be unadjusted for (see `splits`).
+///
+/// Handles both the flat layout (`Date,Open,High,Low,Close,Adj Close,Volume`)
+/// and the two-level layout newer yfinance writes (`Price,Close,...` then
+/// `Ticker,...` and `Date,,,` rows), in any column order. Rows without a
+/// timestamp or close (holidays, provider `null`s) are skipped.
+pub fn read_bars(file_path: &str) -> Result<Vec<Bar>, Box<dyn Error>> {
+    Ok(read_series(file_path)?.0)
+}
+
+/// `read_bars`, with the splits the bars were restated for.
+pub fn read_series(file_path: &str) -> Result<(Vec<Bar>, Vec<Split>), Box<dyn Error>> {
+    let mut rdr = ReaderBuilder::new().has_headers(false).flexible(true).from_path(file_path)?;
+    let mut rows = rdr.records();
+    let Some(first) = rows.next() else {
+        return Ok(Default::default());
+    };
+    let mut headers = first?;
+    // The two-level layout names the index column "Price"; it holds dates.
+    if headers.get(0) == Some("Price") {
+        let renamed: Vec<&str> = std::iter::once("Date").chain(headers.iter().skip(1)).collect();
+        headers = csv::StringRecord::from(renamed);
+    }
+    for required in ["Date", "Close"] {
+        let found = headers.iter().any(|h| h == required || (required == "Date" && h == "Datetime"));
+        if !found {
+            return Err(format!("no {} column", required).into());
+        }
+    }
+
+    let mut bars = Vec::new();
+    let mut adj_closes = Vec::new();
+    for (line, row) in rows.enumerate() {
+        let row = row?;
+        if matches!(row.get(0), Some("Ticker") | Some("Date") | Some("Datetime")) {
+            continue; // second and third header rows of the two-level layout
+        }
+        let raw: RawBar = row
+            .deserialize(Some(&headers))
+            .map_err(|e| format!("line {}: {}", line + 2, e))?;
+        if let (Some(at), Some(close)) = (parse_timestamp(&raw.date), raw.close) {
+            bars.push(Bar { at, close, volume: raw.volume });
+            adj_closes.push(raw.adj_close);
+        }
+    }
+    let ticker = Path::new(file_path).file_stem().and_then(|s| Ticker::parse(&s.to_string_lossy()).ok());
+    let found = splits::restate(&mut bars, &adj_closes, ticker.as_ref());
+    Ok((bars, found))
+}
+
+pub fn get_stock_info(file_path: &str, ticker: &Ticker) -> StockInfo {
+    let mut info = StockInfo {
+        ticker: ticker.clone(),
+        price: 0.0,
+        change: 0.0,
+        pct_change: 0.0,
+        gaps: Vec::new(),
+        return_3m: None,
+        error: None,
+        source: String::new(),
+        series: SeriesKey::default(),
+    };
+    let bars = match prices::cached_bars(Path::new(file_path)) {
+        Ok(bars) => bars,
+        Err(e) => {
+            info.error = Some(e.to_string());
+            return info;
+        }
+    };
+    let times: Vec<NaiveDateTime> = bars.iter().map(|b| b.at).collect();
+    let closes: Vec<f64> = bars.iter().map(|b| b.close).collect();
+    let sessions = Sessions::for_ticker(ticker);
+    if let Some((price, change, pct_change)) = price_change(&times, &closes, sessions) {
+        info.price = price;
+        info.change = change;
+        info.pct_change = pct_change;
+        let dates: Vec<NaiveDate> = times.iter().map(|at| at.date()).collect();
+        info.gaps = calendar::missing_ranges(&dates, sessions);
+        info.return_3m = three_month_return(&times, &closes);
+    }
+    info
+}
+
+/// Latest close with its change and percent change since the previous
+/// session: the last bar of an earlier day for exchange tickers, the last
+/// bar at least 24 hours older for crypto. With intraday bars that's the
+/// day's move rather than the last bar's; if nothing is old enough it's
+/// measured from the first bar. `None` with fewer than two bars. `times`
+/// and `closes` are parallel, oldest first.
+pub fn price_change(times: &[NaiveDateTime], closes: &[f64], sessions: Sessions) -> Option<(f64, f64, f64)> {
+    let (&last_at, &last) = (times.last()?, closes.last()?);
+    if closes.len() < 2 {
+        return None;
+    }
+    let earlier = match sessions {
+        Sessions::Exchange => times.partition_point(|at| at.date() < last_at.date()),
+        Sessions::Continuous => times.partition_point(|&at| at <= last_at - chrono::Duration::hours(24)),
+    };
+    let prev = closes[earlier.saturating_sub(1)];
+    let change = last - prev;
+    Some((last, change, if prev != 0.0 { change / prev * 100.0 } else { 0.0 }))
+}
+
+impl StockInfo {
+    /// Three-month return relative to a benchmark's over the same span, in
+    /// percent: above zero when the stock did better.
+    pub fn relative_strength(&self, benchmark_3m: Option<f64>) -> Option<f64> {
+        let (own, bench) = (1.0 + self.return_3m? / 100.0, 1.0 + benchmark_3m? / 100.0);
+        (bench > 0.0).then(|| (own / bench - 1.0) * 100.0)
+    }
+}
+
+/// Percent change from the last close at least three months before the
+/// latest bar to the latest close. `None` if the history doesn't go back
+/// that far. `dates` and `closes` are parallel, oldest first.
+pub fn three_month_return(dates: &[NaiveDateTime], closes: &[f64]) -> Option<f64> {
+    let (&last_at, &last) = (dates.last()?, closes.last()?);
+    let cutoff = last_at.checked_sub_months(Months::new(3))?;
+    let start = dates.partition_point(|&at| at <= cutoff).checked_sub(1)?;
+    let from = closes[start];
+    (from != 0.0).then(|| (last - from) / from * 100.0)
+}
+
+/// Every root's stocks, subdirectories included, each tagged with its root
+/// and directory. A ticker stored twice is listed from each file.
+pub fn load_stocks() -> Vec<StockInfo> {
+    let roots = universe::roots();
+    universe::scan()
+        .series
+        .into_iter()
+        .map(|(i, series)| {
+            let mut stock = get_stock_info(&series.path.to_string_lossy(), &series.ticker);
+            stock.source = series.source(&roots[i]);
+            stock.series = series.key(&roots[i]);
+            stock
+        })
+        .collect()
+}
+
+// ============================
+// Bar Intervals and Price Series
+// ============================
+/// Bar size of a downloaded series, as understood by yfinance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interval {
+    OneMinute,
+    FiveMinutes,
+    FifteenMinutes,
+    OneHour,
+    #[default]
+    OneDay,
+}
+
+impl Interval {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "1m" => Some(Interval::OneMinute),
+            "5m" => Some(Interval::FiveMinutes),
+            "15m" => Some(Interval::FifteenMinutes),
+            "1h" | "60m" => Some(Interval::OneHour),
+            "1d" => Some(Interval::OneDay),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Interval::OneMinute => "1m",
+            Interval::FiveMinutes => "5m",
+            Interval::FifteenMinutes => "15m",
+            Interval::OneHour => "1h",
+            Interval::OneDay => "1d",
+        }
+    }
+
+    /// Range downloaded when none is given: yfinance caps how far back
+    /// minute bars go, so intraday intervals get 5 days and daily a year.
+    pub fn default_range(self) -> &'static str {
+        if self == Interval::OneDay { "1y" } else { "5d" }
+    }
+
+    /// Format for a single bar's time at this bar size.
+    pub fn label_format(self) -> &'static str {
+        match self {
+            Interval::OneDay => "%Y-%m-%d",
+            _ => "%m-%d %H:%M",
+        }
+    }
+
+    /// Format for chart x-axis labels across `span` of bars: times alone
+    /// when intraday bars fit in a day, months when daily bars cover years.
+    pub fn axis_format(self, span: chrono::Duration) -> &'static str {
+        match self {
+            Interval::OneDay if span > chrono::Duration::days(730) => "%Y-%m",
+            Interval::OneDay => "%Y-%m-%d",
+            _ if span <= chrono::Duration::days(1) => "%H:%M",
+            _ => "%m-%d %H:%M",
+        }
+    }
+}
+
+/// Ranges yfinance accepts for `period`.
+pub const RANGES: [&str; 11] = ["1d", "5d", "1mo", "3mo", "6mo", "1y", "2y", "5y", "10y", "ytd", "max"];
+
+/// Sidecar written by download_stock.py next to each CSV.
+#[derive(Debug, Deserialize)]
+struct SeriesMeta {
+    interval: String,
+    /// The period last downloaded whole.
+    #[serde(default)]
+    range: Option<String>,
+    /// Time of the newest bar stored, as the CSV writes it.
+    #[serde(default)]
+    last_bar: Option<String>,
+}
+
+fn read_meta(dir: &Path, ticker: &Ticker) -> Option<SeriesMeta> {
+    serde_json::from_str(&fs::read_to_string(dir.join(format!("{}.meta.json", ticker))).ok()?).ok()
+}
+
+/// Interval recorded for `ticker`; files without a sidecar are daily.
+pub fn read_interval(ticker: &Ticker) -> Interval {
+    read_interval_in(&universe::dir_of(ticker), ticker)
+}
+
+/// `read_interval` for a series stored in `dir`.
+pub fn read_interval_in(dir: &Path, ticker: &Ticker) -> Interval {
+    read_meta(dir, ticker).and_then(|meta| Interval::parse(&meta.interval)).unwrap_or_default()
+}
+
+/// Days a `RANGES` period reaches back, about; `None` for `max`.
+fn range_days(range: &str) -> Option<Option<u32>> {
+    let count = |suffix: &str| range.strip_suffix(suffix).and_then(|n| n.parse::<u32>().ok());
+    match range {
+        "max" => Some(None),
+        "ytd" => Some(Some(366)),
+        _ => count("mo").map(|n| n * 31).or_else(|| count("y").map(|n| n * 366)).or_else(|| count("d")).map(Some),
+    }
+}
+
+/// Whether a series downloaded for `stored` reaches back as far as `wanted`.
+fn covers(stored: &str, wanted: &str) -> bool {
+    match (range_days(stored), range_days(wanted)) {
+        (Some(None), Some(_)) => true,
+        (Some(Some(stored)), Some(Some(wanted))) => stored >= wanted,
+        _ => false,
+    }
+}
+
+/// The date a download of `range` of `ticker`'s `interval` bars only needs
+/// to start from: its newest stored bar's, when the series is stored at
+/// that interval and reaches back as far already. `None` means the whole
+/// range has to be downloaded.
+pub fn resume_from(ticker: &Ticker, interval: Interval, range: &str) -> Option<NaiveDate> {
+    resume_from_in(&universe::dir_of(ticker), ticker, interval, range)
+}
+
+/// `resume_from` for a series stored in `dir`.
+pub fn resume_from_in(dir: &Path, ticker: &Ticker, interval: Interval, range: &str) -> Option<NaiveDate> {
+    let meta = read_meta(dir, ticker)?;
+    if Interval::parse(&meta.interval) != Some(interval) || !covers(meta.range.as_deref()?, range) {
+        return None;
+    }
+    let last = match meta.last_bar.as_deref().and_then(parse_timestamp) {
+        Some(at) => at,
+        // Written before the sidecar kept it.
+        None => prices::cached_bars(&dir.join(format!("{}.csv", ticker))).ok()?.last()?.at,
+    };
+    Some(last.date())
+}
+
+/// Records `last` as the newest bar stored for `ticker` in `dir`'s sidecar,
+/// keeping its other fields. Series without a sidecar are left without.
+pub fn record_last_bar(dir: &Path, ticker: &Ticker, last: NaiveDateTime) -> Result<(), Box<dyn Error>> {
+    let path = dir.join(format!("{}.meta.json", ticker));
+    let Ok(text) = fs::read_to_string(&path) else {
+        return Ok(());
+    };
+    let mut meta: serde_json::Value = serde_json::from_str(&text)?;
+    meta["last_bar"] = last.format("%Y-%m-%d %H:%M:%S").to_string().into();
+    fs::write(path, meta.to_string())?;
+    Ok(())
+}
+
+/// Parses a yfinance index value: `2024-06-03`, `2024-06-03 09:30:00`, or
+/// the same with a UTC offset. Offsets are dropped, keeping exchange time.
+pub fn parse_timestamp(s: &str) -> Option<NaiveDateTime> {
+    if let Ok(dt) = DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%:z") {
+        return Some(dt.naive_local());
+    }
+    if let Ok(dt) = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S") {
+        return Some(dt);
+    }
+    NaiveDate::parse_from_str(s.get(..10)?, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+}
+
+/// Bars of one ticker for the chart, oldest first.
+#[derive(Debug, Default)]
+pub struct PriceSeries {
+    // `None` for the empty series shown when nothing is selected.
+    pub ticker: Option<Ticker>,
+    pub interval: Interval,
+    pub bars: Vec<Bar>,
+    pub error: Option<String>,
+}
+
+impl PriceSeries {
+    /// This series' latest close at or before each of `times`; `None`
+    /// before its first bar.
+    pub fn closes_at(&self, times: impl IntoIterator<Item = NaiveDateTime>) -> Vec<Option<f64>> {
+        times
+            .into_iter()
+            .map(|at| self.bars.partition_point(|b| b.at <= at).checked_sub(1).map(|i| self.bars[i].close))
+            .collect()
+    }
+}
+
+pub fn load_series(ticker: &Ticker) -> PriceSeries {
+    let mut series = PriceSeries {
+        ticker: Some(ticker.clone()),
+        interval: read_interval(ticker),
+        ..PriceSeries::default()
+    };
+    match prices::bars(ticker, DateRange::default()) {
+        Ok(bars) => series.bars = bars,
+        Err(e) => series.error = Some(e.to_string()),
+    }
+    series
+}
+
+/// `load_series` from the file `key` names rather than the ticker's first.
+pub fn load_series_at(ticker: &Ticker, key: &SeriesKey) -> PriceSeries {
+    let Some(path) = universe::path_for(key) else {
+        return load_series(ticker);
+    };
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut series = PriceSeries { ticker: Some(ticker.clone()), interval: read_interval_in(dir, ticker), ..PriceSeries::default() };
+    match prices::cached_bars(&path) {
+        Ok(bars) => series.bars = bars.to_vec(),
+        Err(e) => series.error = Some(e.to_string()),
+    }
+    series
+}
+
+// ============================
+// Benchmark Mini-Chart
+// ============================
+#[derive(Debug)]
+pub struct Benchmark {
+    pub ticker: Ticker,
+    pub dates: Vec<NaiveDateTime>,
+    pub closes: Vec<f64>,
+    // Three-month return, what the ML list's relative strength is against.
+    pub return_3m: Option<f64>,
+    // Why the closes couldn't be read, if they couldn't.
+    pub error: Option<String>,
+}
+
+impl Benchmark {
+    pub fn load(ticker: &Ticker) -> Self {
+        let (columns, error) = match prices::query(ticker, DateRange::default(), &[Column::Date, Column::Close]) {
+            Ok(columns) => (columns, None),
+            Err(e) => (Default::default(), Some(e.to_string())),
+        };
+        let return_3m = three_month_return(&columns.dates, &columns.closes);
+        Self { ticker: ticker.clone(), dates: columns.dates, closes: columns.closes, return_3m, error }
+    }
+
+    /// The last close of each session, for comparing against other series
+    /// day by day.
+    pub fn sessions(&self) -> Vec<(NaiveDate, f64)> {
+        let bars: Vec<(NaiveDate, f64)> = self.dates.iter().map(|at| at.date()).zip(self.closes.iter().copied()).collect();
+        returns::sessions(&bars)
+    }
+
+    /// Last close and its change versus the previous session.
+    pub fn daily_change(&self) -> Option<(f64, f64, f64)> {
+        price_change(&self.dates, &self.closes, Sessions::for_ticker(&self.ticker))
+    }
+
+    /// The most recent `width` closes rescaled to 0..=100 for a `Sparkline`.
+    pub fn sparkline_data(&self, width: usize) -> Vec<u64> {
+        let recent = &self.closes[self.closes.len().saturating_sub(width)..];
+        let min = recent.iter().copied().fold(f64::MAX, f64::min);
+        let max = recent.iter().copied().fold(f64::MIN, f64::max);
+        let span = (max - min).max(f64::EPSILON);
+        recent.iter().map(|c| ((c - min) / span * 100.0) as u64).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    const FLAT: &str = "Date,Open,High,Low,Close,Adj Close,Volume\n\
+        2024-06-03,10,11,9,10.0,10.0,100\n\
+        2024-06-04,10,11,9,null,null,\n\
+        2024-06-05,10,11,9,12.0,12.0,\n";
+
+    const TWO_LEVEL: &str = "Price,Close,High,Low,Open,Volume\n\
+        Ticker,AAPL,AAPL,AAPL,AAPL,AAPL\n\
+        Date,,,,,\n\
+        2024-06-03,100.0,101,99,100,5000\n\
+        2024-06-04,95.0,101,94,100,6000\n";
+
+    /// A fresh directory under the system temp dir.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("stm-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn read_bars_skips_rows_without_a_close() {
+        let dir = temp_dir("flat");
+        let path = dir.join("X.csv");
+        fs::write(&path, FLAT).unwrap();
+        let bars = read_bars(path.to_str().unwrap()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let closes: Vec<f64> = bars.iter().map(|b| b.close).collect();
+        assert_eq!(closes, vec![10.0, 12.0]);
+        assert_eq!(bars[0].volume, Some(100.0));
+        assert_eq!(bars[1].volume, None);
+    }
+
+    #[test]
+    fn read_bars_handles_the_two_level_layout() {
+        let dir = temp_dir("two-level");
+        let path = dir.join("AAPL.csv");
+        fs::write(&path, TWO_LEVEL).unwrap();
+        let bars = read_bars(path.to_str().unwrap()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(bars.len(), 2);
+        assert_eq!(bars[1].at, NaiveDate::from_ymd_opt(2024, 6, 4).unwrap().and_hms_opt(0, 0, 0).unwrap());
+        assert_eq!(bars[1].close, 95.0);
+    }
+
+    #[test]
+    fn read_bars_needs_a_close_column() {
+        let dir = temp_dir("no-close");
+        let path = dir.join("X.csv");
+        fs::write(&path, "Date,Open\n2024-06-03,10\n").unwrap();
+        let err = read_bars(path.to_str().unwrap()).unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(err.to_string(), "no Close column");
+    }
+
+    #[test]
+    fn stock_info_reads_the_ticker_csv() {
+        let dir = temp_dir("stocks");
+        fs::write(dir.join("AAPL.csv"), TWO_LEVEL).unwrap();
+        fs::write(dir.join("BAD.csv"), "garbage\n").unwrap();
+        let stock = |ticker: &str| {
+            let path = dir.join(format!("{}.csv", ticker));
+            get_stock_info(&path.to_string_lossy(), &Ticker::parse(ticker).unwrap())
+        };
+        let stocks = [stock("AAPL"), stock("BAD")];
+        fs::remove_dir_all(&dir).unwrap();
+
+        let aapl = &stocks[0];
+        assert_eq!(aapl.price, 95.0);
+        assert_eq!(aapl.change, -5.0);
+        assert_eq!(aapl.pct_change, -5.0);
+        assert!(aapl.error.is_none());
+        assert_eq!(stocks[1].error.as_deref(), Some("no Date column"));
+    }
+
+    #[test]
+    fn downloads_resume_from_the_last_stored_bar() {
+        let dir = temp_dir("resume");
+        let ticker = Ticker::parse("AAPL").unwrap();
+        fs::write(dir.join("AAPL.csv"), TWO_LEVEL).unwrap();
+        let day = |d: u32| NaiveDate::from_ymd_opt(2024, 6, d).unwrap();
+        let resume = |range: &str| resume_from_in(&dir, &ticker, Interval::OneDay, range);
+        assert_eq!(resume("1y"), None);
+
+        fs::write(dir.join("AAPL.meta.json"), r#"{"interval":"1d","range":"1y"}"#).unwrap();
+        assert_eq!(resume("1y"), Some(day(4)));
+        record_last_bar(&dir, &ticker, day(7).and_hms_opt(0, 0, 0).unwrap()).unwrap();
+        let (six_months, two_years) = (resume("6mo"), resume("2y"));
+        let hourly = resume_from_in(&dir, &ticker, Interval::OneHour, "1y");
+        let meta = fs::read_to_string(dir.join("AAPL.meta.json")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!((six_months, two_years, hourly), (Some(day(7)), None, None));
+        assert!(meta.contains(r#""range":"1y""#));
+        assert!(covers("max", "10y") && covers("ytd", "3mo") && !covers("5d", "1mo"));
+    }
+
+    #[test]
+    fn three_month_return_starts_from_the_close_three_months_back() {
+        let at = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let dates = [at(1, 2), at(3, 1), at(3, 4), at(6, 3)];
+        assert_eq!(three_month_return(&dates, &[50.0, 100.0, 80.0, 120.0]), Some(20.0));
+        assert_eq!(three_month_return(&dates[2..], &[80.0, 120.0]), None);
+    }
+
+    #[test]
+    fn price_change_measures_from_the_previous_session() {
+        let at = |d, h| NaiveDate::from_ymd_opt(2024, 6, d).unwrap().and_hms_opt(h, 0, 0).unwrap();
+        // Intraday equity bars: the day's move, not the last bar's.
+        let times = [at(3, 15), at(4, 10), at(4, 11), at(4, 12)];
+        let closes = [100.0, 104.0, 106.0, 110.0];
+        assert_eq!(price_change(&times, &closes, Sessions::Exchange), Some((110.0, 10.0, 10.0)));
+        // Crypto: 24 hours back, which here lands on the 3rd's bar too.
+        let times = [at(3, 11), at(3, 12), at(4, 11), at(4, 12)];
+        assert_eq!(price_change(&times, &closes, Sessions::Continuous).map(|c| c.1), Some(6.0));
+        assert_eq!(price_change(&times[..1], &closes[..1], Sessions::Continuous), None);
+        let btc = Ticker::parse("btc-usd").unwrap();
+        assert_eq!(Sessions::for_ticker(&btc), Sessions::Continuous);
+        assert_eq!(Sessions::for_ticker(&Ticker::parse("BRK-B").unwrap()), Sessions::Exchange);
+        let weekend = [NaiveDate::from_ymd_opt(2024, 6, 7).unwrap(), NaiveDate::from_ymd_opt(2024, 6, 10).unwrap()];
+        assert_eq!(calendar::missing_ranges(&weekend, Sessions::Exchange), vec![]);
+        assert_eq!(calendar::session_count(&calendar::missing_ranges(&weekend, Sessions::Continuous), Sessions::Continuous), 2);
+    }
+
+    #[test]
+    fn closes_at_takes_the_latest_bar_not_after_each_time() {
+        let at = |d, h| NaiveDate::from_ymd_opt(2024, 6, d).unwrap().and_hms_opt(h, 0, 0).unwrap();
+        let series = PriceSeries {
+            bars: vec![Bar { at: at(3, 0), close: 10.0, volume: None }, Bar { at: at(5, 0), close: 12.0, volume: None }],
+            ..PriceSeries::default()
+        };
+        assert_eq!(series.closes_at([at(2, 9), at(3, 0), at(4, 9), at(6, 9)]), vec![None, Some(10.0), Some(10.0), Some(12.0)]);
+    }
+
+    #[test]
+    fn parse_timestamp_accepts_yfinance_index_formats() {
+        let day = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        assert_eq!(parse_timestamp("2024-06-03"), day.and_hms_opt(0, 0, 0));
+        assert_eq!(parse_timestamp("2024-06-03 09:30:00"), day.and_hms_opt(9, 30, 0));
+        assert_eq!(parse_timestamp("2024-06-03 09:30:00-04:00"), day.and_hms_opt(9, 30, 0));
+        assert_eq!(parse_timestamp("yesterday"), None);
+    }
+}
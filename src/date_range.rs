@@ -0,0 +1,260 @@
+//! Date range picker popup shared by the chart zoom, alert backtests and
+//! ranged downloads.
+//!
+//! The top row holds quick presets (1M, 3M, YTD, 1Y, Max), picked with
+//! Left/Right or `1`..`5`; below are From/To fields for typing dates as
+//! `YYYY-MM-DD`, where a blank field leaves that end open. Tab moves between
+//! the rows, Enter applies the row the cursor is on, Esc cancels.
+
+use std::fmt;
+
+use chrono::{Datelike, Months, NaiveDate, NaiveDateTime};
+use crossterm::event::KeyCode;
+use serde::{Deserialize, Serialize};
+
+/// Inclusive span of dates; `None` leaves that end unbounded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct DateRange {
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+}
+
+impl DateRange {
+    pub fn is_all(&self) -> bool {
+        self.start.is_none() && self.end.is_none()
+    }
+
+    /// The run of `items` (sorted by time) that falls inside the range.
+    pub fn slice<'a, T>(&self, items: &'a [T], at: impl Fn(&T) -> NaiveDateTime) -> &'a [T] {
+        let first = self.start.map_or(0, |start| items.partition_point(|item| at(item).date() < start));
+        let last = self.end.map_or(items.len(), |end| items.partition_point(|item| at(item).date() <= end));
+        &items[first..last.max(first)]
+    }
+}
+
+impl fmt::Display for DateRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = |date: Option<NaiveDate>, open: &str| date.map_or(open.to_string(), |d| d.format("%Y-%m-%d").to_string());
+        if self.is_all() {
+            return write!(f, "all");
+        }
+        write!(f, "{}..{}", label(self.start, "start"), label(self.end, "latest"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    OneMonth,
+    ThreeMonths,
+    YearToDate,
+    OneYear,
+    Max,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 5] = [Preset::OneMonth, Preset::ThreeMonths, Preset::YearToDate, Preset::OneYear, Preset::Max];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Preset::OneMonth => "1M",
+            Preset::ThreeMonths => "3M",
+            Preset::YearToDate => "YTD",
+            Preset::OneYear => "1Y",
+            Preset::Max => "Max",
+        }
+    }
+
+    /// The matching `--period` of download_stock.py.
+    pub fn period(self) -> &'static str {
+        match self {
+            Preset::OneMonth => "1mo",
+            Preset::ThreeMonths => "3mo",
+            Preset::YearToDate => "ytd",
+            Preset::OneYear => "1y",
+            Preset::Max => "max",
+        }
+    }
+
+    /// The preset's span ending at `anchor`, usually the latest bar.
+    pub fn range(self, anchor: NaiveDate) -> DateRange {
+        let months_back = |n| anchor.checked_sub_months(Months::new(n));
+        let start = match self {
+            Preset::OneMonth => months_back(1),
+            Preset::ThreeMonths => months_back(3),
+            Preset::YearToDate => NaiveDate::from_ymd_opt(anchor.year(), 1, 1),
+            Preset::OneYear => months_back(12),
+            Preset::Max => None,
+        };
+        DateRange { start, end: None }
+    }
+}
+
+/// Picker rows, in Tab order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickerRow {
+    Presets,
+    From,
+    To,
+}
+
+/// What the user settled on. `preset` is set when a preset was picked, for
+/// callers that can use it directly (downloads pass it as `--period`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    pub preset: Option<Preset>,
+    pub range: DateRange,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PickerStep {
+    Open,
+    Cancelled,
+    Picked(Selection),
+}
+
+#[derive(Debug, Clone)]
+pub struct RangePicker {
+    /// Shown in the popup title, e.g. "Chart zoom".
+    pub purpose: String,
+    // Date presets are counted back from.
+    anchor: NaiveDate,
+    pub row: PickerRow,
+    // Index into `Preset::ALL` under the cursor.
+    pub preset: usize,
+    pub from: String,
+    pub to: String,
+    pub error: Option<String>,
+}
+
+impl RangePicker {
+    /// A picker for `purpose`, with presets counted back from `anchor` and
+    /// the date fields filled in from `current`.
+    pub fn new(purpose: impl Into<String>, anchor: NaiveDate, current: DateRange) -> Self {
+        let date = |d: Option<NaiveDate>| d.map_or(String::new(), |d| d.format("%Y-%m-%d").to_string());
+        let preset = Preset::ALL.iter().position(|p| p.range(anchor) == current).unwrap_or(Preset::ALL.len() - 1);
+        Self {
+            purpose: purpose.into(),
+            anchor,
+            row: PickerRow::Presets,
+            preset,
+            from: date(current.start),
+            to: date(current.end),
+            error: None,
+        }
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode) -> PickerStep {
+        match (code, self.row) {
+            (KeyCode::Esc, _) => return PickerStep::Cancelled,
+            (KeyCode::Tab | KeyCode::Down, _) => {
+                self.row = match self.row {
+                    PickerRow::Presets => PickerRow::From,
+                    PickerRow::From => PickerRow::To,
+                    PickerRow::To => PickerRow::Presets,
+                };
+            }
+            (KeyCode::BackTab | KeyCode::Up, _) => {
+                self.row = match self.row {
+                    PickerRow::Presets => PickerRow::To,
+                    PickerRow::From => PickerRow::Presets,
+                    PickerRow::To => PickerRow::From,
+                };
+            }
+            (KeyCode::Left, PickerRow::Presets) => self.preset = self.preset.saturating_sub(1),
+            (KeyCode::Right, PickerRow::Presets) => self.preset = (self.preset + 1).min(Preset::ALL.len() - 1),
+            (KeyCode::Char(c @ '1'..='5'), PickerRow::Presets) => {
+                self.preset = c as usize - '1' as usize;
+                return self.pick_preset();
+            }
+            (KeyCode::Enter, PickerRow::Presets) => return self.pick_preset(),
+            (KeyCode::Enter, PickerRow::From | PickerRow::To) => match self.custom_range() {
+                Ok(range) => return PickerStep::Picked(Selection { preset: None, range }),
+                Err(msg) => self.error = Some(msg),
+            },
+            (KeyCode::Char(c), PickerRow::From | PickerRow::To) if c.is_ascii_digit() || c == '-' => {
+                self.field_mut().push(c);
+                self.error = None;
+            }
+            (KeyCode::Backspace, PickerRow::From | PickerRow::To) => {
+                self.field_mut().pop();
+                self.error = None;
+            }
+            _ => {}
+        }
+        PickerStep::Open
+    }
+
+    fn field_mut(&mut self) -> &mut String {
+        if self.row == PickerRow::To { &mut self.to } else { &mut self.from }
+    }
+
+    fn pick_preset(&self) -> PickerStep {
+        let preset = Preset::ALL[self.preset];
+        PickerStep::Picked(Selection { preset: Some(preset), range: preset.range(self.anchor) })
+    }
+
+    fn custom_range(&self) -> Result<DateRange, String> {
+        let parse = |label: &str, s: &str| match s.trim() {
+            "" => Ok(None),
+            s => NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .map(Some)
+                .map_err(|_| format!("{} must be YYYY-MM-DD, not {}", label, s)),
+        };
+        let range = DateRange { start: parse("From", &self.from)?, end: parse("To", &self.to)? };
+        if let (Some(start), Some(end)) = (range.start, range.end)
+            && start > end
+        {
+            return Err("From must not be after To".to_string());
+        }
+        Ok(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn starts(anchor: NaiveDate) -> Vec<Option<NaiveDate>> {
+        Preset::ALL.iter().map(|p| p.range(anchor).start).collect()
+    }
+
+    #[test]
+    fn presets_count_back_from_the_anchor() {
+        // 1M, 3M, YTD, 1Y, Max. Months short of the anchor's day end on
+        // their last day.
+        assert_eq!(
+            starts(date(2024, 3, 31)),
+            [Some(date(2024, 2, 29)), Some(date(2023, 12, 31)), Some(date(2024, 1, 1)), Some(date(2023, 3, 31)), None]
+        );
+        assert_eq!(
+            starts(date(2024, 2, 29)),
+            [Some(date(2024, 1, 29)), Some(date(2023, 11, 29)), Some(date(2024, 1, 1)), Some(date(2023, 2, 28)), None]
+        );
+        assert_eq!(starts(date(2025, 1, 1))[2], Some(date(2025, 1, 1)));
+        assert!(Preset::ALL.iter().all(|p| p.range(date(2024, 6, 15)).end.is_none()));
+        assert!(Preset::Max.range(date(2024, 6, 15)).is_all());
+
+        // The picker opens on the preset the current range came from.
+        let anchor = date(2024, 3, 31);
+        assert_eq!(RangePicker::new("Chart", anchor, Preset::YearToDate.range(anchor)).preset, 2);
+        assert_eq!(RangePicker::new("Chart", anchor, DateRange { start: Some(anchor), end: None }).preset, 4);
+    }
+
+    #[test]
+    fn slice_keeps_both_ends() {
+        let days: Vec<NaiveDateTime> = (0..10).map(|d| (date(2024, 2, 21) + chrono::Days::new(d)).and_hms_opt(16, 0, 0).unwrap()).collect();
+        let slice = |start, end| DateRange { start, end }.slice(&days, |at| *at).iter().map(|at| at.date()).collect::<Vec<_>>();
+        assert_eq!(slice(Some(date(2024, 2, 28)), Some(date(2024, 3, 1))), [date(2024, 2, 28), date(2024, 2, 29), date(2024, 3, 1)]);
+        assert_eq!(slice(None, Some(date(2024, 2, 21))), [date(2024, 2, 21)]);
+        assert_eq!(slice(Some(date(2024, 3, 1)), None), [date(2024, 3, 1)]);
+        assert_eq!(slice(None, None).len(), 10);
+        assert!(slice(Some(date(2024, 3, 5)), None).is_empty());
+        assert!(slice(None, Some(date(2024, 2, 1))).is_empty());
+        // Reversed ends select nothing rather than panic.
+        assert!(slice(Some(date(2024, 3, 1)), Some(date(2024, 2, 25))).is_empty());
+    }
+}
@@ -0,0 +1,190 @@
+//! Dividend income per holding, and what the holdings should pay in a year.
+//!
+//! Dividends are recorded like trades, with `dividend` set, from the
+//! Dividends view (Enter opens the trade form for the selected holding), so
+//! they're in each account's balance and change, the equity curve and the
+//! performance view's monthly returns. Shares held are those of the broker
+//! fills in the history, as for the tax lots, counted through any splits
+//! since. A holding's projected yearly income is its shares times the
+//! provider's yearly dividend rate, or without one the dividends per share
+//! it was paid over the last year; one whose shares aren't recorded is
+//! projected to pay the last year's again.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Duration, NaiveDate};
+
+use crate::events::EventCache;
+use crate::ids::{AccountId, Ticker};
+use crate::splits::{self, Split};
+use crate::trades::TradeRecord;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Income {
+    pub ticker: Ticker,
+    /// Held now across accounts; 0 when no fills are recorded.
+    pub shares: f64,
+    pub received: f64,
+    /// Received over the last year.
+    pub trailing: f64,
+    /// Expected over the next year.
+    pub projected: f64,
+    /// Yearly dividend per share, from the provider.
+    pub rate: Option<f64>,
+}
+
+/// One account's holding of a ticker, while folding the history.
+#[derive(Debug, Clone, Copy, Default)]
+struct Holding {
+    shares: f64,
+    has_fills: bool,
+    received: f64,
+    trailing: f64,
+    trailing_per_share: f64,
+}
+
+/// Income of every ticker held or paid on, most projected first. `trades`
+/// should be in time order.
+pub fn summary(trades: &[TradeRecord], calendar: &EventCache, today: NaiveDate) -> Vec<Income> {
+    let year_ago = today - Duration::days(365);
+    let mut holdings: HashMap<(String, &Ticker), Holding> = HashMap::new();
+    for trade in trades {
+        let Some(ticker) = &trade.ticker else {
+            continue;
+        };
+        let holding = holdings.entry((trade.name.as_str().to_lowercase(), ticker)).or_default();
+        if trade.dividend {
+            holding.received += trade.transaction;
+            if trade.timestamp.is_some_and(|at| at.date() > year_ago) {
+                holding.trailing += trade.transaction;
+                if holding.shares > 0.0 {
+                    holding.trailing_per_share += trade.transaction / holding.shares;
+                }
+            }
+        } else if let Some(qty) = splits::shares(trade).filter(|_| trade.transfer.is_none()) {
+            holding.shares += qty;
+            holding.has_fills = true;
+        }
+    }
+
+    let mut by_ticker: BTreeMap<&Ticker, Income> = BTreeMap::new();
+    for ((_, ticker), holding) in holdings {
+        let rate = calendar.dividend_rates.get(ticker).copied();
+        let income = by_ticker.entry(ticker).or_insert_with(|| Income {
+            ticker: ticker.clone(),
+            shares: 0.0,
+            received: 0.0,
+            trailing: 0.0,
+            projected: 0.0,
+            rate,
+        });
+        let shares = holding.shares.max(0.0);
+        income.shares += shares;
+        income.received += holding.received;
+        income.trailing += holding.trailing;
+        income.projected += match holding.has_fills {
+            true => shares * rate.unwrap_or(holding.trailing_per_share),
+            false => holding.trailing,
+        };
+    }
+    let mut incomes: Vec<Income> =
+        by_ticker.into_values().filter(|i| i.shares > f64::EPSILON || i.received != 0.0).collect();
+    incomes.sort_by(|a, b| b.projected.total_cmp(&a.projected));
+    incomes
+}
+
+/// What `account` was paid on `ticker` for its latest ex-dividend date
+/// with a known amount: the shares its fills held before that date times
+/// the amount, for pre-filling the form. `None` without either.
+pub fn suggested(
+    trades: &[TradeRecord],
+    calendar: &EventCache,
+    account: &AccountId,
+    ticker: &Ticker,
+    today: NaiveDate,
+) -> Option<f64> {
+    let event = calendar.last_dividend(ticker, today)?;
+    let splits: Vec<Split> = splits::known(ticker).into_iter().filter(|s| s.date <= event.date).collect();
+    let shares: f64 = trades
+        .iter()
+        .filter(|t| !t.dividend && t.transfer.is_none() && t.ticker.as_ref() == Some(ticker))
+        .filter(|t| t.name.as_str().eq_ignore_ascii_case(account.as_str()))
+        .filter(|t| t.timestamp.is_some_and(|at| at.date() < event.date))
+        .filter_map(|t| Some(t.qty? * splits::factor(&splits, t.timestamp)))
+        .sum();
+    (shares > f64::EPSILON).then(|| shares * event.amount.unwrap_or_default())
+}
+
+/// The Dividends view: the summary and the highlighted holding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DividendsView {
+    pub incomes: Vec<Income>,
+    pub selected: usize,
+}
+
+impl DividendsView {
+    pub fn new(incomes: Vec<Income>) -> Self {
+        Self { incomes, selected: 0 }
+    }
+
+    pub fn selected(&self) -> Option<&Income> {
+        self.incomes.get(self.selected)
+    }
+
+    pub fn projected(&self) -> f64 {
+        self.incomes.iter().map(|i| i.projected).sum()
+    }
+
+    pub fn trailing(&self) -> f64 {
+        self.incomes.iter().map(|i| i.trailing).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{Event, EventKind};
+
+    #[test]
+    fn holdings_project_from_rates_or_the_last_year() {
+        let day = |m: u32, d: u32| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+        let row = |ticker: &str, amount: f64, qty: Option<f64>, dividend: bool, at: NaiveDate| TradeRecord {
+            timestamp: at.and_hms_opt(10, 0, 0),
+            ticker: Some(Ticker::parse(ticker).unwrap()),
+            qty,
+            dividend,
+            ..TradeRecord::new(AccountId::parse("Alice").unwrap(), amount, 0.0)
+        };
+        let history = [
+            row("KO", -600.0, Some(10.0), false, day(1, 2)),
+            row("KO", 4.0, None, true, day(3, 1)),
+            row("KO", -600.0, Some(10.0), false, day(4, 2)),
+            row("KO", 10.0, None, true, day(6, 1)),
+            row("PEP", 7.0, None, true, day(5, 1)),
+            row("AAPL", -1900.0, Some(10.0), false, day(5, 1)),
+        ];
+        let mut calendar = EventCache::default();
+        calendar.dividend_rates.insert(Ticker::parse("AAPL").unwrap(), 1.0);
+        let incomes = summary(&history, &calendar, day(7, 1));
+        let by = |t: &str| incomes.iter().find(|i| i.ticker.as_str() == t).unwrap();
+        // 0.40 and then 0.50 a share over the year, on 20 shares.
+        assert_eq!((by("KO").shares, by("KO").received), (20.0, 14.0));
+        assert!((by("KO").projected - 18.0).abs() < 1e-9);
+        assert_eq!(by("PEP").projected, 7.0);
+        assert_eq!((by("AAPL").projected, by("AAPL").received), (10.0, 0.0));
+        assert_eq!(incomes[0].ticker.as_str(), "KO");
+
+        let ex = Event {
+            ticker: Ticker::parse("KO").unwrap(),
+            kind: EventKind::ExDividend,
+            date: day(5, 15),
+            detail: String::new(),
+            amount: Some(0.5),
+        };
+        calendar.events.push(ex);
+        let alice = AccountId::parse("alice").unwrap();
+        let ko = Ticker::parse("KO").unwrap();
+        assert_eq!(suggested(&history, &calendar, &alice, &ko, day(7, 1)), Some(10.0));
+        assert_eq!(suggested(&history, &calendar, &alice, &ko, day(5, 1)), None);
+    }
+}
@@ -0,0 +1,125 @@
+//! Downloading many tickers at once, as U does for the whole ML list.
+//!
+//! Each ticker is its own job, run `[downloads] concurrency` at a time on
+//! the job queue's pool, so the Jobs panel shows where each one is. Starts
+//! against a provider are spaced so that no more than its `per_minute`
+//! begin in a minute however many workers there are; download_stock.py
+//! counts as the `csv` provider. A download that fails with a 429 or a 5xx
+//! is tried again after `backoff_ms`, doubled each time, up to `retries`
+//! times. While a job waits, its message says what for.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::config::DownloadsConfig;
+use crate::jobs::{JobContext, JobError};
+
+static CONFIG: OnceLock<DownloadsConfig> = OnceLock::new();
+static LIMITER: OnceLock<Mutex<RateLimiter>> = OnceLock::new();
+
+/// Configures the queue. Only the first call has any effect; downloads
+/// queued before it use the defaults.
+pub fn init(config: &DownloadsConfig) {
+    let _ = CONFIG.set(config.clone());
+}
+
+pub fn config() -> &'static DownloadsConfig {
+    CONFIG.get_or_init(DownloadsConfig::default)
+}
+
+/// When each provider may next start a download.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    next: HashMap<String, Instant>,
+}
+
+impl RateLimiter {
+    /// Takes `provider`'s next free start at `per_minute`, returning how
+    /// long after `now` it is.
+    pub fn reserve(&mut self, provider: &str, per_minute: u32, now: Instant) -> Duration {
+        let spacing = Duration::from_secs(60) / per_minute.max(1);
+        let start = self.next.get(provider).copied().filter(|&at| at > now).unwrap_or(now);
+        self.next.insert(provider.to_string(), start + spacing);
+        start - now
+    }
+}
+
+/// Whether a failed download is worth trying again: its provider throttled
+/// it (429) or had a server error (5xx), as ureq, urllib and yfinance word
+/// them.
+pub fn retryable(message: &str) -> bool {
+    let words: Vec<&str> = message.split(|c: char| !c.is_ascii_alphanumeric()).filter(|w| !w.is_empty()).collect();
+    message.contains("Too Many Requests")
+        || words.windows(2).any(|pair| {
+            matches!(pair[0], "code" | "Error" | "HTTP")
+                && pair[1].parse::<u16>().is_ok_and(|code| code == 429 || (500..600).contains(&code))
+        })
+}
+
+/// The wait before retry `attempt` (counting from 0).
+pub fn backoff(config: &DownloadsConfig, attempt: u32) -> Duration {
+    Duration::from_millis(config.backoff_ms.saturating_mul(1 << attempt.min(6)))
+}
+
+/// Runs `download` once `provider` has a start free, again while it fails
+/// in a way `retryable` accepts, reporting each wait on `ctx`.
+pub fn throttled(
+    ctx: &JobContext,
+    provider: &str,
+    mut download: impl FnMut() -> Result<String, JobError>,
+) -> Result<String, JobError> {
+    let config = config();
+    let limiter = LIMITER.get_or_init(Default::default);
+    let mut attempt = 0;
+    loop {
+        let wait = limiter.lock().unwrap().reserve(provider, config.per_minute(provider), Instant::now());
+        if !wait.is_zero() {
+            ctx.report(format!("waiting {:.0}s for {}'s rate limit", wait.as_secs_f64().ceil(), provider));
+        }
+        if !ctx.sleep(wait) {
+            return Err(JobError::new("cancelled"));
+        }
+        ctx.report(match attempt {
+            0 => "downloading".to_string(),
+            n => format!("downloading, retry {}/{}", n, config.retries),
+        });
+        let err = match download() {
+            Ok(message) => return Ok(message),
+            Err(err) if attempt < config.retries && retryable(&err.message) => err,
+            Err(err) => return Err(err),
+        };
+        let wait = backoff(config, attempt);
+        attempt += 1;
+        ctx.report(format!("{}; retry {}/{} in {:.0}s", err.message, attempt, config.retries, wait.as_secs_f64()));
+        if !ctx.sleep(wait) {
+            return Err(JobError::new("cancelled"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_are_spaced_per_provider_and_throttling_is_retried() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::default();
+        assert_eq!(limiter.reserve("yahoo", 30, now), Duration::ZERO);
+        assert_eq!(limiter.reserve("yahoo", 30, now), Duration::from_secs(2));
+        assert_eq!(limiter.reserve("yahoo", 30, now + Duration::from_secs(1)), Duration::from_secs(3));
+        assert_eq!(limiter.reserve("stooq", 30, now), Duration::ZERO);
+        assert_eq!(limiter.reserve("yahoo", 30, now + Duration::from_secs(60)), Duration::ZERO);
+
+        assert!(retryable("Download error (yahoo): https://query1.finance.yahoo.com/v8: status code 429"));
+        assert!(retryable("Download error: Data source unavailable: chart-api: HTTP Error 502: Bad Gateway"));
+        assert!(retryable("yfinance: Too Many Requests. Rate limited. Try after a while."));
+        assert!(!retryable("Download error (stooq): status code 404"));
+        assert!(!retryable("Download error: no rows for 5000 days"));
+
+        let config = DownloadsConfig { backoff_ms: 1000, ..DownloadsConfig::default() };
+        assert_eq!((backoff(&config, 0), backoff(&config, 2)), (Duration::from_secs(1), Duration::from_secs(4)));
+        assert_eq!(config.per_minute("stooq"), 30);
+    }
+}
@@ -0,0 +1,175 @@
+//! Lines drawn on a ticker's chart: horizontal price levels, for support
+//! and resistance, and trendlines between two points.
+//!
+//! They're placed with the Stock Chart's crosshair and saved per ticker to
+//! `DRAWINGS_PATH` after each change, so they're back on the next start
+//! whatever dates the chart shows. Trendline ends are kept as bar times
+//! rather than positions on screen. A level can be turned into a
+//! `[daemon.alerts]` rule that fires when the close reaches it.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::ids::Ticker;
+use crate::markets;
+
+pub const DRAWINGS_PATH: &str = "drawings.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub at: NaiveDateTime,
+    pub price: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Drawing {
+    Level { price: f64 },
+    /// `from` is the earlier end.
+    Trend { from: Point, to: Point },
+}
+
+impl Drawing {
+    /// A trendline between two points, in either order.
+    pub fn trend(a: Point, b: Point) -> Self {
+        if a.at <= b.at { Drawing::Trend { from: a, to: b } } else { Drawing::Trend { from: b, to: a } }
+    }
+
+    /// The line's price at `at`; trendlines have none outside their ends.
+    pub fn price_at(&self, at: NaiveDateTime) -> Option<f64> {
+        match *self {
+            Drawing::Level { price } => Some(price),
+            Drawing::Trend { from, to } if at < from.at || at > to.at => None,
+            Drawing::Trend { from, to } => {
+                let span = (to.at - from.at).num_seconds();
+                if span == 0 {
+                    return Some(to.price);
+                }
+                let along = (at - from.at).num_seconds() as f64 / span as f64;
+                Some(from.price + (to.price - from.price) * along)
+            }
+        }
+    }
+
+    /// The `[daemon.alerts]` rule firing when the close reaches a level
+    /// from where `close` is now; `None` for trendlines, which a rule can't
+    /// follow.
+    pub fn alert(&self, ticker: &Ticker, close: f64) -> Option<String> {
+        let Drawing::Level { price } = *self else {
+            return None;
+        };
+        let op = if price >= close { ">=" } else { "<=" };
+        Some(format!("close {} {}", op, markets::price(ticker, price)))
+    }
+
+    /// How it's described in the output line.
+    pub fn describe(&self, ticker: &Ticker) -> String {
+        match self {
+            Drawing::Level { price } => format!("level at {}", markets::price(ticker, *price)),
+            Drawing::Trend { from, to } => format!(
+                "trendline {} {} to {} {}",
+                from.at.date(),
+                markets::price(ticker, from.price),
+                to.at.date(),
+                markets::price(ticker, to.price)
+            ),
+        }
+    }
+}
+
+/// Every ticker's drawings, in the order they were placed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Drawings {
+    pub tickers: BTreeMap<Ticker, Vec<Drawing>>,
+}
+
+impl Drawings {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(serde_json::from_str(&text)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let tmp = format!("{}.tmp", path);
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn get(&self, ticker: &Ticker) -> &[Drawing] {
+        self.tickers.get(ticker).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn add(&mut self, ticker: &Ticker, drawing: Drawing) {
+        self.tickers.entry(ticker.clone()).or_default().push(drawing);
+    }
+
+    /// Index of `ticker`'s drawing passing closest to `point`, among those
+    /// `matching`.
+    pub fn nearest(&self, ticker: &Ticker, point: Point, matching: impl Fn(&Drawing) -> bool) -> Option<usize> {
+        self.get(ticker)
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| matching(d))
+            .filter_map(|(i, d)| Some((i, (d.price_at(point.at)? - point.price).abs())))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+
+    pub fn remove(&mut self, ticker: &Ticker, index: usize) -> Drawing {
+        let drawings = self.tickers.get_mut(ticker).expect("index from nearest");
+        let removed = drawings.remove(index);
+        if drawings.is_empty() {
+            self.tickers.remove(ticker);
+        }
+        removed
+    }
+}
+
+/// The Stock Chart's cursor, placing and picking drawings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Crosshair {
+    /// Index into the chart's visible bars.
+    pub bar: usize,
+    pub price: f64,
+    /// The first end of a trendline being drawn.
+    pub anchor: Option<Point>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32) -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2024, 3, day).unwrap().and_hms_opt(0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn lines_are_picked_by_their_price_under_the_cursor() {
+        let ticker = Ticker::parse("AAPL").unwrap();
+        let mut drawings = Drawings::default();
+        drawings.add(&ticker, Drawing::Level { price: 180.0 });
+        drawings.add(&ticker, Drawing::trend(Point { at: at(11), price: 200.0 }, Point { at: at(1), price: 160.0 }));
+
+        let trend = drawings.get(&ticker)[1];
+        assert_eq!(trend.price_at(at(6)), Some(180.0));
+        assert_eq!(trend.price_at(at(12)), None);
+        assert_eq!(drawings.nearest(&ticker, Point { at: at(11), price: 195.0 }, |_| true), Some(1));
+        assert_eq!(drawings.nearest(&ticker, Point { at: at(12), price: 195.0 }, |_| true), Some(0));
+
+        let level = drawings.remove(&ticker, 0);
+        assert_eq!(level.alert(&ticker, 175.5).as_deref(), Some("close >= 180.00"));
+        assert_eq!(level.alert(&ticker, 181.0).as_deref(), Some("close <= 180.00"));
+        assert_eq!(trend.alert(&ticker, 175.5), None);
+        drawings.remove(&ticker, 0);
+        assert!(drawings.tickers.is_empty());
+    }
+}
@@ -0,0 +1,600 @@
+//! Executes the `Effect`s returned by `App::handle_event`.
+//!
+//! Quick effects run inline and report back as `AppEvent`s, which the main
+//! loop feeds into the reducer. Subprocess work is submitted to the job
+//! queue instead, and panel reloads to the loader; their events arrive when
+//! they finish.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{Duration as Days, NaiveDate};
+
+use crate::app::{AppEvent, Effect};
+use crate::calendar::{self, Sessions};
+use crate::chaos;
+use crate::chart_image;
+use crate::config::{self, PipelineConfig, RetentionConfig};
+use crate::drawings::DRAWINGS_PATH;
+use crate::errors::AppError;
+use crate::export;
+use crate::game;
+use crate::accounts::write_accounts_to_csv;
+use crate::data::{self, load_stocks, Bar, Interval};
+use crate::downloads;
+use crate::date_range::DateRange;
+use crate::ids::Ticker;
+use crate::market::minutes::{self, MinuteBar};
+use crate::market::provider::{self, MarketDataProvider, Span};
+use crate::market::symbols::{self, SymbolBook, SYMBOLS_PATH};
+use crate::markets;
+use crate::jobs::{JobContext, JobDone, JobError, JobQueue, JobResult};
+use crate::ml::history::{self, Prediction, HISTORY_PATH};
+use crate::ml::pipeline::{self, PipelineRun, StageOutcome};
+use crate::ml::registry::{ModelVersion, Registry, REGISTRY_PATH};
+use crate::net;
+use crate::prices;
+use crate::retention;
+use crate::trades::{append_trade, append_transfer};
+use crate::universe;
+use crate::update;
+use crate::refresh::Loader;
+
+/// How often `run_blocking` checks on its job.
+const BLOCKING_POLL: Duration = Duration::from_millis(100);
+
+/// download_stock.py's exit status when every data source it knows failed.
+const EXIT_SOURCE_UNAVAILABLE: i32 = 3;
+
+pub fn run(effect: Effect, jobs: &mut JobQueue, loader: &Loader) -> Vec<AppEvent> {
+    match effect {
+        Effect::RunDownload { ticker, interval, range } => {
+            let label = format!("download {} {} {}", ticker, interval.as_str(), range);
+            jobs.submit(label, move |ctx| {
+                download(ctx, &ticker, interval, &range).map(|message| {
+                    let mut events = vec![AppEvent::DataSource(None), AppEvent::StocksLoaded(load_stocks()), reload_history()];
+                    events.extend(name_events(&ticker));
+                    JobDone { message, events }
+                })
+            });
+            Vec::new()
+        }
+        Effect::FillGaps { ticker, interval, gaps } => {
+            let label = format!("fill gaps {}", ticker);
+            jobs.submit(label, move |ctx| {
+                fill_gaps(ctx, &ticker, interval, &gaps).map(|message| JobDone {
+                    message,
+                    events: vec![AppEvent::DataSource(None), AppEvent::StocksLoaded(load_stocks()), reload_history()],
+                })
+            });
+            Vec::new()
+        }
+        Effect::DownloadRange { ticker, interval, start, end } => {
+            let label = format!("download {} {}..{}", ticker, start, end.map_or("latest".to_string(), |d| d.to_string()));
+            jobs.submit(label, move |ctx| {
+                download_span(ctx, &ticker, interval, start, end, "Download").map(|()| JobDone {
+                    message: format!("Downloaded {} bars for {} from {}", interval.as_str(), ticker, start),
+                    events: vec![AppEvent::DataSource(None), AppEvent::StocksLoaded(load_stocks()), reload_history()],
+                })
+            });
+            Vec::new()
+        }
+        Effect::DownloadAll(tickers) => {
+            let workers = downloads::config().concurrency;
+            for (ticker, interval) in tickers {
+                let range = interval.default_range().to_string();
+                let label = format!("download {} {} {}", ticker, interval.as_str(), range);
+                jobs.submit_parallel(label, workers, move |ctx| {
+                    let provider = provider::for_ticker(&ticker).name();
+                    downloads::throttled(ctx, provider, || download(ctx, &ticker, interval, &range)).map(|message| {
+                        let mut events = vec![AppEvent::DataSource(None), AppEvent::StocksLoaded(load_stocks()), reload_history()];
+                        events.extend(name_events(&ticker));
+                        JobDone { message, events }
+                    })
+                });
+            }
+            Vec::new()
+        }
+        Effect::SearchSymbols(query) => {
+            jobs.submit(format!("search {}", query), move |_| search(&query));
+            Vec::new()
+        }
+        Effect::Preprocess { ticker, pipeline } => {
+            jobs.submit(format!("preprocess {}", ticker), move |ctx| prepare(ctx, &pipeline, &ticker));
+            Vec::new()
+        }
+        Effect::RunMl { ticker, pipeline, train } => {
+            let label = format!("{} {}", if train { "train" } else { "model" }, ticker);
+            jobs.submit(label, move |ctx| run_ml(ctx, &pipeline, &ticker, train));
+            Vec::new()
+        }
+        Effect::Refresh(request) => {
+            loader.submit(request);
+            Vec::new()
+        }
+        Effect::CancelJob(id) => jobs.cancel(id).map(AppEvent::Output).into_iter().collect(),
+        Effect::SaveAccounts(accounts) => match write_accounts_to_csv("account_summary.csv", &accounts) {
+            Ok(()) => Vec::new(),
+            Err(e) => vec![
+                AppEvent::Output(format!("Failed to save account_summary.csv: {}", e)),
+                AppEvent::Error(AppError::save("account_summary.csv", e)),
+            ],
+        },
+        Effect::RecordTrade(trade) => match append_trade("trading_history.csv", &trade) {
+            Ok(()) => Vec::new(),
+            Err(e) => vec![
+                AppEvent::Output(format!("Failed to save trading_history.csv: {}", e)),
+                AppEvent::Error(AppError::save("trading_history.csv", e)),
+            ],
+        },
+        Effect::RecordTransfer(legs) => match append_transfer("trading_history.csv", &legs) {
+            Ok(()) => Vec::new(),
+            Err(e) => vec![
+                AppEvent::Output(format!("Failed to save trading_history.csv: {}", e)),
+                AppEvent::Error(AppError::save("trading_history.csv", e)),
+            ],
+        },
+        Effect::Maintenance { retention, dry_run } => {
+            let label = if dry_run { "retention dry run" } else { "retention" };
+            jobs.submit(label.to_string(), move |_| maintenance(&retention, dry_run, false));
+            Vec::new()
+        }
+        Effect::RetentionReport(retention) => {
+            jobs.submit("retention report".to_string(), move |_| maintenance(&retention, true, true));
+            Vec::new()
+        }
+        Effect::CheckUpdate => {
+            jobs.submit("update check".to_string(), |_| match update::check() {
+                Ok(Some(release)) => Ok(JobDone {
+                    message: format!("stm {} is out (this is {}); see the release notes", release.version, update::CURRENT),
+                    events: vec![AppEvent::UpdateChecked(Some(release))],
+                }),
+                Ok(None) => Ok(JobDone {
+                    message: format!("stm {} is up to date", update::CURRENT),
+                    events: vec![AppEvent::UpdateChecked(None)],
+                }),
+                Err(e) => Err(JobError::new(format!("Update check failed: {}", e))),
+            });
+            Vec::new()
+        }
+        Effect::AppendBars(bars) => {
+            let mut events = Vec::new();
+            let mut tickers: Vec<&Ticker> = bars.iter().map(|(t, _)| t).collect();
+            tickers.sort();
+            tickers.dedup();
+            for ticker in tickers {
+                if data::read_interval(ticker) != Interval::OneMinute {
+                    continue;
+                }
+                let path = universe::path_of(ticker);
+                let ticker_bars: Vec<MinuteBar> = bars.iter().filter(|(t, _)| t == ticker).map(|(_, bar)| *bar).collect();
+                if let Err(e) = minutes::append(&path, &ticker_bars, markets::timezone_for(ticker)) {
+                    events.push(AppEvent::Error(AppError::save(&path.to_string_lossy(), e)));
+                }
+            }
+            events
+        }
+        Effect::WriteChart { ticker, at, format, image } => match chart_image::write(&ticker, at, format, &image) {
+            Ok(path) => vec![AppEvent::Output(format!("Chart saved to {}", path.display()))],
+            Err(e) => vec![
+                AppEvent::Output(format!("Failed to save chart: {}", e)),
+                AppEvent::Error(AppError::save(export::REPORTS_DIR, e)),
+            ],
+        },
+        Effect::WriteReport { at, format, text } => match export::write(at, format, &text) {
+            Ok(path) => vec![AppEvent::Output(format!("Report written to {}", path.display()))],
+            Err(e) => vec![
+                AppEvent::Output(format!("Failed to write report: {}", e)),
+                AppEvent::Error(AppError::save(export::REPORTS_DIR, e)),
+            ],
+        },
+        Effect::SaveModels(registry) => match registry.save(REGISTRY_PATH) {
+            Ok(()) => Vec::new(),
+            Err(e) => vec![
+                AppEvent::Output(format!("Failed to save {}: {}", REGISTRY_PATH, e)),
+                AppEvent::Error(AppError::save(REGISTRY_PATH, e)),
+            ],
+        },
+        Effect::SaveDrawings(drawings) => match drawings.save(DRAWINGS_PATH) {
+            Ok(()) => Vec::new(),
+            Err(e) => vec![
+                AppEvent::Output(format!("Failed to save {}: {}", DRAWINGS_PATH, e)),
+                AppEvent::Error(AppError::save(DRAWINGS_PATH, e)),
+            ],
+        },
+        Effect::AddAlert { ticker, rule } => match config::add_alert(config::CONFIG_PATH, &ticker, &rule) {
+            Ok(()) => vec![AppEvent::Output(format!("Added {}: {} to [daemon.alerts]; stm daemon checks it", ticker, rule))],
+            Err(e) => vec![
+                AppEvent::Output(format!("Failed to add the alert to {}: {}", config::CONFIG_PATH, e)),
+                AppEvent::Error(AppError::save(config::CONFIG_PATH, e)),
+            ],
+        },
+        Effect::SaveGame(game) => match game::save(game::GAME_PATH, &game) {
+            Ok(()) => Vec::new(),
+            Err(e) => vec![
+                AppEvent::Output(format!("Failed to save the game to {}: {}", game::GAME_PATH, e)),
+                AppEvent::Error(AppError::save(game::GAME_PATH, e)),
+            ],
+        },
+        Effect::SaveLayout(layout) => match config::save_layout(config::CONFIG_PATH, &layout) {
+            Ok(()) => Vec::new(),
+            Err(e) => vec![
+                AppEvent::Output(format!("Failed to save layout to {}: {}", config::CONFIG_PATH, e)),
+                AppEvent::Error(AppError::save(config::CONFIG_PATH, e)),
+            ],
+        },
+    }
+}
+
+/// Runs `effect` outside the dashboard: submits its job, if it has one,
+/// and waits for it. Returns the status lines it produced, or the error of
+/// a failed job.
+pub fn run_blocking(effect: Effect) -> Result<Vec<String>, AppError> {
+    let mut jobs = JobQueue::start();
+    let loader = Loader::start();
+    let mut pending: VecDeque<AppEvent> = run(effect, &mut jobs, &loader).into();
+    let mut lines = Vec::new();
+    loop {
+        pending.extend(jobs.poll());
+        while let Some(event) = pending.pop_front() {
+            match event {
+                AppEvent::Output(line) => lines.push(line),
+                AppEvent::Error(err) => return Err(err),
+                _ => {}
+            }
+        }
+        if jobs.active() == 0 {
+            return Ok(lines);
+        }
+        thread::sleep(BLOCKING_POLL);
+    }
+}
+
+fn inject(what: &'static str) -> Result<(), JobError> {
+    chaos::inject(what).map_err(|e| JobError::new(e.to_string()))
+}
+
+/// Downloads `range` of `ticker`'s bars, or when the stored series already
+/// reaches back that far only the bars from its newest one on, merged in.
+fn download(ctx: &JobContext, ticker: &Ticker, interval: Interval, range: &str) -> Result<String, JobError> {
+    if let Some(since) = data::resume_from(ticker, interval, range) {
+        download_span(ctx, ticker, interval, since, None, "Update")?;
+        return Ok(format!("Updated {} bars for {} from {}", interval.as_str(), ticker, since));
+    }
+    let data = provider::for_ticker(ticker);
+    if data.fetches() {
+        fetch(data, ticker, interval, &Span::Period(range.to_string()), "Download")?;
+        return Ok(format!("Downloaded {} of {} bars for {} (via {})", range, interval.as_str(), ticker, data.name()));
+    }
+    inject("download_stock.py")?;
+    let output_dl = ctx.output(
+        Command::new("python3")
+            .arg("download_stock.py")
+            .arg(ticker.as_str())
+            .arg("--interval")
+            .arg(interval.as_str())
+            .arg("--period")
+            .arg(range)
+            .arg("--dir")
+            .arg(universe::dir_of(ticker))
+            .envs(net::python_env()),
+    );
+    match output_dl {
+        Ok(o) if o.status.success() => Ok(format!(
+            "Downloaded {} of {} bars for {}{}",
+            range,
+            interval.as_str(),
+            ticker,
+            source_suffix(&o)
+        )),
+        Ok(o) => Err(download_error("Download", &o)),
+        Err(e) => Err(JobError::new(format!("Failed to run download_stock.py: {}", e))),
+    }
+}
+
+/// Fetches bars from the ticker's provider and stores them where
+/// download_stock.py would have.
+fn fetch(data: &dyn MarketDataProvider, ticker: &Ticker, interval: Interval, span: &Span, what: &str) -> Result<(), JobError> {
+    data.bars(ticker, interval, span)
+        .and_then(|bars| provider::store(data, ticker, interval, span, bars))
+        .map(|_| ())
+        .map_err(|e| JobError::new(format!("{} error ({}): {}", what, data.name(), e)))
+}
+
+/// Symbols matching `query` from `[data] provider`, one per line after a
+/// count.
+fn search(query: &str) -> JobResult {
+    let data = provider::current();
+    let matches = data.search(query).map_err(|e| JobError::new(format!("Search failed ({}): {}", data.name(), e)))?;
+    let mut message = format!("{} matches for {} via {}", matches.len(), query, data.name());
+    for symbol in &matches {
+        message.push_str(format!("\n{:<10} {} {}", symbol.ticker.as_str(), symbol.name, symbol.exchange).trim_end());
+    }
+    let events = match symbols::record(&matches) {
+        Ok(book) => vec![AppEvent::SymbolsLoaded(book)],
+        Err(e) => vec![AppEvent::Output(format!("Failed to save {}: {}", SYMBOLS_PATH, e))],
+    };
+    Ok(JobDone { message, events })
+}
+
+/// Looks up the company name of a newly downloaded ticker. Failures leave
+/// it nameless until a later download or search.
+fn name_events(ticker: &Ticker) -> Vec<AppEvent> {
+    let known = SymbolBook::load(SYMBOLS_PATH).is_ok_and(|book| book.symbols.contains_key(ticker));
+    if known {
+        return Vec::new();
+    }
+    match provider::for_ticker(ticker).lookup(ticker) {
+        Ok(Some(symbol)) => symbols::record(&[symbol]).map(|book| vec![AppEvent::SymbolsLoaded(book)]).unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// A failed download_stock.py run. When no source worked at all, the app is
+/// also told so it can show the data source as unavailable.
+fn download_error(what: &str, output: &Output) -> JobError {
+    let mut err = JobError::from_output(what, output);
+    if output.status.code() == Some(EXIT_SOURCE_UNAVAILABLE) {
+        err.events.push(AppEvent::DataSource(Some(err.message.clone())));
+    }
+    err
+}
+
+/// " (via <source>)" from download_stock.py's closing line, when it names
+/// the source that served the bars.
+fn source_suffix(output: &Output) -> String {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .lines()
+        .rev()
+        .find(|l| !l.trim().is_empty())
+        .and_then(|l| l.rsplit_once(" via "))
+        .map(|(_, source)| format!(" (via {})", source.trim()))
+        .unwrap_or_default()
+}
+
+/// New bars may resolve logged predictions, so the log is re-read after
+/// anything that downloads.
+fn reload_history() -> AppEvent {
+    match history::load_resolved(HISTORY_PATH) {
+        Ok(predictions) => AppEvent::HistoryLoaded(predictions),
+        Err(e) => AppEvent::Error(AppError::load(HISTORY_PATH, e)),
+    }
+}
+
+/// Drops rows past the retention policy, or for a dry run only counts
+/// them, listing them in the report view if `report` is set.
+fn maintenance(retention: &RetentionConfig, dry_run: bool, report: bool) -> JobResult {
+    let today = chrono::Local::now().date_naive();
+    let prunes = retention::plan(retention, HISTORY_PATH, today).map_err(|e| JobError::new(format!("Retention check failed: {}", e)))?;
+    let rows: usize = prunes.iter().map(|p| p.rows).sum();
+    if dry_run {
+        return Ok(JobDone {
+            message: format!("Would drop {} rows from {} files", rows, prunes.len()),
+            events: report.then(|| AppEvent::RetentionReport(prunes.iter().map(|p| p.to_string()).collect())).into_iter().collect(),
+        });
+    }
+    if prunes.is_empty() {
+        return Ok(JobDone { message: "Nothing past the retention policy".to_string(), events: Vec::new() });
+    }
+    retention::apply(&prunes).map_err(|e| JobError::new(format!("Retention failed: {}", e)))?;
+    Ok(JobDone {
+        message: format!("Dropped {} rows from {} files", rows, prunes.len()),
+        events: vec![AppEvent::StocksLoaded(load_stocks()), reload_history()],
+    })
+}
+
+/// Logs the model's prediction for `ticker`. Returns why it wasn't logged,
+/// if it wasn't.
+fn log_prediction(ticker: &Ticker, stdout: &str, events: &mut Vec<AppEvent>) -> Option<String> {
+    let Some(predicted) = history::parse_prediction(stdout) else {
+        return Some("no numeric prediction to log".to_string());
+    };
+    let bars = prices::bars(ticker, DateRange::default()).unwrap_or_default();
+    let Some(prediction) = Prediction::new(ticker, &bars, predicted) else {
+        return Some(format!("no bars for {} to log against", ticker));
+    };
+    match history::record(HISTORY_PATH, prediction.clone()) {
+        Ok(predictions) => {
+            events.push(AppEvent::HistoryLoaded(predictions));
+            events.push(prediction_event(prediction, &bars));
+            None
+        }
+        Err(e) => Some(format!("could not write {}: {}", HISTORY_PATH, e)),
+    }
+}
+
+/// The event that sizes a position for `prediction`, with the closes in
+/// `bars` it was made from.
+fn prediction_event(prediction: Prediction, bars: &[Bar]) -> AppEvent {
+    let closes = bars.iter().filter(|b| b.at <= prediction.as_of).map(|b| b.close).collect();
+    AppEvent::Predicted { prediction, closes }
+}
+
+/// Predicts in-process with the ONNX export of `active`, or without an
+/// active version the one `ml/model.py` exports when run on its own, if
+/// there is one. `None` falls back to the pipeline.
+#[cfg(feature = "native-ml")]
+fn run_native(ticker: &Ticker, active: Option<&ModelVersion>) -> Option<JobResult> {
+    use crate::ml::native;
+
+    let path = active.map_or_else(|| PathBuf::from(native::NATIVE_MODEL_PATH), |v| v.dir.join("lstm_model.onnx"));
+    if !path.exists() {
+        return None;
+    }
+    let mut events = Vec::new();
+    let bars = prices::bars(ticker, DateRange::default()).unwrap_or_default();
+    let closes: Vec<f64> = bars.iter().map(|b| b.close).collect();
+    let predicted = match native::shared(&path).and_then(|model| model.predict(&closes)) {
+        Ok(predicted) => predicted,
+        Err(e) => return Some(Err(JobError::new(format!("Native model error: {}", e)))),
+    };
+    let logged = Prediction::new(ticker, &bars, predicted)
+        .ok_or_else(|| format!("no bars for {}", ticker))
+        .and_then(|p| history::record(HISTORY_PATH, p.clone()).map(|log| (log, p)).map_err(|e| e.to_string()));
+    let how = active.map_or("native".to_string(), |v| format!("native, v{}", v.version));
+    let message = match logged {
+        Ok((predictions, prediction)) => {
+            events.push(AppEvent::HistoryLoaded(predictions));
+            events.push(prediction_event(prediction, &bars));
+            format!("ML Prediction for {}: {:.2} ({})", ticker, predicted, how)
+        }
+        Err(why) => format!("ML Prediction for {}: {:.2} ({}, not logged: {})", ticker, predicted, how, why),
+    };
+    Some(Ok(JobDone { message, events }))
+}
+
+/// Runs the pipeline for `ticker` and logs the model's prediction. The
+/// model stage is given the ticker's active version to predict with, or,
+/// with `train` or no version yet, a new version's directory to train
+/// into, which is registered if the stage leaves anything there. With the
+/// `native-ml` feature and an ONNX export of the model, the prediction is
+/// made in-process instead.
+fn run_ml(ctx: &JobContext, pipeline: &PipelineConfig, ticker: &Ticker, train: bool) -> JobResult {
+    let registry = Registry::load(REGISTRY_PATH)
+        .map_err(|e| JobError::new(format!("Failed to read {}: {}", REGISTRY_PATH, e)))?;
+    let active = registry.active(ticker).filter(|_| !train);
+    #[cfg(feature = "native-ml")]
+    if !train && let Some(result) = run_native(ticker, active) {
+        return result;
+    }
+    let (new_version, model_dir) = match active {
+        Some(v) => (None, v.dir.clone()),
+        None => {
+            let (version, dir) = registry.next_version(ticker);
+            (Some(version), dir)
+        }
+    };
+    let (run, pred) = match pipeline::run(ctx, pipeline, ticker, &model_dir) {
+        Ok(ran) => ran,
+        Err(err) => {
+            if new_version.is_some() {
+                let _ = fs::remove_dir_all(&model_dir);
+            }
+            return Err(err);
+        }
+    };
+    let mut events = stage_events(&run, ticker);
+    let model = match new_version {
+        Some(version) => register(ticker, version, model_dir, &mut events).map(|v| format!(" (trained v{})", v)),
+        None => active.map(|v| format!(" (v{})", v.version)),
+    };
+    let model = model.unwrap_or_default();
+    let message = match log_prediction(ticker, &pred, &mut events) {
+        None => format!("ML Prediction for {}{}: {}", ticker, model, pred.trim()),
+        Some(why) => format!("ML Prediction for {}{}: {} (not logged: {})", ticker, model, pred.trim(), why),
+    };
+    Ok(JobDone { message, events })
+}
+
+/// Records the version the model stage trained into `dir`, if it wrote
+/// anything there, as the ticker's active one.
+fn register(ticker: &Ticker, version: u32, dir: PathBuf, events: &mut Vec<AppEvent>) -> Option<u32> {
+    if fs::read_dir(&dir).map_or(true, |mut entries| entries.next().is_none()) {
+        return None;
+    }
+    let bars = prices::bars(ticker, DateRange::default()).unwrap_or_default();
+    let saved = Registry::load(REGISTRY_PATH).and_then(|mut registry| {
+        registry.register(ModelVersion::new(ticker, version, dir, &bars));
+        registry.save(REGISTRY_PATH).map(|()| registry)
+    });
+    match saved {
+        Ok(registry) => {
+            events.push(AppEvent::ModelsLoaded(registry));
+            Some(version)
+        }
+        Err(e) => {
+            events.push(AppEvent::Output(format!("Trained {} v{} but failed to save {}: {}", ticker, version, REGISTRY_PATH, e)));
+            None
+        }
+    }
+}
+
+/// Runs the pipeline's stages before the model.
+fn prepare(ctx: &JobContext, pipeline: &PipelineConfig, ticker: &Ticker) -> JobResult {
+    let registry = Registry::load(REGISTRY_PATH)
+        .map_err(|e| JobError::new(format!("Failed to read {}: {}", REGISTRY_PATH, e)))?;
+    let model_dir = registry.active(ticker).map_or_else(|| registry.next_version(ticker).1, |v| v.dir.clone());
+    let run = pipeline::prepare(ctx, pipeline, ticker, &model_dir)?;
+    let events = stage_events(&run, ticker);
+    Ok(JobDone { message: format!("Pipeline for {}: {}", ticker, run.summary()), events })
+}
+
+/// Optional stages' failures, then, if anything ran, the reloads a new
+/// download needs.
+fn stage_events(run: &PipelineRun, ticker: &Ticker) -> Vec<AppEvent> {
+    let mut events: Vec<AppEvent> = run
+        .stages
+        .iter()
+        .filter_map(|s| match &s.outcome {
+            StageOutcome::Failed(why) => Some(AppEvent::Output(format!("{} failed for {}, continuing: {}", s.name, ticker, why))),
+            _ => None,
+        })
+        .collect();
+    if run.ran_any() {
+        events.extend([AppEvent::StocksLoaded(load_stocks()), reload_history()]);
+    }
+    events
+}
+
+/// Re-downloads only the missing date ranges for `ticker`, merging them into
+/// its existing CSV. Returns a status line for the ML output box.
+fn fill_gaps(
+    ctx: &JobContext,
+    ticker: &Ticker,
+    interval: Interval,
+    gaps: &[(NaiveDate, NaiveDate)],
+) -> Result<String, JobError> {
+    if gaps.is_empty() {
+        return Ok(format!("No missing sessions for {}", ticker));
+    }
+    for (start, end) in gaps {
+        download_span(ctx, ticker, interval, *start, Some(*end), "Gap fill")?;
+    }
+    Ok(format!(
+        "Filled {} missing session(s) for {}",
+        calendar::session_count(gaps, Sessions::for_ticker(ticker)),
+        ticker
+    ))
+}
+
+/// Downloads `start..=end` (to the latest bar without an `end`) and merges
+/// it into the ticker's existing CSV.
+fn download_span(
+    ctx: &JobContext,
+    ticker: &Ticker,
+    interval: Interval,
+    start: NaiveDate,
+    end: Option<NaiveDate>,
+    what: &str,
+) -> Result<(), JobError> {
+    let data = provider::for_ticker(ticker);
+    if data.fetches() {
+        return fetch(data, ticker, interval, &Span::Dates { start, end }, what);
+    }
+    inject("download_stock.py")?;
+    let mut command = Command::new("python3");
+    command
+        .arg("download_stock.py")
+        .arg(ticker.as_str())
+        .arg("--start")
+        .arg(start.format("%Y-%m-%d").to_string())
+        .arg("--interval")
+        .arg(interval.as_str())
+        .arg("--dir")
+        .arg(universe::dir_of(ticker))
+        .envs(net::python_env());
+    if let Some(end) = end {
+        // yfinance treats the end date as exclusive.
+        command.arg("--end").arg((end + Days::days(1)).format("%Y-%m-%d").to_string());
+    }
+    match ctx.output(&mut command) {
+        Ok(o) if o.status.success() => Ok(()),
+        Ok(o) => Err(download_error(what, &o)),
+        Err(e) => Err(JobError::new(format!("Failed to run download_stock.py: {}", e))),
+    }
+}
@@ -0,0 +1,139 @@
+//! Failures surfaced in the Errors view instead of being swallowed.
+//!
+//! Anything that falls back to empty data (a missing CSV, a failed
+//! download, a dropped feed) is recorded here with when it happened, so an
+//! empty panel comes with a reason. Panels are reloaded on a timer, so a
+//! repeated error bumps its count rather than adding a new entry.
+
+use std::fmt;
+
+use chrono::{DateTime, Local};
+
+/// Entries kept; the oldest are dropped first.
+const MAX_ERRORS: usize = 200;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppError {
+    /// A file that couldn't be read or parsed.
+    Load { path: String, message: String },
+    /// A file that couldn't be written.
+    Save { path: String, message: String },
+    /// A background job (download, gap fill, model run) that failed.
+    Job { label: String, message: String },
+    /// A live quote or stream failure.
+    Feed { source: &'static str, message: String },
+}
+
+impl AppError {
+    pub fn load(path: &str, err: impl fmt::Display) -> Self {
+        AppError::Load { path: path.to_string(), message: err.to_string() }
+    }
+
+    pub fn save(path: &str, err: impl fmt::Display) -> Self {
+        AppError::Save { path: path.to_string(), message: err.to_string() }
+    }
+
+    /// Short category shown in the first column of the Errors view.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppError::Load { .. } => "load",
+            AppError::Save { .. } => "save",
+            AppError::Job { .. } => "job",
+            AppError::Feed { .. } => "feed",
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Load { path, message } => write!(f, "could not read {}: {}", path, message),
+            AppError::Save { path, message } => write!(f, "could not write {}: {}", path, message),
+            AppError::Job { label, message } => write!(f, "{}: {}", label, message),
+            AppError::Feed { source, message } => write!(f, "{}: {}", source, message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+#[derive(Debug, Clone)]
+pub struct LoggedError {
+    pub error: AppError,
+    pub first_seen: DateTime<Local>,
+    pub last_seen: DateTime<Local>,
+    pub count: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct ErrorLog {
+    // Oldest first; a repeat moves its entry to the end.
+    pub entries: Vec<LoggedError>,
+    // Distinct errors added since the Errors view was last opened.
+    pub unseen: usize,
+}
+
+impl ErrorLog {
+    pub fn push(&mut self, error: AppError) {
+        let now = Local::now();
+        if let Some(i) = self.entries.iter().position(|e| e.error == error) {
+            let mut entry = self.entries.remove(i);
+            entry.last_seen = now;
+            entry.count += 1;
+            self.entries.push(entry);
+            return;
+        }
+        self.entries.push(LoggedError { error, first_seen: now, last_seen: now, count: 1 });
+        self.unseen += 1;
+        if self.entries.len() > MAX_ERRORS {
+            self.entries.remove(0);
+        }
+    }
+
+    pub fn mark_seen(&mut self) {
+        self.unseen = 0;
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.unseen = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(n: usize) -> AppError {
+        AppError::Job { label: format!("job {}", n), message: "failed".to_string() }
+    }
+
+    #[test]
+    fn repeats_bump_their_count_and_move_to_the_end() {
+        let mut log = ErrorLog::default();
+        log.push(job(1));
+        log.push(AppError::load("a.csv", "missing"));
+        log.push(job(1));
+        let rows: Vec<_> = log.entries.iter().map(|e| (e.error.to_string(), e.count)).collect();
+        assert_eq!(rows, [("could not read a.csv: missing".to_string(), 1), ("job 1: failed".to_string(), 2)]);
+        assert!(log.entries[1].first_seen <= log.entries[1].last_seen);
+        assert_eq!(log.unseen, 2);
+        log.mark_seen();
+        log.push(job(1));
+        assert_eq!((log.entries[1].count, log.unseen), (3, 0));
+    }
+
+    #[test]
+    fn the_oldest_entries_are_dropped_past_the_limit() {
+        let mut log = ErrorLog::default();
+        for n in 0..=MAX_ERRORS {
+            log.push(job(n));
+        }
+        assert_eq!(log.entries.len(), MAX_ERRORS);
+        assert_eq!(log.entries[0].error, job(1));
+        assert_eq!(log.entries[MAX_ERRORS - 1].error, job(MAX_ERRORS));
+        // A repeat of a kept entry doesn't drop another.
+        log.push(job(1));
+        assert_eq!((log.entries.len(), log.entries[0].error.clone()), (MAX_ERRORS, job(2)));
+    }
+}
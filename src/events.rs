@@ -0,0 +1,382 @@
+//! Upcoming earnings and ex-dividend dates of the ML list's tickers, for
+//! the Calendar panel, and their splits.
+//!
+//! With `[calendar] provider = "yahoo"` both come from Yahoo Finance's
+//! `calendarEvents` summary, which needs no key, along with the yearly
+//! dividend rate and the last split; with `finnhub`, from its earnings
+//! calendar, dividend and split endpoints, which give each dividend's
+//! amount. Both feed the dividend projections as well, see `dividends`,
+//! and the split adjustments, see `splits`. Fetched events are kept in
+//! `calendar.json` with when each ticker was fetched, so the panel fills in
+//! at startup and a ticker is only asked for again after `refresh_hours`.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::{CalendarConfig, CalendarProvider};
+use crate::ids::Ticker;
+use crate::net;
+use crate::splits;
+
+pub const CALENDAR_PATH: &str = "calendar.json";
+
+const YAHOO_URL: &str = "https://query2.finance.yahoo.com/v10/finance/quoteSummary";
+const FINNHUB_URL: &str = "https://finnhub.io/api/v1";
+/// How far back Finnhub is asked for splits.
+const SPLITS_BACK_DAYS: i64 = 5 * 365;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Earnings,
+    ExDividend,
+    Split,
+}
+
+impl EventKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            EventKind::Earnings => "Earnings",
+            EventKind::ExDividend => "Ex-div",
+            EventKind::Split => "Split",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub ticker: Ticker,
+    pub kind: EventKind,
+    pub date: NaiveDate,
+    /// Estimate, amount or timing, where the provider gives one.
+    #[serde(default)]
+    pub detail: String,
+    /// Dividend per share of an ex-dividend date, where the provider gives
+    /// it; new shares per old of a split.
+    #[serde(default)]
+    pub amount: Option<f64>,
+}
+
+/// Events fetched so far, with when each ticker was last fetched.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EventCache {
+    #[serde(default)]
+    pub fetched: BTreeMap<Ticker, NaiveDateTime>,
+    #[serde(default)]
+    pub events: Vec<Event>,
+    /// Yearly dividend per share, where the provider gives it.
+    #[serde(default)]
+    pub dividend_rates: BTreeMap<Ticker, f64>,
+}
+
+impl EventCache {
+    /// The cache at `path`; empty if there isn't one yet.
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(serde_json::from_str(&text)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Whether `ticker` was never fetched or was fetched `max_age` or more
+    /// before `now`.
+    pub fn is_stale(&self, ticker: &Ticker, max_age: Duration, now: NaiveDateTime) -> bool {
+        self.fetched.get(ticker).is_none_or(|&at| now - at >= max_age)
+    }
+
+    /// Replaces `ticker`'s events and dividend rate with a fresh fetch
+    /// made at `now`.
+    pub fn replace(&mut self, ticker: &Ticker, fetched: Fetched, now: NaiveDateTime) {
+        self.events.retain(|e| &e.ticker != ticker);
+        self.events.extend(fetched.events);
+        match fetched.dividend_rate {
+            Some(rate) => self.dividend_rates.insert(ticker.clone(), rate),
+            None => self.dividend_rates.remove(ticker),
+        };
+        self.fetched.insert(ticker.clone(), now);
+    }
+
+    /// `ticker`'s latest ex-dividend date on or before `today` whose amount
+    /// is known.
+    pub fn last_dividend(&self, ticker: &Ticker, today: NaiveDate) -> Option<&Event> {
+        self.events
+            .iter()
+            .filter(|e| &e.ticker == ticker && e.kind == EventKind::ExDividend && e.amount.is_some() && e.date <= today)
+            .max_by_key(|e| e.date)
+    }
+
+    /// Events of `tickers` from `today` to `days` ahead, soonest first.
+    pub fn upcoming(&self, tickers: &[Ticker], today: NaiveDate, days: i64) -> Vec<&Event> {
+        let until = today + Duration::days(days);
+        let mut events: Vec<&Event> = self
+            .events
+            .iter()
+            .filter(|e| e.date >= today && e.date <= until && tickers.contains(&e.ticker))
+            .collect();
+        events.sort_by(|a, b| (a.date, &a.ticker, a.kind).cmp(&(b.date, &b.ticker, b.kind)));
+        events
+    }
+}
+
+/// Refetches the stale ones of `tickers` into the cache at `path` and
+/// saves it. Tickers that fail keep their old events and are tried again
+/// next time; the error is only returned if every fetch failed. Also
+/// returns the tickers that failed.
+pub fn refresh(
+    config: &CalendarConfig,
+    tickers: &[Ticker],
+    path: &str,
+    now: NaiveDateTime,
+) -> Result<(EventCache, Vec<Ticker>), Box<dyn Error>> {
+    let mut cache = EventCache::load(path)?;
+    let max_age = Duration::hours(config.refresh_hours.max(1) as i64);
+    let mut failed = Vec::new();
+    let mut last_error = None;
+    let mut fetched = 0;
+    let stale: Vec<&Ticker> = tickers.iter().filter(|t| cache.is_stale(t, max_age, now)).collect();
+    for ticker in stale {
+        match fetch(config, ticker, now.date()) {
+            Ok(found) => {
+                cache.replace(ticker, found, now);
+                fetched += 1;
+            }
+            Err(e) => {
+                failed.push(ticker.clone());
+                last_error = Some(e);
+            }
+        }
+    }
+    if let Some(e) = last_error.filter(|_| fetched == 0) {
+        return Err(e);
+    }
+    if fetched > 0 {
+        cache.save(path)?;
+    }
+    Ok((cache, failed))
+}
+
+/// What one fetch found for a ticker.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Fetched {
+    pub events: Vec<Event>,
+    pub dividend_rate: Option<f64>,
+}
+
+/// `ticker`'s events from `today` to `[calendar] days_ahead` ahead.
+pub fn fetch(config: &CalendarConfig, ticker: &Ticker, today: NaiveDate) -> Result<Fetched, Box<dyn Error>> {
+    match config.provider {
+        CalendarProvider::Yahoo => {
+            let url = format!("{}/{}?modules=calendarEvents,summaryDetail,defaultKeyStatistics", YAHOO_URL, ticker);
+            parse_yahoo(ticker, &net::client().get(&url, &[])?.into_string()?)
+        }
+        CalendarProvider::Finnhub => {
+            if config.api_key.is_empty() {
+                return Err("finnhub needs [calendar] api_key".into());
+            }
+            let (from, to) = (today, today + Duration::days(config.days_ahead));
+            let query = format!("symbol={}&from={}&to={}&token={}", ticker, from, to, config.api_key);
+            let earnings = net::client().get(&format!("{}/calendar/earnings?{}", FINNHUB_URL, query), &[])?.into_string()?;
+            let dividends = net::client().get(&format!("{}/stock/dividend?{}", FINNHUB_URL, query), &[])?.into_string()?;
+            let since = today - Duration::days(SPLITS_BACK_DAYS);
+            let query = format!("symbol={}&from={}&to={}&token={}", ticker, since, to, config.api_key);
+            let splits = net::client().get(&format!("{}/stock/split?{}", FINNHUB_URL, query), &[])?.into_string()?;
+            let mut events = parse_finnhub_earnings(ticker, &earnings)?;
+            events.extend(parse_finnhub_dividends(ticker, &dividends)?);
+            events.extend(parse_finnhub_splits(ticker, &splits)?);
+            Ok(Fetched { events, dividend_rate: None })
+        }
+    }
+}
+
+fn parse_yahoo(ticker: &Ticker, body: &str) -> Result<Fetched, Box<dyn Error>> {
+    let value: Value = serde_json::from_str(body)?;
+    let summary = &value["quoteSummary"];
+    if let Some(message) = summary["error"]["description"].as_str() {
+        return Err(message.into());
+    }
+    let calendar = &summary["result"][0]["calendarEvents"];
+    let statistics = &summary["result"][0]["defaultKeyStatistics"];
+    let dividend_rate = summary["result"][0]["summaryDetail"]["dividendRate"]["raw"].as_f64().filter(|&r| r > 0.0);
+    let date = |v: &Value| v["raw"].as_i64().and_then(|raw| DateTime::from_timestamp(raw, 0)).map(|d| d.date_naive());
+    let event = |kind, date, detail| Event { ticker: ticker.clone(), kind, date, detail, amount: None };
+    let earnings = &calendar["earnings"];
+    let estimate = earnings["earningsAverage"]["raw"].as_f64().map(|eps| format!("EPS est {:.2}", eps)).unwrap_or_default();
+    // A range of two dates means the company hasn't confirmed the day yet.
+    let dates: Vec<NaiveDate> = earnings["earningsDate"].as_array().into_iter().flatten().filter_map(date).collect();
+    let mut events: Vec<Event> = match &dates[..] {
+        [first, last] if first != last => {
+            let detail = format!("{} to {} {}", first.format("%m-%d"), last.format("%m-%d"), estimate);
+            vec![event(EventKind::Earnings, *first, detail.trim_end().to_string())]
+        }
+        [first, ..] => vec![event(EventKind::Earnings, *first, estimate)],
+        [] => Vec::new(),
+    };
+    if let Some(ex) = date(&calendar["exDividendDate"]) {
+        let pays = date(&calendar["dividendDate"]).map(|d| format!("pays {}", d)).unwrap_or_default();
+        events.push(event(EventKind::ExDividend, ex, pays));
+    }
+    let factor = statistics["lastSplitFactor"].as_str().unwrap_or_default();
+    if let (Some(date), Some(ratio)) = (date(&statistics["lastSplitDate"]), splits::parse_ratio(factor)) {
+        events.push(Event { amount: Some(ratio), ..event(EventKind::Split, date, factor.to_string()) });
+    }
+    Ok(Fetched { events, dividend_rate })
+}
+
+fn parse_finnhub_earnings(ticker: &Ticker, body: &str) -> Result<Vec<Event>, Box<dyn Error>> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Row {
+        date: NaiveDate,
+        #[serde(default)]
+        eps_estimate: Option<f64>,
+        #[serde(default)]
+        hour: String,
+    }
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Calendar {
+        earnings_calendar: Vec<Row>,
+    }
+    let calendar: Calendar = serde_json::from_str(body)?;
+    Ok(calendar
+        .earnings_calendar
+        .into_iter()
+        .map(|row| {
+            let when = match row.hour.as_str() {
+                "bmo" => "before open",
+                "amc" => "after close",
+                _ => "",
+            };
+            let estimate = row.eps_estimate.map(|eps| format!("EPS est {:.2}", eps)).unwrap_or_default();
+            let detail = [when, &estimate].iter().filter(|s| !s.is_empty()).copied().collect::<Vec<_>>().join(", ");
+            Event { ticker: ticker.clone(), kind: EventKind::Earnings, date: row.date, detail, amount: None }
+        })
+        .collect())
+}
+
+fn parse_finnhub_dividends(ticker: &Ticker, body: &str) -> Result<Vec<Event>, Box<dyn Error>> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Dividend {
+        /// The ex-dividend date.
+        date: NaiveDate,
+        #[serde(default)]
+        amount: f64,
+        #[serde(default)]
+        currency: String,
+    }
+    let dividends: Vec<Dividend> = serde_json::from_str(body)?;
+    Ok(dividends
+        .into_iter()
+        .map(|d| Event {
+            ticker: ticker.clone(),
+            kind: EventKind::ExDividend,
+            date: d.date,
+            detail: format!("{:.2} {}", d.amount, d.currency).trim_end().to_string(),
+            amount: Some(d.amount).filter(|&a| a > 0.0),
+        })
+        .collect())
+}
+
+fn parse_finnhub_splits(ticker: &Ticker, body: &str) -> Result<Vec<Event>, Box<dyn Error>> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Split {
+        date: NaiveDate,
+        from_factor: f64,
+        to_factor: f64,
+    }
+    let splits: Vec<Split> = serde_json::from_str(body)?;
+    Ok(splits
+        .into_iter()
+        .filter(|s| s.from_factor > 0.0 && s.to_factor > 0.0)
+        .map(|s| Event {
+            ticker: ticker.clone(),
+            kind: EventKind::Split,
+            date: s.date,
+            detail: format!("{}:{}", s.to_factor, s.from_factor),
+            amount: Some(s.to_factor / s.from_factor),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticker(s: &str) -> Ticker {
+        Ticker::parse(s).unwrap()
+    }
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 10, d).unwrap()
+    }
+
+    #[test]
+    fn parse_yahoo_reads_earnings_and_ex_dividend_dates() {
+        let body = r#"{"quoteSummary":{"result":[{"calendarEvents":{
+            "earnings":{"earningsDate":[{"raw":1730332800,"fmt":"2024-10-31"}],"earningsAverage":{"raw":1.6,"fmt":"1.6"}},
+            "exDividendDate":{"raw":1731024000},"dividendDate":{"raw":1731542400}},
+            "summaryDetail":{"dividendRate":{"raw":1.0,"fmt":"1.00"}},
+            "defaultKeyStatistics":{"lastSplitFactor":"4:1","lastSplitDate":{"raw":1598832000}}}],"error":null}}"#;
+        let fetched = parse_yahoo(&ticker("AAPL"), body).unwrap();
+        let events = fetched.events;
+        assert_eq!(events.len(), 3);
+        assert_eq!((events[2].kind, events[2].date, events[2].amount), (EventKind::Split, NaiveDate::from_ymd_opt(2020, 8, 31).unwrap(), Some(4.0)));
+        assert_eq!((events[0].kind, events[0].date, events[0].detail.as_str()), (EventKind::Earnings, day(31), "EPS est 1.60"));
+        assert_eq!(events[1].kind, EventKind::ExDividend);
+        assert_eq!(events[1].detail, "pays 2024-11-14");
+        assert_eq!(fetched.dividend_rate, Some(1.0));
+        let missing = r#"{"quoteSummary":{"result":null,"error":{"code":"Not Found","description":"Quote not found for symbol: NOPE"}}}"#;
+        assert!(parse_yahoo(&ticker("NOPE"), missing).is_err());
+    }
+
+    #[test]
+    fn parse_finnhub_reads_both_endpoints() {
+        let earnings = r#"{"earningsCalendar":[{"date":"2024-10-31","epsEstimate":1.6,"hour":"amc","symbol":"AAPL"}]}"#;
+        let events = parse_finnhub_earnings(&ticker("AAPL"), earnings).unwrap();
+        assert_eq!(events[0].detail, "after close, EPS est 1.60");
+        let dividends = r#"[{"symbol":"AAPL","date":"2024-11-08","amount":0.25,"currency":"USD","payDate":"2024-11-14"}]"#;
+        let events = parse_finnhub_dividends(&ticker("AAPL"), dividends).unwrap();
+        assert_eq!((events[0].date, events[0].detail.as_str()), (NaiveDate::from_ymd_opt(2024, 11, 8).unwrap(), "0.25 USD"));
+        assert_eq!(events[0].amount, Some(0.25));
+        let splits = r#"[{"symbol":"AAPL","date":"2020-08-31","fromFactor":1,"toFactor":4}]"#;
+        let events = parse_finnhub_splits(&ticker("AAPL"), splits).unwrap();
+        assert_eq!((events[0].kind, events[0].detail.as_str(), events[0].amount), (EventKind::Split, "4:1", Some(4.0)));
+    }
+
+    #[test]
+    fn cache_replaces_per_ticker_and_lists_the_window() {
+        let now = day(10).and_hms_opt(9, 0, 0).unwrap();
+        let event = |t: &str, kind, date| Event { ticker: ticker(t), kind, date, detail: String::new(), amount: None };
+        let fetched = |events| Fetched { events, dividend_rate: None };
+        let mut cache = EventCache::default();
+        cache.replace(
+            &ticker("AAPL"),
+            fetched(vec![event("AAPL", EventKind::Earnings, day(31)), event("AAPL", EventKind::ExDividend, day(5))]),
+            now,
+        );
+        cache.replace(&ticker("MSFT"), fetched(vec![event("MSFT", EventKind::Earnings, day(12))]), now);
+        assert!(!cache.is_stale(&ticker("AAPL"), Duration::hours(12), now + Duration::hours(11)));
+        assert!(cache.is_stale(&ticker("AAPL"), Duration::hours(12), now + Duration::hours(12)));
+        assert!(cache.is_stale(&ticker("TSLA"), Duration::hours(12), now));
+        let tickers = [ticker("AAPL"), ticker("MSFT")];
+        let dates: Vec<NaiveDate> = cache.upcoming(&tickers, day(10), 30).iter().map(|e| e.date).collect();
+        assert_eq!(dates, vec![day(12), day(31)]);
+        assert!(cache.upcoming(&tickers, day(10), 10).iter().all(|e| e.ticker == ticker("MSFT")));
+        cache.replace(&ticker("AAPL"), Fetched::default(), now);
+        assert_eq!(cache.events.len(), 1);
+    }
+}
@@ -0,0 +1,302 @@
+//! Point-in-time portfolio reports for sharing, written under `reports/`.
+//!
+//! A report is a list of titled tables built from what the dashboard has
+//! loaded: account balances with their base-currency total, positions
+//! (recorded P&L per ticker), recent trades, ML list moves past the
+//! `[moves]` threshold and the latest logged model prediction per ticker.
+//! The same tables render as Markdown or as a standalone HTML page.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+
+use crate::accounts::AccountSummary;
+use crate::config::MovesConfig;
+use crate::data::StockInfo;
+use crate::fx::{self, Rates};
+use crate::ids::Ticker;
+use crate::ml::history::Prediction;
+use crate::trades::TradeRecord;
+
+pub const REPORTS_DIR: &str = "reports";
+/// Trades listed, newest first.
+pub const RECENT_TRADES: usize = 20;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Markdown,
+    Html,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Markdown => "md",
+            Format::Html => "html",
+        }
+    }
+}
+
+/// One table of the report. `rows` may be empty, in which case `empty` is
+/// shown instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub title: String,
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    pub empty: &'static str,
+}
+
+/// What a report is built from, borrowed from the app.
+pub struct Snapshot<'a> {
+    pub at: NaiveDateTime,
+    pub accounts: &'a [AccountSummary],
+    pub rates: &'a Rates,
+    pub trades: &'a [TradeRecord],
+    pub stocks: &'a [StockInfo],
+    pub moves: &'a MovesConfig,
+    pub predictions: &'a [Prediction],
+}
+
+pub fn sections(snapshot: &Snapshot) -> Vec<Section> {
+    vec![
+        accounts_section(snapshot),
+        positions_section(snapshot.trades),
+        trades_section(snapshot.trades),
+        alerts_section(snapshot),
+        predictions_section(snapshot.predictions),
+    ]
+}
+
+fn section(title: &str, headers: &[&str], rows: Vec<Vec<String>>, empty: &'static str) -> Section {
+    Section { title: title.to_string(), headers: headers.iter().map(|h| h.to_string()).collect(), rows, empty }
+}
+
+fn accounts_section(snapshot: &Snapshot) -> Section {
+    let open: Vec<&AccountSummary> = snapshot.accounts.iter().filter(|a| !a.archived).collect();
+    let mut rows: Vec<Vec<String>> = open
+        .iter()
+        .map(|a| {
+            vec![
+                a.name.to_string(),
+                format!("{:.2}", a.initial_amount),
+                format!("{:.2}", a.current_amount),
+                format!("{:.2}", a.change),
+                format!("{:.2}%", a.percentage_change),
+                a.currency.clone(),
+            ]
+        })
+        .collect();
+    if !open.is_empty() {
+        let totals = fx::totals(open.iter().copied(), snapshot.rates);
+        let mut label = format!("Total {}", snapshot.rates.base());
+        if !totals.missing.is_empty() {
+            label.push_str(&format!(" (excluding {})", totals.missing.join(", ")));
+        }
+        rows.push(vec![
+            label,
+            format!("{:.2}", totals.initial),
+            format!("{:.2}", totals.current),
+            format!("{:.2}", totals.change),
+            format!("{:.2}%", totals.percentage_change),
+            snapshot.rates.base().to_string(),
+        ]);
+    }
+    section("Accounts", &["Account", "Initial", "Current", "Change", "% Change", "Currency"], rows, "No open accounts")
+}
+
+/// Trades recorded against each ticker, summed: a position's running P&L.
+fn positions_section(trades: &[TradeRecord]) -> Section {
+    let mut by_ticker: BTreeMap<&Ticker, (usize, f64, Option<NaiveDateTime>)> = BTreeMap::new();
+    for trade in trades {
+        if let Some(ticker) = &trade.ticker {
+            let entry = by_ticker.entry(ticker).or_default();
+            entry.0 += 1;
+            entry.1 += trade.transaction;
+            entry.2 = entry.2.max(trade.timestamp);
+        }
+    }
+    let rows = by_ticker
+        .into_iter()
+        .map(|(ticker, (count, pnl, last))| {
+            vec![ticker.to_string(), count.to_string(), format!("{:+.2}", pnl), last.map_or("-".to_string(), time)]
+        })
+        .collect();
+    section("Positions", &["Ticker", "Trades", "P&L", "Last trade"], rows, "No trades recorded against a ticker")
+}
+
+fn trades_section(trades: &[TradeRecord]) -> Section {
+    let rows = trades
+        .iter()
+        .rev()
+        .take(RECENT_TRADES)
+        .map(|t| {
+            let detail = match (t.transfer, &t.ticker) {
+                (Some(_), _) => "transfer".to_string(),
+                (None, Some(ticker)) => ticker.to_string(),
+                (None, None) => String::new(),
+            };
+            vec![
+                t.timestamp.map_or("-".to_string(), time),
+                t.name.to_string(),
+                format!("{:+.2}", t.transaction),
+                format!("{:.2}", t.new_balance),
+                detail,
+                t.note.clone().unwrap_or_default(),
+            ]
+        })
+        .collect();
+    let title = format!("Recent trades (last {})", RECENT_TRADES);
+    section(&title, &["When", "Account", "Amount", "Balance", "Ticker", "Note"], rows, "No trades yet")
+}
+
+fn alerts_section(snapshot: &Snapshot) -> Section {
+    let rows = snapshot
+        .stocks
+        .iter()
+        .filter(|s| snapshot.moves.is_significant(&s.ticker, s.pct_change))
+        .map(|s| vec![s.ticker.to_string(), format!("{:.2}", s.price), format!("{:+.2}%", s.pct_change)])
+        .collect();
+    section("Triggered alerts", &["Ticker", "Price", "% Change"], rows, "No moves past the [moves] threshold")
+}
+
+fn predictions_section(predictions: &[Prediction]) -> Section {
+    let mut latest: BTreeMap<&Ticker, &Prediction> = BTreeMap::new();
+    for p in predictions {
+        latest.entry(&p.ticker).and_modify(|l| if p.predicted_at >= l.predicted_at { *l = p }).or_insert(p);
+    }
+    let rows = latest
+        .into_values()
+        .map(|p| {
+            vec![
+                p.ticker.to_string(),
+                time(p.predicted_at),
+                format!("{:.2}", p.last_close),
+                format!("{:.2}", p.predicted),
+                p.actual.map_or("pending".to_string(), |a| format!("{:.2}", a)),
+            ]
+        })
+        .collect();
+    section("Latest ML predictions", &["Ticker", "Made", "Last close", "Predicted", "Actual"], rows, "No predictions logged yet")
+}
+
+fn time(at: NaiveDateTime) -> String {
+    at.format("%Y-%m-%d %H:%M").to_string()
+}
+
+pub fn render(at: NaiveDateTime, sections: &[Section], format: Format) -> String {
+    let title = format!("Portfolio report {}", time(at));
+    match format {
+        Format::Markdown => markdown(&title, sections),
+        Format::Html => html(&title, sections),
+    }
+}
+
+fn markdown(title: &str, sections: &[Section]) -> String {
+    let cell = |s: &str| s.replace('|', "\\|");
+    let mut out = format!("# {}\n", title);
+    for section in sections {
+        out.push_str(&format!("\n## {}\n\n", section.title));
+        if section.rows.is_empty() {
+            out.push_str(&format!("{}\n", section.empty));
+            continue;
+        }
+        let row = |cells: &[String]| format!("| {} |\n", cells.iter().map(|c| cell(c)).collect::<Vec<_>>().join(" | "));
+        out.push_str(&row(&section.headers));
+        out.push_str(&format!("|{}\n", " --- |".repeat(section.headers.len())));
+        for cells in &section.rows {
+            out.push_str(&row(cells));
+        }
+    }
+    out
+}
+
+fn html(title: &str, sections: &[Section]) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n\
+         <style>body{{font-family:sans-serif}} table{{border-collapse:collapse}} \
+         th,td{{border:1px solid #ccc;padding:2px 8px;text-align:left}}</style>\n</head>\n<body>\n<h1>{0}</h1>\n",
+        escape(title)
+    );
+    for section in sections {
+        out.push_str(&format!("<h2>{}</h2>\n", escape(&section.title)));
+        if section.rows.is_empty() {
+            out.push_str(&format!("<p>{}</p>\n", escape(section.empty)));
+            continue;
+        }
+        let row = |tag: &str, cells: &[String]| {
+            let cells: String = cells.iter().map(|c| format!("<{0}>{1}</{0}>", tag, escape(c))).collect();
+            format!("<tr>{}</tr>\n", cells)
+        };
+        out.push_str("<table>\n");
+        out.push_str(&row("th", &section.headers));
+        for cells in &section.rows {
+            out.push_str(&row("td", cells));
+        }
+        out.push_str("</table>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Writes `text` to `reports/report-YYYYMMDD-HHMMSS.EXT`, creating the
+/// directory if needed, and returns the path.
+pub fn write(at: NaiveDateTime, format: Format, text: &str) -> Result<PathBuf, Box<dyn Error>> {
+    fs::create_dir_all(REPORTS_DIR)?;
+    let path = PathBuf::from(REPORTS_DIR).join(format!("report-{}.{}", at.format("%Y%m%d-%H%M%S"), format.extension()));
+    fs::write(&path, text)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<Section> {
+        vec![
+            section("Accounts", &["Account", "Note"], vec![vec!["Alice".to_string(), "a|b <c>".to_string()]], "none"),
+            section("Positions", &["Ticker"], Vec::new(), "No positions"),
+        ]
+    }
+
+    fn at() -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2024, 6, 3).unwrap().and_hms_opt(9, 30, 0).unwrap()
+    }
+
+    #[test]
+    fn markdown_renders_tables_and_empty_sections() {
+        let text = render(at(), &sample(), Format::Markdown);
+        assert!(text.starts_with("# Portfolio report 2024-06-03 09:30\n"));
+        assert!(text.contains("| Account | Note |\n| --- | --- |\n| Alice | a\\|b <c> |\n"));
+        assert!(text.contains("## Positions\n\nNo positions\n"));
+    }
+
+    #[test]
+    fn html_escapes_cells() {
+        let text = render(at(), &sample(), Format::Html);
+        assert!(text.contains("<tr><td>Alice</td><td>a|b &lt;c&gt;</td></tr>"));
+        assert!(text.contains("<p>No positions</p>"));
+    }
+
+    #[test]
+    fn positions_sum_trades_per_ticker() {
+        let trade = |ticker: Option<&str>, amount: f64| TradeRecord {
+            ticker: ticker.map(|t| Ticker::parse(t).unwrap()),
+            ..TradeRecord::new(crate::ids::AccountId::parse("Alice").unwrap(), amount, 0.0)
+        };
+        let trades = [trade(Some("AAPL"), 5.0), trade(None, 1.0), trade(Some("AAPL"), -2.0), trade(Some("MSFT"), 1.0)];
+        let rows = positions_section(&trades).rows;
+        assert_eq!(rows[0][..3], ["AAPL".to_string(), "2".to_string(), "+3.00".to_string()]);
+        assert_eq!(rows.len(), 2);
+    }
+}
@@ -0,0 +1,119 @@
+//! Panels redrawn only when what they show changed.
+//!
+//! Building a table formats every row's cells each frame, which adds up
+//! on a long ML list even while nothing moves. A panel drawn through
+//! `FrameCache::panel` comes with a hash of its inputs; while that hash and
+//! the panel's area are the same as last frame's, the cells drawn then are
+//! copied back instead of building the widgets again. The terminal still
+//! only writes the cells that differ from the previous frame.
+
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use tui::backend::Backend;
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::widgets::Widget;
+use tui::Frame;
+
+#[derive(Debug, Default)]
+pub struct FrameCache {
+    panels: HashMap<&'static str, (u64, Buffer)>,
+}
+
+impl FrameCache {
+    /// Draws the panel `name` into `area` with `draw`, or copies its last
+    /// render when `key` is the one it was drawn with. `None` always draws,
+    /// for a panel that changes from frame to frame.
+    pub fn panel<B: Backend>(
+        &mut self,
+        f: &mut Frame<B>,
+        name: &'static str,
+        key: Option<u64>,
+        area: Rect,
+        draw: impl FnOnce(&mut Buffer),
+    ) {
+        if let (Some(key), Some((last, buffer))) = (key, self.panels.get(name))
+            && *last == key
+            && buffer.area == area
+        {
+            f.render_widget(Replay(buffer), area);
+            return;
+        }
+        let mut buffer = Buffer::empty(area);
+        draw(&mut buffer);
+        f.render_widget(Replay(&buffer), area);
+        match key {
+            Some(key) => self.panels.insert(name, (key, buffer)),
+            None => self.panels.remove(name),
+        };
+    }
+}
+
+/// Hash of a panel's inputs, for `FrameCache::panel`.
+#[derive(Default)]
+pub struct PanelKey(DefaultHasher);
+
+impl PanelKey {
+    pub fn add(&mut self, part: impl Hash) -> &mut Self {
+        part.hash(&mut self.0);
+        self
+    }
+
+    /// Adds a float by its bits; `-0.0` and `0.0` count as different.
+    pub fn num(&mut self, value: f64) -> &mut Self {
+        self.add(value.to_bits())
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+}
+
+/// Copies a cached render's cells into the frame.
+struct Replay<'a>(&'a Buffer);
+
+impl Widget for Replay<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let area = area.intersection(self.0.area).intersection(buf.area);
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                *buf.get_mut(x, y) = self.0.get(x, y).clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tui::backend::TestBackend;
+    use tui::widgets::Paragraph;
+    use tui::Terminal;
+
+    use super::*;
+
+    #[test]
+    fn unchanged_panels_are_copied_not_redrawn() {
+        let mut terminal = Terminal::new(TestBackend::new(10, 2)).unwrap();
+        let mut cache = FrameCache::default();
+        let mut draws = 0;
+        let mut frame = |key: Option<u64>, text: &str, draws: &mut u32| {
+            terminal
+                .draw(|f| {
+                    cache.panel(f, "list", key, Rect::new(0, 0, 10, 1), |buf| {
+                        *draws += 1;
+                        Paragraph::new(text.to_string()).render(Rect::new(0, 0, 10, 1), buf);
+                    })
+                })
+                .unwrap();
+        };
+        frame(Some(1), "AAPL", &mut draws);
+        frame(Some(1), "stale", &mut draws);
+        assert_eq!(draws, 1);
+        frame(Some(2), "MSFT", &mut draws);
+        frame(None, "TSLA", &mut draws);
+        frame(None, "TSLA", &mut draws);
+        assert_eq!(draws, 4);
+        terminal.backend().assert_buffer(&Buffer::with_lines(vec!["TSLA      ", "          "]));
+    }
+}
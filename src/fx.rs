@@ -0,0 +1,168 @@
+//! Exchange rates for totalling accounts held in different currencies.
+//!
+//! Balances are converted to the `[fx]` base currency with the rates set in
+//! `stm.toml` and, with `fetch = true`, the European Central Bank reference
+//! rates published by frankfurter.app, fetched in the background at startup
+//! and then hourly. Configured rates win over fetched ones, so a rate can be
+//! pinned.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::accounts::AccountSummary;
+use crate::config::FxConfig;
+use crate::net;
+
+pub const FETCH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const RATES_URL: &str = "https://api.frankfurter.app/latest";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rates {
+    base: String,
+    // Value of one unit of each currency in `base`, keyed by upper-case code.
+    configured: BTreeMap<String, f64>,
+    fetched: BTreeMap<String, f64>,
+}
+
+impl Rates {
+    pub fn new(config: &FxConfig) -> Self {
+        Self {
+            base: config.base.to_uppercase(),
+            configured: config.rates.iter().map(|(code, &rate)| (code.to_uppercase(), rate)).collect(),
+            fetched: BTreeMap::new(),
+        }
+    }
+
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    pub fn set_fetched(&mut self, fetched: BTreeMap<String, f64>) {
+        self.fetched = fetched;
+    }
+
+    /// Value of one unit of `currency` in the base currency, if known.
+    pub fn rate(&self, currency: &str) -> Option<f64> {
+        let code = currency.trim().to_uppercase();
+        if code == self.base {
+            return Some(1.0);
+        }
+        self.configured.get(&code).or_else(|| self.fetched.get(&code)).copied()
+    }
+
+    pub fn convert(&self, amount: f64, currency: &str) -> Option<f64> {
+        self.rate(currency).map(|rate| amount * rate)
+    }
+}
+
+/// Combined balances of several accounts in the base currency.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Totals {
+    pub initial: f64,
+    pub current: f64,
+    pub change: f64,
+    pub percentage_change: f64,
+    /// Currencies without a rate; their accounts are left out of the sums.
+    pub missing: Vec<String>,
+}
+
+pub fn totals<'a>(accounts: impl IntoIterator<Item = &'a AccountSummary>, rates: &Rates) -> Totals {
+    let mut totals = Totals::default();
+    for account in accounts {
+        match rates.rate(&account.currency) {
+            Some(rate) => {
+                totals.initial += account.initial_amount * rate;
+                totals.current += account.current_amount * rate;
+            }
+            None => {
+                let code = account.currency.trim().to_uppercase();
+                if !totals.missing.contains(&code) {
+                    totals.missing.push(code);
+                }
+            }
+        }
+    }
+    totals.change = totals.current - totals.initial;
+    totals.percentage_change = if totals.initial != 0.0 { totals.change / totals.initial * 100.0 } else { 0.0 };
+    totals
+}
+
+/// The latest reference rates as `Rates` stores them: base units per unit
+/// of each currency.
+pub fn fetch(base: &str) -> Result<BTreeMap<String, f64>, Box<dyn Error>> {
+    let url = format!("{}?from={}", RATES_URL, base);
+    let body = net::client().get(&url, &[])?.into_string()?;
+    parse_latest(&body)
+}
+
+/// frankfurter.app quotes each currency per unit of the base, so the rates
+/// are inverted.
+fn parse_latest(body: &str) -> Result<BTreeMap<String, f64>, Box<dyn Error>> {
+    #[derive(Deserialize)]
+    struct Latest {
+        rates: BTreeMap<String, f64>,
+    }
+    let latest: Latest = serde_json::from_str(body)?;
+    Ok(latest.rates.into_iter().filter(|&(_, rate)| rate > 0.0).map(|(code, rate)| (code, 1.0 / rate)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ids::AccountId;
+
+    use super::*;
+
+    fn rates() -> Rates {
+        let config = FxConfig {
+            base: "usd".to_string(),
+            fetch: false,
+            rates: BTreeMap::from([("eur".to_string(), 1.1)]),
+        };
+        let mut rates = Rates::new(&config);
+        rates.set_fetched(BTreeMap::from([("EUR".to_string(), 1.05), ("GBP".to_string(), 1.25)]));
+        rates
+    }
+
+    fn account(name: &str, initial: f64, current: f64, currency: &str) -> AccountSummary {
+        AccountSummary {
+            current_amount: current,
+            change: current - initial,
+            ..AccountSummary::new(AccountId::parse(name).unwrap(), initial, currency)
+        }
+    }
+
+    #[test]
+    fn configured_rates_win_over_fetched_ones() {
+        let rates = rates();
+        assert_eq!(rates.rate("USD"), Some(1.0));
+        assert_eq!(rates.rate("eur"), Some(1.1));
+        assert_eq!(rates.rate("GBP"), Some(1.25));
+        assert_eq!(rates.rate("JPY"), None);
+    }
+
+    #[test]
+    fn totals_convert_to_the_base_and_skip_unknown_currencies() {
+        let accounts = [
+            account("Alice", 100.0, 110.0, "USD"),
+            account("Bob", 100.0, 90.0, "GBP"),
+            account("Carol", 1000.0, 2000.0, "JPY"),
+        ];
+        let totals = totals(&accounts, &rates());
+        assert_eq!(totals.initial, 225.0);
+        assert_eq!(totals.current, 222.5);
+        assert_eq!(totals.change, -2.5);
+        assert!((totals.percentage_change - -2.5 / 225.0 * 100.0).abs() < 1e-9);
+        assert_eq!(totals.missing, vec!["JPY".to_string()]);
+    }
+
+    #[test]
+    fn parse_latest_inverts_quoted_rates() {
+        let body = r#"{"amount":1.0,"base":"USD","date":"2024-06-03","rates":{"EUR":0.8,"GBP":0.5}}"#;
+        let rates = parse_latest(body).unwrap();
+        assert_eq!(rates, BTreeMap::from([("EUR".to_string(), 1.25), ("GBP".to_string(), 2.0)]));
+    }
+}
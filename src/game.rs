@@ -0,0 +1,526 @@
+//! Trading game: local accounts trade the loaded symbols on a shared book.
+//!
+//! For classrooms and practice rounds. Every open account joins with
+//! `[game] starting_cash` of play money, kept apart from its real balance,
+//! and places orders typed as `NAME buy|sell QTY TICKER [PRICE]`, or takes
+//! one off the book with `NAME cancel ID`. Orders with a price are limit
+//! orders and rest on the book until matched; without one they take what
+//! is offered and the rest is dropped. Matching is by price, then time, at
+//! the resting order's price. Unless
+//! `house_spread_pct` is zero a house market maker also quotes each symbol
+//! around its latest close, with unlimited size, so there is always
+//! someone to trade with. Positions are marked at the latest price and the
+//! leaderboard ranks players by return on their starting cash. The game is
+//! saved to `GAME_PATH` after every order.
+
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::io;
+
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::ids::{AccountId, Ticker};
+use crate::market::book::{Level, OrderBook};
+
+pub const GAME_PATH: &str = "game.json";
+/// Shown as the counterparty of fills against the house quotes.
+pub const HOUSE: &str = "house";
+/// Fills kept for the view; the oldest are dropped.
+const MAX_FILLS: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// An order as typed; `limit` is `None` for a market order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderRequest {
+    pub player: AccountId,
+    pub side: Side,
+    pub qty: u64,
+    pub ticker: Ticker,
+    pub limit: Option<f64>,
+}
+
+impl OrderRequest {
+    /// Parses `NAME buy|sell QTY TICKER [PRICE]`; the name may contain
+    /// spaces.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let usage = "Expected NAME buy|sell QTY TICKER [PRICE]";
+        let at = words.iter().position(|w| matches!(w.to_lowercase().as_str(), "buy" | "sell")).ok_or(usage)?;
+        let player = AccountId::parse(&words[..at].join(" ")).map_err(|e| format!("Invalid name: {}", e))?;
+        let side = if words[at].eq_ignore_ascii_case("buy") { Side::Buy } else { Side::Sell };
+        let rest = &words[at + 1..];
+        let (qty, ticker, limit) = match rest {
+            [qty, ticker] => (qty, ticker, None),
+            [qty, ticker, price] => (qty, ticker, Some(price.trim_start_matches('@'))),
+            _ => return Err(usage.to_string()),
+        };
+        let qty = qty.parse::<u64>().ok().filter(|&q| q > 0).ok_or("Quantity must be a whole number above 0")?;
+        let ticker = Ticker::parse(ticker).map_err(|e| format!("Invalid ticker: {}", e))?;
+        let limit = match limit {
+            Some(p) => Some(p.parse::<f64>().ok().filter(|p| p.is_finite() && *p > 0.0).ok_or("Price must be above 0")?),
+            None => None,
+        };
+        Ok(Self { player, side, qty, ticker, limit })
+    }
+}
+
+/// A line typed into the game view.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    Place(OrderRequest),
+    Cancel { player: AccountId, id: u64 },
+}
+
+impl Instruction {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        match words.iter().position(|w| w.eq_ignore_ascii_case("cancel")) {
+            Some(at) => {
+                let player = AccountId::parse(&words[..at].join(" ")).map_err(|e| format!("Invalid name: {}", e))?;
+                let id = match &words[at + 1..] {
+                    [id] => id.trim_start_matches('#').parse().map_err(|_| format!("Invalid order number {}", id))?,
+                    _ => return Err("Expected NAME cancel ID".to_string()),
+                };
+                Ok(Instruction::Cancel { player, id })
+            }
+            None => OrderRequest::parse(text).map(Instruction::Place),
+        }
+    }
+}
+
+/// The game as the view shows it, with the line being typed and the
+/// outcome of the last one.
+#[derive(Debug)]
+pub struct GameView {
+    pub game: Game,
+    pub input: String,
+    pub message: String,
+    /// Whose book is shown: that of the last order, else the selected stock.
+    pub ticker: Option<Ticker>,
+}
+
+/// A limit order resting on the book.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Order {
+    pub id: u64,
+    pub player: AccountId,
+    pub side: Side,
+    pub ticker: Ticker,
+    /// Still unfilled.
+    pub qty: u64,
+    pub limit: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fill {
+    pub ticker: Ticker,
+    pub qty: u64,
+    pub price: f64,
+    /// Account names, or `HOUSE`.
+    pub buyer: String,
+    pub seller: String,
+    pub at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Player {
+    pub name: AccountId,
+    pub cash: f64,
+    pub positions: BTreeMap<Ticker, u64>,
+}
+
+/// A player's row on the leaderboard.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Standing {
+    pub name: AccountId,
+    pub cash: f64,
+    /// Positions at the latest prices.
+    pub holdings: f64,
+    pub total: f64,
+    pub return_pct: f64,
+}
+
+/// What placing an order did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placed {
+    pub fills: Vec<Fill>,
+    /// The rest of a limit order, left on the book.
+    pub resting: Option<Order>,
+    /// Left over from a market order with nothing more to take.
+    pub dropped: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Game {
+    pub starting_cash: f64,
+    pub players: Vec<Player>,
+    pub orders: Vec<Order>,
+    pub fills: Vec<Fill>,
+    next_id: u64,
+}
+
+impl Game {
+    pub fn new(starting_cash: f64) -> Self {
+        Self { starting_cash, players: Vec::new(), orders: Vec::new(), fills: Vec::new(), next_id: 1 }
+    }
+
+    /// Adds the accounts not yet playing, with the starting cash.
+    pub fn join<'a>(&mut self, names: impl IntoIterator<Item = &'a AccountId>) {
+        for name in names {
+            if !self.players.iter().any(|p| &p.name == name) {
+                self.players.push(Player { name: name.clone(), cash: self.starting_cash, positions: BTreeMap::new() });
+            }
+        }
+    }
+
+    /// Matches `request` against the book and, within `spread_pct` of the
+    /// mark, the house. Fails without changing anything when the player
+    /// can't cover the order: cash for a limit buy, shares for any sell,
+    /// counting what their resting orders already commit.
+    pub fn place(
+        &mut self,
+        request: &OrderRequest,
+        marks: &HashMap<Ticker, f64>,
+        spread_pct: f64,
+        at: NaiveDateTime,
+    ) -> Result<Placed, String> {
+        let player = self.player(&request.player).ok_or_else(|| format!("{} isn't playing", request.player))?;
+        let mark = marks.get(&request.ticker).copied().filter(|m| *m > 0.0);
+        if mark.is_none() && request.limit.is_none() && !self.orders.iter().any(|o| o.ticker == request.ticker) {
+            return Err(format!("No price for {}; give a limit price", request.ticker));
+        }
+        match request.side {
+            Side::Buy => {
+                let free = player.cash - self.committed_cash(&request.player);
+                if let Some(limit) = request.limit
+                    && limit * request.qty as f64 > free + 1e-9
+                {
+                    return Err(format!("{} has {:.2} free, {:.2} needed", request.player, free, limit * request.qty as f64));
+                }
+            }
+            Side::Sell => {
+                let held = player.positions.get(&request.ticker).copied().unwrap_or(0);
+                let free = held.saturating_sub(self.committed_shares(&request.player, &request.ticker));
+                if request.qty > free {
+                    return Err(format!("{} has {} {} free to sell", request.player, free, request.ticker));
+                }
+            }
+        }
+
+        let house = mark.filter(|_| spread_pct > 0.0).map(|m| match request.side {
+            Side::Buy => m * (1.0 + spread_pct / 200.0),
+            Side::Sell => m * (1.0 - spread_pct / 200.0),
+        });
+        let crosses = |price: f64| match (request.side, request.limit) {
+            (_, None) => true,
+            (Side::Buy, Some(limit)) => price <= limit + 1e-9,
+            (Side::Sell, Some(limit)) => price >= limit - 1e-9,
+        };
+        let mut remaining = request.qty;
+        let mut fills = Vec::new();
+        while remaining > 0 {
+            // Resting orders win ties with the house; they were there first.
+            let resting = self.best_opposite(request);
+            let (price, index) = match (resting, house) {
+                (Some(i), Some(h)) if !self.better_or_equal(request.side, self.orders[i].limit, h) => (h, None),
+                (Some(i), _) => (self.orders[i].limit, Some(i)),
+                (None, Some(h)) => (h, None),
+                (None, None) => break,
+            };
+            if !crosses(price) {
+                break;
+            }
+            let mut qty = index.map_or(remaining, |i| remaining.min(self.orders[i].qty));
+            if request.side == Side::Buy && request.limit.is_none() {
+                // A market buy takes what the player's free cash affords.
+                let cash = self.player(&request.player).map_or(0.0, |p| p.cash) - self.committed_cash(&request.player);
+                qty = qty.min((cash / price + 1e-9).floor().max(0.0) as u64);
+                if qty == 0 {
+                    break;
+                }
+            }
+            let counterparty = match index {
+                Some(i) => {
+                    let order = &mut self.orders[i];
+                    order.qty -= qty;
+                    let name = order.player.clone();
+                    if order.qty == 0 {
+                        self.orders.remove(i);
+                    }
+                    Some(name)
+                }
+                None => None,
+            };
+            let (buyer, seller) = match request.side {
+                Side::Buy => (Some(&request.player), counterparty.as_ref()),
+                Side::Sell => (counterparty.as_ref(), Some(&request.player)),
+            };
+            self.settle(buyer, seller, &request.ticker, qty, price);
+            let name = |p: Option<&AccountId>| p.map_or(HOUSE.to_string(), |p| p.to_string());
+            fills.push(Fill { ticker: request.ticker.clone(), qty, price, buyer: name(buyer), seller: name(seller), at });
+            remaining -= qty;
+        }
+
+        let mut placed = Placed { fills: fills.clone(), resting: None, dropped: 0 };
+        match request.limit {
+            Some(limit) if remaining > 0 => {
+                let order = Order {
+                    id: self.next_id,
+                    player: request.player.clone(),
+                    side: request.side,
+                    ticker: request.ticker.clone(),
+                    qty: remaining,
+                    limit,
+                };
+                self.next_id += 1;
+                self.orders.push(order.clone());
+                placed.resting = Some(order);
+            }
+            _ => placed.dropped = remaining,
+        }
+        self.fills.extend(fills);
+        let excess = self.fills.len().saturating_sub(MAX_FILLS);
+        self.fills.drain(..excess);
+        Ok(placed)
+    }
+
+    /// Takes `player`'s order `id` off the book.
+    pub fn cancel(&mut self, player: &AccountId, id: u64) -> Result<Order, String> {
+        let i = self
+            .orders
+            .iter()
+            .position(|o| o.id == id && &o.player == player)
+            .ok_or_else(|| format!("{} has no resting order #{}", player, id))?;
+        Ok(self.orders.remove(i))
+    }
+
+    /// The players' resting orders on `ticker`, best price first, a level
+    /// per price.
+    pub fn book(&self, ticker: &Ticker) -> OrderBook {
+        let side = |side: Side| {
+            let mut levels: Vec<Level> = Vec::new();
+            let mut orders: Vec<&Order> = self.orders.iter().filter(|o| &o.ticker == ticker && o.side == side).collect();
+            orders.sort_by(|a, b| match side {
+                Side::Buy => b.limit.total_cmp(&a.limit),
+                Side::Sell => a.limit.total_cmp(&b.limit),
+            });
+            for order in orders {
+                match levels.last_mut() {
+                    Some(level) if level.price == order.limit => level.size += order.qty as f64,
+                    _ => levels.push(Level { price: order.limit, size: order.qty as f64 }),
+                }
+            }
+            levels
+        };
+        OrderBook { bids: side(Side::Buy), asks: side(Side::Sell), at: Local::now() }
+    }
+
+    /// Players by return, best first. Positions without a mark count at
+    /// nothing.
+    pub fn leaderboard(&self, marks: &HashMap<Ticker, f64>) -> Vec<Standing> {
+        let mut standings: Vec<Standing> = self
+            .players
+            .iter()
+            .map(|p| {
+                let holdings =
+                    p.positions.iter().map(|(t, &qty)| marks.get(t).copied().unwrap_or(0.0) * qty as f64).fold(0.0, |a, b| a + b);
+                let total = p.cash + holdings;
+                let return_pct = if self.starting_cash > 0.0 {
+                    (total - self.starting_cash) / self.starting_cash * 100.0
+                } else {
+                    0.0
+                };
+                Standing { name: p.name.clone(), cash: p.cash, holdings, total, return_pct }
+            })
+            .collect();
+        standings.sort_by(|a, b| b.return_pct.total_cmp(&a.return_pct).then_with(|| a.name.cmp(&b.name)));
+        standings
+    }
+
+    fn player(&self, name: &AccountId) -> Option<&Player> {
+        self.players.iter().find(|p| &p.name == name)
+    }
+
+    /// Cash set aside for `player`'s resting buys.
+    fn committed_cash(&self, player: &AccountId) -> f64 {
+        self.orders.iter().filter(|o| &o.player == player && o.side == Side::Buy).map(|o| o.limit * o.qty as f64).sum()
+    }
+
+    /// Shares of `ticker` set aside for `player`'s resting sells.
+    fn committed_shares(&self, player: &AccountId, ticker: &Ticker) -> u64 {
+        self.orders.iter().filter(|o| &o.player == player && &o.ticker == ticker && o.side == Side::Sell).map(|o| o.qty).sum()
+    }
+
+    /// Index of the best resting order on the other side from another
+    /// player, oldest first among equal prices.
+    fn best_opposite(&self, request: &OrderRequest) -> Option<usize> {
+        let mut best: Option<usize> = None;
+        for (i, order) in self.orders.iter().enumerate() {
+            if order.ticker != request.ticker || order.side == request.side || order.player == request.player {
+                continue;
+            }
+            // Orders are kept in arrival order, so only a strictly better
+            // price replaces an earlier one.
+            if best.is_none_or(|b| !self.better_or_equal(request.side, self.orders[b].limit, order.limit)) {
+                best = Some(i);
+            }
+        }
+        best
+    }
+
+    /// Whether `a` is at least as good a price as `b` for someone on `side`.
+    fn better_or_equal(&self, side: Side, a: f64, b: f64) -> bool {
+        match side {
+            Side::Buy => a <= b,
+            Side::Sell => a >= b,
+        }
+    }
+
+    fn settle(&mut self, buyer: Option<&AccountId>, seller: Option<&AccountId>, ticker: &Ticker, qty: u64, price: f64) {
+        for player in &mut self.players {
+            if Some(&player.name) == buyer {
+                player.cash -= price * qty as f64;
+                *player.positions.entry(ticker.clone()).or_default() += qty;
+            } else if Some(&player.name) == seller {
+                player.cash += price * qty as f64;
+                let held = player.positions.entry(ticker.clone()).or_default();
+                *held -= qty;
+                if *held == 0 {
+                    player.positions.remove(ticker);
+                }
+            }
+        }
+    }
+}
+
+/// One line on what placing `request` did, for the view.
+pub fn describe(request: &OrderRequest, placed: &Placed) -> String {
+    let verb = match request.side {
+        Side::Buy => "bought",
+        Side::Sell => "sold",
+    };
+    let mut parts = Vec::new();
+    let filled: u64 = placed.fills.iter().map(|f| f.qty).sum();
+    if filled > 0 {
+        let value: f64 = placed.fills.iter().map(|f| f.price * f.qty as f64).sum();
+        parts.push(format!(
+            "{} {} {} {} at {:.2} avg ({} fill{})",
+            request.player,
+            verb,
+            filled,
+            request.ticker,
+            value / filled as f64,
+            placed.fills.len(),
+            if placed.fills.len() == 1 { "" } else { "s" }
+        ));
+    } else {
+        parts.push(format!("{} {} nothing", request.player, verb));
+    }
+    if let Some(order) = &placed.resting {
+        parts.push(format!("{} rest as #{} at {:.2}", order.qty, order.id, order.limit));
+    }
+    if placed.dropped > 0 {
+        parts.push(format!("{} dropped with nothing more to take", placed.dropped));
+    }
+    parts.join("; ")
+}
+
+/// The saved game, or `None` before the first one.
+pub fn load(path: &str) -> Result<Option<Game>, Box<dyn Error>> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(Some(serde_json::from_str(&text)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn save(path: &str, game: &Game) -> Result<(), Box<dyn Error>> {
+    let tmp = format!("{}.tmp", path);
+    fs::write(&tmp, serde_json::to_string_pretty(game)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(s: &str) -> AccountId {
+        AccountId::parse(s).unwrap()
+    }
+
+    fn at() -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2024, 6, 3).unwrap().and_hms_opt(10, 0, 0).unwrap()
+    }
+
+    fn game() -> Game {
+        let mut game = Game::new(1000.0);
+        game.join(&[name("Alice"), name("Bob"), name("Cy")]);
+        game
+    }
+
+    #[test]
+    fn parse_reads_names_with_spaces_and_optional_prices() {
+        let order = OrderRequest::parse("Mrs Smith buy 10 aapl @150.5").unwrap();
+        assert_eq!(order.player, name("Mrs Smith"));
+        assert_eq!((order.side, order.qty, order.ticker.as_str(), order.limit), (Side::Buy, 10, "AAPL", Some(150.5)));
+        assert_eq!(OrderRequest::parse("Bob SELL 3 MSFT").unwrap().limit, None);
+        assert!(OrderRequest::parse("Bob buy 0 MSFT").is_err());
+        assert!(OrderRequest::parse("buy 1 MSFT").is_err());
+        assert!(OrderRequest::parse("Bob hold 1 MSFT").is_err());
+        assert_eq!(Instruction::parse("Bob cancel #4"), Ok(Instruction::Cancel { player: name("Bob"), id: 4 }));
+    }
+
+    #[test]
+    fn orders_match_by_price_then_time_at_the_resting_price() {
+        let mut game = game();
+        let marks = HashMap::new();
+        let aapl = Ticker::parse("AAPL").unwrap();
+        for player in ["Alice", "Bob"] {
+            game.players.iter_mut().find(|p| p.name == name(player)).unwrap().positions.insert(aapl.clone(), 10);
+        }
+        let order = |text: &str| OrderRequest::parse(text).unwrap();
+        game.place(&order("Alice sell 5 AAPL 11"), &marks, 0.0, at()).unwrap();
+        game.place(&order("Bob sell 5 AAPL 10"), &marks, 0.0, at()).unwrap();
+        game.place(&order("Alice sell 5 AAPL 10"), &marks, 0.0, at()).unwrap();
+        let placed = game.place(&order("Cy buy 12 AAPL 10.5"), &marks, 0.0, at()).unwrap();
+        // Bob's 10 came before Alice's; the 11 is too dear, so 2 rest.
+        let fills: Vec<(&str, u64, f64)> = placed.fills.iter().map(|f| (f.seller.as_str(), f.qty, f.price)).collect();
+        assert_eq!(fills, vec![("Bob", 5, 10.0), ("Alice", 5, 10.0)]);
+        assert_eq!(placed.resting.map(|o| (o.qty, o.limit)), Some((2, 10.5)));
+        let cy = game.player(&name("Cy")).unwrap();
+        assert_eq!((cy.cash, cy.positions[&aapl]), (900.0, 10));
+        let book = game.book(&aapl);
+        assert_eq!(book.bids, vec![Level { price: 10.5, size: 2.0 }]);
+        assert_eq!(book.asks, vec![Level { price: 11.0, size: 5.0 }]);
+        // Cy's 2 resting at 10.5 commit 21 of the 900.
+        assert!(game.place(&order("Cy buy 100 AAPL 8.8"), &marks, 0.0, at()).is_err());
+        assert!(game.place(&order("Bob sell 6 AAPL 9"), &marks, 0.0, at()).is_err());
+    }
+
+    #[test]
+    fn market_orders_trade_with_the_house_and_rank_by_return() {
+        let mut game = game();
+        let aapl = Ticker::parse("AAPL").unwrap();
+        let mut marks = HashMap::from([(aapl.clone(), 100.0)]);
+        let placed = game.place(&OrderRequest::parse("Alice buy 20 AAPL").unwrap(), &marks, 2.0, at()).unwrap();
+        // 1% over the mark is 101 a share; 1000 covers 9 of them.
+        assert_eq!(placed.fills.len(), 1);
+        assert_eq!((placed.fills[0].qty, placed.fills[0].price, placed.fills[0].seller.as_str()), (9, 101.0, HOUSE));
+        assert_eq!(placed.dropped, 11);
+        marks.insert(aapl, 110.0);
+        let board = game.leaderboard(&marks);
+        assert_eq!(board[0].name, name("Alice"));
+        assert!((board[0].total - 1081.0).abs() < 1e-9);
+        assert!((board[0].return_pct - 8.1).abs() < 1e-9);
+        assert_eq!(board[1].return_pct, 0.0);
+    }
+}
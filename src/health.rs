@@ -0,0 +1,291 @@
+//! Startup health check: what the dashboard needs from its surroundings,
+//! checked once before it starts.
+//!
+//! Each check passes, warns (something will be missing but the dashboard
+//! works) or fails (something won't work until it's fixed), with a hint on
+//! what to do. The report is shown before the dashboard when anything isn't
+//! passing, or always with `[health] always_show`; `stm check` prints it.
+
+use std::fs;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::accounts;
+use crate::config::CONFIG_PATH;
+use crate::ledger;
+use crate::prices;
+use crate::trades;
+use crate::universe::{self, Root, Series};
+
+/// Host the downloader fetches from, to tell whether the network is up.
+const PROBE_HOST: &str = "query1.finance.yahoo.com:443";
+/// Longest the network probe waits, whatever `[net]` allows.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+/// Python packages the scripts import, with what needs them.
+const PACKAGES: [(&str, &str); 3] = [("pandas", "downloads"), ("numpy", "the model"), ("torch", "the model")];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Outcome {
+    Pass,
+    /// Not applicable to this build or setup.
+    Skip,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    pub detail: String,
+    /// What to do about it; empty when it passed.
+    pub hint: String,
+}
+
+impl Check {
+    fn new(name: &'static str, outcome: Outcome, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { name, outcome, detail: detail.into(), hint: hint.into() }
+    }
+
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self::new(name, Outcome::Pass, detail, "")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthReport {
+    pub checks: Vec<Check>,
+}
+
+impl HealthReport {
+    /// Runs every check. `config_error` is why `stm.toml` didn't load, if
+    /// it didn't; the caller has already tried.
+    pub fn run(config_error: Option<&str>, connect_timeout: Duration) -> Self {
+        let mut checks = vec![config(config_error), writable(".", "Data dir")];
+        let dirs: Vec<String> = universe::roots().iter().map(|root| root.dir.to_string_lossy().into_owned()).collect();
+        checks.extend(dirs.iter().map(|dir| writable(dir, "Price dir")));
+        checks.extend(python());
+        checks.push(onnx());
+        checks.push(network(connect_timeout.min(PROBE_TIMEOUT)));
+        checks.push(csv_file("account_summary.csv", |p| accounts::read_accounts_from_csv(p).map(|a| a.len())));
+        checks.push(csv_file("trading_history.csv", |p| trades::read_trades_from_csv(p).map(|t| t.len())));
+        checks.push(balances());
+        checks.extend(universe::roots().iter().map(price_files));
+        Self { checks }
+    }
+
+    pub fn worst(&self) -> Outcome {
+        self.checks.iter().map(|c| c.outcome).max().unwrap_or(Outcome::Pass)
+    }
+
+    /// Whether anything warned or failed.
+    pub fn needs_attention(&self) -> bool {
+        self.worst() >= Outcome::Warn
+    }
+
+    pub fn count(&self, outcome: Outcome) -> usize {
+        self.checks.iter().filter(|c| c.outcome == outcome).count()
+    }
+}
+
+fn config(error: Option<&str>) -> Check {
+    match error {
+        None if Path::new(CONFIG_PATH).exists() => Check::pass("Config", format!("{} parsed", CONFIG_PATH)),
+        None => Check::pass("Config", format!("No {}, using defaults", CONFIG_PATH)),
+        Some(err) => Check::new(
+            "Config",
+            Outcome::Fail,
+            format!("{}: {}", CONFIG_PATH, err),
+            "Fix the line named above; every setting is on its default until then",
+        ),
+    }
+}
+
+/// Whether a file can be created in `dir`.
+fn writable(dir: &str, name: &'static str) -> Check {
+    if !Path::new(dir).is_dir() {
+        return Check::new(name, Outcome::Warn, format!("{}/ doesn't exist", dir), "Downloading a ticker creates it");
+    }
+    let probe = Path::new(dir).join(".stm_write_check");
+    match fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            Check::pass(name, format!("{} is writable", dir))
+        }
+        Err(e) => Check::new(
+            name,
+            Outcome::Fail,
+            format!("Can't write to {}: {}", dir, e),
+            "Run stm from a directory you own, or fix its permissions",
+        ),
+    }
+}
+
+/// python3 itself, then the packages the scripts import.
+fn python() -> Vec<Check> {
+    let version = match Command::new("python3").arg("--version").output() {
+        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).trim().to_string(),
+        Ok(o) => {
+            let detail = format!("python3 --version failed: {}", String::from_utf8_lossy(&o.stderr).trim());
+            return vec![Check::new("Python", Outcome::Fail, detail, "Reinstall Python 3")];
+        }
+        Err(e) => {
+            let hint = "Install Python 3 and run install.sh; downloads and predictions need it";
+            return vec![Check::new("Python", Outcome::Fail, format!("python3 not found: {}", e), hint)];
+        }
+    };
+    let names: Vec<&str> = PACKAGES.iter().map(|&(name, _)| name).collect();
+    let script = "import importlib.util, sys; print(' '.join(n for n in sys.argv[1:] if importlib.util.find_spec(n) is None))";
+    let packages = match Command::new("python3").arg("-c").arg(script).args(&names).output() {
+        Ok(o) if o.status.success() => {
+            let missing: Vec<String> = String::from_utf8_lossy(&o.stdout).split_whitespace().map(str::to_string).collect();
+            packages_check(&missing)
+        }
+        _ => Check::new("Python packages", Outcome::Warn, "Couldn't list the installed packages", ""),
+    };
+    vec![Check::pass("Python", version), packages]
+}
+
+fn packages_check(missing: &[String]) -> Check {
+    if missing.is_empty() {
+        return Check::pass("Python packages", PACKAGES.map(|(name, _)| name).join(", "));
+    }
+    let mut needed_by: Vec<&str> =
+        PACKAGES.iter().filter(|(name, _)| missing.iter().any(|m| m == name)).map(|&(_, what)| what).collect();
+    needed_by.dedup();
+    Check::new(
+        "Python packages",
+        Outcome::Warn,
+        format!("Missing {} (needed by {})", missing.join(", "), needed_by.join(" and ")),
+        format!("pip3 install {}, or run install.sh", missing.join(" ")),
+    )
+}
+
+fn onnx() -> Check {
+    #[cfg(feature = "native-ml")]
+    {
+        use crate::ml::native::NATIVE_MODEL_PATH;
+        if Path::new(NATIVE_MODEL_PATH).exists() {
+            Check::pass("ONNX model", format!("{} found", NATIVE_MODEL_PATH))
+        } else {
+            Check::new(
+                "ONNX model",
+                Outcome::Warn,
+                format!("No {}; predictions run ml/model.py instead", NATIVE_MODEL_PATH),
+                "Run ml/model.py once to export it",
+            )
+        }
+    }
+    #[cfg(not(feature = "native-ml"))]
+    Check::new("ONNX model", Outcome::Skip, "Not built with native-ml; predictions run ml/model.py", "")
+}
+
+fn network(timeout: Duration) -> Check {
+    let host = PROBE_HOST.trim_end_matches(":443");
+    let reached = PROBE_HOST
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())
+        .and_then(|mut addrs| addrs.next().ok_or_else(|| "no address".to_string()))
+        .and_then(|addr| TcpStream::connect_timeout(&addr, timeout).map_err(|e| e.to_string()));
+    match reached {
+        Ok(_) => Check::pass("Network", format!("{} reachable", host)),
+        Err(e) => Check::new(
+            "Network",
+            Outcome::Warn,
+            format!("Can't reach {}: {}", host, e),
+            "Stored data still works; downloads, live quotes and news need a connection ([net] in stm.toml for proxies)",
+        ),
+    }
+}
+
+fn csv_file(path: &'static str, read: impl Fn(&str) -> Result<usize, Box<dyn std::error::Error>>) -> Check {
+    if !Path::new(path).exists() {
+        return Check::new(path, Outcome::Warn, "Not found", "It's created when you add an account or record a trade");
+    }
+    match read(path) {
+        Ok(rows) => Check::pass(path, format!("{} rows", rows)),
+        Err(e) => Check::new(path, Outcome::Fail, e.to_string(), format!("Fix or move aside {}; the panels using it stay empty", path)),
+    }
+}
+
+/// account_summary.csv against the balances the trade history folds to.
+fn balances() -> Check {
+    let Ok((accounts, trades)) = ledger::read("account_summary.csv", "trading_history.csv") else {
+        return Check::new("Balances", Outcome::Skip, "The account or trade CSV can't be read", "");
+    };
+    match ledger::verify(&accounts, &trades).1.as_slice() {
+        [] => Check::pass("Balances", format!("{} accounts match the trade history", accounts.len())),
+        [first, rest @ ..] => Check::new(
+            "Balances",
+            Outcome::Warn,
+            match rest.len() {
+                0 => first.to_string(),
+                n => format!("{} (and {} more)", first, n),
+            },
+            "The dashboard shows the history's balances; stm verify lists the differences and --repair saves those",
+        ),
+    }
+}
+
+/// Every CSV under `root` as the price loader reads it, noting tickers
+/// stored in more than one directory.
+fn price_files(root: &Root) -> Check {
+    let dir = root.dir.display();
+    if !root.dir.is_dir() {
+        return Check::new("Price files", Outcome::Warn, format!("No {}/", dir), "Download a ticker to start");
+    }
+    let csvs = root.csvs();
+    let paths: Vec<&PathBuf> = csvs.iter().map(|s| &s.path).collect();
+    // Each file after the first holding its ticker.
+    let twice: Vec<&Series> =
+        csvs.iter().enumerate().filter(|(i, s)| csvs[..*i].iter().any(|o| o.ticker == s.ticker)).map(|(_, s)| s).collect();
+    let bad: Vec<String> = paths
+        .iter()
+        .filter_map(|p| prices::cached_bars(p).err().map(|e| format!("{}: {}", p.display(), e)))
+        .collect();
+    match bad.len() {
+        0 if paths.is_empty() => Check::new("Price files", Outcome::Warn, format!("No CSVs in {}/", dir), "Download a ticker to start"),
+        0 if twice.is_empty() => Check::pass("Price files", format!("{} CSVs readable", paths.len())),
+        0 => Check::pass(
+            "Price files",
+            format!(
+                "{} CSVs readable; {} more copies of a ticker, each listed by its directory, first: {}",
+                paths.len(),
+                twice.len(),
+                twice[0].path.display()
+            ),
+        ),
+        n => Check::new(
+            "Price files",
+            Outcome::Fail,
+            format!("{} of {} unreadable; first: {}", n, paths.len(), bad[0]),
+            "Download those tickers again to replace them",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_packages_name_what_needs_them() {
+        let check = packages_check(&["numpy".to_string(), "torch".to_string()]);
+        assert_eq!(check.outcome, Outcome::Warn);
+        assert_eq!(check.detail, "Missing numpy, torch (needed by the model)");
+        assert_eq!(check.hint, "pip3 install numpy torch, or run install.sh");
+        assert_eq!(packages_check(&[]).outcome, Outcome::Pass);
+    }
+
+    #[test]
+    fn worst_outcome_ranks_fail_over_warn() {
+        let mut report = HealthReport { checks: vec![Check::pass("a", ""), config(Some("bad line"))] };
+        assert_eq!(report.worst(), Outcome::Fail);
+        report.checks.pop();
+        report.checks.push(Check::new("b", Outcome::Skip, "", ""));
+        assert_eq!(report.worst(), Outcome::Skip);
+        assert_eq!(report.count(Outcome::Pass), 1);
+    }
+}
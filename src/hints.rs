@@ -0,0 +1,131 @@
+//! The hints bar: the keys that matter most where the user is.
+//!
+//! With `[layout] hints_bar` the dashboard's bottom line lists the actions
+//! of the focused panel, or the keys of what's being typed, then the ones
+//! that work everywhere, as many as fit. Action keys are looked up in the
+//! keymap as it's bound, so `[keys]` overrides show.
+
+use crate::app::{Focus, MLMode};
+use crate::keymap::{Action, Keymap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hint {
+    /// An action, shown with its bound key.
+    Action(Action, &'static str),
+    /// A key handled directly rather than through the keymap.
+    Key(&'static str, &'static str),
+}
+
+impl Hint {
+    /// The key and what it does.
+    pub fn parts(self, keymap: &Keymap) -> (String, &'static str) {
+        match self {
+            Hint::Action(action, text) => (keymap.label(action), text),
+            Hint::Key(key, text) => (key.to_string(), text),
+        }
+    }
+}
+
+/// What the keys currently go to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    /// The account, trade or transfer form, or the date range picker.
+    Form,
+    Typing(MLMode),
+    /// The Stock Chart's crosshair, see `drawings`.
+    Crosshair,
+    Panel(Focus),
+}
+
+/// Hints shown after the context's own, in any panel.
+const GLOBAL: &[Hint] = &[
+    Hint::Action(Action::FocusNext, "next panel"),
+    Hint::Action(Action::Menu, "menu"),
+    Hint::Action(Action::Help, "help"),
+    Hint::Action(Action::Quit, "quit"),
+];
+
+/// Most relevant first, since the bar cuts off what doesn't fit.
+pub fn hints(context: Context) -> Vec<Hint> {
+    let own: &[Hint] = match context {
+        Context::Form => {
+            return vec![Hint::Key("Tab", "next field"), Hint::Key("Enter", "save"), Hint::Key("Esc", "cancel")];
+        }
+        Context::Typing(mode) => {
+            let enter = match mode {
+                MLMode::Search => "download",
+                MLMode::Filter => "select",
+                MLMode::Alert => "backtest the rule",
+                MLMode::JumpToDate => "jump",
+                MLMode::List => "run model",
+            };
+            let mut typing = vec![Hint::Key("Enter", enter), Hint::Key("Esc", "cancel")];
+            if mode == MLMode::Filter {
+                typing.insert(1, Hint::Key("↑/↓", "pick"));
+            }
+            return typing;
+        }
+        Context::Crosshair => &[
+            Hint::Key("←/→/↑/↓", "move"),
+            Hint::Key("Enter", "price level"),
+            Hint::Key("t", "trendline"),
+            Hint::Key("a", "alert on level"),
+            Hint::Key("Del", "remove line"),
+            Hint::Key("Esc", "done"),
+        ],
+        Context::Panel(Focus::MLList) => &[
+            Hint::Key("Enter", "run model"),
+            Hint::Action(Action::Search, "search"),
+            Hint::Action(Action::Filter, "filter"),
+            Hint::Action(Action::ToggleCompare, "compare"),
+            Hint::Action(Action::SortPctChange, "sort by %"),
+            Hint::Action(Action::AlertPreview, "alert backtest"),
+            Hint::Action(Action::ShowReturns, "returns"),
+            Hint::Action(Action::FillGaps, "fill gaps"),
+        ],
+        Context::Panel(Focus::Chart) => &[
+            Hint::Action(Action::DateRange, "date range"),
+            Hint::Action(Action::Crosshair, "draw"),
+            Hint::Action(Action::ChartScale, "axis"),
+            Hint::Action(Action::ToggleVolume, "volume"),
+            Hint::Action(Action::ToggleOrderBook, "order book"),
+            Hint::Action(Action::GrowChart, "taller"),
+            Hint::Action(Action::ExportChart, "export"),
+        ],
+        Context::Panel(Focus::LiveTrades) => &[
+            Hint::Action(Action::RecordTrade, "trade"),
+            Hint::Action(Action::JumpToDate, "jump to date"),
+            Hint::Action(Action::PageDown, "page"),
+            Hint::Action(Action::Analytics, "performance"),
+        ],
+        Context::Panel(Focus::Accounts) => &[
+            Hint::Action(Action::NewAccount, "new"),
+            Hint::Action(Action::EditAccount, "edit"),
+            Hint::Action(Action::RecordTrade, "trade"),
+            Hint::Action(Action::Transfer, "transfer"),
+            Hint::Action(Action::CloseAccount, "close"),
+            Hint::Action(Action::ToggleArchived, "archived"),
+            Hint::Action(Action::RankAccounts, "rank"),
+        ],
+        Context::Panel(Focus::Jobs) => {
+            &[Hint::Action(Action::CancelJob, "cancel job"), Hint::Action(Action::ShowErrors, "errors")]
+        }
+        Context::Panel(Focus::News | Focus::Calendar) => &[Hint::Action(Action::Refresh, "refresh")],
+    };
+    own.iter().chain(GLOBAL).copied().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panels_lead_with_their_own_actions() {
+        let accounts = hints(Context::Panel(Focus::Accounts));
+        assert_eq!(accounts[0], Hint::Action(Action::NewAccount, "new"));
+        assert_eq!(accounts.last(), Some(&Hint::Action(Action::Quit, "quit")));
+        assert_eq!(accounts[0].parts(&Keymap::default()), ("n".to_string(), "new"));
+        assert!(!hints(Context::Form).contains(&GLOBAL[0]));
+        assert_eq!(hints(Context::Typing(MLMode::Filter))[1], Hint::Key("↑/↓", "pick"));
+    }
+}
@@ -0,0 +1,176 @@
+//! Validated identifiers for tickers and accounts.
+//!
+//! Both newtypes normalize on construction, so two values that compare equal
+//! always refer to the same thing, and a `Ticker` can't be passed where an
+//! `AccountId` is expected.
+
+use std::error::Error;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+const MAX_TICKER_LEN: usize = 15;
+const MAX_ACCOUNT_LEN: usize = 32;
+/// Quote currencies Yahoo lists crypto pairs against (`BTC-USD`, `ETH-EUR`).
+const CRYPTO_QUOTES: [&str; 9] = ["USD", "USDT", "USDC", "EUR", "GBP", "JPY", "CAD", "BTC", "ETH"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdError {
+    Empty,
+    TooLong(String),
+    InvalidChar(String, char),
+}
+
+impl fmt::Display for IdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdError::Empty => write!(f, "identifier is empty"),
+            IdError::TooLong(s) => write!(f, "{} is too long", s),
+            IdError::InvalidChar(s, c) => write!(f, "{} contains invalid character {:?}", s, c),
+        }
+    }
+}
+
+impl Error for IdError {}
+
+/// Exchange symbol, uppercased. Allows letters, digits and the punctuation
+/// Yahoo uses for share classes, indices, FX and crypto pairs (`BRK-B`,
+/// `^GSPC`, `EURUSD=X`, `BTC-USD`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Ticker(String);
+
+impl Ticker {
+    pub fn parse(s: &str) -> Result<Self, IdError> {
+        let s = s.trim().to_uppercase();
+        if s.is_empty() {
+            return Err(IdError::Empty);
+        }
+        if s.len() > MAX_TICKER_LEN {
+            return Err(IdError::TooLong(s));
+        }
+        if let Some(c) = s.chars().find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '^' | '='))) {
+            return Err(IdError::InvalidChar(s, c));
+        }
+        Ok(Self(s))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Base and quote currency of a crypto pair like `BTC-USD`. Share
+    /// classes (`BRK-B`) don't count: the quote must be a currency and the
+    /// base at least two letters.
+    pub fn crypto_pair(&self) -> Option<(&str, &str)> {
+        let (base, quote) = self.0.split_once('-')?;
+        (base.len() >= 2 && base.chars().all(|c| c.is_ascii_alphanumeric()) && CRYPTO_QUOTES.contains(&quote))
+            .then_some((base, quote))
+    }
+
+    /// Whether this is a crypto pair, which trades around the clock.
+    pub fn is_crypto(&self) -> bool {
+        self.crypto_pair().is_some()
+    }
+}
+
+/// Account name as shown in the summary table. Case is kept, surrounding
+/// whitespace is trimmed and internal runs of whitespace collapse to one
+/// space.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct AccountId(String);
+
+impl AccountId {
+    pub fn parse(s: &str) -> Result<Self, IdError> {
+        let s = s.split_whitespace().collect::<Vec<_>>().join(" ");
+        if s.is_empty() {
+            return Err(IdError::Empty);
+        }
+        if s.chars().count() > MAX_ACCOUNT_LEN {
+            return Err(IdError::TooLong(s));
+        }
+        if let Some(c) = s.chars().find(|c| !(c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.'))) {
+            return Err(IdError::InvalidChar(s, c));
+        }
+        Ok(Self(s))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+macro_rules! string_conversions {
+    ($ty:ident) => {
+        impl fmt::Display for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl TryFrom<String> for $ty {
+            type Error = IdError;
+
+            fn try_from(s: String) -> Result<Self, Self::Error> {
+                Self::parse(&s)
+            }
+        }
+
+        impl From<$ty> for String {
+            fn from(id: $ty) -> String {
+                id.0
+            }
+        }
+    };
+}
+
+string_conversions!(Ticker);
+string_conversions!(AccountId);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tickers_uppercase_and_reject_what_yahoo_never_lists() {
+        assert_eq!(Ticker::parse(" brk-b ").unwrap().as_str(), "BRK-B");
+        for symbol in ["^GSPC", "EURUSD=X", "RDS.A", "7203.T"] {
+            assert_eq!(Ticker::parse(symbol).unwrap().as_str(), symbol);
+        }
+        assert_eq!(Ticker::parse("  "), Err(IdError::Empty));
+        assert_eq!(Ticker::parse("aapl msft"), Err(IdError::InvalidChar("AAPL MSFT".to_string(), ' ')));
+        assert_eq!(Ticker::parse("../etc"), Err(IdError::InvalidChar("../ETC".to_string(), '/')));
+        assert_eq!(Ticker::parse("a$"), Err(IdError::InvalidChar("A$".to_string(), '$')));
+        assert_eq!(Ticker::parse("abcdefghijklmnop"), Err(IdError::TooLong("ABCDEFGHIJKLMNOP".to_string())));
+        // Serde goes through the same checks.
+        assert_eq!(serde_json::from_str::<Ticker>("\"msft\"").unwrap().as_str(), "MSFT");
+        assert!(serde_json::from_str::<Ticker>("\"ms ft\"").is_err());
+    }
+
+    #[test]
+    fn crypto_pairs_need_a_currency_quote() {
+        let pair = |s| Ticker::parse(s).unwrap().crypto_pair().map(|(b, q)| (b.to_string(), q.to_string()));
+        assert_eq!(pair("btc-usd"), Some(("BTC".to_string(), "USD".to_string())));
+        assert_eq!(pair("ETH-BTC"), Some(("ETH".to_string(), "BTC".to_string())));
+        assert!(Ticker::parse("SOL-USDT").unwrap().is_crypto());
+        // Share classes and unknown quotes aren't pairs.
+        for symbol in ["BRK-B", "B-USD", "BTC-XYZ", "BTCUSD", "BTC.X-USD"] {
+            assert_eq!(pair(symbol), None, "{}", symbol);
+        }
+    }
+
+    #[test]
+    fn account_names_keep_case_and_collapse_whitespace() {
+        assert_eq!(AccountId::parse("  Joint \t savings\n acct ").unwrap().as_str(), "Joint savings acct");
+        assert_eq!(AccountId::parse("Roth_IRA-2.0").unwrap().to_string(), "Roth_IRA-2.0");
+        assert_eq!(AccountId::parse("Épargne").unwrap().as_str(), "Épargne");
+        assert_ne!(AccountId::parse("alice"), AccountId::parse("Alice"));
+        assert_eq!(AccountId::parse(" \t "), Err(IdError::Empty));
+        assert_eq!(AccountId::parse("a,b"), Err(IdError::InvalidChar("a,b".to_string(), ',')));
+        // The limit counts characters after collapsing, not bytes.
+        assert!(AccountId::parse(&"é".repeat(MAX_ACCOUNT_LEN)).is_ok());
+        assert!(AccountId::parse(&format!("{}  x", "a".repeat(MAX_ACCOUNT_LEN - 2))).is_ok());
+        assert_eq!(AccountId::parse(&"a".repeat(MAX_ACCOUNT_LEN + 1)), Err(IdError::TooLong("a".repeat(MAX_ACCOUNT_LEN + 1))));
+    }
+}
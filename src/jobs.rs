@@ -0,0 +1,467 @@
+//! Background queue for the Python subprocess work.
+//!
+//! Downloads, gap fills and model runs are submitted as jobs and run one at
+//! a time on a worker thread (they write the same CSVs), so the UI keeps
+//! drawing while they work. Downloads of many tickers, which each write
+//! their own, go to a pool of workers instead (see `downloads`). The UI
+//! thread drains status updates each frame and feeds finished jobs' events
+//! into the reducer.
+
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::app::AppEvent;
+use crate::errors::AppError;
+
+/// Finished jobs kept for the Jobs panel.
+const MAX_JOBS: usize = 50;
+/// Lines of stderr kept from a failed job.
+const STDERR_TAIL: usize = 5;
+/// How often a running subprocess is checked for exit or cancellation.
+const WAIT_POLL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Done => "done",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Done | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+#[derive(Debug)]
+pub struct Job {
+    pub id: u64,
+    pub label: String,
+    pub status: JobStatus,
+    pub started: Option<Instant>,
+    pub finished: Option<Instant>,
+    // Result line, or why it failed; while running, what it's doing.
+    pub message: String,
+    pub stderr_tail: Vec<String>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Job {
+    /// Time spent running so far, or in total once finished.
+    pub fn elapsed(&self) -> Option<Duration> {
+        let started = self.started?;
+        Some(self.finished.unwrap_or_else(Instant::now).duration_since(started))
+    }
+}
+
+/// A successful job's status line and the events it produced.
+pub struct JobDone {
+    pub message: String,
+    pub events: Vec<AppEvent>,
+}
+
+/// Why a job failed, with the subprocess's stderr if there was one.
+pub struct JobError {
+    pub message: String,
+    pub stderr: String,
+    // Delivered even though the job failed, unless it was cancelled.
+    pub events: Vec<AppEvent>,
+}
+
+impl JobError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self { message: message.into(), stderr: String::new(), events: Vec::new() }
+    }
+
+    /// A failed subprocess: the last stderr line is the message.
+    pub fn from_output(what: &str, output: &Output) -> Self {
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        let last = stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("no stderr");
+        Self { message: format!("{} error: {}", what, last.trim()), stderr, events: Vec::new() }
+    }
+}
+
+pub type JobResult = Result<JobDone, JobError>;
+type Work = Box<dyn FnOnce(&JobContext) -> JobResult + Send>;
+
+type Submitted = (u64, Arc<AtomicBool>, Work);
+
+/// Handed to running work so subprocesses can be cancelled and progress
+/// shown.
+pub struct JobContext {
+    id: u64,
+    cancel: Arc<AtomicBool>,
+    tx: Sender<Update>,
+}
+
+impl JobContext {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    /// Shows `status` as the running job's message in the Jobs panel.
+    pub fn report(&self, status: impl Into<String>) {
+        let _ = self.tx.send(Update::Progress(self.id, status.into()));
+    }
+
+    /// Sleeps for `wait`, or less if the job is cancelled meanwhile.
+    /// Returns whether it slept the whole time.
+    pub fn sleep(&self, wait: Duration) -> bool {
+        let until = Instant::now() + wait;
+        while let Some(left) = until.checked_duration_since(Instant::now()) {
+            if self.is_cancelled() {
+                return false;
+            }
+            thread::sleep(left.min(WAIT_POLL));
+        }
+        !self.is_cancelled()
+    }
+
+    /// Like `Command::output`, but kills the child if the job is cancelled.
+    pub fn output(&self, cmd: &mut Command) -> io::Result<Output> {
+        if self.is_cancelled() {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+        }
+        let mut child = cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+        // Drain the pipes while waiting so a chatty child can't fill them and block.
+        let stdout = read_to_end(child.stdout.take());
+        let stderr = read_to_end(child.stderr.take());
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if self.is_cancelled() {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+            }
+            thread::sleep(WAIT_POLL);
+        };
+        Ok(Output {
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        })
+    }
+}
+
+fn read_to_end<R: Read + Send + 'static>(pipe: Option<R>) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+enum Update {
+    Started(u64),
+    Progress(u64, String),
+    Finished(u64, JobResult),
+}
+
+/// Handle to the worker threads plus every job they have been given.
+pub struct JobQueue {
+    tx: Sender<Submitted>,
+    // The pool's queue, once something has been submitted to it.
+    pool: Option<Sender<Submitted>>,
+    updates: Sender<Update>,
+    rx: Receiver<Update>,
+    // Oldest first.
+    pub jobs: VecDeque<Job>,
+    next_id: u64,
+}
+
+/// Runs one submitted job, returning false once the UI side has gone.
+fn work_on((id, cancel, work): Submitted, tx: &Sender<Update>) -> bool {
+    let ctx = JobContext { id, cancel, tx: tx.clone() };
+    if ctx.is_cancelled() {
+        return true; // already marked cancelled on the UI side
+    }
+    tx.send(Update::Started(id)).is_ok() && tx.send(Update::Finished(id, work(&ctx))).is_ok()
+}
+
+impl JobQueue {
+    pub fn start() -> Self {
+        let (work_tx, work_rx) = mpsc::channel::<Submitted>();
+        let (tx, rx) = mpsc::channel();
+        let updates = tx.clone();
+        thread::spawn(move || {
+            for submitted in work_rx {
+                if !work_on(submitted, &tx) {
+                    return;
+                }
+            }
+        });
+        Self { tx: work_tx, pool: None, updates, rx, jobs: VecDeque::new(), next_id: 1 }
+    }
+
+    /// Queues `work` behind anything already submitted.
+    pub fn submit(&mut self, label: String, work: impl FnOnce(&JobContext) -> JobResult + Send + 'static) -> u64 {
+        let tx = self.tx.clone();
+        self.push(label, &tx, Box::new(work))
+    }
+
+    /// Queues `work` for the pool, which runs `workers` jobs at a time
+    /// alongside the one worker `submit` uses. The pool is started by the
+    /// first call, with that call's `workers`.
+    pub fn submit_parallel(
+        &mut self,
+        label: String,
+        workers: usize,
+        work: impl FnOnce(&JobContext) -> JobResult + Send + 'static,
+    ) -> u64 {
+        let pool = self.pool.get_or_insert_with(|| {
+            let (work_tx, work_rx) = mpsc::channel::<Submitted>();
+            let work_rx = Arc::new(Mutex::new(work_rx));
+            for _ in 0..workers.max(1) {
+                let (work_rx, tx) = (work_rx.clone(), self.updates.clone());
+                thread::spawn(move || {
+                    loop {
+                        let next = work_rx.lock().ok().and_then(|rx| rx.recv().ok());
+                        if !next.is_some_and(|submitted| work_on(submitted, &tx)) {
+                            return;
+                        }
+                    }
+                });
+            }
+            work_tx
+        });
+        let pool = pool.clone();
+        self.push(label, &pool, Box::new(work))
+    }
+
+    fn push(&mut self, label: String, queue: &Sender<Submitted>, work: Work) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let cancel = Arc::new(AtomicBool::new(false));
+        let mut job = Job {
+            id,
+            label,
+            status: JobStatus::Queued,
+            started: None,
+            finished: None,
+            message: String::new(),
+            stderr_tail: Vec::new(),
+            cancel: cancel.clone(),
+        };
+        if queue.send((id, cancel, work)).is_err() {
+            job.status = JobStatus::Failed;
+            job.message = "job worker has stopped".to_string();
+        }
+        self.jobs.push_back(job);
+        self.prune();
+        id
+    }
+
+    /// Cancels a queued job outright, or kills a running one's subprocess.
+    pub fn cancel(&mut self, id: u64) -> Option<String> {
+        let job = self.jobs.iter_mut().find(|j| j.id == id)?;
+        if job.status.is_finished() {
+            return None;
+        }
+        job.cancel.store(true, Ordering::Relaxed);
+        if job.status == JobStatus::Queued {
+            job.status = JobStatus::Cancelled;
+            job.finished = Some(Instant::now());
+            job.message = "cancelled before it started".to_string();
+        }
+        Some(format!("Cancelling {}", job.label))
+    }
+
+    /// Drains status updates, returning the events of jobs that finished.
+    pub fn poll(&mut self) -> Vec<AppEvent> {
+        let mut events = Vec::new();
+        while let Ok(update) = self.rx.try_recv() {
+            match update {
+                Update::Started(id) => {
+                    if let Some(job) = self.jobs.iter_mut().find(|j| j.id == id) {
+                        job.status = JobStatus::Running;
+                        job.started = Some(Instant::now());
+                    }
+                }
+                Update::Progress(id, status) => {
+                    if let Some(job) = self.jobs.iter_mut().find(|j| j.id == id && j.status == JobStatus::Running) {
+                        job.message = status;
+                    }
+                }
+                Update::Finished(id, result) => {
+                    let Some(job) = self.jobs.iter_mut().find(|j| j.id == id) else {
+                        continue;
+                    };
+                    job.finished = Some(Instant::now());
+                    match result {
+                        Ok(done) => {
+                            job.status = JobStatus::Done;
+                            job.message = done.message.clone();
+                            events.extend(done.events);
+                            events.push(AppEvent::Output(done.message));
+                        }
+                        Err(err) => {
+                            let cancelled = job.cancel.load(Ordering::Relaxed);
+                            job.status = if cancelled { JobStatus::Cancelled } else { JobStatus::Failed };
+                            job.message = if cancelled { "cancelled".to_string() } else { err.message.clone() };
+                            let lines: Vec<&str> = err.stderr.lines().filter(|l| !l.trim().is_empty()).collect();
+                            job.stderr_tail = lines[lines.len().saturating_sub(STDERR_TAIL)..]
+                                .iter()
+                                .map(|l| l.to_string())
+                                .collect();
+                            events.push(AppEvent::Output(format!("{}: {}", job.label, job.message)));
+                            if !cancelled {
+                                events.extend(err.events);
+                                events.push(AppEvent::Error(AppError::Job {
+                                    label: job.label.clone(),
+                                    message: job.message.clone(),
+                                }));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        events
+    }
+
+    /// Jobs not yet finished.
+    pub fn active(&self) -> usize {
+        self.jobs.iter().filter(|j| !j.status.is_finished()).count()
+    }
+
+    /// Drops the oldest finished jobs beyond `MAX_JOBS`.
+    fn prune(&mut self) {
+        while self.jobs.len() > MAX_JOBS {
+            match self.jobs.iter().position(|j| j.status.is_finished()) {
+                Some(i) => {
+                    self.jobs.remove(i);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Polls until `done` holds, returning the events seen on the way.
+    fn settle(queue: &mut JobQueue, done: impl Fn(&JobQueue) -> bool) -> Vec<AppEvent> {
+        let mut events = Vec::new();
+        let until = Instant::now() + Duration::from_secs(5);
+        while !done(queue) {
+            assert!(Instant::now() < until, "jobs stuck: {:?}", queue.jobs);
+            thread::sleep(Duration::from_millis(5));
+            events.extend(queue.poll());
+        }
+        events.extend(queue.poll());
+        events
+    }
+
+    fn status(queue: &JobQueue, id: u64) -> JobStatus {
+        queue.jobs.iter().find(|j| j.id == id).unwrap().status
+    }
+
+    fn outputs(events: &[AppEvent]) -> Vec<&str> {
+        events.iter().filter_map(|e| if let AppEvent::Output(line) = e { Some(line.as_str()) } else { None }).collect()
+    }
+
+    #[test]
+    fn jobs_run_in_turn_and_cancel_whether_queued_or_running() {
+        let mut queue = JobQueue::start();
+        let (release, gate) = mpsc::channel::<()>();
+        let fetch = queue.submit("fetch".to_string(), move |ctx| {
+            ctx.report("waiting");
+            let _ = gate.recv();
+            Ok(JobDone { message: "fetched".to_string(), events: Vec::new() })
+        });
+        let train = queue.submit("train".to_string(), |_| {
+            let stderr = (1..=7).map(|i| format!("line {}\n\n", i)).collect();
+            Err(JobError { message: "train error: line 7".to_string(), stderr, events: vec![AppEvent::Output("partial".to_string())] })
+        });
+        let ran = Arc::new(AtomicBool::new(false));
+        let skipped = {
+            let ran = ran.clone();
+            queue.submit("skipped".to_string(), move |_| {
+                ran.store(true, Ordering::Relaxed);
+                Err(JobError::new("ran anyway"))
+            })
+        };
+
+        // Cancelled while queued, it finishes at once and is never run.
+        assert_eq!(queue.cancel(skipped), Some("Cancelling skipped".to_string()));
+        assert_eq!(status(&queue, skipped), JobStatus::Cancelled);
+        assert_eq!(queue.cancel(skipped), None);
+        settle(&mut queue, |q| q.jobs[0].message == "waiting");
+        assert_eq!((status(&queue, fetch), status(&queue, train)), (JobStatus::Running, JobStatus::Queued));
+        assert_eq!(queue.active(), 2);
+
+        release.send(()).unwrap();
+        let events = settle(&mut queue, |q| q.active() == 0);
+        assert_eq!(outputs(&events), ["fetched", "train: train error: line 7", "partial"]);
+        assert!(matches!(events.last(), Some(AppEvent::Error(AppError::Job { label, .. })) if label == "train"));
+        let failed = &queue.jobs[1];
+        assert_eq!((failed.status, failed.stderr_tail.len()), (JobStatus::Failed, STDERR_TAIL));
+        assert_eq!((failed.stderr_tail[0].as_str(), failed.stderr_tail[4].as_str()), ("line 3", "line 7"));
+        assert!(queue.jobs[0].elapsed().is_some() && queue.jobs[2].elapsed().is_none());
+        assert!(!ran.load(Ordering::Relaxed));
+
+        // A running job is only asked to stop; it shows as cancelled, with
+        // no error, once its work returns.
+        let slow = queue.submit("slow".to_string(), |ctx| match ctx.sleep(Duration::from_secs(30)) {
+            true => Ok(JobDone { message: "slept".to_string(), events: Vec::new() }),
+            false => Err(JobError::new("interrupted")),
+        });
+        settle(&mut queue, |q| status(q, slow) == JobStatus::Running);
+        assert!(queue.cancel(slow).is_some());
+        assert_eq!(status(&queue, slow), JobStatus::Running);
+        let events = settle(&mut queue, |q| q.active() == 0);
+        assert_eq!(outputs(&events), ["slow: cancelled"]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(status(&queue, slow), JobStatus::Cancelled);
+    }
+
+    #[test]
+    fn only_finished_jobs_are_dropped_past_the_limit() {
+        let mut queue = JobQueue::start();
+        let (release, gate) = mpsc::channel::<()>();
+        let first = queue.submit("first".to_string(), move |_| {
+            let _ = gate.recv();
+            Err(JobError::new("released"))
+        });
+        for i in 0..MAX_JOBS + 10 {
+            let id = queue.submit(format!("job {}", i), |_| Err(JobError::new("not cancelled")));
+            queue.cancel(id);
+        }
+        // The oldest finished go first; the unfinished one stays.
+        let ids: Vec<u64> = queue.jobs.iter().map(|j| j.id).collect();
+        assert_eq!(ids.len(), MAX_JOBS);
+        assert_eq!((ids[0], ids[1], ids[MAX_JOBS - 1]), (first, 13, 61));
+
+        // With nothing finished left to drop, the list grows.
+        for i in 0..MAX_JOBS {
+            queue.submit(format!("queued {}", i), |_| Ok(JobDone { message: String::new(), events: Vec::new() }));
+        }
+        assert_eq!(queue.jobs.len(), MAX_JOBS + 1);
+        assert!(queue.jobs.iter().all(|j| !j.status.is_finished()));
+        release.send(()).unwrap();
+    }
+}
@@ -0,0 +1,488 @@
+//! Remappable shortcuts.
+//!
+//! Each `Action` has a default key, overridable in the `[keys]` section of
+//! `stm.toml`, e.g.
+//!
+//! ```toml
+//! [keys]
+//! quit = "Q"
+//! search = "f2"
+//! ```
+//!
+//! Keys are a single character or one of `tab`, `backtab`, `enter`, `esc`,
+//! `space`, `backspace`, `up`, `down`, `left`, `right` and `f1`..`f12`,
+//! optionally prefixed with `ctrl+`.
+//! Several actions may share a key; the first one that applies in the
+//! current context (e.g. the focused panel) wins. Ctrl+Z suspends to the
+//! shell, as in any terminal program, and can't be rebound.
+
+use std::collections::BTreeMap;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Help,
+    FocusNext,
+    FocusPrev,
+    Search,
+    Filter,
+    FillGaps,
+    SortTicker,
+    SortPrice,
+    SortChange,
+    SortPctChange,
+    SortRelStrength,
+    ToggleVolume,
+    ToggleOrderBook,
+    ShowErrors,
+    ShowUsage,
+    NewAccount,
+    EditAccount,
+    CloseAccount,
+    ToggleArchived,
+    RankAccounts,
+    RecordTrade,
+    Transfer,
+    PageUp,
+    PageDown,
+    JumpToDate,
+    CancelJob,
+    GrowChart,
+    ShrinkChart,
+    CollapsePanel,
+    RestorePanels,
+    AlertPreview,
+    ShowReturns,
+    RetentionReport,
+    ExportReport,
+    ReleaseNotes,
+    ExportChart,
+    TradingGame,
+    TimeTravel,
+    Broker,
+    PaperOrder,
+    AutoTrade,
+    Models,
+    DateRange,
+    ToggleCompare,
+    Refresh,
+    Menu,
+    Analytics,
+    Changelog,
+    Dividends,
+    DownloadAll,
+    Crosshair,
+    ChartScale,
+    MarketOverview,
+}
+
+/// A key as bindings see it: the code plus whether Ctrl was held. Other
+/// modifiers are already folded into the code (`A`, `BackTab`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    pub code: KeyCode,
+    pub ctrl: bool,
+}
+
+impl Key {
+    pub fn plain(code: KeyCode) -> Self {
+        Self { code, ctrl: false }
+    }
+
+    pub fn ctrl(code: KeyCode) -> Self {
+        Self { code, ctrl: true }
+    }
+}
+
+/// Suspends the dashboard to the shell until `fg`, whatever is open.
+pub const SUSPEND: Key = Key { code: KeyCode::Char('z'), ctrl: true };
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        Self { code: event.code, ctrl: event.modifiers.contains(KeyModifiers::CONTROL) }
+    }
+}
+
+impl Action {
+    /// Every action, in the order the help overlay lists them.
+    pub const ALL: [Action; 54] = [
+        Action::FocusNext,
+        Action::FocusPrev,
+        Action::SortTicker,
+        Action::SortPrice,
+        Action::SortChange,
+        Action::SortPctChange,
+        Action::SortRelStrength,
+        Action::FillGaps,
+        Action::DownloadAll,
+        Action::ToggleVolume,
+        Action::ChartScale,
+        Action::ToggleOrderBook,
+        Action::Refresh,
+        Action::DateRange,
+        Action::ToggleCompare,
+        Action::Crosshair,
+        Action::GrowChart,
+        Action::ShrinkChart,
+        Action::CollapsePanel,
+        Action::RestorePanels,
+        Action::Search,
+        Action::Filter,
+        Action::AlertPreview,
+        Action::ShowReturns,
+        Action::RetentionReport,
+        Action::ExportReport,
+        Action::ReleaseNotes,
+        Action::Changelog,
+        Action::ExportChart,
+        Action::TradingGame,
+        Action::TimeTravel,
+        Action::MarketOverview,
+        Action::Analytics,
+        Action::Dividends,
+        Action::Broker,
+        Action::PaperOrder,
+        Action::AutoTrade,
+        Action::Models,
+        Action::NewAccount,
+        Action::EditAccount,
+        Action::CloseAccount,
+        Action::ToggleArchived,
+        Action::RankAccounts,
+        Action::RecordTrade,
+        Action::Transfer,
+        Action::PageUp,
+        Action::PageDown,
+        Action::JumpToDate,
+        Action::CancelJob,
+        Action::ShowErrors,
+        Action::ShowUsage,
+        Action::Menu,
+        Action::Help,
+        Action::Quit,
+    ];
+
+    /// Name used in `[keys]`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Help => "help",
+            Action::FocusNext => "focus_next",
+            Action::FocusPrev => "focus_prev",
+            Action::Search => "search",
+            Action::Filter => "filter",
+            Action::FillGaps => "fill_gaps",
+            Action::DownloadAll => "download_all",
+            Action::SortTicker => "sort_ticker",
+            Action::SortPrice => "sort_price",
+            Action::SortChange => "sort_change",
+            Action::SortPctChange => "sort_pct_change",
+            Action::SortRelStrength => "sort_rel_strength",
+            Action::ToggleVolume => "toggle_volume",
+            Action::ToggleOrderBook => "toggle_order_book",
+            Action::ShowErrors => "show_errors",
+            Action::ShowUsage => "show_usage",
+            Action::NewAccount => "new_account",
+            Action::EditAccount => "edit_account",
+            Action::CloseAccount => "close_account",
+            Action::ToggleArchived => "toggle_archived",
+            Action::RankAccounts => "rank_accounts",
+            Action::RecordTrade => "record_trade",
+            Action::Transfer => "transfer",
+            Action::PageUp => "page_up",
+            Action::PageDown => "page_down",
+            Action::JumpToDate => "jump_to_date",
+            Action::CancelJob => "cancel_job",
+            Action::GrowChart => "grow_chart",
+            Action::ShrinkChart => "shrink_chart",
+            Action::CollapsePanel => "collapse_panel",
+            Action::RestorePanels => "restore_panels",
+            Action::AlertPreview => "alert_preview",
+            Action::ShowReturns => "show_returns",
+            Action::RetentionReport => "retention_report",
+            Action::ExportReport => "export_report",
+            Action::ReleaseNotes => "release_notes",
+            Action::ExportChart => "export_chart",
+            Action::TradingGame => "trading_game",
+            Action::TimeTravel => "time_travel",
+            Action::Broker => "broker",
+            Action::PaperOrder => "paper_order",
+            Action::AutoTrade => "auto_trade",
+            Action::Models => "models",
+            Action::DateRange => "date_range",
+            Action::ToggleCompare => "toggle_compare",
+            Action::Refresh => "refresh",
+            Action::Menu => "menu",
+            Action::Analytics => "analytics",
+            Action::Changelog => "changelog",
+            Action::Dividends => "dividends",
+            Action::Crosshair => "crosshair",
+            Action::ChartScale => "chart_scale",
+            Action::MarketOverview => "market_overview",
+        }
+    }
+
+    /// What the action does, for the help overlay.
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::Help => "Toggle this help",
+            Action::FocusNext => "Focus next panel",
+            Action::FocusPrev => "Focus previous panel",
+            Action::Search => "Search box: TICKER [INTERVAL] [RANGE], Enter downloads; ?QUERY looks up symbols",
+            Action::Filter => "Fuzzy-filter the ML list (ROOT: for one data root); Enter jumps to the match",
+            Action::FillGaps => "Re-download missing sessions for the selected stock",
+            Action::DownloadAll => "Download every stock in the ML list ([downloads] in stm.toml)",
+            Action::SortTicker => "Sort ML list by ticker (again to reverse)",
+            Action::SortPrice => "Sort ML list by price",
+            Action::SortChange => "Sort ML list by change",
+            Action::SortPctChange => "Sort ML list by % change",
+            Action::SortRelStrength => "Sort ML list by 3-month strength relative to the benchmark",
+            Action::ToggleVolume => "Toggle volume bars under the chart",
+            Action::ToggleOrderBook => "Show/hide bid and ask depth beside the chart (Chart focused)",
+            Action::ShowErrors => "Show errors (failed loads, downloads, jobs, feeds)",
+            Action::ShowUsage => "Show local usage stats",
+            Action::NewAccount => "New account (Accounts focused)",
+            Action::EditAccount => "Edit selected account (Accounts focused)",
+            Action::CloseAccount => "Close/reopen selected account (Accounts focused)",
+            Action::ToggleArchived => "Show/hide closed accounts (Accounts focused)",
+            Action::RankAccounts => "Rank accounts by % change, best first (Accounts focused)",
+            Action::RecordTrade => "Record a gain or loss on an account",
+            Action::Transfer => "Move cash from one account to another",
+            Action::PageUp => "Page up the trade history (Live Trades focused)",
+            Action::PageDown => "Page down the trade history (Live Trades focused)",
+            Action::JumpToDate => "Jump the trade history to a date, YYYY-MM-DD (Live Trades focused)",
+            Action::CancelJob => "Cancel selected job (Jobs focused)",
+            Action::GrowChart => "Give the chart row more height than the tables",
+            Action::ShrinkChart => "Give the tables more height than the chart",
+            Action::CollapsePanel => "Hide the focused panel",
+            Action::RestorePanels => "Show all hidden panels",
+            Action::AlertPreview => "Backtest an alert rule on the selected stock, e.g. 'close > 150'",
+            Action::ShowReturns => "Histogram of the selected stock's daily returns",
+            Action::RetentionReport => "List what the retention policy would delete",
+            Action::ExportReport => "Write a portfolio report to reports/",
+            Action::ReleaseNotes => "Show the notes of a newer stm release",
+            Action::ExportChart => "Save the chart as a PNG or SVG image in reports/",
+            Action::TradingGame => "Trading game: accounts trade with play money, ranked by return",
+            Action::TimeTravel => "Time travel: balances, positions and prices as of a past date",
+            Action::Broker => "Broker: orders, positions and fills on the paper account, or [alpaca]",
+            Action::PaperOrder => "Open the broker with the last model run's suggested order typed in",
+            Action::AutoTrade => "Arm or disarm orders on [strategy] signals ([autotrade] in stm.toml)",
+            Action::Models => "Model versions of the selected stock: pick the one it predicts with, or train another",
+            Action::DateRange => "Pick dates: chart zoom (Chart), download (ML list), backtest (alert preview)",
+            Action::ToggleCompare => "Mark/unmark the selected stock; the chart compares marked stocks",
+            Action::Refresh => "Reload the focused panel's data",
+            Action::Analytics => "Performance: win rate, profit factor and monthly returns of the trade history",
+            Action::Changelog => "Changelog: what each stm version added",
+            Action::Dividends => "Dividends: income per holding and the next year's projected; Enter records one",
+            Action::MarketOverview => "Market overview: the ML list as cells coloured by % change; Enter charts one",
+            Action::ChartScale => "Chart axis: linear, log, or % change from the first bar shown",
+            Action::Crosshair => "Crosshair: Enter places a price level, t a trendline, a makes a level an alert (Chart focused)",
+            Action::Menu => "Menu bar with every action: arrows pick one, Enter runs it",
+        }
+    }
+
+    fn default_key(self) -> Key {
+        let code = match self {
+            Action::GrowChart => return Key::ctrl(KeyCode::Up),
+            Action::ShrinkChart => return Key::ctrl(KeyCode::Down),
+            Action::Quit => KeyCode::Char('q'),
+            Action::Help => KeyCode::Char('h'),
+            Action::FocusNext => KeyCode::Tab,
+            Action::FocusPrev => KeyCode::BackTab,
+            Action::Search => KeyCode::Char('s'),
+            Action::Filter => KeyCode::Char('/'),
+            Action::FillGaps => KeyCode::Char('g'),
+            Action::SortTicker => KeyCode::Char('1'),
+            Action::SortPrice => KeyCode::Char('2'),
+            Action::SortChange => KeyCode::Char('3'),
+            Action::SortPctChange => KeyCode::Char('4'),
+            Action::SortRelStrength => KeyCode::Char('5'),
+            Action::ToggleVolume => KeyCode::Char('v'),
+            Action::ToggleOrderBook => KeyCode::Char('o'),
+            Action::ShowErrors => KeyCode::Char('l'),
+            Action::ShowUsage => KeyCode::Char('u'),
+            Action::NewAccount => KeyCode::Char('n'),
+            Action::EditAccount => KeyCode::Char('e'),
+            Action::CloseAccount => KeyCode::Char('x'),
+            Action::ToggleArchived => KeyCode::Char('a'),
+            Action::RankAccounts => KeyCode::Char('p'),
+            Action::RecordTrade => KeyCode::Char('t'),
+            Action::Transfer => KeyCode::Char('T'),
+            Action::PageUp => KeyCode::PageUp,
+            Action::PageDown => KeyCode::PageDown,
+            Action::JumpToDate => KeyCode::Char('j'),
+            Action::CancelJob => KeyCode::Char('c'),
+            Action::CollapsePanel => KeyCode::Char('z'),
+            Action::RestorePanels => KeyCode::Char('Z'),
+            Action::AlertPreview => KeyCode::Char('b'),
+            Action::ShowReturns => KeyCode::Char('H'),
+            Action::RetentionReport => KeyCode::Char('R'),
+            Action::ExportReport => KeyCode::Char('E'),
+            Action::ReleaseNotes => KeyCode::Char('N'),
+            Action::ExportChart => KeyCode::Char('C'),
+            Action::TradingGame => KeyCode::Char('G'),
+            Action::TimeTravel => KeyCode::Char('A'),
+            Action::Broker => KeyCode::Char('B'),
+            Action::PaperOrder => KeyCode::Char('P'),
+            Action::AutoTrade => KeyCode::Char('S'),
+            Action::Models => KeyCode::Char('M'),
+            Action::DateRange => KeyCode::Char('d'),
+            Action::Refresh => KeyCode::Char('r'),
+            Action::ToggleCompare => KeyCode::Char(' '),
+            Action::Menu => KeyCode::F(10),
+            Action::Analytics => KeyCode::Char('W'),
+            Action::Changelog => KeyCode::Char('V'),
+            Action::Dividends => KeyCode::Char('D'),
+            Action::DownloadAll => KeyCode::Char('U'),
+            Action::Crosshair => KeyCode::Char('x'),
+            Action::ChartScale => KeyCode::Char('L'),
+            Action::MarketOverview => KeyCode::Char('O'),
+        };
+        Key::plain(code)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(Action, Key)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self { bindings: Action::ALL.iter().map(|&a| (a, a.default_key())).collect() }
+    }
+}
+
+impl Keymap {
+    /// Defaults with the `[keys]` overrides applied. Entries that name an
+    /// unknown action or key are skipped and described in the returned list.
+    pub fn from_config(keys: &BTreeMap<String, String>) -> (Self, Vec<String>) {
+        let mut keymap = Self::default();
+        let mut problems = Vec::new();
+        for (name, spec) in keys {
+            let Some(action) = Action::ALL.iter().copied().find(|a| a.name() == name) else {
+                problems.push(format!("[keys] unknown action {:?}", name));
+                continue;
+            };
+            let Some(key) = parse_key(spec) else {
+                problems.push(format!("[keys] {}: unknown key {:?}", name, spec));
+                continue;
+            };
+            for binding in &mut keymap.bindings {
+                if binding.0 == action {
+                    binding.1 = key;
+                }
+            }
+        }
+        (keymap, problems)
+    }
+
+    /// Actions bound to `key`, in `Action::ALL` order.
+    pub fn actions_for(&self, key: Key) -> impl Iterator<Item = Action> + '_ {
+        self.bindings.iter().filter(move |(_, k)| *k == key).map(|&(a, _)| a)
+    }
+
+    pub fn key(&self, action: Action) -> Key {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map_or_else(|| action.default_key(), |&(_, k)| k)
+    }
+
+    /// Display name of the key bound to `action`.
+    pub fn label(&self, action: Action) -> String {
+        key_label(self.key(action))
+    }
+}
+
+fn parse_key(spec: &str) -> Option<Key> {
+    if spec.get(..5).is_some_and(|prefix| prefix.eq_ignore_ascii_case("ctrl+")) {
+        return parse_code(&spec[5..]).map(Key::ctrl);
+    }
+    parse_code(spec).map(Key::plain)
+}
+
+fn parse_code(spec: &str) -> Option<KeyCode> {
+    let mut chars = spec.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+    let lower = spec.to_ascii_lowercase();
+    Some(match lower.as_str() {
+        "tab" => KeyCode::Tab,
+        "backtab" | "shift+tab" => KeyCode::BackTab,
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "space" => KeyCode::Char(' '),
+        "backspace" => KeyCode::Backspace,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        f if f.starts_with('f') => KeyCode::F(f[1..].parse().ok().filter(|n| (1..=12).contains(n))?),
+        _ => return None,
+    })
+}
+
+pub fn key_label(key: Key) -> String {
+    if key.ctrl {
+        return format!("Ctrl+{}", code_label(key.code));
+    }
+    code_label(key.code)
+}
+
+fn code_label(code: KeyCode) -> String {
+    match code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::BackTab => "Shift+Tab".to_string(),
+        KeyCode::Enter => "Enter".to_string(),
+        KeyCode::Esc => "Esc".to_string(),
+        KeyCode::Backspace => "Backspace".to_string(),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Right".to_string(),
+        KeyCode::PageUp => "PageUp".to_string(),
+        KeyCode::PageDown => "PageDown".to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        other => format!("{:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_parse_plain_ctrl_and_named() {
+        assert_eq!(parse_key("q"), Some(Key::plain(KeyCode::Char('q'))));
+        assert_eq!(parse_key("é"), Some(Key::plain(KeyCode::Char('é'))));
+        assert_eq!(parse_key("Ctrl+r"), Some(Key::ctrl(KeyCode::Char('r'))));
+        assert_eq!(parse_key("ctrl+PageUp"), Some(Key::ctrl(KeyCode::PageUp)));
+        assert_eq!(parse_code("shift+tab"), Some(KeyCode::BackTab));
+        assert_eq!(parse_code("Space"), Some(KeyCode::Char(' ')));
+        assert_eq!(parse_code("F12"), Some(KeyCode::F(12)));
+        for bad in ["", "ctrl+", "f13", "f0", "fx", "home", "ctrlé", "ctrl+é€", "€€", "ctrl€+q"] {
+            assert_eq!(parse_key(bad), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn config_overrides_keys_and_reports_unknown_entries() {
+        let keys = [("quit", "Q"), ("search", "f2"), ("launch", "l"), ("help", "hyper+h")];
+        let keys = keys.iter().map(|&(a, k)| (a.to_string(), k.to_string())).collect();
+        let (keymap, problems) = Keymap::from_config(&keys);
+        assert_eq!(keymap.key(Action::Quit), Key::plain(KeyCode::Char('Q')));
+        assert_eq!(keymap.key(Action::Search), Key::plain(KeyCode::F(2)));
+        assert_eq!(keymap.key(Action::Help), Action::Help.default_key());
+        assert_eq!(problems, vec![r#"[keys] help: unknown key "hyper+h""#, r#"[keys] unknown action "launch""#]);
+        assert_eq!(keymap.label(Action::Search), "F2");
+    }
+}
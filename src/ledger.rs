@@ -0,0 +1,204 @@
+//! Account balances folded from the trade history.
+//!
+//! `trading_history.csv` is the record of what happened to each account;
+//! `account_summary.csv` holds the accounts themselves and the balance each
+//! opened with. An account's starting balance is its opening one plus the
+//! transfers in the history, and its current balance adds the trades too,
+//! so neither can drift from the history. `Ledger` is the running fold:
+//! the sums per account so far, which a reload that found only appended
+//! rows adds those to instead of folding the whole history again.
+//!
+//! The balance columns are still written to account_summary.csv, as the
+//! fold's snapshot: when the accounts themselves change and after a reload
+//! that found new trades, rather than with each trade. They're rebuilt once
+//! the history is read, and `verify` (`stm verify`, and the startup check)
+//! reports where the stored ones differ. Files from before `opening_amount`
+//! was stored take it as their starting balance less the transfers in the
+//! history.
+
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+use crate::accounts::{self, AccountSummary};
+use crate::ids::AccountId;
+use crate::trades::{self, TradeRecord};
+
+/// How far a stored balance may be from the folded one, for rounding.
+const TOLERANCE: f64 = 1e-6;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Sums {
+    transfers: f64,
+    trades: f64,
+}
+
+/// The history folded so far, per account name in lowercase.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Ledger {
+    sums: HashMap<String, Sums>,
+    folded: usize,
+}
+
+impl Ledger {
+    pub fn new(trades: &[TradeRecord]) -> Self {
+        let mut ledger = Self::default();
+        ledger.fold(trades);
+        ledger
+    }
+
+    /// Adds `trades` to the fold; sums don't depend on order, so appended
+    /// rows that sort between older ones are fine.
+    pub fn fold(&mut self, trades: &[TradeRecord]) {
+        for trade in trades {
+            let sums = self.sums.entry(trade.name.as_str().to_lowercase()).or_default();
+            if trade.transfer.is_some() {
+                sums.transfers += trade.transaction;
+            } else {
+                sums.trades += trade.transaction;
+            }
+        }
+        self.folded += trades.len();
+    }
+
+    /// Whether any trade in the fold is under `name`, in any case.
+    pub fn has_trades(&self, name: &AccountId) -> bool {
+        self.sums.contains_key(&name.as_str().to_lowercase())
+    }
+
+    /// How many trades are in the fold.
+    pub fn folded(&self) -> usize {
+        self.folded
+    }
+
+    /// Sets every account's balance columns from its opening balance and
+    /// the folded history, filling in `opening_amount` where it's missing.
+    pub fn apply(&self, accounts: &mut [AccountSummary]) {
+        for account in accounts {
+            let sums = self.sums.get(&account.name.as_str().to_lowercase()).copied().unwrap_or_default();
+            let opening = *account.opening_amount.get_or_insert(account.initial_amount - sums.transfers);
+            account.initial_amount = opening + sums.transfers;
+            account.current_amount = account.initial_amount + sums.trades;
+            account.change = sums.trades;
+            account.percentage_change =
+                if account.initial_amount != 0.0 { account.change / account.initial_amount * 100.0 } else { 0.0 };
+        }
+    }
+}
+
+/// Where account_summary.csv and the trade history disagree.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Divergence {
+    /// A stored balance column that isn't what the history folds to.
+    Balance { account: AccountId, column: &'static str, stored: f64, folded: f64 },
+    /// Trades under a name no account has, which no balance includes.
+    Orphaned { name: AccountId, trades: usize },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::Balance { account, column, stored, folded } => {
+                write!(f, "{} {}: stored {:.2}, history gives {:.2}", account, column, stored, folded)
+            }
+            Divergence::Orphaned { name, trades } => write!(f, "{}: {} trades but no account", name, trades),
+        }
+    }
+}
+
+/// Checks the `stored` accounts against what `trades` fold to, returning
+/// the folded accounts (the repair) and where they differ.
+pub fn verify(stored: &[AccountSummary], trades: &[TradeRecord]) -> (Vec<AccountSummary>, Vec<Divergence>) {
+    let mut folded = stored.to_vec();
+    Ledger::new(trades).apply(&mut folded);
+    let mut found = Vec::new();
+    for (before, after) in stored.iter().zip(&folded) {
+        let columns = [
+            ("initial_amount", before.initial_amount, after.initial_amount),
+            ("current_amount", before.current_amount, after.current_amount),
+            ("change", before.change, after.change),
+            ("percentage_change", before.percentage_change, after.percentage_change),
+        ];
+        found.extend(columns.into_iter().filter(|(_, stored, folded)| (stored - folded).abs() > TOLERANCE).map(
+            |(column, stored, folded)| Divergence::Balance { account: before.name.clone(), column, stored, folded },
+        ));
+    }
+    let mut orphaned: BTreeMap<String, (AccountId, usize)> = BTreeMap::new();
+    for trade in trades {
+        let key = trade.name.as_str().to_lowercase();
+        if !stored.iter().any(|a| a.name.as_str().to_lowercase() == key) {
+            orphaned.entry(key).or_insert_with(|| (trade.name.clone(), 0)).1 += 1;
+        }
+    }
+    found.extend(orphaned.into_values().map(|(name, trades)| Divergence::Orphaned { name, trades }));
+    (folded, found)
+}
+
+/// The accounts in `accounts_path` and the trades in `trades_path`, for the
+/// command-line tools. A missing history is an empty one.
+pub fn read(accounts_path: &str, trades_path: &str) -> Result<(Vec<AccountSummary>, Vec<TradeRecord>), Box<dyn Error>> {
+    let accounts = accounts::read_accounts_from_csv(accounts_path)?;
+    let trades = if Path::new(trades_path).exists() { trades::read_trades_from_csv(trades_path)? } else { Vec::new() };
+    Ok((accounts, trades))
+}
+
+/// The accounts in `accounts_path` with their balances folded from the
+/// history at `trades_path`.
+pub fn load(accounts_path: &str, trades_path: &str) -> Result<Vec<AccountSummary>, Box<dyn Error>> {
+    let (mut accounts, trades) = read(accounts_path, trades_path)?;
+    Ledger::new(&trades).apply(&mut accounts);
+    Ok(accounts)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn balances_follow_the_history_not_the_stored_columns() {
+        let at = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap().and_hms_opt(9, 30, 0).unwrap();
+        let mut accounts: Vec<AccountSummary> = [("Alice", 10.0), ("Bob", 20.0)]
+            .iter()
+            .map(|&(name, amount)| AccountSummary::new(AccountId::parse(name).unwrap(), amount, "USD"))
+            .collect();
+        let mut history = vec![accounts::process_trade(&mut accounts, &AccountId::parse("Alice").unwrap(), 5.0, at).unwrap()];
+        let legs = accounts::process_transfer(
+            &mut accounts,
+            &AccountId::parse("Alice").unwrap(),
+            &AccountId::parse("Bob").unwrap(),
+            4.0,
+            at,
+        )
+        .unwrap();
+        history.extend(legs.iter().map(|leg| TradeRecord { transfer: Some(1), ..leg.clone() }));
+        let expected = accounts.clone();
+
+        // A summary that drifted is put back in line with the history.
+        let mut drifted = accounts.clone();
+        drifted[0].current_amount = 99.0;
+        drifted[1].opening_amount = None;
+        let mut ledger = Ledger::new(&history[..1]);
+        ledger.fold(&history[1..]);
+        assert_eq!(ledger.folded(), 3);
+        ledger.apply(&mut drifted);
+        assert_eq!(drifted, expected);
+        assert_eq!((drifted[0].initial_amount, drifted[0].current_amount, drifted[0].change), (6.0, 11.0, 5.0));
+
+        let mut stale = expected.clone();
+        stale[0].current_amount = 99.0;
+        history.push(TradeRecord { name: AccountId::parse("Carol").unwrap(), ..history[0].clone() });
+        let (repaired, found) = verify(&stale, &history);
+        assert_eq!(repaired, expected);
+        assert_eq!(
+            found,
+            vec![
+                Divergence::Balance { account: AccountId::parse("Alice").unwrap(), column: "current_amount", stored: 99.0, folded: 11.0 },
+                Divergence::Orphaned { name: AccountId::parse("Carol").unwrap(), trades: 1 },
+            ]
+        );
+        assert!(verify(&expected, &history[..3]).1.is_empty());
+    }
+}
@@ -0,0 +1,66 @@
+//! Stock trading dashboard: account and trade bookkeeping, the price data
+//! download_stock.py fetches, and the terminal UI built on them.
+//!
+//! The `stock_trading_tui` binary runs the dashboard; other tools (see
+//! test/account.rs) share the `accounts`, `trades`, `data` and `prices`
+//! modules.
+
+pub mod accounts;
+pub mod alerts;
+pub mod analytics;
+pub mod app;
+pub mod axis;
+pub mod broker;
+pub mod bundle;
+pub mod calendar;
+pub mod capabilities;
+pub mod changelog;
+pub mod chaos;
+pub mod chart_image;
+pub mod cli;
+pub mod config;
+pub mod daemon;
+pub mod data;
+pub mod date_range;
+pub mod dividends;
+pub mod drawings;
+pub mod downloads;
+pub mod effects;
+pub mod errors;
+pub mod events;
+pub mod export;
+pub mod frame_cache;
+pub mod fx;
+pub mod game;
+pub mod health;
+pub mod hints;
+pub mod ids;
+pub mod jobs;
+pub mod keymap;
+pub mod ledger;
+pub mod market;
+pub mod markets;
+pub mod menu;
+pub mod ml;
+pub mod net;
+pub mod news;
+pub mod number_input;
+pub mod overview;
+pub mod prices;
+pub mod recovery;
+pub mod refresh;
+pub mod retention;
+pub mod returns;
+pub mod risk;
+pub mod session;
+pub mod sizing;
+pub mod splits;
+pub mod stats;
+pub mod strategy;
+pub mod tax;
+pub mod theme;
+pub mod time_travel;
+pub mod trades;
+pub mod ui;
+pub mod universe;
+pub mod update;
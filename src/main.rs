@@ -1,183 +1,105 @@
+use std::collections::VecDeque;
 use std::error::Error;
-use std::fs;
 use std::io;
-use std::process::Command;
-use std::time::Duration;
+use std::process;
+use std::time::{Duration, Instant};
 
-use csv::ReaderBuilder;
-use tui::{
-    backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
-    style::{Color},
-    widgets::{Block, Borders, Paragraph, Row, Table},
-    Terminal,
-};
-use tui::widgets::canvas::{Canvas, Line};
+use tui::{backend::CrosstermBackend, layout::Rect, Terminal};
 
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 
-use serde::Deserialize;
+use stock_trading_tui::accounts::read_accounts_from_csv;
+use stock_trading_tui::app::{App, AppEvent};
+use stock_trading_tui::capabilities::{self, Capabilities};
+use stock_trading_tui::drawings::{Drawings, DRAWINGS_PATH};
+use stock_trading_tui::errors::AppError;
+use stock_trading_tui::frame_cache::FrameCache;
+use stock_trading_tui::health::HealthReport;
+use stock_trading_tui::market::provider;
+use stock_trading_tui::market::symbols::{SymbolBook, SYMBOLS_PATH};
+use stock_trading_tui::refresh::Source;
+use stock_trading_tui::{cli, config, downloads, effects, markets, net, recovery, session, stats, ui, universe};
 
 // ============================
-// CSV Structures and Functions
+// Main TUI Application
 // ============================
-#[derive(Debug, Deserialize)]
-struct AccountSummary {
-    name: String,
-    initial_amount: f64,
-    current_amount: f64,
-    change: f64,
-    percentage_change: f64,
-}
-
-#[derive(Debug, Deserialize)]
-struct TradeRecord {
-    name: String,
-    transaction: f64,
-    new_balance: f64,
-}
-
-fn read_accounts_from_csv(path: &str) -> Result<Vec<AccountSummary>, Box<dyn Error>> {
-    let mut rdr = ReaderBuilder::new().from_path(path)?;
-    let mut records = Vec::new();
-    for result in rdr.deserialize() {
-        let rec: AccountSummary = result?;
-        records.push(rec);
-    }
-    Ok(records)
-}
-
-fn read_trades_from_csv(path: &str) -> Result<Vec<TradeRecord>, Box<dyn Error>> {
-    let mut rdr = ReaderBuilder::new().from_path(path)?;
-    let mut trades = Vec::new();
-    for result in rdr.deserialize() {
-        let rec: TradeRecord = result?;
-        trades.push(rec);
+fn main() -> Result<(), Box<dyn Error>> {
+    match cli::parse(std::env::args().skip(1)) {
+        Ok(None) => {}
+        Ok(Some(command)) => headless(command),
+        // Prints the help, or the error and usage, and exits.
+        Err(err) => err.exit(),
     }
-    Ok(trades)
-}
 
-// ============================
-// Stock Data for ML List
-// ============================
-#[derive(Debug)]
-enum MLMode {
-    List,
-    Search,
-}
+    // Failures before the TUI is up are kept for the Errors view; printing
+    // them would be hidden by the alternate screen.
+    let mut startup_errors = Vec::new();
 
-#[derive(Debug)]
-struct StockInfo {
-    ticker: String,
-    price: f64,
-    change: f64,
-    pct_change: f64,
-}
-
-fn get_stock_info(file_path: &str, ticker: &str) -> Option<StockInfo> {
-    // Expects a Yahoo Finance CSV with header; "Close" is at index 4.
-    let mut rdr = ReaderBuilder::new().from_path(file_path).ok()?;
-    let mut close_prices = Vec::new();
-    for result in rdr.records() {
-        if let Ok(record) = result {
-            if let Some(close_str) = record.get(1) {
-                if let Ok(close) = close_str.parse::<f64>() {
-                    close_prices.push(close);
-                }
-            }
+    let (config, config_error) = match config::load(config::CONFIG_PATH) {
+        Ok(config) => (config, None),
+        Err(err) => {
+            let message = err.to_string();
+            startup_errors.push(AppError::load(config::CONFIG_PATH, err));
+            (config::Config::default(), Some(message))
         }
-    }
-    if close_prices.len() >= 2 {
-        let last = *close_prices.last()?;
-        let prev = close_prices[close_prices.len()-2];
-        let change = last - prev;
-        let pct_change = if prev != 0.0 { change / prev * 100.0 } else { 0.0 };
-        Some(StockInfo {
-            ticker: ticker.to_string(),
-            price: last,
-            change,
-            pct_change,
-        })
-    } else {
-        None
-    }
-}
+    };
+
+    net::init(&config.net);
+    downloads::init(&config.downloads);
+    provider::init(&config.data);
+    universe::init(&config.data);
+    markets::init(&config.markets);
+
+    // Checked before the terminal is taken over, so a slow probe shows as a
+    // pause at the prompt rather than a blank screen.
+    let health = config.health.enabled.then(|| {
+        eprintln!("Checking setup…");
+        HealthReport::run(config_error.as_deref(), Duration::from_secs(config.net.connect_timeout_secs))
+    });
 
-fn load_stocks() -> Vec<StockInfo> {
-    let mut stocks = Vec::new();
-    let dir = "pre_stock";
-    if let Ok(entries) = fs::read_dir(dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_file() {
-                if let Some(ext) = path.extension() {
-                    if ext == "csv" {
-                        if let Some(ticker_os) = path.file_stem() {
-                            if let Some(ticker) = ticker_os.to_str() {
-                                if let Some(info) = get_stock_info(path.to_str().unwrap(), ticker) {
-                                    stocks.push(info);
-                                } else {
-                                    stocks.push(StockInfo {
-                                        ticker: ticker.to_string(),
-                                        price: 0.0,
-                                        change: 0.0,
-                                        pct_change: 0.0,
-                                    });
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    capabilities::init(Capabilities::detect(&config, health.as_ref()));
+
+    let mut app = App::new(&config);
+    // Read by the first reload rather than here, so a large pre_stock/
+    // doesn't keep the dashboard from showing; the prediction history
+    // waits for whatever shows it.
+    app.load_started(Source::Stocks);
+    // Accounts are only reloaded on request, so they're read up front.
+    match read_accounts_from_csv("account_summary.csv") {
+        Ok(accounts) => app.accounts = accounts,
+        Err(err) => app.load_failed(Source::Accounts, AppError::load("account_summary.csv", err)),
     }
-    stocks
-}
-
-// ============================
-// App State
-// ============================
-struct App {
-    stocks: Vec<StockInfo>,
-    selected: usize,
-    ml_mode: MLMode,
-    search_input: String,
-    show_instructions: bool,
-    ml_output: String,
-    accounts: Vec<AccountSummary>,
-}
-
-impl App {
-    fn new() -> Self {
-        Self {
-            stocks: Vec::new(),
-            selected: 0,
-            ml_mode: MLMode::List,
-            search_input: String::new(),
-            show_instructions: false,
-            ml_output: String::new(),
-            accounts: Vec::new(),
-        }
+    app.symbols = SymbolBook::load(SYMBOLS_PATH).unwrap_or_else(|err| {
+        startup_errors.push(AppError::load(SYMBOLS_PATH, err));
+        SymbolBook::default()
+    });
+    app.drawings = Drawings::load(DRAWINGS_PATH).unwrap_or_else(|err| {
+        startup_errors.push(AppError::load(DRAWINGS_PATH, err));
+        Drawings::default()
+    });
+    match recovery::load(recovery::RECOVERY_PATH) {
+        Ok(Some(drafts)) => app.restore_drafts(drafts),
+        Ok(None) => {}
+        Err(err) => startup_errors.push(AppError::load(recovery::RECOVERY_PATH, err)),
     }
-}
-
-// ============================
-// Main TUI Application
-// ============================
-fn main() -> Result<(), Box<dyn Error>> {
-    // Load account summary data from CSV
-    let accounts = read_accounts_from_csv("account_summary.csv").unwrap_or_else(|err| {
-        eprintln!("Warning: could not read account_summary.csv: {}", err);
-        Vec::new()
+    match session::load(session::SESSION_PATH) {
+        Ok(Some(session)) => app.restore_session(session),
+        Ok(None) => {}
+        Err(err) => startup_errors.push(AppError::load(session::SESSION_PATH, err)),
+    }
+    app.usage = stats::load(stats::STATS_PATH).unwrap_or_else(|err| {
+        startup_errors.push(AppError::load(stats::STATS_PATH, err));
+        stats::UsageStats::default()
     });
-
-    let mut app = App::new();
-    app.stocks = load_stocks();
-    app.accounts = accounts;
+    app.usage.start_session();
+    app.health = health.filter(|report| report.needs_attention() || config.health.always_show);
+    for err in startup_errors {
+        app.errors.push(err);
+    }
 
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -201,250 +123,134 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Runs a command without the dashboard and exits.
+fn headless(command: cli::Command) -> ! {
+    let config = config::load(config::CONFIG_PATH).unwrap_or_else(|err| {
+        eprintln!("Using default settings: {}: {}", config::CONFIG_PATH, err);
+        config::Config::default()
+    });
+    net::init(&config.net);
+    downloads::init(&config.downloads);
+    provider::init(&config.data);
+    universe::init(&config.data);
+    markets::init(&config.markets);
+    match cli::run(command, &config) {
+        Ok(()) => process::exit(0),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
 fn run_app<B: tui::backend::Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    let mut last_autosave = Instant::now();
+    let mut saved_drafts = app.drafts();
+    let mut frames = FrameCache::default();
     loop {
-        // Refresh stocks list each loop
-        app.stocks = load_stocks();
-
-        terminal.draw(|f| {
-            let size = f.size();
-
-            if app.show_instructions {
-                let instructions = "\
-Instructions:
- - Up/Down: Navigate ML stock list
- - Enter (List mode): Preprocess & train on selected stock
- - s: Activate search box
- - In Search mode: Type ticker and press Enter to download data
- - Esc (in Search mode): Cancel search
- - h: Toggle instructions overlay
- - q: Quit";
-                let block = Block::default().title("Instructions").borders(Borders::ALL);
-                let paragraph = Paragraph::new(instructions).block(block);
-                f.render_widget(paragraph, size);
-                return;
+        // Autosave unfinished input so a crash or dropped terminal doesn't
+        // lose it, along with usage counts. Failures are retried on the next
+        // interval.
+        if last_autosave.elapsed() >= recovery::AUTOSAVE_INTERVAL {
+            last_autosave = Instant::now();
+            let drafts = app.drafts();
+            if drafts != saved_drafts && recovery::save(recovery::RECOVERY_PATH, &drafts).is_ok() {
+                saved_drafts = drafts;
             }
+            if let Err(err) = stats::save(stats::STATS_PATH, &mut app.usage) {
+                app.errors.push(AppError::save(stats::STATS_PATH, err));
+            }
+        }
 
-            // Main vertical layout: Top (50%), Middle (30%), Bottom (20%)
-            let vertical_chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .margin(1)
-                .constraints([
-                    Constraint::Percentage(50),
-                    Constraint::Percentage(30),
-                    Constraint::Percentage(20),
-                ].as_ref())
-                .split(size);
-
-            // Top panel: split horizontally into Left (Stock Chart) and Right (Live Trades)
-            let top_chunks = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([
-                    Constraint::Percentage(70),
-                    Constraint::Percentage(30),
-                ].as_ref())
-                .split(vertical_chunks[0]);
-
-            // Top Left: Stock Chart (dummy line chart)
-            let data = vec![
-                (0.0, 100.0),
-                (1.0, 102.5),
-                (2.0, 105.0),
-                (3.0, 103.0),
-                (4.0, 107.0),
-                (5.0, 106.0),
-                (6.0, 110.0),
-            ];
-            let (x_min, x_max) = data.iter().fold((f64::MAX, f64::MIN), |(mn, mx), &(x,_)| (mn.min(x), mx.max(x)));
-            let (y_min, y_max) = data.iter().fold((f64::MAX, f64::MIN), |(mn, mx), &(_, y)| (mn.min(y), mx.max(y)));
-            let line_segments = data.windows(2).map(|pair| {
-                let (x1, y1) = pair[0];
-                let (x2, y2) = pair[1];
-                Line { x1, y1, x2, y2, color: Color::Green }
-            });
-            let chart = Canvas::default()
-                .block(Block::default().title("Stock Chart").borders(Borders::ALL))
-                .x_bounds([x_min - 0.5, x_max + 0.5])
-                .y_bounds([y_min - 2.0, y_max + 2.0])
-                .paint(move |ctx| {
-                    for seg in line_segments.clone() {
-                        ctx.draw(&seg);
-                    }
-                });
-            f.render_widget(chart, top_chunks[0]);
-
-            // Top Right: Live Trades from trading_history.csv
-            let trades = read_trades_from_csv("trading_history.csv").unwrap_or_else(|_| Vec::new());
-            let live_trades_text = trades.iter().map(|t| {
-                format!("{}  {:.2}  {:.2}", t.name, t.transaction, t.new_balance)
-            }).collect::<Vec<_>>().join("\n");
-            let live_trades = Paragraph::new(live_trades_text)
-                .block(Block::default().title("Live Trades").borders(Borders::ALL));
-            f.render_widget(live_trades, top_chunks[1]);
-
-            // Middle: Account Summary Table
-            let rows: Vec<Row> = app.accounts.iter().map(|acc| {
-                Row::new(vec![
-                    acc.name.clone(),
-                    format!("{:.2}", acc.initial_amount),
-                    format!("{:.2}", acc.current_amount),
-                    format!("{:.2}", acc.change),
-                    format!("{:.2}%", acc.percentage_change),
-                ])
-            }).collect();
-            let table = Table::new(rows)
-                .header(
-                    Row::new(vec!["Name", "Initial", "Current", "Change", "% Change"])
-                        .bottom_margin(1),
-                )
-                .block(Block::default().title("Account Summary").borders(Borders::ALL))
-                .widths(&[
-                    Constraint::Length(10),
-                    Constraint::Length(10),
-                    Constraint::Length(10),
-                    Constraint::Length(10),
-                    Constraint::Length(10),
-                ]);
-            f.render_widget(table, vertical_chunks[1]);
-
-            // Bottom: Split horizontally into ML List and Search Box
-            let bottom_chunks = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([
-                    Constraint::Percentage(70),
-                    Constraint::Percentage(30),
-                ].as_ref())
-                .split(vertical_chunks[2]);
-
-            // Bottom Left: ML List of available stocks from pre_stock/
-            let ml_list_text = app.stocks.iter().enumerate().map(|(i, s)| {
-                let marker = if i == app.selected { ">" } else { " " };
-                format!("{} {}  {:.2}  {:.2} ({:.2}%)", marker, s.ticker, s.price, s.change, s.pct_change)
-            }).collect::<Vec<String>>().join("\n");
-            let ml_list = Paragraph::new(ml_list_text)
-                .block(Block::default().title("ML List").borders(Borders::ALL));
-            f.render_widget(ml_list, bottom_chunks[0]);
+        // Live quotes are overlaid as reloaded stock lists come in.
+        app.poll_feeds();
 
-            // Bottom Right: Search Box (always visible)
-            let search_text = format!("Search Ticker: {}\n\n{}", app.search_input, app.ml_output);
-            let search_box = Paragraph::new(search_text)
-                .block(Block::default().title("Search").borders(Borders::ALL));
-            f.render_widget(search_box, bottom_chunks[1]);
-        })?;
+        if let Some(view) = &mut app.overview {
+            let area = ui::overview_area(terminal.size()?);
+            view.fit(area.width, area.height);
+        }
+        terminal.draw(|f| ui::draw(f, app, &mut frames))?;
+        app.trades_page = ui::trades_page_rows(app, terminal.size()?);
 
-        // Event handling
+        // Event handling: feed the key or mouse event through the reducer and run whatever
+        // effects it asks for until nothing is left pending.
+        let mut pending = VecDeque::new();
         if event::poll(Duration::from_millis(300))? {
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char('q') => break,
-                    KeyCode::Char('h') => {
-                        app.show_instructions = !app.show_instructions;
-                    }
-                    KeyCode::Char('s') => {
-                        app.ml_mode = MLMode::Search;
-                        app.search_input.clear();
-                    }
-                    KeyCode::Esc => {
-                        app.ml_mode = MLMode::List;
-                        app.search_input.clear();
-                    }
-                    KeyCode::Enter => {
-                        if let MLMode::Search = app.ml_mode {
-                            // In search mode, download stock data.
-                            let ticker = app.search_input.trim().to_uppercase();
-                            if !ticker.is_empty() {
-                                let output_dl = Command::new("python3")
-                                    .arg("download_stock.py")
-                                    .arg(&ticker)
-                                    .output();
-                                match output_dl {
-                                    Ok(o) if o.status.success() => {
-                                        app.ml_output = format!("Downloaded data for {}", ticker);
-                                    }
-                                    Ok(o) => {
-                                        let err = String::from_utf8_lossy(&o.stderr);
-                                        app.ml_output = format!("Download error: {}", err.trim());
-                                    }
-                                    Err(e) => {
-                                        app.ml_output = format!("Failed to run download_stock.py: {}", e);
-                                    }
-                                }
-                                app.ml_mode = MLMode::List;
-                                app.search_input.clear();
-                                app.stocks = load_stocks();
-                            }
-                        } else {
-                            // In list mode, run preprocess & model on selected stock.
-                            if let Some(stock) = app.stocks.get(app.selected) {
-                                let csv_file = format!("pre_stock/{}.csv", stock.ticker);
-                                let output_pre = Command::new("python3")
-                                    .arg("ml/preprocess.py")
-                                    .arg(&csv_file)
-                                    .output();
-                                match output_pre {
-                                    Ok(o) if o.status.success() => {
-                                        app.ml_output = format!("Preprocess OK for {}", stock.ticker);
-                                    }
-                                    Ok(o) => {
-                                        let err = String::from_utf8_lossy(&o.stderr);
-                                        app.ml_output = format!("Preprocess error: {}", err.trim());
-                                    }
-                                    Err(e) => {
-                                        app.ml_output = format!("Failed to run preprocess.py: {}", e);
-                                    }
-                                }
-                                let output_model = Command::new("python3")
-                                    .arg("ml/model.py")
-                                    .output();
-                                match output_model {
-                                    Ok(o) if o.status.success() => {
-                                        let pred = String::from_utf8_lossy(&o.stdout);
-                                        app.ml_output = format!("ML Prediction for {}: {}", stock.ticker, pred.trim());
-                                    }
-                                    Ok(o) => {
-                                        let err = String::from_utf8_lossy(&o.stderr);
-                                        app.ml_output = format!("Model error: {}", err.trim());
-                                    }
-                                    Err(e) => {
-                                        app.ml_output = format!("Failed to run model.py: {}", e);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    KeyCode::Down => {
-                        if let MLMode::List = app.ml_mode {
-                            if !app.stocks.is_empty() {
-                                app.selected = (app.selected + 1) % app.stocks.len();
-                            }
-                        }
-                    }
-                    KeyCode::Up => {
-                        if let MLMode::List = app.ml_mode {
-                            if !app.stocks.is_empty() {
-                                if app.selected == 0 {
-                                    app.selected = app.stocks.len() - 1;
-                                } else {
-                                    app.selected -= 1;
-                                }
-                            }
-                        }
-                    }
-                    KeyCode::Char(c) => {
-                        if let MLMode::Search = app.ml_mode {
-                            app.search_input.push(c);
-                        }
-                    }
-                    KeyCode::Backspace => {
-                        if let MLMode::Search = app.ml_mode {
-                            app.search_input.pop();
-                        }
-                    }
-                    _ => {}
+            match event::read()? {
+                Event::Key(key) => pending.push_back(AppEvent::Key(key.into())),
+                Event::Mouse(mouse) => pending.extend(ui::mouse_event(app, terminal.size()?, mouse)),
+                // Redraw at the new size straight away rather than after
+                // this frame's jobs and reloads.
+                Event::Resize(width, height) => {
+                    terminal.resize(Rect::new(0, 0, width, height))?;
+                    continue;
                 }
             }
         }
+        // Events from finished background jobs, reloads and broker fills go
+        // through the same path.
+        pending.extend(app.jobs.poll());
+        pending.extend(app.loader.poll());
+        pending.extend(app.poll_broker());
+        // Last, so reloads see the selection after this frame's keys.
+        pending.push_back(AppEvent::Tick);
+        while let Some(event) = pending.pop_front() {
+            for effect in app.handle_event(event) {
+                pending.extend(effects::run(effect, &mut app.jobs, &app.loader));
+            }
+        }
+        if app.suspend_requested {
+            app.suspend_requested = false;
+            // A stopped job is often killed rather than resumed, so save
+            // what a quit would before stopping.
+            let drafts = app.drafts();
+            if recovery::save(recovery::RECOVERY_PATH, &drafts).is_ok() {
+                saved_drafts = drafts;
+            }
+            let _ = stats::save(stats::STATS_PATH, &mut app.usage);
+            let _ = session::save(session::SESSION_PATH, &app.session());
+            suspend(terminal)?;
+        }
+        if app.should_quit {
+            let _ = recovery::clear(recovery::RECOVERY_PATH);
+            let _ = stats::save(stats::STATS_PATH, &mut app.usage);
+            let _ = session::save(session::SESSION_PATH, &app.session());
+            break;
+        }
     }
     Ok(())
 }
 
+/// Hands the terminal back to the shell and stops, as Ctrl+Z does in a
+/// cooked terminal; raw mode turns that off, so the app asks for it. Once
+/// `fg` continues the process the screen is set up again and repainted in
+/// full, at whatever size the terminal is now. The screen is set up again
+/// even if leaving it or stopping failed, so the app never keeps running
+/// in a cooked terminal.
+#[cfg(unix)]
+fn suspend<B: tui::backend::Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
+    let stopped = disable_raw_mode()
+        .and_then(|()| execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture))
+        .and_then(|()| terminal.show_cursor())
+        .and_then(|()| {
+            // Returns after the process has been stopped and continued, or
+            // at once if the shell has no job control and the stop is
+            // ignored.
+            // SAFETY: raise only sends a signal to this thread.
+            match unsafe { libc::raise(libc::SIGTSTP) } {
+                0 => Ok(()),
+                _ => Err(io::Error::last_os_error()),
+            }
+        });
+    let restored = enable_raw_mode()
+        .and_then(|()| execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture))
+        .and_then(|()| terminal.hide_cursor())
+        .and_then(|()| terminal.clear());
+    stopped.and(restored)
+}
+
+/// Without job control there's nothing to suspend to.
+#[cfg(not(unix))]
+fn suspend<B: tui::backend::Backend>(_terminal: &mut Terminal<B>) -> io::Result<()> {
+    Ok(())
+}
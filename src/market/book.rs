@@ -0,0 +1,67 @@
+//! Bid/ask depth for the order book panel.
+//!
+//! Kept outside the `streaming` feature so the panel can be drawn, and
+//! explain why it's empty, in any build. Levels are ordered best first:
+//! highest bid, lowest ask. Providers that only send the quote at the top of
+//! the book produce one level a side.
+
+use chrono::{DateTime, Local};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Level {
+    pub price: f64,
+    pub size: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderBook {
+    pub bids: Vec<Level>,
+    pub asks: Vec<Level>,
+    pub at: DateTime<Local>,
+}
+
+impl OrderBook {
+    /// Best ask minus best bid, if both sides are quoted.
+    pub fn spread(&self) -> Option<f64> {
+        Some(self.asks.first()?.price - self.bids.first()?.price)
+    }
+
+    pub fn mid(&self) -> Option<f64> {
+        Some((self.asks.first()?.price + self.bids.first()?.price) / 2.0)
+    }
+
+    /// Largest size on either side, to scale the depth bars by.
+    pub fn max_size(&self) -> f64 {
+        self.bids.iter().chain(&self.asks).map(|l| l.size).fold(0.0, f64::max)
+    }
+
+    /// Share of the shown size that is bids, from 0 to 1; `None` when both
+    /// sides are empty.
+    pub fn imbalance(&self) -> Option<f64> {
+        let bids: f64 = self.bids.iter().map(|l| l.size).sum();
+        let asks: f64 = self.asks.iter().map(|l| l.size).sum();
+        (bids + asks > 0.0).then(|| bids / (bids + asks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spread_mid_and_imbalance_use_both_sides() {
+        let level = |price, size| Level { price, size };
+        let book = OrderBook {
+            bids: vec![level(99.5, 300.0), level(99.0, 100.0)],
+            asks: vec![level(100.5, 200.0), level(101.0, 400.0)],
+            at: Local::now(),
+        };
+        assert_eq!(book.spread(), Some(1.0));
+        assert_eq!(book.mid(), Some(100.0));
+        assert_eq!(book.max_size(), 400.0);
+        assert_eq!(book.imbalance(), Some(0.4));
+        let one_sided = OrderBook { asks: Vec::new(), ..book };
+        assert_eq!(one_sided.spread(), None);
+        assert_eq!(one_sided.imbalance(), Some(1.0));
+    }
+}
@@ -0,0 +1,258 @@
+//! Live quote polling.
+//!
+//! A background thread polls the configured quote endpoint for every ticker
+//! in the watchlist and sends results over a channel; the UI thread drains
+//! the channel each frame and overlays the quotes on the CSV-derived prices.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use serde_json::Value;
+
+use crate::chaos;
+use crate::net::cache::HttpCache;
+use crate::ids::Ticker;
+use crate::config::{LiveConfig, QuoteProvider};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Quote {
+    pub price: f64,
+    pub change: f64,
+    pub pct_change: f64,
+}
+
+enum Update {
+    Quote(Ticker, Quote),
+    Error(Ticker, String),
+}
+
+/// Handle to the polling thread plus the latest state it reported.
+pub struct LiveFeed {
+    provider: QuoteProvider,
+    stale_after: Duration,
+    tickers: Arc<Mutex<Vec<Ticker>>>,
+    rx: Receiver<Update>,
+    pub quotes: HashMap<Ticker, Quote>,
+    pub last_update: Option<DateTime<Local>>,
+    pub last_error: Option<String>,
+}
+
+impl LiveFeed {
+    /// Starts polling in the background. Returns `None` when live quotes are
+    /// disabled in the config.
+    pub fn start(config: &LiveConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        let tickers = Arc::new(Mutex::new(Vec::new()));
+        let (tx, rx) = mpsc::channel();
+        let shared = Arc::clone(&tickers);
+        let poll = config.clone();
+        thread::spawn(move || poll_loop(poll, shared, tx));
+        Some(Self {
+            provider: config.provider,
+            stale_after: Duration::from_secs(config.stale_after_secs),
+            tickers,
+            rx,
+            quotes: HashMap::new(),
+            last_update: None,
+            last_error: None,
+        })
+    }
+
+    /// Replaces the set of tickers polled on the next round.
+    pub fn set_tickers(&self, tickers: Vec<Ticker>) {
+        if let Ok(mut shared) = self.tickers.lock() {
+            *shared = tickers;
+        }
+    }
+
+    /// Drains pending updates from the polling thread without blocking,
+    /// returning the errors among them.
+    pub fn poll(&mut self) -> Vec<String> {
+        let mut errors = Vec::new();
+        while let Ok(update) = self.rx.try_recv() {
+            match update {
+                Update::Quote(ticker, quote) => {
+                    self.quotes.insert(ticker, quote);
+                    self.last_update = Some(Local::now());
+                    self.last_error = None;
+                }
+                Update::Error(ticker, err) => {
+                    let err = format!("{}: {}", ticker, err);
+                    errors.push(err.clone());
+                    self.last_error = Some(err);
+                }
+            }
+        }
+        errors
+    }
+
+    /// True when no quote has arrived within the configured window.
+    pub fn is_stale(&self) -> bool {
+        match self.last_update {
+            Some(at) => (Local::now() - at).to_std().unwrap_or_default() > self.stale_after,
+            None => true,
+        }
+    }
+
+    /// One-line status for the header: provider, last update, warnings.
+    pub fn status(&self) -> String {
+        let provider = self.provider.name();
+        match (self.last_update, &self.last_error) {
+            (None, Some(err)) => format!("Live {}: {}", provider, err),
+            (None, None) => format!("Live {}: waiting for first quote", provider),
+            (Some(at), err) => {
+                let mut line = format!("Live {}: {}", provider, at.format("%H:%M:%S"));
+                if self.is_stale() {
+                    line.push_str(" STALE");
+                }
+                if let Some(err) = err {
+                    line.push_str(&format!(" ({})", err));
+                }
+                line
+            }
+        }
+    }
+}
+
+fn poll_loop(config: LiveConfig, tickers: Arc<Mutex<Vec<Ticker>>>, tx: Sender<Update>) {
+    let interval = Duration::from_secs(config.interval_secs.max(1));
+    let mut cache = HttpCache::default();
+    loop {
+        let round = tickers.lock().map(|t| t.clone()).unwrap_or_default();
+        for ticker in round {
+            let update = match fetch_quote(&config, &ticker, &mut cache) {
+                Ok(quote) => Update::Quote(ticker, quote),
+                Err(e) => Update::Error(ticker, redact(&e.to_string(), &config.api_key)),
+            };
+            if tx.send(update).is_err() {
+                return; // UI has gone away
+            }
+        }
+        thread::sleep(interval);
+    }
+}
+
+fn fetch_quote(config: &LiveConfig, ticker: &Ticker, cache: &mut HttpCache) -> Result<Quote, Box<dyn Error>> {
+    match config.provider {
+        QuoteProvider::Yahoo => {
+            let url = format!(
+                "https://query1.finance.yahoo.com/v8/finance/chart/{}?range=1d&interval=1d",
+                ticker
+            );
+            let body: Value = get_json(cache, &url, &[])?;
+            let meta = &body["chart"]["result"][0]["meta"];
+            let price = number(&meta["regularMarketPrice"])?;
+            let prev = number(&meta["chartPreviousClose"])?;
+            Ok(quote_from_prev(price, prev))
+        }
+        QuoteProvider::Finnhub => {
+            let url = format!("https://finnhub.io/api/v1/quote?symbol={}", finnhub_symbol(ticker));
+            let body: Value = get_json(cache, &url, &[("X-Finnhub-Token", &config.api_key)])?;
+            Ok(Quote {
+                price: number(&body["c"])?,
+                change: number(&body["d"])?,
+                pct_change: number(&body["dp"])?,
+            })
+        }
+        QuoteProvider::AlphaVantage if ticker.is_crypto() => {
+            Err("Alpha Vantage has no crypto quotes; use the yahoo or finnhub provider".into())
+        }
+        QuoteProvider::AlphaVantage => {
+            let url = format!(
+                "https://www.alphavantage.co/query?function=GLOBAL_QUOTE&symbol={}&apikey={}",
+                ticker, config.api_key
+            );
+            let body: Value = get_json(cache, &url, &[])?;
+            let quote = &body["Global Quote"];
+            Ok(Quote {
+                price: number(&quote["05. price"])?,
+                change: number(&quote["09. change"])?,
+                pct_change: number(&quote["10. change percent"])?,
+            })
+        }
+    }
+}
+
+/// Finnhub's name for `ticker`: crypto pairs are quoted from Binance, with
+/// USD pairs against Tether (`BTC-USD` is `BINANCE:BTCUSDT`).
+pub(crate) fn finnhub_symbol(ticker: &Ticker) -> String {
+    match ticker.crypto_pair() {
+        Some((base, "USD")) => format!("BINANCE:{}USDT", base),
+        Some((base, quote)) => format!("BINANCE:{}{}", base, quote),
+        None => ticker.to_string(),
+    }
+}
+
+fn get_json(cache: &mut HttpCache, url: &str, headers: &[(&str, &str)]) -> Result<Value, Box<dyn Error>> {
+    chaos::inject("quote fetch")?;
+    Ok(serde_json::from_str(&cache.get(url, headers)?)?)
+}
+
+/// `message` with `key` blanked out. Request errors quote the URL, which
+/// carries the key for Alpha Vantage, and they end up in the Errors view.
+fn redact(message: &str, key: &str) -> String {
+    if key.is_empty() {
+        return message.to_string();
+    }
+    message.replace(key, "***")
+}
+
+/// Accepts JSON numbers and numeric strings (Alpha Vantage quotes every
+/// field, with a trailing `%` on percentages).
+fn number(value: &Value) -> Result<f64, Box<dyn Error>> {
+    match value {
+        Value::Number(n) => n.as_f64().ok_or_else(|| "invalid number".into()),
+        Value::String(s) => Ok(s.trim_end_matches('%').parse()?),
+        _ => Err("missing field in quote response".into()),
+    }
+}
+
+pub(crate) fn quote_from_prev(price: f64, prev: f64) -> Quote {
+    let change = price - prev;
+    let pct_change = if prev != 0.0 { change / prev * 100.0 } else { 0.0 };
+    Quote { price, change, pct_change }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn numbers_are_read_from_json_numbers_and_strings() {
+        assert_eq!(number(&json!(187.25)).unwrap(), 187.25);
+        assert_eq!(number(&json!("-1.5")).unwrap(), -1.5);
+        assert_eq!(number(&json!("1.23%")).unwrap(), 1.23);
+        assert!(number(&json!("n/a")).is_err());
+        assert_eq!(number(&Value::Null).unwrap_err().to_string(), "missing field in quote response");
+    }
+
+    #[test]
+    fn quotes_from_the_previous_close() {
+        let quote = quote_from_prev(110.0, 100.0);
+        assert_eq!((quote.price, quote.change, quote.pct_change), (110.0, 10.0, 10.0));
+        // No previous close: no percentage rather than infinity.
+        assert_eq!(quote_from_prev(5.0, 0.0).pct_change, 0.0);
+    }
+
+    #[test]
+    fn finnhub_symbols_and_redacted_errors() {
+        let symbol = |s| finnhub_symbol(&Ticker::parse(s).unwrap());
+        assert_eq!(symbol("aapl"), "AAPL");
+        assert_eq!(symbol("BRK-B"), "BRK-B");
+        assert_eq!(symbol("BTC-USD"), "BINANCE:BTCUSDT");
+        assert_eq!(symbol("ETH-EUR"), "BINANCE:ETHEUR");
+
+        let error = "https://www.alphavantage.co/query?symbol=AAPL&apikey=SECRET42: status code 503";
+        assert_eq!(redact(error, "SECRET42"), "https://www.alphavantage.co/query?symbol=AAPL&apikey=***: status code 503");
+        assert_eq!(redact(error, ""), error);
+    }
+}
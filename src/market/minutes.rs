@@ -0,0 +1,201 @@
+//! One-minute bars built from streamed ticks.
+//!
+//! Besides moving the watchlist's prices, every `[stream]` tick goes into
+//! the bar of its ticker's current minute. A bar is finished when a tick
+//! from a later minute arrives or its minute has passed; it's then appended
+//! to the ticker's CSV if that holds one-minute bars, so a 1m chart keeps
+//! growing without downloading again, and shows the forming bar meanwhile.
+//! Ticks are stamped with the local time they arrive at; appended bars are
+//! written in the exchange's zone from `markets`, with its UTC offset, as
+//! downloaded rows are.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDateTime, TimeZone, Timelike};
+use chrono_tz::Tz;
+
+use crate::data::Bar;
+use crate::ids::Ticker;
+use crate::prices;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinuteBar {
+    /// Start of the minute.
+    pub at: NaiveDateTime,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    /// Shares traded, as far as the provider reports sizes.
+    pub volume: f64,
+}
+
+impl MinuteBar {
+    fn open(at: NaiveDateTime, price: f64, size: f64) -> Self {
+        Self { at, open: price, high: price, low: price, close: price, volume: size }
+    }
+
+    pub fn bar(&self) -> Bar {
+        Bar { at: self.at, close: self.close, volume: Some(self.volume) }
+    }
+}
+
+/// The forming bar of each ticker that has ticked this minute.
+#[derive(Debug, Default)]
+pub struct MinuteBars {
+    forming: HashMap<Ticker, MinuteBar>,
+}
+
+impl MinuteBars {
+    /// Adds a trade of `size` at `price` made at `at`, returning the bar it
+    /// finished by opening a new minute. Ticks older than the forming bar
+    /// are dropped.
+    pub fn add(&mut self, ticker: &Ticker, at: NaiveDateTime, price: f64, size: f64) -> Option<MinuteBar> {
+        let minute = at.with_second(0)?.with_nanosecond(0)?;
+        if let Some(bar) = self.forming.get_mut(ticker)
+            && bar.at >= minute
+        {
+            if bar.at == minute {
+                bar.high = bar.high.max(price);
+                bar.low = bar.low.min(price);
+                bar.close = price;
+                bar.volume += size;
+            }
+            return None;
+        }
+        self.forming.insert(ticker.clone(), MinuteBar::open(minute, price, size))
+    }
+
+    /// Finishes the bars whose minute is over by `now`, for tickers that
+    /// haven't ticked since.
+    pub fn close_due(&mut self, now: NaiveDateTime) -> Vec<(Ticker, MinuteBar)> {
+        let due: Vec<Ticker> =
+            self.forming.iter().filter(|(_, bar)| bar.at + Duration::minutes(1) <= now).map(|(t, _)| t.clone()).collect();
+        due.into_iter().filter_map(|ticker| self.forming.remove_entry(&ticker)).collect()
+    }
+
+    pub fn forming(&self, ticker: &Ticker) -> Option<&MinuteBar> {
+        self.forming.get(ticker)
+    }
+}
+
+/// Puts `bar` at the end of `bars`: in place of the last one if it's the
+/// same minute, after it if it's later. Older bars are left out.
+pub fn merge(bars: &mut Vec<Bar>, bar: &MinuteBar) {
+    match bars.last_mut() {
+        Some(last) if last.at == bar.at => *last = bar.bar(),
+        Some(last) if last.at > bar.at => {}
+        _ => bars.push(bar.bar()),
+    }
+}
+
+/// `at`, a local time, in `zone`, or with the local offset without one.
+/// `None` for a time a clock change skipped.
+fn exchange_time(at: NaiveDateTime, zone: Option<Tz>) -> Option<DateTime<FixedOffset>> {
+    let at = Local.from_local_datetime(&at).earliest()?;
+    Some(zone.map_or_else(|| at.fixed_offset(), |zone| at.with_timezone(&zone).fixed_offset()))
+}
+
+/// Appends the `bars` newer than the last stored one to the CSV at `path`,
+/// filling its columns by header name (the first is the timestamp). Times
+/// are written in `zone`, the exchange's, like yfinance's
+/// `2024-06-03 09:30:00-04:00`, and compared with the stored ones there.
+/// Returns how many were written.
+pub fn append(path: &Path, bars: &[MinuteBar], zone: Option<Tz>) -> Result<usize, Box<dyn Error>> {
+    let last = prices::cached_bars(path)?.last().map(|b| b.at);
+    let new: Vec<(DateTime<FixedOffset>, &MinuteBar)> = bars
+        .iter()
+        .filter_map(|b| Some((exchange_time(b.at, zone)?, b)))
+        .filter(|(at, _)| last.is_none_or(|last| at.naive_local() > last))
+        .collect();
+    if new.is_empty() {
+        return Ok(0);
+    }
+    let text = fs::read_to_string(path)?;
+    let header: Vec<String> = text.lines().next().unwrap_or_default().split(',').map(|c| c.trim().to_string()).collect();
+    let mut out = String::new();
+    if !text.is_empty() && !text.ends_with('\n') {
+        out.push('\n');
+    }
+    for (at, bar) in new.iter() {
+        let cells: Vec<String> = header
+            .iter()
+            .enumerate()
+            .map(|(i, column)| match column.as_str() {
+                _ if i == 0 => at.format("%Y-%m-%d %H:%M:%S%:z").to_string(),
+                "Close" | "Adj Close" => bar.close.to_string(),
+                "Open" => bar.open.to_string(),
+                "High" => bar.high.to_string(),
+                "Low" => bar.low.to_string(),
+                "Volume" => bar.volume.to_string(),
+                _ => String::new(),
+            })
+            .collect();
+        out.push_str(&cells.join(","));
+        out.push('\n');
+    }
+    OpenOptions::new().append(true).open(path)?.write_all(out.as_bytes())?;
+    Ok(new.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn ticks_fold_into_minutes_appended_to_the_csv() {
+        let at = |m: u32, s: u32| NaiveDate::from_ymd_opt(2024, 6, 3).unwrap().and_hms_opt(9, m, s).unwrap();
+        let aapl = Ticker::parse("AAPL").unwrap();
+        let mut minutes = MinuteBars::default();
+        assert_eq!(minutes.add(&aapl, at(31, 5), 10.0, 100.0), None);
+        assert_eq!(minutes.add(&aapl, at(31, 40), 12.0, 50.0), None);
+        assert_eq!(minutes.add(&aapl, at(31, 59), 9.0, 10.0), None);
+        let first = minutes.add(&aapl, at(32, 1), 11.0, 5.0).unwrap();
+        assert_eq!(first, MinuteBar { at: at(31, 0), open: 10.0, high: 12.0, low: 9.0, close: 9.0, volume: 160.0 });
+        // A late tick doesn't reopen the finished minute.
+        assert_eq!(minutes.add(&aapl, at(31, 59), 50.0, 1.0), None);
+        assert!(minutes.close_due(at(32, 59)).is_empty());
+        let due = minutes.close_due(at(33, 0));
+        assert_eq!(due.len(), 1);
+        assert!(minutes.forming(&aapl).is_none());
+
+        let path = std::env::temp_dir().join(format!("stm-{}-minutes.csv", std::process::id()));
+        fs::write(&path, "Datetime,Close,Volume\n2024-06-03 09:30:00,10.5,1000").unwrap();
+        assert_eq!(append(&path, &[first, due[0].1], None).unwrap(), 2);
+        // Already stored.
+        assert_eq!(append(&path, &[first], None).unwrap(), 0);
+        let bars = prices::cached_bars(&path).unwrap();
+        assert_eq!(bars.len(), 3);
+        assert_eq!((bars[1].at, bars[1].close, bars[1].volume), (at(31, 0), 9.0, Some(160.0)));
+        fs::remove_file(&path).unwrap();
+
+        let mut chart = bars.to_vec();
+        merge(&mut chart, &MinuteBar { close: 11.5, ..due[0].1 });
+        assert_eq!((chart.len(), chart[2].close), (3, 11.5));
+    }
+
+    #[test]
+    fn appended_bars_are_written_in_the_exchange_zone() {
+        let new_york = chrono_tz::America::New_York;
+        let exchange = |m: u32| NaiveDate::from_ymd_opt(2024, 6, 3).unwrap().and_hms_opt(9, m, 0).unwrap();
+        // Streamed bars carry the local time of the same minutes.
+        let local = |m| new_york.from_local_datetime(&exchange(m)).unwrap().with_timezone(&Local).naive_local();
+        let bar = |m, close| MinuteBar { at: local(m), open: close, high: close, low: close, close, volume: 1.0 };
+
+        let path = std::env::temp_dir().join(format!("stm-{}-minutes-tz.csv", std::process::id()));
+        fs::write(&path, "Datetime,Close,Volume\n2024-06-03 09:30:00-04:00,10.5,1000\n").unwrap();
+        let bars = [bar(29, 9.0), bar(30, 9.5), bar(31, 10.0), bar(32, 11.0)];
+        assert_eq!(append(&path, &bars, Some(new_york)).unwrap(), 2);
+        let text = fs::read_to_string(&path).unwrap();
+        let stored = prices::cached_bars(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(text.ends_with("\n2024-06-03 09:31:00-04:00,10,1\n2024-06-03 09:32:00-04:00,11,1\n"), "{}", text);
+        assert_eq!(stored.iter().map(|b| b.at).collect::<Vec<_>>(), [exchange(30), exchange(31), exchange(32)]);
+    }
+}
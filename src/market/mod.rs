@@ -0,0 +1,10 @@
+//! Market data sources: the CSV files in `pre_stock/` and the providers
+//! that fill them, and live quotes and ticks beyond them.
+
+pub mod book;
+pub mod live;
+pub mod minutes;
+pub mod provider;
+pub mod symbols;
+#[cfg(feature = "streaming")]
+pub mod stream;
@@ -0,0 +1,467 @@
+//! Where historical bars, latest quotes and symbol searches come from.
+//!
+//! `[data] provider` picks one `MarketDataProvider` for the session, and a
+//! `universe` root can pick another for its tickers. The default reads the
+//! stored CSVs, which download_stock.py fills
+//! from whichever of its sources answers; Yahoo and Stooq fetch from that
+//! provider alone, and a download stores what they return in the same
+//! files, so the panels read stored bars whichever one is set. Downloads,
+//! company names (`symbols`), `stm quote`, `stm search` and `?QUERY` in
+//! the search box only see the trait: another provider is an
+//! implementation plus a case in `open`.
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Datelike, Months, NaiveDate, NaiveDateTime};
+use serde_json::{json, Value};
+
+use crate::calendar::Sessions;
+use crate::chaos;
+use crate::config::{DataConfig, DataProvider};
+use crate::data::{self, Bar, Interval};
+use crate::date_range::DateRange;
+use crate::ids::Ticker;
+use crate::market::live::{self, Quote};
+use crate::market::symbols::{SymbolBook, SYMBOLS_PATH};
+use crate::net;
+use crate::prices;
+use crate::universe;
+
+/// Matches a search returns at most.
+const MAX_MATCHES: usize = 10;
+
+/// Bars to fetch: a yfinance-style period back from now (`5d`, `1mo`,
+/// `1y`, `ytd`, `max`), or dates, up to the latest bar without an end.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Span {
+    Period(String),
+    Dates { start: NaiveDate, end: Option<NaiveDate> },
+}
+
+/// A search match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub ticker: Ticker,
+    /// Empty when the provider doesn't say.
+    pub name: String,
+    pub exchange: String,
+}
+
+pub trait MarketDataProvider: Send + Sync {
+    /// As in `[data] provider`.
+    fn name(&self) -> &'static str;
+
+    /// Whether `bars` fetches them, so a download stores what it returns.
+    /// The CSV provider reads the stored files, which download_stock.py
+    /// downloads instead.
+    fn fetches(&self) -> bool {
+        true
+    }
+
+    /// `ticker`'s bars over `span`, oldest first.
+    fn bars(&self, ticker: &Ticker, interval: Interval, span: &Span) -> Result<Vec<Bar>, Box<dyn Error>>;
+
+    fn quote(&self, ticker: &Ticker) -> Result<Quote, Box<dyn Error>>;
+
+    /// Symbols matching `query`, by ticker or company name, best first.
+    fn search(&self, query: &str) -> Result<Vec<Symbol>, Box<dyn Error>>;
+
+    /// `ticker`'s name and exchange, if the provider knows it.
+    fn lookup(&self, ticker: &Ticker) -> Result<Option<Symbol>, Box<dyn Error>> {
+        Ok(self.search(ticker.as_str())?.into_iter().find(|s| &s.ticker == ticker))
+    }
+}
+
+static PROVIDER: OnceLock<Box<dyn MarketDataProvider>> = OnceLock::new();
+
+/// Picks the session's provider. Only the first call has any effect;
+/// before it the CSV provider is used.
+pub fn init(config: &DataConfig) {
+    let _ = PROVIDER.set(open(config.provider));
+}
+
+pub fn current() -> &'static dyn MarketDataProvider {
+    PROVIDER.get_or_init(|| open(DataProvider::Csv)).as_ref()
+}
+
+/// The provider of `ticker`'s `universe` root, which its downloads and
+/// quotes use.
+pub fn for_ticker(ticker: &Ticker) -> &'static dyn MarketDataProvider {
+    match universe::root_of(ticker).provider {
+        DataProvider::Csv => &Csv,
+        DataProvider::Yahoo => &Yahoo,
+        DataProvider::Stooq => &Stooq,
+    }
+}
+
+pub fn open(kind: DataProvider) -> Box<dyn MarketDataProvider> {
+    match kind {
+        DataProvider::Csv => Box::new(Csv),
+        DataProvider::Yahoo => Box::new(Yahoo),
+        DataProvider::Stooq => Box::new(Stooq),
+    }
+}
+
+/// The stored CSVs.
+pub struct Csv;
+
+impl MarketDataProvider for Csv {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    fn fetches(&self) -> bool {
+        false
+    }
+
+    fn bars(&self, ticker: &Ticker, _interval: Interval, span: &Span) -> Result<Vec<Bar>, Box<dyn Error>> {
+        let bars = prices::bars(ticker, DateRange::default())?;
+        let Some(last) = bars.last().map(|b| b.at) else {
+            return Ok(bars);
+        };
+        let (start, end) = match span {
+            Span::Period(period) => (period_start(period, last)?, None),
+            Span::Dates { start, end } => (start.and_hms_opt(0, 0, 0), *end),
+        };
+        Ok(bars
+            .into_iter()
+            .filter(|b| start.is_none_or(|s| b.at >= s) && end.is_none_or(|e| b.at.date() <= e))
+            .collect())
+    }
+
+    /// The last stored close and its change on the session before.
+    fn quote(&self, ticker: &Ticker) -> Result<Quote, Box<dyn Error>> {
+        let bars = prices::bars(ticker, DateRange::default())
+            .map_err(|e| format!("No stored prices for {} ({}); try `stm download {}`", ticker, e, ticker))?;
+        quote_from_bars(ticker, &bars)
+    }
+
+    /// Yahoo's matches, as download_stock.py mostly downloads from it;
+    /// offline, the stored tickers and known names containing `query`.
+    fn search(&self, query: &str) -> Result<Vec<Symbol>, Box<dyn Error>> {
+        if let Ok(matches) = Yahoo.search(query) {
+            return Ok(matches);
+        }
+        let book = SymbolBook::load(SYMBOLS_PATH).unwrap_or_default();
+        let known = book.search(query);
+        let query = query.trim().to_uppercase();
+        let mut matches: Vec<Symbol> = Vec::new();
+        for root in universe::roots() {
+            matches.extend(
+                root.csvs()
+                    .into_iter()
+                    .filter(|s| s.ticker.as_str().contains(&query) && !known.iter().any(|k| k.ticker == s.ticker))
+                    .map(|s| Symbol { exchange: s.source(root), ticker: s.ticker, name: String::new() }),
+            );
+        }
+        matches.extend(known);
+        // Exact, then prefix matches first; a ticker in two roots once.
+        matches.sort_by_key(|s| (s.ticker.as_str() != query, !s.ticker.as_str().starts_with(&query), s.ticker.clone()));
+        matches.dedup_by(|a, b| a.ticker == b.ticker);
+        matches.truncate(MAX_MATCHES);
+        Ok(matches)
+    }
+
+    fn lookup(&self, ticker: &Ticker) -> Result<Option<Symbol>, Box<dyn Error>> {
+        let book = SymbolBook::load(SYMBOLS_PATH)?;
+        match book.symbols.get(ticker) {
+            Some(info) => Ok(Some(Symbol { ticker: ticker.clone(), name: info.name.clone(), exchange: info.exchange.clone() })),
+            None => Yahoo.lookup(ticker),
+        }
+    }
+}
+
+/// Yahoo Finance's chart and search endpoints.
+pub struct Yahoo;
+
+impl MarketDataProvider for Yahoo {
+    fn name(&self) -> &'static str {
+        "yahoo"
+    }
+
+    fn bars(&self, ticker: &Ticker, interval: Interval, span: &Span) -> Result<Vec<Bar>, Box<dyn Error>> {
+        let bar_size = match interval {
+            Interval::OneHour => "60m",
+            other => other.as_str(),
+        };
+        let window = match span {
+            Span::Period(period) => format!("range={}", period),
+            Span::Dates { start, end } => {
+                let to = end.map_or_else(|| chrono::Utc::now().timestamp(), |e| midnight(e.succ_opt().unwrap_or(e)));
+                format!("period1={}&period2={}", midnight(*start), to)
+            }
+        };
+        let url = format!(
+            "https://query1.finance.yahoo.com/v8/finance/chart/{}?interval={}&includePrePost=false&{}",
+            encode(ticker.as_str()),
+            bar_size,
+            window
+        );
+        let body = get_json(&url)?;
+        let chart = &body["chart"];
+        if let Some(description) = chart["error"]["description"].as_str() {
+            return Err(description.to_string().into());
+        }
+        let result = &chart["result"][0];
+        let times = result["timestamp"].as_array().ok_or("no bars in response")?;
+        // Exchange-local times, as download_stock.py stores them.
+        let offset = result["meta"]["gmtoffset"].as_i64().unwrap_or(0);
+        let quote = &result["indicators"]["quote"][0];
+        let mut bars = Vec::new();
+        for (i, t) in times.iter().enumerate() {
+            let (Some(t), Some(close)) = (t.as_i64(), quote["close"][i].as_f64()) else {
+                continue;
+            };
+            let Some(at) = DateTime::from_timestamp(t + offset, 0).map(|at| at.naive_utc()) else {
+                continue;
+            };
+            let at = if interval == Interval::OneDay { at.date().and_hms_opt(0, 0, 0).unwrap_or(at) } else { at };
+            bars.push(Bar { at, close, volume: quote["volume"][i].as_f64() });
+        }
+        Ok(bars)
+    }
+
+    fn quote(&self, ticker: &Ticker) -> Result<Quote, Box<dyn Error>> {
+        let url = format!("https://query1.finance.yahoo.com/v8/finance/chart/{}?range=1d&interval=1d", encode(ticker.as_str()));
+        let body = get_json(&url)?;
+        let meta = &body["chart"]["result"][0]["meta"];
+        let price = meta["regularMarketPrice"].as_f64().ok_or("no price in response")?;
+        let prev = meta["chartPreviousClose"].as_f64().ok_or("no previous close in response")?;
+        Ok(live::quote_from_prev(price, prev))
+    }
+
+    fn search(&self, query: &str) -> Result<Vec<Symbol>, Box<dyn Error>> {
+        let url = format!(
+            "https://query1.finance.yahoo.com/v1/finance/search?q={}&quotesCount={}&newsCount=0",
+            encode(query.trim()),
+            MAX_MATCHES
+        );
+        let body = get_json(&url)?;
+        let quotes = body["quotes"].as_array().map(Vec::as_slice).unwrap_or_default();
+        Ok(quotes
+            .iter()
+            .filter_map(|q| {
+                let ticker = Ticker::parse(q["symbol"].as_str()?).ok()?;
+                let name = q["longname"].as_str().or(q["shortname"].as_str()).unwrap_or_default();
+                Some(Symbol { ticker, name: name.to_string(), exchange: q["exchDisp"].as_str().unwrap_or_default().to_string() })
+            })
+            .collect())
+    }
+}
+
+/// Stooq's daily CSV export.
+pub struct Stooq;
+
+impl Stooq {
+    /// Stooq's name for `ticker`: US listings get `.us` (share classes use
+    /// a dot), crypto pairs are written together (`BTC-USD` is `btcusd`).
+    fn symbol(ticker: &Ticker) -> String {
+        match ticker.crypto_pair() {
+            Some((base, quote)) => format!("{}{}", base, quote).to_lowercase(),
+            None => format!("{}.us", ticker.as_str().replace('-', ".").to_lowercase()),
+        }
+    }
+}
+
+impl MarketDataProvider for Stooq {
+    fn name(&self) -> &'static str {
+        "stooq"
+    }
+
+    fn bars(&self, ticker: &Ticker, interval: Interval, span: &Span) -> Result<Vec<Bar>, Box<dyn Error>> {
+        if interval != Interval::OneDay {
+            return Err("Stooq has daily bars only".into());
+        }
+        let mut url = format!("https://stooq.com/q/d/l/?s={}&i=d", encode(&Self::symbol(ticker)));
+        if let Span::Dates { start, end } = span {
+            url.push_str(&format!("&d1={}", start.format("%Y%m%d")));
+            if let Some(end) = end {
+                url.push_str(&format!("&d2={}", end.format("%Y%m%d")));
+            }
+        }
+        chaos::inject("Stooq download")?;
+        let text = net::client().get(&url, &[])?.into_string()?;
+        if text.trim().is_empty() || text.starts_with("No data") {
+            return Err(format!("Stooq has no data for {}", Self::symbol(ticker)).into());
+        }
+        let mut bars = parse_stooq(&text)?;
+        if let (Span::Period(period), Some(last)) = (span, bars.last().map(|b| b.at))
+            && let Some(start) = period_start(period, last)?
+        {
+            bars.retain(|b| b.at >= start);
+        }
+        Ok(bars)
+    }
+
+    /// The last daily close and its change on the day before.
+    fn quote(&self, ticker: &Ticker) -> Result<Quote, Box<dyn Error>> {
+        let bars = self.bars(ticker, Interval::OneDay, &Span::Period("5d".to_string()))?;
+        quote_from_bars(ticker, &bars)
+    }
+
+    fn search(&self, _query: &str) -> Result<Vec<Symbol>, Box<dyn Error>> {
+        Err("Stooq has no symbol search; use the yahoo or csv provider".into())
+    }
+}
+
+/// Stores `bars` fetched by `provider` in `ticker`'s CSV, in its
+/// `universe` root: a period
+/// replaces the file, dates are merged into it, newer bars winning.
+/// Returns the number of bars written.
+pub fn store(
+    provider: &dyn MarketDataProvider,
+    ticker: &Ticker,
+    interval: Interval,
+    span: &Span,
+    bars: Vec<Bar>,
+) -> Result<usize, Box<dyn Error>> {
+    store_in(&universe::dir_of(ticker), provider.name(), ticker, interval, span, bars)
+}
+
+fn store_in(dir: &Path, source: &str, ticker: &Ticker, interval: Interval, span: &Span, bars: Vec<Bar>) -> Result<usize, Box<dyn Error>> {
+    if bars.is_empty() {
+        return Err(format!("{} returned no bars for {}", source, ticker).into());
+    }
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}.csv", ticker));
+    let mut bars = bars;
+    if let Span::Dates { .. } = span
+        && path.exists()
+    {
+        let mut existing = data::read_bars(&path.to_string_lossy())?;
+        existing.retain(|old| !bars.iter().any(|b| b.at == old.at));
+        bars.extend(existing);
+        bars.sort_by_key(|b| b.at);
+    }
+    let tmp = path.with_extension("csv.tmp");
+    let mut wtr = csv::Writer::from_path(&tmp)?;
+    wtr.write_record([if interval == Interval::OneDay { "Date" } else { "Datetime" }, "Close", "Volume"])?;
+    for bar in &bars {
+        let at = if interval == Interval::OneDay { bar.at.format("%Y-%m-%d") } else { bar.at.format("%Y-%m-%d %H:%M:%S") };
+        wtr.write_record([at.to_string(), bar.close.to_string(), bar.volume.map(|v| v.to_string()).unwrap_or_default()])?;
+    }
+    wtr.flush()?;
+    drop(wtr);
+    fs::rename(&tmp, &path)?;
+    if let Span::Period(period) = span {
+        // The sidecar download_stock.py writes, for the chart's axis.
+        let fetched_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let sessions = if ticker.is_crypto() { "24/7" } else { "exchange" };
+        let last_bar = bars.last().map(|b| b.at.format("%Y-%m-%d %H:%M:%S").to_string());
+        let meta = json!({
+            "interval": interval.as_str(),
+            "range": period,
+            "source": source,
+            "fetched_at": fetched_at,
+            "sessions": sessions,
+            "last_bar": last_bar,
+        });
+        fs::write(dir.join(format!("{}.meta.json", ticker)), meta.to_string())?;
+    } else if let Some(last) = bars.last() {
+        data::record_last_bar(dir, ticker, last.at)?;
+    }
+    Ok(bars.len())
+}
+
+/// Start of `period` back from `last`; `None` for everything.
+fn period_start(period: &str, last: NaiveDateTime) -> Result<Option<NaiveDateTime>, Box<dyn Error>> {
+    if period == "max" {
+        return Ok(None);
+    }
+    if period == "ytd" {
+        return Ok(NaiveDate::from_ymd_opt(last.year(), 1, 1).and_then(|d| d.and_hms_opt(0, 0, 0)));
+    }
+    let count = |suffix: &str| period.strip_suffix(suffix).and_then(|n| n.parse::<u32>().ok());
+    let start = if let Some(n) = count("mo") {
+        last.checked_sub_months(Months::new(n))
+    } else if let Some(n) = count("y") {
+        last.checked_sub_months(Months::new(n * 12))
+    } else if let Some(n) = count("d") {
+        last.checked_sub_signed(chrono::Duration::days(n.into()))
+    } else {
+        return Err(format!("Unknown range {} ({})", period, data::RANGES.join("/")).into());
+    };
+    Ok(start)
+}
+
+fn parse_stooq(text: &str) -> Result<Vec<Bar>, Box<dyn Error>> {
+    let mut rdr = csv::Reader::from_reader(text.as_bytes());
+    let headers = rdr.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|h| h == name);
+    let (date, close) = (column("Date").ok_or("no Date column")?, column("Close").ok_or("no Close column")?);
+    let volume = column("Volume");
+    let mut bars = Vec::new();
+    for row in rdr.records() {
+        let row = row?;
+        let at = row.get(date).and_then(data::parse_timestamp);
+        let close = row.get(close).and_then(|c| c.parse::<f64>().ok());
+        if let (Some(at), Some(close)) = (at, close) {
+            bars.push(Bar { at, close, volume: volume.and_then(|v| row.get(v)?.parse().ok()) });
+        }
+    }
+    Ok(bars)
+}
+
+fn quote_from_bars(ticker: &Ticker, bars: &[Bar]) -> Result<Quote, Box<dyn Error>> {
+    let times: Vec<NaiveDateTime> = bars.iter().map(|b| b.at).collect();
+    let closes: Vec<f64> = bars.iter().map(|b| b.close).collect();
+    let (price, change, pct_change) =
+        data::price_change(&times, &closes, Sessions::for_ticker(ticker)).ok_or_else(|| format!("Too few bars of {} for a quote", ticker))?;
+    Ok(Quote { price, change, pct_change })
+}
+
+fn get_json(url: &str) -> Result<Value, Box<dyn Error>> {
+    chaos::inject("market data fetch")?;
+    Ok(serde_json::from_str(&net::client().get(url, &[])?.into_string()?)?)
+}
+
+fn midnight(date: NaiveDate) -> i64 {
+    date.and_hms_opt(0, 0, 0).map_or(0, |d| d.and_utc().timestamp())
+}
+
+/// Percent-encodes everything but unreserved characters, for query values.
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 6, day).unwrap().and_hms_opt(0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn stored_date_spans_merge_and_periods_replace() {
+        let dir = std::env::temp_dir().join(format!("stm-{}-provider", std::process::id()));
+        let ticker = Ticker::parse("X").unwrap();
+        let bar = |day, close| Bar { at: at(day), close, volume: None };
+        let period = Span::Period("1mo".to_string());
+        store_in(&dir, "test", &ticker, Interval::OneDay, &period, vec![bar(3, 1.0), bar(4, 2.0)]).unwrap();
+        let gap = Span::Dates { start: at(4).date(), end: None };
+        assert_eq!(store_in(&dir, "test", &ticker, Interval::OneDay, &gap, vec![bar(4, 2.5), bar(5, 3.0)]).unwrap(), 3);
+        let closes: Vec<f64> = data::read_bars(&dir.join("X.csv").to_string_lossy()).unwrap().iter().map(|b| b.close).collect();
+        assert_eq!(closes, vec![1.0, 2.5, 3.0]);
+        assert_eq!(data::read_interval_in(&dir, &ticker), Interval::OneDay);
+        store_in(&dir, "test", &ticker, Interval::OneDay, &period, vec![bar(5, 4.0)]).unwrap();
+        assert_eq!(data::read_bars(&dir.join("X.csv").to_string_lossy()).unwrap().len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let csv = "Date,Open,High,Low,Close,Volume\n2024-06-03,1,1,1,10.5,100\n2024-06-04,1,1,1,11,\n";
+        let bars = parse_stooq(csv).unwrap();
+        assert_eq!((bars[1].at, bars[1].close, bars[1].volume), (at(4), 11.0, None));
+        assert_eq!(period_start("1mo", at(30)).unwrap(), Some(NaiveDate::from_ymd_opt(2024, 5, 30).unwrap().and_hms_opt(0, 0, 0).unwrap()));
+        assert_eq!(encode("BRK B&"), "BRK%20B%26");
+    }
+}
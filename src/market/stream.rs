@@ -0,0 +1,327 @@
+//! WebSocket tick streaming (`streaming` feature).
+//!
+//! Complements `live` polling: a background thread holds a socket open to
+//! Finnhub or Polygon, subscribes to every watchlist ticker and forwards
+//! trade ticks over a channel, so prices update sub-second without hitting
+//! REST rate limits. Providers that stream quotes also send the bid and ask
+//! at the top of the book, kept per ticker for the order book panel, and
+//! ticks are folded into one-minute bars (see `minutes`).
+//! Crypto pairs stream from Finnhub under their Binance symbols; Polygon's
+//! stocks socket doesn't carry them, so there they stay on polling.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::ErrorKind;
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use serde_json::{json, Value};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+use crate::chaos;
+use crate::ids::Ticker;
+use crate::config::{StreamConfig, StreamProvider};
+use crate::market::book::{Level, OrderBook};
+use crate::market::live::finnhub_symbol;
+use crate::market::minutes::{MinuteBar, MinuteBars};
+
+/// How long a socket read blocks before checking for new subscriptions.
+const READ_TIMEOUT: Duration = Duration::from_millis(500);
+/// Delay before reconnecting after the socket drops.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Tick {
+    pub price: f64,
+    /// Shares traded, when the provider says.
+    pub size: Option<f64>,
+}
+
+enum Update {
+    Connected,
+    Tick(Ticker, Tick),
+    Book(Ticker, OrderBook),
+    Error(String),
+}
+
+pub struct StreamFeed {
+    provider: StreamProvider,
+    tickers: Arc<Mutex<Vec<Ticker>>>,
+    rx: Receiver<Update>,
+    pub ticks: HashMap<Ticker, Tick>,
+    pub books: HashMap<Ticker, OrderBook>,
+    pub minutes: MinuteBars,
+    // Bars finished since the last `take_closed`.
+    closed: Vec<(Ticker, MinuteBar)>,
+    pub connected: bool,
+    pub last_tick: Option<DateTime<Local>>,
+    pub last_error: Option<String>,
+}
+
+impl StreamFeed {
+    /// Opens the stream in the background, or `None` when disabled.
+    pub fn start(config: &StreamConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        let tickers = Arc::new(Mutex::new(Vec::new()));
+        let (tx, rx) = mpsc::channel();
+        let shared = Arc::clone(&tickers);
+        let stream = config.clone();
+        thread::spawn(move || stream_loop(stream, shared, tx));
+        Some(Self {
+            provider: config.provider,
+            tickers,
+            rx,
+            ticks: HashMap::new(),
+            books: HashMap::new(),
+            minutes: MinuteBars::default(),
+            closed: Vec::new(),
+            connected: false,
+            last_tick: None,
+            last_error: None,
+        })
+    }
+
+    /// Tickers to subscribe to; new ones are picked up within `READ_TIMEOUT`.
+    pub fn set_tickers(&self, tickers: Vec<Ticker>) {
+        if let Ok(mut shared) = self.tickers.lock() {
+            *shared = tickers;
+        }
+    }
+
+    /// Drains pending ticks without blocking, returning any errors.
+    pub fn poll(&mut self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let now = Local::now().naive_local();
+        while let Ok(update) = self.rx.try_recv() {
+            match update {
+                Update::Connected => {
+                    self.connected = true;
+                    self.last_error = None;
+                }
+                Update::Tick(ticker, tick) => {
+                    if let Some(bar) = self.minutes.add(&ticker, now, tick.price, tick.size.unwrap_or(0.0)) {
+                        self.closed.push((ticker.clone(), bar));
+                    }
+                    self.ticks.insert(ticker, tick);
+                    self.last_tick = Some(Local::now());
+                }
+                Update::Book(ticker, book) => {
+                    self.books.insert(ticker, book);
+                }
+                Update::Error(err) => {
+                    self.connected = false;
+                    errors.push(err.clone());
+                    self.last_error = Some(err);
+                }
+            }
+        }
+        self.closed.extend(self.minutes.close_due(now));
+        errors
+    }
+
+    /// Minute bars finished since the last call, oldest first.
+    pub fn closed(&self) -> &[(Ticker, MinuteBar)] {
+        &self.closed
+    }
+
+    pub fn take_closed(&mut self) -> Vec<(Ticker, MinuteBar)> {
+        std::mem::take(&mut self.closed)
+    }
+
+    pub fn provider(&self) -> StreamProvider {
+        self.provider
+    }
+
+    pub fn status(&self) -> String {
+        let provider = self.provider.name();
+        match (&self.last_error, self.connected, self.last_tick) {
+            (Some(err), false, _) => format!("Stream {}: {}", provider, err),
+            (_, _, Some(at)) => format!("Stream {}: tick {}", provider, at.format("%H:%M:%S")),
+            (_, true, None) => format!("Stream {}: connected", provider),
+            (_, false, None) => format!("Stream {}: connecting", provider),
+        }
+    }
+}
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+fn stream_loop(config: StreamConfig, tickers: Arc<Mutex<Vec<Ticker>>>, tx: Sender<Update>) {
+    loop {
+        let result = run_session(&config, &tickers, &tx);
+        let err = match result {
+            Ok(()) => return, // UI has gone away
+            Err(e) => e.to_string(),
+        };
+        if tx.send(Update::Error(err)).is_err() {
+            return;
+        }
+        thread::sleep(RECONNECT_DELAY);
+    }
+}
+
+/// One connection lifetime. Returns `Ok` only when the receiver is dropped.
+fn run_session(
+    config: &StreamConfig,
+    tickers: &Arc<Mutex<Vec<Ticker>>>,
+    tx: &Sender<Update>,
+) -> Result<(), Box<dyn Error>> {
+    let url = match config.provider {
+        StreamProvider::Finnhub => format!("wss://ws.finnhub.io?token={}", config.api_key),
+        StreamProvider::Polygon => "wss://socket.polygon.io/stocks".to_string(),
+    };
+    chaos::inject("stream connect")?;
+    let (mut socket, _) = tungstenite::connect(url)?;
+    set_read_timeout(&socket)?;
+    if let StreamProvider::Polygon = config.provider {
+        send_json(&mut socket, json!({ "action": "auth", "params": config.api_key }))?;
+    }
+    if tx.send(Update::Connected).is_err() {
+        return Ok(());
+    }
+
+    // Provider symbol of each subscription, to map ticks back to tickers.
+    let mut subscribed: HashMap<String, Ticker> = HashMap::new();
+    loop {
+        let wanted = tickers.lock().map(|t| t.clone()).unwrap_or_default();
+        for ticker in wanted {
+            if matches!(config.provider, StreamProvider::Polygon) && ticker.is_crypto() {
+                continue;
+            }
+            let symbol = provider_symbol(config.provider, &ticker);
+            if let Entry::Vacant(entry) = subscribed.entry(symbol) {
+                send_json(&mut socket, subscribe_message(config.provider, entry.key()))?;
+                entry.insert(ticker);
+            }
+        }
+
+        let message = match socket.read() {
+            Ok(message) => message,
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+            {
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if let Message::Text(text) = message {
+            for update in parse_frame(config.provider, &text, &subscribed) {
+                if tx.send(update).is_err() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+fn set_read_timeout(socket: &Socket) -> std::io::Result<()> {
+    match socket.get_ref() {
+        MaybeTlsStream::Plain(s) => s.set_read_timeout(Some(READ_TIMEOUT)),
+        MaybeTlsStream::Rustls(s) => s.get_ref().set_read_timeout(Some(READ_TIMEOUT)),
+        _ => Ok(()),
+    }
+}
+
+fn send_json(socket: &mut Socket, value: Value) -> Result<(), Box<dyn Error>> {
+    socket.send(Message::Text(value.to_string()))?;
+    Ok(())
+}
+
+fn provider_symbol(provider: StreamProvider, ticker: &Ticker) -> String {
+    match provider {
+        StreamProvider::Finnhub => finnhub_symbol(ticker),
+        StreamProvider::Polygon => ticker.to_string(),
+    }
+}
+
+fn subscribe_message(provider: StreamProvider, symbol: &str) -> Value {
+    match provider {
+        StreamProvider::Finnhub => json!({ "type": "subscribe", "symbol": symbol }),
+        StreamProvider::Polygon => json!({ "action": "subscribe", "params": format!("T.{0},Q.{0}", symbol) }),
+    }
+}
+
+/// Extracts trade ticks and quotes from a text frame; other frames (pings,
+/// status messages) yield nothing. `symbols` maps subscribed provider
+/// symbols that aren't tickers themselves back to theirs.
+fn parse_frame(provider: StreamProvider, text: &str, symbols: &HashMap<String, Ticker>) -> Vec<Update> {
+    let Ok(value) = serde_json::from_str::<Value>(text) else {
+        return Vec::new();
+    };
+    match provider {
+        // {"type":"trade","data":[{"s":"AAPL","p":189.5,"v":100,...}]}
+        StreamProvider::Finnhub => {
+            let trades = value["data"].as_array().cloned().unwrap_or_default();
+            trades.iter().filter_map(|t| trade(t, "s", "v", symbols)).collect()
+        }
+        // [{"ev":"T","sym":"AAPL","p":189.5,"s":100,...},
+        //  {"ev":"Q","sym":"AAPL","bp":189.4,"bs":300,"ap":189.6,"as":200,...}]
+        StreamProvider::Polygon => {
+            let events = value.as_array().cloned().unwrap_or_default();
+            events
+                .iter()
+                .filter_map(|e| match e["ev"].as_str()? {
+                    "T" => trade(e, "sym", "s", symbols),
+                    "Q" => quote(e, symbols),
+                    _ => None,
+                })
+                .collect()
+        }
+    }
+}
+
+fn ticker_for(symbol: &str, symbols: &HashMap<String, Ticker>) -> Option<Ticker> {
+    symbols.get(symbol).cloned().or_else(|| Ticker::parse(symbol).ok())
+}
+
+fn trade(t: &Value, symbol_key: &str, size_key: &str, symbols: &HashMap<String, Ticker>) -> Option<Update> {
+    let ticker = ticker_for(t[symbol_key].as_str()?, symbols)?;
+    Some(Update::Tick(ticker, Tick { price: t["p"].as_f64()?, size: t[size_key].as_f64() }))
+}
+
+/// A Polygon quote: the best bid and ask, so one level a side.
+fn quote(q: &Value, symbols: &HashMap<String, Ticker>) -> Option<Update> {
+    let ticker = ticker_for(q["sym"].as_str()?, symbols)?;
+    let side = |price: &str, size: &str| -> Vec<Level> {
+        match (q[price].as_f64(), q[size].as_f64()) {
+            (Some(price), Some(size)) if price > 0.0 => vec![Level { price, size }],
+            _ => Vec::new(),
+        }
+    };
+    let book = OrderBook { bids: side("bp", "bs"), asks: side("ap", "as"), at: Local::now() };
+    Some(Update::Book(ticker, book))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polygon_frames_carry_trades_and_quotes() {
+        let frame = r#"[{"ev":"T","sym":"AAPL","p":189.5,"s":100},{"ev":"Q","sym":"AAPL","bp":189.4,"bs":300,"ap":189.6,"as":200},{"ev":"status"}]"#;
+        let updates = parse_frame(StreamProvider::Polygon, frame, &HashMap::new());
+        assert_eq!(updates.len(), 2);
+        assert!(matches!(&updates[0], Update::Tick(t, tick) if t.as_str() == "AAPL" && tick.price == 189.5 && tick.size == Some(100.0)));
+        let Update::Book(_, book) = &updates[1] else { panic!("expected a quote") };
+        assert_eq!(book.bids, vec![Level { price: 189.4, size: 300.0 }]);
+        assert_eq!(book.asks, vec![Level { price: 189.6, size: 200.0 }]);
+    }
+
+    #[test]
+    fn finnhub_crypto_ticks_map_back_to_their_pair() {
+        let btc = Ticker::parse("BTC-USD").unwrap();
+        let symbol = provider_symbol(StreamProvider::Finnhub, &btc);
+        assert_eq!(symbol, "BINANCE:BTCUSDT");
+        let symbols = HashMap::from([(symbol, btc.clone())]);
+        let frame = r#"{"type":"trade","data":[{"s":"BINANCE:BTCUSDT","p":64000.5},{"s":"AAPL","p":189.5}]}"#;
+        let updates = parse_frame(StreamProvider::Finnhub, frame, &symbols);
+        assert!(matches!(&updates[..], [Update::Tick(a, _), Update::Tick(b, _)] if *a == btc && b.as_str() == "AAPL"));
+    }
+}
@@ -0,0 +1,105 @@
+//! Company names and exchanges of tickers, for the ML list and chart
+//! titles.
+//!
+//! Names come from `[data] provider`: every symbol search remembers its
+//! matches, and a download looks up a ticker that isn't known yet. They're
+//! kept in `SYMBOLS_PATH`, so the list shows them from startup and works
+//! offline.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ids::Ticker;
+use crate::market::provider::Symbol;
+
+pub const SYMBOLS_PATH: &str = "symbols.json";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SymbolInfo {
+    pub name: String,
+    #[serde(default)]
+    pub exchange: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SymbolBook {
+    pub symbols: BTreeMap<Ticker, SymbolInfo>,
+}
+
+impl SymbolBook {
+    /// The book at `path`; empty if there isn't one yet.
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(serde_json::from_str(&text)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let tmp = format!("{}.tmp", path);
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Company name of `ticker`, if known.
+    pub fn name(&self, ticker: &Ticker) -> Option<&str> {
+        self.symbols.get(ticker).map(|s| s.name.as_str()).filter(|n| !n.is_empty())
+    }
+
+    /// Adds the named ones of `symbols`, returning whether anything
+    /// changed.
+    pub fn remember(&mut self, symbols: &[Symbol]) -> bool {
+        let mut changed = false;
+        for symbol in symbols.iter().filter(|s| !s.name.is_empty()) {
+            let info = SymbolInfo { name: symbol.name.clone(), exchange: symbol.exchange.clone() };
+            if self.symbols.get(&symbol.ticker) != Some(&info) {
+                self.symbols.insert(symbol.ticker.clone(), info);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Known symbols whose ticker or name contains `query`, ignoring case.
+    pub fn search(&self, query: &str) -> Vec<Symbol> {
+        let query = query.trim().to_lowercase();
+        self.symbols
+            .iter()
+            .filter(|(ticker, info)| ticker.as_str().to_lowercase().contains(&query) || info.name.to_lowercase().contains(&query))
+            .map(|(ticker, info)| Symbol { ticker: ticker.clone(), name: info.name.clone(), exchange: info.exchange.clone() })
+            .collect()
+    }
+}
+
+/// Adds the named ones of `symbols` to the book at `SYMBOLS_PATH`,
+/// returning the book.
+pub fn record(symbols: &[Symbol]) -> Result<SymbolBook, Box<dyn Error>> {
+    let mut book = SymbolBook::load(SYMBOLS_PATH)?;
+    if book.remember(symbols) {
+        book.save(SYMBOLS_PATH)?;
+    }
+    Ok(book)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remembered_names_are_searchable() {
+        let symbol = |t: &str, name: &str| Symbol { ticker: Ticker::parse(t).unwrap(), name: name.to_string(), exchange: "NASDAQ".to_string() };
+        let mut book = SymbolBook::default();
+        assert!(book.remember(&[symbol("AAPL", "Apple Inc."), symbol("APLE", "")]));
+        assert!(!book.remember(&[symbol("AAPL", "Apple Inc.")]));
+        assert_eq!(book.name(&Ticker::parse("AAPL").unwrap()), Some("Apple Inc."));
+        assert_eq!(book.name(&Ticker::parse("APLE").unwrap()), None);
+        assert_eq!(book.search("APPLE").len(), 1);
+        assert!(book.search("msft").is_empty());
+    }
+}
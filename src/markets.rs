@@ -0,0 +1,172 @@
+//! Tick and lot sizes, by market.
+//!
+//! Each `[[markets]]` entry covers the tickers ending in one of its
+//! `suffixes` (`.T`, `.L`, `-USD`) or held by one of its `universe` roots;
+//! the first that covers a ticker gives its rules. Orders entered in the
+//! broker view are checked against them: a price off the tick or a
+//! quantity that isn't a whole number of lots is refused, with the nearest
+//! valid values. Prices are shown to as many decimals as the tick has.
+//! Tickers no entry covers trade at 0.01 in any quantity. An entry's
+//! `timezone` is the one streamed minute bars are written in.
+
+use std::sync::OnceLock;
+
+use chrono_tz::Tz;
+
+use crate::broker::OrderRequest;
+use crate::config::MarketConfig;
+use crate::ids::Ticker;
+use crate::universe;
+
+/// Most decimals a tick is shown to.
+const MAX_DECIMALS: usize = 8;
+
+static MARKETS: OnceLock<Vec<MarketConfig>> = OnceLock::new();
+
+/// Sets the session's markets. Only the first call has any effect.
+pub fn init(markets: &[MarketConfig]) {
+    let _ = MARKETS.set(markets.to_vec());
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rules {
+    /// The market's name; empty for the defaults.
+    pub market: String,
+    pub tick: f64,
+    /// 0 when fractions are allowed.
+    pub lot: f64,
+    pub decimals: usize,
+}
+
+impl Rules {
+    pub fn new(market: &str, tick: f64, lot: f64) -> Self {
+        let tick = if tick > 0.0 { tick } else { 0.01 };
+        Self { market: market.to_string(), tick, lot: lot.max(0.0), decimals: decimals(tick) }
+    }
+
+    /// `price` to the tick's decimals.
+    pub fn price(&self, price: f64) -> String {
+        format!("{:.*}", self.decimals, price)
+    }
+
+    /// `qty` rounded down to whole lots.
+    pub fn whole_lots(&self, qty: f64) -> f64 {
+        if self.lot > 0.0 { (qty / self.lot + 1e-9).floor() * self.lot } else { qty }
+    }
+
+    /// Why `order` can't be placed in this market, if it can't.
+    pub fn check(&self, order: &OrderRequest) -> Result<(), String> {
+        let within = if self.market.is_empty() { String::new() } else { format!(" on {}", self.market) };
+        if self.lot > 0.0 && !on_step(order.qty, self.lot) {
+            let below = self.whole_lots(order.qty);
+            return Err(format!(
+                "{} trades in lots of {}{}: {} or {} shares, not {}",
+                order.ticker,
+                self.lot,
+                within,
+                below,
+                below + self.lot,
+                order.qty
+            ));
+        }
+        for price in order.limit.into_iter().chain(order.trigger.map(|t| t.price())) {
+            if !on_step(price, self.tick) {
+                let below = (price / self.tick).floor() * self.tick;
+                return Err(format!(
+                    "{} moves in ticks of {}{}: {} or {}, not {}",
+                    order.ticker,
+                    self.price(self.tick),
+                    within,
+                    self.price(below),
+                    self.price(below + self.tick),
+                    price
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self::new("", 0.01, 0.0)
+    }
+}
+
+fn on_step(value: f64, step: f64) -> bool {
+    let steps = value / step;
+    (steps - steps.round()).abs() < 1e-6
+}
+
+/// Decimals `tick` needs: 2 for 0.01 or 0.05, 0 for 1.
+fn decimals(tick: f64) -> usize {
+    (0..MAX_DECIMALS)
+        .find(|&d| {
+            let scaled = tick * 10f64.powi(d as i32);
+            (scaled - scaled.round()).abs() < 1e-6
+        })
+        .unwrap_or(MAX_DECIMALS)
+}
+
+/// The first of `markets` covering `ticker`, in `root`.
+fn market_in<'a>(markets: &'a [MarketConfig], ticker: &Ticker, root: &str) -> Option<&'a MarketConfig> {
+    let name = ticker.as_str().to_uppercase();
+    markets.iter().find(|m| {
+        m.suffixes.iter().any(|s| name.ends_with(&s.to_uppercase())) || m.roots.iter().any(|r| r == root)
+    })
+}
+
+/// The rules of the first of `markets` covering `ticker`, in `root`.
+pub fn rules_in(markets: &[MarketConfig], ticker: &Ticker, root: &str) -> Rules {
+    market_in(markets, ticker, root).map_or_else(Rules::default, |m| Rules::new(&m.name, m.tick, m.lot))
+}
+
+fn market_for(ticker: &Ticker) -> Option<&'static MarketConfig> {
+    let markets = MARKETS.get().filter(|markets| !markets.is_empty())?;
+    market_in(markets, ticker, &universe::root_of(ticker).name)
+}
+
+/// The rules `ticker` trades by.
+pub fn rules_for(ticker: &Ticker) -> Rules {
+    market_for(ticker).map_or_else(Rules::default, |m| Rules::new(&m.name, m.tick, m.lot))
+}
+
+/// Zone `ticker`'s exchange keeps time in, if its market sets one.
+pub fn timezone_for(ticker: &Ticker) -> Option<Tz> {
+    market_for(ticker)?.timezone
+}
+
+/// `ticker`'s `price` to its tick's decimals.
+pub fn price(ticker: &Ticker, price: f64) -> String {
+    rules_for(ticker).price(price)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::{Side, Trigger};
+
+    #[test]
+    fn orders_keep_to_the_market_tick_and_lot() {
+        let markets = [
+            MarketConfig { name: "Tokyo".to_string(), suffixes: vec![".T".to_string()], tick: 1.0, lot: 100.0, ..Default::default() },
+            MarketConfig { name: "crypto".to_string(), roots: vec!["crypto".to_string()], tick: 0.0001, ..Default::default() },
+        ];
+        let ticker = |t: &str| Ticker::parse(t).unwrap();
+        let tokyo = rules_in(&markets, &ticker("7203.T"), "equities");
+        let crypto = rules_in(&markets, &ticker("BTC-USD"), "crypto");
+        assert_eq!((tokyo.decimals, crypto.decimals, Rules::default().decimals), (0, 4, 2));
+        assert_eq!(rules_in(&markets, &ticker("AAPL"), "equities"), Rules::default());
+        assert_eq!((tokyo.price(2512.4), crypto.price(0.5)), ("2512".to_string(), "0.5000".to_string()));
+        assert_eq!(Rules::new("", 0.05, 0.0).decimals, 2);
+
+        let order = |qty: f64, limit: Option<f64>| OrderRequest { side: Side::Buy, qty, ticker: ticker("7203.T"), limit, trigger: None };
+        assert_eq!(tokyo.check(&order(200.0, Some(2500.0))), Ok(()));
+        assert_eq!(tokyo.check(&order(150.0, None)), Err("7203.T trades in lots of 100 on Tokyo: 100 or 200 shares, not 150".to_string()));
+        assert_eq!(tokyo.check(&order(100.0, Some(2500.5))), Err("7203.T moves in ticks of 1 on Tokyo: 2500 or 2501, not 2500.5".to_string()));
+        let stop = OrderRequest { trigger: Some(Trigger::StopLoss(2400.2)), ..order(100.0, None) };
+        assert!(tokyo.check(&stop).is_err());
+        assert_eq!(Rules::default().check(&order(0.5, Some(10.01))), Ok(()));
+        assert_eq!(tokyo.whole_lots(250.0), 200.0);
+    }
+}
@@ -0,0 +1,159 @@
+//! The menu bar: every action under File, View, Trade, ML and Help, for
+//! finding one without knowing its key.
+//!
+//! F10 (`[keys] menu`) opens it from the dashboard; Left/Right or a menu's
+//! initial pick the menu, Up/Down the action, Enter runs it as its key
+//! would and Esc closes. With `[layout] menu_bar = true` the bar stays
+//! along the top while closed.
+
+use crossterm::event::KeyCode;
+
+use crate::keymap::Action;
+
+pub const MENUS: [(&str, &[Action]); 5] = [
+    (
+        "File",
+        &[Action::ExportReport, Action::ExportChart, Action::RetentionReport, Action::Refresh, Action::Quit],
+    ),
+    (
+        "View",
+        &[
+            Action::FocusNext,
+            Action::FocusPrev,
+            Action::ToggleVolume,
+            Action::ChartScale,
+            Action::ToggleOrderBook,
+            Action::DateRange,
+            Action::ToggleCompare,
+            Action::Crosshair,
+            Action::GrowChart,
+            Action::ShrinkChart,
+            Action::CollapsePanel,
+            Action::RestorePanels,
+            Action::PageUp,
+            Action::PageDown,
+            Action::JumpToDate,
+            Action::ShowErrors,
+            Action::TimeTravel,
+            Action::MarketOverview,
+            Action::CancelJob,
+        ],
+    ),
+    (
+        "Trade",
+        &[
+            Action::NewAccount,
+            Action::EditAccount,
+            Action::CloseAccount,
+            Action::ToggleArchived,
+            Action::RankAccounts,
+            Action::RecordTrade,
+            Action::Transfer,
+            Action::Analytics,
+            Action::Dividends,
+            Action::Broker,
+            Action::PaperOrder,
+            Action::AutoTrade,
+            Action::TradingGame,
+        ],
+    ),
+    (
+        "ML",
+        &[
+            Action::Search,
+            Action::Filter,
+            Action::FillGaps,
+            Action::DownloadAll,
+            Action::SortTicker,
+            Action::SortPrice,
+            Action::SortChange,
+            Action::SortPctChange,
+            Action::SortRelStrength,
+            Action::AlertPreview,
+            Action::ShowReturns,
+            Action::Models,
+        ],
+    ),
+    ("Help", &[Action::Help, Action::ShowUsage, Action::ReleaseNotes, Action::Changelog]),
+];
+
+/// An action's description up to its first `:` or parenthesis, as a menu
+/// item.
+pub fn label(action: Action) -> &'static str {
+    let description = action.description();
+    let end = [": ", " ("].iter().filter_map(|sep| description.find(sep)).min().unwrap_or(description.len());
+    &description[..end]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuStep {
+    Stay,
+    Close,
+    Run(Action),
+}
+
+/// The open menu and its highlighted action.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MenuState {
+    pub menu: usize,
+    pub item: usize,
+}
+
+impl MenuState {
+    pub fn items(&self) -> &'static [Action] {
+        MENUS[self.menu].1
+    }
+
+    pub fn selected(&self) -> Action {
+        self.items()[self.item]
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode) -> MenuStep {
+        let (menus, items) = (MENUS.len(), self.items().len());
+        match code {
+            KeyCode::Esc => return MenuStep::Close,
+            KeyCode::Enter => return MenuStep::Run(self.selected()),
+            KeyCode::Left => self.open((self.menu + menus - 1) % menus),
+            KeyCode::Right | KeyCode::Tab => self.open((self.menu + 1) % menus),
+            KeyCode::Up => self.item = (self.item + items - 1) % items,
+            KeyCode::Down => self.item = (self.item + 1) % items,
+            KeyCode::Home => self.item = 0,
+            KeyCode::End => self.item = items - 1,
+            KeyCode::Char(c) => {
+                if let Some(i) = MENUS.iter().position(|(title, _)| title.starts_with(c.to_ascii_uppercase())) {
+                    self.open(i);
+                }
+            }
+            _ => {}
+        }
+        MenuStep::Stay
+    }
+
+    fn open(&mut self, menu: usize) {
+        *self = Self { menu, item: 0 };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn menus_hold_every_action_once() {
+        let listed: Vec<Action> = MENUS.iter().flat_map(|(_, items)| items.iter().copied()).collect();
+        for action in Action::ALL.iter().filter(|&&a| a != Action::Menu) {
+            assert_eq!(listed.iter().filter(|&a| a == action).count(), 1, "{:?}", action);
+        }
+        assert_eq!(label(Action::SortTicker), "Sort ML list by ticker");
+        assert_eq!(label(Action::Broker), "Broker");
+
+        let mut menu = MenuState::default();
+        assert_eq!(menu.handle_key(KeyCode::Up), MenuStep::Stay);
+        assert_eq!(menu.selected(), Action::Quit);
+        menu.handle_key(KeyCode::Char('t'));
+        menu.handle_key(KeyCode::Down);
+        assert_eq!(menu.handle_key(KeyCode::Enter), MenuStep::Run(Action::EditAccount));
+        menu.handle_key(KeyCode::Left);
+        assert_eq!((menu.menu, menu.item), (1, 0));
+    }
+}
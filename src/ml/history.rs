@@ -0,0 +1,255 @@
+//! Prediction log and per-ticker model accuracy.
+//!
+//! Every successful `ml/model.py` run appends a row to `ml_history.csv` with
+//! the predicted next close and the last close it was made from. Once the
+//! ticker's CSV has a bar after that one, its close is recorded as the
+//! actual, and the resolved rows feed the rolling MAE and hit rate shown in
+//! the Model Performance panel.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+
+use chrono::{Local, NaiveDateTime};
+use csv::{ReaderBuilder, WriterBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::data::Bar;
+use crate::date_range::DateRange;
+use crate::prices;
+use crate::ids::Ticker;
+
+pub const HISTORY_PATH: &str = "ml_history.csv";
+/// Number of most recent resolved predictions the accuracy stats cover.
+pub const ROLLING_WINDOW: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Prediction {
+    pub ticker: Ticker,
+    // Wall-clock time the model ran.
+    pub predicted_at: NaiveDateTime,
+    // Timestamp and close of the last bar the prediction was made from.
+    pub as_of: NaiveDateTime,
+    pub last_close: f64,
+    pub predicted: f64,
+    // Close of the first bar after `as_of`, once it has been downloaded.
+    pub actual: Option<f64>,
+}
+
+impl Prediction {
+    /// Builds a prediction for the bar following the last one in `bars`.
+    pub fn new(ticker: &Ticker, bars: &[Bar], predicted: f64) -> Option<Self> {
+        let last = bars.last()?;
+        Some(Self {
+            ticker: ticker.clone(),
+            predicted_at: Local::now().naive_local(),
+            as_of: last.at,
+            last_close: last.close,
+            predicted,
+            actual: None,
+        })
+    }
+
+    /// True when the predicted and actual moves from `last_close` agree in
+    /// direction. `None` until the actual is known.
+    pub fn direction_hit(&self) -> Option<bool> {
+        let actual = self.actual?;
+        Some((self.predicted - self.last_close).signum() == (actual - self.last_close).signum())
+    }
+}
+
+/// Pulls the predicted value out of model.py's output: the last number on
+/// its last non-empty line. Earlier lines are training progress.
+pub fn parse_prediction(stdout: &str) -> Option<f64> {
+    let line = stdout.lines().rev().find(|l| !l.trim().is_empty())?;
+    line.split_whitespace()
+        .rev()
+        .map(|word| word.trim_matches(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-')))
+        .find_map(|word| word.parse::<f64>().ok().filter(|v| v.is_finite()))
+}
+
+/// All logged predictions, oldest first. A missing file is an empty log.
+pub fn load(path: &str) -> Result<Vec<Prediction>, Box<dyn Error>> {
+    if !Path::new(path).exists() {
+        return Ok(Vec::new());
+    }
+    let mut rdr = ReaderBuilder::new().from_path(path)?;
+    let mut records = Vec::new();
+    for result in rdr.deserialize() {
+        records.push(result?);
+    }
+    Ok(records)
+}
+
+pub fn save(path: &str, predictions: &[Prediction]) -> Result<(), Box<dyn Error>> {
+    let mut wtr = WriterBuilder::new().from_path(path)?;
+    for prediction in predictions {
+        wtr.serialize(prediction)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Fills in actuals from `pre_stock/` for predictions whose next bar has
+/// since been downloaded. Returns true if anything changed.
+pub fn resolve_actuals(predictions: &mut [Prediction]) -> bool {
+    resolve_from(predictions, |ticker| prices::bars(ticker, DateRange::default()).unwrap_or_default())
+}
+
+/// `resolve_actuals` against the bars `bars_of` gives, asked once per
+/// ticker.
+fn resolve_from(predictions: &mut [Prediction], mut bars_of: impl FnMut(&Ticker) -> Vec<Bar>) -> bool {
+    let mut bars_by_ticker: BTreeMap<Ticker, Vec<Bar>> = BTreeMap::new();
+    let mut changed = false;
+    for prediction in predictions.iter_mut().filter(|p| p.actual.is_none()) {
+        let bars = bars_by_ticker.entry(prediction.ticker.clone()).or_insert_with(|| bars_of(&prediction.ticker));
+        if let Some(next) = bars.iter().find(|b| b.at > prediction.as_of) {
+            prediction.actual = Some(next.close);
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Loads the log, resolving and saving any newly known actuals.
+pub fn load_resolved(path: &str) -> Result<Vec<Prediction>, Box<dyn Error>> {
+    let mut predictions = load(path)?;
+    if resolve_actuals(&mut predictions) {
+        save(path, &predictions)?;
+    }
+    Ok(predictions)
+}
+
+/// Appends `prediction` to the log and returns the updated, resolved log.
+pub fn record(path: &str, prediction: Prediction) -> Result<Vec<Prediction>, Box<dyn Error>> {
+    let mut predictions = load(path)?;
+    predictions.push(prediction);
+    resolve_actuals(&mut predictions);
+    save(path, &predictions)?;
+    Ok(predictions)
+}
+
+/// Rolling accuracy of one ticker's resolved predictions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accuracy {
+    pub ticker: Ticker,
+    // Predictions logged, resolved or not.
+    pub total: usize,
+    // Resolved predictions inside the rolling window.
+    pub window: usize,
+    pub mae: f64,
+    // Share of the window that called the direction right, 0.0..=1.0.
+    pub hit_rate: f64,
+    // Most recent prediction still waiting for its actual.
+    pub pending: Option<f64>,
+}
+
+/// Per-ticker stats over the last `ROLLING_WINDOW` resolved predictions,
+/// sorted by ticker.
+pub fn accuracy(predictions: &[Prediction]) -> Vec<Accuracy> {
+    let mut by_ticker: BTreeMap<&Ticker, Vec<&Prediction>> = BTreeMap::new();
+    for prediction in predictions {
+        by_ticker.entry(&prediction.ticker).or_default().push(prediction);
+    }
+    by_ticker
+        .into_iter()
+        .map(|(ticker, preds)| {
+            let resolved: Vec<&Prediction> = preds.iter().copied().filter(|p| p.actual.is_some()).collect();
+            let recent = &resolved[resolved.len().saturating_sub(ROLLING_WINDOW)..];
+            let n = recent.len().max(1) as f64;
+            let mae = recent.iter().filter_map(|p| Some((p.predicted - p.actual?).abs())).sum::<f64>() / n;
+            let hits = recent.iter().filter(|p| p.direction_hit() == Some(true)).count();
+            Accuracy {
+                ticker: ticker.clone(),
+                total: preds.len(),
+                window: recent.len(),
+                mae,
+                hit_rate: hits as f64 / n,
+                pending: preds.iter().rev().find(|p| p.actual.is_none()).map(|p| p.predicted),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn day(d: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 6, d).unwrap().and_hms_opt(0, 0, 0).unwrap()
+    }
+
+    fn bar(d: u32, close: f64) -> Bar {
+        Bar { at: day(d), close, volume: None }
+    }
+
+    fn prediction(symbol: &str, as_of: u32, last_close: f64, predicted: f64, actual: Option<f64>) -> Prediction {
+        let ticker = Ticker::parse(symbol).unwrap();
+        let mut prediction = Prediction::new(&ticker, &[bar(as_of, last_close)], predicted).unwrap();
+        prediction.actual = actual;
+        prediction
+    }
+
+    #[test]
+    fn logged_predictions_read_back_as_written() {
+        let stdout = "Epoch 1/2 loss: 0.31\nEpoch 2/2 loss: 0.12\nPredicted next close: $187.25\n\n";
+        let predicted = parse_prediction(stdout).unwrap();
+        assert_eq!(predicted, 187.25);
+        assert_eq!(parse_prediction("training...\ndone\n"), None);
+
+        let logged = vec![prediction("AAPL", 3, 185.0, predicted, None), prediction("MSFT", 4, 410.5, 405.0, Some(412.0))];
+        let path = std::env::temp_dir().join(format!("stm-{}-ml-history.csv", std::process::id())).to_string_lossy().into_owned();
+        save(&path, &logged).unwrap();
+        let read = load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read, logged);
+    }
+
+    #[test]
+    fn actuals_come_from_the_first_bar_after_the_prediction() {
+        // No bar on the 5th, so the prediction made on the 4th resolves
+        // against the 6th; the one made on the last bar waits.
+        let bars = vec![bar(3, 10.0), bar(4, 11.0), bar(6, 12.5)];
+        let mut predictions = vec![
+            prediction("AAPL", 3, 10.0, 10.5, None),
+            prediction("AAPL", 4, 11.0, 11.5, None),
+            prediction("AAPL", 6, 12.5, 13.0, None),
+            prediction("AAPL", 3, 10.0, 9.0, Some(9.5)),
+        ];
+        let mut reads = 0;
+        assert!(resolve_from(&mut predictions, |_| {
+            reads += 1;
+            bars.clone()
+        }));
+        assert_eq!(reads, 1);
+        let actuals: Vec<_> = predictions.iter().map(|p| p.actual).collect();
+        assert_eq!(actuals, [Some(11.0), Some(12.5), None, Some(9.5)]);
+        assert!(!resolve_from(&mut predictions, |_| bars.clone()));
+    }
+
+    #[test]
+    fn accuracy_covers_resolved_predictions_per_ticker() {
+        let predictions = vec![
+            // Up 2, was up 1: a hit, off by 1.
+            prediction("AAPL", 3, 100.0, 102.0, Some(101.0)),
+            // Down 1, was up 2: a miss, off by 3.
+            prediction("AAPL", 4, 101.0, 100.0, Some(103.0)),
+            // Down 3, was down 1: a hit, off by 2.
+            prediction("AAPL", 5, 103.0, 100.0, Some(102.0)),
+            prediction("AAPL", 6, 102.0, 104.0, None),
+            prediction("MSFT", 6, 400.0, 401.0, None),
+        ];
+        let stats = accuracy(&predictions);
+        assert_eq!(stats.len(), 2);
+        let aapl = &stats[0];
+        assert_eq!((aapl.total, aapl.window, aapl.mae, aapl.pending), (4, 3, 2.0, Some(104.0)));
+        assert!((aapl.hit_rate - 2.0 / 3.0).abs() < 1e-12);
+
+        // Nothing resolved yet is zero, not NaN.
+        let msft = &stats[1];
+        assert_eq!((msft.window, msft.mae, msft.hit_rate, msft.pending), (0, 0.0, 0.0, Some(401.0)));
+        assert!(accuracy(&[]).is_empty());
+    }
+}
@@ -0,0 +1,7 @@
+//! Bookkeeping around the Python model in `ml/`.
+
+pub mod history;
+#[cfg(feature = "native-ml")]
+pub mod native;
+pub mod pipeline;
+pub mod registry;
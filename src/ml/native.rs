@@ -0,0 +1,71 @@
+//! In-process inference on the ONNX export of `ml/model.py`.
+//!
+//! The LSTM takes the last `SEQ_LEN` values of a series scaled to -1..=1
+//! (the range of the sine waves it was trained on) and predicts the next
+//! one. Closes are min-max scaled into that range going in and the output is
+//! scaled back to a price.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use tract_onnx::prelude::*;
+
+/// Where `ml/model.py` exports the model run on its own, next to its
+/// `.pth` weights. Versions trained by the pipeline are in `registry`.
+pub const NATIVE_MODEL_PATH: &str = "../model/lstm_model.onnx";
+/// Sequence length the model was trained with (`seq_length` in model.py).
+pub const SEQ_LEN: usize = 10;
+
+#[derive(Clone)]
+pub struct NativeModel {
+    plan: Arc<TypedRunnableModel>,
+}
+
+impl NativeModel {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let plan = tract_onnx::onnx()
+            .model_for_path(path)?
+            .with_input_fact(0, f32::fact([1, SEQ_LEN, 1]).into())?
+            .into_optimized()?
+            .into_runnable()?;
+        Ok(Self { plan })
+    }
+
+    /// Predicts the close after the last of `closes`.
+    pub fn predict(&self, closes: &[f64]) -> Result<f64, Box<dyn Error>> {
+        if closes.len() < SEQ_LEN {
+            return Err(format!("need at least {} closes, have {}", SEQ_LEN, closes.len()).into());
+        }
+        let window = &closes[closes.len() - SEQ_LEN..];
+        let min = window.iter().copied().fold(f64::MAX, f64::min);
+        let max = window.iter().copied().fold(f64::MIN, f64::max);
+        let half_span = ((max - min) / 2.0).max(f64::EPSILON);
+        let mid = (max + min) / 2.0;
+        let scaled: Vec<f32> = window.iter().map(|c| ((c - mid) / half_span) as f32).collect();
+
+        let input: Tensor = tract_ndarray::Array3::from_shape_vec((1, SEQ_LEN, 1), scaled)?.into();
+        let outputs = self.plan.run(tvec!(input.into()))?;
+        let output = outputs[0]
+            .to_plain_array_view::<f32>()?
+            .iter()
+            .next()
+            .copied()
+            .ok_or("model produced no output")?;
+        Ok(mid + output as f64 * half_span)
+    }
+}
+
+/// The model at `path`, loaded on first use and kept for the rest of the
+/// session. A failed load is retried on the next call.
+pub fn shared(path: &Path) -> Result<NativeModel, Box<dyn Error>> {
+    static SHARED: OnceLock<Mutex<HashMap<PathBuf, NativeModel>>> = OnceLock::new();
+    let mut loaded = SHARED.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(model) = loaded.get(path) {
+        return Ok(model.clone());
+    }
+    let model = NativeModel::load(&path.to_string_lossy())?;
+    loaded.insert(path.to_path_buf(), model.clone());
+    Ok(model)
+}
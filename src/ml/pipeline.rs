@@ -0,0 +1,291 @@
+//! The model run as the stages in `[pipeline]`.
+//!
+//! Each stage is a command with the files it reads and writes, and they run
+//! in order on the job worker. `{ticker}`, `{interval}`, `{data_dir}`, the
+//! ticker's price directory (see `universe`), and `{model_dir}`, the
+//! directory of the model version being trained or used (see `registry`),
+//! are filled in for the run. A stage with outputs is skipped
+//! while they all exist, none is older than its inputs and its command is
+//! the one that last wrote them; outputs made some other way, such as by
+//! the Download action, count as up to date too. Every run is logged,
+//! command, exit status and output, to `<log_dir>/<ticker>/<stage>.log`.
+//! The last stage is the model: it always runs, and its stdout is the
+//! prediction.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::time::SystemTime;
+
+use crate::chaos;
+use crate::config::{PipelineConfig, StageConfig};
+use crate::data;
+use crate::ids::Ticker;
+use crate::jobs::{JobContext, JobError};
+use crate::net;
+use crate::universe;
+
+/// A stage with its placeholders filled in for one ticker.
+#[derive(Debug, Clone, PartialEq)]
+struct Stage {
+    name: String,
+    command: Vec<String>,
+    inputs: Vec<PathBuf>,
+    outputs: Vec<PathBuf>,
+    optional: bool,
+}
+
+impl Stage {
+    fn new(config: &StageConfig, ticker: &Ticker, interval: &str, model_dir: &Path) -> Self {
+        let data_dir = universe::dir_of(ticker);
+        let fill = |text: &String| {
+            text.replace("{ticker}", ticker.as_str())
+                .replace("{interval}", interval)
+                .replace("{data_dir}", &data_dir.to_string_lossy())
+                .replace("{model_dir}", &model_dir.to_string_lossy())
+        };
+        Self {
+            name: config.name.clone(),
+            command: config.command.iter().map(fill).collect(),
+            inputs: config.inputs.iter().map(|p| PathBuf::from(fill(p))).collect(),
+            outputs: config.outputs.iter().map(|p| PathBuf::from(fill(p))).collect(),
+            optional: config.optional,
+        }
+    }
+
+    fn command_line(&self) -> String {
+        self.command.join(" ")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StageOutcome {
+    Ran,
+    /// Skipped, its outputs being up to date.
+    Cached,
+    /// An optional stage failed, with why.
+    Failed(String),
+}
+
+/// What one stage did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StageRun {
+    pub name: String,
+    pub outcome: StageOutcome,
+}
+
+/// The stages run for a ticker, in order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PipelineRun {
+    pub stages: Vec<StageRun>,
+}
+
+impl PipelineRun {
+    /// e.g. `download cached, preprocess ran`.
+    pub fn summary(&self) -> String {
+        self.stages
+            .iter()
+            .map(|s| {
+                let outcome = match s.outcome {
+                    StageOutcome::Ran => "ran",
+                    StageOutcome::Cached => "cached",
+                    StageOutcome::Failed(_) => "failed",
+                };
+                format!("{} {}", s.name, outcome)
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Whether any stage ran, so the data may have changed.
+    pub fn ran_any(&self) -> bool {
+        self.stages.iter().any(|s| s.outcome == StageOutcome::Ran)
+    }
+}
+
+/// Runs every stage before the model.
+pub fn prepare(ctx: &JobContext, config: &PipelineConfig, ticker: &Ticker, model_dir: &Path) -> Result<PipelineRun, JobError> {
+    let stages = stages(config, ticker, model_dir)?;
+    let mut run = PipelineRun::default();
+    for stage in &stages[..stages.len() - 1] {
+        run.stages.push(run_stage(ctx, config, ticker, stage)?);
+    }
+    Ok(run)
+}
+
+/// Runs the whole pipeline: what `prepare` runs, then the model. Returns
+/// the earlier stages' outcomes and the model's stdout.
+pub fn run(
+    ctx: &JobContext,
+    config: &PipelineConfig,
+    ticker: &Ticker,
+    model_dir: &Path,
+) -> Result<(PipelineRun, String), JobError> {
+    let stages = stages(config, ticker, model_dir)?;
+    let (model, earlier) = stages.split_last().expect("stages checked non-empty");
+    let mut run = PipelineRun::default();
+    for stage in earlier {
+        run.stages.push(run_stage(ctx, config, ticker, stage)?);
+    }
+    let output = execute(ctx, config, ticker, model)?;
+    Ok((run, String::from_utf8_lossy(&output.stdout).into_owned()))
+}
+
+/// The configured stages for `ticker`, once they're known to be runnable.
+fn stages(config: &PipelineConfig, ticker: &Ticker, model_dir: &Path) -> Result<Vec<Stage>, JobError> {
+    if config.stages.is_empty() {
+        return Err(JobError::new("[pipeline] has no stages; the last one should run the model"));
+    }
+    for (i, stage) in config.stages.iter().enumerate() {
+        let valid_name = !stage.name.is_empty()
+            && stage.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_name {
+            return Err(JobError::new(format!("[pipeline] stage {} needs a name of letters, digits, - or _", i + 1)));
+        }
+        if config.stages[..i].iter().any(|s| s.name == stage.name) {
+            return Err(JobError::new(format!("[pipeline] has two stages named {}", stage.name)));
+        }
+        if stage.command.is_empty() {
+            return Err(JobError::new(format!("[pipeline] stage {} has no command", stage.name)));
+        }
+    }
+    let interval = data::read_interval(ticker);
+    Ok(config.stages.iter().map(|s| Stage::new(s, ticker, interval.as_str(), model_dir)).collect())
+}
+
+/// Runs `stage` unless it's up to date. An optional stage's failure is
+/// returned as its outcome rather than as an error.
+fn run_stage(
+    ctx: &JobContext,
+    config: &PipelineConfig,
+    ticker: &Ticker,
+    stage: &Stage,
+) -> Result<StageRun, JobError> {
+    let stamp = stage_file(config, ticker, stage, "stamp");
+    let outcome = if is_up_to_date(stage, &stamp) {
+        StageOutcome::Cached
+    } else {
+        match execute(ctx, config, ticker, stage) {
+            Ok(_) => StageOutcome::Ran,
+            Err(err) if stage.optional && !ctx.is_cancelled() => StageOutcome::Failed(err.message),
+            Err(err) => return Err(err),
+        }
+    };
+    Ok(StageRun { name: stage.name.clone(), outcome })
+}
+
+/// Runs the stage's command and logs it, stamping its outputs on success.
+fn execute(ctx: &JobContext, config: &PipelineConfig, ticker: &Ticker, stage: &Stage) -> Result<Output, JobError> {
+    chaos::inject("a pipeline stage").map_err(|e| JobError::new(format!("{}: {}", stage.name, e)))?;
+    let log = stage_file(config, ticker, stage, "log");
+    let stamp = stage_file(config, ticker, stage, "stamp");
+    let _ = fs::remove_file(&stamp);
+    if let Some(missing) = stage.inputs.iter().find(|p| !p.exists()) {
+        let message = format!("{}: {} doesn't exist", stage.name, missing.display());
+        write_log(&log, stage, &message, None);
+        return Err(JobError::new(message));
+    }
+    let (program, args) = stage.command.split_first().expect("commands checked non-empty");
+    match ctx.output(Command::new(program).args(args).envs(net::python_env())) {
+        Ok(o) if o.status.success() => {
+            write_log(&log, stage, &o.status.to_string(), Some(&o));
+            if !stage.outputs.is_empty() {
+                let _ = fs::write(&stamp, stage.command_line());
+            }
+            Ok(o)
+        }
+        Ok(o) => {
+            write_log(&log, stage, &o.status.to_string(), Some(&o));
+            let mut err = JobError::from_output(&stage.name, &o);
+            err.message = format!("{} (log: {})", err.message, log.display());
+            Err(err)
+        }
+        Err(e) => {
+            let message = format!("Failed to run {} for {}: {}", program, stage.name, e);
+            write_log(&log, stage, &message, None);
+            Err(JobError::new(message))
+        }
+    }
+}
+
+/// `<log_dir>/<ticker>/<stage>.<extension>`, creating the directory.
+fn stage_file(config: &PipelineConfig, ticker: &Ticker, stage: &Stage, extension: &str) -> PathBuf {
+    let dir = Path::new(&config.log_dir).join(ticker.as_str());
+    let _ = fs::create_dir_all(&dir);
+    dir.join(format!("{}.{}", stage.name, extension))
+}
+
+/// Replaces the stage's log with this run. A log that can't be written
+/// doesn't stop the run.
+fn write_log(path: &Path, stage: &Stage, status: &str, output: Option<&Output>) {
+    let mut text = format!(
+        "$ {}\n{} at {}\n",
+        stage.command_line(),
+        status,
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+    );
+    if let Some(o) = output {
+        text += &format!(
+            "--- stdout\n{}\n--- stderr\n{}\n",
+            String::from_utf8_lossy(&o.stdout).trim_end(),
+            String::from_utf8_lossy(&o.stderr).trim_end()
+        );
+    }
+    let _ = fs::write(path, text);
+}
+
+/// Whether the stage's outputs all exist, none is older than an input, and
+/// the last run that wrote them, if it was this pipeline's, ran the same
+/// command.
+fn is_up_to_date(stage: &Stage, stamp: &Path) -> bool {
+    if stage.outputs.is_empty() {
+        return false;
+    }
+    if fs::read_to_string(stamp).is_ok_and(|made_by| made_by != stage.command_line()) {
+        return false;
+    }
+    let modified = |p: &PathBuf| fs::metadata(p).and_then(|m| m.modified()).ok();
+    let Some(oldest_output) = stage.outputs.iter().map(modified).collect::<Option<Vec<SystemTime>>>().and_then(|t| t.into_iter().min())
+    else {
+        return false;
+    };
+    stage.inputs.iter().all(|p| modified(p).is_some_and(|t| t <= oldest_output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::time::Duration;
+
+    #[test]
+    fn stages_rerun_for_newer_inputs_or_a_changed_command() {
+        let dir = std::env::temp_dir().join(format!("stm-{}-pipeline", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config = StageConfig {
+            name: "features".to_string(),
+            command: vec!["python3".to_string(), "features.py".to_string(), "{ticker}".to_string()],
+            inputs: vec![dir.join("{ticker}.csv").to_string_lossy().into_owned()],
+            outputs: vec![dir.join("{ticker}.features").to_string_lossy().into_owned()],
+            optional: false,
+        };
+        let stage = Stage::new(&config, &Ticker::parse("AAPL").unwrap(), "1d", Path::new("models/AAPL/v1"));
+        assert_eq!(stage.command_line(), "python3 features.py AAPL");
+        let stamp = dir.join("features.stamp");
+        let touch = |path: &PathBuf, age_secs| {
+            let file = File::create(path).unwrap();
+            file.set_modified(SystemTime::now() - Duration::from_secs(age_secs)).unwrap();
+        };
+        touch(&stage.inputs[0], 60);
+        assert!(!is_up_to_date(&stage, &stamp), "no output yet");
+        touch(&stage.outputs[0], 30);
+        assert!(is_up_to_date(&stage, &stamp), "made outside the pipeline");
+        fs::write(&stamp, "python3 old_features.py AAPL").unwrap();
+        assert!(!is_up_to_date(&stage, &stamp));
+        fs::write(&stamp, stage.command_line()).unwrap();
+        assert!(is_up_to_date(&stage, &stamp));
+        touch(&stage.inputs[0], 0);
+        assert!(!is_up_to_date(&stage, &stamp));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
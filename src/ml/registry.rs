@@ -0,0 +1,174 @@
+//! Trained models kept per ticker, each run's artifacts in a version
+//! directory of their own.
+//!
+//! A model run for a ticker without an active version trains one into
+//! `models/<TICKER>/v<N>/` (the pipeline's `{model_dir}`), and it is
+//! recorded in `models/registry.json` with when it was trained, the bars it
+//! saw and the metrics `ml/model.py` wrote to its `metrics.json`. A new
+//! version becomes the active one; later runs predict with it until another
+//! is trained, or an older one is picked in the models view to roll back.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::data::Bar;
+use crate::ids::Ticker;
+
+pub const MODELS_DIR: &str = "models";
+pub const REGISTRY_PATH: &str = "models/registry.json";
+/// Written by `ml/model.py` next to the weights: metric name to value.
+const METRICS_FILE: &str = "metrics.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelVersion {
+    pub ticker: Ticker,
+    /// From 1, per ticker.
+    pub version: u32,
+    pub trained_at: DateTime<Local>,
+    /// First and last of the ticker's bars when it was trained.
+    pub data_start: Option<NaiveDateTime>,
+    pub data_end: Option<NaiveDateTime>,
+    pub bars: usize,
+    pub metrics: BTreeMap<String, f64>,
+    /// Where the artifacts are.
+    pub dir: PathBuf,
+}
+
+impl ModelVersion {
+    /// The version trained into `dir` from `bars`, with the metrics the
+    /// training left there.
+    pub fn new(ticker: &Ticker, version: u32, dir: PathBuf, bars: &[Bar]) -> Self {
+        let metrics = fs::read_to_string(dir.join(METRICS_FILE))
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self {
+            ticker: ticker.clone(),
+            version,
+            trained_at: Local::now(),
+            data_start: bars.first().map(|b| b.at),
+            data_end: bars.last().map(|b| b.at),
+            bars: bars.len(),
+            metrics,
+            dir,
+        }
+    }
+
+    /// e.g. `AAPL v3`.
+    pub fn label(&self) -> String {
+        format!("{} v{}", self.ticker, self.version)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Registry {
+    pub versions: Vec<ModelVersion>,
+    /// Version each ticker predicts with.
+    pub active: BTreeMap<Ticker, u32>,
+}
+
+impl Registry {
+    /// The registry at `path`; empty before the first model is trained.
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(serde_json::from_str(&text)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        if let Some(dir) = Path::new(path).parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = format!("{}.tmp", path);
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// `ticker`'s versions, newest first.
+    pub fn versions(&self, ticker: &Ticker) -> Vec<&ModelVersion> {
+        let mut versions: Vec<&ModelVersion> = self.versions.iter().filter(|v| &v.ticker == ticker).collect();
+        versions.sort_by_key(|v| std::cmp::Reverse(v.version));
+        versions
+    }
+
+    pub fn active(&self, ticker: &Ticker) -> Option<&ModelVersion> {
+        let version = *self.active.get(ticker)?;
+        self.versions.iter().find(|v| &v.ticker == ticker && v.version == version)
+    }
+
+    /// The number and directory the next version of `ticker` is trained
+    /// into, past any directory left without a registry entry.
+    pub fn next_version(&self, ticker: &Ticker) -> (u32, PathBuf) {
+        let mut version = self.versions(ticker).first().map_or(1, |v| v.version + 1);
+        loop {
+            let dir = Path::new(MODELS_DIR).join(ticker.as_str()).join(format!("v{}", version));
+            if !dir.exists() {
+                return (version, dir);
+            }
+            version += 1;
+        }
+    }
+
+    /// Records a newly trained version and makes it the active one.
+    pub fn register(&mut self, version: ModelVersion) {
+        self.active.insert(version.ticker.clone(), version.version);
+        self.versions.retain(|v| !(v.ticker == version.ticker && v.version == version.version));
+        self.versions.push(version);
+    }
+
+    /// Makes `version` the one `ticker` predicts with.
+    pub fn set_active(&mut self, ticker: &Ticker, version: u32) -> Result<(), String> {
+        if !self.versions.iter().any(|v| &v.ticker == ticker && v.version == version) {
+            return Err(format!("{} has no v{}", ticker, version));
+        }
+        self.active.insert(ticker.clone(), version);
+        Ok(())
+    }
+}
+
+/// The models view: a ticker's versions, to pick the one it predicts with.
+#[derive(Debug)]
+pub struct ModelsView {
+    pub ticker: Ticker,
+    pub registry: Registry,
+    /// Row in `registry.versions(&ticker)`.
+    pub selected: usize,
+    pub message: String,
+}
+
+impl ModelsView {
+    pub fn selected_version(&self) -> Option<&ModelVersion> {
+        self.registry.versions(&self.ticker).get(self.selected).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_versions_take_over_until_rolled_back() {
+        let aapl = Ticker::parse("AAPL").unwrap();
+        let mut registry = Registry::default();
+        assert_eq!(registry.next_version(&aapl), (1, PathBuf::from("models/AAPL/v1")));
+        for _ in 0..2 {
+            let (n, dir) = registry.next_version(&aapl);
+            registry.register(ModelVersion::new(&aapl, n, dir, &[]));
+        }
+        assert_eq!(registry.active(&aapl).map(|v| v.version), Some(2));
+        assert_eq!(registry.versions(&aapl).iter().map(|v| v.version).collect::<Vec<_>>(), vec![2, 1]);
+        registry.set_active(&aapl, 1).unwrap();
+        assert_eq!(registry.active(&aapl).map(|v| v.label()), Some("AAPL v1".to_string()));
+        assert!(registry.set_active(&aapl, 7).is_err());
+        assert_eq!(registry.next_version(&aapl).0, 3);
+    }
+}
@@ -0,0 +1,71 @@
+//! Conditional GETs for the quote endpoints.
+//!
+//! Responses are kept in memory with their `ETag` / `Last-Modified`
+//! validators. Later requests for the same URL send them back, and a
+//! `304 Not Modified` reuses the stored body. Outside market hours most
+//! quote polls become empty round trips, which also spend less of a
+//! provider's rate limit on some plans.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::time::Instant;
+
+use crate::net;
+
+/// URLs remembered at once; the least recently used is dropped past this.
+const MAX_ENTRIES: usize = 256;
+
+struct Entry {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    body: String,
+    used: Instant,
+}
+
+#[derive(Default)]
+pub struct HttpCache {
+    entries: HashMap<String, Entry>,
+}
+
+impl HttpCache {
+    /// Fetches `url` with `headers` (e.g. an API key), revalidating any
+    /// stored copy instead of downloading it again.
+    pub fn get(&mut self, url: &str, headers: &[(&str, &str)]) -> Result<String, Box<dyn Error>> {
+        let mut headers = headers.to_vec();
+        if let Some(entry) = self.entries.get(url) {
+            if let Some(etag) = &entry.etag {
+                headers.push(("If-None-Match", etag.as_str()));
+            }
+            if let Some(modified) = &entry.last_modified {
+                headers.push(("If-Modified-Since", modified.as_str()));
+            }
+        }
+        let response = net::client().get(url, &headers)?;
+        if response.status() == 304
+            && let Some(entry) = self.entries.get_mut(url)
+        {
+            entry.used = Instant::now();
+            return Ok(entry.body.clone());
+        }
+        let etag = response.header("ETag").map(str::to_string);
+        let last_modified = response.header("Last-Modified").map(str::to_string);
+        let body = response.into_string()?;
+        if etag.is_some() || last_modified.is_some() {
+            self.insert(url, Entry { etag, last_modified, body: body.clone(), used: Instant::now() });
+        } else {
+            // Nothing to revalidate with next time.
+            self.entries.remove(url);
+        }
+        Ok(body)
+    }
+
+    fn insert(&mut self, url: &str, entry: Entry) {
+        if self.entries.len() >= MAX_ENTRIES && !self.entries.contains_key(url) {
+            let oldest = self.entries.iter().min_by_key(|(_, e)| e.used).map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(url.to_string(), entry);
+    }
+}
@@ -0,0 +1,148 @@
+//! The one HTTP client every Rust-side HTTP request goes through.
+//!
+//! It is built once from the `[net]` section of `stm.toml` and shares a
+//! connection pool, timeouts and user agent between callers. Transport
+//! errors, throttling responses (429, 503) and other server errors are
+//! retried with backoff.
+//! When a host says to slow down, every caller waits out its `Retry-After`
+//! instead of just the one that got the response. The Python downloader
+//! gets the same timeout and user agent through `python_env`.
+
+pub mod cache;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::NetConfig;
+
+/// Longest `Retry-After` honoured; anything longer fails the request now
+/// rather than stalling its thread.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+static CLIENT: OnceLock<Client> = OnceLock::new();
+
+pub struct Client {
+    agent: ureq::Agent,
+    config: NetConfig,
+    // Hosts that asked for a pause, and until when.
+    cool_down: Mutex<HashMap<String, Instant>>,
+}
+
+/// Configures the shared client. Only the first call has any effect;
+/// requests made before it use the defaults.
+pub fn init(config: &NetConfig) {
+    let _ = CLIENT.set(Client::new(config.clone()));
+}
+
+pub fn client() -> &'static Client {
+    CLIENT.get_or_init(|| Client::new(NetConfig::default()))
+}
+
+/// Environment for the Python scripts, so their requests use the same
+/// timeout and user agent.
+pub fn python_env() -> [(&'static str, String); 2] {
+    let config = &client().config;
+    [
+        ("STM_HTTP_TIMEOUT", config.timeout_secs.to_string()),
+        ("STM_USER_AGENT", config.user_agent.clone()),
+    ]
+}
+
+impl Client {
+    fn new(config: NetConfig) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(config.timeout_secs.max(1)))
+            .timeout_connect(Duration::from_secs(config.connect_timeout_secs.max(1)))
+            .user_agent(&config.user_agent)
+            .max_idle_connections_per_host(config.pool_per_host)
+            .build();
+        Self { agent, config, cool_down: Mutex::new(HashMap::new()) }
+    }
+
+    /// GETs `url` with the given extra headers. Non-error responses,
+    /// including `304 Not Modified`, are returned as is.
+    pub fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<ureq::Response, Box<dyn Error>> {
+        self.send("GET", url, headers, None)
+    }
+
+    /// Sends `method` with an optional JSON `body`. Throttled requests are
+    /// retried whatever the method, since the host didn't act on them, but
+    /// transport and other server errors only for GET and DELETE: a POST
+    /// may have gone through before the connection dropped.
+    pub fn send(
+        &self,
+        method: &str,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: Option<&str>,
+    ) -> Result<ureq::Response, Box<dyn Error>> {
+        let host = host_of(url).to_string();
+        let idempotent = matches!(method, "GET" | "DELETE");
+        let mut attempt = 0;
+        loop {
+            self.wait_for(&host);
+            let mut request = self.agent.request(method, url);
+            for (name, value) in headers {
+                request = request.set(name, value);
+            }
+            let sent = match body {
+                Some(body) => request.set("Content-Type", "application/json").send_string(body),
+                None => request.call(),
+            };
+            let retry_in = match sent {
+                Ok(response) => return Ok(response),
+                Err(ureq::Error::Status(code @ (429 | 503), response)) if attempt < self.config.retries => {
+                    let wait = retry_after(&response).unwrap_or_else(|| self.backoff(attempt));
+                    if wait > MAX_RETRY_AFTER {
+                        return Err(format!("{} asked to wait {}s (HTTP {})", host, wait.as_secs(), code).into());
+                    }
+                    self.pause(&host, wait);
+                    wait
+                }
+                Err(ureq::Error::Status(500..=599, _)) | Err(ureq::Error::Transport(_))
+                    if idempotent && attempt < self.config.retries =>
+                {
+                    self.backoff(attempt)
+                }
+                Err(e) => return Err(e.into()),
+            };
+            thread::sleep(retry_in);
+            attempt += 1;
+        }
+    }
+
+    /// Doubles from `backoff_ms` with each attempt.
+    fn backoff(&self, attempt: u32) -> Duration {
+        Duration::from_millis(self.config.backoff_ms.saturating_mul(1 << attempt.min(6)))
+    }
+
+    fn pause(&self, host: &str, wait: Duration) {
+        if let Ok(mut cool_down) = self.cool_down.lock() {
+            let until = Instant::now() + wait;
+            let entry = cool_down.entry(host.to_string()).or_insert(until);
+            *entry = (*entry).max(until);
+        }
+    }
+
+    /// Sleeps until `host` is out of any pause it asked for.
+    fn wait_for(&self, host: &str) {
+        let until = self.cool_down.lock().ok().and_then(|c| c.get(host).copied());
+        if let Some(wait) = until.and_then(|u| u.checked_duration_since(Instant::now())) {
+            thread::sleep(wait);
+        }
+    }
+}
+
+fn host_of(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split(['/', '?']).next().unwrap_or(rest)
+}
+
+/// `Retry-After` in its delay-seconds form; HTTP dates are rare enough from
+/// quote APIs to fall back to backoff.
+fn retry_after(response: &ureq::Response) -> Option<Duration> {
+    response.header("Retry-After")?.trim().parse().ok().map(Duration::from_secs)
+}
@@ -0,0 +1,171 @@
+//! Recent headlines for the selected ticker, for the News panel.
+//!
+//! With `[news] provider = "rss"` they come from Yahoo Finance's per-ticker
+//! RSS feed, which needs no key; with `finnhub`, from its company news
+//! endpoint. Fetched headlines are cached per ticker for `refresh_mins`, so
+//! going back to a ticker shows its news at once rather than fetching it
+//! again.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local, TimeZone};
+use serde::Deserialize;
+
+use crate::config::{NewsConfig, NewsProvider};
+use crate::ids::Ticker;
+use crate::net;
+
+const RSS_URL: &str = "https://feeds.finance.yahoo.com/rss/2.0/headline";
+const FINNHUB_URL: &str = "https://finnhub.io/api/v1/company-news";
+/// Days of Finnhub news asked for, up to today.
+const FINNHUB_DAYS: i64 = 7;
+/// Headlines kept per ticker, newest first.
+const MAX_HEADLINES: usize = 50;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Headline {
+    pub title: String,
+    pub link: String,
+    /// Publisher, where the provider names one.
+    pub source: String,
+    pub published: Option<DateTime<Local>>,
+}
+
+/// Fetches `ticker`'s recent headlines, newest first.
+pub fn fetch(config: &NewsConfig, ticker: &Ticker) -> Result<Vec<Headline>, Box<dyn Error>> {
+    let mut headlines = match config.provider {
+        NewsProvider::Rss => {
+            let url = format!("{}?s={}&region=US&lang=en-US", RSS_URL, ticker);
+            parse_rss(&net::client().get(&url, &[])?.into_string()?)
+        }
+        NewsProvider::Finnhub => {
+            if config.api_key.is_empty() {
+                return Err("finnhub needs [news] api_key".into());
+            }
+            let to = Local::now().date_naive();
+            let from = to - chrono::Duration::days(FINNHUB_DAYS);
+            let url = format!("{}?symbol={}&from={}&to={}&token={}", FINNHUB_URL, ticker, from, to, config.api_key);
+            parse_finnhub(&net::client().get(&url, &[])?.into_string()?)?
+        }
+    };
+    headlines.sort_by_key(|h| std::cmp::Reverse(h.published));
+    headlines.truncate(MAX_HEADLINES);
+    Ok(headlines)
+}
+
+/// The `<item>`s of an RSS 2.0 feed. Items without a title are skipped.
+fn parse_rss(body: &str) -> Vec<Headline> {
+    body.split("<item>")
+        .skip(1)
+        .filter_map(|item| {
+            let item = item.split("</item>").next()?;
+            let title = tag(item, "title").filter(|t| !t.is_empty())?;
+            Some(Headline {
+                title,
+                link: tag(item, "link").unwrap_or_default(),
+                source: tag(item, "source").unwrap_or_default(),
+                published: tag(item, "pubDate")
+                    .and_then(|d| DateTime::parse_from_rfc2822(&d).ok())
+                    .map(|d| d.with_timezone(&Local)),
+            })
+        })
+        .collect()
+}
+
+/// Text of the first `<name>` element in `xml`, unwrapped from CDATA and
+/// with the common entities decoded.
+fn tag(xml: &str, name: &str) -> Option<String> {
+    let start = xml.find(&format!("<{}", name))?;
+    let open_end = start + xml[start..].find('>')? + 1;
+    let close = open_end + xml[open_end..].find(&format!("</{}>", name))?;
+    let text = xml[open_end..close].trim();
+    let text = text.strip_prefix("<![CDATA[").and_then(|t| t.strip_suffix("]]>")).unwrap_or(text);
+    Some(
+        text.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&#39;", "'")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&")
+            .trim()
+            .to_string(),
+    )
+}
+
+fn parse_finnhub(body: &str) -> Result<Vec<Headline>, Box<dyn Error>> {
+    #[derive(Deserialize)]
+    struct Article {
+        headline: String,
+        #[serde(default)]
+        url: String,
+        #[serde(default)]
+        source: String,
+        #[serde(default)]
+        datetime: i64,
+    }
+    let articles: Vec<Article> = serde_json::from_str(body)?;
+    Ok(articles
+        .into_iter()
+        .filter(|a| !a.headline.trim().is_empty())
+        .map(|a| Headline {
+            title: a.headline.trim().to_string(),
+            link: a.url,
+            source: a.source,
+            published: (a.datetime > 0).then(|| Local.timestamp_opt(a.datetime, 0).single()).flatten(),
+        })
+        .collect())
+}
+
+/// Headlines fetched so far, per ticker, with when they were fetched.
+#[derive(Debug, Default)]
+pub struct NewsCache {
+    entries: HashMap<Ticker, (Instant, Vec<Headline>)>,
+}
+
+impl NewsCache {
+    pub fn get(&self, ticker: &Ticker) -> Option<&[Headline]> {
+        self.entries.get(ticker).map(|(_, headlines)| &headlines[..])
+    }
+
+    /// Whether `ticker`'s headlines are missing or older than `max_age`.
+    pub fn is_stale(&self, ticker: &Ticker, max_age: Duration) -> bool {
+        self.entries.get(ticker).is_none_or(|(at, _)| at.elapsed() >= max_age)
+    }
+
+    pub fn insert(&mut self, ticker: Ticker, headlines: Vec<Headline>) {
+        self.entries.insert(ticker, (Instant::now(), headlines));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rss_reads_items_with_cdata_and_entities() {
+        let body = r#"<?xml version="1.0"?><rss><channel><title>Yahoo</title>
+            <item><title><![CDATA[Apple &amp; the AI race]]></title><link>https://example.com/a</link>
+            <pubDate>Mon, 03 Jun 2024 14:30:00 +0000</pubDate></item>
+            <item><title>Q&amp;A: what&#39;s next</title><source url="https://x">Reuters</source></item>
+            <item><title></title></item></channel></rss>"#;
+        let headlines = parse_rss(body);
+        assert_eq!(headlines.len(), 2);
+        assert_eq!(headlines[0].title, "Apple & the AI race");
+        assert_eq!(headlines[0].link, "https://example.com/a");
+        assert_eq!(headlines[0].published.map(|d| d.timestamp()), Some(1_717_425_000));
+        assert_eq!((headlines[1].title.as_str(), headlines[1].source.as_str()), ("Q&A: what's next", "Reuters"));
+        assert_eq!(headlines[1].published, None);
+    }
+
+    #[test]
+    fn parse_finnhub_skips_untitled_articles() {
+        let body = r#"[{"headline":"Apple beats","url":"https://e.com","source":"CNBC","datetime":1717425000},{"headline":" "}]"#;
+        let headlines = parse_finnhub(body).unwrap();
+        assert_eq!(headlines.len(), 1);
+        assert_eq!(headlines[0].source, "CNBC");
+        assert_eq!(headlines[0].published.map(|d| d.timestamp()), Some(1_717_425_000));
+        assert!(parse_finnhub(r#"{"error":"Invalid API key"}"#).is_err());
+    }
+}
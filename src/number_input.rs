@@ -0,0 +1,179 @@
+//! Numeric form fields.
+//!
+//! A `NumberInput` describes one field (its bounds, step and what a
+//! percentage is taken of) and edits and parses the field's text, so forms
+//! keep plain strings (which drafts autosave) while sharing one set of rules:
+//!
+//! - digits, `.`, and `k`/`m`/`b` suffixes (`5m` is 5,000,000) are typed;
+//! - `+` and `-` step the value, except a leading `-` where negatives are
+//!   allowed;
+//! - a trailing `%` makes the value a percentage of `percent_of`, e.g. `25%`
+//!   of an account's balance.
+
+use crossterm::event::KeyCode;
+
+#[derive(Debug, Clone, Copy)]
+pub struct NumberInput {
+    /// Named in error messages.
+    pub label: &'static str,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Amount `+`/`-` add or take away.
+    pub step: f64,
+    /// Decimal places a stepped value is written with.
+    pub decimals: usize,
+    /// What `N%` is a percentage of; `%` is rejected without one.
+    pub percent_of: Option<f64>,
+}
+
+impl NumberInput {
+    pub fn new(label: &'static str) -> Self {
+        Self { label, min: None, max: None, step: 1.0, decimals: 2, percent_of: None }
+    }
+
+    pub fn min(mut self, min: f64) -> Self {
+        self.min = Some(min);
+        self
+    }
+
+    pub fn step(mut self, step: f64) -> Self {
+        self.step = step;
+        self
+    }
+
+    pub fn percent_of(mut self, base: f64) -> Self {
+        self.percent_of = Some(base);
+        self
+    }
+
+    /// Applies `code` to the field's `text`. Returns false for keys the
+    /// field doesn't use, which the form may handle itself.
+    pub fn handle_key(&self, text: &mut String, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Char('-') if text.is_empty() && self.min.is_none_or(|min| min < 0.0) => text.push('-'),
+            KeyCode::Char('+') => self.bump(text, 1.0),
+            KeyCode::Char('-') => self.bump(text, -1.0),
+            KeyCode::Char(c) if c.is_ascii_digit() || matches!(c, '.' | '%' | 'k' | 'm' | 'b' | 'K' | 'M' | 'B') => {
+                text.push(c)
+            }
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Steps the value by `direction` steps and writes it back in plain
+    /// form, clamped to the bounds. Text that doesn't parse yet steps from
+    /// zero (or the minimum).
+    fn bump(&self, text: &mut String, direction: f64) {
+        let current = self.parse_unchecked(text).unwrap_or(self.min.unwrap_or(0.0).max(0.0));
+        let mut value = current + direction * self.step;
+        if let Some(min) = self.min {
+            value = value.max(min);
+        }
+        if let Some(max) = self.max {
+            value = value.min(max);
+        }
+        *text = format!("{:.*}", self.decimals, value);
+    }
+
+    /// The field's value, or why it isn't acceptable.
+    pub fn parse(&self, text: &str) -> Result<f64, String> {
+        let value = self.parse_unchecked(text)?;
+        if let Some(min) = self.min
+            && value < min
+        {
+            return Err(format!("{} must be at least {}", self.label, min));
+        }
+        if let Some(max) = self.max
+            && value > max
+        {
+            return Err(format!("{} must be at most {}", self.label, max));
+        }
+        Ok(value)
+    }
+
+    fn parse_unchecked(&self, text: &str) -> Result<f64, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(format!("{} is required", self.label));
+        }
+        if let Some(pct) = text.strip_suffix('%') {
+            let base = self.percent_of.ok_or_else(|| format!("{} can't be a percentage here", self.label))?;
+            let pct = parse_amount(pct).ok_or_else(|| format!("{}: not a number: {}", self.label, text))?;
+            return Ok(base * pct / 100.0);
+        }
+        parse_amount(text).ok_or_else(|| format!("{}: not a number: {}", self.label, text))
+    }
+}
+
+/// A plain number with an optional `k`/`m`/`b` suffix.
+fn parse_amount(s: &str) -> Option<f64> {
+    let lower = s.trim().to_ascii_lowercase();
+    let (digits, scale) = match lower.chars().last()? {
+        'k' => (&lower[..lower.len() - 1], 1e3),
+        'm' => (&lower[..lower.len() - 1], 1e6),
+        'b' => (&lower[..lower.len() - 1], 1e9),
+        _ => (lower.as_str(), 1.0),
+    };
+    digits.parse::<f64>().ok().filter(|n| n.is_finite()).map(|n| n * scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(input: &NumberInput, keys: &str) -> String {
+        let mut text = String::new();
+        for c in keys.chars() {
+            input.handle_key(&mut text, KeyCode::Char(c));
+        }
+        text
+    }
+
+    #[test]
+    fn parse_reads_suffixes_and_percentages_within_bounds() {
+        let shares = NumberInput { max: Some(1e6), ..NumberInput::new("Shares").min(0.0) };
+        assert_eq!(shares.parse("2.5k"), Ok(2_500.0));
+        assert_eq!(shares.parse(" 1M "), Ok(1e6));
+        assert_eq!(NumberInput::new("Volume").parse("3b"), Ok(3e9));
+        assert_eq!(shares.parse("-1"), Err("Shares must be at least 0".to_string()));
+        assert_eq!(shares.parse("1.5m"), Err("Shares must be at most 1000000".to_string()));
+        assert_eq!(shares.parse(""), Err("Shares is required".to_string()));
+        assert_eq!(shares.parse("5x"), Err("Shares: not a number: 5x".to_string()));
+        assert!(shares.parse("k").is_err());
+
+        assert_eq!(shares.parse("25%"), Err("Shares can't be a percentage here".to_string()));
+        let amount = NumberInput::new("Amount").percent_of(8_000.0);
+        assert_eq!(amount.parse("25%"), Ok(2_000.0));
+        assert_eq!(amount.parse("-12.5%"), Ok(-1_000.0));
+        assert_eq!(amount.parse("0.1k%"), Ok(8_000.0));
+        assert_eq!(amount.parse("%"), Err("Amount: not a number: %".to_string()));
+    }
+
+    #[test]
+    fn minus_starts_a_negative_or_steps_down_and_steps_clamp() {
+        let amount = NumberInput::new("Amount");
+        assert_eq!(typed(&amount, "-5"), "-5");
+        // After the first character, - and + step instead.
+        assert_eq!(typed(&amount, "5-"), "4.00");
+        assert_eq!(typed(&amount, "5++"), "7.00");
+        assert_eq!(typed(&amount, "-5-"), "-6.00");
+        assert!(!amount.handle_key(&mut String::new(), KeyCode::Char('x')));
+
+        // Where negatives aren't allowed a leading - steps down, and steps
+        // stay within the bounds.
+        let balance = NumberInput { max: Some(250.0), ..NumberInput::new("Balance").min(0.0).step(100.0) };
+        assert_eq!(typed(&balance, "-"), "0.00");
+        assert_eq!(typed(&balance, "+++"), "250.00");
+        assert_eq!(typed(&balance, "50--"), "0.00");
+        // Unparsable text steps from the minimum.
+        let floor = NumberInput::new("Price").min(10.0);
+        assert_eq!(typed(&floor, "%+"), "11.00");
+        let mut text = "2k".to_string();
+        balance.handle_key(&mut text, KeyCode::Char('-'));
+        assert_eq!(text, "250.00");
+    }
+}
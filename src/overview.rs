@@ -0,0 +1,213 @@
+//! The market overview: the ML list's tickers as a treemap of cells
+//! coloured by % change, for an at-a-glance read of the whole list.
+//!
+//! With `[overview] size = "market_cap"` each cell's area is in proportion
+//! to the ticker's `[overview.market_caps]` entry, biggest first; tickers
+//! without one count as the median. Otherwise every cell is the same size
+//! and in ML list order. The list is split in two of about equal weight,
+//! across the longer side of the screen, and each half again, so cells stay
+//! near square. Arrows move to the neighbouring cell and Enter charts it.
+
+use crate::config::{OverviewConfig, TileSize};
+use crate::data::StockInfo;
+use crate::ids::Ticker;
+
+/// Height of a terminal cell over its width, so areas are split along
+/// their visually longer side.
+const CELL_ASPECT: f64 = 2.0;
+
+/// A rectangle in terminal cells, not yet rounded to whole ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Area {
+    pub x: f64,
+    pub y: f64,
+    pub w: f64,
+    pub h: f64,
+}
+
+impl Area {
+    fn center(&self) -> (f64, f64) {
+        (self.x + self.w / 2.0, self.y + self.h / 2.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tile {
+    pub ticker: Ticker,
+    pub price: f64,
+    pub pct_change: f64,
+    /// Market cap when sized by it.
+    pub market_cap: Option<f64>,
+    weight: f64,
+    /// Where it's drawn, from the last `fit`.
+    pub area: Area,
+}
+
+pub struct OverviewView {
+    pub size: TileSize,
+    pub tiles: Vec<Tile>,
+    pub selected: usize,
+    // The width and height laid out for.
+    fitted: (u16, u16),
+}
+
+impl OverviewView {
+    /// Tiles of the readable `stocks`; laid out by the first `fit`.
+    pub fn new(stocks: &[StockInfo], config: &OverviewConfig) -> Self {
+        let caps: Vec<Option<f64>> = stocks.iter().map(|s| config.market_caps.get(&s.ticker).copied().filter(|&c| c > 0.0)).collect();
+        let mut known: Vec<f64> = caps.iter().flatten().copied().collect();
+        known.sort_by(f64::total_cmp);
+        let median = known.get(known.len() / 2).copied().unwrap_or(1.0);
+        let mut tiles: Vec<Tile> = stocks
+            .iter()
+            .zip(caps)
+            .filter(|(s, _)| s.error.is_none())
+            .map(|(s, cap)| Tile {
+                ticker: s.ticker.clone(),
+                price: s.price,
+                pct_change: s.pct_change,
+                market_cap: cap.filter(|_| config.size == TileSize::MarketCap),
+                weight: match config.size {
+                    TileSize::Equal => 1.0,
+                    TileSize::MarketCap => cap.unwrap_or(median),
+                },
+                area: Area::default(),
+            })
+            .collect();
+        if config.size == TileSize::MarketCap {
+            tiles.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+        }
+        Self { size: config.size, tiles, selected: 0, fitted: (0, 0) }
+    }
+
+    /// Lays the tiles out over `width` by `height` cells, unless they
+    /// already are.
+    pub fn fit(&mut self, width: u16, height: u16) {
+        if self.fitted == (width, height) {
+            return;
+        }
+        self.fitted = (width, height);
+        let weights: Vec<f64> = self.tiles.iter().map(|t| t.weight).collect();
+        let areas = treemap(&weights, Area { x: 0.0, y: 0.0, w: width as f64, h: height as f64 });
+        for (tile, area) in self.tiles.iter_mut().zip(areas) {
+            tile.area = area;
+        }
+    }
+
+    pub fn selected(&self) -> Option<&Tile> {
+        self.tiles.get(self.selected)
+    }
+
+    /// Selects the nearest tile whose centre lies `dx` across or `dy` down
+    /// (each -1, 0 or 1) from the selected one's, favouring those in line
+    /// with it.
+    pub fn step(&mut self, dx: i8, dy: i8) {
+        let Some(from) = self.selected().map(|t| t.area.center()) else {
+            return;
+        };
+        let (dx, dy) = (dx as f64, dy as f64);
+        let nearest = self
+            .tiles
+            .iter()
+            .enumerate()
+            .filter_map(|(i, t)| {
+                let (x, y) = t.area.center();
+                // In visual units, so a row down counts as two columns.
+                let (ox, oy) = (x - from.0, (y - from.1) * CELL_ASPECT);
+                let ahead = ox * dx + oy * dy;
+                let aside = (ox * dy).abs() + (oy * dx).abs();
+                (ahead > 0.5).then_some((i, ahead + aside * 2.0))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, _)) = nearest {
+            self.selected = i;
+        }
+    }
+}
+
+/// Areas dividing `area` in proportion to `weights`, in their order.
+pub fn treemap(weights: &[f64], area: Area) -> Vec<Area> {
+    let mut areas = vec![Area::default(); weights.len()];
+    split(weights, area, &mut areas);
+    areas
+}
+
+fn split(weights: &[f64], area: Area, out: &mut [Area]) {
+    match weights.len() {
+        0 => return,
+        1 => {
+            out[0] = area;
+            return;
+        }
+        _ => {}
+    }
+    let total: f64 = weights.iter().sum();
+    // Where the running sum comes closest to half the total.
+    let (cut, before) = (1..weights.len())
+        .map(|i| (i, weights[..i].iter().sum::<f64>()))
+        .min_by(|a, b| (a.1 - total / 2.0).abs().total_cmp(&(b.1 - total / 2.0).abs()))
+        .expect("two or more weights");
+    let share = if total > 0.0 { before / total } else { cut as f64 / weights.len() as f64 };
+    let (first, second) = if area.w > area.h * CELL_ASPECT {
+        let w = area.w * share;
+        (Area { w, ..area }, Area { x: area.x + w, w: area.w - w, ..area })
+    } else {
+        let h = area.h * share;
+        (Area { h, ..area }, Area { y: area.y + h, h: area.h - h, ..area })
+    };
+    let (left, right) = out.split_at_mut(cut);
+    split(&weights[..cut], first, left);
+    split(&weights[cut..], second, right);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stock(ticker: &str, pct_change: f64) -> StockInfo {
+        StockInfo {
+            ticker: Ticker::parse(ticker).unwrap(),
+            price: 100.0,
+            change: pct_change,
+            pct_change,
+            gaps: Vec::new(),
+            return_3m: None,
+            error: None,
+            source: String::new(),
+            series: Default::default(),
+        }
+    }
+
+    #[test]
+    fn cells_split_the_screen_by_weight() {
+        let areas = treemap(&[2.0, 1.0, 1.0], Area { x: 0.0, y: 0.0, w: 80.0, h: 20.0 });
+        assert_eq!(areas[0], Area { x: 0.0, y: 0.0, w: 40.0, h: 20.0 });
+        assert_eq!(areas[1], Area { x: 40.0, y: 0.0, w: 40.0, h: 10.0 });
+        assert_eq!(areas[2], Area { x: 40.0, y: 10.0, w: 40.0, h: 10.0 });
+
+        let mut config = OverviewConfig { size: TileSize::MarketCap, ..OverviewConfig::default() };
+        for (ticker, cap) in [("MSFT", 2.0e12), ("IBM", 1.0e12), ("F", 1.0e12)] {
+            config.market_caps.insert(Ticker::parse(ticker).unwrap(), cap);
+        }
+        let stocks = [stock("IBM", -1.0), stock("MSFT", 2.0), stock("F", 0.5)];
+        let mut view = OverviewView::new(&stocks, &config);
+        view.fit(80, 20);
+        let order: Vec<&str> = view.tiles.iter().map(|t| t.ticker.as_str()).collect();
+        assert_eq!(order, ["MSFT", "IBM", "F"]);
+        assert_eq!(view.tiles.iter().map(|t| t.area).collect::<Vec<_>>(), areas);
+
+        view.step(1, 0);
+        assert_eq!(view.selected().unwrap().ticker.as_str(), "IBM");
+        view.step(0, 1);
+        assert_eq!(view.selected().unwrap().ticker.as_str(), "F");
+        view.step(0, 1);
+        assert_eq!(view.selected().unwrap().ticker.as_str(), "F");
+        view.step(-1, 0);
+        assert_eq!(view.selected().unwrap().ticker.as_str(), "MSFT");
+
+        // Without a cap a ticker counts as the median.
+        let view = OverviewView::new(&[stock("GM", 0.0), stock("IBM", -1.0), stock("MSFT", 2.0)], &config);
+        let gm = view.tiles.iter().find(|t| t.ticker.as_str() == "GM").unwrap();
+        assert_eq!((gm.market_cap, gm.weight), (None, 2.0e12));
+    }
+}
@@ -0,0 +1,171 @@
+//! Column queries over stored price history.
+//!
+//! `query` is the one path from the chart, the benchmark, jobs and other
+//! tools to the stored bars, in the ticker's `universe` root: it returns only the columns asked
+//! for, over a date range. Each file is parsed once and the bars kept in
+//! memory; later queries reuse them until the file's size or modification
+//! time changes, so panels reloading every second don't re-read unchanged
+//! files. Bars before a split the file isn't adjusted for are restated
+//! in shares after it, see `splits`. The CSVs download_stock.py writes are
+//! the only store today; another would slot in behind `cached_bars`.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+use chrono::NaiveDateTime;
+
+use crate::data::{self, Bar};
+use crate::date_range::DateRange;
+use crate::ids::Ticker;
+use crate::splits::{self, Split};
+use crate::universe;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Date,
+    Close,
+    Volume,
+}
+
+/// Result of a query, oldest bar first. Columns that weren't asked for are
+/// empty; the rest have one entry per bar.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Columns {
+    pub dates: Vec<NaiveDateTime>,
+    pub closes: Vec<f64>,
+    // `None` where the file has no volume for the bar.
+    pub volumes: Vec<Option<f64>>,
+}
+
+impl Columns {
+    fn from_bars(bars: &[Bar], columns: &[Column]) -> Self {
+        let mut out = Columns::default();
+        for column in columns {
+            match column {
+                Column::Date => out.dates = bars.iter().map(|b| b.at).collect(),
+                Column::Close => out.closes = bars.iter().map(|b| b.close).collect(),
+                Column::Volume => out.volumes = bars.iter().map(|b| b.volume).collect(),
+            }
+        }
+        out
+    }
+}
+
+struct Cached {
+    len: u64,
+    modified: Option<SystemTime>,
+    /// The splits the provider had reported when the file was read.
+    reported: Vec<Split>,
+    bars: Arc<Vec<Bar>>,
+    /// Those the bars were restated for.
+    splits: Vec<Split>,
+}
+
+static CACHE: OnceLock<Mutex<HashMap<PathBuf, Cached>>> = OnceLock::new();
+
+/// `columns` of `ticker`'s stored history within `range`.
+pub fn query(ticker: &Ticker, range: DateRange, columns: &[Column]) -> Result<Columns, Box<dyn Error>> {
+    query_in(&universe::dir_of(ticker).to_string_lossy(), ticker, range, columns)
+}
+
+/// `query` against the files in `dir`.
+pub fn query_in(dir: &str, ticker: &Ticker, range: DateRange, columns: &[Column]) -> Result<Columns, Box<dyn Error>> {
+    let bars = cached_bars(&ticker_path(dir, ticker))?;
+    Ok(Columns::from_bars(range.slice(&bars, |b| b.at), columns))
+}
+
+/// Whole bars of `ticker` within `range`, for callers that need every
+/// column together.
+pub fn bars(ticker: &Ticker, range: DateRange) -> Result<Vec<Bar>, Box<dyn Error>> {
+    let bars = cached_bars(&universe::path_of(ticker))?;
+    Ok(range.slice(&bars, |b| b.at).to_vec())
+}
+
+fn ticker_path(dir: &str, ticker: &Ticker) -> PathBuf {
+    Path::new(dir).join(format!("{}.csv", ticker))
+}
+
+/// Bars of the CSV at `path`, parsed again only if the file changed since
+/// the last call, or the provider has reported a split for it since.
+/// Failed reads aren't kept.
+pub fn cached_bars(path: &Path) -> Result<Arc<Vec<Bar>>, Box<dyn Error>> {
+    Ok(cached(path)?.0)
+}
+
+/// The splits `ticker`'s stored bars were restated for, oldest first.
+pub fn splits(ticker: &Ticker) -> Vec<Split> {
+    cached(&universe::path_of(ticker)).map(|(_, splits)| splits).unwrap_or_default()
+}
+
+/// Bars and the splits they were restated for.
+type Restated = (Arc<Vec<Bar>>, Vec<Split>);
+
+fn cached(path: &Path) -> Result<Restated, Box<dyn Error>> {
+    let meta = fs::metadata(path)?;
+    let (len, modified) = (meta.len(), meta.modified().ok());
+    let ticker = path.file_stem().and_then(|s| Ticker::parse(&s.to_string_lossy()).ok());
+    let reported = ticker.as_ref().map(splits::reported).unwrap_or_default();
+    let cache = CACHE.get_or_init(Default::default);
+    if let Some(hit) = cache.lock().unwrap().get(path)
+        && hit.len == len
+        && modified.is_some()
+        && hit.modified == modified
+        && hit.reported == reported
+    {
+        return Ok((Arc::clone(&hit.bars), hit.splits.clone()));
+    }
+    let (bars, splits) = data::read_series(&path.to_string_lossy())?;
+    let bars = Arc::new(bars);
+    let entry = Cached { len, modified, reported, bars: Arc::clone(&bars), splits: splits.clone() };
+    cache.lock().unwrap().insert(path.to_path_buf(), entry);
+    Ok((bars, splits))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 6, d).unwrap()
+    }
+
+    #[test]
+    fn query_returns_the_requested_columns_in_range() {
+        let dir = std::env::temp_dir().join(format!("stm-{}-prices", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("X.csv"),
+            "Date,Close,Volume\n2024-06-03,10.0,100\n2024-06-04,11.0,\n2024-06-05,12.0,300\n",
+        )
+        .unwrap();
+        let ticker = Ticker::parse("X").unwrap();
+        let range = DateRange { start: Some(day(4)), end: None };
+        let got = query_in(dir.to_str().unwrap(), &ticker, range, &[Column::Close, Column::Volume]).unwrap();
+        let all = query_in(dir.to_str().unwrap(), &ticker, DateRange::default(), &[Column::Date]).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(got.closes, vec![11.0, 12.0]);
+        assert_eq!(got.volumes, vec![None, Some(300.0)]);
+        assert!(got.dates.is_empty());
+        assert_eq!(all.dates.len(), 3);
+        assert_eq!(all.dates[0], day(3).and_hms_opt(0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn cached_bars_rereads_changed_files() {
+        let dir = std::env::temp_dir().join(format!("stm-{}-prices-changed", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("X.csv");
+        fs::write(&path, "Date,Close\n2024-06-03,10.0\n").unwrap();
+        assert_eq!(cached_bars(&path).unwrap().len(), 1);
+        fs::write(&path, "Date,Close\n2024-06-03,10.0\n2024-06-04,11.0\n").unwrap();
+        let bars = cached_bars(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(bars.len(), 2);
+    }
+}
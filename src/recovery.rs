@@ -0,0 +1,158 @@
+//! Autosave of unfinished input, restored on the next start.
+//!
+//! The main loop snapshots whatever the user is in the middle of typing
+//! every few seconds and writes it to `RECOVERY_PATH`. A clean quit removes
+//! the file, so finding one at startup means the last session died with
+//! drafts open.
+
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ids::AccountId;
+
+pub const RECOVERY_PATH: &str = ".stm_recovery.json";
+/// How often the main loop checks for changed drafts.
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Everything typed but not yet submitted.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Drafts {
+    #[serde(default)]
+    pub account_form: Option<AccountDraft>,
+    #[serde(default)]
+    pub trade_form: Option<TradeDraft>,
+    #[serde(default)]
+    pub search: Option<String>,
+    #[serde(default)]
+    pub filter: Option<String>,
+}
+
+impl Drafts {
+    pub fn is_empty(&self) -> bool {
+        *self == Drafts::default()
+    }
+}
+
+/// An open account form. The edited account is stored by name rather than
+/// index, since the list may have changed by the time it's restored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountDraft {
+    pub editing: Option<AccountId>,
+    pub fields: [String; 3],
+    pub active: usize,
+}
+
+/// An open trade form.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradeDraft {
+    pub fields: [String; 4],
+    pub active: usize,
+    #[serde(default)]
+    pub transfer: bool,
+    #[serde(default)]
+    pub dividend: bool,
+}
+
+/// Drafts left by a session that didn't exit cleanly, if any.
+pub fn load(path: &str) -> Result<Option<Drafts>, Box<dyn Error>> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(Some(serde_json::from_str(&text)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Writes `drafts`, or removes the file when there's nothing to keep.
+pub fn save(path: &str, drafts: &Drafts) -> Result<(), Box<dyn Error>> {
+    if drafts.is_empty() {
+        return clear(path);
+    }
+    // Write then rename, so a crash mid-write leaves the previous drafts.
+    let tmp = format!("{}.tmp", path);
+    fs::write(&tmp, serde_json::to_string_pretty(drafts)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+pub fn clear(path: &str) -> Result<(), Box<dyn Error>> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::AccountSummary;
+    use crate::app::{App, Focus, MLMode};
+    use crate::config::Config;
+
+    fn drafts() -> Drafts {
+        Drafts {
+            account_form: Some(AccountDraft {
+                editing: Some(AccountId::parse("Brokerage").unwrap()),
+                fields: ["Brokerage".to_string(), "12k".to_string(), String::new()],
+                active: 1,
+            }),
+            trade_form: Some(TradeDraft {
+                fields: ["Brokerage".to_string(), "-5%".to_string(), "AAPL".to_string(), "trim".to_string()],
+                active: 3,
+                transfer: false,
+                dividend: true,
+            }),
+            search: Some("msft 1h".to_string()),
+            filter: None,
+        }
+    }
+
+    #[test]
+    fn drafts_round_trip_and_empty_ones_remove_the_file() {
+        let dir = std::env::temp_dir().join(format!("stm-{}-recovery", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("drafts.json");
+        let path = path.to_str().unwrap();
+
+        assert_eq!(load(path).unwrap(), None);
+        save(path, &drafts()).unwrap();
+        assert_eq!(load(path).unwrap(), Some(drafts()));
+        // Nothing left to keep: the file goes, as on a clean quit.
+        save(path, &Drafts::default()).unwrap();
+        assert!(!fs::exists(path).unwrap());
+        save(path, &Drafts::default()).unwrap();
+
+        // Fields added later default when reading an older file.
+        fs::write(path, r#"{"search": "aapl"}"#).unwrap();
+        assert_eq!(load(path).unwrap(), Some(Drafts { search: Some("aapl".to_string()), ..Drafts::default() }));
+        fs::write(path, "{").unwrap();
+        assert!(load(path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn restoring_an_edit_of_a_removed_account_opens_a_new_one() {
+        let mut app = App::new(&Config::default());
+        app.accounts = vec![AccountSummary::new(AccountId::parse("Cash").unwrap(), 500.0, "USD")];
+        app.restore_drafts(drafts());
+        let form = app.account_form.as_ref().unwrap();
+        assert_eq!((form.editing, form.fields[1].as_str(), form.active), (None, "12k", 1));
+        assert_eq!(app.focus, Focus::Accounts);
+        assert!(app.trade_form.as_ref().is_some_and(|f| f.dividend && f.fields[1] == "-5%"));
+        assert_eq!((app.ml_mode, app.search_input.as_str()), (MLMode::Search, "msft 1h"));
+
+        // While the account is still there the form edits it, and saving
+        // the drafts again gives back what was restored.
+        let mut app = App::new(&Config::default());
+        app.accounts = vec![
+            AccountSummary::new(AccountId::parse("Cash").unwrap(), 500.0, "USD"),
+            AccountSummary::new(AccountId::parse("Brokerage").unwrap(), 10_000.0, "USD"),
+        ];
+        app.restore_drafts(drafts());
+        assert_eq!(app.account_form.as_ref().unwrap().editing, Some(1));
+        assert_eq!(app.drafts(), drafts());
+    }
+}
@@ -0,0 +1,202 @@
+//! Background reloads of the data behind each panel.
+//!
+//! Every panel's data comes from a `Source`, reloaded off the UI thread: the
+//! files that change outside the app (prices, trades, the chart series) on
+//! a timer, and any source on demand with the refresh key. Each source has a
+//! `Status`, which the panel shows: "loading…" while a requested reload is
+//! running, or the error when the last one failed. Exchange rates are a
+//! source too, fetched on their own `fx::FETCH_INTERVAL` when enabled, and
+//! so are headlines, fetched when the selected ticker has none cached, and
+//! the earnings calendar, refreshed from its cache file every
+//! `[calendar] refresh_hours`.
+//!
+//! Nothing is read before the first frame: the ML list comes in with the
+//! first timed reload, showing as loading until then, and the prediction
+//! history only once the Model Performance panel is shown or a report
+//! needs it, so a large `pre_stock/` or a long log doesn't hold up startup.
+//! The model registry and the trading game are sources as well, read when
+//! their views are opened, which show once the read is back.
+
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use crate::app::AppEvent;
+use crate::errors::AppError;
+use crate::config::{CalendarConfig, NewsConfig};
+use crate::events::{self, EventCache};
+use crate::fx;
+use crate::game::{self, Game, GAME_PATH};
+use crate::news::{self, Headline};
+use crate::accounts::{self, AccountSummary};
+use crate::data::{self, Benchmark, PriceSeries, StockInfo};
+use crate::ids::Ticker;
+use crate::ml::history::{self, Prediction, HISTORY_PATH};
+use crate::ml::registry::{Registry, REGISTRY_PATH};
+use crate::trades::{self, TradeCursor, TradeRead};
+use crate::universe::SeriesKey;
+
+/// How often the timed sources are reloaded.
+pub const AUTO_REFRESH: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Source {
+    Stocks,
+    Trades,
+    Accounts,
+    Chart,
+    Benchmark,
+    Fx,
+    News,
+    Calendar,
+    History,
+    Models,
+    Game,
+}
+
+impl Source {
+    /// Sources reloaded on the `AUTO_REFRESH` timer. Accounts are only
+    /// written by the app itself, so they're read at startup and on demand.
+    pub const TIMED: [Source; 4] = [Source::Stocks, Source::Trades, Source::Chart, Source::Benchmark];
+}
+
+/// Load state of one source, as its panels show it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Status {
+    #[default]
+    Ready,
+    Loading,
+    Failed(String),
+}
+
+/// A reload to run, with what it needs to know.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    Stocks,
+    /// Rows added since the cursor's read, or the whole history without one.
+    Trades(Option<TradeCursor>),
+    Accounts,
+    /// The selected ticker's series, from the file of its row, those of
+    /// the marked tickers and that of the benchmark overlaid on it.
+    Chart { ticker: Option<Ticker>, series: Option<SeriesKey>, marked: Vec<Ticker>, overlay: Option<Ticker> },
+    Benchmark(Ticker),
+    /// Reference rates against the base currency.
+    Fx(String),
+    /// Headlines for the selected ticker.
+    News { ticker: Option<Ticker>, config: NewsConfig },
+    /// Earnings and dividend dates of the listed tickers, fetched for those
+    /// whose cached ones are stale.
+    Calendar { tickers: Vec<Ticker>, config: CalendarConfig },
+    /// The prediction log, with actuals resolved.
+    History,
+    /// The model registry, for the Models view.
+    Models,
+    /// The saved trading game, if there is one.
+    Game,
+}
+
+impl Request {
+    pub fn source(&self) -> Source {
+        match self {
+            Request::Stocks => Source::Stocks,
+            Request::Trades(_) => Source::Trades,
+            Request::Accounts => Source::Accounts,
+            Request::Chart { .. } => Source::Chart,
+            Request::Benchmark(_) => Source::Benchmark,
+            Request::Fx(_) => Source::Fx,
+            Request::News { .. } => Source::News,
+            Request::Calendar { .. } => Source::Calendar,
+            Request::History => Source::History,
+            Request::Models => Source::Models,
+            Request::Game => Source::Game,
+        }
+    }
+
+    fn load(self) -> Result<Loaded, AppError> {
+        Ok(match self {
+            Request::Stocks => Loaded::Stocks(data::load_stocks()),
+            Request::Trades(since) => Loaded::Trades(
+                trades::read_trades_since("trading_history.csv", since.as_ref())
+                    .map_err(|e| AppError::load("trading_history.csv", e))?,
+            ),
+            Request::Accounts => Loaded::Accounts(
+                accounts::read_accounts_from_csv("account_summary.csv").map_err(|e| AppError::load("account_summary.csv", e))?,
+            ),
+            Request::Chart { ticker, series, marked, overlay } => Loaded::Chart {
+                series: match (&ticker, &series) {
+                    (Some(ticker), Some(key)) => data::load_series_at(ticker, key),
+                    (Some(ticker), None) => data::load_series(ticker),
+                    (None, _) => PriceSeries::default(),
+                },
+                compare: marked.iter().map(data::load_series).collect(),
+                overlay: overlay.as_ref().map(data::load_series),
+            },
+            Request::Benchmark(ticker) => Loaded::Benchmark(Benchmark::load(&ticker)),
+            Request::Fx(base) => {
+                Loaded::Fx(fx::fetch(&base).map_err(|e| AppError::Feed { source: "fx", message: e.to_string() })?)
+            }
+            Request::News { ticker: None, .. } => Loaded::News { ticker: None, headlines: Vec::new() },
+            Request::News { ticker: Some(ticker), config } => {
+                let headlines =
+                    news::fetch(&config, &ticker).map_err(|e| AppError::Feed { source: "news", message: e.to_string() })?;
+                Loaded::News { ticker: Some(ticker), headlines }
+            }
+            Request::Calendar { tickers, config } => {
+                let now = chrono::Local::now().naive_local();
+                let (cache, failed) = events::refresh(&config, &tickers, events::CALENDAR_PATH, now)
+                    .map_err(|e| AppError::Feed { source: "calendar", message: e.to_string() })?;
+                Loaded::Calendar { cache, failed }
+            }
+            Request::History => Loaded::History(
+                history::load_resolved(HISTORY_PATH).map_err(|e| AppError::load(HISTORY_PATH, e))?,
+            ),
+            Request::Models => Loaded::Models(Registry::load(REGISTRY_PATH).map_err(|e| AppError::load(REGISTRY_PATH, e))?),
+            Request::Game => Loaded::Game(game::load(GAME_PATH).map_err(|e| AppError::load(GAME_PATH, e))?),
+        })
+    }
+}
+
+/// Fresh data for one source.
+#[derive(Debug)]
+pub enum Loaded {
+    Stocks(Vec<StockInfo>),
+    Trades(TradeRead),
+    Accounts(Vec<AccountSummary>),
+    Chart { series: PriceSeries, compare: Vec<PriceSeries>, overlay: Option<PriceSeries> },
+    Benchmark(Benchmark),
+    Fx(BTreeMap<String, f64>),
+    News { ticker: Option<Ticker>, headlines: Vec<Headline> },
+    /// The whole cache, and the tickers whose fetch failed this time.
+    Calendar { cache: EventCache, failed: Vec<Ticker> },
+    History(Vec<Prediction>),
+    Models(Registry),
+    Game(Option<Game>),
+}
+
+/// Runs each request on its own thread and hands the results back to the
+/// UI thread.
+pub struct Loader {
+    tx: Sender<AppEvent>,
+    rx: Receiver<AppEvent>,
+}
+
+impl Loader {
+    pub fn start() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self { tx, rx }
+    }
+
+    pub fn submit(&self, request: Request) {
+        let tx = self.tx.clone();
+        thread::spawn(move || {
+            let source = request.source();
+            let _ = tx.send(AppEvent::Loaded { source, result: request.load() });
+        });
+    }
+
+    /// Reloads that finished since the last call.
+    pub fn poll(&self) -> Vec<AppEvent> {
+        self.rx.try_iter().collect()
+    }
+}
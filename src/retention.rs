@@ -0,0 +1,163 @@
+//! Data retention: trimming old intraday bars and logged predictions.
+//!
+//! `[retention]` in `stm.toml` sets how many days of each kind of data to
+//! keep; anything unset is kept forever. The dashboard runs the policy as
+//! a maintenance job at startup and then daily, and the retention report
+//! key runs it as a dry run that only lists what would go. Rows are dropped
+//! in place, keeping the files' header rows and everything newer as is.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use csv::{ReaderBuilder, StringRecord};
+
+use crate::config::RetentionConfig;
+use crate::data::{self, Interval};
+use crate::universe::{self, Root};
+
+pub const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Rows of one file older than the policy allows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prune {
+    pub path: PathBuf,
+    /// Rows dated before this are dropped.
+    pub before: NaiveDate,
+    pub rows: usize,
+    pub kept: usize,
+    // Field holding each row's date.
+    column: usize,
+}
+
+impl fmt::Display for Prune {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} rows before {} ({} kept)", self.path.display(), self.rows, self.before, self.kept)
+    }
+}
+
+/// What `config` would drop from the `universe` roots and `history_path`
+/// as of `today`. Files with nothing to drop aren't listed.
+pub fn plan(config: &RetentionConfig, history_path: &str, today: NaiveDate) -> Result<Vec<Prune>, Box<dyn Error>> {
+    plan_in(config, universe::roots(), Path::new(history_path), today)
+}
+
+/// `plan` for the price files under `roots` and the prediction log at
+/// `history`.
+pub fn plan_in(config: &RetentionConfig, roots: &[Root], history: &Path, today: NaiveDate) -> Result<Vec<Prune>, Box<dyn Error>> {
+    let mut prunes = Vec::new();
+    if let Some(days) = config.intraday_days {
+        let before = today - chrono::Duration::days(days.into());
+        for root in roots {
+            prunes.extend(plan_root(root, before)?);
+        }
+    }
+    if let Some(days) = config.predictions_days
+        && history.exists()
+    {
+        // By `predicted_at`, when the model ran.
+        prunes.extend(check(history, 1, today - chrono::Duration::days(days.into()))?);
+    }
+    Ok(prunes)
+}
+
+/// Intraday series under `root` with bars before `before`.
+fn plan_root(root: &Root, before: NaiveDate) -> Result<Vec<Prune>, Box<dyn Error>> {
+    let mut prunes = Vec::new();
+    for series in root.csvs() {
+        let dir = series.path.parent().unwrap_or(&root.dir);
+        // Daily series are small and what the ML list and model use.
+        if data::read_interval_in(dir, &series.ticker) != Interval::OneDay {
+            prunes.extend(check(&series.path, 0, before)?);
+        }
+    }
+    Ok(prunes)
+}
+
+/// Drops the planned rows. Each file is written to a temporary file next
+/// to it and renamed over it, so an interrupted run leaves it whole.
+pub fn apply(prunes: &[Prune]) -> Result<(), Box<dyn Error>> {
+    for prune in prunes {
+        let (kept, ..) = split(&fs::read(&prune.path)?, prune.column, prune.before)?;
+        let mut tmp = prune.path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, kept)?;
+        fs::rename(&tmp, &prune.path)?;
+    }
+    Ok(())
+}
+
+fn check(path: &Path, column: usize, before: NaiveDate) -> Result<Option<Prune>, Box<dyn Error>> {
+    let (_, rows, kept) = split(&fs::read(path)?, column, before)?;
+    Ok((rows > 0).then(|| Prune { path: path.to_path_buf(), before, rows, kept, column }))
+}
+
+/// The file without its rows dated before `before`, with the number of
+/// dated rows dropped and kept. Rows whose `column` isn't a date, such as
+/// header rows, are kept.
+fn split(bytes: &[u8], column: usize, before: NaiveDate) -> Result<(Vec<u8>, usize, usize), Box<dyn Error>> {
+    let mut rdr = ReaderBuilder::new().has_headers(false).flexible(true).from_reader(bytes);
+    let mut record = StringRecord::new();
+    let mut kept = Vec::with_capacity(bytes.len());
+    let (mut dropped, mut dated) = (0, 0);
+    loop {
+        let start = rdr.position().byte() as usize;
+        if !rdr.read_record(&mut record)? {
+            break;
+        }
+        let end = rdr.position().byte() as usize;
+        match record.get(column).and_then(data::parse_timestamp) {
+            Some(at) if at.date() < before => dropped += 1,
+            date => {
+                dated += usize::from(date.is_some());
+                kept.extend_from_slice(&bytes[start..end]);
+            }
+        }
+    }
+    Ok((kept, dropped, dated))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plan_and_apply_drop_old_intraday_bars_and_predictions() {
+        let base = std::env::temp_dir().join(format!("stm-{}-retention", std::process::id()));
+        // Intraday bars in a subdirectory of the root.
+        let dir = base.join("intraday");
+        fs::create_dir_all(&dir).unwrap();
+        let header = "Price,Close\nTicker,X\nDatetime,\n";
+        let bars = "2024-05-01 09:30:00-04:00,1.0\n2024-06-01 09:30:00-04:00,2.0\n";
+        fs::write(dir.join("X.csv"), format!("{}{}", header, bars)).unwrap();
+        fs::write(dir.join("X.meta.json"), r#"{"interval":"5m"}"#).unwrap();
+        fs::write(base.join("D.csv"), format!("{}{}", header, bars)).unwrap();
+        let history = base.join("ml_history.csv");
+        fs::write(&history, "ticker,predicted_at,as_of\nX,2023-01-02T10:00:00,2023-01-01T00:00:00\n").unwrap();
+        let config = RetentionConfig { intraday_days: Some(30), predictions_days: Some(365), dry_run: false };
+        let today = NaiveDate::from_ymd_opt(2024, 6, 10).unwrap();
+
+        let root = Root {
+            name: "prices".to_string(),
+            dir: base.clone(),
+            provider: Default::default(),
+            refresh_mins: None,
+            max_depth: 1,
+        };
+        let prunes = plan_in(&config, &[root], &history, today).unwrap();
+        let summary: Vec<(usize, usize)> = prunes.iter().map(|p| (p.rows, p.kept)).collect();
+        assert_eq!(prunes[0].path, dir.join("X.csv"));
+        assert_eq!(summary, vec![(1, 1), (1, 0)]);
+        apply(&prunes).unwrap();
+        let x = fs::read_to_string(dir.join("X.csv")).unwrap();
+        let daily = fs::read_to_string(base.join("D.csv")).unwrap();
+        let log = fs::read_to_string(&history).unwrap();
+        fs::remove_dir_all(&base).unwrap();
+        assert_eq!(x, format!("{}2024-06-01 09:30:00-04:00,2.0\n", header));
+        assert_eq!(daily, format!("{}{}", header, bars));
+        assert_eq!(log, "ticker,predicted_at,as_of\n");
+    }
+}
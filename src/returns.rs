@@ -0,0 +1,143 @@
+//! Distribution of a ticker's daily returns, for the returns view.
+//!
+//! Returns are session-to-session percent changes of the last close of each
+//! day, so intraday series give daily figures too. The moments are
+//! population ones: σ is the root mean squared deviation and skew the third
+//! standardised moment.
+
+use chrono::NaiveDate;
+
+use crate::date_range::DateRange;
+use crate::ids::Ticker;
+use crate::prices::{self, Column};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReturnStats {
+    pub count: usize,
+    pub mean: f64,
+    pub std_dev: f64,
+    // Zero when every return is the same.
+    pub skew: f64,
+}
+
+/// One histogram bar: returns in `low..high` (the last bin includes
+/// `high`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bin {
+    pub low: f64,
+    pub high: f64,
+    pub count: usize,
+}
+
+/// The selected ticker's returns, as the view shows them.
+#[derive(Debug)]
+pub struct ReturnsView {
+    pub ticker: Ticker,
+    pub returns: Vec<f64>,
+    pub stats: ReturnStats,
+}
+
+impl ReturnsView {
+    /// Reads `ticker`'s stored history. Fails with the message to show if
+    /// it has fewer than two sessions of returns.
+    pub fn load(ticker: &Ticker) -> Result<Self, String> {
+        let columns = prices::query(ticker, DateRange::default(), &[Column::Date, Column::Close])
+            .map_err(|e| format!("No price history for {}: {}", ticker, e))?;
+        let sessions: Vec<(NaiveDate, f64)> =
+            columns.dates.iter().map(|at| at.date()).zip(columns.closes).collect();
+        let returns = daily_returns(&sessions);
+        let stats = stats(&returns).ok_or_else(|| format!("Not enough history for {} returns", ticker))?;
+        Ok(Self { ticker: ticker.clone(), returns, stats })
+    }
+}
+
+/// The last close of each session, from `(date, close)` pairs in time
+/// order.
+pub fn sessions(bars: &[(NaiveDate, f64)]) -> Vec<(NaiveDate, f64)> {
+    let mut closes: Vec<(NaiveDate, f64)> = Vec::new();
+    for &(date, close) in bars {
+        match closes.last_mut() {
+            Some(last) if last.0 == date => last.1 = close,
+            _ => closes.push((date, close)),
+        }
+    }
+    closes
+}
+
+/// Percent change between the last closes of consecutive sessions, from
+/// `(date, close)` pairs in time order.
+pub fn daily_returns(bars: &[(NaiveDate, f64)]) -> Vec<f64> {
+    sessions(bars)
+        .windows(2)
+        .filter(|w| w[0].1 != 0.0)
+        .map(|w| (w[1].1 - w[0].1) / w[0].1 * 100.0)
+        .collect()
+}
+
+/// `None` with fewer than two returns.
+pub fn stats(returns: &[f64]) -> Option<ReturnStats> {
+    if returns.len() < 2 {
+        return None;
+    }
+    let n = returns.len() as f64;
+    let mean = returns.iter().sum::<f64>() / n;
+    let moment = |k| returns.iter().map(|r| (r - mean).powi(k)).sum::<f64>() / n;
+    let std_dev = moment(2).sqrt();
+    let skew = if std_dev > 0.0 { moment(3) / std_dev.powi(3) } else { 0.0 };
+    Some(ReturnStats { count: returns.len(), mean, std_dev, skew })
+}
+
+/// `bins` equal-width bins spanning the smallest to the largest return.
+pub fn histogram(returns: &[f64], bins: usize) -> Vec<Bin> {
+    let (Some(&first), true) = (returns.first(), bins > 0) else {
+        return Vec::new();
+    };
+    let (min, max) = returns.iter().fold((first, first), |(lo, hi), &r| (lo.min(r), hi.max(r)));
+    let width = ((max - min) / bins as f64).max(f64::EPSILON);
+    let mut out: Vec<Bin> = (0..bins)
+        .map(|i| Bin { low: min + width * i as f64, high: min + width * (i + 1) as f64, count: 0 })
+        .collect();
+    for &r in returns {
+        let i = (((r - min) / width) as usize).min(bins - 1);
+        out[i].count += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 6, d).unwrap()
+    }
+
+    #[test]
+    fn daily_returns_use_the_last_close_of_each_session() {
+        let bars = [(day(3), 100.0), (day(4), 90.0), (day(4), 110.0), (day(5), 99.0)];
+        let returns = daily_returns(&bars);
+        assert_eq!(returns.len(), 2);
+        assert!((returns[0] - 10.0).abs() < 1e-9);
+        assert!((returns[1] - -10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn stats_give_population_moments() {
+        let stats = stats(&[1.0, 2.0, 3.0, 10.0]).unwrap();
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.mean, 4.0);
+        assert!((stats.std_dev - 3.5355339).abs() < 1e-6);
+        assert!(stats.skew > 1.0);
+        assert_eq!(super::stats(&[1.0]), None);
+        assert_eq!(super::stats(&[2.0, 2.0]).unwrap().skew, 0.0);
+    }
+
+    #[test]
+    fn histogram_counts_every_return_once() {
+        let bins = histogram(&[-2.0, -1.0, 0.0, 0.5, 2.0], 4);
+        let counts: Vec<usize> = bins.iter().map(|b| b.count).collect();
+        assert_eq!(counts, vec![1, 1, 2, 1]);
+        assert_eq!(bins[0].low, -2.0);
+        assert_eq!(bins[3].high, 2.0);
+    }
+}
@@ -0,0 +1,202 @@
+//! Risk metrics for the Risk panel: annualised volatility, beta against the
+//! benchmark, maximum drawdown and one-day historical VaR.
+//!
+//! Everything works on daily values, `(date, value)` pairs oldest first:
+//! a ticker's last close of each session, or the portfolio's total account
+//! value carried forward onto the benchmark's sessions. Volatility uses the
+//! sample standard deviation of daily returns over `TRADING_DAYS` a year.
+
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+
+use crate::data::{Benchmark, PriceSeries};
+use crate::ids::Ticker;
+use crate::returns;
+use crate::trades::EquityPoint;
+
+pub const TRADING_DAYS: f64 = 252.0;
+
+/// VaR is the daily loss exceeded on this share of days.
+pub const VAR_TAIL: f64 = 0.05;
+
+/// Each metric is `None` when there are too few values for it. Percent
+/// figures are positive: a 12% drawdown is `12.0`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RiskMetrics {
+    pub volatility: Option<f64>,
+    pub beta: Option<f64>,
+    pub max_drawdown: Option<f64>,
+    pub var: Option<f64>,
+}
+
+/// What the Risk panel shows, worked out again whenever the chart, the
+/// benchmark, the accounts or the trades reload.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RiskReport {
+    pub ticker: Option<Ticker>,
+    pub selected: RiskMetrics,
+    pub portfolio: RiskMetrics,
+}
+
+impl RiskReport {
+    pub fn new(chart: &PriceSeries, benchmark: &Benchmark, equity: &[EquityPoint]) -> Self {
+        let bench = benchmark.sessions();
+        let bars: Vec<(NaiveDate, f64)> = chart.bars.iter().map(|b| (b.at.date(), b.close)).collect();
+        let days: Vec<NaiveDate> = bench.iter().map(|&(d, _)| d).collect();
+        Self {
+            ticker: chart.ticker.clone(),
+            selected: metrics(&returns::sessions(&bars), &bench),
+            portfolio: metrics(&portfolio_series(equity, &days), &bench),
+        }
+    }
+}
+
+pub fn metrics(series: &[(NaiveDate, f64)], benchmark: &[(NaiveDate, f64)]) -> RiskMetrics {
+    let values: Vec<f64> = series.iter().map(|&(_, v)| v).collect();
+    let returns = returns(&values);
+    RiskMetrics {
+        volatility: sample_std_dev(&returns).map(|sd| sd * TRADING_DAYS.sqrt() * 100.0),
+        beta: beta(series, benchmark),
+        max_drawdown: max_drawdown(&values),
+        var: historical_var(&returns),
+    }
+}
+
+/// Total account value at the close of each benchmark session from the
+/// first dated trade on. Undated trades are taken to precede it.
+pub fn portfolio_series(points: &[EquityPoint], sessions: &[NaiveDate]) -> Vec<(NaiveDate, f64)> {
+    let Some(first) = points.iter().find_map(|p| p.at) else {
+        return Vec::new();
+    };
+    let mut next = 0;
+    let mut value = None;
+    let mut out = Vec::new();
+    for &day in sessions.iter().filter(|&&d| d >= first.date()) {
+        while let Some(point) = points.get(next)
+            && point.at.is_none_or(|at| at.date() <= day)
+        {
+            value = Some(point.total);
+            next += 1;
+        }
+        if let Some(value) = value {
+            out.push((day, value));
+        }
+    }
+    out
+}
+
+fn returns(values: &[f64]) -> Vec<f64> {
+    values.windows(2).filter(|w| w[0] != 0.0).map(|w| w[1] / w[0] - 1.0).collect()
+}
+
+fn mean(xs: &[f64]) -> f64 {
+    xs.iter().sum::<f64>() / xs.len() as f64
+}
+
+fn sample_std_dev(xs: &[f64]) -> Option<f64> {
+    if xs.len() < 2 {
+        return None;
+    }
+    let m = mean(xs);
+    Some((xs.iter().map(|x| (x - m).powi(2)).sum::<f64>() / (xs.len() - 1) as f64).sqrt())
+}
+
+/// Regression slope of the series' returns on the benchmark's, over the
+/// sessions both have.
+fn beta(series: &[(NaiveDate, f64)], benchmark: &[(NaiveDate, f64)]) -> Option<f64> {
+    let bench: HashMap<NaiveDate, f64> = benchmark.iter().copied().collect();
+    let common: Vec<(f64, f64)> = series.iter().filter_map(|&(d, v)| Some((v, *bench.get(&d)?))).collect();
+    let pairs: Vec<(f64, f64)> = common
+        .windows(2)
+        .filter(|w| w[0].0 != 0.0 && w[0].1 != 0.0)
+        .map(|w| (w[1].0 / w[0].0 - 1.0, w[1].1 / w[0].1 - 1.0))
+        .collect();
+    if pairs.len() < 2 {
+        return None;
+    }
+    let (own, market): (Vec<f64>, Vec<f64>) = pairs.into_iter().unzip();
+    let (mo, mm) = (mean(&own), mean(&market));
+    let cov: f64 = own.iter().zip(&market).map(|(o, m)| (o - mo) * (m - mm)).sum();
+    let var: f64 = market.iter().map(|m| (m - mm).powi(2)).sum();
+    (var > 0.0).then(|| cov / var)
+}
+
+/// Largest fall from a running peak, in percent of the peak.
+fn max_drawdown(values: &[f64]) -> Option<f64> {
+    if values.len() < 2 {
+        return None;
+    }
+    let mut peak = f64::MIN;
+    let mut worst: f64 = 0.0;
+    for &v in values {
+        peak = peak.max(v);
+        if peak > 0.0 {
+            worst = worst.max((peak - v) / peak);
+        }
+    }
+    Some(worst * 100.0)
+}
+
+/// Loss of the `VAR_TAIL` quantile daily return, in percent; zero if even
+/// that day was a gain.
+fn historical_var(returns: &[f64]) -> Option<f64> {
+    if returns.len() < 20 {
+        return None;
+    }
+    let mut sorted = returns.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let at = ((sorted.len() as f64 * VAR_TAIL).ceil() as usize).saturating_sub(1);
+    Some((-sorted[at]).max(0.0) * 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn days(values: &[f64]) -> Vec<(NaiveDate, f64)> {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        values.iter().enumerate().map(|(i, &v)| (start + chrono::Duration::days(i as i64), v)).collect()
+    }
+
+    #[test]
+    fn beta_of_a_doubled_move_is_two() {
+        let bench = days(&[100.0, 101.0, 99.0, 102.0, 100.0]);
+        // Each day moves twice as far, in percent, as the benchmark.
+        let mut v = 100.0;
+        let mut values = vec![v];
+        for w in bench.windows(2) {
+            v *= 1.0 + 2.0 * (w[1].1 / w[0].1 - 1.0);
+            values.push(v);
+        }
+        let beta = metrics(&days(&values), &bench).beta.unwrap();
+        assert!((beta - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn max_drawdown_measures_from_the_running_peak() {
+        assert_eq!(max_drawdown(&[100.0, 120.0, 90.0, 130.0, 117.0]), Some(25.0));
+        assert_eq!(max_drawdown(&[100.0]), None);
+    }
+
+    #[test]
+    fn historical_var_is_the_tail_loss() {
+        let mut returns: Vec<f64> = (0..19).map(|_| 0.01).collect();
+        returns.push(-0.04);
+        assert!((historical_var(&returns).unwrap() - 4.0).abs() < 1e-9);
+        assert_eq!(historical_var(&returns[..10]), None);
+    }
+
+    #[test]
+    fn portfolio_series_carries_totals_onto_sessions() {
+        let at = |d| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+        let points = [
+            EquityPoint { at: None, total: 100.0 },
+            EquityPoint { at: at(2).and_hms_opt(10, 0, 0), total: 110.0 },
+            EquityPoint { at: at(4).and_hms_opt(10, 0, 0), total: 105.0 },
+        ];
+        let sessions = [at(1), at(2), at(3), at(4), at(5)];
+        let series = portfolio_series(&points, &sessions);
+        assert_eq!(series, vec![(at(2), 110.0), (at(3), 110.0), (at(4), 105.0), (at(5), 105.0)]);
+    }
+}
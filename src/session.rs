@@ -0,0 +1,90 @@
+//! Where the user left the dashboard, restored on the next start.
+//!
+//! On quit the app writes the selected ticker, the focused panel, the ML
+//! list sort, the chart's date range and axis, and the chart and panel
+//! toggles to `SESSION_PATH`. Unlike the recovery file it outlives a
+//! clean exit, and anything missing from it keeps its default, so older
+//! files still load.
+
+use std::error::Error;
+use std::fs;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::{Focus, SortKey};
+use crate::axis::Scale;
+use crate::date_range::DateRange;
+use crate::ids::Ticker;
+
+pub const SESSION_PATH: &str = ".stm_session.json";
+
+/// Defaults are those of a fresh start.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// Kept by ticker, since the list may have changed by the next start.
+    pub selected: Option<Ticker>,
+    pub focus: Focus,
+    pub sort_key: SortKey,
+    pub sort_desc: bool,
+    pub chart_range: DateRange,
+    pub marked: Vec<Ticker>,
+    pub show_volume: bool,
+    pub chart_scale: Scale,
+    pub show_order_book: bool,
+    pub show_archived: bool,
+    pub rank_accounts: bool,
+    /// The stm version that saved it, for the "what's new" popup.
+    pub last_seen_version: Option<String>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            selected: None,
+            focus: Focus::default(),
+            sort_key: SortKey::default(),
+            sort_desc: false,
+            chart_range: DateRange::default(),
+            marked: Vec::new(),
+            show_volume: true,
+            chart_scale: Scale::default(),
+            show_order_book: false,
+            show_archived: false,
+            rank_accounts: false,
+            last_seen_version: None,
+        }
+    }
+}
+
+/// The last session's state, or `None` before the first quit.
+pub fn load(path: &str) -> Result<Option<Session>, Box<dyn Error>> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(Some(serde_json::from_str(&text)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn save(path: &str, session: &Session) -> Result<(), Box<dyn Error>> {
+    let tmp = format!("{}.tmp", path);
+    fs::write(&tmp, serde_json::to_string_pretty(session)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_keep_their_defaults() {
+        let session: Session = serde_json::from_str(r#"{"selected":"msft","sort_key":"pct_change","sort_desc":true}"#).unwrap();
+        assert_eq!(session.selected, Some(Ticker::parse("MSFT").unwrap()));
+        assert_eq!(session.sort_key, SortKey::PctChange);
+        assert_eq!(session.focus, Focus::default());
+        assert!(session.chart_range.is_all());
+        assert!(session.show_volume);
+    }
+}
@@ -0,0 +1,159 @@
+//! Position suggestions from the model's predictions.
+//!
+//! After a model run, the predicted move from the last close is weighed by
+//! how much to trust it: the ticker's rolling hit rate (`history::accuracy`),
+//! shrunk towards a coin flip while few predictions have resolved, times
+//! the move's size in ATRs, capped at one. A buy is sized so that hitting
+//! its stop, `stop_atr` ATRs below the entry, loses `risk_per_trade` of the
+//! broker's equity at full confidence and proportionally less below it,
+//! within `max_position` of equity and the cash on hand. A predicted fall
+//! suggests selling shares already held, sized the same way, as the paper
+//! engine doesn't short.
+//!
+//! The stored bars have closes only, so the ATR is the average absolute
+//! close-to-close change.
+
+use std::fmt;
+
+use crate::broker::{Balances, Side};
+use crate::config::SizingConfig;
+use crate::ids::Ticker;
+use crate::ml::history::{Accuracy, Prediction};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Advice {
+    Trade { side: Side, qty: u64, stop: Option<f64> },
+    /// Why there's nothing to do.
+    Hold(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub ticker: Ticker,
+    pub advice: Advice,
+    /// 0.0..=1.0.
+    pub confidence: f64,
+    pub atr: Option<f64>,
+}
+
+impl Suggestion {
+    /// The broker view line placing it, if it's a trade.
+    pub fn order_line(&self) -> Option<String> {
+        match self.advice {
+            Advice::Trade { side, qty, .. } => Some(format!("{} {} {}", side.as_str(), qty, self.ticker)),
+            Advice::Hold(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Suggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.advice {
+            Advice::Trade { side, qty, stop } => {
+                write!(f, "{} {} shares", side.as_str(), qty)?;
+                if let Some(stop) = stop {
+                    write!(f, ", stop {:.2}", stop)?;
+                }
+            }
+            Advice::Hold(why) => write!(f, "hold: {}", why)?,
+        }
+        write!(f, " (confidence {:.0}%", self.confidence * 100.0)?;
+        if let Some(atr) = self.atr {
+            write!(f, ", ATR {:.2}", atr)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Average absolute change over the last `period` closes, `None` with
+/// fewer than `period + 1` of them.
+pub fn atr(closes: &[f64], period: usize) -> Option<f64> {
+    if period == 0 || closes.len() <= period {
+        return None;
+    }
+    let recent = &closes[closes.len() - period - 1..];
+    Some(recent.windows(2).map(|w| (w[1] - w[0]).abs()).sum::<f64>() / period as f64)
+}
+
+/// Hit rate with one hit and one miss added, so a new model starts at a
+/// half, scaled by the predicted move in ATRs up to one.
+pub fn confidence(accuracy: Option<&Accuracy>, move_atrs: f64) -> f64 {
+    let (hits, window) = accuracy.map_or((0.0, 0.0), |a| (a.hit_rate * a.window as f64, a.window as f64));
+    (hits + 1.0) / (window + 2.0) * move_atrs.abs().min(1.0)
+}
+
+/// What to do about `prediction`, made from `closes` (oldest first), with
+/// `balances` the broker's account and `held` the shares it has.
+pub fn suggest(
+    prediction: &Prediction,
+    closes: &[f64],
+    accuracy: Option<&Accuracy>,
+    balances: Option<&Balances>,
+    held: f64,
+    config: &SizingConfig,
+) -> Suggestion {
+    let ticker = prediction.ticker.clone();
+    let entry = prediction.last_close;
+    let hold = |why: String, confidence: f64, atr: Option<f64>| Suggestion {
+        ticker: ticker.clone(),
+        advice: Advice::Hold(why),
+        confidence,
+        atr,
+    };
+    let Some(atr) = atr(closes, config.atr_period).filter(|a| *a > 0.0) else {
+        return hold(format!("fewer than {} closes to measure the ATR", config.atr_period + 1), 0.0, None);
+    };
+    let change = prediction.predicted - entry;
+    let confidence = confidence(accuracy, change / atr);
+    if confidence < config.min_confidence {
+        return hold(format!("below the {:.0}% minimum", config.min_confidence * 100.0), confidence, Some(atr));
+    }
+    let Some(balances) = balances else {
+        return hold("no broker account to size against".to_string(), confidence, Some(atr));
+    };
+    let stop_distance = config.stop_atr * atr;
+    let sized = (balances.equity * config.risk_per_trade * confidence / stop_distance).floor();
+    let (side, qty, stop) = if change > 0.0 {
+        let cap = (balances.equity * config.max_position).min(balances.cash) / entry;
+        (Side::Buy, sized.min(cap.floor()), Some(entry - stop_distance))
+    } else if held > 0.0 {
+        (Side::Sell, sized.min(held.floor()), None)
+    } else {
+        return hold("a fall is predicted and nothing is held".to_string(), confidence, Some(atr));
+    };
+    if qty < 1.0 {
+        return hold("under one share at this risk".to_string(), confidence, Some(atr));
+    }
+    Suggestion { ticker, advice: Advice::Trade { side, qty: qty as u64, stop }, confidence, atr: Some(atr) }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn buys_are_sized_by_confidence_and_the_stop() {
+        let at = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let ticker = Ticker::parse("AAPL").unwrap();
+        let closes: Vec<f64> = (0..15).map(|i| if i % 2 == 0 { 100.0 } else { 102.0 }).collect();
+        assert_eq!(atr(&closes, 14), Some(2.0));
+        let prediction =
+            Prediction { ticker: ticker.clone(), predicted_at: at, as_of: at, last_close: 100.0, predicted: 104.0, actual: None };
+        let accuracy = Accuracy { ticker, total: 8, window: 8, mae: 1.0, hit_rate: 0.75, pending: None };
+        let balances = Balances { cash: 50_000.0, equity: 100_000.0, buying_power: 50_000.0 };
+        let config = SizingConfig::default();
+
+        // 7 of 10 with the prior, a 2 ATR move capped at 1: 700 at risk, 4 a share.
+        let s = suggest(&prediction, &closes, Some(&accuracy), Some(&balances), 0.0, &config);
+        assert_eq!(s.advice, Advice::Trade { side: Side::Buy, qty: 175, stop: Some(96.0) });
+        assert_eq!(s.order_line().as_deref(), Some("buy 175 AAPL"));
+
+        let falling = Prediction { predicted: 99.0, ..prediction };
+        let s = suggest(&falling, &closes, Some(&accuracy), Some(&balances), 0.0, &config);
+        assert!(matches!(s.advice, Advice::Hold(_)));
+        let s = suggest(&falling, &closes, Some(&accuracy), Some(&balances), 30.0, &config);
+        assert_eq!(s.advice, Advice::Trade { side: Side::Sell, qty: 30, stop: None });
+    }
+}
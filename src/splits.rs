@@ -0,0 +1,214 @@
+//! Stock splits, and what was recorded before one in terms of after it.
+//!
+//! A split shows up two ways. A price file whose Close isn't adjusted
+//! while its Adj Close is has the ratio of the two jump by the split's
+//! ratio between two bars; and the calendar provider reports splits like
+//! other events. Stored bars before a split are divided by its ratio (their
+//! volume multiplied) when read, so charts and returns don't show a fall
+//! that didn't happen. A split the provider reports is only applied to the
+//! bars when the closes jump by about its ratio across it, since most
+//! downloads are adjusted already. Shares of fills booked before a split
+//! are multiplied by its ratio wherever holdings are counted, so the tax
+//! lots' and positions' cost per share is divided by it and the cost
+//! basis stays whole.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::data::Bar;
+use crate::events::{EventCache, EventKind};
+use crate::ids::Ticker;
+use crate::prices;
+use crate::trades::TradeRecord;
+
+/// A Close over Adj Close ratio that moves by less than this is a
+/// dividend adjustment, not a split.
+const MIN_RATIO: f64 = 1.4;
+/// How far the closes across a reported split may be from its ratio for
+/// the bars to count as unadjusted.
+const TOLERANCE: f64 = 1.25;
+/// A detected split this close to a reported one is the same split.
+const SAME_SPLIT_DAYS: i64 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Split {
+    /// The first session trading at the new price.
+    pub date: NaiveDate,
+    /// New shares per old: 4 for a 4:1 split, 0.1 for a 1:10 reverse one.
+    pub ratio: f64,
+}
+
+/// Parses a ratio like `4:1` or `1-for-10`.
+pub fn parse_ratio(text: &str) -> Option<f64> {
+    let (new, old) = text.split_once(':').or_else(|| text.split_once("-for-"))?;
+    let (new, old) = (new.trim().parse::<f64>().ok()?, old.trim().parse::<f64>().ok()?);
+    (new > 0.0 && old > 0.0 && new != old).then(|| new / old)
+}
+
+/// The nearest ratio splits are made at: halves above one, the inverse
+/// of halves below.
+fn round_ratio(ratio: f64) -> f64 {
+    if ratio >= 1.0 { (ratio * 2.0).round() / 2.0 } else { 2.0 / (2.0 / ratio).round() }
+}
+
+/// Splits that a file's Close, unadjusted, and Adj Close show. `rows` are
+/// each bar with its Adj Close, oldest first.
+pub fn detect(rows: &[(NaiveDateTime, f64, Option<f64>)]) -> Vec<Split> {
+    let ratios: Vec<(NaiveDateTime, Option<f64>)> = rows
+        .iter()
+        .map(|&(at, close, adj)| (at, adj.filter(|&a| a > 0.0 && close > 0.0).map(|a| close / a)))
+        .collect();
+    ratios
+        .windows(2)
+        .filter_map(|pair| {
+            let (Some(before), (at, Some(after))) = (pair[0].1, pair[1]) else {
+                return None;
+            };
+            let ratio = before / after;
+            (ratio >= MIN_RATIO || ratio <= 1.0 / MIN_RATIO).then(|| Split { date: at.date(), ratio: round_ratio(ratio) })
+        })
+        .collect()
+}
+
+/// Those of `reported` that `bars` haven't been adjusted for: the last
+/// close before each falls to about the next one's times its ratio.
+pub fn unadjusted(bars: &[Bar], reported: &[Split]) -> Vec<Split> {
+    reported
+        .iter()
+        .filter(|split| {
+            let i = bars.partition_point(|b| b.at.date() < split.date);
+            let (Some(before), Some(after)) = (i.checked_sub(1).and_then(|i| bars.get(i)), bars.get(i)) else {
+                return false;
+            };
+            let jump = before.close / after.close / split.ratio;
+            after.close > 0.0 && jump < TOLERANCE && jump > 1.0 / TOLERANCE
+        })
+        .copied()
+        .collect()
+}
+
+/// Restates the bars before each of `splits` in shares after it.
+pub fn adjust(bars: &mut [Bar], splits: &[Split]) {
+    for split in splits {
+        for bar in bars.iter_mut().take_while(|b| b.at.date() < split.date) {
+            bar.close /= split.ratio;
+            bar.volume = bar.volume.map(|v| v * split.ratio);
+        }
+    }
+}
+
+/// Restates `bars` for the splits their Adj Close shows and the reported
+/// ones of `ticker` they haven't been adjusted for, returning those.
+/// `adj_closes` are parallel to `bars`.
+pub fn restate(bars: &mut [Bar], adj_closes: &[Option<f64>], ticker: Option<&Ticker>) -> Vec<Split> {
+    let rows: Vec<(NaiveDateTime, f64, Option<f64>)> =
+        bars.iter().zip(adj_closes).map(|(bar, &adj)| (bar.at, bar.close, adj)).collect();
+    let mut found = detect(&rows);
+    let reported: Vec<Split> = ticker
+        .map(reported)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|r| !found.iter().any(|f| same_split(f, r)))
+        .collect();
+    found.extend(unadjusted(bars, &reported));
+    found.sort_by_key(|s| s.date);
+    adjust(bars, &found);
+    found
+}
+
+fn same_split(a: &Split, b: &Split) -> bool {
+    (a.date - b.date).num_days().abs() <= SAME_SPLIT_DAYS
+}
+
+/// How many shares now one share held at `at` became: the product of the
+/// ratios of `splits` after it. Undated fills are taken as held through
+/// none of them.
+pub fn factor(splits: &[Split], at: Option<NaiveDateTime>) -> f64 {
+    at.map_or(1.0, |at| splits.iter().filter(|s| s.date > at.date()).map(|s| s.ratio).product())
+}
+
+static REPORTED: OnceLock<Mutex<HashMap<Ticker, Vec<Split>>>> = OnceLock::new();
+
+/// Keeps the splits in `calendar` for reads of the price files and the
+/// holdings to use, replacing those kept before.
+pub fn remember(calendar: &EventCache) {
+    let mut reported: HashMap<Ticker, Vec<Split>> = HashMap::new();
+    for event in calendar.events.iter().filter(|e| e.kind == EventKind::Split) {
+        if let Some(ratio) = event.amount {
+            reported.entry(event.ticker.clone()).or_default().push(Split { date: event.date, ratio });
+        }
+    }
+    for splits in reported.values_mut() {
+        splits.sort_by_key(|s| s.date);
+        splits.dedup_by_key(|s| s.date);
+    }
+    *REPORTED.get_or_init(Default::default).lock().unwrap() = reported;
+}
+
+/// `ticker`'s splits as the calendar provider reported them, oldest first.
+pub fn reported(ticker: &Ticker) -> Vec<Split> {
+    REPORTED.get().and_then(|r| r.lock().unwrap().get(ticker).cloned()).unwrap_or_default()
+}
+
+/// Every split of `ticker` known: reported, or shown by its price file.
+pub fn known(ticker: &Ticker) -> Vec<Split> {
+    let mut splits = reported(ticker);
+    for found in prices::splits(ticker) {
+        if !splits.iter().any(|s| same_split(s, &found)) {
+            splits.push(found);
+        }
+    }
+    splits.sort_by_key(|s| s.date);
+    splits
+}
+
+/// Shares of `trade`, a broker fill, in today's terms.
+pub fn shares(trade: &TradeRecord) -> Option<f64> {
+    let qty = trade.qty?;
+    Some(trade.ticker.as_ref().map_or(qty, |ticker| qty * factor(&known(ticker), trade.timestamp)))
+}
+
+/// Splits of `splits` after `since`, up to `until`.
+pub fn between(splits: &[Split], since: NaiveDate, until: NaiveDate) -> impl Iterator<Item = &Split> {
+    splits.iter().filter(move |s| s.date > since && s.date <= until)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(d: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 6, d).unwrap().and_hms_opt(0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn splits_are_detected_and_restated() {
+        // Unadjusted closes fall from 400 to 100 at a 4:1 split on the 5th;
+        // the Adj Close carries on, a dividend nudging it on the 4th.
+        let rows = [
+            (at(3), 400.0, Some(99.0)),
+            (at(4), 404.0, Some(100.5)),
+            (at(5), 100.0, Some(99.5)),
+            (at(6), 102.0, Some(102.0)),
+        ];
+        let splits = detect(&rows);
+        assert_eq!(splits, vec![Split { date: at(5).date(), ratio: 4.0 }]);
+        assert_eq!(detect(&[(at(3), 10.0, Some(100.0)), (at(4), 10.0, Some(10.0))])[0].ratio, 0.1);
+
+        let mut bars: Vec<Bar> = rows.iter().map(|&(at, close, _)| Bar { at, close, volume: Some(10.0) }).collect();
+        let reported = [splits[0], Split { date: at(6).date(), ratio: 2.0 }];
+        assert_eq!(unadjusted(&bars, &reported), vec![splits[0]]);
+        adjust(&mut bars, &splits);
+        assert_eq!((bars[0].close, bars[0].volume, bars[2].close), (100.0, Some(40.0), 100.0));
+        assert!(unadjusted(&bars, &reported).is_empty());
+
+        assert_eq!(parse_ratio("3:2"), Some(1.5));
+        assert_eq!(parse_ratio("1-for-10"), Some(0.1));
+        assert_eq!(parse_ratio("1:1"), None);
+        assert_eq!(factor(&reported, Some(at(4))), 8.0);
+        assert_eq!(factor(&reported, Some(at(5))), 2.0);
+        assert_eq!(factor(&reported, None), 1.0);
+    }
+}
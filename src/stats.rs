@@ -0,0 +1,81 @@
+//! Local usage counters for the Stats view.
+//!
+//! Counts are kept in `STATS_PATH` in the working directory and never sent
+//! anywhere. They exist so users can look back at their own workflow: how
+//! often they run the model, download data, and which tickers they look at.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::ids::Ticker;
+
+pub const STATS_PATH: &str = "stm_stats.json";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageStats {
+    pub first_used: Option<DateTime<Local>>,
+    pub sessions: u64,
+    pub ml_runs: u64,
+    pub downloads: u64,
+    pub gap_fills: u64,
+    pub trades_entered: u64,
+    pub accounts_created: u64,
+    // Times each ticker was brought up in the chart.
+    pub ticker_views: BTreeMap<Ticker, u64>,
+    // Set when a counter changed since the last save; not persisted.
+    #[serde(skip)]
+    pub dirty: bool,
+}
+
+impl UsageStats {
+    /// Counts the start of a session.
+    pub fn start_session(&mut self) {
+        self.first_used.get_or_insert_with(Local::now);
+        self.sessions += 1;
+        self.dirty = true;
+    }
+
+    /// Bumps one of the counters, e.g. `stats.bump(|s| &mut s.ml_runs)`.
+    pub fn bump(&mut self, counter: impl FnOnce(&mut Self) -> &mut u64) {
+        *counter(self) += 1;
+        self.dirty = true;
+    }
+
+    pub fn view_ticker(&mut self, ticker: &Ticker) {
+        *self.ticker_views.entry(ticker.clone()).or_default() += 1;
+        self.dirty = true;
+    }
+
+    /// Most viewed tickers, most views first, ties by ticker.
+    pub fn top_tickers(&self, n: usize) -> Vec<(&Ticker, u64)> {
+        let mut views: Vec<(&Ticker, u64)> = self.ticker_views.iter().map(|(t, &c)| (t, c)).collect();
+        views.sort_by_key(|&(t, c)| (std::cmp::Reverse(c), t));
+        views.truncate(n);
+        views
+    }
+}
+
+/// Stats from earlier sessions; a missing file starts from zero.
+pub fn load(path: &str) -> Result<UsageStats, Box<dyn Error>> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(serde_json::from_str(&text)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(UsageStats::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Writes the stats if anything changed since the last save.
+pub fn save(path: &str, stats: &mut UsageStats) -> Result<(), Box<dyn Error>> {
+    if !stats.dirty {
+        return Ok(());
+    }
+    fs::write(path, serde_json::to_string_pretty(stats)?)?;
+    stats.dirty = false;
+    Ok(())
+}
@@ -0,0 +1,278 @@
+//! Trading strategies and the signals they emit.
+//!
+//! A `Strategy` looks at a ticker's bars, oldest first, and says whether to
+//! buy, sell or hold at the close of the last one. `[strategy] kind` picks
+//! the one the app uses: a fast moving average crossing a slow one, RSI
+//! mean reversion, or the model's logged predictions. The same signals feed
+//! the ML list's Signal column (`latest`), `stm backtest` (`backtest`) and,
+//! while `[autotrade]` is armed, orders the broker places when a signal
+//! arrives on a new bar.
+
+use std::fmt;
+
+use chrono::NaiveDateTime;
+
+use crate::broker::Side;
+use crate::config::{StrategyConfig, StrategyKind};
+use crate::data::Bar;
+use crate::ids::Ticker;
+use crate::ml::history::Prediction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Signal {
+    Buy,
+    Sell,
+    Hold,
+}
+
+impl Signal {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Signal::Buy => "Buy",
+            Signal::Sell => "Sell",
+            Signal::Hold => "Hold",
+        }
+    }
+
+    /// The order side acting on it, `None` for a hold.
+    pub fn side(self) -> Option<Side> {
+        match self {
+            Signal::Buy => Some(Side::Buy),
+            Signal::Sell => Some(Side::Sell),
+            Signal::Hold => None,
+        }
+    }
+}
+
+pub trait Strategy {
+    /// e.g. `SMA 10/30`, for messages and reports.
+    fn name(&self) -> String;
+
+    /// What to do at the close of the last of `ticker`'s `bars`.
+    fn signal(&self, ticker: &Ticker, bars: &[Bar]) -> Signal;
+}
+
+/// Buys when the `fast`-bar average crosses above the `slow`-bar one and
+/// sells when it crosses back below.
+pub struct SmaCrossover {
+    pub fast: usize,
+    pub slow: usize,
+}
+
+impl Strategy for SmaCrossover {
+    fn name(&self) -> String {
+        format!("SMA {}/{}", self.fast, self.slow)
+    }
+
+    fn signal(&self, _ticker: &Ticker, bars: &[Bar]) -> Signal {
+        let Some(prev) = bars.len().checked_sub(1).filter(|_| self.fast > 0) else {
+            return Signal::Hold;
+        };
+        let spread = |bars: &[Bar]| Some(sma(bars, self.fast)? - sma(bars, self.slow)?);
+        match (spread(&bars[..prev]), spread(bars)) {
+            (Some(before), Some(now)) if before <= 0.0 && now > 0.0 => Signal::Buy,
+            (Some(before), Some(now)) if before >= 0.0 && now < 0.0 => Signal::Sell,
+            _ => Signal::Hold,
+        }
+    }
+}
+
+/// Buys while the RSI is below `oversold` and sells while it's above
+/// `overbought`, betting on a return to the middle.
+pub struct RsiReversion {
+    pub period: usize,
+    pub oversold: f64,
+    pub overbought: f64,
+}
+
+impl Strategy for RsiReversion {
+    fn name(&self) -> String {
+        format!("RSI {} ({:.0}/{:.0})", self.period, self.oversold, self.overbought)
+    }
+
+    fn signal(&self, _ticker: &Ticker, bars: &[Bar]) -> Signal {
+        match rsi(bars, self.period) {
+            Some(rsi) if rsi < self.oversold => Signal::Buy,
+            Some(rsi) if rsi > self.overbought => Signal::Sell,
+            _ => Signal::Hold,
+        }
+    }
+}
+
+/// Follows the model: buys when the prediction made from a bar is more
+/// than `threshold_pct` above its close and sells when it's as far below.
+/// Bars without a logged prediction are holds.
+pub struct MlSignal<'a> {
+    pub predictions: &'a [Prediction],
+    pub threshold_pct: f64,
+}
+
+impl Strategy for MlSignal<'_> {
+    fn name(&self) -> String {
+        format!("ML ±{}%", self.threshold_pct)
+    }
+
+    fn signal(&self, ticker: &Ticker, bars: &[Bar]) -> Signal {
+        let Some(last) = bars.last() else {
+            return Signal::Hold;
+        };
+        let latest = self
+            .predictions
+            .iter()
+            .filter(|p| &p.ticker == ticker && p.as_of == last.at && p.last_close != 0.0)
+            .max_by_key(|p| p.predicted_at);
+        match latest.map(|p| (p.predicted - p.last_close) / p.last_close * 100.0) {
+            Some(change) if change > self.threshold_pct => Signal::Buy,
+            Some(change) if change < -self.threshold_pct => Signal::Sell,
+            _ => Signal::Hold,
+        }
+    }
+}
+
+/// The strategy `config` picks; `predictions` are the logged ones, for
+/// `ml`.
+pub fn build<'a>(config: &StrategyConfig, predictions: &'a [Prediction]) -> Box<dyn Strategy + 'a> {
+    match config.kind {
+        StrategyKind::Sma => Box::new(SmaCrossover { fast: config.fast, slow: config.slow }),
+        StrategyKind::Rsi => {
+            Box::new(RsiReversion { period: config.rsi_period, oversold: config.oversold, overbought: config.overbought })
+        }
+        StrategyKind::Ml => Box::new(MlSignal { predictions, threshold_pct: config.ml_threshold_pct }),
+    }
+}
+
+/// Average close of the last `n` bars, `None` with fewer.
+pub fn sma(bars: &[Bar], n: usize) -> Option<f64> {
+    let start = bars.len().checked_sub(n).filter(|_| n > 0)?;
+    Some(bars[start..].iter().map(|b| b.close).sum::<f64>() / n as f64)
+}
+
+/// RSI over the last `period` changes, with plain averages of the gains
+/// and losses; 50 when the price didn't move. `None` with fewer than
+/// `period + 1` bars.
+pub fn rsi(bars: &[Bar], period: usize) -> Option<f64> {
+    let start = bars.len().checked_sub(period + 1).filter(|_| period > 0)?;
+    let (mut gains, mut losses) = (0.0, 0.0);
+    for w in bars[start..].windows(2) {
+        let change = w[1].close - w[0].close;
+        if change > 0.0 {
+            gains += change;
+        } else {
+            losses -= change;
+        }
+    }
+    Some(if gains + losses == 0.0 { 50.0 } else { 100.0 * gains / (gains + losses) })
+}
+
+/// A ticker's most recent buy or sell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Latest {
+    pub signal: Signal,
+    /// Bars since, 0 for the last bar.
+    pub bars_ago: usize,
+    pub at: NaiveDateTime,
+}
+
+impl fmt::Display for Latest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.bars_ago {
+            0 => write!(f, "{}", self.signal.as_str()),
+            n => write!(f, "{} {} ago", self.signal.as_str(), n),
+        }
+    }
+}
+
+/// The latest signal that isn't a hold among the last `lookback` bars.
+pub fn latest(strategy: &dyn Strategy, ticker: &Ticker, bars: &[Bar], lookback: usize) -> Option<Latest> {
+    (0..lookback.min(bars.len())).find_map(|bars_ago| {
+        let end = bars.len() - bars_ago;
+        match strategy.signal(ticker, &bars[..end]) {
+            Signal::Hold => None,
+            signal => Some(Latest { signal, bars_ago, at: bars[end - 1].at }),
+        }
+    })
+}
+
+/// A fill of the backtest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trade {
+    pub at: NaiveDateTime,
+    pub side: Side,
+    pub qty: f64,
+    pub price: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backtest {
+    pub strategy: String,
+    pub trades: Vec<Trade>,
+    pub start_cash: f64,
+    /// Cash plus the shares held at the last close.
+    pub equity: f64,
+    pub return_pct: f64,
+    /// Holding the ticker from the first close to the last instead.
+    pub buy_hold_pct: f64,
+}
+
+/// Trades `bars` on `strategy`'s signals from `cash`: a buy puts all the
+/// cash into whole shares while none are held, a sell closes the position,
+/// both at the signal bar's close. Nothing is shorted or charged.
+pub fn backtest(strategy: &dyn Strategy, ticker: &Ticker, bars: &[Bar], cash: f64) -> Backtest {
+    let (mut left, mut held, mut trades) = (cash, 0.0, Vec::new());
+    for end in 1..=bars.len() {
+        let bar = &bars[end - 1];
+        match strategy.signal(ticker, &bars[..end]) {
+            Signal::Buy if held == 0.0 && bar.close > 0.0 => {
+                let qty = (left / bar.close).floor();
+                if qty >= 1.0 {
+                    left -= qty * bar.close;
+                    held = qty;
+                    trades.push(Trade { at: bar.at, side: Side::Buy, qty, price: bar.close });
+                }
+            }
+            Signal::Sell if held > 0.0 => {
+                left += held * bar.close;
+                trades.push(Trade { at: bar.at, side: Side::Sell, qty: held, price: bar.close });
+                held = 0.0;
+            }
+            _ => {}
+        }
+    }
+    let equity = left + held * bars.last().map_or(0.0, |b| b.close);
+    let percent = |from: f64, to: f64| if from != 0.0 { (to / from - 1.0) * 100.0 } else { 0.0 };
+    let buy_hold_pct = match (bars.first(), bars.last()) {
+        (Some(first), Some(last)) => percent(first.close, last.close),
+        _ => 0.0,
+    };
+    Backtest { strategy: strategy.name(), trades, start_cash: cash, equity, return_pct: percent(cash, equity), buy_hold_pct }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn crossovers_signal_and_backtest() {
+        let day = |d: u64| NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap() + chrono::Days::new(d);
+        let closes = [10.0, 10.0, 10.0, 9.0, 12.0, 14.0, 13.0, 9.0, 8.0];
+        let bars: Vec<Bar> = closes.iter().enumerate().map(|(i, &close)| Bar { at: day(i as u64), close, volume: None }).collect();
+        let ticker = Ticker::parse("AAPL").unwrap();
+        let sma = SmaCrossover { fast: 1, slow: 3 };
+        let signals: Vec<Signal> = (1..=bars.len()).map(|end| sma.signal(&ticker, &bars[..end])).collect();
+        use Signal::*;
+        assert_eq!(signals, vec![Hold, Hold, Hold, Sell, Buy, Hold, Hold, Sell, Hold]);
+        assert_eq!(latest(&sma, &ticker, &bars, 5), Some(Latest { signal: Sell, bars_ago: 1, at: day(7) }));
+        assert_eq!(latest(&sma, &ticker, &bars[..7], 2), None);
+
+        // Buys 8 at 12, sells them at 9.
+        let run = backtest(&sma, &ticker, &bars, 100.0);
+        assert_eq!(run.trades.len(), 2);
+        assert_eq!(run.equity, 76.0);
+        assert!((run.buy_hold_pct + 20.0).abs() < 1e-9);
+
+        assert_eq!(rsi(&bars[..4], 3), Some(0.0));
+        assert_eq!(RsiReversion { period: 3, oversold: 30.0, overbought: 70.0 }.signal(&ticker, &bars[..6]), Sell);
+    }
+}
@@ -0,0 +1,227 @@
+//! Tax lots and the gains realized on them, from the trade history.
+//!
+//! Broker fills booked to an account record their shares, so the history
+//! says what each account bought and sold of each ticker and at what price
+//! (`transaction` over the shares). A buy opens a lot; a sell closes shares
+//! of that account's lots of the ticker, oldest first with `fifo` or newest
+//! first with `lifo` (`[tax] method`), splitting the last lot it takes part
+//! of. Each piece closed is a realized gain or loss, long-term if the lot
+//! was held more than `[tax] long_term_days`; undated trades count as
+//! short-term. Trades entered by hand have no shares and aren't lots.
+//! Shares bought before a split count as the shares they became, at the
+//! cost per share divided by its ratio (see `splits`).
+//! Shares sold beyond the lots held, as when the history starts after the
+//! buy, have no cost basis and are listed as unmatched instead.
+
+use std::error::Error;
+
+use chrono::{Duration, NaiveDateTime};
+use csv::WriterBuilder;
+
+use crate::config::{LotMethod, TaxConfig};
+use crate::ids::{AccountId, Ticker};
+use crate::splits;
+use crate::trades::TradeRecord;
+
+/// Shares bought together that are still held.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lot {
+    pub account: AccountId,
+    pub ticker: Ticker,
+    pub qty: f64,
+    /// Cost per share.
+    pub price: f64,
+    pub acquired: Option<NaiveDateTime>,
+}
+
+/// Shares of one lot closed by a sell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Realized {
+    pub account: AccountId,
+    pub ticker: Ticker,
+    pub qty: f64,
+    pub acquired: Option<NaiveDateTime>,
+    pub sold: Option<NaiveDateTime>,
+    pub cost: f64,
+    pub proceeds: f64,
+    pub long_term: bool,
+}
+
+impl Realized {
+    pub fn gain(&self) -> f64 {
+        self.proceeds - self.cost
+    }
+
+    pub fn term(&self) -> &'static str {
+        if self.long_term { "long" } else { "short" }
+    }
+}
+
+/// Shares a sell closed that no lot held.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unmatched {
+    pub account: AccountId,
+    pub ticker: Ticker,
+    pub qty: f64,
+    pub sold: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TaxLots {
+    /// In the order they were bought.
+    pub open: Vec<Lot>,
+    /// In the order they were sold.
+    pub realized: Vec<Realized>,
+    pub unmatched: Vec<Unmatched>,
+}
+
+impl TaxLots {
+    /// `trades` should be in time order, as `trades::sort_by_time` leaves
+    /// them.
+    pub fn new(trades: &[TradeRecord], config: &TaxConfig) -> Self {
+        let mut lots = Self::default();
+        for trade in trades {
+            let (Some(ticker), Some(qty)) = (&trade.ticker, splits::shares(trade)) else {
+                continue;
+            };
+            if qty == 0.0 || trade.transfer.is_some() {
+                continue;
+            }
+            let price = trade.transaction.abs() / qty.abs();
+            if qty > 0.0 {
+                lots.open.push(Lot {
+                    account: trade.name.clone(),
+                    ticker: ticker.clone(),
+                    qty,
+                    price,
+                    acquired: trade.timestamp,
+                });
+            } else {
+                lots.sell(trade, ticker, -qty, price, config);
+            }
+        }
+        lots
+    }
+
+    fn sell(&mut self, trade: &TradeRecord, ticker: &Ticker, qty: f64, price: f64, config: &TaxConfig) {
+        let mut left = qty;
+        while left > f64::EPSILON {
+            let mut matching = self.open.iter().enumerate().filter(|(_, lot)| {
+                &lot.ticker == ticker && lot.account.as_str().eq_ignore_ascii_case(trade.name.as_str())
+            });
+            let found = match config.method {
+                LotMethod::Fifo => matching.next(),
+                LotMethod::Lifo => matching.next_back(),
+            };
+            let Some((i, _)) = found else {
+                self.unmatched.push(Unmatched {
+                    account: trade.name.clone(),
+                    ticker: ticker.clone(),
+                    qty: left,
+                    sold: trade.timestamp,
+                });
+                return;
+            };
+            let lot = &mut self.open[i];
+            let taken = lot.qty.min(left);
+            let long_term = match (lot.acquired, trade.timestamp) {
+                (Some(acquired), Some(sold)) => sold - acquired > Duration::days(config.long_term_days),
+                _ => false,
+            };
+            self.realized.push(Realized {
+                account: trade.name.clone(),
+                ticker: ticker.clone(),
+                qty: taken,
+                acquired: lot.acquired,
+                sold: trade.timestamp,
+                cost: taken * lot.price,
+                proceeds: taken * price,
+                long_term,
+            });
+            lot.qty -= taken;
+            left -= taken;
+            if lot.qty <= f64::EPSILON {
+                self.open.remove(i);
+            }
+        }
+    }
+
+    /// Shares of `ticker` that `account` holds in open lots, and what they
+    /// cost, fees included.
+    pub fn holding(&self, account: &str, ticker: &Ticker) -> Option<(f64, f64)> {
+        let lots = self.open.iter().filter(|l| &l.ticker == ticker && l.account.as_str().eq_ignore_ascii_case(account));
+        let (qty, cost) = lots.fold((0.0, 0.0), |(qty, cost), lot| (qty + lot.qty, cost + lot.qty * lot.price));
+        (qty > f64::EPSILON).then_some((qty, cost))
+    }
+
+    /// Total short-term and long-term gains, losses taken off.
+    pub fn totals(&self) -> (f64, f64) {
+        let sum = |long: bool| self.realized.iter().filter(|r| r.long_term == long).map(Realized::gain).fold(0.0, |a, b| a + b);
+        (sum(false), sum(true))
+    }
+}
+
+/// Writes `realized` to the CSV at `path`, one row per lot piece closed.
+pub fn write_realized_csv(path: &str, realized: &[Realized]) -> Result<(), Box<dyn Error>> {
+    let date = |at: Option<NaiveDateTime>| at.map_or(String::new(), |at| at.format("%Y-%m-%d %H:%M:%S").to_string());
+    let mut wtr = WriterBuilder::new().from_path(path)?;
+    wtr.write_record(["account", "ticker", "qty", "acquired", "sold", "cost", "proceeds", "gain", "term"])?;
+    for r in realized {
+        wtr.write_record([
+            r.account.to_string(),
+            r.ticker.to_string(),
+            r.qty.to_string(),
+            date(r.acquired),
+            date(r.sold),
+            format!("{:.2}", r.cost),
+            format!("{:.2}", r.proceeds),
+            format!("{:.2}", r.gain()),
+            r.term().to_string(),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn sells_close_lots_in_method_order() {
+        let fill = |ticker: &str, qty: f64, price: f64, year: i32| TradeRecord {
+            timestamp: NaiveDate::from_ymd_opt(year, 3, 1).unwrap().and_hms_opt(10, 0, 0),
+            ticker: Some(Ticker::parse(ticker).unwrap()),
+            qty: Some(qty),
+            ..TradeRecord::new(AccountId::parse("Alice").unwrap(), -qty * price, 0.0)
+        };
+        let history = [
+            fill("AAPL", 10.0, 100.0, 2022),
+            fill("AAPL", 10.0, 150.0, 2024),
+            TradeRecord { ticker: None, qty: None, ..fill("AAPL", 1.0, 1.0, 2024) },
+            fill("AAPL", -15.0, 200.0, 2024),
+            fill("MSFT", -2.0, 50.0, 2024),
+        ];
+
+        let fifo = TaxLots::new(&history, &TaxConfig::default());
+        let pieces: Vec<(f64, f64, bool)> = fifo.realized.iter().map(|r| (r.qty, r.gain(), r.long_term)).collect();
+        assert_eq!(pieces, vec![(10.0, 1000.0, true), (5.0, 250.0, false)]);
+        assert_eq!(fifo.totals(), (250.0, 1000.0));
+        assert_eq!((fifo.open.len(), fifo.open[0].qty, fifo.open[0].price), (1, 5.0, 150.0));
+        assert_eq!(fifo.unmatched.len(), 1);
+        assert_eq!(fifo.unmatched[0].qty, 2.0);
+
+        let lifo = TaxLots::new(&history, &TaxConfig { method: LotMethod::Lifo, ..TaxConfig::default() });
+        assert_eq!(lifo.totals(), (500.0, 500.0));
+        assert_eq!(lifo.open[0].price, 100.0);
+
+        let path = std::env::temp_dir().join(format!("stm-{}-gains.csv", std::process::id()));
+        let path = path.to_str().unwrap();
+        write_realized_csv(path, &fifo.realized).unwrap();
+        let text = std::fs::read_to_string(path).unwrap();
+        assert_eq!(text.lines().nth(1), Some("Alice,AAPL,10,2022-03-01 10:00:00,2024-03-01 10:00:00,1000.00,2000.00,1000.00,long"));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,184 @@
+//! Dashboard colours.
+//!
+//! `theme` in `stm.toml` picks `dark` (the default), `light`, or a custom
+//! theme defined in a `[themes.NAME]` table. A custom theme starts from
+//! its `base` built-in and overrides any of the colours, e.g.
+//!
+//! ```toml
+//! theme = "mine"
+//!
+//! [themes.mine]
+//! base = "light"
+//! gain = "#007f00"
+//! chart_line = "blue"
+//! ```
+//!
+//! Colours are a name (`red`, `lightblue`, `darkgray`, `reset`, ...), a
+//! `#rrggbb` hex value, or a 0-255 palette index.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use tui::style::{Color, Modifier, Style};
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub border: Color,
+    pub border_focused: Color,
+    pub gain: Color,
+    pub loss: Color,
+    /// Unchanged prices and other plain values.
+    pub neutral: Color,
+    /// Secondary text: timestamps, hints, closed accounts.
+    pub muted: Color,
+    /// Background of the selected row.
+    pub selection: Color,
+    pub chart_line: Color,
+    /// Key labels, markers and other highlights.
+    pub accent: Color,
+    pub error: Color,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            border: Color::Reset,
+            border_focused: Color::Yellow,
+            gain: Color::Green,
+            loss: Color::Red,
+            neutral: Color::Reset,
+            muted: Color::DarkGray,
+            selection: Color::DarkGray,
+            chart_line: Color::Green,
+            accent: Color::Yellow,
+            error: Color::Red,
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            border: Color::Black,
+            border_focused: Color::Blue,
+            gain: Color::Rgb(0, 128, 0),
+            loss: Color::Rgb(192, 0, 0),
+            neutral: Color::Black,
+            muted: Color::DarkGray,
+            selection: Color::Rgb(210, 210, 210),
+            chart_line: Color::Blue,
+            accent: Color::Magenta,
+            error: Color::Rgb(192, 0, 0),
+        }
+    }
+
+    fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            _ => None,
+        }
+    }
+
+    /// The theme called `name`, built-in or from `themes`. Unknown names
+    /// and colours fall back to the dark theme's and are described in the
+    /// returned list.
+    pub fn from_config(name: &str, themes: &BTreeMap<String, ThemeConfig>) -> (Self, Vec<String>) {
+        let mut problems = Vec::new();
+        if let Some(theme) = Self::builtin(name) {
+            return (theme, problems);
+        }
+        let Some(custom) = themes.get(name) else {
+            problems.push(format!("theme {:?} is neither built in nor a [themes.{}] table", name, name));
+            return (Self::dark(), problems);
+        };
+        let base = custom.base.as_deref().unwrap_or("dark");
+        let mut theme = Self::builtin(base).unwrap_or_else(|| {
+            problems.push(format!("[themes.{}] unknown base {:?} (dark/light)", name, base));
+            Self::dark()
+        });
+        for (field, spec) in &custom.colors {
+            let Some(slot) = theme.slot(field) else {
+                problems.push(format!("[themes.{}] unknown setting {:?}", name, field));
+                continue;
+            };
+            match parse_color(spec) {
+                Some(color) => *slot = color,
+                None => problems.push(format!("[themes.{}] {}: unknown colour {:?}", name, field, spec)),
+            }
+        }
+        (theme, problems)
+    }
+
+    fn slot(&mut self, field: &str) -> Option<&mut Color> {
+        Some(match field {
+            "border" => &mut self.border,
+            "border_focused" => &mut self.border_focused,
+            "gain" => &mut self.gain,
+            "loss" => &mut self.loss,
+            "neutral" => &mut self.neutral,
+            "muted" => &mut self.muted,
+            "selection" => &mut self.selection,
+            "chart_line" => &mut self.chart_line,
+            "accent" => &mut self.accent,
+            "error" => &mut self.error,
+            _ => return None,
+        })
+    }
+
+    /// Gain colour for rises, loss colour for falls, neutral otherwise.
+    pub fn change(&self, change: f64) -> Color {
+        if change > 0.0 {
+            self.gain
+        } else if change < 0.0 {
+            self.loss
+        } else {
+            self.neutral
+        }
+    }
+
+    pub fn selected(&self) -> Style {
+        Style::default().bg(self.selection).add_modifier(Modifier::BOLD)
+    }
+}
+
+/// A `[themes.NAME]` table.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ThemeConfig {
+    /// Built-in theme the overrides apply to; `dark` if not given.
+    pub base: Option<String>,
+    #[serde(flatten)]
+    pub colors: BTreeMap<String, String>,
+}
+
+fn parse_color(spec: &str) -> Option<Color> {
+    let spec = spec.trim();
+    if let Some(hex) = spec.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+    }
+    if let Ok(index) = spec.parse::<u8>() {
+        return Some(Color::Indexed(index));
+    }
+    Some(match spec.to_ascii_lowercase().replace(['_', '-', ' '], "").as_str() {
+        "reset" | "default" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => return None,
+    })
+}
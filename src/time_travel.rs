@@ -0,0 +1,230 @@
+//! The portfolio as it stood at the end of a past day, for the time-travel
+//! view.
+//!
+//! Balances replay the trade history up to and including that day: each
+//! account shows the balance after its last trade by then, or its starting
+//! balance if it hadn't traded yet. Undated trades sort before dated ones
+//! and so always count. The stock list shows only tickers with stored bars
+//! by then, priced at that day's last close. Left/Right step a day,
+//! PageUp/PageDown a month, and typing `YYYY-MM-DD` then Enter jumps.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Duration, Months, NaiveDate};
+use crossterm::event::KeyCode;
+
+use crate::accounts::AccountSummary;
+use crate::calendar::Sessions;
+use crate::data::{self, StockInfo};
+use crate::date_range::DateRange;
+use crate::ids::{AccountId, Ticker};
+use crate::prices;
+use crate::trades::{self, TradeRecord};
+use crate::universe::{self, SeriesKey};
+
+/// Trade results per ticker up to the day.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Position {
+    pub trades: usize,
+    pub net: f64,
+}
+
+#[derive(Debug)]
+pub struct Snapshot {
+    pub date: NaiveDate,
+    pub accounts: Vec<AccountSummary>,
+    pub stocks: Vec<StockInfo>,
+    pub positions: BTreeMap<Ticker, Position>,
+    /// The trades made up to the day, oldest first.
+    pub trades: Vec<TradeRecord>,
+}
+
+impl Snapshot {
+    /// `accounts` and `trades` (in time order) as of the end of `date`,
+    /// with the stored prices of `tickers`.
+    pub fn at(date: NaiveDate, accounts: &[AccountSummary], trades: &[TradeRecord], tickers: &[Ticker]) -> Self {
+        let trades = &trades[..trades_until(trades, date)];
+        let mut positions: BTreeMap<Ticker, Position> = BTreeMap::new();
+        for trade in trades {
+            if let Some(ticker) = &trade.ticker {
+                let position = positions.entry(ticker.clone()).or_default();
+                position.trades += 1;
+                position.net += trade.transaction;
+            }
+        }
+        Self {
+            date,
+            accounts: accounts_as_of(accounts, trades),
+            stocks: tickers.iter().filter_map(|t| stock_as_of(t, date)).collect(),
+            positions,
+            trades: trades.to_vec(),
+        }
+    }
+}
+
+/// How many of `trades` (in time order) were made by the end of `date`.
+pub fn trades_until(trades: &[TradeRecord], date: NaiveDate) -> usize {
+    date.succ_opt().and_then(|next| trades::first_on_or_after(trades, next)).unwrap_or(trades.len())
+}
+
+/// `accounts` with their balances after `trades`, which should stop at the
+/// day being looked at.
+pub fn accounts_as_of(accounts: &[AccountSummary], trades: &[TradeRecord]) -> Vec<AccountSummary> {
+    let balances: HashMap<&AccountId, f64> = trades.iter().map(|t| (&t.name, t.new_balance)).collect();
+    accounts
+        .iter()
+        .map(|a| {
+            let balance = balances.get(&a.name).copied().unwrap_or(a.initial_amount);
+            let change = balance - a.initial_amount;
+            let percentage_change = if a.initial_amount != 0.0 { change / a.initial_amount * 100.0 } else { 0.0 };
+            AccountSummary { current_amount: balance, change, percentage_change, ..a.clone() }
+        })
+        .collect()
+}
+
+/// `ticker` priced at the last bar on or before `date`; `None` if its
+/// stored history starts later or can't be read.
+fn stock_as_of(ticker: &Ticker, date: NaiveDate) -> Option<StockInfo> {
+    let bars = prices::bars(ticker, DateRange { start: None, end: Some(date) }).ok()?;
+    let price = bars.last()?.close;
+    let times: Vec<_> = bars.iter().map(|b| b.at).collect();
+    let closes: Vec<f64> = bars.iter().map(|b| b.close).collect();
+    let (_, change, pct_change) =
+        data::price_change(&times, &closes, Sessions::for_ticker(ticker)).unwrap_or((price, 0.0, 0.0));
+    Some(StockInfo {
+        ticker: ticker.clone(),
+        price,
+        change,
+        pct_change,
+        gaps: Vec::new(),
+        return_3m: data::three_month_return(&times, &closes),
+        error: None,
+        source: universe::root_of(ticker).name.clone(),
+        series: SeriesKey::default(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TravelStep {
+    Stay,
+    Close,
+    Go(NaiveDate),
+}
+
+#[derive(Debug)]
+pub struct TimeTravelView {
+    pub snapshot: Snapshot,
+    /// A date being typed, replacing the title's date until Enter.
+    pub input: String,
+    pub error: Option<String>,
+    /// Latest date that can be picked, usually today.
+    pub latest: NaiveDate,
+}
+
+impl TimeTravelView {
+    pub fn new(snapshot: Snapshot, latest: NaiveDate) -> Self {
+        Self { snapshot, input: String::new(), error: None, latest }
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode) -> TravelStep {
+        let date = self.snapshot.date;
+        let step = match code {
+            KeyCode::Esc if !self.input.is_empty() => {
+                self.input.clear();
+                None
+            }
+            KeyCode::Esc => return TravelStep::Close,
+            KeyCode::Char(c) if c.is_ascii_digit() || c == '-' => {
+                self.input.push(c);
+                None
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+                None
+            }
+            KeyCode::Enter if !self.input.is_empty() => match NaiveDate::parse_from_str(self.input.trim(), "%Y-%m-%d") {
+                Ok(date) => Some(date),
+                Err(_) => {
+                    self.error = Some(format!("{:?} isn't a YYYY-MM-DD date", self.input));
+                    return TravelStep::Stay;
+                }
+            },
+            KeyCode::Left => date.checked_sub_signed(Duration::days(1)),
+            KeyCode::Right => date.checked_add_signed(Duration::days(1)),
+            KeyCode::PageUp => date.checked_sub_months(Months::new(1)),
+            KeyCode::PageDown => date.checked_add_months(Months::new(1)),
+            _ => None,
+        };
+        self.error = None;
+        match step {
+            Some(to) if to.min(self.latest) != date => {
+                self.input.clear();
+                TravelStep::Go(to.min(self.latest))
+            }
+            _ => TravelStep::Stay,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+
+    fn at(day: u32) -> Option<NaiveDateTime> {
+        NaiveDate::from_ymd_opt(2024, 6, day).unwrap().and_hms_opt(10, 0, 0)
+    }
+
+    fn trade(name: &str, transaction: f64, new_balance: f64, timestamp: Option<NaiveDateTime>) -> TradeRecord {
+        TradeRecord {
+            timestamp,
+            ticker: Some(Ticker::parse("AAPL").unwrap()),
+            ..TradeRecord::new(AccountId::parse(name).unwrap(), transaction, new_balance)
+        }
+    }
+
+    fn account(name: &str, initial_amount: f64, current_amount: f64) -> AccountSummary {
+        AccountSummary {
+            current_amount,
+            change: current_amount - initial_amount,
+            ..AccountSummary::new(AccountId::parse(name).unwrap(), initial_amount, "USD")
+        }
+    }
+
+    #[test]
+    fn snapshots_count_trades_through_the_end_of_the_day() {
+        let trades = [
+            trade("Alice", 5.0, 105.0, None),
+            trade("Alice", 20.0, 125.0, at(3)),
+            trade("Bob", -10.0, 40.0, at(4)),
+            trade("Alice", -25.0, 100.0, at(5)),
+        ];
+        let accounts = [account("Alice", 100.0, 100.0), account("Bob", 50.0, 40.0)];
+        let day = NaiveDate::from_ymd_opt(2024, 6, 4).unwrap();
+        assert_eq!(trades_until(&trades, day), 3);
+        let snapshot = Snapshot::at(day.pred_opt().unwrap(), &accounts, &trades, &[]);
+        assert_eq!(snapshot.trades.len(), 2);
+        let balances: Vec<(f64, f64)> = snapshot.accounts.iter().map(|a| (a.current_amount, a.percentage_change)).collect();
+        assert_eq!(balances, vec![(125.0, 25.0), (50.0, 0.0)]);
+        let position = snapshot.positions[&Ticker::parse("AAPL").unwrap()];
+        assert_eq!(position, Position { trades: 2, net: 25.0 });
+    }
+
+    #[test]
+    fn keys_step_and_jump_without_passing_the_latest_day() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 6, d).unwrap();
+        let snapshot = Snapshot::at(day(10), &[], &[], &[]);
+        let mut view = TimeTravelView::new(snapshot, day(11));
+        assert_eq!(view.handle_key(KeyCode::Left), TravelStep::Go(day(9)));
+        assert_eq!(view.handle_key(KeyCode::PageDown), TravelStep::Go(day(11)));
+        "2024-06-02".chars().for_each(|c| assert_eq!(view.handle_key(KeyCode::Char(c)), TravelStep::Stay));
+        assert_eq!(view.handle_key(KeyCode::Enter), TravelStep::Go(day(2)));
+        assert!(view.input.is_empty());
+        view.input = "2024-13-01".to_string();
+        assert_eq!(view.handle_key(KeyCode::Enter), TravelStep::Stay);
+        assert!(view.error.is_some());
+        assert_eq!(view.handle_key(KeyCode::Esc), TravelStep::Stay);
+        assert_eq!(view.handle_key(KeyCode::Esc), TravelStep::Close);
+    }
+}
@@ -0,0 +1,419 @@
+//! Trade history (`trading_history.csv`) and the equity curve drawn from it.
+//!
+//! Histories can run to tens of thousands of rows, so the dashboard's timed
+//! reloads use `read_trades_since`, which parses only the rows appended
+//! since the previous read.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::accounts::AccountSummary;
+use crate::ids::{AccountId, Ticker};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradeRecord {
+    pub name: AccountId,
+    pub transaction: f64,
+    pub new_balance: f64,
+    /// When the trade was made. Older rows have no timestamp column.
+    #[serde(default)]
+    pub timestamp: Option<NaiveDateTime>,
+    /// Stock the trade was in, if it was recorded with one.
+    #[serde(default)]
+    pub ticker: Option<Ticker>,
+    #[serde(default)]
+    pub note: Option<String>,
+    /// Shared by the two legs of a transfer between accounts, which move
+    /// cash rather than gain or lose it.
+    #[serde(default)]
+    pub transfer: Option<u64>,
+    /// Shares a broker fill bought (positive) or sold (negative), for the
+    /// tax lots; `transaction` is what they cost or brought in.
+    #[serde(default)]
+    pub qty: Option<f64>,
+    /// A dividend paid on `ticker`, income rather than a trade's result.
+    #[serde(default)]
+    pub dividend: bool,
+    /// Commission a broker fill paid, already taken off `transaction`.
+    #[serde(default)]
+    pub fee: Option<f64>,
+}
+
+impl TradeRecord {
+    /// A plain gain or loss of `transaction` that left `name` at
+    /// `new_balance`, with no time, ticker or note.
+    pub fn new(name: AccountId, transaction: f64, new_balance: f64) -> Self {
+        Self {
+            name,
+            transaction,
+            new_balance,
+            timestamp: None,
+            ticker: None,
+            note: None,
+            transfer: None,
+            qty: None,
+            dividend: false,
+            fee: None,
+        }
+    }
+}
+
+pub fn read_trades_from_csv(path: &str) -> Result<Vec<TradeRecord>, Box<dyn Error>> {
+    let mut rdr = ReaderBuilder::new().from_path(path)?;
+    let mut trades = Vec::new();
+    for result in rdr.deserialize() {
+        let rec: TradeRecord = result?;
+        trades.push(rec);
+    }
+    Ok(trades)
+}
+
+/// Where a read of a trade history stopped. The file counts as appended to,
+/// rather than rewritten, while its header and the last row read are still
+/// in place; edits elsewhere are only seen by a full read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradeCursor {
+    headers: StringRecord,
+    header_bytes: Vec<u8>,
+    // Empty if the file had no rows.
+    last_row: Vec<u8>,
+    // File length at the read.
+    offset: u64,
+}
+
+impl TradeCursor {
+    fn still_valid(&self, file: &mut File) -> io::Result<bool> {
+        let tail = if self.last_row.is_empty() { &self.header_bytes } else { &self.last_row };
+        // A row without its newline may have been cut off mid-write.
+        if file.metadata()?.len() < self.offset || !tail.ends_with(b"\n") {
+            return Ok(false);
+        }
+        Ok(read_at(file, 0, self.header_bytes.len())? == self.header_bytes
+            && read_at(file, self.offset - tail.len() as u64, tail.len())? == *tail)
+    }
+}
+
+fn read_at(file: &mut File, at: u64, len: usize) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0; len];
+    file.seek(SeekFrom::Start(at))?;
+    file.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+#[derive(Debug)]
+pub struct TradeRead {
+    pub trades: Vec<TradeRecord>,
+    /// True if `trades` follow on from the earlier read; false if they're
+    /// the whole history.
+    pub appended: bool,
+    pub cursor: TradeCursor,
+}
+
+/// The trades added to `path` since the read that left `since`, or the
+/// whole history if there's no cursor or the file was rewritten.
+pub fn read_trades_since(path: &str, since: Option<&TradeCursor>) -> Result<TradeRead, Box<dyn Error>> {
+    let mut file = File::open(path)?;
+    if let Some(cursor) = since
+        && cursor.still_valid(&mut file)?
+    {
+        let mut rest = Vec::new();
+        file.seek(SeekFrom::Start(cursor.offset))?;
+        file.read_to_end(&mut rest)?;
+        let (trades, last_start) = parse_rows(&cursor.headers, &rest)?;
+        let mut cursor = cursor.clone();
+        if let Some(start) = last_start {
+            cursor.last_row = rest[start..].to_vec();
+        }
+        cursor.offset += rest.len() as u64;
+        return Ok(TradeRead { trades, appended: true, cursor });
+    }
+
+    let mut bytes = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_end(&mut bytes)?;
+    let mut rdr = ReaderBuilder::new().from_reader(&bytes[..]);
+    let headers = rdr.headers()?.clone();
+    let body = rdr.position().byte() as usize;
+    let (trades, last_start) = parse_rows(&headers, &bytes[body..])?;
+    let cursor = TradeCursor {
+        headers,
+        header_bytes: bytes[..body].to_vec(),
+        last_row: last_start.map_or(Vec::new(), |start| bytes[body + start..].to_vec()),
+        offset: bytes.len() as u64,
+    };
+    Ok(TradeRead { trades, appended: false, cursor })
+}
+
+/// Trades in `rows`, which has no header row, and the offset the last one
+/// starts at.
+fn parse_rows(headers: &StringRecord, rows: &[u8]) -> Result<(Vec<TradeRecord>, Option<usize>), Box<dyn Error>> {
+    let mut rdr = ReaderBuilder::new().has_headers(false).from_reader(rows);
+    let mut record = StringRecord::new();
+    let mut trades = Vec::new();
+    let mut last_start = None;
+    loop {
+        let start = rdr.position().byte() as usize;
+        if !rdr.read_record(&mut record)? {
+            break;
+        }
+        trades.push(record.deserialize(Some(headers))?);
+        last_start = Some(start);
+    }
+    Ok((trades, last_start))
+}
+
+/// Puts `trades` in time order, oldest first, keeping file order between
+/// trades with the same or no timestamp; undated (older) rows come first.
+/// Returns false if they already were.
+pub fn sort_by_time(trades: &mut [TradeRecord]) -> bool {
+    if trades.is_sorted_by_key(|t| t.timestamp) {
+        return false;
+    }
+    trades.sort_by_key(|t| t.timestamp);
+    true
+}
+
+/// Index of the first trade made on or after `date`. Trades without a
+/// timestamp are skipped over.
+pub fn first_on_or_after(trades: &[TradeRecord], date: NaiveDate) -> Option<usize> {
+    trades.iter().position(|t| t.timestamp.is_some_and(|at| at.date() >= date))
+}
+
+/// The other leg of the transfer `trades[i]` is part of, if it is one.
+/// Legs are written next to each other.
+pub fn transfer_leg(trades: &[TradeRecord], i: usize) -> Option<&TradeRecord> {
+    let id = trades.get(i)?.transfer?;
+    [i.checked_sub(1), Some(i + 1)].into_iter().flatten().filter_map(|j| trades.get(j)).find(|t| t.transfer == Some(id))
+}
+
+pub fn write_trades_to_csv(path: &str, trades: &[TradeRecord]) -> Result<(), Box<dyn Error>> {
+    let mut wtr = WriterBuilder::new().from_path(path)?;
+    for trade in trades {
+        wtr.serialize(trade)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Columns of a history written by this version, in `TradeRecord` order.
+const HEADER: &str = "name,transaction,new_balance,timestamp,ticker,note,transfer,qty,dividend,fee";
+
+/// Adds `trade` to the end of the history at `path`, creating the file if
+/// needed.
+pub fn append_trade(path: &str, trade: &TradeRecord) -> Result<(), Box<dyn Error>> {
+    append(path, std::slice::from_ref(trade))
+}
+
+/// Adds the two legs of a transfer to the history at `path`, linked by a
+/// transfer id one past the highest already used.
+pub fn append_transfer(path: &str, legs: &[TradeRecord; 2]) -> Result<(), Box<dyn Error>> {
+    let trades = if Path::new(path).exists() { read_trades_from_csv(path)? } else { Vec::new() };
+    let id = trades.iter().filter_map(|t| t.transfer).max().map_or(1, |id| id + 1);
+    append(path, &legs.clone().map(|leg| TradeRecord { transfer: Some(id), ..leg }))
+}
+
+/// Writes `new` after the last row of `path`. A file with an older header
+/// is instead copied with them to a temporary file, which is renamed over
+/// it, so it picks up the newer columns and a crash mid-write keeps the
+/// history as it was.
+fn append(path: &str, new: &[TradeRecord]) -> Result<(), Box<dyn Error>> {
+    let mut file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+    let len = file.metadata()?.len();
+    let mut header = String::new();
+    BufReader::new(&file).read_line(&mut header)?;
+    if len > 0 && header.trim_end() != HEADER {
+        let mut trades = read_trades_from_csv(path)?;
+        trades.extend_from_slice(new);
+        let tmp = format!("{}.tmp", path);
+        write_trades_to_csv(&tmp, &trades)?;
+        fs::rename(&tmp, path)?;
+        return Ok(());
+    }
+    // A row cut off by an earlier crash would otherwise run into the first
+    // new one.
+    if len > 0 && read_at(&mut file, len - 1, 1)? != b"\n" {
+        file.write_all(b"\n")?;
+    }
+    let mut wtr = WriterBuilder::new().has_headers(len == 0).from_writer(file);
+    for trade in new {
+        wtr.serialize(trade)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Combined balance of all accounts at one point of the trade history.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EquityPoint {
+    /// `None` for the starting point and for trades without a timestamp.
+    pub at: Option<NaiveDateTime>,
+    pub total: f64,
+}
+
+/// Total account value before the first trade and after each one, in file
+/// order. Accounts start at their starting balance; one missing from the
+/// summary starts at its first trade's balance before that trade. Balances
+/// are summed as is, whatever their currency. A transfer gives one point,
+/// after both legs, so it doesn't show as a dip and a recovery.
+pub fn equity_curve(accounts: &[AccountSummary], trades: &[TradeRecord]) -> Vec<EquityPoint> {
+    let mut balances: HashMap<&AccountId, f64> = accounts.iter().map(|a| (&a.name, a.initial_amount)).collect();
+    for trade in trades {
+        balances.entry(&trade.name).or_insert(trade.new_balance - trade.transaction);
+    }
+    let mut points = vec![EquityPoint { at: None, total: balances.values().sum() }];
+    for (i, trade) in trades.iter().enumerate() {
+        balances.insert(&trade.name, trade.new_balance);
+        if trade.transfer.is_some() && trades.get(i + 1).is_some_and(|next| next.transfer == trade.transfer) {
+            continue;
+        }
+        points.push(EquityPoint { at: trade.timestamp, total: balances.values().sum() });
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn trade(name: &str, transaction: f64, new_balance: f64) -> TradeRecord {
+        TradeRecord::new(AccountId::parse(name).unwrap(), transaction, new_balance)
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("stm-{}-{}", std::process::id(), name)).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn append_trade_creates_the_file_and_keeps_earlier_trades() {
+        let path = temp_path("new-trades.csv");
+        let _ = fs::remove_file(&path);
+        let mut second = trade("Bob", -3.0, 17.0);
+        second.ticker = Some(Ticker::parse("aapl").unwrap());
+        second.note = Some("stopped out, early".to_string());
+        append_trade(&path, &trade("Alice", 5.0, 15.0)).unwrap();
+        append_trade(&path, &second).unwrap();
+        let trades = read_trades_from_csv(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(trades, vec![trade("Alice", 5.0, 15.0), second]);
+    }
+
+    #[test]
+    fn append_trade_upgrades_older_files() {
+        let path = temp_path("old-trades.csv");
+        fs::write(&path, "name,transaction,new_balance\nAlice,5.0,15.0\n").unwrap();
+        append_trade(&path, &trade("Bob", -3.0, 17.0)).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        let trades = read_trades_from_csv(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(text.starts_with("name,transaction,new_balance,timestamp,ticker,note,transfer,qty,dividend,fee\n"));
+        assert!(!Path::new(&format!("{}.tmp", path)).exists());
+        assert_eq!(trades, vec![trade("Alice", 5.0, 15.0), trade("Bob", -3.0, 17.0)]);
+    }
+
+    #[test]
+    fn append_trade_leaves_earlier_rows_of_current_files_untouched() {
+        let path = temp_path("current-trades.csv");
+        // Hand-written, and the last row is missing its newline.
+        let existing = format!("{}\nAlice,5,15,,,\"kept, as is\",,,false,", HEADER);
+        fs::write(&path, &existing).unwrap();
+        append_trade(&path, &trade("Bob", -3.0, 17.0)).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        let trades = read_trades_from_csv(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(text, format!("{}\nBob,-3.0,17.0,,,,,,false,\n", existing));
+        assert_eq!(trades[1], trade("Bob", -3.0, 17.0));
+    }
+
+    #[test]
+    fn read_trades_since_reads_only_appended_rows() {
+        let path = temp_path("since-trades.csv");
+        fs::write(&path, "name,transaction,new_balance\nAlice,5.0,15.0\n").unwrap();
+        let first = read_trades_since(&path, None).unwrap();
+        assert!(!first.appended);
+        assert_eq!(first.trades, vec![trade("Alice", 5.0, 15.0)]);
+
+        let unchanged = read_trades_since(&path, Some(&first.cursor)).unwrap();
+        assert!(unchanged.appended);
+        assert!(unchanged.trades.is_empty());
+
+        fs::write(&path, "name,transaction,new_balance\nAlice,5.0,15.0\nBob,-3.0,17.0\nAlice,2.0,17.0\n").unwrap();
+        let next = read_trades_since(&path, Some(&unchanged.cursor)).unwrap();
+        assert!(next.appended);
+        assert_eq!(next.trades, vec![trade("Bob", -3.0, 17.0), trade("Alice", 2.0, 17.0)]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn read_trades_since_rereads_rewritten_files() {
+        let path = temp_path("rewritten-trades.csv");
+        fs::write(&path, "name,transaction,new_balance\nAlice,5.0,15.0\n").unwrap();
+        let first = read_trades_since(&path, None).unwrap();
+        // Same length, different last row.
+        fs::write(&path, "name,transaction,new_balance\nCarol,1.0,11.0\nBob,-3.0,17.0\n").unwrap();
+        let next = read_trades_since(&path, Some(&first.cursor)).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(!next.appended);
+        assert_eq!(next.trades, vec![trade("Carol", 1.0, 11.0), trade("Bob", -3.0, 17.0)]);
+    }
+
+    #[test]
+    fn sort_by_time_keeps_undated_rows_first_in_file_order() {
+        let at = |h| NaiveDate::from_ymd_opt(2024, 6, 3).unwrap().and_hms_opt(h, 0, 0);
+        let mut trades = vec![trade("Alice", 1.0, 11.0), trade("Bob", 1.0, 21.0), trade("Alice", 1.0, 12.0), trade("Bob", 1.0, 22.0)];
+        trades[2].timestamp = at(15);
+        trades[3].timestamp = at(10);
+        assert!(sort_by_time(&mut trades));
+        let balances: Vec<f64> = trades.iter().map(|t| t.new_balance).collect();
+        assert_eq!(balances, vec![11.0, 21.0, 22.0, 12.0]);
+        assert!(!sort_by_time(&mut trades));
+    }
+
+    #[test]
+    fn first_on_or_after_skips_earlier_and_undated_trades() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 6, d).unwrap();
+        let mut trades = vec![trade("Alice", 1.0, 11.0), trade("Alice", 1.0, 12.0), trade("Alice", 1.0, 13.0)];
+        trades[1].timestamp = day(3).and_hms_opt(16, 0, 0);
+        trades[2].timestamp = day(5).and_hms_opt(9, 30, 0);
+        assert_eq!(first_on_or_after(&trades, day(3)), Some(1));
+        assert_eq!(first_on_or_after(&trades, day(4)), Some(2));
+        assert_eq!(first_on_or_after(&trades, day(6)), None);
+    }
+
+    #[test]
+    fn equity_curve_sums_balances_after_each_trade() {
+        let alice = AccountSummary {
+            current_amount: 17.0,
+            change: 7.0,
+            percentage_change: 70.0,
+            ..AccountSummary::new(AccountId::parse("Alice").unwrap(), 10.0, "USD")
+        };
+        // Carol isn't in the summary, so she starts at 50 - 10.
+        let trades = [trade("Alice", 5.0, 15.0), trade("Carol", 10.0, 50.0), trade("Alice", 2.0, 17.0)];
+        let totals: Vec<f64> = equity_curve(&[alice], &trades).iter().map(|p| p.total).collect();
+        assert_eq!(totals, vec![50.0, 55.0, 65.0, 67.0]);
+    }
+
+    #[test]
+    fn transfers_are_linked_and_leave_the_equity_curve_flat() {
+        let path = temp_path("transfer-trades.csv");
+        let _ = fs::remove_file(&path);
+        append_trade(&path, &trade("Alice", 5.0, 15.0)).unwrap();
+        append_transfer(&path, &[trade("Alice", -4.0, 11.0), trade("Bob", 4.0, 24.0)]).unwrap();
+        append_transfer(&path, &[trade("Bob", -1.0, 23.0), trade("Alice", 1.0, 12.0)]).unwrap();
+        let trades = read_trades_from_csv(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let ids: Vec<Option<u64>> = trades.iter().map(|t| t.transfer).collect();
+        assert_eq!(ids, vec![None, Some(1), Some(1), Some(2), Some(2)]);
+        let totals: Vec<f64> = equity_curve(&[], &trades).iter().map(|p| p.total).collect();
+        assert_eq!(totals, vec![30.0, 35.0, 35.0, 35.0]);
+    }
+}
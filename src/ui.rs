@@ -0,0 +1,2896 @@
+//! Dashboard rendering.
+
+use std::time::Instant;
+
+use chrono::{DateTime, Datelike, NaiveDateTime};
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    symbols::Marker,
+    text::{Span, Spans},
+    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Sparkline, StatefulWidget, Table, TableState, Widget, Wrap},
+    widgets::canvas::{Canvas, Line, Points},
+    Frame,
+};
+
+use crate::alerts::{AlertPreview, Metric};
+use crate::analytics::{self, Analytics};
+use crate::axis::{self, Scale};
+use crate::changelog::Entry;
+use crate::app::{
+    AccountForm, App, AppEvent, Focus, MLMode, SortKey, TradeForm, ACCOUNT_FIELDS, AMOUNT_FIELD, BALANCE_FIELD, TRADE_FIELDS,
+    TRANSFER_FIELDS,
+};
+use crate::calendar::{self, Sessions};
+use crate::capabilities::{self, Capability};
+use crate::chaos;
+use crate::config::{self, ChartMarker, LayoutConfig, Panel, TileSize};
+use crate::accounts::AccountSummary;
+use crate::data::{Bar, Interval, PriceSeries, StockInfo};
+use crate::drawings::{Drawing, Point};
+use crate::frame_cache::{FrameCache, PanelKey};
+use crate::overview::OverviewView;
+use crate::fx::{self, Rates};
+use crate::date_range::{PickerRow, Preset, RangePicker};
+use crate::dividends::DividendsView;
+use crate::ids::Ticker;
+use crate::jobs::JobStatus;
+use crate::keymap::Action;
+use crate::broker::{BrokerState, BrokerView, Order, Trigger};
+use crate::game::{GameView, Side, HOUSE};
+use crate::health::{HealthReport, Outcome};
+use crate::hints::{self, Hint};
+use crate::market::book::{Level, OrderBook};
+use crate::markets;
+use crate::menu::{self, MenuState};
+use crate::ml::history;
+use crate::ml::registry::ModelsView;
+use crate::refresh::{Source, Status};
+use crate::returns::{self, ReturnsView};
+use crate::stats;
+use crate::strategy::Signal;
+use crate::theme::Theme;
+use crate::time_travel::TimeTravelView;
+use crate::trades;
+use crate::universe;
+use crate::update::{self, Release};
+
+/// Bordered panel block, highlighted when the panel has focus.
+fn panel_block<'a>(theme: &Theme, title: impl Into<Spans<'a>>, focused: bool) -> Block<'a> {
+    let block = Block::default().title(title).borders(Borders::ALL);
+    if focused {
+        block.border_style(Style::default().fg(theme.border_focused).add_modifier(Modifier::BOLD))
+    } else {
+        block.border_style(Style::default().fg(theme.border))
+    }
+}
+
+/// `panel_block` for a panel showing `source`'s data. The title notes a
+/// reload in progress; if the last load failed, the error is drawn in place
+/// of the contents and `None` returned, so the caller draws nothing else.
+fn source_block<'a, B: Backend>(
+    f: &mut Frame<B>,
+    app: &App,
+    source: Source,
+    title: impl Into<Spans<'a>>,
+    focused: bool,
+    area: Rect,
+) -> Option<Block<'a>> {
+    let theme = &app.theme;
+    let mut title = title.into();
+    match app.load_status(source) {
+        Status::Ready => Some(panel_block(theme, title, focused)),
+        Status::Loading => {
+            title.0.push(Span::styled(" (loading…)", Style::default().fg(theme.muted)));
+            Some(panel_block(theme, title, focused))
+        }
+        Status::Failed(message) => {
+            title.0.push(Span::styled(" (error)", Style::default().fg(theme.error)));
+            let lines = vec![
+                Spans::from(Span::styled(message.clone(), Style::default().fg(theme.error))),
+                Spans::from(Span::styled(
+                    format!("{} to retry", app.keymap.label(Action::Refresh)),
+                    Style::default().fg(theme.muted),
+                )),
+            ];
+            let block = panel_block(theme, title, focused);
+            f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: true }).block(block), area);
+            None
+        }
+    }
+}
+
+/// `3400000.0` as `3.4M`, for axis labels.
+fn compact_number(n: f64) -> String {
+    match n.abs() {
+        a if a >= 1e9 => format!("{:.1}B", n / 1e9),
+        a if a >= 1e6 => format!("{:.1}M", n / 1e6),
+        a if a >= 1e3 => format!("{:.1}K", n / 1e3),
+        _ => format!("{:.0}", n),
+    }
+}
+
+/// What `[chart] marker` draws the charts' lines with.
+fn marker(app: &App) -> Marker {
+    match app.chart_config.marker {
+        ChartMarker::Braille => Marker::Braille,
+        ChartMarker::Dot => Marker::Dot,
+        ChartMarker::Block => Marker::Block,
+    }
+}
+
+/// Labelled ticks up the left of a Canvas `area` showing `x` by `y`, see
+/// `price_axis`.
+struct PriceAxis {
+    /// The x bound leaving the labels a gutter left of `x`.
+    left: f64,
+    x: [f64; 2],
+    /// Above the bottom row, which the date labels take, to the top.
+    y: [f64; 2],
+    /// Width and height of a cell in the canvas's units.
+    cell: (f64, f64),
+    ticks: Vec<(f64, String)>,
+}
+
+impl PriceAxis {
+    /// Gridlines across at each tick and up at each of `columns`, dotted
+    /// every other cell so the lines drawn over them stand out.
+    fn grid(&self, columns: &[f64]) -> Vec<(f64, f64)> {
+        let (w, h) = self.cell;
+        let across = self.ticks.iter().flat_map(|&(y, _)| {
+            let dots = ((self.x[1] - self.x[0]) / (w * 2.0)) as usize;
+            (0..=dots).map(move |i| (self.x[0] + i as f64 * w * 2.0, y))
+        });
+        let up = columns.iter().flat_map(|&x| {
+            let dots = ((self.y[1] - self.y[0]) / h) as usize;
+            (0..=dots).map(move |i| (x, self.y[0] + i as f64 * h))
+        });
+        across.chain(up).collect()
+    }
+}
+
+/// `[chart] ticks` round values of `y`, each labelled by `label`. With
+/// `log` set `y` is the log of the values, which are ticked as
+/// `axis::log_ticks`. The gutter is dropped when `area` is too narrow to
+/// spare it.
+fn price_axis(app: &App, area: Rect, x: [f64; 2], y: [f64; 2], log: bool, label: impl Fn(&axis::Ticks, f64) -> String) -> PriceAxis {
+    let height = (y[1] - y[0]) / area.height.max(1) as f64;
+    let bottom = y[0] + height;
+    let count = app.chart_config.ticks;
+    let (ticks, at): (_, fn(f64) -> f64) = match log {
+        true => (axis::log_ticks(bottom.exp(), y[1].exp(), count), f64::ln),
+        false => (axis::ticks(bottom, y[1], count), |v| v),
+    };
+    let labels: Vec<(f64, String)> = ticks.values.iter().map(|&v| (at(v), label(&ticks, v))).collect();
+    let width = labels.iter().map(|(_, l)| l.chars().count()).max().unwrap_or(0) + 1;
+    let gutter = !labels.is_empty() && area.width as usize >= width * 4;
+    let columns = area.width.max(1) as f64 - if gutter { width as f64 } else { 0.0 };
+    let cell = ((x[1] - x[0]) / columns, height);
+    let left = if gutter { x[0] - width as f64 * cell.0 } else { x[0] };
+    let labels = match gutter {
+        true => labels.into_iter().map(|(y, l)| (y, format!("{:>1$}", l, width - 1))).collect(),
+        false => Vec::new(),
+    };
+    PriceAxis { left, x, y: [bottom, y[1]], cell, ticks: labels }
+}
+
+/// Eighth-cell block characters for fractional bar ends, narrowest first.
+const PARTIAL_BLOCKS: [&str; 7] = ["▏", "▎", "▍", "▌", "▋", "▊", "▉"];
+
+/// A bar `cells` wide (fractions drawn with partial blocks).
+fn bar(cells: f64) -> String {
+    let eighths = (cells.max(0.0) * 8.0).round() as usize;
+    let mut s = "█".repeat(eighths / 8);
+    if let Some(partial) = (eighths % 8).checked_sub(1) {
+        s.push_str(PARTIAL_BLOCKS[partial]);
+    }
+    s
+}
+
+/// One line per open account: name, a bar proportional to its share of the
+/// combined current balance, and the share itself. Balances are compared in
+/// the base currency; accounts without a rate are left out.
+fn allocation_lines(accounts: &[AccountSummary], rates: &Rates, width: u16) -> Vec<Spans<'static>> {
+    const NAME_WIDTH: usize = 12;
+    let open: Vec<(&AccountSummary, f64)> = accounts
+        .iter()
+        .filter(|a| !a.archived)
+        .filter_map(|a| Some((a, rates.convert(a.current_amount, &a.currency)?.max(0.0))))
+        .collect();
+    let total: f64 = open.iter().map(|&(_, value)| value).sum();
+    if total <= 0.0 {
+        return vec![Spans::from("No open accounts with a positive balance")];
+    }
+    let bar_width = (width as usize).saturating_sub(NAME_WIDTH + 9) as f64;
+    open.iter()
+        .map(|&(acc, value)| {
+            let share = value / total;
+            let name: String = acc.name.as_str().chars().take(NAME_WIDTH - 1).collect();
+            Spans::from(vec![
+                Span::raw(format!("{:<w$}", name, w = NAME_WIDTH)),
+                Span::styled(format!("{:<w$}", bar(share * bar_width), w = bar_width as usize), Style::default().fg(Color::Cyan)),
+                Span::raw(format!(" {:>6.1}%", share * 100.0)),
+            ])
+        })
+        .collect()
+}
+
+/// Help overlay text, built from the active key bindings.
+fn instruction_lines(app: &App) -> Vec<Spans<'static>> {
+    let key_style = Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD);
+    let mut lines = vec![Spans::from("Shortcuts (remap in the [keys] section of stm.toml):")];
+    for action in Action::ALL {
+        lines.push(Spans::from(vec![
+            Span::styled(format!(" {:>10}  ", app.keymap.label(action)), key_style),
+            Span::raw(action.description()),
+        ]));
+    }
+    lines.push(Spans::from(""));
+    lines.push(Spans::from("Fixed keys:"));
+    for (key, what) in [
+        ("Up/Down", "Scroll the focused panel (chart pans through bars)"),
+        ("Enter", "Run preprocess & model on the selected stock; in a box, submit"),
+        ("Esc", "Cancel search/filter, close forms and views"),
+        ("Click", "Focus a panel and select the row under the pointer"),
+        ("Wheel", "Scroll the panel under the pointer"),
+        ("Ctrl+Z", "Suspend to the shell; fg to resume"),
+        ("", "Search: TICKER [INTERVAL] [RANGE], e.g. 'AAPL', 'AAPL 5m', 'AAPL 1h 1mo'"),
+    ] {
+        lines.push(Spans::from(vec![Span::styled(format!(" {:>10}  ", key), key_style), Span::raw(what)]));
+    }
+    lines
+}
+
+/// Full-screen Errors view, newest first.
+fn draw_errors<B: Backend>(f: &mut Frame<B>, app: &App, size: Rect) {
+    let mut lines: Vec<Spans> = app
+        .errors
+        .entries
+        .iter()
+        .rev()
+        .map(|entry| {
+            let mut spans = vec![
+                Span::styled(entry.last_seen.format("%H:%M:%S ").to_string(), Style::default().fg(app.theme.muted)),
+                Span::styled(format!("{:<5}", entry.error.kind()), Style::default().fg(app.theme.error)),
+                Span::raw(entry.error.to_string()),
+            ];
+            if entry.count > 1 {
+                spans.push(Span::styled(
+                    format!("  (x{} since {})", entry.count, entry.first_seen.format("%H:%M:%S")),
+                    Style::default().fg(app.theme.muted),
+                ));
+            }
+            Spans::from(spans)
+        })
+        .collect();
+    if lines.is_empty() {
+        lines.push(Spans::from("No errors this session"));
+    }
+    let title = format!("Errors ({}/Esc: close, c: clear)", app.keymap.label(Action::ShowErrors));
+    let block = panel_block(&app.theme, title, false);
+    f.render_widget(Paragraph::new(lines).block(block), size);
+}
+
+/// Full-screen startup check report: one line per check, with the hint
+/// under any that didn't pass.
+fn draw_health<B: Backend>(f: &mut Frame<B>, app: &App, report: &HealthReport, size: Rect) {
+    let theme = &app.theme;
+    let mut lines = Vec::new();
+    for check in &report.checks {
+        let (mark, color) = match check.outcome {
+            Outcome::Pass => ("ok  ", theme.gain),
+            Outcome::Skip => ("--  ", theme.muted),
+            Outcome::Warn => ("warn", theme.accent),
+            Outcome::Fail => ("FAIL", theme.error),
+        };
+        lines.push(Spans::from(vec![
+            Span::styled(format!(" {} ", mark), Style::default().fg(color).add_modifier(Modifier::BOLD)),
+            Span::styled(format!("{:<20}", check.name), Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(check.detail.clone()),
+        ]));
+        if !check.hint.is_empty() {
+            lines.push(Spans::from(Span::styled(format!("{:26}→ {}", "", check.hint), Style::default().fg(theme.muted))));
+        }
+    }
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(Span::styled(
+        format!(
+            "{}: quit. Problems found later show in the Errors view ({})",
+            app.keymap.label(Action::Quit),
+            app.keymap.label(Action::ShowErrors)
+        ),
+        Style::default().fg(theme.muted),
+    )));
+    let title = format!(
+        "Setup check: {} passed, {} warnings, {} failed (Enter/Esc: continue to the dashboard)",
+        report.count(Outcome::Pass),
+        report.count(Outcome::Warn),
+        report.count(Outcome::Fail)
+    );
+    let block = panel_block(theme, title, true);
+    f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }).block(block), size);
+}
+
+/// Full-screen Stats view of the local usage counters.
+fn draw_usage<B: Backend>(f: &mut Frame<B>, app: &App, size: Rect) {
+    let usage = &app.usage;
+    let since = usage.first_used.map_or("this session".to_string(), |at| at.format("%Y-%m-%d").to_string());
+    let mut lines = vec![
+        Spans::from(format!("Since {} ({} sessions)", since, usage.sessions)),
+        Spans::from(""),
+        Spans::from(format!("  Model runs        {:>6}", usage.ml_runs)),
+        Spans::from(format!("  Downloads         {:>6}", usage.downloads)),
+        Spans::from(format!("  Gap fills         {:>6}", usage.gap_fills)),
+        Spans::from(format!("  Trades entered    {:>6}", usage.trades_entered)),
+        Spans::from(format!("  Accounts created  {:>6}", usage.accounts_created)),
+        Spans::from(""),
+        Spans::from(Span::styled("Most viewed tickers", Style::default().add_modifier(Modifier::BOLD))),
+    ];
+    let top = usage.top_tickers(10);
+    if top.is_empty() {
+        lines.push(Spans::from("  none yet"));
+    }
+    for (ticker, views) in top {
+        lines.push(Spans::from(format!("  {:<10} {:>6}", ticker.as_str(), views)));
+    }
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(Span::styled(
+        format!("Stored only in {}; nothing is sent anywhere.", stats::STATS_PATH),
+        Style::default().fg(app.theme.muted),
+    )));
+    let title = format!("Stats about stm ({}/Esc: close)", app.keymap.label(Action::ShowUsage));
+    let block = panel_block(&app.theme, title, false);
+    f.render_widget(Paragraph::new(lines).block(block), size);
+}
+
+/// Full-screen list of what the retention policy would drop.
+fn draw_retention_report<B: Backend>(f: &mut Frame<B>, app: &App, report: &[String], size: Rect) {
+    let mut lines: Vec<Spans> = report.iter().map(|line| Spans::from(format!("  {}", line))).collect();
+    if lines.is_empty() {
+        lines.push(Spans::from("  Nothing is past the retention policy"));
+    }
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(Span::styled(
+        format!("Dry run: nothing was deleted. Policy in [retention] of {}.", config::CONFIG_PATH),
+        Style::default().fg(app.theme.muted),
+    )));
+    let title = format!("Retention report ({}/Esc: close)", app.keymap.label(Action::RetentionReport));
+    f.render_widget(Paragraph::new(lines).block(panel_block(&app.theme, title, false)), size);
+}
+
+/// Full-screen histogram of a ticker's daily returns, one line per bin,
+/// with the bins holding the mean and one σ either side marked.
+fn draw_returns<B: Backend>(f: &mut Frame<B>, app: &App, view: &ReturnsView, size: Rect) {
+    const LABEL_WIDTH: usize = 18;
+    const COUNT_WIDTH: usize = 12;
+    let theme = &app.theme;
+    let stats = view.stats;
+    let title = format!(
+        "Daily returns: {} ({} sessions, {}/Esc: close)",
+        view.ticker,
+        stats.count,
+        app.keymap.label(Action::ShowReturns)
+    );
+    let block = panel_block(theme, title, false);
+    let inner = block.inner(size);
+    let mut lines = vec![
+        Spans::from(format!("Mean {:+.2}%   σ {:.2}%   Skew {:+.2}", stats.mean, stats.std_dev, stats.skew)),
+        Spans::from(""),
+    ];
+    let bins = returns::histogram(&view.returns, (inner.height as usize).saturating_sub(2).clamp(1, 40));
+    let most = bins.iter().map(|b| b.count).max().unwrap_or(0).max(1) as f64;
+    let bar_width = (inner.width as usize).saturating_sub(LABEL_WIDTH + COUNT_WIDTH) as f64;
+    let marks = [(stats.mean - stats.std_dev, "-σ"), (stats.mean, "mean"), (stats.mean + stats.std_dev, "+σ")];
+    for (i, bin) in bins.iter().enumerate() {
+        let last = i + 1 == bins.len();
+        let holds = |x: f64| x >= bin.low && (x < bin.high || (last && x <= bin.high));
+        let marked: Vec<&str> = marks.iter().filter(|(x, _)| holds(*x)).map(|&(_, label)| label).collect();
+        let color = theme.change((bin.low + bin.high) / 2.0);
+        let cells = bin.count as f64 / most * bar_width;
+        lines.push(Spans::from(vec![
+            Span::raw(format!("{:>w$} ", format!("{:+.2}..{:+.2}%", bin.low, bin.high), w = LABEL_WIDTH - 1)),
+            Span::styled(format!("{:<w$}", bar(cells), w = bar_width as usize), Style::default().fg(color)),
+            Span::raw(format!(" {:>4}", bin.count)),
+            Span::styled(
+                if marked.is_empty() { String::new() } else { format!(" {}", marked.join(" ")) },
+                Style::default().fg(theme.accent),
+            ),
+        ]));
+    }
+    f.render_widget(Paragraph::new(lines).block(block), size);
+}
+
+/// Full-screen performance of the trade history: the ratios, a heatmap of
+/// monthly returns by year, and each month's results, newest first.
+fn draw_analytics<B: Backend>(f: &mut Frame<B>, app: &App, stats: &Analytics, size: Rect) {
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+    let theme = &app.theme;
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [Constraint::Length(6), Constraint::Length(stats.years.len().max(1) as u16 + 3), Constraint::Min(0)].as_ref(),
+        )
+        .split(size);
+    let money = |v: Option<f64>| v.map_or("-".to_string(), |v| format!("{:+.2}", v));
+    let ratio = |v: Option<f64>, unit: &str| v.map_or("-".to_string(), |v| format!("{:.2}{}", v, unit));
+    let summary = vec![
+        Spans::from(format!(
+            "Trades {}   Wins {}   Losses {}   Win rate {}   Dividends {:.2}",
+            stats.trades,
+            stats.wins,
+            stats.losses,
+            ratio(stats.win_rate(), "%"),
+            stats.dividends
+        )),
+        Spans::from(format!(
+            "Net P&L {:+.2}   Gross P&L {:+.2}   Fees {:.2}",
+            stats.net_pnl(),
+            stats.gross_pnl(),
+            stats.fees
+        )),
+        Spans::from(format!(
+            "Avg win {}   Avg loss {}   Profit factor {}   Expectancy {} per trade",
+            money(stats.avg_win()),
+            money(stats.avg_loss()),
+            ratio(stats.profit_factor(), ""),
+            money(stats.expectancy())
+        )),
+        Spans::from(Span::styled(
+            "Transfers are left out and fees taken off; returns are of the total account value at the start of each month",
+            Style::default().fg(theme.muted),
+        )),
+    ];
+    let title = format!("Performance of the trade history ({}/Esc: close)", app.keymap.label(Action::Analytics));
+    f.render_widget(Paragraph::new(summary).block(panel_block(theme, title, true)), rows[0]);
+
+    // Stronger moves fill the cell, weaker ones only color the figure.
+    let heat = |period: Option<&analytics::Period>| match period.and_then(|p| p.return_pct()) {
+        None => Cell::from(format!("{:>7}", "")),
+        Some(pct) if pct.abs() >= 5.0 => Cell::from(format!("{:>+7.1}", pct))
+            .style(Style::default().fg(Color::Black).bg(theme.change(pct)).add_modifier(Modifier::BOLD)),
+        Some(pct) if pct.abs() >= 1.0 => {
+            Cell::from(format!("{:>+7.1}", pct)).style(Style::default().fg(theme.change(pct)).add_modifier(Modifier::BOLD))
+        }
+        Some(pct) => Cell::from(format!("{:>+7.1}", pct)).style(Style::default().fg(theme.change(pct))),
+    };
+    let heat_rows: Vec<Row> = stats
+        .years
+        .iter()
+        .map(|(&year, total)| {
+            let mut cells = vec![Cell::from(year.to_string())];
+            cells.extend((1..=12).map(|month| heat(stats.months.get(&(year, month)))));
+            cells.push(heat(Some(total)));
+            Row::new(cells)
+        })
+        .collect();
+    let mut header = vec!["Year".to_string()];
+    header.extend(MONTHS.iter().chain(&["Year"]).map(|m| format!("{:>7}", m)));
+    let mut widths = vec![Constraint::Length(6)];
+    widths.extend([Constraint::Length(7); 13]);
+    let heatmap = Table::new(heat_rows)
+        .header(Row::new(header).style(Style::default().add_modifier(Modifier::BOLD)))
+        .block(panel_block(theme, "Monthly returns, %", false))
+        .widths(&widths);
+    f.render_widget(heatmap, rows[1]);
+
+    let month_rows: Vec<Row> = stats
+        .months
+        .iter()
+        .rev()
+        .map(|(&(year, month), period)| {
+            let pct = period.return_pct().map_or("-".to_string(), |p| format!("{:+.2}%", p));
+            Row::new(vec![
+                Cell::from(format!("{} {}", MONTHS[month as usize - 1], year)),
+                Cell::from(format!("{:>6}", period.trades)),
+                Cell::from(format!("{:>+12.2}", period.pnl)).style(Style::default().fg(theme.change(period.pnl))),
+                Cell::from(format!("{:>9}", pct)),
+                Cell::from(format!("{:>12.2}", period.start)),
+            ])
+        })
+        .collect();
+    let header = Row::new(vec!["Month", "Trades", "         P&L", "   Return", "  Start value"])
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    let months = Table::new(month_rows)
+        .header(header)
+        .block(panel_block(theme, format!("Monthly P&L ({} months, newest first)", stats.months.len()), false))
+        .widths(&[
+            Constraint::Length(9),
+            Constraint::Length(7),
+            Constraint::Length(13),
+            Constraint::Length(10),
+            Constraint::Length(13),
+        ]);
+    f.render_widget(months, rows[2]);
+}
+
+/// Full-screen dividend income per holding, with what each should pay over
+/// the next year.
+fn draw_dividends<B: Backend>(f: &mut Frame<B>, app: &App, view: &DividendsView, size: Rect) {
+    let theme = &app.theme;
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(4), Constraint::Min(0)].as_ref())
+        .split(size);
+    let summary = vec![
+        Spans::from(format!(
+            "Received over the last year {:.2}   Projected over the next year {:.2}",
+            view.trailing(),
+            view.projected()
+        )),
+        Spans::from(Span::styled(
+            "Projected from the provider's yearly rate per share where known, else the last year's dividends",
+            Style::default().fg(theme.muted),
+        )),
+    ];
+    let title = format!("Dividends ({}/Esc: close, Enter: record a dividend)", app.keymap.label(Action::Dividends));
+    f.render_widget(Paragraph::new(summary).block(panel_block(theme, title, true)), rows[0]);
+
+    if view.incomes.is_empty() {
+        let text = "No dividends recorded, and no shares held by broker fills";
+        let empty = Paragraph::new(Span::styled(text, Style::default().fg(theme.muted)))
+            .block(panel_block(theme, "Holdings (↑/↓: pick)", false));
+        f.render_widget(empty, rows[1]);
+        return;
+    }
+    let income_rows: Vec<Row> = view
+        .incomes
+        .iter()
+        .map(|i| {
+            let shares = if i.shares > 0.0 { format!("{:>8}", i.shares) } else { format!("{:>8}", "-") };
+            let rate = i.rate.map_or(format!("{:>9}", "-"), |r| format!("{:>9.2}", r));
+            Row::new(vec![
+                Cell::from(i.ticker.to_string()),
+                Cell::from(shares),
+                Cell::from(rate),
+                Cell::from(format!("{:>10.2}", i.received)),
+                Cell::from(format!("{:>10.2}", i.trailing)),
+                Cell::from(format!("{:>10.2}", i.projected)).style(Style::default().fg(theme.change(i.projected))),
+            ])
+        })
+        .collect();
+    let header = Row::new(vec!["Ticker", "  Shares", "Rate/yr", "  Received", " Last year", " Next year"])
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    let mut state = TableState::default();
+    state.select(Some(view.selected.min(view.incomes.len() - 1)));
+    let table = Table::new(income_rows)
+        .header(header)
+        .block(panel_block(theme, "Holdings (↑/↓: pick)", false))
+        .highlight_style(theme.selected())
+        .widths(&[
+            Constraint::Length(10),
+            Constraint::Length(9),
+            Constraint::Length(10),
+            Constraint::Length(11),
+            Constraint::Length(11),
+            Constraint::Length(11),
+        ]);
+    f.render_stateful_widget(table, rows[1], &mut state);
+}
+
+/// Full-screen trading game: the order line, the leaderboard and recent
+/// fills, and the book and open orders of the last ticker traded.
+fn draw_game<B: Backend>(f: &mut Frame<B>, app: &App, view: &GameView, size: Rect) {
+    let theme = &app.theme;
+    let game = &view.game;
+    let marks = app.marks();
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(4), Constraint::Min(0)].as_ref())
+        .split(size);
+    let title = format!("Trading Game: order (Enter: place, {}/Esc: close)", app.keymap.label(Action::TradingGame));
+    let input = Paragraph::new(vec![
+        Spans::from(format!("> {}", view.input)),
+        Spans::from(Span::styled(view.message.clone(), Style::default().fg(theme.muted))),
+    ])
+    .block(panel_block(theme, title, true));
+    f.render_widget(input, rows[0]);
+
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(65), Constraint::Percentage(35)].as_ref())
+        .split(rows[1]);
+    let left = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+        .split(columns[0]);
+
+    let standings = game.leaderboard(&marks);
+    let table_rows: Vec<Row> = standings
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            let positions = game
+                .players
+                .iter()
+                .find(|p| p.name == s.name)
+                .map(|p| p.positions.iter().map(|(t, q)| format!("{} {}", t, q)).collect::<Vec<_>>().join(", "))
+                .unwrap_or_default();
+            Row::new(vec![
+                Cell::from(format!("{}", i + 1)),
+                Cell::from(s.name.to_string()),
+                Cell::from(format!("{:>10.2}", s.cash)),
+                Cell::from(format!("{:>10.2}", s.holdings)),
+                Cell::from(format!("{:>10.2}", s.total)),
+                Cell::from(format!("{:>+7.2}%", s.return_pct)).style(Style::default().fg(theme.change(s.return_pct))),
+                Cell::from(positions),
+            ])
+        })
+        .collect();
+    let header = Row::new(vec!["#", "Player", "      Cash", "  Holdings", "     Total", " Return", "Positions"])
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    let leaderboard = Table::new(table_rows)
+        .header(header)
+        .block(panel_block(theme, format!("Leaderboard (started with {:.2} each)", game.starting_cash), false))
+        .widths(&[
+            Constraint::Length(3),
+            Constraint::Length(14),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(8),
+            Constraint::Min(10),
+        ]);
+    f.render_widget(leaderboard, left[0]);
+
+    let fills: Vec<Spans> = game
+        .fills
+        .iter()
+        .rev()
+        .take(left[1].height.saturating_sub(2) as usize)
+        .map(|fill| {
+            Spans::from(format!(
+                "{}  {} bought {} {} at {:.2} from {}",
+                fill.at.format("%H:%M:%S"),
+                fill.buyer,
+                fill.qty,
+                fill.ticker,
+                fill.price,
+                fill.seller
+            ))
+        })
+        .collect();
+    let fills = if fills.is_empty() { vec![Spans::from("No trades yet")] } else { fills };
+    f.render_widget(Paragraph::new(fills).block(panel_block(theme, "Fills", false)), left[1]);
+
+    let Some(ticker) = &view.ticker else {
+        let hint = Paragraph::new("Place an order to see its book").block(panel_block(theme, "Book", false));
+        f.render_widget(hint, columns[1]);
+        return;
+    };
+    let spread = app.game_config.house_spread_pct;
+    let title = match marks.get(ticker) {
+        Some(&mark) if spread > 0.0 => format!("Book: {} (last {:.2}, {} ±{:.2}%)", ticker, mark, HOUSE, spread / 2.0),
+        Some(&mark) => format!("Book: {} (last {:.2})", ticker, mark),
+        None => format!("Book: {}", ticker),
+    };
+    let book = game.book(ticker);
+    let mut lines = if book.bids.is_empty() && book.asks.is_empty() {
+        vec![Spans::from("No resting orders")]
+    } else {
+        book_lines(theme, &book, columns[1].width.saturating_sub(2))
+    };
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(Span::styled("Open orders", Style::default().add_modifier(Modifier::BOLD))));
+    for order in game.orders.iter().filter(|o| &o.ticker == ticker) {
+        let side = match order.side {
+            Side::Buy => "buy",
+            Side::Sell => "sell",
+        };
+        lines.push(Spans::from(format!("#{} {} {} {} at {:.2}", order.id, order.player, side, order.qty, order.limit)));
+    }
+    f.render_widget(Paragraph::new(lines).block(panel_block(theme, title, false)), columns[1]);
+}
+
+/// Full-screen broker view: the order line over the paper account's
+/// positions, each with its break-even price and P&L before and after
+/// fees, how far it is from its entry and its nearest stop or target, and
+/// this session's orders.
+fn draw_broker<B: Backend>(f: &mut Frame<B>, app: &App, view: &BrokerView, size: Rect) {
+    let theme = &app.theme;
+    let none = BrokerState::default();
+    let broker = app.broker.as_deref();
+    let state = broker.map_or(&none, |b| b.state());
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [Constraint::Length(4), Constraint::Percentage(30), Constraint::Percentage(25), Constraint::Min(0)].as_ref(),
+        )
+        .split(size);
+    let mut title = broker.map_or("Broker", |b| b.name()).to_string();
+    if let Some(b) = broker.and_then(|b| b.account()) {
+        title.push_str(&format!(": cash {:.2}, equity {:.2}, buying power {:.2}", b.cash, b.equity, b.buying_power));
+    }
+    title.push_str(&format!(" (Enter: send, {}/Esc: close)", app.keymap.label(Action::Broker)));
+    let status = match (&state.last_error, state.last_update) {
+        (Some(err), _) => Span::styled(err.clone(), Style::default().fg(theme.error)),
+        (None, None) => Span::styled("Connecting…", Style::default().fg(theme.muted)),
+        (None, Some(_)) => Span::styled(view.message.clone(), Style::default().fg(theme.muted)),
+    };
+    let input = Paragraph::new(vec![Spans::from(format!("> {}", view.input)), Spans::from(status)])
+        .block(panel_block(theme, title, true));
+    f.render_widget(input, rows[0]);
+
+    let positions = broker.map_or(&[][..], |b| b.positions());
+    let booking = broker.map_or("", |b| b.booking_account()).trim();
+    let position_rows: Vec<Row> = positions
+        .iter()
+        .map(|p| {
+            // The dashboard's price is refreshed more often than the
+            // broker reports.
+            let listed = app.stocks.iter().find(|s| s.ticker == p.ticker).map(|s| s.price);
+            let p = listed.filter(|&price| price > 0.0).map_or_else(|| p.clone(), |price| p.repriced(price));
+            let from_entry = match p.pct_from_entry() {
+                Some(pct) => Cell::from(format!("{:>+9.2}%", pct)).style(Style::default().fg(theme.change(pct))),
+                None => Cell::from(format!("{:>10}", "-")),
+            };
+            let rules = markets::rules_for(&p.ticker);
+            let cost = p.cost(app.lots.holding(booking, &p.ticker), &app.fees_config);
+            let break_even = p.break_even(cost, &app.fees_config);
+            let net = match p.net_pl(cost, &app.fees_config) {
+                Some(net) => Cell::from(format!("{:>+10.2}", net)).style(Style::default().fg(theme.change(net))),
+                None => Cell::from(format!("{:>10}", "-")),
+            };
+            let exit = match p.nearest_exit(&state.orders) {
+                Some((trigger, pct)) => {
+                    let (label, color) = match trigger {
+                        Trigger::StopLoss(_) => ("stop", theme.loss),
+                        Trigger::TakeProfit(_) => ("tp", theme.gain),
+                    };
+                    Cell::from(format!("{:>4} {:>+7.2}%", label, pct)).style(Style::default().fg(color))
+                }
+                None => Cell::from(format!("{:>13}", "-")),
+            };
+            Row::new(vec![
+                Cell::from(p.ticker.to_string()),
+                Cell::from(format!("{:>10}", p.qty)),
+                Cell::from(format!("{:>10}", rules.price(p.avg_entry_price))),
+                Cell::from(format!("{:>10}", rules.price(p.current_price))),
+                Cell::from(format!("{:>12.2}", p.market_value)),
+                Cell::from(format!("{:>10}", break_even.map_or("-".to_string(), |b| rules.price(b)))),
+                Cell::from(format!("{:>+10.2}", p.unrealized_pl)).style(Style::default().fg(theme.change(p.unrealized_pl))),
+                net,
+                from_entry,
+                exit,
+            ])
+        })
+        .collect();
+    let header = Row::new(vec![
+        "Ticker",
+        "       Qty",
+        "  Avg cost",
+        "     Price",
+        "       Value",
+        "Break-even",
+        "     Gross",
+        "       Net",
+        "From entry",
+        "  Stop/target",
+    ])
+    .style(Style::default().add_modifier(Modifier::BOLD));
+    let positions = Table::new(position_rows)
+        .header(header)
+        .block(panel_block(theme, format!("Positions ({})", positions.len()), false))
+        .widths(&[
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(12),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(13),
+        ]);
+    f.render_widget(positions, rows[1]);
+
+    let order_row = |o: &Order| {
+        Row::new(vec![
+            Cell::from(o.submitted_at.map(|at| at.format("%H:%M:%S").to_string()).unwrap_or_default()),
+            Cell::from(o.id.chars().take(8).collect::<String>()),
+            Cell::from(o.side.as_str()),
+            Cell::from(format!("{}/{}", o.filled_qty, o.qty)),
+            Cell::from(o.ticker.to_string()),
+            Cell::from(o.price_label()),
+            Cell::from(o.status.clone()),
+        ])
+    };
+    let header = || {
+        Row::new(vec!["Sent", "Id", "Side", "Filled", "Ticker", "Price", "Status"])
+            .style(Style::default().add_modifier(Modifier::BOLD))
+    };
+    let widths = [
+        Constraint::Length(9),
+        Constraint::Length(9),
+        Constraint::Length(5),
+        Constraint::Length(12),
+        Constraint::Length(10),
+        Constraint::Length(12),
+        Constraint::Min(10),
+    ];
+
+    let open: Vec<Row> = state.open_orders().map(order_row).collect();
+    let mut open_state = TableState::default();
+    if !open.is_empty() {
+        open_state.select(Some(view.selected.min(open.len() - 1)));
+    }
+    let open_title = format!("Open orders ({}, ↑/↓: pick, Del: cancel)", open.len());
+    let open = Table::new(open)
+        .header(header())
+        .block(panel_block(theme, open_title, false))
+        .highlight_style(theme.selected())
+        .widths(&widths);
+    f.render_stateful_widget(open, rows[2], &mut open_state);
+
+    let order_rows: Vec<Row> = state
+        .orders
+        .iter()
+        .map(|o| {
+            let style = if o.is_open() { Style::default().fg(theme.accent) } else { Style::default() };
+            order_row(o).style(style)
+        })
+        .collect();
+    let orders = Table::new(order_rows)
+        .header(header())
+        .block(panel_block(theme, "Orders, newest first (open ones highlighted)", false))
+        .widths(&widths);
+    f.render_widget(orders, rows[3]);
+}
+
+/// Full-screen model versions of a ticker, newest first, the active one
+/// marked.
+fn draw_models<B: Backend>(f: &mut Frame<B>, app: &App, view: &ModelsView, size: Rect) {
+    let theme = &app.theme;
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .split(size);
+    let title = format!(
+        "Models for {} (Enter: use, t: train new, {}/Esc: close)",
+        view.ticker,
+        app.keymap.label(Action::Models)
+    );
+    let message = Paragraph::new(Span::styled(view.message.clone(), Style::default().fg(theme.muted)))
+        .block(panel_block(theme, title, true));
+    f.render_widget(message, rows[0]);
+
+    let active = view.registry.active(&view.ticker).map(|v| v.version);
+    let versions = view.registry.versions(&view.ticker);
+    let date = |at: Option<NaiveDateTime>| at.map_or("-".to_string(), |at| at.format("%Y-%m-%d").to_string());
+    let table_rows: Vec<Row> = versions
+        .iter()
+        .map(|v| {
+            let metrics: Vec<String> = v.metrics.iter().map(|(name, value)| format!("{} {:.4}", name, value)).collect();
+            let row = Row::new(vec![
+                Cell::from(format!("{} v{}", if active == Some(v.version) { "●" } else { " " }, v.version)),
+                Cell::from(v.trained_at.format("%Y-%m-%d %H:%M").to_string()),
+                Cell::from(format!("{} to {}", date(v.data_start), date(v.data_end))),
+                Cell::from(format!("{:>6}", v.bars)),
+                Cell::from(metrics.join(", ")),
+            ]);
+            if active == Some(v.version) { row.style(Style::default().fg(theme.accent)) } else { row }
+        })
+        .collect();
+    let header = Row::new(vec!["Version", "Trained", "Data", "  Bars", "Metrics"])
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    let table = Table::new(table_rows)
+        .header(header)
+        .block(panel_block(theme, format!("Versions ({}, ● predicts)", versions.len()), false))
+        .highlight_style(theme.selected())
+        .widths(&[
+            Constraint::Length(8),
+            Constraint::Length(17),
+            Constraint::Length(25),
+            Constraint::Length(6),
+            Constraint::Length(60),
+        ]);
+    let mut state = TableState::default();
+    if !versions.is_empty() {
+        state.select(Some(view.selected));
+    }
+    f.render_stateful_widget(table, rows[1], &mut state);
+}
+
+/// Full-screen portfolio as of a past day: balances and stored prices then,
+/// and the trades made up to it, newest first.
+fn draw_time_travel<B: Backend>(f: &mut Frame<B>, app: &App, view: &TimeTravelView, size: Rect) {
+    let theme = &app.theme;
+    let snapshot = &view.snapshot;
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(4), Constraint::Percentage(45), Constraint::Min(0)].as_ref())
+        .split(size);
+    let title = format!(
+        "Time travel: {} (←/→: day, PgUp/PgDn: month, type a date + Enter, {}/Esc: close)",
+        snapshot.date.format("%a %Y-%m-%d"),
+        app.keymap.label(Action::TimeTravel)
+    );
+    let status = match &view.error {
+        Some(err) => Span::styled(err.clone(), Style::default().fg(theme.error)),
+        None => Span::styled(
+            format!("{} trades by then; balances after the last one, prices at that day's close", snapshot.trades.len()),
+            Style::default().fg(theme.muted),
+        ),
+    };
+    let header = Paragraph::new(vec![Spans::from(format!("> {}", view.input)), Spans::from(status)])
+        .block(panel_block(theme, title, true));
+    f.render_widget(header, rows[0]);
+
+    let top = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(55), Constraint::Percentage(45)].as_ref())
+        .split(rows[1]);
+    let mut account_rows: Vec<Row> = snapshot
+        .accounts
+        .iter()
+        .map(|a| {
+            Row::new(vec![
+                Cell::from(a.name.to_string()),
+                Cell::from(format!("{:>10.2}", a.initial_amount)),
+                Cell::from(format!("{:>10.2}", a.current_amount)),
+                Cell::from(format!("{:>+10.2}", a.change)).style(Style::default().fg(theme.change(a.change))),
+                Cell::from(format!("{:>+8.2}%", a.percentage_change)),
+                Cell::from(a.currency.clone()),
+            ])
+        })
+        .collect();
+    let totals = fx::totals(snapshot.accounts.iter().filter(|a| !a.archived), &app.fx);
+    if !snapshot.accounts.is_empty() {
+        account_rows.push(
+            Row::new(vec![
+                format!("Total {}", app.fx.base()),
+                format!("{:>10.2}", totals.initial),
+                format!("{:>10.2}", totals.current),
+                format!("{:>+10.2}", totals.change),
+                format!("{:>+8.2}%", totals.percentage_change),
+                app.fx.base().to_string(),
+            ])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+        );
+    }
+    let accounts = Table::new(account_rows)
+        .header(Row::new(vec!["Name", "   Initial", "   Balance", "    Change", " % Change", "Ccy"]).bottom_margin(1))
+        .block(panel_block(theme, "Accounts (today's rates)", false))
+        .widths(&[
+            Constraint::Length(16),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(9),
+            Constraint::Length(4),
+        ]);
+    f.render_widget(accounts, top[0]);
+
+    let stock_rows: Vec<Row> = snapshot
+        .stocks
+        .iter()
+        .map(|s| {
+            let change_style = Style::default().fg(theme.change(s.change));
+            Row::new(vec![
+                Cell::from(s.ticker.as_str()),
+                Cell::from(format!("{:>10}", markets::price(&s.ticker, s.price))),
+                Cell::from(format!("{:>+10.2}", s.change)).style(change_style),
+                Cell::from(format!("{:>+8.2}%", s.pct_change)).style(change_style),
+                Cell::from(s.return_3m.map_or(format!("{:>9}", "-"), |r| format!("{:>+8.1}%", r))),
+            ])
+        })
+        .collect();
+    let stocks_title = match app.stocks.len().saturating_sub(snapshot.stocks.len()) {
+        0 => "Stocks".to_string(),
+        n => format!("Stocks ({} without history yet)", n),
+    };
+    let stocks = if stock_rows.is_empty() {
+        Table::new(vec![Row::new(vec!["No stored prices by then"])]).widths(&[Constraint::Min(10)])
+    } else {
+        Table::new(stock_rows)
+            .header(Row::new(vec!["Ticker", "     Price", "    Change", "     %", "   3M"]).bottom_margin(1))
+            .widths(&[
+                Constraint::Length(8),
+                Constraint::Length(10),
+                Constraint::Length(10),
+                Constraint::Length(9),
+                Constraint::Length(9),
+            ])
+    };
+    f.render_widget(stocks.block(panel_block(theme, stocks_title, false)), top[1]);
+
+    let bottom = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(36), Constraint::Min(0)].as_ref())
+        .split(rows[2]);
+    let position_rows: Vec<Row> = snapshot
+        .positions
+        .iter()
+        .map(|(ticker, p)| {
+            Row::new(vec![
+                Cell::from(ticker.as_str()),
+                Cell::from(format!("{:>6}", p.trades)),
+                Cell::from(format!("{:>+12.2}", p.net)).style(Style::default().fg(theme.change(p.net))),
+            ])
+        })
+        .collect();
+    let positions = Table::new(position_rows)
+        .header(Row::new(vec!["Ticker", "Trades", "         Net"]).bottom_margin(1))
+        .block(panel_block(theme, "Positions", false))
+        .widths(&[Constraint::Length(8), Constraint::Length(6), Constraint::Length(12)]);
+    f.render_widget(positions, bottom[0]);
+
+    let trade_rows: Vec<Row> = snapshot
+        .trades
+        .iter()
+        .rev()
+        .take(bottom[1].height.saturating_sub(4) as usize)
+        .map(|t| {
+            Row::new(vec![
+                Cell::from(t.timestamp.map_or("-".to_string(), |at| at.format("%Y-%m-%d %H:%M").to_string())),
+                Cell::from(t.name.to_string()),
+                Cell::from(format!("{:>+10.2}", t.transaction)).style(Style::default().fg(theme.change(t.transaction))),
+                Cell::from(format!("{:>10.2}", t.new_balance)),
+                Cell::from(t.ticker.as_ref().map_or("", |t| t.as_str())),
+                Cell::from(t.note.clone().unwrap_or_default()),
+            ])
+        })
+        .collect();
+    let widths = [
+        Constraint::Length(16),
+        Constraint::Length(12),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(6),
+        Constraint::Length(bottom[1].width.saturating_sub(61).max(10)),
+    ];
+    let trades = Table::new(trade_rows)
+        .header(Row::new(vec!["When", "Account", "    Amount", "   Balance", "Ticker", "Note"]).bottom_margin(1))
+        .block(panel_block(theme, "Trades up to then, newest first", false))
+        .widths(&widths);
+    f.render_widget(trades, bottom[1]);
+}
+
+/// Full-screen backtest of an alert rule: the price line with a marker on
+/// every bar the alert would have fired on, and the list of those bars.
+fn draw_alert_preview<B: Backend>(f: &mut Frame<B>, app: &App, preview: &AlertPreview, size: Rect) {
+    let theme = &app.theme;
+    let dates = if preview.range.is_all() { String::new() } else { format!(" {}", preview.range) };
+    let title = format!(
+        "Alert backtest: {} {} ({} triggers over {} bars{}, {}: dates, Esc: close)",
+        preview.ticker,
+        preview.rule,
+        preview.triggers.len(),
+        preview.bars.len(),
+        dates,
+        app.keymap.label(Action::DateRange)
+    );
+    let block = panel_block(theme, title, false);
+    let inner = block.inner(size);
+    f.render_widget(block, size);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+        .split(inner);
+
+    let bars = &preview.bars;
+    let x_max = bars.len().saturating_sub(1).max(1) as f64;
+    let (y_min, y_max) = bars.iter().fold((f64::MAX, f64::MIN), |(mn, mx), b| (mn.min(b.close), mx.max(b.close)));
+    let pad = ((y_max - y_min) * 0.1).max(0.01);
+    let line_segments: Vec<Line> = bars
+        .windows(2)
+        .enumerate()
+        .map(|(i, pair)| Line { x1: i as f64, y1: pair[0].close, x2: (i + 1) as f64, y2: pair[1].close, color: theme.chart_line })
+        .collect();
+    let markers: Vec<(f64, f64)> = preview.triggers.iter().map(|t| (t.index as f64, t.close)).collect();
+    // Close thresholds are drawn as a level line; the other metrics aren't
+    // on the price axis.
+    let level = (preview.rule.metric == Metric::Close).then_some(preview.rule.threshold);
+    let (level_color, marker_color) = (theme.muted, theme.accent);
+    let chart = Canvas::default()
+        .marker(marker(app))
+        .x_bounds([-0.5, x_max + 0.5])
+        .y_bounds([y_min.min(level.unwrap_or(y_min)) - pad, y_max.max(level.unwrap_or(y_max)) + pad])
+        .paint(move |ctx| {
+            for seg in &line_segments {
+                ctx.draw(seg);
+            }
+            if let Some(level) = level {
+                ctx.draw(&Line { x1: 0.0, y1: level, x2: x_max, y2: level, color: level_color });
+            }
+            ctx.draw(&Points { coords: &markers, color: marker_color });
+        });
+    f.render_widget(chart, chunks[0]);
+
+    let mut lines = vec![Spans::from(Span::styled(
+        format!("{:<20} {:>12} {:>10}", "Fired at", preview.rule.metric.name(), "Close"),
+        Style::default().add_modifier(Modifier::BOLD),
+    ))];
+    if preview.triggers.is_empty() {
+        lines.push(Spans::from("Never fired on this history."));
+    }
+    let at_format = if preview.interval == Interval::OneDay { "%Y-%m-%d" } else { "%Y-%m-%d %H:%M" };
+    for trigger in preview.triggers.iter().skip(preview.scroll) {
+        let value = match preview.rule.metric {
+            Metric::Close => format!("{:.2}", trigger.value),
+            Metric::PctChange => format!("{:+.2}%", trigger.value),
+            Metric::Volume => compact_number(trigger.value),
+        };
+        lines.push(Spans::from(format!(
+            "{:<20} {:>12} {:>10.2}",
+            trigger.at.format(at_format),
+            value,
+            trigger.close
+        )));
+    }
+    f.render_widget(Paragraph::new(lines), chunks[1]);
+}
+
+/// Status colour for a job row.
+fn job_color(theme: &Theme, status: JobStatus) -> Color {
+    match status {
+        JobStatus::Queued => Color::Gray,
+        JobStatus::Running => theme.accent,
+        JobStatus::Done => theme.gain,
+        JobStatus::Failed => theme.error,
+        JobStatus::Cancelled => theme.muted,
+    }
+}
+
+/// Jobs panel contents: one row per job, newest first, with what running
+/// ones are doing, and the message and stderr tail of the job under the
+/// cursor when the panel has focus.
+fn job_lines(app: &App) -> Vec<Spans<'static>> {
+    if app.jobs.jobs.is_empty() {
+        let missing = [
+            app.unavailable(Action::DownloadAll).map(|reason| format!("Downloads {}", reason)),
+            app.capabilities.check(capabilities::prediction(false)).err().map(|reason| format!("Predictions {}", reason)),
+        ];
+        let mut lines = vec![Spans::from(format!(
+            "No jobs yet (Enter, {} or a download queues one)",
+            app.keymap.label(Action::FillGaps)
+        ))];
+        lines.extend(missing.into_iter().flatten().map(|line| Spans::from(Span::styled(line, Style::default().fg(app.theme.error)))));
+        return lines;
+    }
+    let focused = app.focus == Focus::Jobs;
+    let mut lines = Vec::new();
+    for (i, job) in app.jobs.jobs.iter().rev().enumerate() {
+        let selected = focused && i == app.jobs_selected;
+        let elapsed = job.elapsed().map_or(String::new(), |d| format!(" {:.1}s", d.as_secs_f64()));
+        let marker = if selected { "> " } else { "  " };
+        let mut status_style = Style::default().fg(job_color(&app.theme, job.status));
+        if selected {
+            status_style = status_style.add_modifier(Modifier::BOLD);
+        }
+        let mut spans = vec![
+            Span::raw(format!("{}{} ", marker, job.label)),
+            Span::styled(format!("{}{}", job.status.label(), elapsed), status_style),
+        ];
+        if job.status == JobStatus::Running && !selected && !job.message.is_empty() {
+            spans.push(Span::styled(format!(" {}", job.message), Style::default().fg(app.theme.muted)));
+        }
+        lines.push(Spans::from(spans));
+        if selected && !job.message.is_empty() {
+            lines.push(Spans::from(Span::styled(format!("    {}", job.message), Style::default().fg(Color::Gray))));
+            for line in &job.stderr_tail {
+                lines.push(Spans::from(Span::styled(format!("    | {}", line), Style::default().fg(app.theme.muted))));
+            }
+        }
+    }
+    lines
+}
+
+/// Width in cells of the advancers/decliners bar.
+const BREADTH_BAR_WIDTH: usize = 12;
+
+/// Title spans summarising the watchlist's tone: counts of tickers up, down
+/// and unchanged on the day, plus a proportional bar. Tickers without data
+/// are left out.
+/// Everything the ML list is drawn from, for `FrameCache::panel`.
+fn ml_list_key(app: &App, visible: &[usize]) -> u64 {
+    let mut key = PanelKey::default();
+    key.add(app.focus == Focus::MLList)
+        .add(std::mem::discriminant(&app.ml_mode))
+        .add(&app.filter_input)
+        .add(app.filter_selected)
+        .add(app.selected)
+        .add(app.sort_key)
+        .add(app.sort_desc)
+        .add(&app.marked)
+        .add(*app.load_status(Source::Stocks) == Status::Loading)
+        .add(universe::is_merged())
+        .add(visible)
+        .add(app.benchmark.return_3m.map(f64::to_bits));
+    for s in &app.stocks {
+        key.add(&s.ticker).num(s.price).num(s.change).num(s.pct_change).add(s.return_3m.map(f64::to_bits));
+        key.add(&s.gaps).add(&s.error).add(&s.source);
+        key.add(app.symbols.name(&s.ticker)).add(app.signals.get(&s.ticker)).add(app.moves.is_significant(&s.ticker, s.pct_change));
+    }
+    key.finish()
+}
+
+fn breadth_spans(theme: &Theme, stocks: &[StockInfo]) -> Vec<Span<'static>> {
+    let with_data = stocks.iter().filter(|s| s.price != 0.0);
+    let (mut up, mut down, mut flat) = (0usize, 0usize, 0usize);
+    for s in with_data {
+        if s.change > 0.0 {
+            up += 1;
+        } else if s.change < 0.0 {
+            down += 1;
+        } else {
+            flat += 1;
+        }
+    }
+    let total = up + down + flat;
+    let mut spans = vec![
+        Span::styled(format!("▲{} ", up), Style::default().fg(theme.gain)),
+        Span::styled(format!("▼{} ", down), Style::default().fg(theme.loss)),
+        Span::styled(format!("={} ", flat), Style::default().fg(Color::Gray)),
+    ];
+    if let Some(up_cells) = (up * BREADTH_BAR_WIDTH).checked_div(total)
+        && let Some(down_cells) = (down * BREADTH_BAR_WIDTH).checked_div(total)
+    {
+        let flat_cells = BREADTH_BAR_WIDTH - up_cells - down_cells;
+        spans.push(Span::styled("█".repeat(up_cells), Style::default().fg(theme.gain)));
+        spans.push(Span::styled("█".repeat(flat_cells), Style::default().fg(theme.muted)));
+        spans.push(Span::styled("█".repeat(down_cells), Style::default().fg(theme.loss)));
+    }
+    spans
+}
+
+/// Where each dashboard panel sits for a given terminal size. Shared by
+/// `draw` and mouse hit-testing so the two can't disagree.
+pub struct Panels {
+    pub benchmark: Rect,
+    pub status: Rect,
+    pub chart: Rect,
+    pub live_trades: Rect,
+    pub performance: Rect,
+    pub risk: Rect,
+    pub accounts: Rect,
+    pub allocation: Rect,
+    pub equity: Rect,
+    pub jobs: Rect,
+    pub ml_list: Rect,
+    pub news: Rect,
+    pub calendar: Rect,
+    pub search: Rect,
+}
+
+impl Panels {
+    /// Row heights come from `layout.rows`. Hidden panels get an empty
+    /// rect and the rest of their row takes up the space; a row with
+    /// nothing left in it gives its height to the other rows.
+    pub fn new(size: Rect, layout: &LayoutConfig) -> Self {
+        let shown = |panel| !layout.is_hidden(panel);
+        // Header strip above the panels, always visible
+        let outer_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+            .split(size);
+        let header_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(70), Constraint::Percentage(30)].as_ref())
+            .split(outer_chunks[0]);
+        let right_column = shown(Panel::LiveTrades) || shown(Panel::Performance) || shown(Panel::Risk);
+        let top_row = shown(Panel::Chart) || right_column;
+        let middle_row =
+            shown(Panel::Accounts) || shown(Panel::Allocation) || shown(Panel::Equity) || shown(Panel::Jobs);
+        let bottom_row =
+            shown(Panel::MlList) || shown(Panel::News) || shown(Panel::Calendar) || shown(Panel::Search);
+        // Main vertical layout: chart row, table row, ML list row
+        let [rows_top, rows_middle, rows_bottom] = layout.rows;
+        let vertical_chunks = weighted_split(
+            outer_chunks[1],
+            Direction::Vertical,
+            &[(rows_top, top_row), (rows_middle, middle_row), (rows_bottom, bottom_row)],
+        );
+        // Top: Stock Chart on the left; Live Trades, Model Performance and Risk on the right
+        let top_chunks =
+            weighted_split(vertical_chunks[0], Direction::Horizontal, &[(70, shown(Panel::Chart)), (30, right_column)]);
+        let right_chunks = weighted_split(
+            top_chunks[1],
+            Direction::Vertical,
+            &[(40, shown(Panel::LiveTrades)), (30, shown(Panel::Performance)), (30, shown(Panel::Risk))],
+        );
+        // Middle: Accounts, Allocation, Equity, Jobs
+        let middle_chunks = weighted_split(
+            vertical_chunks[1],
+            Direction::Horizontal,
+            &[
+                (40, shown(Panel::Accounts)),
+                (18, shown(Panel::Allocation)),
+                (20, shown(Panel::Equity)),
+                (22, shown(Panel::Jobs)),
+            ],
+        );
+        // Bottom: ML List, News, Calendar and Search Box
+        let bottom_chunks = weighted_split(
+            vertical_chunks[2],
+            Direction::Horizontal,
+            &[
+                (42, shown(Panel::MlList)),
+                (22, shown(Panel::News)),
+                (20, shown(Panel::Calendar)),
+                (16, shown(Panel::Search)),
+            ],
+        );
+        Self {
+            benchmark: header_chunks[0],
+            status: header_chunks[1],
+            chart: top_chunks[0],
+            live_trades: right_chunks[0],
+            performance: right_chunks[1],
+            risk: right_chunks[2],
+            accounts: middle_chunks[0],
+            allocation: middle_chunks[1],
+            equity: middle_chunks[2],
+            jobs: middle_chunks[3],
+            ml_list: bottom_chunks[0],
+            news: bottom_chunks[1],
+            calendar: bottom_chunks[2],
+            search: bottom_chunks[3],
+        }
+    }
+}
+
+/// Splits `area` between the shown parts in proportion to their weights.
+/// Parts that aren't shown get an empty rect.
+fn weighted_split(area: Rect, direction: Direction, parts: &[(u16, bool)]) -> Vec<Rect> {
+    let total: u32 = parts.iter().filter(|(_, shown)| *shown).map(|&(w, _)| u32::from(w.max(1))).sum();
+    // Lengths are worked out here rather than with ratio constraints, which
+    // can leave a stray gap between parts; the last part takes the rounding.
+    let length = if direction == Direction::Horizontal { area.width } else { area.height };
+    let mut left = length;
+    let mut constraints: Vec<Constraint> = parts
+        .iter()
+        .filter(|(_, shown)| *shown)
+        .map(|&(w, _)| {
+            let part = (u32::from(length) * u32::from(w.max(1)) / total) as u16;
+            left -= part;
+            Constraint::Length(part)
+        })
+        .collect();
+    if let Some(Constraint::Length(last)) = constraints.last_mut() {
+        *last += left;
+    }
+    let mut chunks = Layout::default().direction(direction).constraints(constraints).split(area).into_iter();
+    parts
+        .iter()
+        .map(|(_, shown)| if *shown { chunks.next().unwrap_or_default() } else { Rect::default() })
+        .collect()
+}
+
+fn contains(area: Rect, column: u16, row: u16) -> bool {
+    column >= area.x && column < area.x + area.width && row >= area.y && row < area.y + area.height
+}
+
+/// First list entry drawn by a table with `rows` lines of body space. Fresh
+/// `TableState`s start at the top, and the table scrolls just far enough to
+/// keep the selection on screen.
+fn table_offset(selected: Option<usize>, rows: usize) -> usize {
+    match selected {
+        Some(s) if rows > 0 && s >= rows => s + 1 - rows,
+        _ => 0,
+    }
+}
+
+/// Job under body line `line` of the Jobs panel. The selected job takes
+/// extra lines for its message and stderr when the panel has focus.
+fn job_at(app: &App, line: usize) -> Option<usize> {
+    let mut top = 0;
+    for (i, job) in app.jobs.jobs.iter().rev().enumerate() {
+        let mut height = 1;
+        if app.focus == Focus::Jobs && i == app.jobs_selected && !job.message.is_empty() {
+            height += 1 + job.stderr_tail.len();
+        }
+        if line < top + height {
+            return Some(i);
+        }
+        top += height;
+    }
+    None
+}
+
+/// Short trade time for Live Trades: month, day and time this year, the
+/// full date for older trades.
+fn trade_time(at: NaiveDateTime, this_year: i32) -> String {
+    let format = if at.year() == this_year { "%m-%d %H:%M" } else { "%Y-%m-%d" };
+    at.format(format).to_string()
+}
+
+/// Width of the company name column in the ML list.
+const NAME_WIDTH: usize = 16;
+
+/// Width of the ML list's data root column, shown with `[[data.roots]]`.
+const SOURCE_WIDTH: usize = 9;
+
+/// `text` cut to `width` characters, ending in `…` when it's longer.
+fn clip(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut clipped: String = text.chars().take(width.saturating_sub(1)).collect();
+    clipped.push('…');
+    clipped
+}
+
+/// The screen below the menu bar, when it shows.
+fn dashboard_area(app: &App, size: Rect) -> Rect {
+    match app.layout.menu_bar || app.menu.is_some() {
+        true => Rect { y: size.y + 1, height: size.height.saturating_sub(1), ..size },
+        false => size,
+    }
+}
+
+/// Trade rows that fit in the Live Trades panel, at least one.
+pub fn trades_page_rows(app: &App, size: Rect) -> usize {
+    let area = Panels::new(dashboard_area(app, size), &app.layout).live_trades;
+    (area.height.saturating_sub(2) as usize).max(1)
+}
+
+/// Where the market overview's cells go when drawn over `size`: inside
+/// its border, above the line describing the selected one.
+pub fn overview_area(size: Rect) -> Rect {
+    let inner = Rect::new(size.x + 1, size.y + 1, size.width.saturating_sub(2), size.height.saturating_sub(2));
+    Rect { height: inner.height.saturating_sub(1), ..inner }
+}
+
+/// Background of a market overview cell: green for gains and red for
+/// losses, brighter up to a move of `full_pct`.
+fn heat_color(pct: f64, full_pct: f64) -> Color {
+    let strength = if full_pct > 0.0 { (pct.abs() / full_pct).min(1.0) } else { 1.0 };
+    let level = (40.0 + 160.0 * strength) as u8;
+    match pct {
+        p if p > 0.0 => Color::Rgb(0, level, 0),
+        p if p < 0.0 => Color::Rgb(level, 0, 0),
+        _ => Color::Rgb(60, 60, 60),
+    }
+}
+
+/// The ML list's tickers as the cells `OverviewView::fit` laid out, each
+/// showing its ticker, % change and, where there's room, price.
+fn draw_overview<B: Backend>(f: &mut Frame<B>, app: &App, view: &OverviewView, size: Rect) {
+    let theme = &app.theme;
+    let sizing = match view.size {
+        TileSize::Equal => "sized equally",
+        TileSize::MarketCap => "sized by market cap",
+    };
+    let title = format!(
+        "Market Overview: {} tickers {} ({}/Esc: close)",
+        view.tiles.len(),
+        sizing,
+        app.keymap.label(Action::MarketOverview)
+    );
+    f.render_widget(panel_block(theme, title, true), size);
+    let map = overview_area(size);
+    for (i, tile) in view.tiles.iter().enumerate() {
+        let (x0, y0) = (tile.area.x.round() as u16, tile.area.y.round() as u16);
+        let (x1, y1) = ((tile.area.x + tile.area.w).round() as u16, (tile.area.y + tile.area.h).round() as u16);
+        let cell = Rect::new(map.x + x0, map.y + y0, x1.saturating_sub(x0), y1.saturating_sub(y0)).intersection(map);
+        if cell.width == 0 || cell.height == 0 {
+            continue;
+        }
+        let mut style = Style::default().fg(Color::White).bg(heat_color(tile.pct_change, app.overview_config.full_pct));
+        if i == view.selected {
+            style = style.add_modifier(Modifier::REVERSED | Modifier::BOLD);
+        }
+        let mut lines = vec![Spans::from(tile.ticker.to_string()), Spans::from(format!("{:+.2}%", tile.pct_change))];
+        if cell.height >= 3 {
+            lines.push(Spans::from(markets::price(&tile.ticker, tile.price)));
+        }
+        f.render_widget(Paragraph::new(lines).style(style), cell);
+    }
+    let footer = match view.selected() {
+        Some(tile) => {
+            let mut parts = vec![tile.ticker.to_string()];
+            parts.extend(app.symbols.name(&tile.ticker).map(str::to_string));
+            parts.push(markets::price(&tile.ticker, tile.price));
+            parts.push(format!("{:+.2}%", tile.pct_change));
+            parts.extend(tile.market_cap.map(|cap| format!("cap {}", compact_number(cap))));
+            format!("{}   Arrows: pick  Enter: chart it", parts.join("  "))
+        }
+        None => String::new(),
+    };
+    let footer_area = Rect { y: map.y + map.height, height: 1.min(size.height.saturating_sub(2)), ..map };
+    f.render_widget(Paragraph::new(footer).style(Style::default().fg(theme.muted)), footer_area);
+}
+
+/// Turns a click or wheel movement into an event for the panel under the
+/// pointer, resolving which list entry was clicked from the layout `draw`
+/// would produce for `size`.
+pub fn mouse_event(app: &App, size: Rect, mouse: MouseEvent) -> Option<AppEvent> {
+    let panels = Panels::new(dashboard_area(app, size), &app.layout);
+    let (column, row) = (mouse.column, mouse.row);
+    let (panel, area) = [
+        (Focus::Chart, panels.chart),
+        (Focus::LiveTrades, panels.live_trades),
+        (Focus::Accounts, panels.accounts),
+        (Focus::Jobs, panels.jobs),
+        (Focus::MLList, panels.ml_list),
+        (Focus::News, panels.news),
+        (Focus::Calendar, panels.calendar),
+    ]
+    .into_iter()
+    .find(|&(_, area)| contains(area, column, row))?;
+    match mouse.kind {
+        MouseEventKind::ScrollDown => return Some(AppEvent::Scroll { panel, delta: 1 }),
+        MouseEventKind::ScrollUp => return Some(AppEvent::Scroll { panel, delta: -1 }),
+        MouseEventKind::Down(MouseButton::Left) => {}
+        _ => return None,
+    }
+    // Body line under the pointer, below the border; None on the border.
+    let inner = Block::default().borders(Borders::ALL).inner(area);
+    let line = contains(inner, column, row).then(|| (row - inner.y) as usize);
+    let item = line.and_then(|line| match panel {
+        Focus::MLList => {
+            // One header line.
+            let visible = app.visible_stocks();
+            let selected = match app.ml_mode {
+                MLMode::Filter if !visible.is_empty() => Some(app.filter_selected.min(visible.len() - 1)),
+                MLMode::Filter => None,
+                _ => (!app.stocks.is_empty()).then_some(app.selected),
+            };
+            let rows = (inner.height as usize).saturating_sub(1);
+            let item = table_offset(selected, rows) + line.checked_sub(1)?;
+            (item < visible.len()).then_some(item)
+        }
+        Focus::Accounts => {
+            // Header plus its bottom margin.
+            let selected = (app.focus == Focus::Accounts).then_some(app.accounts_selected);
+            let rows = (inner.height as usize).saturating_sub(2);
+            let item = table_offset(selected, rows) + line.checked_sub(2)?;
+            (item < app.visible_accounts().len()).then_some(item)
+        }
+        Focus::LiveTrades => {
+            let item = app.trades_scroll + line;
+            (item < app.trades.len()).then_some(item)
+        }
+        Focus::Jobs => job_at(app, line),
+        Focus::News => {
+            let selected = (app.focus == Focus::News).then_some(app.news_selected);
+            let item = table_offset(selected, inner.height as usize) + line;
+            (item < app.headlines().len()).then_some(item)
+        }
+        Focus::Calendar => {
+            let selected = (app.focus == Focus::Calendar).then_some(app.calendar_selected);
+            let item = table_offset(selected, inner.height as usize) + line;
+            (item < app.upcoming_events().len()).then_some(item)
+        }
+        Focus::Chart => None,
+    });
+    Some(AppEvent::Click { panel, item })
+}
+
+/// Draws the dashboard, with `frames` holding the last frame's panels to
+/// reuse where nothing they show changed.
+pub fn draw<B: Backend>(f: &mut Frame<B>, app: &App, frames: &mut FrameCache) {
+    let size = f.size();
+
+    if let Some(report) = &app.health {
+        draw_health(f, app, report, size);
+        return;
+    }
+
+    if app.show_instructions {
+        let block = panel_block(&app.theme, "Instructions", false);
+        let paragraph = Paragraph::new(instruction_lines(app)).block(block);
+        f.render_widget(paragraph, size);
+        return;
+    }
+
+    if app.show_errors {
+        draw_errors(f, app, size);
+        return;
+    }
+    if app.show_usage {
+        draw_usage(f, app, size);
+        return;
+    }
+    if let Some(view) = &app.returns_view {
+        draw_returns(f, app, view, size);
+        return;
+    }
+    if let Some(stats) = &app.analytics {
+        draw_analytics(f, app, stats, size);
+        return;
+    }
+    if let Some(view) = &app.dividends {
+        draw_dividends(f, app, view, size);
+        return;
+    }
+    if let Some(view) = &app.overview {
+        draw_overview(f, app, view, size);
+        return;
+    }
+    if let Some(report) = &app.retention_report {
+        draw_retention_report(f, app, report, size);
+        return;
+    }
+    if let Some(view) = &app.game_view {
+        draw_game(f, app, view, size);
+        return;
+    }
+    if let Some(view) = &app.broker_view {
+        draw_broker(f, app, view, size);
+        return;
+    }
+    if let Some(view) = &app.models_view {
+        draw_models(f, app, view, size);
+        return;
+    }
+    if let Some(view) = &app.time_travel {
+        draw_time_travel(f, app, view, size);
+        return;
+    }
+    if let Some(preview) = &app.alert_preview {
+        draw_alert_preview(f, app, preview, size);
+        if let Some((_, picker)) = &app.range_picker {
+            draw_range_picker(f, &app.theme, picker, size);
+        }
+        return;
+    }
+    let theme = &app.theme;
+    let panels = Panels::new(dashboard_area(app, size), &app.layout);
+    if app.layout.menu_bar || app.menu.is_some() {
+        draw_menu_bar(f, app, Rect { height: 1.min(size.height), ..size });
+    }
+    // In the margin below the panels.
+    if app.layout.hints_bar && size.height > 1 {
+        draw_hints_bar(f, app, Rect { y: size.bottom() - 1, height: 1, ..size });
+    }
+
+    // Header: benchmark sparkline with its daily change
+    let (bench_title, bench_color) = match app.benchmark.daily_change() {
+        Some((last, change, pct)) => (
+            format!("{}  {:.2}  {:+.2} ({:+.2}%)", app.benchmark.ticker, last, change, pct),
+            theme.change(change),
+        ),
+        None => (
+            format!("{}  no data ({} to download)", app.benchmark.ticker, app.keymap.label(Action::Search)),
+            theme.neutral,
+        ),
+    };
+    let bench_width = panels.benchmark.width.saturating_sub(2) as usize;
+    let bench_data = app.benchmark.sparkline_data(bench_width);
+    if let Some(block) = source_block(f, app, Source::Benchmark, bench_title, false, panels.benchmark) {
+        let bench = Sparkline::default().block(block).style(Style::default().fg(bench_color)).data(&bench_data);
+        f.render_widget(bench, panels.benchmark);
+    }
+
+    // Header right: live quote status
+    let (live_text, live_color) = match &app.live {
+        Some(live) if live.is_stale() => (live.status(), theme.accent),
+        Some(live) => (live.status(), theme.gain),
+        None => match &app.live_unavailable {
+            Some(reason) => (format!("Live quotes {}", reason), theme.error),
+            None => ("Live quotes off (stm.toml [live])".to_string(), theme.muted),
+        },
+    };
+    let mut status_spans = vec![Span::styled(live_text, Style::default().fg(live_color))];
+    if !app.errors.entries.is_empty() {
+        let style = if app.errors.unseen > 0 {
+            Style::default().fg(Color::White).bg(theme.error)
+        } else {
+            Style::default().fg(theme.error)
+        };
+        status_spans.insert(0, Span::styled(format!("{} errors ({}) ", app.errors.entries.len(), app.keymap.label(Action::ShowErrors)), style));
+    }
+    if app.source_unavailable.is_some() {
+        status_spans.insert(0, Span::styled(
+            "Data source unavailable ",
+            Style::default().fg(Color::White).bg(theme.error),
+        ));
+    }
+    if app.autotrade.enabled {
+        let (text, style) = if app.armed {
+            (format!("AUTO ARMED: {} ", app.strategy_name()), Style::default().fg(Color::White).bg(theme.loss).add_modifier(Modifier::BOLD))
+        } else {
+            (format!("AUTO DISARMED ({}) ", app.keymap.label(Action::AutoTrade)), Style::default().fg(theme.muted))
+        };
+        status_spans.insert(0, Span::styled(text, style));
+    }
+    if let Some(chaos) = chaos::settings() {
+        status_spans.insert(0, Span::styled(
+            format!("CHAOS {}ms/{}% ", chaos.delay.as_millis(), chaos.fail_pct),
+            Style::default().fg(Color::Black).bg(Color::Magenta),
+        ));
+    }
+    if !app.layout.hidden.is_empty() {
+        status_spans.push(Span::styled(
+            format!(" | {} hidden ({})", app.layout.hidden.len(), app.keymap.label(Action::RestorePanels)),
+            Style::default().fg(theme.muted),
+        ));
+    }
+    if let Some(stream) = app.stream_status() {
+        status_spans.push(Span::raw(" | "));
+        status_spans.push(Span::raw(stream));
+    }
+    let live_status = Paragraph::new(Spans::from(status_spans))
+        .block(panel_block(theme, "Status", false));
+    f.render_widget(live_status, panels.status);
+
+
+
+    // Top Left: Stock Chart of the selected ticker, with its order book to
+    // the right when toggled on and there's room.
+    let mut chart_area = panels.chart;
+    if app.show_order_book && chart_area.width >= 2 * ORDER_BOOK_WIDTH {
+        let split = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(0), Constraint::Length(ORDER_BOOK_WIDTH)].as_ref())
+            .split(chart_area);
+        chart_area = split[0];
+        draw_order_book(f, app, split[1]);
+    }
+    if chart_area.area() > 0 && !app.compare.is_empty() {
+        draw_compare(f, app, chart_area);
+    } else if chart_area.area() > 0 {
+        draw_chart(f, app, chart_area);
+    }
+
+    // Top Right: Live Trades from trading_history.csv. Only the rows on
+    // screen are formatted; histories can be long.
+    let rows = trades_page_rows(app, size);
+    let this_year = chrono::Local::now().year();
+    let focused = app.focus == Focus::LiveTrades;
+    let trades_title = if app.trades.len() > rows {
+        let last = (app.trades_scroll + rows).min(app.trades.len());
+        format!("Live Trades {}-{} of {}", app.trades_scroll + 1, last, app.trades.len())
+    } else {
+        "Live Trades".to_string()
+    };
+    let mut trades_key = PanelKey::default();
+    trades_key
+        .add(focused)
+        .add(*app.load_status(Source::Trades) == Status::Loading)
+        .add((app.trades.len(), app.trades_scroll, rows, app.trades_selected, this_year));
+    for (i, t) in app.trades.iter().enumerate().skip(app.trades_scroll).take(rows) {
+        trades_key.add(&t.name).num(t.transaction).num(t.new_balance).add(t.timestamp).add(&t.ticker).add(&t.note);
+        trades_key.add(trades::transfer_leg(&app.trades, i).map(|other| &other.name));
+    }
+    if let Some(block) = source_block(f, app, Source::Trades, trades_title, focused, panels.live_trades) {
+        let area = panels.live_trades;
+        frames.panel(f, "live_trades", Some(trades_key.finish()), area, |buf| {
+            let live_trades_text: Vec<Spans> = app.trades.iter().enumerate().skip(app.trades_scroll).take(rows).map(|(i, t)| {
+                let mut line = format!("{}  {:.2}  {:.2}", t.name, t.transaction, t.new_balance);
+                if let Some(at) = t.timestamp {
+                    line = format!("{}  {}", trade_time(at, this_year), line);
+                }
+                if let Some(other) = trades::transfer_leg(&app.trades, i) {
+                    line.push_str(&format!("  {} {}", if t.transaction < 0.0 { "to" } else { "from" }, other.name));
+                }
+                for extra in t.ticker.as_ref().map(|t| t.as_str()).into_iter().chain(t.note.as_deref()) {
+                    line.push_str("  ");
+                    line.push_str(extra);
+                }
+                if app.trades_selected == Some(i) {
+                    Spans::from(Span::styled(line, theme.selected()))
+                } else {
+                    Spans::from(line)
+                }
+            }).collect();
+            Paragraph::new(live_trades_text).block(block).render(area, buf);
+        });
+    }
+
+    // Top Right, below: rolling accuracy of logged model predictions
+    let perf_rows: Vec<Row> = history::accuracy(&app.ml_history)
+        .into_iter()
+        .map(|acc| {
+            let (mae, hits) = if acc.window > 0 {
+                (format!("{:.2}", acc.mae), format!("{:.0}%", acc.hit_rate * 100.0))
+            } else {
+                ("-".to_string(), "-".to_string())
+            };
+            Row::new(vec![
+                acc.ticker.to_string(),
+                format!("{}/{}", acc.window, acc.total),
+                mae,
+                hits,
+                acc.pending.map_or(String::new(), |p| format!("{:.2}", p)),
+            ])
+        })
+        .collect();
+    let perf_title = format!("Model Performance (last {})", history::ROLLING_WINDOW);
+    let perf_table = if perf_rows.is_empty() {
+        let empty = if *app.load_status(Source::History) == Status::Loading { "" } else { "No predictions logged yet" };
+        Table::new(vec![Row::new(vec![empty])]).widths(&[Constraint::Percentage(100)])
+    } else {
+        Table::new(perf_rows)
+            .header(Row::new(vec!["Ticker", "N", "MAE", "Dir", "Next"]).style(Style::default().add_modifier(Modifier::BOLD)))
+            .widths(&[
+                Constraint::Length(7),
+                Constraint::Length(5),
+                Constraint::Length(7),
+                Constraint::Length(4),
+                Constraint::Length(7),
+            ])
+    };
+    if let Some(block) = source_block(f, app, Source::History, perf_title, false, panels.performance) {
+        f.render_widget(perf_table.block(block), panels.performance);
+    }
+
+    if panels.risk.area() > 0 {
+        draw_risk(f, app, panels.risk);
+    }
+
+    // Middle: Account Summary Table, with each account's allocation beside it
+    let visible_accounts = app.visible_accounts();
+    let mut rows: Vec<Row> = visible_accounts.iter().map(|&i| &app.accounts[i]).map(|acc| {
+        let row = Row::new(vec![
+            if acc.archived { format!("{} (closed)", acc.name) } else { acc.name.to_string() },
+            format!("{:.2}", acc.initial_amount),
+            format!("{:.2}", acc.current_amount),
+            format!("{:.2}", acc.change),
+            format!("{:.2}%", acc.percentage_change),
+            acc.currency.clone(),
+        ]);
+        if acc.archived { row.style(Style::default().fg(theme.muted)) } else { row }
+    }).collect();
+    // Open accounts only, converted to the base currency.
+    let totals = fx::totals(app.accounts.iter().filter(|a| !a.archived), &app.fx);
+    if !visible_accounts.is_empty() {
+        rows.push(
+            Row::new(vec![
+                format!("Total {}", app.fx.base()),
+                format!("{:.2}", totals.initial),
+                format!("{:.2}", totals.current),
+                format!("{:.2}", totals.change),
+                format!("{:.2}%", totals.percentage_change),
+                app.fx.base().to_string(),
+            ])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+        );
+    }
+    let archived_count = app.accounts.iter().filter(|a| a.archived).count();
+    let mut accounts_title = match (archived_count, app.show_archived) {
+        (0, _) => "Account Summary".to_string(),
+        (n, true) => format!("Account Summary (incl. {} closed, {} to hide)", n, app.keymap.label(Action::ToggleArchived)),
+        (n, false) => format!("Account Summary ({} closed hidden, {} to show)", n, app.keymap.label(Action::ToggleArchived)),
+    };
+    if app.rank_accounts {
+        accounts_title.push_str(" - ranked by % change");
+    }
+    if !totals.missing.is_empty() {
+        accounts_title.push_str(&format!(" - no {} rate for {}", app.fx.base(), totals.missing.join(", ")));
+    }
+    let focused = app.focus == Focus::Accounts;
+    if let Some(block) = source_block(f, app, Source::Accounts, accounts_title, focused, panels.accounts) {
+        let table = Table::new(rows)
+            .header(
+                Row::new(vec!["Name", "Initial", "Current", "Change", "% Change", "Ccy"])
+                    .bottom_margin(1),
+            )
+            .block(block)
+            .highlight_style(theme.selected())
+            .widths(&[
+                Constraint::Length(18),
+                Constraint::Length(10),
+                Constraint::Length(10),
+                Constraint::Length(10),
+                Constraint::Length(10),
+                Constraint::Length(4),
+            ]);
+        let mut table_state = TableState::default();
+        if focused && !visible_accounts.is_empty() {
+            table_state.select(Some(app.accounts_selected));
+        }
+        f.render_stateful_widget(table, panels.accounts, &mut table_state);
+    }
+
+    let allocation_area = panels.allocation;
+    if let Some(block) = source_block(f, app, Source::Accounts, "Allocation", false, allocation_area) {
+        let allocation = Paragraph::new(allocation_lines(&app.accounts, &app.fx, allocation_area.width.saturating_sub(2)));
+        f.render_widget(allocation.block(block), allocation_area);
+    }
+
+    if panels.equity.area() > 0 {
+        draw_equity(f, app, panels.equity);
+    }
+
+    // Middle Right: background jobs, newest first
+    let jobs_title = match app.jobs.active() {
+        0 => "Jobs".to_string(),
+        n => format!("Jobs ({} active)", n),
+    };
+    f.render_widget(
+        Paragraph::new(job_lines(app)).block(panel_block(theme, jobs_title, app.focus == Focus::Jobs)),
+        panels.jobs,
+    );
+
+
+    // Bottom Left: ML List of available stocks from pre_stock/
+    let now = Instant::now();
+    let visible = app.visible_stocks();
+    // Each ticker's root, when there's more than one.
+    let merged = universe::is_merged();
+    let header_cell = |key: SortKey, label: &str, width: usize| {
+        let arrow = if app.sort_key == key {
+            if app.sort_desc { "▼" } else { "▲" }
+        } else {
+            ""
+        };
+        if key == SortKey::Ticker {
+            format!("{}{}", label, arrow)
+        } else {
+            format!("{:>width$}", format!("{}{}", arrow, label))
+        }
+    };
+    let mut ml_title = vec![if let MLMode::Filter = app.ml_mode {
+        Span::raw(format!("ML List (filter: {}, {} match) ", app.filter_input, visible.len()))
+    } else {
+        Span::raw("ML List ")
+    }];
+    ml_title.extend(breadth_spans(&app.theme, &app.stocks));
+    if let Err(reason) = app.capabilities.check(capabilities::prediction(false)) {
+        ml_title.push(Span::styled(format!(" Predictions {} ", reason), Style::default().fg(theme.error)));
+    }
+    let focused = app.focus == Focus::MLList;
+    // Rows are only built again when something they show changed; a
+    // flashing row changes with every frame.
+    let flashing = visible.iter().any(|&i| app.flashes.get(&app.stocks[i].ticker).is_some_and(|f| f.color(now).is_some()));
+    let ml_key = (!flashing).then(|| ml_list_key(app, &visible));
+    if let Some(ml_block) = source_block(f, app, Source::Stocks, ml_title, focused, panels.ml_list) {
+        let area = panels.ml_list;
+        frames.panel(f, "ml_list", ml_key, area, |buf| {
+            let ml_rows: Vec<Row> = visible.iter().map(|&i| &app.stocks[i]).map(|s| {
+                let change_style = Style::default().fg(theme.change(s.change));
+                let history = match &s.error {
+                    Some(err) => Cell::from(format!("error: {}", err)).style(Style::default().fg(theme.error)),
+                    None if s.gaps.is_empty() => Cell::from(""),
+                    None => Cell::from(format!("{} missing", calendar::session_count(&s.gaps, Sessions::for_ticker(&s.ticker)))),
+                };
+                let mut cells = vec![
+                    match app.marked.iter().position(|t| *t == s.ticker) {
+                        Some(i) => Cell::from(Spans::from(vec![
+                            Span::styled("■ ", Style::default().fg(compare_color(theme, i))),
+                            Span::raw(s.ticker.as_str()),
+                        ])),
+                        None => Cell::from(s.ticker.as_str()),
+                    },
+                    Cell::from(clip(app.symbols.name(&s.ticker).unwrap_or_default(), NAME_WIDTH)).style(Style::default().fg(theme.muted)),
+                    Cell::from(format!("{:>10}", markets::price(&s.ticker, s.price))),
+                    Cell::from(format!("{:>+10.2}", s.change)).style(change_style),
+                    Cell::from(format!("{:>+8.2}%", s.pct_change)).style(change_style),
+                    match s.relative_strength(app.benchmark.return_3m) {
+                        Some(rs) => Cell::from(format!("{:>+8.1}%", rs)).style(Style::default().fg(theme.change(rs))),
+                        None => Cell::from(format!("{:>9}", "-")),
+                    },
+                    match app.signals.get(&s.ticker) {
+                        Some(latest) => Cell::from(latest.to_string()).style(Style::default().fg(match latest.signal {
+                            Signal::Buy => theme.gain,
+                            Signal::Sell => theme.loss,
+                            Signal::Hold => theme.muted,
+                        })),
+                        None => Cell::from(""),
+                    },
+                    history,
+                ];
+                if merged {
+                    cells.insert(1, Cell::from(clip(&s.source, SOURCE_WIDTH)).style(Style::default().fg(theme.accent)));
+                }
+                let row = Row::new(cells);
+                let mut style = Style::default();
+                if app.moves.is_significant(&s.ticker, s.pct_change) {
+                    style = style.add_modifier(Modifier::BOLD | Modifier::REVERSED);
+                }
+                if let Some(bg) = app.flashes.get(&s.ticker).and_then(|f| f.color(now)) {
+                    style = style.bg(bg);
+                }
+                row.style(style)
+            }).collect();
+            let mut header = vec![
+                header_cell(SortKey::Ticker, "1 Ticker", 10),
+                "Name".to_string(),
+                header_cell(SortKey::Price, "2 Price", 10),
+                header_cell(SortKey::Change, "3 Change", 10),
+                header_cell(SortKey::PctChange, "4 %Chg", 9),
+                header_cell(SortKey::RelStrength, "5 RS 3M", 9),
+                "Signal".to_string(),
+                "History".to_string(),
+            ];
+            let mut widths = vec![
+                Constraint::Length(10),
+                Constraint::Length(NAME_WIDTH as u16),
+                Constraint::Length(10),
+                Constraint::Length(10),
+                Constraint::Length(9),
+                Constraint::Length(9),
+                Constraint::Length(11),
+                Constraint::Length(36),
+            ];
+            if merged {
+                header.insert(1, "Source".to_string());
+                widths.insert(1, Constraint::Length(SOURCE_WIDTH as u16));
+            }
+            let ml_table = Table::new(ml_rows)
+                .header(Row::new(header).style(Style::default().add_modifier(Modifier::BOLD)))
+                .block(ml_block)
+                .highlight_symbol("> ")
+                .highlight_style(theme.selected())
+                .widths(&widths);
+            let mut ml_state = TableState::default();
+            if let MLMode::Filter = app.ml_mode {
+                if !visible.is_empty() {
+                    ml_state.select(Some(app.filter_selected.min(visible.len() - 1)));
+                }
+            } else if !app.stocks.is_empty() {
+                ml_state.select(Some(app.selected));
+            }
+            StatefulWidget::render(ml_table, area, buf, &mut ml_state);
+        });
+    }
+    if panels.news.area() > 0 {
+        draw_news(f, app, panels.news);
+    }
+    if panels.calendar.area() > 0 {
+        draw_calendar(f, app, panels.calendar);
+    }
+
+    // Bottom Right: Search Box (always visible)
+    let search_text = match app.ml_mode {
+        MLMode::Filter => format!("Filter: {}\n\n{}", app.filter_input, app.ml_output),
+        MLMode::Alert => format!("Alert rule: {}\n\n{}", app.alert_input, app.ml_output),
+        MLMode::JumpToDate => format!("Jump to date: {}\n\n{}", app.jump_input, app.ml_output),
+        _ => format!("Search Ticker: {}\n\n{}", app.search_input, app.ml_output),
+    };
+    let search_text = match &app.suggestion {
+        Some(s) if s.order_line().is_some() => {
+            format!("{}\nSuggested for {}: {} ({}: paper order)", search_text, s.ticker, s, app.keymap.label(Action::PaperOrder))
+        },
+        Some(s) => format!("{}\nSuggested for {}: {}", search_text, s.ticker, s),
+        None => search_text,
+    };
+    let search_box = Paragraph::new(search_text)
+        .block(panel_block(theme, "Search", false));
+    f.render_widget(search_box, panels.search);
+
+    if let Some(form) = &app.account_form {
+        draw_account_form(f, theme, form, size);
+    }
+    if let Some(form) = &app.trade_form {
+        draw_trade_form(f, theme, form, size);
+    }
+    if let Some((_, picker)) = &app.range_picker {
+        draw_range_picker(f, theme, picker, size);
+    }
+    if let Some(release) = app.release.as_ref().filter(|_| app.show_release_notes) {
+        draw_release_notes(f, app, release, size);
+    }
+    if let Some((entries, upgraded)) = &app.changelog {
+        draw_changelog(f, app, entries, *upgraded, size);
+    }
+    if let Some(menu) = &app.menu {
+        draw_menu(f, app, menu, size);
+    }
+}
+
+/// Menu titles, the open one highlighted, then what the highlighted action
+/// does, or the key that opens the menu.
+fn draw_menu_bar<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let mut spans = Vec::new();
+    for (i, (title, _)) in menu::MENUS.iter().enumerate() {
+        let style = match &app.menu {
+            Some(open) if open.menu == i => theme.selected(),
+            _ => Style::default().add_modifier(Modifier::BOLD),
+        };
+        spans.push(Span::styled(format!(" {} ", title), style));
+        spans.push(Span::raw(" "));
+    }
+    let hint = match &app.menu {
+        Some(open) => match app.unavailable(open.selected()) {
+            Some(reason) => format!("  {} ({})", open.selected().description(), reason),
+            None => format!("  {}", open.selected().description()),
+        },
+        None => format!("  {}: menu", app.keymap.label(Action::Menu)),
+    };
+    spans.push(Span::styled(hint, Style::default().fg(theme.muted)));
+    f.render_widget(Paragraph::new(Spans::from(spans)), area);
+}
+
+/// The focused panel's keys, then the ones that work anywhere, as many as
+/// fit the width.
+fn draw_hints_bar<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let key_style = Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD);
+    let mut spans = Vec::new();
+    let mut used = 0;
+    for hint in hints::hints(app.hint_context()) {
+        if let Hint::Action(action, _) = hint
+            && app.unavailable(action).is_some()
+        {
+            continue;
+        }
+        let (key, text) = hint.parts(&app.keymap);
+        let width = key.chars().count() + text.chars().count() + 3;
+        if used + width > area.width as usize {
+            break;
+        }
+        used += width;
+        spans.push(Span::styled(format!(" {}", key), key_style));
+        spans.push(Span::styled(format!(" {} ", text), Style::default().fg(app.theme.muted)));
+    }
+    f.render_widget(Paragraph::new(Spans::from(spans)), area);
+}
+
+/// The open menu's actions under its title, each with its key.
+fn draw_menu<B: Backend>(f: &mut Frame<B>, app: &App, menu: &MenuState, size: Rect) {
+    let theme = &app.theme;
+    let items = menu.items();
+    let left: usize = menu::MENUS[..menu.menu].iter().map(|(title, _)| title.len() + 3).sum();
+    let keys: Vec<String> = items.iter().map(|&a| app.keymap.label(a)).collect();
+    let labels: Vec<String> = items.iter().map(|&a| clip(menu::label(a), 44)).collect();
+    let label_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let key_width = keys.iter().map(|k| k.chars().count()).max().unwrap_or(0);
+    let width = ((label_width + key_width + 5) as u16).min(size.width);
+    let height = (items.len() as u16 + 2).min(size.height.saturating_sub(1));
+    let x = (left as u16).min(size.width.saturating_sub(width));
+    let area = Rect::new(size.x + x, size.y + 1.min(size.height), width, height);
+    let lines: Vec<Spans> = labels
+        .iter()
+        .zip(&keys)
+        .enumerate()
+        .map(|(i, (label, key))| {
+            let text = format!(" {:<lw$} {:>kw$} ", label, key, lw = label_width, kw = key_width);
+            let style = match app.unavailable(items[i]) {
+                _ if i == menu.item => theme.selected(),
+                Some(_) => Style::default().fg(theme.muted),
+                None => Style::default(),
+            };
+            Spans::from(Span::styled(text, style))
+        })
+        .collect();
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(panel_block(theme, menu::MENUS[menu.menu].0, true)), area);
+}
+
+/// Price line of the selected ticker, panned by `chart_offset`, with the
+/// volume pane under it.
+fn draw_chart<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let bars = app.chart_bars();
+    let visible = app.visible_bars().len();
+    let data: Vec<(f64, f64)> = bars[..visible]
+        .iter()
+        .enumerate()
+        .map(|(i, bar)| (i as f64, bar.close))
+        .collect();
+    // Tagged with where it's stored when tickers need their tag, as one
+    // may be held in two places.
+    let tag = app
+        .stocks
+        .get(app.selected)
+        .filter(|s| app.chart.ticker.as_ref() == Some(&s.ticker) && universe::is_merged())
+        .map_or(String::new(), |s| format!(" [{}]", s.source));
+    let named = |ticker: &Ticker| match app.symbols.name(ticker) {
+        Some(name) => format!("{}{} {}", ticker, tag, name),
+        None => format!("{}{}", ticker, tag),
+    };
+    let chart_title = match &app.chart.ticker {
+        Some(ticker) if app.chart_range.is_all() => format!("Stock Chart: {} ({})", named(ticker), app.chart.interval.as_str()),
+        Some(ticker) => format!("Stock Chart: {} ({}, {})", named(ticker), app.chart.interval.as_str(), app.chart_range),
+        None => "Stock Chart".to_string(),
+    };
+    let theme = &app.theme;
+    let overlay = app.chart_overlay(&bars[..visible]);
+    let mut title = vec![Span::raw(chart_title)];
+    match (&overlay, &app.overlay) {
+        (Some(overlay), _) => {
+            let lead = overlay.own - overlay.benchmark;
+            title.push(Span::raw(format!(" {:+.1}% vs {} {:+.1}%, ", overlay.own, overlay.ticker, overlay.benchmark)));
+            title.push(Span::styled(format!("{:+.1} pts", lead), Style::default().fg(theme.change(lead))));
+        }
+        (None, Some(PriceSeries { ticker: Some(ticker), .. })) if data.len() >= 2 => {
+            title.push(Span::raw(format!(" - no {} history", ticker)));
+        }
+        _ => {}
+    }
+    // The crosshair's bar and price, shown while the chart is focused.
+    let crosshair = app
+        .crosshair
+        .as_ref()
+        .filter(|_| app.focus == Focus::Chart && data.len() >= 2)
+        .map(|c| (c.bar.min(visible - 1), c.price, c.anchor));
+    if let (Some((bar, price, anchor)), Some(ticker)) = (crosshair, &app.chart.ticker) {
+        title.push(Span::styled(
+            format!(" ┼ {} {}", bars[bar].at.format("%Y-%m-%d %H:%M"), markets::price(ticker, price)),
+            Style::default().fg(theme.accent),
+        ));
+        if anchor.is_some() {
+            title.push(Span::raw(" (trendline from here)"));
+        }
+    }
+    if app.chart_scale != Scale::Linear {
+        title.push(Span::styled(format!(" [{}]", app.chart_scale.name()), Style::default().fg(theme.muted)));
+    }
+    let Some(chart_block) = source_block(f, app, Source::Chart, title, app.focus == Focus::Chart, area) else {
+        return;
+    };
+    if data.len() < 2 {
+        let message = if app.chart.bars.len() >= 2 {
+            format!("No bars in {} ({} to change the dates)", app.chart_range, app.keymap.label(Action::DateRange))
+        } else {
+            "No price history for the selected ticker".to_string()
+        };
+        let empty = Paragraph::new(message).block(chart_block);
+        f.render_widget(empty, area);
+    } else {
+        let x_max = (data.len() - 1) as f64;
+        let overlay_points = overlay.map(|o| o.points).unwrap_or_default();
+        // Everything drawn up the chart is placed by the axis scale.
+        let (scale, first) = (app.chart_scale, data[0].1);
+        let to_y = |price: f64| scale.apply(price, first);
+        let scaled = |points: &[(f64, f64)]| -> Vec<(f64, f64)> { points.iter().filter_map(|&(x, y)| Some((x, to_y(y)?))).collect() };
+        let (data, overlay_points) = (scaled(&data), scaled(&overlay_points));
+        let cursor = crosshair.and_then(|(bar, price, _)| Some((bar as f64, to_y(price)?)));
+        let (y_min, y_max) = data
+            .iter()
+            .chain(&overlay_points)
+            .chain(&cursor)
+            .fold((f64::MAX, f64::MIN), |(mn, mx), &(_, y)| (mn.min(y), mx.max(y)));
+        let (y_min, y_max) = if y_min <= y_max { (y_min, y_max) } else { (0.0, 1.0) };
+        let pad = ((y_max - y_min) * 0.1).max(0.01);
+        // Bars are spaced evenly by index so overnight and weekend gaps in
+        // intraday data don't stretch the line; labels carry the real time.
+        let label_format = app.chart.interval.axis_format(bars[visible - 1].at - bars[0].at);
+        let labels: Vec<(f64, String)> = [0.0, 0.25, 0.5, 0.75]
+            .iter()
+            .map(|frac| {
+                let i = (x_max * frac).round() as usize;
+                (i as f64, bars[i].at.format(label_format).to_string())
+            })
+            .collect();
+        let line_segments: Vec<Line> = data.windows(2).map(|pair| {
+            let (x1, y1) = pair[0];
+            let (x2, y2) = pair[1];
+            Line { x1, y1, x2, y2, color: theme.chart_line }
+        }).collect();
+        let accent = theme.accent;
+        let overlay_segments: Vec<Line> = overlay_points
+            .windows(2)
+            .map(|pair| Line { x1: pair[0].0, y1: pair[0].1, x2: pair[1].0, y2: pair[1].1, color: accent })
+            .collect();
+        let drawn = drawing_lines(app, &bars[..visible], x_max, crosshair, to_y);
+        let label_color = theme.muted;
+        let inner = chart_block.inner(area);
+        f.render_widget(chart_block, area);
+        let has_volume = app.show_volume && bars[..visible].iter().any(|b| b.volume.is_some());
+        let (price_area, volume_area) = if has_volume && inner.height >= 8 {
+            let split = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Percentage(75), Constraint::Percentage(25)].as_ref())
+                .split(inner);
+            (split[0], Some(split[1]))
+        } else {
+            (inner, None)
+        };
+        let y_bounds = [y_min - pad * 2.0, y_max + pad];
+        let axis = match scale {
+            Scale::Linear => price_axis(app, price_area, [-0.5, x_max + 0.5], y_bounds, false, |t, v| t.label(v)),
+            Scale::Log => price_axis(app, price_area, [-0.5, x_max + 0.5], y_bounds, true, |t, v| t.label(v)),
+            Scale::Percent => price_axis(app, price_area, [-0.5, x_max + 0.5], y_bounds, false, |t, v| format!("{}%", t.label(v))),
+        };
+        let left = axis.left;
+        let columns: Vec<f64> = labels.iter().map(|&(x, _)| x).collect();
+        let grid = if app.chart_config.grid { axis.grid(&columns) } else { Vec::new() };
+        let chart = Canvas::default()
+            .marker(marker(app))
+            .x_bounds([left, x_max + 0.5])
+            .y_bounds([y_min - pad * 2.0, y_max + pad])
+            .paint(move |ctx| {
+                ctx.draw(&Points { coords: &grid, color: label_color });
+                ctx.layer();
+                for seg in &overlay_segments {
+                    ctx.draw(seg);
+                }
+                ctx.layer();
+                for seg in &line_segments {
+                    ctx.draw(seg);
+                }
+                ctx.layer();
+                for line in &drawn {
+                    ctx.draw(line);
+                }
+                for (x, label) in &labels {
+                    ctx.print(*x, y_min - pad * 2.0, Span::styled(label.clone(), Style::default().fg(label_color)));
+                }
+                for (y, label) in &axis.ticks {
+                    ctx.print(left, *y, Span::styled(label.clone(), Style::default().fg(label_color)));
+                }
+            });
+        f.render_widget(chart, price_area);
+
+        // Volume pane, sharing the price chart's x axis. Bars take the gain
+        // colour when the close is at or above the previous one, the loss
+        // colour otherwise.
+        if let Some(volume_area) = volume_area {
+            let shown = &bars[..visible];
+            let vol_max = shown.iter().filter_map(|b| b.volume).fold(0.0, f64::max).max(1.0);
+            let volume_lines: Vec<Line> = shown
+                .iter()
+                .enumerate()
+                .filter_map(|(i, bar)| {
+                    let volume = bar.volume?;
+                    let up = i == 0 || bar.close >= shown[i - 1].close;
+                    let x = i as f64;
+                    Some(Line { x1: x, y1: 0.0, x2: x, y2: volume, color: if up { theme.gain } else { theme.loss } })
+                })
+                .collect();
+            let label_color = theme.muted;
+            let volume = Canvas::default()
+                .marker(marker(app))
+                .x_bounds([left, x_max + 0.5])
+                .y_bounds([0.0, vol_max])
+                .paint(move |ctx| {
+                    for line in &volume_lines {
+                        ctx.draw(line);
+                    }
+                    ctx.print(left, vol_max, Span::styled(format!("Vol {}", compact_number(vol_max)), Style::default().fg(label_color)));
+                });
+            f.render_widget(volume, volume_area);
+        }
+    }
+}
+
+/// The chart ticker's levels and trendlines over `bars`, and the
+/// crosshair's lines through `(bar, price)` with the trendline it's
+/// drawing from its anchor, their prices placed on the axis by `to_y`.
+fn drawing_lines(
+    app: &App,
+    bars: &[Bar],
+    x_max: f64,
+    crosshair: Option<(usize, f64, Option<Point>)>,
+    to_y: impl Fn(f64) -> Option<f64>,
+) -> Vec<Line> {
+    let theme = &app.theme;
+    let mut lines = Vec::new();
+    let trend = |lines: &mut Vec<Line>, drawing: &Drawing, color| {
+        let points: Vec<Option<f64>> = bars.iter().map(|b| drawing.price_at(b.at).and_then(&to_y)).collect();
+        for (i, pair) in points.windows(2).enumerate() {
+            if let [Some(y1), Some(y2)] = *pair {
+                lines.push(Line { x1: i as f64, y1, x2: i as f64 + 1.0, y2, color });
+            }
+        }
+    };
+    if let Some(ticker) = &app.chart.ticker {
+        for drawing in app.drawings.get(ticker) {
+            match *drawing {
+                Drawing::Level { price } => {
+                    if let Some(y) = to_y(price) {
+                        lines.push(Line { x1: -0.5, y1: y, x2: x_max + 0.5, y2: y, color: theme.accent });
+                    }
+                }
+                Drawing::Trend { .. } => trend(&mut lines, drawing, theme.accent),
+            }
+        }
+    }
+    if let Some((bar, price, anchor)) = crosshair {
+        let x = bar as f64;
+        let (lo, hi) = bars.iter().fold((price, price), |(lo, hi), b| (lo.min(b.close), hi.max(b.close)));
+        if let (Some(y), Some(lo), Some(hi)) = (to_y(price), to_y(lo), to_y(hi)) {
+            lines.push(Line { x1: -0.5, y1: y, x2: x_max + 0.5, y2: y, color: theme.neutral });
+            lines.push(Line { x1: x, y1: lo, x2: x, y2: hi, color: theme.neutral });
+        }
+        if let Some(anchor) = anchor {
+            trend(&mut lines, &Drawing::trend(anchor, Point { at: bars[bar].at, price }), theme.neutral);
+        }
+    }
+    lines
+}
+
+/// Headlines of the selected ticker, newest first, with their publish
+/// times; the selected one's publisher is shown after it.
+fn draw_news<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let focused = app.focus == Focus::News;
+    let ticker = app.selected_ticker();
+    let headlines = app.headlines();
+    let title = match ticker {
+        Some(ticker) if !headlines.is_empty() => format!("News: {} ({})", ticker, headlines.len()),
+        Some(ticker) => format!("News: {}", ticker),
+        None => "News".to_string(),
+    };
+    let Some(block) = source_block(f, app, Source::News, title, focused, area) else {
+        return;
+    };
+    let empty = match ticker {
+        _ if !app.news_config.enabled => Some("News off (stm.toml [news])".to_string()),
+        None => Some("Select a stock for its news".to_string()),
+        Some(ticker) if app.news.get(ticker).is_none() => Some(match app.capabilities.check(&[Capability::Network]) {
+            Ok(()) => "Fetching headlines…".to_string(),
+            Err(reason) => format!("Headlines {}", reason),
+        }),
+        Some(ticker) if headlines.is_empty() => Some(format!("No recent headlines for {}", ticker)),
+        Some(_) => None,
+    };
+    if let Some(message) = empty {
+        let text = Paragraph::new(message).style(Style::default().fg(theme.muted)).wrap(Wrap { trim: true });
+        f.render_widget(text.block(block), area);
+        return;
+    }
+    let selected = app.news_selected.min(headlines.len() - 1);
+    let this_year = chrono::Local::now().year();
+    let rows: Vec<Row> = headlines
+        .iter()
+        .enumerate()
+        .map(|(i, h)| {
+            let when = h.published.map_or(String::new(), |at| trade_time(at.naive_local(), this_year));
+            let mut title = h.title.clone();
+            if focused && i == selected && !h.source.is_empty() {
+                title = format!("{} ({})", title, h.source);
+            }
+            Row::new(vec![Cell::from(when).style(Style::default().fg(theme.muted)), Cell::from(title)])
+        })
+        .collect();
+    let table = Table::new(rows)
+        .block(block)
+        .highlight_style(theme.selected())
+        .widths(&[Constraint::Length(11), Constraint::Min(10)]);
+    let mut state = TableState::default();
+    if focused {
+        state.select(Some(selected));
+    }
+    f.render_stateful_widget(table, area, &mut state);
+}
+
+/// Upcoming earnings and ex-dividend dates of the ML list's tickers,
+/// soonest first; those within `[calendar] highlight_days` stand out.
+fn draw_calendar<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let focused = app.focus == Focus::Calendar;
+    let events = app.upcoming_events();
+    let mut title = match events.len() {
+        0 => "Calendar".to_string(),
+        n => format!("Calendar ({})", n),
+    };
+    if !app.calendar_failed.is_empty() {
+        let failed: Vec<&str> = app.calendar_failed.iter().map(|t| t.as_str()).collect();
+        title.push_str(&format!(" - no data for {}", failed.join(", ")));
+    }
+    let Some(block) = source_block(f, app, Source::Calendar, title, focused, area) else {
+        return;
+    };
+    let config = &app.calendar_config;
+    let empty = if !config.enabled {
+        Some("Calendar off (stm.toml [calendar])".to_string())
+    } else if app.calendar.fetched.is_empty() {
+        Some(match app.capabilities.check(&[Capability::Network]) {
+            Ok(()) => "Fetching earnings and dividend dates…".to_string(),
+            Err(reason) => format!("Calendar {}", reason),
+        })
+    } else if events.is_empty() {
+        Some(format!("Nothing in the next {} days", config.days_ahead))
+    } else {
+        None
+    };
+    if let Some(message) = empty {
+        let text = Paragraph::new(message).style(Style::default().fg(theme.muted)).wrap(Wrap { trim: true });
+        f.render_widget(text.block(block), area);
+        return;
+    }
+    let today = chrono::Local::now().date_naive();
+    let selected = app.calendar_selected.min(events.len() - 1);
+    let rows: Vec<Row> = events
+        .iter()
+        .enumerate()
+        .map(|(i, e)| {
+            let days = (e.date - today).num_days();
+            let when = match days {
+                0 => "today".to_string(),
+                1 => "1d".to_string(),
+                n => format!("{}d", n),
+            };
+            let mut kind = e.kind.label().to_string();
+            if focused && i == selected {
+                kind = format!("{} {}", kind, e.date.format("%a %m-%d"));
+                if !e.detail.is_empty() {
+                    kind = format!("{}, {}", kind, e.detail);
+                }
+            }
+            let row = Row::new(vec![Cell::from(when), Cell::from(e.ticker.as_str()), Cell::from(kind)]);
+            if days <= config.highlight_days {
+                row.style(Style::default().fg(theme.accent).add_modifier(Modifier::BOLD))
+            } else {
+                row
+            }
+        })
+        .collect();
+    let table = Table::new(rows)
+        .block(block)
+        .highlight_style(theme.selected())
+        .widths(&[Constraint::Length(5), Constraint::Length(6), Constraint::Min(8)]);
+    let mut state = TableState::default();
+    if focused {
+        state.select(Some(selected));
+    }
+    f.render_stateful_widget(table, area, &mut state);
+}
+
+const ORDER_BOOK_WIDTH: u16 = 34;
+
+/// Asks above the spread, highest first, then bids, each level with a bar
+/// for its size.
+fn book_lines(theme: &Theme, book: &OrderBook, width: u16) -> Vec<Spans<'static>> {
+    let bar_width = (width as usize).saturating_sub(20) as f64;
+    let max_size = book.max_size().max(1.0);
+    let level = |l: &Level, color: Color| {
+        Spans::from(vec![
+            Span::styled(format!("{:>10.2}", l.price), Style::default().fg(color)),
+            Span::raw(format!(" {:>8} ", compact_number(l.size))),
+            Span::styled(bar(l.size / max_size * bar_width), Style::default().fg(color)),
+        ])
+    };
+    let mut lines: Vec<Spans> = book.asks.iter().rev().map(|l| level(l, theme.loss)).collect();
+    let spread = match (book.spread(), book.mid()) {
+        (Some(spread), Some(mid)) if mid != 0.0 => format!("  spread {:.2} ({:.2}%)", spread, spread / mid * 100.0),
+        _ => "  one-sided".to_string(),
+    };
+    lines.push(Spans::from(Span::styled(spread, Style::default().fg(theme.muted))));
+    lines.extend(book.bids.iter().map(|l| level(l, theme.gain)));
+    lines
+}
+
+/// Streamed bid and ask levels of the selected ticker.
+fn draw_order_book<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let title = match app.selected_ticker() {
+        Some(ticker) => format!("Order Book: {}", ticker),
+        None => "Order Book".to_string(),
+    };
+    let block = panel_block(theme, title, false);
+    let book = match app.order_book() {
+        Ok(book) if !(book.bids.is_empty() && book.asks.is_empty()) => book,
+        Ok(_) => {
+            f.render_widget(Paragraph::new("No bid or ask quoted").wrap(Wrap { trim: true }).block(block), area);
+            return;
+        }
+        Err(message) => {
+            let text = Paragraph::new(message).style(Style::default().fg(theme.muted)).wrap(Wrap { trim: true });
+            f.render_widget(text.block(block), area);
+            return;
+        }
+    };
+    let mut lines = book_lines(theme, book, area.width.saturating_sub(2));
+    lines.push(Spans::from(""));
+    let imbalance = book.imbalance().map_or(String::new(), |b| format!("bids {:.0}%, ", b * 100.0));
+    lines.push(Spans::from(Span::styled(
+        format!("  {}at {}", imbalance, book.at.format("%H:%M:%S")),
+        Style::default().fg(theme.muted),
+    )));
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Risk of the charted ticker beside that of the accounts as a whole. A
+/// dash marks a figure there's too little history for.
+fn draw_risk<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let risk = &app.risk;
+    let percent = |v: Option<f64>| v.map_or("-".to_string(), |v| format!("{:.1}%", v));
+    let beta = |v: Option<f64>| v.map_or("-".to_string(), |v| format!("{:.2}", v));
+    let (own, all) = (&risk.selected, &risk.portfolio);
+    let table_rows = vec![
+        Row::new(vec!["Volatility".to_string(), percent(own.volatility), percent(all.volatility)]),
+        Row::new(vec![format!("Beta vs {}", app.benchmark.ticker), beta(own.beta), beta(all.beta)]),
+        Row::new(vec!["Max drawdown".to_string(), percent(own.max_drawdown), percent(all.max_drawdown)]),
+        Row::new(vec!["VaR 95% 1d".to_string(), percent(own.var), percent(all.var)]),
+    ];
+    let selected = risk.ticker.as_ref().map_or("-".to_string(), |t| t.to_string());
+    let table = Table::new(table_rows)
+        .header(Row::new(vec![String::new(), selected, "Portfolio".to_string()]).style(Style::default().add_modifier(Modifier::BOLD)))
+        .widths(&[Constraint::Length(13), Constraint::Length(8), Constraint::Length(9)]);
+    f.render_widget(table.block(panel_block(&app.theme, "Risk", false)), area);
+}
+
+/// Total account value over the trade history, coloured by whether it
+/// ended above or below where it started. Trades sit at their timestamps
+/// when they all have one, otherwise they're spaced evenly.
+fn draw_equity<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let points = trades::equity_curve(&app.accounts, &app.trades);
+    if points.len() < 2 {
+        if let Some(block) = source_block(f, app, Source::Trades, "Equity", false, area) {
+            f.render_widget(Paragraph::new("No trades yet").block(block), area);
+        }
+        return;
+    }
+    let (first, last) = (points[0].total, points[points.len() - 1].total);
+    let change = last - first;
+    let pct = if first != 0.0 { format!(" {:+.1}%", change / first * 100.0) } else { String::new() };
+    let title = Spans::from(vec![
+        Span::raw(format!("Equity {:.2} ", last)),
+        Span::styled(format!("{:+.2}{}", change, pct), Style::default().fg(theme.change(change))),
+    ]);
+    let Some(block) = source_block(f, app, Source::Trades, title, false, area) else {
+        return;
+    };
+
+    let times: Option<Vec<f64>> = points[1..].iter().map(|p| p.at.map(|at| at.and_utc().timestamp() as f64)).collect();
+    let xs: Vec<f64> = match times {
+        // The starting point shares the first trade's time.
+        Some(times) => std::iter::once(times[0]).chain(times).collect(),
+        None => (0..points.len()).map(|i| i as f64).collect(),
+    };
+    let (x_min, x_max) = (xs[0], xs[xs.len() - 1].max(xs[0] + 1.0));
+    let (y_min, y_max) = points.iter().fold((f64::MAX, f64::MIN), |(mn, mx), p| (mn.min(p.total), mx.max(p.total)));
+    let pad = ((y_max - y_min) * 0.1).max(0.01);
+    let color = theme.change(change);
+    let lines: Vec<Line> = points
+        .windows(2)
+        .zip(xs.windows(2))
+        .map(|(pair, x)| Line { x1: x[0], y1: pair[0].total, x2: x[1], y2: pair[1].total, color })
+        .collect();
+    let chart = Canvas::default()
+        .marker(marker(app))
+        .x_bounds([x_min, x_max])
+        .y_bounds([y_min - pad, y_max + pad])
+        .paint(move |ctx| {
+            for line in &lines {
+                ctx.draw(line);
+            }
+        });
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    f.render_widget(chart, inner);
+}
+
+/// Line colours of the compared tickers, in marking order after the
+/// theme's chart line colour.
+const COMPARE_COLORS: [Color; 5] = [Color::Cyan, Color::Magenta, Color::Yellow, Color::LightBlue, Color::LightRed];
+
+fn compare_color(theme: &Theme, i: usize) -> Color {
+    match i.checked_sub(1) {
+        None => theme.chart_line,
+        Some(j) => COMPARE_COLORS[j % COMPARE_COLORS.len()],
+    }
+}
+
+/// The marked tickers' closes as percent change from the first bar time
+/// they all have, within `chart_range`, with a legend in the title. Unlike
+/// the single-ticker chart the x axis is real time, since the tickers'
+/// bars needn't line up.
+fn draw_compare<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let shown: Vec<&[Bar]> = app.compare.iter().map(|s| app.chart_range.slice(&s.bars, |b| b.at)).collect();
+    let start = shown.iter().filter_map(|bars| bars.first()).map(|b| b.at).max();
+    let x = |at: NaiveDateTime| at.and_utc().timestamp() as f64;
+
+    // Percent changes, on a log axis as the log of each close per 100 of
+    // the first so equal moves take equal heights; the percent axis is the
+    // linear one here.
+    let log = app.chart_scale == Scale::Log;
+    let to_y = |ratio: f64| if log { (ratio * 100.0).ln() } else { (ratio - 1.0) * 100.0 };
+    let zero = to_y(1.0);
+
+    let mut legend = vec![Span::raw(match start {
+        Some(start) => format!("Compare % since {}{} ", start.format(app.chart.interval.label_format()), if log { " [log]" } else { "" }),
+        None => "Compare ".to_string(),
+    })];
+    let mut lines: Vec<Line> = Vec::new();
+    let (mut y_min, mut y_max) = (zero, zero);
+    let (mut x_min, mut x_max) = (f64::MAX, f64::MIN);
+    for (i, (ticker, bars)) in app.marked.iter().zip(&shown).enumerate() {
+        let color = compare_color(theme, i);
+        let bars = start.map_or(&bars[..0], |start| &bars[bars.partition_point(|b| b.at < start)..]);
+        let Some(first) = bars.first().map(|b| b.close).filter(|&c| c != 0.0) else {
+            legend.push(Span::styled(format!("■ {} no data ", ticker), Style::default().fg(color)));
+            continue;
+        };
+        let points: Vec<(f64, f64)> =
+            bars.iter().filter(|b| !log || b.close > 0.0).map(|b| (x(b.at), to_y(b.close / first))).collect();
+        for &(px, py) in &points {
+            (x_min, x_max) = (x_min.min(px), x_max.max(px));
+            (y_min, y_max) = (y_min.min(py), y_max.max(py));
+        }
+        lines.extend(points.windows(2).map(|pair| Line { x1: pair[0].0, y1: pair[0].1, x2: pair[1].0, y2: pair[1].1, color }));
+        let last = bars.last().map_or(0.0, |b| (b.close / first - 1.0) * 100.0);
+        legend.push(Span::styled(format!("■ {} {:+.1}% ", ticker, last), Style::default().fg(color)));
+    }
+    legend.push(Span::raw(format!("({} to unmark)", app.keymap.label(Action::ToggleCompare))));
+    let Some(block) = source_block(f, app, Source::Chart, legend, app.focus == Focus::Chart, area) else {
+        return;
+    };
+    if lines.is_empty() {
+        f.render_widget(Paragraph::new("Not enough overlapping history to compare").block(block), area);
+        return;
+    }
+
+    let pad = ((y_max - y_min) * 0.1).max(0.01);
+    let label_format = app.chart.interval.axis_format(chrono::Duration::seconds((x_max - x_min) as i64));
+    let labels: Vec<(f64, String)> = [0.0, 0.25, 0.5, 0.75]
+        .iter()
+        .filter_map(|frac| {
+            let at = x_min + (x_max - x_min) * frac;
+            let date = DateTime::from_timestamp(at as i64, 0)?.naive_utc();
+            Some((at, date.format(label_format).to_string()))
+        })
+        .collect();
+    let muted = theme.muted;
+    let inner = block.inner(area);
+    let x_max = x_max.max(x_min + 1.0);
+    let axis = match log {
+        true => price_axis(app, inner, [x_min, x_max], [y_min - pad * 2.0, y_max + pad], true, |t, v| format!("{}%", t.label(v - 100.0))),
+        false => price_axis(app, inner, [x_min, x_max], [y_min - pad * 2.0, y_max + pad], false, |t, v| format!("{}%", t.label(v))),
+    };
+    let left = axis.left;
+    let columns: Vec<f64> = labels.iter().map(|&(x, _)| x).collect();
+    let grid = if app.chart_config.grid { axis.grid(&columns) } else { Vec::new() };
+    let chart = Canvas::default()
+        .marker(marker(app))
+        .x_bounds([left, x_max])
+        .y_bounds([y_min - pad * 2.0, y_max + pad])
+        .paint(move |ctx| {
+            ctx.draw(&Points { coords: &grid, color: muted });
+            ctx.draw(&Line { x1: x_min, y1: zero, x2: x_max, y2: zero, color: muted });
+            ctx.layer();
+            for line in &lines {
+                ctx.draw(line);
+            }
+            for (x, label) in &labels {
+                ctx.print(*x, y_min - pad * 2.0, Span::styled(label.clone(), Style::default().fg(muted)));
+            }
+            for (y, label) in &axis.ticks {
+                ctx.print(left, *y, Span::styled(label.clone(), Style::default().fg(muted)));
+            }
+        });
+    f.render_widget(block, area);
+    f.render_widget(chart, inner);
+}
+
+/// Create/edit account popup, centred over the dashboard.
+fn draw_account_form<B: Backend>(f: &mut Frame<B>, theme: &Theme, form: &AccountForm, size: Rect) {
+    let width = 50.min(size.width);
+    let height = 7.min(size.height);
+    let area = Rect::new((size.width - width) / 2, (size.height - height) / 2, width, height);
+
+    let mut lines = field_lines(theme, &ACCOUNT_FIELDS, &form.fields, form.active);
+    lines.push(Spans::from(""));
+    lines.push(match &form.error {
+        Some(err) => Spans::from(Span::styled(err.clone(), Style::default().fg(theme.error))),
+        None if form.active == BALANCE_FIELD => Spans::from("+/-: step  Tab: next  Enter: save  Esc: cancel"),
+        None => Spans::from("Tab: next field  Enter: save  Esc: cancel"),
+    });
+
+    let title = if form.editing.is_some() { "Edit Account" } else { "New Account" };
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(panel_block(theme, title, true)), area);
+}
+
+/// Record-trade or transfer popup, centred over the dashboard.
+fn draw_trade_form<B: Backend>(f: &mut Frame<B>, theme: &Theme, form: &TradeForm, size: Rect) {
+    let width = 56.min(size.width);
+    let height = 8.min(size.height);
+    let area = Rect::new((size.width - width) / 2, (size.height - height) / 2, width, height);
+
+    let (labels, title) = match (form.transfer, form.dividend) {
+        (true, _) => (&TRANSFER_FIELDS, "Transfer"),
+        (_, true) => (&TRADE_FIELDS, "Record Dividend"),
+        _ => (&TRADE_FIELDS, "Record Trade"),
+    };
+    let mut lines = field_lines(theme, labels, &form.fields, form.active);
+    lines.push(Spans::from(""));
+    lines.push(match &form.error {
+        Some(err) => Spans::from(Span::styled(err.clone(), Style::default().fg(theme.error))),
+        None if form.active == AMOUNT_FIELD && form.transfer => Spans::from("N% of balance  +/-: step  Enter: move  Esc: cancel"),
+        None if form.active == AMOUNT_FIELD && form.dividend => Spans::from("+/-: step  Enter: record  Esc: cancel"),
+        None if form.active == AMOUNT_FIELD => Spans::from("Loss: -N  +/-: step  Enter: record  Esc: cancel"),
+        None => Spans::from("Tab: next field  Enter: record  Esc: cancel"),
+    });
+
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(panel_block(theme, title, true)), area);
+}
+
+/// One line per form field, the active one highlighted with a cursor.
+fn field_lines<'a>(theme: &Theme, labels: &[&str], values: &'a [String], active: usize) -> Vec<Spans<'a>> {
+    labels
+        .iter()
+        .zip(values)
+        .enumerate()
+        .map(|(i, (label, value))| {
+            let style = if i == active {
+                Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            Spans::from(vec![
+                Span::styled(format!("{:>17}: ", label), style),
+                Span::raw(value.as_str()),
+                Span::styled(if i == active { "_" } else { "" }, style),
+            ])
+        })
+        .collect()
+}
+
+/// Notes of the newer release, centred over the dashboard.
+fn draw_release_notes<B: Backend>(f: &mut Frame<B>, app: &App, release: &Release, size: Rect) {
+    let width = (size.width * 3 / 4).max(40).min(size.width);
+    let height = (size.height * 3 / 4).max(10).min(size.height);
+    let area = Rect::new((size.width - width) / 2, (size.height - height) / 2, width, height);
+
+    let mut lines = vec![Spans::from(format!("stm {} is out; this is {}.", release.version, update::CURRENT)), Spans::from("")];
+    if release.notes.trim().is_empty() {
+        lines.push(Spans::from(Span::styled("No release notes", Style::default().fg(app.theme.muted))));
+    }
+    lines.extend(release.notes.lines().map(|line| Spans::from(line.to_string())));
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(Span::styled(release.url.clone(), Style::default().fg(app.theme.accent))));
+
+    let title = format!("Release notes ({}/Esc: close)", app.keymap.label(Action::ReleaseNotes));
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).wrap(Wrap { trim: false }).block(panel_block(&app.theme, title, true)),
+        area,
+    );
+}
+
+/// Changelog entries, centred over the dashboard: the ones since the last
+/// session's version after an upgrade, or all of them.
+fn draw_changelog<B: Backend>(f: &mut Frame<B>, app: &App, entries: &[&Entry], upgraded: bool, size: Rect) {
+    let width = (size.width * 3 / 4).max(40).min(size.width);
+    let height = (size.height * 3 / 4).max(10).min(size.height);
+    let area = Rect::new((size.width - width) / 2, (size.height - height) / 2, width, height);
+    let key = Style::default().fg(app.theme.accent);
+
+    let mut lines = Vec::new();
+    for entry in entries {
+        if !lines.is_empty() {
+            lines.push(Spans::from(""));
+        }
+        lines.push(Spans::from(Span::styled(format!("stm {}", entry.version), Style::default().add_modifier(Modifier::BOLD))));
+        for note in entry.notes {
+            let mut spans = vec![Span::raw(format!("  • {}", note.text))];
+            if let Some(action) = note.action {
+                spans.push(Span::styled(format!(" [{}]", app.keymap.label(action)), key));
+            }
+            lines.push(Spans::from(spans));
+        }
+    }
+
+    let heading = if upgraded { format!("What's new in stm {}", update::CURRENT) } else { "Changelog".to_string() };
+    let title = format!("{} ({}/Esc: close)", heading, app.keymap.label(Action::Changelog));
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).wrap(Wrap { trim: false }).block(panel_block(&app.theme, title, true)),
+        area,
+    );
+}
+
+/// Date range picker popup, centred over whatever it was opened from.
+fn draw_range_picker<B: Backend>(f: &mut Frame<B>, theme: &Theme, picker: &RangePicker, size: Rect) {
+    let width = 50.min(size.width);
+    let height = 8.min(size.height);
+    let area = Rect::new((size.width - width) / 2, (size.height - height) / 2, width, height);
+    let active = Style::default().fg(theme.accent).add_modifier(Modifier::BOLD);
+
+    let mut presets = vec![Span::styled(
+        format!("{:>6}: ", "Preset"),
+        if picker.row == PickerRow::Presets { active } else { Style::default() },
+    )];
+    for (i, preset) in Preset::ALL.iter().enumerate() {
+        let style = if i == picker.preset && picker.row == PickerRow::Presets {
+            theme.selected()
+        } else if i == picker.preset {
+            Style::default().add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        presets.push(Span::styled(format!(" {} ", preset.label()), style));
+    }
+    let mut lines = vec![Spans::from(presets)];
+    for (row, label, value) in [(PickerRow::From, "From", &picker.from), (PickerRow::To, "To", &picker.to)] {
+        let style = if picker.row == row { active } else { Style::default() };
+        let hint = if value.is_empty() && picker.row != row { "(open)" } else { "" };
+        lines.push(Spans::from(vec![
+            Span::styled(format!("{:>6}: ", label), style),
+            Span::raw(value.clone()),
+            Span::styled(if picker.row == row { "_" } else { hint }, style),
+        ]));
+    }
+    lines.push(Spans::from(""));
+    lines.push(match &picker.error {
+        Some(err) => Spans::from(Span::styled(err.clone(), Style::default().fg(theme.error))),
+        None => Spans::from("Left/Right, 1-5: preset  Tab: dates (YYYY-MM-DD)"),
+    });
+    lines.push(Spans::from("Enter: apply  Esc: cancel"));
+
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(panel_block(theme, picker.purpose.clone(), true)), area);
+}
@@ -0,0 +1,331 @@
+//! The price directories merged into one universe.
+//!
+//! Every series is in `pre_stock/` unless `[[data.roots]]` lists other
+//! directories, say `equities/`, `crypto/` and `forex/`. Their tickers are
+//! listed together, each tagged with its root's name, and each root has
+//! its own `[data] provider` for downloads and quotes and its own
+//! `refresh_mins` for `stm daemon`. A ticker belongs to the first root
+//! holding its CSV; one none holds yet is downloaded into the first root.
+//!
+//! A root's CSVs may be organised in subdirectories, by exchange or asset
+//! class, down to `[data] max_depth` levels. Such a ticker is tagged with
+//! its root and directory (`pre_stock/nyse`), and its downloads, sidecar
+//! and model inputs stay in that directory. Symlinks are followed, but a
+//! directory reached twice, as through a link back up the tree, is read
+//! once. A ticker held in more than one place (`nyse/ABC.csv` and
+//! `lse/ABC.csv`) is listed once for each, told apart by its `SeriesKey`
+//! and tag; what goes by the ticker alone, like downloads, quotes and the
+//! daemon, uses the first file found.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::config::{DataConfig, DataProvider};
+use crate::ids::Ticker;
+
+/// Where prices are stored without `[[data.roots]]`.
+pub const DEFAULT_DIR: &str = "pre_stock";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Root {
+    pub name: String,
+    pub dir: PathBuf,
+    pub provider: DataProvider,
+    /// Minutes between daemon downloads; `None` for `[daemon] every_mins`.
+    pub refresh_mins: Option<u64>,
+    /// Levels of subdirectories searched.
+    pub max_depth: usize,
+}
+
+/// A price CSV found under a root.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    pub ticker: Ticker,
+    pub path: PathBuf,
+    /// Directories between the root and the file, `/`-joined; empty for
+    /// one in the root itself.
+    pub subdir: String,
+}
+
+impl Series {
+    /// The tag shown beside its ticker: `root` or `root/subdir`.
+    pub fn source(&self, root: &Root) -> String {
+        if self.subdir.is_empty() { root.name.clone() } else { format!("{}/{}", root.name, self.subdir) }
+    }
+
+    /// Which series it is, found under `root`.
+    pub fn key(&self, root: &Root) -> SeriesKey {
+        let file = format!("{}.csv", self.ticker);
+        SeriesKey {
+            root: root.name.clone(),
+            file: if self.subdir.is_empty() { file } else { format!("{}/{}", self.subdir, file) },
+        }
+    }
+}
+
+/// Which stored series a listed stock is: its root's name and its CSV's
+/// path below the root, `/`-joined. Unlike the ticker it's unique.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SeriesKey {
+    pub root: String,
+    pub file: String,
+}
+
+impl Root {
+    /// Where `ticker`'s CSV goes in the root itself.
+    pub fn path(&self, ticker: &Ticker) -> PathBuf {
+        self.dir.join(format!("{}.csv", ticker))
+    }
+
+    /// Every `TICKER.csv` down to `max_depth`: each directory's files in
+    /// name order, then its subdirectories'. Hidden directories, like
+    /// download_stock.py's `.http_cache`, are skipped.
+    pub fn csvs(&self) -> Vec<Series> {
+        let mut found = Vec::new();
+        walk(&self.dir, "", self.max_depth, &mut HashSet::new(), &mut found);
+        found
+    }
+}
+
+fn walk(dir: &Path, subdir: &str, depth: usize, seen: &mut HashSet<PathBuf>, found: &mut Vec<Series>) {
+    // Canonical paths see through symlinks, so a loop ends where it began.
+    let (Ok(canonical), Ok(entries)) = (fs::canonicalize(dir), fs::read_dir(dir)) else {
+        return;
+    };
+    if !seen.insert(canonical) {
+        return;
+    }
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+    paths.sort();
+    for path in &paths {
+        if path.is_file()
+            && path.extension().is_some_and(|ext| ext == "csv")
+            && let Some(ticker) = path.file_stem().and_then(|t| t.to_str())
+            && let Ok(ticker) = Ticker::parse(ticker)
+        {
+            found.push(Series { ticker, path: path.clone(), subdir: subdir.to_string() });
+        }
+    }
+    if depth == 0 {
+        return;
+    }
+    for path in paths.iter().filter(|p| p.is_dir()) {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()).filter(|n| !n.starts_with('.')) else {
+            continue;
+        };
+        let subdir = if subdir.is_empty() { name.to_string() } else { format!("{}/{}", subdir, name) };
+        walk(path, &subdir, depth - 1, seen, found);
+    }
+}
+
+/// What a scan of the roots found.
+#[derive(Debug, Default)]
+pub struct Scan {
+    /// Every file, root by root, with the index of its root.
+    pub series: Vec<(usize, Series)>,
+}
+
+pub fn scan_in(roots: &[Root]) -> Scan {
+    let series = roots.iter().enumerate().flat_map(|(i, root)| root.csvs().into_iter().map(move |s| (i, s))).collect();
+    Scan { series }
+}
+
+/// Where the latest scan first found each ticker: its root's index, its
+/// file and whether that is in a subdirectory.
+type Found = HashMap<Ticker, (usize, PathBuf, bool)>;
+
+static ROOTS: OnceLock<Vec<Root>> = OnceLock::new();
+static FOUND: OnceLock<Mutex<Found>> = OnceLock::new();
+
+fn index(scan: &Scan) -> Found {
+    let mut found = Found::new();
+    for (i, s) in &scan.series {
+        found.entry(s.ticker.clone()).or_insert_with(|| (*i, s.path.clone(), !s.subdir.is_empty()));
+    }
+    found
+}
+
+fn found() -> &'static Mutex<Found> {
+    FOUND.get_or_init(|| Mutex::new(index(&scan_in(roots()))))
+}
+
+/// Scans the session's roots, remembering where each ticker was found.
+pub fn scan() -> Scan {
+    let scan = scan_in(roots());
+    *found().lock().unwrap() = index(&scan);
+    scan
+}
+
+/// Sets the session's roots. Only the first call has any effect; before it
+/// there's just `pre_stock/`, from the CSV provider.
+pub fn init(config: &DataConfig) {
+    let _ = ROOTS.set(from_config(config));
+}
+
+pub fn from_config(config: &DataConfig) -> Vec<Root> {
+    if config.roots.is_empty() {
+        return vec![Root {
+            name: DEFAULT_DIR.to_string(),
+            dir: PathBuf::from(DEFAULT_DIR),
+            provider: config.provider,
+            refresh_mins: None,
+            max_depth: config.max_depth,
+        }];
+    }
+    config
+        .roots
+        .iter()
+        .map(|root| Root {
+            name: root.name.clone(),
+            dir: PathBuf::from(&root.dir),
+            provider: root.provider.unwrap_or(config.provider),
+            refresh_mins: root.refresh_mins,
+            max_depth: config.max_depth,
+        })
+        .collect()
+}
+
+pub fn roots() -> &'static [Root] {
+    ROOTS.get_or_init(|| from_config(&DataConfig::default()))
+}
+
+/// Whether there's more than one root or a ticker in a subdirectory, so
+/// tickers need their tag.
+pub fn is_merged() -> bool {
+    roots().len() > 1 || found().lock().unwrap().values().any(|&(_, _, nested)| nested)
+}
+
+/// The root `ticker` belongs to.
+pub fn root_of(ticker: &Ticker) -> &'static Root {
+    match found().lock().unwrap().get(ticker) {
+        Some(&(i, ..)) => &roots()[i],
+        None => root_in(roots(), ticker),
+    }
+}
+
+/// Directory `ticker`'s files are in, or go in.
+pub fn dir_of(ticker: &Ticker) -> PathBuf {
+    let path = path_of(ticker);
+    path.parent().map_or_else(|| root_of(ticker).dir.clone(), Path::to_path_buf)
+}
+
+/// The CSV `key` names, if its root is one of the session's.
+pub fn path_for(key: &SeriesKey) -> Option<PathBuf> {
+    let root = roots().iter().find(|root| root.name == key.root)?;
+    Some(root.dir.join(&key.file))
+}
+
+/// `ticker`'s price CSV.
+pub fn path_of(ticker: &Ticker) -> PathBuf {
+    match found().lock().unwrap().get(ticker) {
+        Some((_, path, _)) => path.clone(),
+        None => root_in(roots(), ticker).path(ticker),
+    }
+}
+
+fn root_in<'a>(roots: &'a [Root], ticker: &Ticker) -> &'a Root {
+    roots.iter().find(|root| root.path(ticker).is_file()).unwrap_or(&roots[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::config::RootConfig;
+
+    #[test]
+    fn tickers_belong_to_the_first_root_holding_them() {
+        let base = std::env::temp_dir().join(format!("stm-{}-universe", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let root = |name: &str, provider| RootConfig {
+            name: name.to_string(),
+            dir: base.join(name).to_string_lossy().into_owned(),
+            provider,
+            refresh_mins: None,
+        };
+        let config = DataConfig {
+            provider: DataProvider::Stooq,
+            roots: vec![root("equities", None), root("crypto", Some(DataProvider::Yahoo))],
+            max_depth: 2,
+        };
+        let roots = from_config(&config);
+        assert_eq!(roots[0].provider, DataProvider::Stooq);
+        assert_eq!(roots[1].provider, DataProvider::Yahoo);
+
+        fs::create_dir_all(&roots[1].dir).unwrap();
+        fs::write(roots[1].dir.join("BTC-USD.csv"), "Date,Close\n").unwrap();
+        let btc = Ticker::parse("BTC-USD").unwrap();
+        assert_eq!(root_in(&roots, &btc).name, "crypto");
+        // Not stored anywhere yet: downloaded into the first root.
+        assert_eq!(root_in(&roots, &Ticker::parse("AAPL").unwrap()).name, "equities");
+
+        // A ticker held in two places is listed from both, each with its
+        // own key; what goes by the ticker uses the first.
+        let equities = &roots[0].dir;
+        for dir in ["nyse", "lse"] {
+            fs::create_dir_all(equities.join(dir)).unwrap();
+            fs::write(equities.join(dir).join("ABC.csv"), "Date,Close\n").unwrap();
+        }
+        let scan = scan_in(&roots);
+        let found: Vec<(String, SeriesKey)> = scan.series.iter().map(|(i, s)| (s.source(&roots[*i]), s.key(&roots[*i]))).collect();
+        let key = |root: &str, file: &str| SeriesKey { root: root.to_string(), file: file.to_string() };
+        assert_eq!(
+            found,
+            vec![
+                ("equities/lse".to_string(), key("equities", "lse/ABC.csv")),
+                ("equities/nyse".to_string(), key("equities", "nyse/ABC.csv")),
+                ("crypto".to_string(), key("crypto", "BTC-USD.csv")),
+            ]
+        );
+        assert_eq!(index(&scan)[&Ticker::parse("ABC").unwrap()].1, equities.join("lse/ABC.csv"));
+        fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(from_config(&DataConfig::default())[0].dir, PathBuf::from(DEFAULT_DIR));
+    }
+
+    #[test]
+    fn walk_follows_links_once_down_to_max_depth_skipping_hidden_directories() {
+        let base = std::env::temp_dir().join(format!("stm-{}-walk", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let (dir, outside) = (base.join("root"), base.join("outside"));
+        for sub in ["a/b/c", ".http_cache", "nyse"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        fs::create_dir_all(&outside).unwrap();
+        for file in ["IBM.csv", "a/A.csv", "a/b/B.csv", "a/b/c/C.csv", ".http_cache/X.csv", "nyse/notes.txt"] {
+            fs::write(dir.join(file), "Date,Close\n").unwrap();
+        }
+        fs::write(outside.join("OUT.csv"), "Date,Close\n").unwrap();
+        #[cfg(unix)]
+        {
+            // Links back up the tree and to its own subdirectory are read
+            // once, where first reached; one out of the tree is followed.
+            std::os::unix::fs::symlink(&dir, dir.join("nyse/up")).unwrap();
+            std::os::unix::fs::symlink(dir.join("a"), dir.join("nyse/again")).unwrap();
+            std::os::unix::fs::symlink(&outside, dir.join("nyse/out")).unwrap();
+        }
+        let root = |max_depth| Root {
+            name: "root".to_string(),
+            dir: dir.clone(),
+            provider: DataProvider::Csv,
+            refresh_mins: None,
+            max_depth,
+        };
+        let listed = |max_depth| -> Vec<String> {
+            root(max_depth).csvs().iter().map(|s| format!("{}:{}", s.subdir, s.ticker)).collect()
+        };
+        let (flat, two, three) = (listed(0), listed(2), listed(3));
+        fs::remove_dir_all(&base).unwrap();
+        assert_eq!(flat, [":IBM"]);
+        let mut expected = vec![":IBM", "a:A", "a/b:B"];
+        if cfg!(unix) {
+            expected.push("nyse/out:OUT");
+        }
+        assert_eq!(two, expected);
+        expected.insert(3, "a/b/c:C");
+        assert_eq!(three, expected);
+    }
+}
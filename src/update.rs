@@ -0,0 +1,84 @@
+//! Checking GitHub releases for a newer stm.
+//!
+//! With `[updates] check = true` the dashboard asks GitHub for the latest
+//! release once at startup, in the background. A release whose tag is a
+//! higher version than this build is announced on the status line, and its
+//! notes can be read in a popup. Nothing is downloaded or installed.
+
+use std::error::Error;
+
+use serde::Deserialize;
+
+use crate::net;
+
+const LATEST_URL: &str = "https://api.github.com/repos/jamesb5959/stm/releases/latest";
+
+/// This build's version.
+pub const CURRENT: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Release {
+    pub version: String,
+    pub notes: String,
+    pub url: String,
+}
+
+/// The latest release if it's newer than this build.
+pub fn check() -> Result<Option<Release>, Box<dyn Error>> {
+    let body = net::client().get(LATEST_URL, &[("Accept", "application/vnd.github+json")])?.into_string()?;
+    let release = parse_latest(&body)?;
+    Ok(is_newer(&release.version, CURRENT).then_some(release))
+}
+
+fn parse_latest(body: &str) -> Result<Release, Box<dyn Error>> {
+    #[derive(Deserialize)]
+    struct Latest {
+        tag_name: String,
+        #[serde(default)]
+        body: Option<String>,
+        html_url: String,
+    }
+    let latest: Latest = serde_json::from_str(body)?;
+    Ok(Release {
+        version: latest.tag_name.trim_start_matches('v').to_string(),
+        notes: latest.body.unwrap_or_default().replace("\r\n", "\n"),
+        url: latest.html_url,
+    })
+}
+
+/// Compares dotted version numbers, ignoring a leading `v` and anything
+/// after a `-` or `+`. Tags that aren't versions are never newer.
+pub fn is_newer(tag: &str, current: &str) -> bool {
+    let parts = |v: &str| -> Option<Vec<u64>> {
+        let v = v.trim().trim_start_matches('v');
+        v.split(['-', '+']).next()?.split('.').map(|p| p.parse().ok()).collect()
+    };
+    match (parts(tag), parts(current)) {
+        (Some(tag), Some(current)) => tag > current,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_compare_by_number() {
+        assert!(is_newer("v0.10.0", "0.9.1"));
+        assert!(is_newer("1.0.0", "0.1.0"));
+        assert!(is_newer("0.1.1-rc1", "0.1.0"));
+        assert!(!is_newer("v0.1.0", "0.1.0"));
+        assert!(!is_newer("0.0.9", "0.1.0"));
+        assert!(!is_newer("nightly", "0.1.0"));
+    }
+
+    #[test]
+    fn parse_latest_reads_tag_notes_and_link() {
+        let body = r#"{"tag_name":"v0.2.0","body":"- Faster\r\n- Fewer bugs","html_url":"https://github.com/jamesb5959/stm/releases/tag/v0.2.0"}"#;
+        let release = parse_latest(body).unwrap();
+        assert_eq!(release.version, "0.2.0");
+        assert_eq!(release.notes, "- Faster\n- Fewer bugs");
+        assert!(release.url.ends_with("/v0.2.0"));
+    }
+}